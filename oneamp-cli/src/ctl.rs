@@ -0,0 +1,144 @@
+// Remote Control
+// `oneamp-cli ctl ...` sends commands to a running player (desktop app or
+// `oneamp-daemon`) over its control socket.
+
+use super::format_time;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use oneamp_core::ipc::{self, IpcClient, IpcReply, IpcRequest};
+use oneamp_core::{PlaybackState, PlayerStatus};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct CtlArgs {
+    /// Control socket path (defaults to $ONEAMP_SOCKET or $XDG_RUNTIME_DIR/oneamp.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Print replies as JSON
+    #[arg(long)]
+    json: bool,
+
+    #[command(subcommand)]
+    action: CtlAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum CtlAction {
+    /// Start or resume playback
+    Play,
+    /// Pause playback
+    Pause,
+    /// Toggle between playing and paused
+    Toggle,
+    /// Stop playback
+    Stop,
+    /// Skip to the next track
+    Next,
+    /// Go back to the previous track
+    Previous,
+    /// Show what is playing
+    Status,
+    /// Add files to the end of the playlist
+    Enqueue {
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print playback events as JSON lines until interrupted
+    Watch,
+}
+
+pub fn run(args: CtlArgs) -> Result<()> {
+    let socket = args.socket.unwrap_or_else(ipc::default_socket_path);
+    let mut client = IpcClient::connect(&socket)?;
+
+    let request = match args.action {
+        CtlAction::Play => IpcRequest::Play,
+        CtlAction::Pause => IpcRequest::Pause,
+        CtlAction::Toggle => IpcRequest::TogglePause,
+        CtlAction::Stop => IpcRequest::Stop,
+        CtlAction::Next => IpcRequest::Next,
+        CtlAction::Previous => IpcRequest::Previous,
+        CtlAction::Status => IpcRequest::Status,
+        CtlAction::Enqueue { files } => IpcRequest::Enqueue {
+            paths: absolute_paths(files)?,
+        },
+        CtlAction::Watch => return watch(&mut client),
+    };
+
+    let reply = client.request(&request)?.into_result()?;
+    if args.json {
+        println!("{}", serde_json::to_string(&reply)?);
+    } else if let IpcReply::Status(status) = reply {
+        print_status(&status);
+    }
+    Ok(())
+}
+
+/// The player may run in another directory, so send absolute paths
+fn absolute_paths(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    files
+        .into_iter()
+        .map(|file| {
+            file.canonicalize()
+                .with_context(|| format!("File not found: {}", file.display()))
+        })
+        .collect()
+}
+
+fn watch(client: &mut IpcClient) -> Result<()> {
+    client.request(&IpcRequest::Subscribe)?.into_result()?;
+    let stdout = std::io::stdout();
+    // Runs until the player quits and closes the connection
+    while let Ok(message) = client.recv() {
+        if let IpcReply::Event(event) = message {
+            let mut out = stdout.lock();
+            writeln!(out, "{}", serde_json::to_string(&event)?)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn print_status(status: &PlayerStatus) {
+    let state = match status.state {
+        PlaybackState::Playing => "▶ Playing",
+        PlaybackState::Paused => "⏸ Paused",
+        PlaybackState::Stopped => "■ Stopped",
+    };
+
+    match &status.track {
+        Some(track) => {
+            let title = track.title.clone().unwrap_or_else(|| {
+                track
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+            match &track.artist {
+                Some(artist) => println!("{}: {} - {}", state, artist, title),
+                None => println!("{}: {}", state, title),
+            }
+            println!(
+                "  {} / {}",
+                format_time(status.position_secs),
+                format_time(status.duration_secs)
+            );
+        }
+        None => println!("{}", state),
+    }
+
+    let position = match status.current_index {
+        Some(index) => format!("track {}/{}", index + 1, status.playlist_length),
+        None => format!("{} tracks", status.playlist_length),
+    };
+    println!(
+        "  {} • volume {:.0}% • repeat {} • shuffle {}",
+        position,
+        status.volume * 100.0,
+        status.repeat,
+        if status.shuffle { "on" } else { "off" }
+    );
+}
@@ -0,0 +1,229 @@
+// Offline Export
+// `oneamp-cli export ...` renders files through the equalizer, DSP plugins
+// and resampler into WAV or FLAC, several files at a time.
+
+use super::parse_preset;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use oneamp_core::export::{self, ExportFormat, ExportOptions};
+use oneamp_core::plugins::PluginRegistry;
+use oneamp_core::{cue, file_name_safe, library, CueSheet};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Audio files, folders (searched recursively) or CUE sheets (one file per track)
+    #[arg(value_name = "PATH", required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Directory to write to; folder structure below folder arguments is kept
+    #[arg(short, long, value_name = "DIR")]
+    output: PathBuf,
+
+    /// Output format: wav or flac
+    #[arg(short, long, default_value = "flac")]
+    format: ExportFormat,
+
+    /// Output sample rate in Hz (default: same as the source)
+    #[arg(long, value_name = "HZ")]
+    rate: Option<u32>,
+
+    /// Output bit depth: 16 or 24
+    #[arg(long, value_name = "BITS", default_value_t = 16)]
+    bits: u16,
+
+    /// Truncate instead of applying TPDF dither when reducing the bit depth
+    #[arg(long)]
+    no_dither: bool,
+
+    /// Equalizer preset to bake in (see `oneamp-cli --help`)
+    #[arg(long, value_name = "PRESET", value_parser = parse_preset)]
+    eq: Option<[f32; 10]>,
+
+    /// DSP plugin to apply after the equalizer, by name (repeat for a chain)
+    #[arg(long, value_name = "NAME")]
+    dsp: Vec<String>,
+
+    /// Plugin directory (defaults to ~/.config/oneamp/plugins)
+    #[arg(long, value_name = "DIR")]
+    plugin_dir: Option<PathBuf>,
+
+    /// Don't copy tags and cover art
+    #[arg(long)]
+    no_tags: bool,
+
+    /// Overwrite existing output files
+    #[arg(long)]
+    force: bool,
+
+    /// Number of files exported in parallel (defaults to the CPU count)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+}
+
+/// One file to produce
+struct Job {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+pub fn run(args: ExportArgs) -> Result<()> {
+    if !export::BIT_DEPTHS.contains(&args.bits) {
+        bail!("Unsupported bit depth {} (expected 16 or 24)", args.bits);
+    }
+
+    let mut options = ExportOptions {
+        format: args.format,
+        sample_rate: args.rate,
+        bits_per_sample: args.bits,
+        dither: !args.no_dither,
+        equalizer: args.eq,
+        dsp_plugins: Vec::new(),
+        copy_tags: !args.no_tags,
+    };
+    if !args.dsp.is_empty() {
+        let dir = args.plugin_dir.clone().unwrap_or_else(default_plugin_dir);
+        let mut registry = PluginRegistry::new(dir);
+        registry.discover_plugins()?;
+        for name in &args.dsp {
+            let plugin = registry
+                .find_dsp_plugin_by_name(name)
+                .with_context(|| format!("Unknown DSP plugin: {}", name))?;
+            options.dsp_plugins.push(plugin);
+        }
+    }
+
+    let jobs = plan(&args)?;
+    let workers = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .clamp(1, jobs.len());
+
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let started = Instant::now();
+                    let result = std::fs::create_dir_all(job.output.parent().unwrap())
+                        .map_err(anyhow::Error::from)
+                        .and_then(|()| export::export(&job.input, &job.output, &options));
+                    match result {
+                        Ok(summary) => eprintln!(
+                            "✓ {} → {} ({:.0}× real time)",
+                            job.input.display(),
+                            job.output.display(),
+                            summary.duration_secs() / started.elapsed().as_secs_f64().max(1e-3)
+                        ),
+                        Err(err) => {
+                            eprintln!("✗ {}: {:#}", job.input.display(), err);
+                            *failures.lock().unwrap() += 1;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    if failures > 0 {
+        bail!("{} of {} exports failed", failures, jobs.len());
+    }
+    eprintln!(
+        "Exported {} file(s) to {}",
+        jobs.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Work out the output path of every input, refusing clashes and overwrites
+fn plan(args: &ExportArgs) -> Result<Vec<Job>> {
+    let extension = args.format.extension();
+    let mut jobs = Vec::new();
+
+    for path in &args.paths {
+        if path.is_dir() {
+            let files = library::audio_files_in(path)
+                .with_context(|| format!("Failed to read folder {}", path.display()))?;
+            for file in files {
+                let relative = file.strip_prefix(path).unwrap_or(&file);
+                jobs.push(Job {
+                    output: args.output.join(relative).with_extension(extension),
+                    input: file,
+                });
+            }
+        } else if cue::is_cue_file(path) {
+            let sheet = CueSheet::from_file(path)?;
+            for track in &sheet.tracks {
+                let title = track
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| format!("Track {}", track.number));
+                let name = format!(
+                    "{:02} - {}.{}",
+                    track.number,
+                    file_name_safe(&title),
+                    extension
+                );
+                jobs.push(Job {
+                    input: track.virtual_path(),
+                    output: args.output.join(name),
+                });
+            }
+        } else if path.exists() {
+            let stem = path.file_stem().context("Invalid file name")?;
+            jobs.push(Job {
+                input: path.clone(),
+                output: args.output.join(stem).with_extension(extension),
+            });
+        } else {
+            bail!("File not found: {}", path.display());
+        }
+    }
+    if jobs.is_empty() {
+        bail!("No audio files found");
+    }
+
+    let mut outputs = HashSet::new();
+    for job in &jobs {
+        if !outputs.insert(&job.output) {
+            bail!(
+                "Several inputs would be written to {}",
+                job.output.display()
+            );
+        }
+        if same_file(&job.input, &job.output) {
+            bail!("{} would overwrite its own source", job.output.display());
+        }
+        if job.output.exists() && !args.force {
+            bail!(
+                "{} already exists (use --force to overwrite)",
+                job.output.display()
+            );
+        }
+    }
+    Ok(jobs)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn default_plugin_dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default()
+        .join("oneamp")
+        .join("plugins")
+}
@@ -0,0 +1,325 @@
+// Batch Inspection
+// `oneamp-cli info ...` reports the metadata, codec parameters, tags,
+// embedded pictures and decode validity of audio files as JSON, CSV or TSV,
+// with an exit code that tells CI jobs whether every file is sound.
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use oneamp_core::inspect::{self, FileReport};
+use oneamp_core::library;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// Exit code when at least one file could not be opened or probed
+pub const EXIT_UNREADABLE: u8 = 3;
+/// Exit code when every file was read but at least one failed the decode check
+pub const EXIT_CORRUPT: u8 = 4;
+
+#[derive(Args, Debug)]
+#[command(
+    after_help = "Exit status: 0 if every file is valid, 1 on usage or I/O errors, \
+    3 if a file could not be read, 4 if a file has corrupt or missing frames"
+)]
+pub struct InfoArgs {
+    /// Audio files or folders (searched recursively)
+    #[arg(value_name = "PATH", required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Skip the full-decode check (much faster, only headers are read)
+    #[arg(long)]
+    no_decode: bool,
+
+    /// Number of files inspected in parallel (defaults to the CPU count)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    /// A JSON array with one object per file
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+/// Columns of the CSV and TSV output
+const COLUMNS: [&str; 30] = [
+    "path",
+    "valid",
+    "error",
+    "title",
+    "artist",
+    "album",
+    "duration_secs",
+    "sample_rate",
+    "channels",
+    "codec",
+    "bitrate",
+    "start_offset_secs",
+    "end_offset_secs",
+    "codec_name",
+    "codec_long_name",
+    "sample_format",
+    "bits_per_sample",
+    "bits_per_coded_sample",
+    "channel_layout",
+    "n_frames",
+    "time_base",
+    "delay",
+    "padding",
+    "max_frames_per_packet",
+    "tag_keys",
+    "pictures",
+    "packets",
+    "frames",
+    "corrupt_packets",
+    "truncated",
+];
+
+pub fn run(args: InfoArgs) -> Result<ExitCode> {
+    let files = collect_files(&args.paths)?;
+    let jobs = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .clamp(1, files.len().max(1));
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    match args.format {
+        Format::Json => writeln!(out, "[")?,
+        Format::Csv => writeln!(out, "{}", COLUMNS.join(","))?,
+        Format::Tsv => writeln!(out, "{}", COLUMNS.join("\t"))?,
+    }
+
+    let mut unreadable = 0;
+    let mut corrupt = 0;
+    let (sender, receiver) = mpsc::channel();
+    let next = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| -> Result<()> {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (files, next) = (&files, &next);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else {
+                    break;
+                };
+                let result = inspect::inspect(path, !args.no_decode);
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Print in input order as results come in
+        let mut pending = BTreeMap::new();
+        let mut printed = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&printed) {
+                let path = &files[printed];
+                match &result {
+                    Err(_) => unreadable += 1,
+                    Ok(report) if !report.is_valid() => corrupt += 1,
+                    Ok(_) => {}
+                }
+                match args.format {
+                    Format::Json => {
+                        let separator = if printed + 1 < files.len() { "," } else { "" };
+                        writeln!(out, "{}{}", json_entry(path, &result)?, separator)?;
+                    }
+                    Format::Csv => writeln!(out, "{}", csv_row(&row(path, &result)))?,
+                    Format::Tsv => writeln!(out, "{}", tsv_row(&row(path, &result)))?,
+                }
+                printed += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Format::Json = args.format {
+        writeln!(out, "]")?;
+    }
+    out.flush()?;
+
+    eprintln!(
+        "{} file(s) checked: {} unreadable, {} corrupt",
+        files.len(),
+        unreadable,
+        corrupt
+    );
+    Ok(if unreadable > 0 {
+        ExitCode::from(EXIT_UNREADABLE)
+    } else if corrupt > 0 {
+        ExitCode::from(EXIT_CORRUPT)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Expand folders into the audio files they contain; files are taken as given
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(
+                library::audio_files_in(path)
+                    .with_context(|| format!("Failed to read folder {}", path.display()))?,
+            );
+        } else if path.exists() {
+            files.push(path.clone());
+        } else {
+            bail!("File not found: {}", path.display());
+        }
+    }
+    if files.is_empty() {
+        bail!("No audio files found");
+    }
+    Ok(files)
+}
+
+/// The report as a JSON object, plus `path`, `valid` and `error`
+fn json_entry(path: &Path, result: &Result<FileReport>) -> Result<String> {
+    let mut entry = serde_json::Map::new();
+    entry.insert("path".into(), path.display().to_string().into());
+    match result {
+        Ok(report) => {
+            entry.insert("valid".into(), report.is_valid().into());
+            let error = report.decode.as_ref().and_then(|check| check.error.clone());
+            entry.insert("error".into(), error.into());
+            if let serde_json::Value::Object(fields) = serde_json::to_value(report)? {
+                entry.extend(fields);
+            }
+        }
+        Err(err) => {
+            entry.insert("valid".into(), false.into());
+            entry.insert("error".into(), format!("{:#}", err).into());
+        }
+    }
+    Ok(serde_json::to_string(&entry)?)
+}
+
+/// The report as one value per `COLUMNS` entry; missing values are empty
+fn row(path: &Path, result: &Result<FileReport>) -> Vec<String> {
+    fn opt<T: ToString>(value: Option<T>) -> String {
+        value.map(|value| value.to_string()).unwrap_or_default()
+    }
+
+    let report = match result {
+        Ok(report) => report,
+        Err(err) => {
+            let mut row = vec![String::new(); COLUMNS.len()];
+            row[0] = path.display().to_string();
+            row[1] = false.to_string();
+            row[2] = format!("{:#}", err);
+            return row;
+        }
+    };
+    let track = &report.track;
+    let codec = &report.codec;
+    let decode = report.decode.as_ref();
+    let pictures: Vec<String> = report
+        .pictures
+        .iter()
+        .map(|picture| {
+            let mut description = format!(
+                "{} {}",
+                picture.usage.as_deref().unwrap_or("Other"),
+                picture.media_type
+            );
+            if let (Some(width), Some(height)) = (picture.width, picture.height) {
+                description.push_str(&format!(" {}x{}", width, height));
+            }
+            description.push_str(&format!(" {}B", picture.size_bytes));
+            description
+        })
+        .collect();
+
+    vec![
+        path.display().to_string(),
+        report.is_valid().to_string(),
+        opt(decode.and_then(|check| check.error.as_ref())),
+        opt(track.title.as_ref()),
+        opt(track.artist.as_ref()),
+        opt(track.album.as_ref()),
+        opt(track.duration_secs),
+        opt(track.sample_rate),
+        opt(track.channels),
+        opt(track.codec.as_ref()),
+        opt(track.bitrate),
+        opt(track.start_offset_secs),
+        opt(track.end_offset_secs),
+        opt(codec.name.as_ref()),
+        opt(codec.long_name.as_ref()),
+        opt(codec.sample_format.as_ref()),
+        opt(codec.bits_per_sample),
+        opt(codec.bits_per_coded_sample),
+        opt(codec.channel_layout.as_ref()),
+        opt(codec.n_frames),
+        opt(codec.time_base.as_ref()),
+        opt(codec.delay),
+        opt(codec.padding),
+        opt(codec.max_frames_per_packet),
+        report.tag_keys.join(";"),
+        pictures.join(";"),
+        opt(decode.map(|check| check.packets)),
+        opt(decode.map(|check| check.frames)),
+        opt(decode.map(|check| check.corrupt_packets)),
+        opt(decode.map(|check| check.truncated)),
+    ]
+}
+
+/// RFC 4180: quote fields containing separators, quotes or line breaks
+fn csv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// TSV has no quoting; tabs and line breaks become spaces
+fn tsv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| field.replace(['\t', '\n', '\r'], " "))
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        let fields = vec!["a,b".to_string(), "say \"hi\"".to_string(), "x\ty".into()];
+        assert_eq!(csv_row(&fields), "\"a,b\",\"say \"\"hi\"\"\",x\ty");
+        assert_eq!(tsv_row(&fields), "a,b\tsay \"hi\"\tx y");
+
+        let report = FileReport::default();
+        assert_eq!(row(Path::new("a.wav"), &Ok(report)).len(), COLUMNS.len());
+        let failed = row(Path::new("b.wav"), &Err(anyhow::anyhow!("bad")));
+        assert_eq!(failed.len(), COLUMNS.len());
+        assert_eq!(failed[1..3], ["false", "bad"]);
+    }
+}
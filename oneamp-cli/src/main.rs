@@ -1,187 +1,314 @@
-use anyhow::{Context, Result};
-use clap::Parser;
+// OneAmp CLI
+// Plays files, folders and playlists from the terminal through the core audio
+// engine (equalizer, seeking, CUE tracks), controls a running player,
+// inspects files in batches, exports them to WAV or FLAC, and checks and
+// packs skins.
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
-use rodio::{Decoder, OutputStream, Sink, Source};
-use std::fs::File;
-use std::io::BufReader;
+use oneamp_core::{
+    cue, equalizer, library, playlist_file, stream, AudioCommand, AudioEvent, Player, RepeatMode,
+    TrackInfo,
+};
 use std::path::PathBuf;
-use std::thread;
-use std::time::Duration;
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
-
-/// A simple CLI audio player for MP3 and FLAC files
+use std::process::ExitCode;
+
+#[cfg(unix)]
+mod ctl;
+mod export;
+mod info;
+mod skin;
+mod tui;
+
+/// A simple CLI audio player
 #[derive(Parser, Debug)]
 #[command(name = "oneamp-cli")]
 #[command(about = "OneAmp - A Winamp-like audio player CLI for Linux", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Path to the audio file to play
-    #[arg(value_name = "FILE")]
-    file: PathBuf,
+    /// Audio files, folders, M3U/PLS playlists or HTTP(S) stream URLs to play, in order
+    #[arg(value_name = "PATH", required = true)]
+    paths: Vec<PathBuf>,
 
-    /// Show detailed metadata
+    /// Show codec, sample rate and other track details
     #[arg(short, long)]
     verbose: bool,
-}
-
-/// Extract and display metadata from an audio file using Symphonia
-fn display_metadata(file_path: &PathBuf) -> Result<()> {
-    let file = File::open(file_path).context("Failed to open audio file for metadata reading")?;
 
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    /// Play the files in random order
+    #[arg(short, long)]
+    shuffle: bool,
 
-    let mut hint = Hint::new();
-    if let Some(ext) = file_path.extension() {
-        hint.with_extension(ext.to_str().unwrap_or(""));
-    }
+    /// Repeat mode: off, one or all
+    #[arg(short, long, value_name = "MODE", default_value = "off")]
+    repeat: RepeatMode,
 
-    let format_opts = FormatOptions::default();
-    let metadata_opts = MetadataOptions::default();
+    /// Equalizer preset (flat, classical, club, dance, full-bass, large-hall,
+    /// live, party, pop, reggae, rock, soft, techno)
+    #[arg(long, value_name = "PRESET", value_parser = parse_preset)]
+    eq: Option<[f32; 10]>,
 
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &format_opts, &metadata_opts)
-        .context("Failed to probe audio file")?;
+    /// Start the first track at this position (seconds or m:ss)
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    start: Option<f32>,
 
-    let mut format = probed.format;
+    /// Full-screen terminal interface with playlist, equalizer and spectrum
+    #[arg(short, long)]
+    tui: bool,
 
-    println!("\n📀 Track Information:");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    // Get metadata from the format
-    if let Some(metadata_rev) = format.metadata().current() {
-        for tag in metadata_rev.tags() {
-            match tag.std_key {
-                Some(symphonia::core::meta::StandardTagKey::TrackTitle) => {
-                    println!("  Title:  {}", tag.value);
-                }
-                Some(symphonia::core::meta::StandardTagKey::Artist) => {
-                    println!("  Artist: {}", tag.value);
-                }
-                Some(symphonia::core::meta::StandardTagKey::Album) => {
-                    println!("  Album:  {}", tag.value);
-                }
-                Some(symphonia::core::meta::StandardTagKey::Date) => {
-                    println!("  Year:   {}", tag.value);
-                }
-                _ => {}
-            }
-        }
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Control a running player (desktop app or oneamp-daemon)
+    #[cfg(unix)]
+    Ctl(ctl::CtlArgs),
+    /// Print metadata, codec details and decode validity of files as JSON, CSV or TSV
+    Info(info::InfoArgs),
+    /// Render files through the equalizer and DSP plugins to WAV or FLAC
+    Export(export::ExportArgs),
+    /// Tools for skin authors
+    Skin(skin::SkinArgs),
+}
 
-    // Get track information
-    if let Some(track) = format.default_track() {
-        let codec_params = &track.codec_params;
+fn parse_preset(name: &str) -> Result<[f32; 10], String> {
+    equalizer::preset(name).ok_or_else(|| {
+        let names: Vec<String> = equalizer::PRESETS
+            .iter()
+            .map(|(name, _)| name.to_lowercase().replace(' ', "-"))
+            .collect();
+        format!("unknown preset (available: {})", names.join(", "))
+    })
+}
 
-        if let Some(sample_rate) = codec_params.sample_rate {
-            println!("  Sample Rate: {} Hz", sample_rate);
-        }
+/// Parse `90`, `1:30` or `1:02:03.5` into seconds
+fn parse_time(time: &str) -> Result<f32, String> {
+    time.split(':')
+        .try_fold(0.0f32, |total, part| {
+            part.parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .map(|value| total * 60.0 + value)
+        })
+        .ok_or_else(|| "expected seconds or m:ss".to_string())
+}
 
-        if let Some(channels) = codec_params.channels {
-            println!("  Channels: {}", channels.count());
-        }
+/// Format seconds as `m:ss`
+fn format_time(secs: f32) -> String {
+    let secs = secs.max(0.0) as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
 
-        if let Some(n_frames) = codec_params.n_frames {
-            if let Some(sample_rate) = codec_params.sample_rate {
-                let duration_secs = n_frames / sample_rate as u64;
-                println!(
-                    "  Duration: {}:{:02}",
-                    duration_secs / 60,
-                    duration_secs % 60
-                );
+/// Expand the command line paths: folders are searched recursively and
+/// playlists are replaced by their entries. Stream URLs are kept as they are.
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if stream::is_stream_url(path) {
+            files.push(path.clone());
+        } else if path.is_dir() {
+            let found = library::audio_files_in(path)
+                .with_context(|| format!("Failed to read folder {}", path.display()))?;
+            if found.is_empty() {
+                eprintln!("⚠ No audio files in {}", path.display());
             }
+            files.extend(found);
+        } else if playlist_file::is_playlist_file(path) {
+            files.extend(playlist_file::load(path)?);
+        } else if !path.exists() {
+            bail!("File not found: {}", path.display());
+        } else if library::is_audio_file(path) || cue::is_cue_file(path) {
+            files.push(path.clone());
+        } else {
+            bail!(
+                "Unsupported file format: {}. Supported formats: {}",
+                path.display(),
+                library::AUDIO_EXTENSIONS.join(", ").to_uppercase()
+            );
         }
     }
 
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
-
-    Ok(())
+    if files.is_empty() {
+        bail!("Nothing to play");
+    }
+    Ok(files)
 }
 
-/// Play an audio file using rodio
-fn play_audio(file_path: &PathBuf) -> Result<()> {
-    // Get a output stream handle to the default physical sound device
-    let (_stream, stream_handle) =
-        OutputStream::try_default().context("Failed to get default audio output device")?;
-
-    // Create a sink to control playback
-    let sink = Sink::try_new(&stream_handle).context("Failed to create audio sink")?;
-
-    // Load the audio file
-    let file =
-        BufReader::new(File::open(file_path).context("Failed to open audio file for playback")?);
-
-    // Decode the audio file
-    let source = Decoder::new(file).context("Failed to decode audio file")?;
-
-    // Get the total duration if available
-    let total_duration = source.total_duration();
-    let duration_for_display = total_duration;
-
-    // Append the source to the sink
-    sink.append(source);
-
-    println!("🎵 Now playing: {}", file_path.display());
-
-    // Create a progress bar if we know the duration
-    if let Some(duration) = duration_for_display {
-        let pb = ProgressBar::new(duration.as_secs());
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len}s {msg}",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-        );
-
-        // Update progress bar
-        while !sink.empty() {
-            let elapsed = duration.as_secs().saturating_sub(sink.get_pos().as_secs());
-            pb.set_position(elapsed);
-            thread::sleep(Duration::from_millis(100));
-        }
+fn print_track(track: &TrackInfo, verbose: bool) {
+    let name = track.path.file_name().map(|name| name.to_string_lossy());
+    let title = track.title.as_deref().or(name.as_deref()).unwrap_or("");
+    match &track.artist {
+        Some(artist) => println!("\n🎵 Now playing: {} - {}", artist, title),
+        None => println!("\n🎵 Now playing: {}", title),
+    }
 
-        pb.finish_with_message("✓ Playback complete");
-    } else {
-        // If no duration available, just wait for playback to finish
-        println!("⏸  Playing... (Press Ctrl+C to stop)");
-        sink.sleep_until_end();
+    if verbose {
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        if let Some(album) = &track.album {
+            println!("  Album:    {}", album);
+        }
+        if let Some(duration) = track.duration_secs {
+            println!("  Duration: {}", format_time(duration));
+        }
+        let audio_info = track.format_audio_info();
+        if !audio_info.is_empty() {
+            println!("  Format:   {}", audio_info);
+        }
+        println!("  File:     {}", track.path.display());
+        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     }
+}
 
-    Ok(())
+fn progress_bar(duration_secs: f32) -> ProgressBar {
+    let bar = ProgressBar::new(duration_secs.max(0.0) as u64);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    bar
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// Set up a player from the command line and start the first track
+fn start_player(args: &Args) -> Result<Player> {
+    let files = collect_files(&args.paths)?;
+
+    let mut player = Player::new().context("Failed to start the audio engine")?;
+    player.enqueue(&files);
+    player.playlist_mut().set_repeat(args.repeat);
+    player.playlist_mut().set_shuffle(args.shuffle);
 
-    // Verify the file exists
-    if !args.file.exists() {
-        anyhow::bail!("File not found: {}", args.file.display());
+    if let Some(gains) = args.eq {
+        player.send_command(AudioCommand::SetEqualizerBands(gains.to_vec()));
+        player.send_command(AudioCommand::SetEqualizerEnabled(true));
     }
 
-    // Verify the file has a supported extension
-    let ext = args.file.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let first = player
+        .playlist_mut()
+        .next()
+        .context("The playlist is empty")?;
+    match args.start {
+        Some(start) => player.play_index_from(first, start),
+        None => player.play_index(first),
+    };
+    Ok(player)
+}
 
-    if !["mp3", "flac"].contains(&ext.to_lowercase().as_str()) {
-        anyhow::bail!(
-            "Unsupported file format: {}. Supported formats: MP3, FLAC",
-            ext
-        );
+/// Play until the playlist ends, printing tracks and progress
+fn play(args: Args) -> Result<()> {
+    let mut player = start_player(&args)?;
+    if args.tui {
+        return tui::run(player, args.eq);
     }
 
     println!("\n🎧 OneAmp CLI v{}", env!("CARGO_PKG_VERSION"));
 
-    // Display metadata
-    if args.verbose {
-        display_metadata(&args.file)?;
-    }
+    let events = player.events().clone();
+    let mut progress: Option<ProgressBar> = None;
+    // Artist and title printed last; updates such as a cover found later
+    // don't change them
+    let mut shown: (Option<String>, Option<String>) = (None, None);
+    // Tracks that failed in a row; stop once every entry has failed
+    let mut failures = 0;
+    // Tracks that failed in total, reported in the exit status
+    let mut failed = 0;
+
+    loop {
+        let event = events.recv().context("The audio engine stopped")?;
+        match &event {
+            AudioEvent::TrackLoaded(track) => {
+                if let Some(bar) = progress.take() {
+                    bar.finish_and_clear();
+                }
+                print_track(track, args.verbose);
+                shown = (track.artist.clone(), track.title.clone());
+                progress = Some(progress_bar(track.duration_secs.unwrap_or(0.0)));
+            }
+            AudioEvent::TrackUpdated(track)
+                if (&track.artist, &track.title) != (&shown.0, &shown.1) =>
+            {
+                shown = (track.artist.clone(), track.title.clone());
+                let title = track.title.as_deref().unwrap_or("");
+                let line = match &track.artist {
+                    Some(artist) => format!("♪ {} - {}", artist, title),
+                    None => format!("♪ {}", title),
+                };
+                match &progress {
+                    Some(bar) => bar.println(line),
+                    None => println!("{}", line),
+                }
+            }
+            AudioEvent::Buffering(fill) => {
+                if let Some(bar) = &progress {
+                    bar.set_message(format!("Buffering… {:.0}%", fill * 100.0));
+                }
+            }
+            AudioEvent::Playing => failures = 0,
+            AudioEvent::Position(position, duration) => {
+                if let Some(bar) = &progress {
+                    bar.set_length(duration.max(0.0) as u64);
+                    bar.set_position(position.max(0.0) as u64);
+                    bar.set_message(format!(
+                        "{} / {}",
+                        format_time(*position),
+                        format_time(*duration)
+                    ));
+                }
+            }
+            AudioEvent::Finished => {
+                if let Some(bar) = progress.take() {
+                    bar.finish_with_message("✓ Playback complete");
+                }
+            }
+            AudioEvent::Error(message) => {
+                if let Some(bar) = progress.take() {
+                    bar.abandon();
+                }
+                eprintln!("⚠ {}", message);
+            }
+            AudioEvent::SeekFailed(message) => match &progress {
+                Some(bar) => bar.println(format!("⚠ {}", message)),
+                None => eprintln!("⚠ {}", message),
+            },
+            _ => {}
+        }
 
-    // Play the audio file
-    play_audio(&args.file)?;
+        player.handle_event(&event);
 
-    println!("\n👋 Thanks for using OneAmp!\n");
+        if let AudioEvent::Error(_) = event {
+            failures += 1;
+            failed += 1;
+            if failures >= player.playlist().len() {
+                bail!("None of the tracks could be played");
+            }
+            if !player.next() {
+                break;
+            }
+        }
+        if player.is_finished() {
+            break;
+        }
+    }
 
+    if failed > 0 {
+        bail!("{} track(s) could not be played", failed);
+    }
+    println!("\n👋 Thanks for using OneAmp!\n");
     Ok(())
 }
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+
+    match args.command {
+        #[cfg(unix)]
+        Some(Command::Ctl(ctl)) => ctl::run(ctl).map(|()| ExitCode::SUCCESS),
+        Some(Command::Info(info)) => info::run(info),
+        Some(Command::Export(export)) => export::run(export).map(|()| ExitCode::SUCCESS),
+        Some(Command::Skin(skin)) => skin::run(skin),
+        None => play(args).map(|()| ExitCode::SUCCESS),
+    }
+}
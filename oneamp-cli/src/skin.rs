@@ -0,0 +1,157 @@
+// Skin Tools
+// `oneamp-cli skin check ...` validates skins the way the desktop app loads
+// them, so skin authors and skin repositories can check them in CI, and
+// `oneamp-cli skin pack ...` turns a skin folder into a `.oaskin` package to
+// share, and `oneamp-cli skin migrate ...` converts the `theme.toml` files of
+// the old theme system into skins.
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use oneamp_core::skin::{self, Skin, SkinError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Args, Debug)]
+pub struct SkinArgs {
+    #[command(subcommand)]
+    action: SkinAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum SkinAction {
+    /// Check skin folders, zipped skins or .oaskin packages, printing every
+    /// problem with its line and column in skin.toml; exits with status 1 if
+    /// any skin is invalid
+    Check {
+        #[arg(value_name = "DIR|ZIP|OASKIN", required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Pack a skin folder with a manifest.toml into a .oaskin package
+    Pack {
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Package file to write [default: <id>-<version>.oaskin]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Convert old theme.toml files into skin folders named after the themes
+    Migrate {
+        #[arg(value_name = "THEME", required = true)]
+        themes: Vec<PathBuf>,
+
+        /// Folder to create the skins in
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+pub fn run(args: SkinArgs) -> Result<ExitCode> {
+    match args.action {
+        SkinAction::Check { paths } => Ok(check_all(&paths)),
+        SkinAction::Pack { dir, output } => pack(&dir, output),
+        SkinAction::Migrate { themes, output } => Ok(migrate_all(&themes, &output)),
+    }
+}
+
+fn check_all(paths: &[PathBuf]) -> ExitCode {
+    let mut invalid = 0;
+    for path in paths {
+        match check(path) {
+            Ok(skin) => println!(
+                "✓ {}: {} {} by {}",
+                path.display(),
+                skin.metadata.name,
+                skin.metadata.version,
+                skin.metadata.author
+            ),
+            Err(error) => {
+                invalid += 1;
+                for line in error.lines() {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    eprintln!("{} skin(s) checked: {} invalid", paths.len(), invalid);
+    if invalid > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Loads a skin folder, a package, or a zipped skin when `path` is another file
+fn check(path: &Path) -> Result<Skin, SkinError> {
+    if skin::is_package(path) {
+        skin::load_package(path).map(|package| package.skin)
+    } else if path.is_file() {
+        skin::load_skin_archive(path)
+    } else {
+        skin::load_skin(path)
+    }
+}
+
+fn pack(dir: &Path, output: Option<PathBuf>) -> Result<ExitCode> {
+    // The id and version are only known from the manifest, so pack under a
+    // temporary name first
+    let temporary = output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!(".oneamp-pack-{}.oaskin", std::process::id())));
+    let manifest = match skin::pack_skin(dir, &temporary) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            for line in error.lines() {
+                println!("{}", line);
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let output = PathBuf::from(format!(
+                "{}-{}.{}",
+                manifest.id,
+                manifest.version,
+                skin::PACKAGE_EXTENSION
+            ));
+            fs::rename(&temporary, &output)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            output
+        }
+    };
+    println!(
+        "✓ {}: {} {}",
+        output.display(),
+        manifest.id,
+        manifest.version
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn migrate_all(themes: &[PathBuf], output: &Path) -> ExitCode {
+    let mut failed = 0;
+    for theme in themes {
+        match skin::migrate_theme_file(theme, output) {
+            Ok(skin_file) => println!("✓ {}: {}", theme.display(), skin_file.display()),
+            Err(error) => {
+                failed += 1;
+                for line in error.lines() {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    eprintln!("{} theme(s) migrated: {} failed", themes.len(), failed);
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
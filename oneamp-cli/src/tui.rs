@@ -0,0 +1,517 @@
+// Terminal UI
+// Full-screen player for terminals and SSH sessions: now playing, seek bar,
+// playlist, cover art, 10-band equalizer and spectrum analyzer, on the same
+// core `Player` as the line mode. Keys follow Winamp (Z/X/C/V/B).
+
+use crate::format_time;
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+use oneamp_core::spectrum::SpectrumAnalyzer;
+use oneamp_core::{AudioCommand, AudioEvent, MetadataService, PlaybackState, Player};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SEEK_STEP_SECS: f32 = 5.0;
+const VOLUME_STEP: f32 = 0.05;
+const EQ_STEP_DB: f32 = 1.0;
+const EQ_LABELS: [&str; 10] = [
+    "31", "62", "125", "250", "500", "1K", "2K", "4K", "8K", "16K",
+];
+/// Height of the cover pane, shown when the equalizer and spectrum still fit
+const COVER_ROWS: u16 = 10;
+/// Redraw interval while waiting for keys
+const FRAME: Duration = Duration::from_millis(33);
+
+const HELP: &str = "Z prev  X play  C pause  V stop  B next  ←/→ seek  +/- volume  \
+                    S shuffle  R repeat  E eq  Tab focus  Q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Playlist,
+    Equalizer,
+}
+
+struct Tui {
+    player: Player,
+    metadata: MetadataService,
+    analyzer: SpectrumAnalyzer,
+    eq_enabled: bool,
+    eq_gains: Vec<f32>,
+    selected_band: usize,
+    focus: Focus,
+    playlist_state: ListState,
+    /// Cover of the playing track, drawn for the pane's last size
+    cover: Option<CoverArt>,
+    /// Last playback error, shown in place of the key help
+    error: Option<String>,
+    running: bool,
+}
+
+/// Run the terminal UI until the user quits. `eq` is the preset the player
+/// was started with, if any.
+pub fn run(player: Player, eq: Option<[f32; 10]>) -> Result<()> {
+    let mut metadata = MetadataService::new();
+    metadata.scan(
+        player
+            .playlist()
+            .entries()
+            .iter()
+            .map(|path| path.as_path()),
+    );
+
+    let mut tui = Tui {
+        eq_enabled: eq.is_some(),
+        eq_gains: eq.unwrap_or_default().to_vec(),
+        playlist_state: ListState::default().with_selected(player.playlist().current_index()),
+        player,
+        metadata,
+        analyzer: SpectrumAnalyzer::new(64),
+        selected_band: 0,
+        focus: Focus::Playlist,
+        cover: None,
+        error: None,
+        running: true,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = tui.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Tui {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let events = self.player.events().clone();
+        while self.running {
+            while let Ok(event) = events.try_recv() {
+                self.handle_audio_event(&event);
+            }
+            if self.player.state() != PlaybackState::Playing {
+                self.analyzer.decay();
+            }
+            self.metadata.poll();
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(FRAME)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_audio_event(&mut self, event: &AudioEvent) {
+        match event {
+            AudioEvent::TrackLoaded(_) => self.error = None,
+            AudioEvent::VisualizationData(samples) => self.analyzer.update(samples),
+            AudioEvent::EqualizerUpdated(enabled, gains) => {
+                self.eq_enabled = *enabled;
+                self.eq_gains = gains.clone();
+            }
+            AudioEvent::Error(message) | AudioEvent::SeekFailed(message) => {
+                self.error = Some(message.clone())
+            }
+            _ => {}
+        }
+        self.player.handle_event(event);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.running = false,
+            KeyCode::Char('c') if ctrl => self.running = false,
+
+            // Winamp transport keys
+            KeyCode::Char('z') => {
+                self.player.previous();
+            }
+            KeyCode::Char('x') => match self.player.playlist().current_index() {
+                // X restarts the current track while playing
+                Some(index) if self.player.state() == PlaybackState::Playing => {
+                    self.player.play_index(index);
+                }
+                _ => self.player.play(),
+            },
+            KeyCode::Char('c') => self.player.toggle_pause(),
+            KeyCode::Char('v') => self.player.stop(),
+            KeyCode::Char('b') => {
+                self.player.next();
+            }
+
+            KeyCode::Char('s') => {
+                let playlist = self.player.playlist_mut();
+                playlist.set_shuffle(!playlist.shuffle());
+            }
+            KeyCode::Char('r') => {
+                self.player.playlist_mut().cycle_repeat();
+            }
+            KeyCode::Char('e') => {
+                self.player
+                    .send_command(AudioCommand::SetEqualizerEnabled(!self.eq_enabled));
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => self.change_volume(VOLUME_STEP),
+            KeyCode::Char('-') => self.change_volume(-VOLUME_STEP),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Playlist => Focus::Equalizer,
+                    Focus::Equalizer => Focus::Playlist,
+                };
+            }
+
+            code => match self.focus {
+                Focus::Playlist => self.handle_playlist_key(code),
+                Focus::Equalizer => self.handle_equalizer_key(code),
+            },
+        }
+    }
+
+    fn handle_playlist_key(&mut self, code: KeyCode) {
+        let len = self.player.playlist().len();
+        let selected = self.playlist_state.selected();
+        match code {
+            KeyCode::Left => self.seek_by(-SEEK_STEP_SECS),
+            KeyCode::Right => self.seek_by(SEEK_STEP_SECS),
+            KeyCode::Up => self.playlist_state.select_previous(),
+            KeyCode::Down => self.playlist_state.select_next(),
+            KeyCode::PageUp => self.playlist_state.scroll_up_by(10),
+            KeyCode::PageDown => self.playlist_state.scroll_down_by(10),
+            KeyCode::Home => self.playlist_state.select_first(),
+            KeyCode::End => self.playlist_state.select(len.checked_sub(1)),
+            KeyCode::Enter => {
+                if let Some(index) = selected {
+                    self.player.play_index(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_equalizer_key(&mut self, code: KeyCode) {
+        let band = self.selected_band;
+        match code {
+            KeyCode::Left => self.selected_band = band.saturating_sub(1),
+            KeyCode::Right => self.selected_band = (band + 1).min(EQ_LABELS.len() - 1),
+            KeyCode::Up => self.set_band(band, self.band_gain(band) + EQ_STEP_DB),
+            KeyCode::Down => self.set_band(band, self.band_gain(band) - EQ_STEP_DB),
+            KeyCode::Char('0') => self.set_band(band, 0.0),
+            KeyCode::Backspace => self.player.send_command(AudioCommand::ResetEqualizer),
+            _ => {}
+        }
+    }
+
+    fn band_gain(&self, band: usize) -> f32 {
+        self.eq_gains.get(band).copied().unwrap_or(0.0)
+    }
+
+    fn set_band(&mut self, band: usize, gain_db: f32) {
+        self.player.send_command(AudioCommand::SetEqualizerBand(
+            band,
+            gain_db.clamp(-12.0, 12.0),
+        ));
+    }
+
+    fn seek_by(&mut self, delta: f32) {
+        if self.player.state() != PlaybackState::Stopped {
+            let position = self.player.position_secs() + delta;
+            self.player.seek(position);
+        }
+    }
+
+    fn change_volume(&mut self, delta: f32) {
+        let volume = self.player.status().volume + delta;
+        self.player.set_volume(volume.clamp(0.0, 1.0));
+    }
+
+    /// Title shown for a playlist entry: "Artist - Title" once probed,
+    /// the file name until then
+    fn entry_title(&self, path: &Path) -> String {
+        let info = self.metadata.get(path).and_then(|m| m.info());
+        match info.and_then(|info| info.title.as_deref()) {
+            Some(title) => match info.and_then(|info| info.artist.as_deref()) {
+                Some(artist) => format!("{} - {}", artist, title),
+                None => title.to_string(),
+            },
+            None => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, seek_bar, body, footer] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [playlist, side] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(body);
+        let has_cover = self
+            .player
+            .track()
+            .is_some_and(|track| track.cover_art.is_some());
+        let cover_rows = if has_cover && side.height >= COVER_ROWS + 12 + 3 {
+            COVER_ROWS
+        } else {
+            0
+        };
+        let [cover, equalizer, spectrum] = Layout::vertical([
+            Constraint::Length(cover_rows),
+            Constraint::Length(12),
+            Constraint::Min(3),
+        ])
+        .areas(side);
+
+        self.draw_now_playing(frame, header);
+        self.draw_seek_bar(frame, seek_bar);
+        self.draw_playlist(frame, playlist);
+        if cover_rows > 0 {
+            self.draw_cover(frame, cover);
+        }
+        self.draw_equalizer(frame, equalizer);
+        self.draw_spectrum(frame, spectrum);
+
+        let footer_line = match &self.error {
+            Some(error) => Line::from(format!("⚠ {}", error)).fg(Color::Red),
+            None => Line::from(HELP).fg(Color::DarkGray),
+        };
+        frame.render_widget(Paragraph::new(footer_line), footer);
+    }
+
+    fn draw_now_playing(&self, frame: &mut Frame, area: Rect) {
+        let status = self.player.status();
+        let state = match status.state {
+            PlaybackState::Playing => "▶",
+            PlaybackState::Paused => "⏸",
+            PlaybackState::Stopped => "■",
+        };
+        let (title, details) = match &status.track {
+            Some(track) => (self.entry_title(&track.path), track.format_audio_info()),
+            None => ("Nothing playing".to_string(), String::new()),
+        };
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let lines = vec![
+            Line::from(vec![
+                Span::raw(format!("{} ", state)),
+                Span::styled(title, Style::new().add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from(format!(
+                "{}  vol {:.0}%  shuffle {}  repeat {}  eq {}",
+                details,
+                status.volume * 100.0,
+                on_off(status.shuffle),
+                status.repeat,
+                on_off(self.eq_enabled)
+            ))
+            .fg(Color::Gray),
+        ];
+        let block = Block::bordered().title(" OneAmp ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_seek_bar(&self, frame: &mut Frame, area: Rect) {
+        let position = self.player.position_secs();
+        let duration = self.player.status().duration_secs;
+        let ratio = if duration > 0.0 {
+            (position / duration).clamp(0.0, 1.0) as f64
+        } else {
+            0.0
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered())
+            .gauge_style(Style::new().fg(Color::Green).bg(Color::Black))
+            .ratio(ratio)
+            .label(format!(
+                "{} / {}",
+                format_time(position),
+                format_time(duration)
+            ));
+        frame.render_widget(gauge, area);
+    }
+
+    fn draw_playlist(&mut self, frame: &mut Frame, area: Rect) {
+        let playlist = self.player.playlist();
+        let current = playlist.current_index();
+        let items: Vec<ListItem> = playlist
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let duration = self
+                    .metadata
+                    .get(path)
+                    .and_then(|m| m.duration_secs())
+                    .map(format_time)
+                    .unwrap_or_default();
+                let marker = if Some(i) == current { "▶" } else { " " };
+                let line = Line::from(vec![
+                    Span::raw(format!("{} {:>3}. ", marker, i + 1)),
+                    Span::raw(self.entry_title(path)),
+                    Span::raw(format!("  {}", duration)).fg(Color::DarkGray),
+                ]);
+                if Some(i) == current {
+                    ListItem::new(line).fg(Color::Green)
+                } else {
+                    ListItem::new(line)
+                }
+            })
+            .collect();
+
+        let title = format!(" Playlist ({} tracks) ", playlist.len());
+        let list = List::new(items)
+            .block(self.focus_block(title, Focus::Playlist))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.playlist_state);
+    }
+
+    fn draw_cover(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(path) = self
+            .player
+            .track()
+            .and_then(|track| track.cover_art.clone())
+        else {
+            return;
+        };
+        let block = Block::bordered().title(" Cover ");
+        let inner = block.inner(area);
+        let size = (inner.width, inner.height);
+        if !self
+            .cover
+            .as_ref()
+            .is_some_and(|cover| cover.path == path && cover.size == size)
+        {
+            self.cover = Some(CoverArt::load(path, size));
+        }
+        let lines = self
+            .cover
+            .as_ref()
+            .map(|cover| cover.lines.clone())
+            .unwrap_or_default();
+        frame.render_widget(Paragraph::new(lines).centered().block(block), area);
+    }
+
+    fn draw_equalizer(&self, frame: &mut Frame, area: Rect) {
+        // Label, slider and gain take "  16K " + slider + " +12.0 dB"
+        let slider_width = (area.width as usize).saturating_sub(18).max(5);
+        let lines: Vec<Line> = EQ_LABELS
+            .iter()
+            .enumerate()
+            .map(|(band, label)| {
+                let gain = self.band_gain(band);
+                let knob = (((gain + 12.0) / 24.0) * (slider_width - 1) as f32).round() as usize;
+                let slider: String = (0..slider_width)
+                    .map(|i| match i {
+                        i if i == knob => '●',
+                        i if i == slider_width / 2 => '┼',
+                        _ => '─',
+                    })
+                    .collect();
+                let line = Line::from(format!("{:>4} {} {:+5.1} dB", label, slider, gain));
+                if self.focus == Focus::Equalizer && band == self.selected_band {
+                    line.add_modifier(Modifier::REVERSED)
+                } else if self.eq_enabled {
+                    line
+                } else {
+                    line.fg(Color::DarkGray)
+                }
+            })
+            .collect();
+
+        let title = format!(
+            " Equalizer [{}] ",
+            if self.eq_enabled { "ON" } else { "OFF" }
+        );
+        let block = self.focus_block(title, Focus::Equalizer);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_spectrum(&self, frame: &mut Frame, area: Rect) {
+        // One column per band, resampled to the available width
+        let bands = self.analyzer.bands();
+        let width = area.width.saturating_sub(2).max(1) as usize;
+        let data: Vec<u64> = (0..width)
+            .map(|i| (bands[i * bands.len() / width] * 100.0) as u64)
+            .collect();
+        let sparkline = Sparkline::default()
+            .block(Block::bordered().title(" Spectrum "))
+            .data(&data)
+            .max(100)
+            .style(Style::new().fg(Color::Cyan));
+        frame.render_widget(sparkline, area);
+    }
+
+    fn focus_block(&self, title: String, pane: Focus) -> Block<'static> {
+        let block = Block::bordered().title(title);
+        if self.focus == pane {
+            block.border_style(Style::new().fg(Color::Yellow))
+        } else {
+            block
+        }
+    }
+}
+
+/// A cover image drawn with half blocks, two square pixels per cell
+struct CoverArt {
+    path: PathBuf,
+    /// Cells it was drawn for
+    size: (u16, u16),
+    lines: Vec<Line<'static>>,
+}
+
+impl CoverArt {
+    /// Scale the image to fit the cells; an unreadable image draws nothing
+    fn load(path: PathBuf, (width, height): (u16, u16)) -> Self {
+        let lines = image::open(&path)
+            .map(|image| {
+                let image = image.resize(
+                    u32::from(width),
+                    u32::from(height) * 2,
+                    FilterType::Triangle,
+                );
+                half_blocks(&image.to_rgb8())
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            size: (width, height),
+            lines,
+        }
+    }
+}
+
+/// One line per two pixel rows: `▀` in the top pixel's color on the bottom one's
+fn half_blocks(image: &RgbImage) -> Vec<Line<'static>> {
+    let color = |pixel: &Rgb<u8>| Color::Rgb(pixel[0], pixel[1], pixel[2]);
+    (0..image.height())
+        .step_by(2)
+        .map(|y| {
+            let cells: Vec<Span> = (0..image.width())
+                .map(|x| {
+                    let top = color(image.get_pixel(x, y));
+                    let bottom = if y + 1 < image.height() {
+                        color(image.get_pixel(x, y + 1))
+                    } else {
+                        Color::Reset
+                    };
+                    Span::styled("▀", Style::new().fg(top).bg(bottom))
+                })
+                .collect();
+            Line::from(cells)
+        })
+        .collect()
+}
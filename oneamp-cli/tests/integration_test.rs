@@ -1,3 +1,4 @@
+use oneamp_core::test_wav::wav;
 use std::process::Command;
 
 #[test]
@@ -33,3 +34,101 @@ fn test_cli_missing_file() {
         "Should output error message"
     );
 }
+
+/// Write a silent 8 kHz mono WAV file; `missing` bytes of the announced
+/// data are left out
+fn write_wav(path: &std::path::Path, samples: u32, missing: u32) {
+    let mut bytes = wav(8000, 1, &vec![0; samples as usize]);
+    bytes.truncate(bytes.len() - missing as usize);
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn test_cli_info_exit_codes() {
+    let dir = std::env::temp_dir().join("oneamp_cli_info_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("album")).unwrap();
+    write_wav(&dir.join("album/01.wav"), 8000, 0);
+    write_wav(&dir.join("album/02.wav"), 4000, 0);
+    write_wav(&dir.join("cut.wav"), 8000, 8000);
+    std::fs::write(dir.join("junk.mp3"), b"not audio").unwrap();
+
+    let info = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oneamp-cli"))
+            .arg("info")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = info(&["album"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with('['));
+    assert_eq!(stdout.matches("\"valid\":true").count(), 2);
+
+    let output = info(&["--format", "csv", "album", "cut.wav"]);
+    assert_eq!(output.status.code(), Some(4), "truncated file is corrupt");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("path,valid,error,"));
+    assert!(lines[3].starts_with("cut.wav,false,"));
+
+    let output = info(&["--format", "tsv", "album", "cut.wav", "junk.mp3"]);
+    assert_eq!(output.status.code(), Some(3), "unreadable file wins");
+
+    // Without the decode check only the headers have to be sound
+    let output = info(&["--no-decode", "cut.wav"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = info(&["missing.wav"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_cli_export() {
+    let dir = std::env::temp_dir().join("oneamp_cli_export_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("album/disc 1")).unwrap();
+    write_wav(&dir.join("album/disc 1/01.wav"), 8000, 0);
+    write_wav(&dir.join("album/02.wav"), 4000, 0);
+    std::fs::write(dir.join("junk.mp3"), b"not audio").unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oneamp-cli"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = run(&[
+        "export", "album", "-o", "out", "--eq", "rock", "--rate", "48000",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("out/disc 1/01.flac").exists());
+    assert!(dir.join("out/02.flac").exists());
+
+    // The exports decode cleanly
+    let output = run(&["info", "out"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("\"sample_rate\":48000").count(), 2);
+
+    let output = run(&["export", "album", "-o", "out"]);
+    assert!(!output.status.success(), "existing files need --force");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--force"));
+
+    let output = run(&["export", "album", "junk.mp3", "-o", "wav", "-f", "wav"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 3 exports failed"));
+    assert!(dir.join("wav/02.wav").exists());
+    assert!(!dir.join("wav/junk.wav").exists());
+    assert!(!dir.join("wav/junk.wav.part").exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,375 @@
+// Album Art
+// Cover image of a track: the embedded front cover, else an image such as
+// `cover.jpg` or `AlbumArt_{...}_Large.jpg` in the track's folder. Covers are
+// scaled down to JPEG thumbnails cached on disk under a hash of the original
+// image, so the tracks of an album share one file and every front end (MPRIS
+// `artUrl`, the TUI, the desktop app) gets a small image it can read. An index
+// maps each source (audio file or folder image) by path, size and modification
+// time to its thumbnail, so finding a known cover doesn't read the image.
+
+use crate::metadata::FileStamp;
+use crate::{cue, stream};
+use anyhow::{Context, Result};
+use image::ImageFormat;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+/// Longest side of a cached thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 512;
+
+/// Folder image names (lowercase, without extension) in order of preference;
+/// Windows Media Player's `AlbumArt*` files are matched by prefix after these
+const FOLDER_NAMES: [&str; 3] = ["cover", "folder", "front"];
+const ALBUM_ART_PREFIX: &str = "albumart";
+const IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Temporary file counter, so threads creating the same thumbnail don't
+/// write to the same file
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Index entry of a source without a picture
+const NO_PICTURE: &str = "";
+
+/// Cover image of a track, from the cache in the default directory
+pub fn find(track_path: &Path) -> Option<PathBuf> {
+    ArtCache::default().cover_art(track_path)
+}
+
+/// Cover image of a track if the default cache already knows it
+pub fn cached(track_path: &Path) -> Option<PathBuf> {
+    ArtCache::default().cached_cover_art(track_path)
+}
+
+/// Thumbnails of cover images, named after a hash of the original image
+#[derive(Debug, Clone)]
+pub struct ArtCache {
+    dir: PathBuf,
+}
+
+impl ArtCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/oneamp/covers`, else `~/.cache/oneamp/covers`
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("oneamp")
+            .join("covers")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cover image of a track: a thumbnail of its embedded front cover or of
+    /// a folder image. A folder image that can't be cached is returned as it
+    /// is. Streams have no cover.
+    pub fn cover_art(&self, track_path: &Path) -> Option<PathBuf> {
+        if stream::is_stream_url(track_path) {
+            return None;
+        }
+        let file = cue::audio_file(track_path);
+        // A broken embedded picture falls back to the folder
+        if let Some(path) = self.source_thumbnail(&file, embedded_cover) {
+            return Some(path);
+        }
+        let image = folder_image(file.parent()?)?;
+        self.source_thumbnail(&image, |image| fs::read(image).ok())
+            .or(Some(image))
+    }
+
+    /// Cover image of a track from the index alone: `None` unless its
+    /// thumbnail was created before and the source hasn't changed since
+    pub fn cached_cover_art(&self, track_path: &Path) -> Option<PathBuf> {
+        if stream::is_stream_url(track_path) {
+            return None;
+        }
+        let file = cue::audio_file(track_path);
+        match self.indexed(&file)? {
+            Some(path) => Some(path),
+            None => self.indexed(&folder_image(file.parent()?)?)?,
+        }
+    }
+
+    /// Thumbnail of the picture in a source file, read with `read` unless
+    /// the index knows it. Sources without a picture are indexed too.
+    fn source_thumbnail(
+        &self,
+        source: &Path,
+        read: impl FnOnce(&Path) -> Option<Vec<u8>>,
+    ) -> Option<PathBuf> {
+        if let Some(known) = self.indexed(source) {
+            return known;
+        }
+        let entry = self.index_entry(source)?;
+        let Some(data) = read(source) else {
+            let _ = write_atomically(&entry, NO_PICTURE.as_bytes());
+            return None;
+        };
+        let path = self.thumbnail(&data).ok()?;
+        let name = path.file_name()?.to_string_lossy();
+        let _ = write_atomically(&entry, name.as_bytes());
+        Some(path)
+    }
+
+    /// What the index knows about a source: `Some(None)` if it has no
+    /// picture, `None` if it isn't indexed or its thumbnail is gone
+    fn indexed(&self, source: &Path) -> Option<Option<PathBuf>> {
+        let name = fs::read_to_string(self.index_entry(source)?).ok()?;
+        if name == NO_PICTURE {
+            return Some(None);
+        }
+        let path = self.dir.join(name);
+        path.is_file().then_some(Some(path))
+    }
+
+    /// Index file of a source, named after its path, size and modification
+    /// time so a changed file gets a new entry
+    fn index_entry(&self, source: &Path) -> Option<PathBuf> {
+        let stamp = FileStamp::of(source).ok()?;
+        let modified = stamp
+            .modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        let mut key = source.as_os_str().as_encoded_bytes().to_vec();
+        key.extend_from_slice(&stamp.size.to_le_bytes());
+        key.extend_from_slice(&modified.to_le_bytes());
+        Some(
+            self.dir
+                .join("index")
+                .join(format!("{:016x}", content_hash(&key))),
+        )
+    }
+
+    /// Thumbnail of an encoded image, created on first use
+    pub fn thumbnail(&self, data: &[u8]) -> Result<PathBuf> {
+        let path = self.dir.join(format!("{:016x}.jpg", content_hash(data)));
+        if path.is_file() {
+            return Ok(path);
+        }
+
+        let image = image::load_from_memory(data).context("Unreadable cover image")?;
+        let image = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+            image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        } else {
+            image
+        };
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        // Readers never see a half-written thumbnail
+        let temp = temp_path(&path);
+        let written = image
+            .to_rgb8()
+            .save_with_format(&temp, ImageFormat::Jpeg)
+            .context("Failed to write the thumbnail")
+            .and_then(|()| fs::rename(&temp, &path).context("Failed to write the thumbnail"));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written.map(|()| path)
+    }
+}
+
+/// Unique temporary file next to `path`
+fn temp_path(path: &Path) -> PathBuf {
+    path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Write a file through a temporary file, creating its directory
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = temp_path(path);
+    let written = fs::write(&temp, contents).and_then(|()| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(written?)
+}
+
+impl Default for ArtCache {
+    fn default() -> Self {
+        Self::new(Self::default_dir())
+    }
+}
+
+/// Embedded picture of an audio file: the front cover, else the first one
+pub fn embedded_cover(path: &Path) -> Option<Vec<u8>> {
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    // Tags read before the container (ID3v2) and by the container itself
+    let mut revisions: Vec<MetadataRevision> = Vec::new();
+    if let Some(revision) = probed.metadata.get().and_then(|m| m.current().cloned()) {
+        revisions.push(revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        revisions.push(revision.clone());
+    }
+    let visuals = || revisions.iter().flat_map(|revision| revision.visuals());
+    visuals()
+        .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+        .or_else(|| visuals().next())
+        .map(|visual| visual.data.to_vec())
+}
+
+/// Cover image in a folder: `cover`, `folder` or `front` with an image
+/// extension, else the largest `AlbumArt*.jpg`
+pub fn folder_image(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let stem = path.file_stem()?.to_str()?.to_lowercase();
+            let ext = path.extension()?.to_str()?.to_lowercase();
+            let rank = match FOLDER_NAMES.iter().position(|name| *name == stem) {
+                Some(rank) if IMAGE_EXTENSIONS.contains(&ext.as_str()) => rank,
+                None if stem.starts_with(ALBUM_ART_PREFIX) && ext == "jpg" => FOLDER_NAMES.len(),
+                _ => return None,
+            };
+            let size = entry.metadata().ok()?.len();
+            Some((rank, std::cmp::Reverse(size), path))
+        })
+        .min()
+        .map(|(_, _, path)| path)
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms
+fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("oneamp-album-art-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_pixel(width, height, Rgb([200, 30, 60]));
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_folder_image_preference() {
+        let dir = temp_dir("folder");
+        assert_eq!(folder_image(&dir), None);
+
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("cover.gif"), "").unwrap();
+        fs::write(dir.join("AlbumArtSmall.jpg"), [0; 10]).unwrap();
+        fs::write(dir.join("AlbumArt_{1234}_Large.jpg"), [0; 100]).unwrap();
+        assert_eq!(
+            folder_image(&dir),
+            Some(dir.join("AlbumArt_{1234}_Large.jpg"))
+        );
+
+        fs::write(dir.join("Front.PNG"), "").unwrap();
+        assert_eq!(folder_image(&dir), Some(dir.join("Front.PNG")));
+        fs::write(dir.join("folder.png"), "").unwrap();
+        assert_eq!(folder_image(&dir), Some(dir.join("folder.png")));
+        fs::write(dir.join("cover.jpg"), "").unwrap();
+        assert_eq!(folder_image(&dir), Some(dir.join("cover.jpg")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_thumbnail_cache() {
+        let dir = temp_dir("cache");
+        let cache = ArtCache::new(dir.join("covers"));
+
+        let large = png(1200, 600);
+        let path = cache.thumbnail(&large).unwrap();
+        assert_eq!(path.parent(), Some(cache.dir()));
+        let thumbnail = image::open(&path).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (512, 256));
+
+        // Same content, same file; small images keep their size
+        assert_eq!(cache.thumbnail(&large).unwrap(), path);
+        let small = cache.thumbnail(&png(64, 64)).unwrap();
+        assert_ne!(small, path);
+        assert_eq!(image::open(&small).unwrap().width(), 64);
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 2);
+
+        assert!(cache.thumbnail(b"not an image").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cover_art_from_folder() {
+        let dir = temp_dir("track");
+        let cache = ArtCache::new(dir.join("cache"));
+        let track = dir.join("01.flac");
+        fs::write(&track, "not audio").unwrap();
+        assert_eq!(cache.cover_art(&track), None);
+        assert_eq!(cache.cached_cover_art(&track), None);
+
+        // The index only knows covers found before
+        fs::write(dir.join("cover.png"), png(32, 32)).unwrap();
+        assert_eq!(cache.cached_cover_art(&track), None);
+        let cover = cache.cover_art(&track).unwrap();
+        assert_eq!(cover.parent(), Some(cache.dir()));
+        assert_eq!(cache.cached_cover_art(&track), Some(cover.clone()));
+        // CUE virtual tracks use their file's cover
+        let virtual_track = PathBuf::from(format!("{}#track=2", track.display()));
+        assert_eq!(cache.cover_art(&virtual_track), Some(cover));
+
+        // A folder image that can't be decoded is returned as it is
+        fs::write(dir.join("cover.png"), "broken").unwrap();
+        assert_eq!(cache.cached_cover_art(&track), None);
+        assert_eq!(cache.cover_art(&track), Some(dir.join("cover.png")));
+
+        assert_eq!(
+            cache.cover_art(Path::new("http://radio.example.com/stream")),
+            None
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
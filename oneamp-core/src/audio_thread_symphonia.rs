@@ -1,11 +1,13 @@
 use anyhow::{Context, Result};
 use crossbeam_channel::{Receiver, Sender};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use crate::album_art;
 use crate::rodio_output::RodioOutput;
+use crate::stream::{self, BufferStatus, HttpStream, StreamHandle, StreamOptions};
 use crate::symphonia_player::SymphoniaPlayer;
 use crate::{AudioCaptureBuffer, AudioCommand, AudioEvent, Equalizer, TrackInfo};
 
@@ -14,6 +16,44 @@ struct PlaybackState {
     player: SymphoniaPlayer,
     output: RodioOutput,
     is_paused: bool,
+    /// Buffer and metadata of an HTTP stream
+    stream: Option<StreamHandle>,
+    /// Last buffering progress reported while a stream fills its buffer
+    buffering: Option<f32>,
+}
+
+/// A track opened for playback, paused until its metadata is probed
+struct PendingLoad {
+    id: u64,
+    /// The opened file, or `None` while a stream is still connecting
+    state: Option<PlaybackState>,
+    /// Whether to stay paused once the track is ready
+    paused: bool,
+}
+
+/// A stream connected and probed off the audio thread
+struct ConnectedStream {
+    track_info: TrackInfo,
+    player: SymphoniaPlayer,
+    stream: StreamHandle,
+}
+
+/// What the probe thread found out about a track, in this order
+enum Probed {
+    Info {
+        id: u64,
+        info: Result<TrackInfo>,
+    },
+    /// Covers can take a while to find and scale down, so they come later
+    Cover {
+        id: u64,
+        cover_art: Option<PathBuf>,
+    },
+    /// A stream is ready to play, or could not be opened
+    Stream {
+        id: u64,
+        stream: Box<Result<ConnectedStream>>,
+    },
 }
 
 /// Main audio thread function using Symphonia + cpal
@@ -23,6 +63,12 @@ pub fn audio_thread_main_symphonia(
 ) -> Result<()> {
     let mut playback: Option<PlaybackState> = None;
     let mut current_track: Option<TrackInfo> = None;
+    let mut volume = 1.0;
+    let mut stream_options = StreamOptions::default();
+    // File opened but waiting for its metadata, and the load it belongs to
+    let mut loading: Option<PendingLoad> = None;
+    let mut load_id: u64 = 0;
+    let (probe_tx, probe_rx) = crossbeam_channel::unbounded::<Probed>();
 
     // Create equalizer (shared between audio processing and command handling)
     let equalizer = Arc::new(Mutex::new(Equalizer::new(44100.0)));
@@ -39,31 +85,73 @@ pub fn audio_thread_main_symphonia(
         // Check for commands
         if let Ok(cmd) = command_rx.try_recv() {
             match cmd {
+                AudioCommand::Play(path) if stream::is_stream_url(&path) => {
+                    playback = None;
+                    load_id += 1;
+                    loading = Some(start_stream(
+                        &path,
+                        load_id,
+                        false,
+                        &stream_options,
+                        equalizer.clone(),
+                        capture_buffer.clone(),
+                        &probe_tx,
+                    ));
+                    let _ = event_tx.send(AudioEvent::Buffering(0.0));
+                }
                 AudioCommand::Play(path) => {
                     // Stop current playback
                     playback = None;
+                    loading = None;
+                    load_id += 1;
 
-                    // Load track metadata
-                    match TrackInfo::from_file(&path) {
-                        Ok(track_info) => {
-                            current_track = Some(track_info.clone());
-                            let _ = event_tx.send(AudioEvent::TrackLoaded(track_info));
+                    // Open the file now and start once its metadata arrives
+                    match load_and_play(&path, equalizer.clone(), capture_buffer.clone(), volume) {
+                        Ok(state) => {
+                            loading = Some(start_probe(&path, load_id, state, false, &probe_tx));
+                        }
+                        Err(e) => {
+                            let _ =
+                                event_tx.send(AudioEvent::Error(format!("Failed to play: {}", e)));
+                        }
+                    }
+                }
+                // Streams can't resume at a position; connect and stay paused
+                AudioCommand::Load(path, _) if stream::is_stream_url(&path) => {
+                    playback = None;
+                    load_id += 1;
+                    loading = Some(start_stream(
+                        &path,
+                        load_id,
+                        true,
+                        &stream_options,
+                        equalizer.clone(),
+                        capture_buffer.clone(),
+                        &probe_tx,
+                    ));
+                }
+                AudioCommand::Load(path, position) => {
+                    // Stop current playback
+                    playback = None;
+                    loading = None;
+                    load_id += 1;
 
-                            // Load and play the file
-                            match load_and_play(&path, equalizer.clone(), capture_buffer.clone()) {
-                                Ok(state) => {
-                                    playback = Some(state);
-                                    let _ = event_tx.send(AudioEvent::Playing);
-                                }
-                                Err(e) => {
-                                    let _ = event_tx
-                                        .send(AudioEvent::Error(format!("Failed to play: {}", e)));
+                    // Open the file, seek, and leave it paused
+                    match load_and_play(&path, equalizer.clone(), capture_buffer.clone(), volume) {
+                        Ok(mut state) => {
+                            if position > 0.0 {
+                                if let Err(e) = state.player.seek(position) {
+                                    let _ = event_tx.send(AudioEvent::SeekFailed(format!(
+                                        "Failed to seek: {}",
+                                        e
+                                    )));
                                 }
                             }
+                            loading = Some(start_probe(&path, load_id, state, true, &probe_tx));
                         }
                         Err(e) => {
-                            let _ = event_tx
-                                .send(AudioEvent::Error(format!("Failed to load track: {}", e)));
+                            let _ =
+                                event_tx.send(AudioEvent::Error(format!("Failed to load: {}", e)));
                         }
                     }
                 }
@@ -75,6 +163,10 @@ pub fn audio_thread_main_symphonia(
                             let _ = event_tx.send(AudioEvent::Paused);
                         }
                     }
+                    // A track still loading starts paused
+                    if let Some(ref mut pending) = loading {
+                        pending.paused = true;
+                    }
                 }
                 AudioCommand::Resume => {
                     if let Some(ref mut state) = playback {
@@ -84,14 +176,19 @@ pub fn audio_thread_main_symphonia(
                             let _ = event_tx.send(AudioEvent::Playing);
                         }
                     }
+                    if let Some(ref mut pending) = loading {
+                        pending.paused = false;
+                    }
                 }
                 AudioCommand::Stop => {
                     playback = None;
+                    loading = None;
                     current_track = None;
                     let _ = event_tx.send(AudioEvent::Stopped);
                 }
                 AudioCommand::Seek(pos) => {
-                    if let Some(ref mut state) = playback {
+                    // Streams can't seek
+                    if let Some(state) = playback.as_mut().filter(|state| state.stream.is_none()) {
                         // Perform the seek
                         match state.player.seek(pos) {
                             Ok(()) => {
@@ -101,20 +198,28 @@ pub fn audio_thread_main_symphonia(
                             }
                             Err(e) => {
                                 let _ = event_tx
-                                    .send(AudioEvent::Error(format!("Failed to seek: {}", e)));
+                                    .send(AudioEvent::SeekFailed(format!("Failed to seek: {}", e)));
                             }
                         }
                     }
+                    if let Some(state) = loading.as_mut().and_then(|p| p.state.as_mut()) {
+                        if let Err(e) = state.player.seek(pos) {
+                            let _ = event_tx
+                                .send(AudioEvent::SeekFailed(format!("Failed to seek: {}", e)));
+                        }
+                    }
                 }
                 AudioCommand::Next => {
                     // Stop current playback and request next track from GUI
                     playback = None;
+                    loading = None;
                     current_track = None;
                     let _ = event_tx.send(AudioEvent::RequestNext);
                 }
                 AudioCommand::Previous => {
                     // Stop current playback and request previous track from GUI
                     playback = None;
+                    loading = None;
                     current_track = None;
                     let _ = event_tx.send(AudioEvent::RequestPrevious);
                 }
@@ -149,16 +254,133 @@ pub fn audio_thread_main_symphonia(
                         let _ = event_tx.send(AudioEvent::EqualizerUpdated(enabled, gains));
                     }
                 }
+                AudioCommand::SetVolume(new_volume) => {
+                    volume = new_volume.clamp(0.0, 1.0);
+                    for state in playback
+                        .iter()
+                        .chain(loading.iter().filter_map(|pending| pending.state.as_ref()))
+                    {
+                        state.output.set_volume(volume);
+                    }
+                    let _ = event_tx.send(AudioEvent::VolumeChanged(volume));
+                }
+                AudioCommand::SetStreamOptions(options) => {
+                    stream_options = options;
+                }
                 AudioCommand::Shutdown => {
                     break;
                 }
             }
         }
 
+        // Start a loaded file once its metadata arrives, and show its cover
+        // once found
+        match probe_rx.try_recv() {
+            Ok(Probed::Info { id, info }) => {
+                if let Some(PendingLoad {
+                    state: Some(mut state),
+                    paused,
+                    ..
+                }) = loading.take_if(|pending| pending.id == id)
+                {
+                    state.is_paused = paused;
+                    match info {
+                        Ok(track_info) => {
+                            let total_duration = track_info.duration_secs.unwrap_or(0.0);
+                            current_track = Some(track_info.clone());
+                            let _ = event_tx.send(AudioEvent::TrackLoaded(track_info));
+                            if state.is_paused {
+                                let current_pos = state.player.current_position();
+                                let _ = event_tx
+                                    .send(AudioEvent::Position(current_pos, total_duration));
+                                let _ = event_tx.send(AudioEvent::Paused);
+                            } else {
+                                let _ = state.output.play();
+                                let _ = event_tx.send(AudioEvent::Playing);
+                            }
+                            playback = Some(state);
+                        }
+                        Err(e) => {
+                            let _ = event_tx
+                                .send(AudioEvent::Error(format!("Failed to load track: {}", e)));
+                        }
+                    }
+                }
+            }
+            Ok(Probed::Cover { id, cover_art }) => {
+                if let Some(track) = current_track.as_mut().filter(|_| id == load_id) {
+                    if track.cover_art != cover_art {
+                        track.cover_art = cover_art;
+                        let _ = event_tx.send(AudioEvent::TrackUpdated(track.clone()));
+                    }
+                }
+            }
+            Ok(Probed::Stream { id, stream }) => {
+                if let Some(PendingLoad { paused, .. }) =
+                    loading.take_if(|pending| pending.id == id)
+                {
+                    let context = if paused { "load" } else { "play" };
+                    match (*stream).and_then(|stream| stream_playback(stream, paused, volume)) {
+                        Ok((track_info, state)) => {
+                            current_track = Some(track_info.clone());
+                            playback = Some(state);
+                            let _ = event_tx.send(AudioEvent::TrackLoaded(track_info));
+                            if paused {
+                                let _ = event_tx.send(AudioEvent::Paused);
+                            } else {
+                                let _ = event_tx.send(AudioEvent::Buffering(0.0));
+                            }
+                        }
+                        Err(e) => {
+                            let _ = event_tx
+                                .send(AudioEvent::Error(format!("Failed to {}: {:#}", context, e)));
+                        }
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+
         // Decode and feed audio to output
         let mut end_of_stream = false;
+        let mut stream_error = None;
         if let Some(ref mut state) = playback {
-            if !state.is_paused {
+            let mut buffering = None;
+            if let Some(ref stream) = state.stream {
+                // New stream title
+                if let (Some(title), Some(track)) = (stream.take_title(), current_track.as_mut()) {
+                    let (artist, title) = stream::split_stream_title(&title);
+                    track.artist = artist;
+                    track.title = title.or_else(|| stream.info().name.clone());
+                    let _ = event_tx.send(AudioEvent::TrackUpdated(track.clone()));
+                }
+
+                match stream.status() {
+                    BufferStatus::Ready => {}
+                    BufferStatus::Buffering(progress) => buffering = Some(progress),
+                    BufferStatus::Failed(message) => stream_error = Some(message),
+                }
+            }
+
+            // Report buffering progress, and playing again once the buffer is full
+            match buffering {
+                Some(progress) => {
+                    if state
+                        .buffering
+                        .is_none_or(|last| (progress - last).abs() >= 0.05)
+                    {
+                        state.buffering = Some(progress);
+                        let _ = event_tx.send(AudioEvent::Buffering(progress));
+                    }
+                }
+                None => {
+                    if state.buffering.take().is_some() && !state.is_paused {
+                        let _ = event_tx.send(AudioEvent::Playing);
+                    }
+                }
+            }
+
+            if !state.is_paused && buffering.is_none() && stream_error.is_none() {
                 // Check if output needs more data
                 if state.output.needs_data() {
                     match state.player.decode_next() {
@@ -197,6 +419,11 @@ pub fn audio_thread_main_symphonia(
         }
 
         // Handle end of stream outside the borrow
+        if let Some(message) = stream_error {
+            playback = None;
+            current_track = None;
+            let _ = event_tx.send(AudioEvent::Error(message));
+        }
         if end_of_stream {
             playback = None;
             current_track = None;
@@ -210,11 +437,86 @@ pub fn audio_thread_main_symphonia(
     Ok(())
 }
 
+/// Pause a freshly opened file and probe its metadata on another thread, so
+/// reading tags and covers doesn't hold up the audio thread
+fn start_probe(
+    path: &Path,
+    id: u64,
+    state: PlaybackState,
+    paused: bool,
+    probe_tx: &Sender<Probed>,
+) -> PendingLoad {
+    let _ = state.output.pause();
+
+    let path = path.to_path_buf();
+    let probe_tx = probe_tx.clone();
+    let probe = move || {
+        let info = TrackInfo::from_file(&path);
+        let found = info.is_ok();
+        if probe_tx.send(Probed::Info { id, info }).is_ok() && found {
+            let cover_art = album_art::find(&path);
+            let _ = probe_tx.send(Probed::Cover { id, cover_art });
+        }
+    };
+    // Without a thread, probe here rather than fail the track
+    let spawned = thread::Builder::new()
+        .name("oneamp-probe".to_string())
+        .spawn(probe.clone());
+    if let Err(e) = spawned {
+        eprintln!("Failed to start the metadata probe: {}", e);
+        probe();
+    }
+
+    PendingLoad {
+        id,
+        state: Some(state),
+        paused,
+    }
+}
+
+/// Connect to an HTTP stream and probe its format on another thread, so a
+/// slow server doesn't hold up the audio thread
+fn start_stream(
+    path: &Path,
+    id: u64,
+    paused: bool,
+    options: &StreamOptions,
+    equalizer: Arc<Mutex<Equalizer>>,
+    capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
+    probe_tx: &Sender<Probed>,
+) -> PendingLoad {
+    let path = path.to_path_buf();
+    let options = options.clone();
+    let probe_tx = probe_tx.clone();
+    let connect = move || {
+        let stream = connect_stream(&path, &options, equalizer, capture_buffer);
+        let _ = probe_tx.send(Probed::Stream {
+            id,
+            stream: Box::new(stream),
+        });
+    };
+    // Without a thread, connect here rather than fail the stream
+    let spawned = thread::Builder::new()
+        .name("oneamp-stream".to_string())
+        .spawn(connect.clone());
+    if let Err(e) = spawned {
+        eprintln!("Failed to start the stream connection: {}", e);
+        connect();
+    }
+
+    PendingLoad {
+        id,
+        state: None,
+        paused,
+    }
+}
+
 /// Load and start playing an audio file
 fn load_and_play(
     path: &Path,
     equalizer: Arc<Mutex<Equalizer>>,
     capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
+    volume: f32,
 ) -> Result<PlaybackState> {
     // Create player
     let player = SymphoniaPlayer::load(path, equalizer, capture_buffer)
@@ -223,10 +525,72 @@ fn load_and_play(
     // Create output
     let output = RodioOutput::new(player.sample_rate(), player.channels())
         .context("Failed to create audio output")?;
+    output.set_volume(volume);
 
     Ok(PlaybackState {
         player,
         output,
         is_paused: false,
+        stream: None,
+        buffering: None,
     })
 }
+
+/// Connect to an HTTP stream and probe its format
+fn connect_stream(
+    path: &Path,
+    options: &StreamOptions,
+    equalizer: Arc<Mutex<Equalizer>>,
+    capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
+) -> Result<ConnectedStream> {
+    let url = path.to_str().context("Invalid stream URL")?;
+    let (source, stream) = HttpStream::open(url, options)?;
+    let player = SymphoniaPlayer::from_source(
+        Box::new(source),
+        stream.info().extension(),
+        equalizer,
+        capture_buffer,
+    )
+    .context("Failed to decode the stream")?;
+
+    let mut track_info = stream.info().track_info();
+    track_info.sample_rate = Some(player.sample_rate());
+    track_info.channels = Some(player.channels() as u8);
+
+    Ok(ConnectedStream {
+        track_info,
+        player,
+        stream,
+    })
+}
+
+/// Open the output for a connected stream, which starts playing once buffered
+fn stream_playback(
+    connected: ConnectedStream,
+    paused: bool,
+    volume: f32,
+) -> Result<(TrackInfo, PlaybackState)> {
+    let ConnectedStream {
+        track_info,
+        player,
+        stream,
+    } = connected;
+
+    let output = RodioOutput::new(player.sample_rate(), player.channels())
+        .context("Failed to create audio output")?;
+    output.set_volume(volume);
+    if paused {
+        let _ = output.pause();
+    }
+
+    Ok((
+        track_info,
+        PlaybackState {
+            player,
+            output,
+            is_paused: paused,
+            stream: Some(stream),
+            buffering: Some(0.0),
+        },
+    ))
+}
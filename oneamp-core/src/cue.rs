@@ -0,0 +1,641 @@
+// CUE Sheet Support
+// Parses CUE sheets (sidecar `.cue` files or embedded `CUESHEET` tags) and maps
+// their tracks to virtual tracks addressed as `file.flac#track=3`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Suffix marker used to address a single track inside a file.
+const VIRTUAL_TRACK_MARKER: &str = "#track=";
+
+/// CD frames per second used by CUE timestamps (mm:ss:ff).
+const CUE_FRAMES_PER_SECOND: f32 = 75.0;
+
+/// A single track described by a CUE sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueTrack {
+    /// Track number as written in the sheet (1-based).
+    pub number: u32,
+    /// Audio file containing this track.
+    pub file: PathBuf,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Start of the track (INDEX 01) in seconds from the start of the file.
+    pub start_secs: f32,
+    /// End of the track in seconds, or `None` if it runs to the end of the file.
+    pub end_secs: Option<f32>,
+}
+
+impl CueTrack {
+    /// Get the virtual path addressing this track (e.g. `album.flac#track=3`).
+    pub fn virtual_path(&self) -> PathBuf {
+        virtual_track_path(&self.file, self.number)
+    }
+}
+
+/// A parsed CUE sheet.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueSheet {
+    /// Album title.
+    pub title: Option<String>,
+    /// Album performer.
+    pub performer: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+impl CueSheet {
+    /// Parse CUE sheet text.
+    ///
+    /// # Arguments
+    /// * `content` - The CUE sheet contents
+    /// * `base_dir` - Directory used to resolve relative `FILE` entries
+    pub fn parse(content: &str, base_dir: &Path) -> Result<Self> {
+        let mut sheet = CueSheet::default();
+        let mut current_file: Option<PathBuf> = None;
+        let mut current_track: Option<CueTrack> = None;
+        let mut current_has_start = false;
+
+        let content = content.trim_start_matches('\u{feff}');
+
+        for (line_no, raw_line) in content.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (command, rest) = match line.split_once(char::is_whitespace) {
+                Some((command, rest)) => (command, rest.trim()),
+                None => (line, ""),
+            };
+
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    let name = parse_file_name(rest);
+                    if name.is_empty() {
+                        return Err(anyhow!("Line {}: FILE without a file name", line_no + 1));
+                    }
+                    current_file = Some(base_dir.join(name));
+                }
+                "TRACK" => {
+                    if let Some(track) = current_track.take() {
+                        if current_has_start {
+                            sheet.tracks.push(track);
+                        }
+                    }
+
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .ok_or_else(|| anyhow!("Line {}: invalid TRACK number", line_no + 1))?;
+                    let file = current_file
+                        .clone()
+                        .ok_or_else(|| anyhow!("Line {}: TRACK before FILE", line_no + 1))?;
+
+                    current_track = Some(CueTrack {
+                        number,
+                        file,
+                        title: None,
+                        performer: None,
+                        start_secs: 0.0,
+                        end_secs: None,
+                    });
+                    current_has_start = false;
+                }
+                "TITLE" => {
+                    let value = unquote(rest);
+                    match current_track {
+                        Some(ref mut track) => track.title = Some(value),
+                        None => sheet.title = Some(value),
+                    }
+                }
+                "PERFORMER" => {
+                    let value = unquote(rest);
+                    match current_track {
+                        Some(ref mut track) => track.performer = Some(value),
+                        None => sheet.performer = Some(value),
+                    }
+                }
+                "INDEX" => {
+                    let mut parts = rest.split_whitespace();
+                    let index = parts.next().and_then(|n| n.parse::<u32>().ok());
+                    let time = parts.next().and_then(parse_cue_time);
+
+                    let (Some(index), Some(time)) = (index, time) else {
+                        return Err(anyhow!("Line {}: invalid INDEX entry", line_no + 1));
+                    };
+
+                    // INDEX 00 is the pregap, which belongs to the previous track
+                    if index == 1 {
+                        if let Some(ref mut track) = current_track {
+                            track.start_secs = time;
+                            current_has_start = true;
+                        }
+                    }
+                }
+                // REM, FLAGS, ISRC, CATALOG, PREGAP, POSTGAP... are not needed for playback
+                _ => {}
+            }
+        }
+
+        if let Some(track) = current_track.take() {
+            if current_has_start {
+                sheet.tracks.push(track);
+            }
+        }
+
+        if sheet.tracks.is_empty() {
+            return Err(anyhow!("CUE sheet contains no playable tracks"));
+        }
+
+        // A track ends where the next track in the same file starts
+        for i in 0..sheet.tracks.len().saturating_sub(1) {
+            if sheet.tracks[i].file == sheet.tracks[i + 1].file {
+                sheet.tracks[i].end_secs = Some(sheet.tracks[i + 1].start_secs);
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Load a sidecar `.cue` file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).context("Failed to read CUE sheet")?;
+        // Many rips use legacy encodings; fall back to a lossy conversion
+        let content = String::from_utf8_lossy(&bytes);
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&content, base_dir)
+    }
+
+    /// Load a CUE sheet embedded in a FLAC file's `CUESHEET` tag.
+    ///
+    /// Only FLAC files are read, and only up to their Vorbis comments, so this
+    /// stays cheap enough to run for every file added to a playlist. All tracks
+    /// of an embedded sheet refer to `audio_path` itself, whatever `FILE` entry
+    /// the sheet contains.
+    pub fn from_embedded(audio_path: &Path) -> Result<Option<Self>> {
+        let is_flac = audio_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("flac"));
+        if !is_flac {
+            return Ok(None);
+        }
+
+        let file = File::open(audio_path).context("Failed to open audio file")?;
+        let Some(content) = read_flac_cuesheet(&mut BufReader::new(file))? else {
+            return Ok(None);
+        };
+
+        let base_dir = audio_path.parent().unwrap_or_else(|| Path::new(""));
+        let mut sheet = Self::parse(&content, base_dir)?;
+        for track in &mut sheet.tracks {
+            track.file = audio_path.to_path_buf();
+        }
+        // Every track now lives in the same file, so recompute end offsets
+        for i in 0..sheet.tracks.len().saturating_sub(1) {
+            sheet.tracks[i].end_secs = Some(sheet.tracks[i + 1].start_secs);
+        }
+
+        Ok(Some(sheet))
+    }
+
+    /// Find the CUE sheet describing an audio file.
+    ///
+    /// Looks for a sidecar `album.cue` or `album.flac.cue` next to the file first,
+    /// then for an embedded `CUESHEET` tag in FLAC files.
+    pub fn find_for(audio_path: &Path) -> Option<Self> {
+        let mut candidates = vec![audio_path.with_extension("cue")];
+        let mut appended = audio_path.as_os_str().to_owned();
+        appended.push(".cue");
+        candidates.push(PathBuf::from(appended));
+
+        for candidate in candidates {
+            if !candidate.is_file() {
+                continue;
+            }
+            if let Ok(mut sheet) = Self::from_file(&candidate) {
+                if sheet.tracks.iter().any(|t| t.file == audio_path) {
+                    return Some(sheet);
+                }
+
+                // Sheets often still name the original rip (e.g. album.wav) after
+                // transcoding; a single-file sheet next to the audio file is ours
+                let first_file = sheet.tracks[0].file.clone();
+                if sheet.tracks.iter().all(|t| t.file == first_file) {
+                    for track in &mut sheet.tracks {
+                        track.file = audio_path.to_path_buf();
+                    }
+                    return Some(sheet);
+                }
+            }
+        }
+
+        Self::from_embedded(audio_path).ok().flatten()
+    }
+
+    /// Get a track by its number.
+    pub fn track(&self, number: u32) -> Option<&CueTrack> {
+        self.tracks.iter().find(|t| t.number == number)
+    }
+}
+
+/// A virtual track resolved from a `file#track=N` path.
+#[derive(Debug, Clone)]
+pub struct ResolvedTrack {
+    pub sheet: CueSheet,
+    pub track: CueTrack,
+}
+
+/// Build the virtual path addressing track `number` of `file`.
+pub fn virtual_track_path(file: &Path, number: u32) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(format!("{}{}", VIRTUAL_TRACK_MARKER, number));
+    PathBuf::from(path)
+}
+
+/// Split a possibly virtual path into the underlying file and the track number.
+///
+/// `album.flac#track=3` yields `(album.flac, Some(3))`; regular paths yield
+/// `(path, None)`.
+pub fn split_virtual_track(path: &Path) -> (PathBuf, Option<u32>) {
+    if let Some(path_str) = path.to_str() {
+        if let Some((file, number)) = path_str.rsplit_once(VIRTUAL_TRACK_MARKER) {
+            if let Ok(number) = number.parse::<u32>() {
+                return (PathBuf::from(file), Some(number));
+            }
+        }
+    }
+    (path.to_path_buf(), None)
+}
+
+/// Resolve a virtual track path to its CUE sheet and track.
+///
+/// Returns `Ok(None)` for regular paths. The base of a virtual path may be
+/// either the audio file or the `.cue` file itself.
+pub fn resolve_virtual_track(path: &Path) -> Result<Option<ResolvedTrack>> {
+    let (base, Some(number)) = split_virtual_track(path) else {
+        return Ok(None);
+    };
+
+    let sheet = if is_cue_file(&base) {
+        CueSheet::from_file(&base)?
+    } else {
+        CueSheet::find_for(&base)
+            .ok_or_else(|| anyhow!("No CUE sheet found for {}", base.display()))?
+    };
+
+    let track = sheet
+        .track(number)
+        .cloned()
+        .ok_or_else(|| anyhow!("Track {} not found in CUE sheet", number))?;
+
+    Ok(Some(ResolvedTrack { sheet, track }))
+}
+
+/// Expand a playlist entry into the paths that should be added to a playlist.
+///
+/// `.cue` files and audio files with a CUE sheet expand to one virtual track
+/// per sheet entry; anything else is returned unchanged. Tracks of a `.cue`
+/// file are addressed through the sheet (`disc.cue#track=3`), since its name
+/// may not match the audio file it describes.
+pub fn expand_playlist_entry(path: &Path) -> Vec<PathBuf> {
+    if split_virtual_track(path).1.is_some() || crate::stream::is_stream_url(path) {
+        return vec![path.to_path_buf()];
+    }
+
+    if is_cue_file(path) {
+        return match CueSheet::from_file(path) {
+            Ok(sheet) => sheet
+                .tracks
+                .iter()
+                .map(|track| virtual_track_path(path, track.number))
+                .collect(),
+            Err(_) => vec![path.to_path_buf()],
+        };
+    }
+
+    match CueSheet::find_for(path) {
+        Some(sheet) => sheet.tracks.iter().map(CueTrack::virtual_path).collect(),
+        None => vec![path.to_path_buf()],
+    }
+}
+
+/// The audio file behind a playlist entry: the file a virtual track lives in,
+/// read from the sheet when the track is addressed through a `.cue` file, or
+/// the path itself.
+pub fn audio_file(path: &Path) -> PathBuf {
+    let (base, number) = split_virtual_track(path);
+    match number {
+        Some(_) if is_cue_file(&base) => resolve_virtual_track(path)
+            .ok()
+            .flatten()
+            .map_or(base, |resolved| resolved.track.file),
+        _ => base,
+    }
+}
+
+/// Check whether a path points to a `.cue` file.
+pub fn is_cue_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+/// Parse a CUE timestamp (mm:ss:ff) into seconds.
+fn parse_cue_time(time: &str) -> Option<f32> {
+    let mut parts = time.split(':');
+    let minutes = parts.next()?.parse::<u32>().ok()?;
+    let seconds = parts.next()?.parse::<u32>().ok()?;
+    let frames = parts.next()?.parse::<u32>().ok()?;
+    if parts.next().is_some() || seconds >= 60 || frames as f32 >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+    Some(minutes as f32 * 60.0 + seconds as f32 + frames as f32 / CUE_FRAMES_PER_SECOND)
+}
+
+/// Extract the file name from the arguments of a FILE command.
+/// `"My Album.wav" WAVE` -> `My Album.wav`
+fn parse_file_name(rest: &str) -> String {
+    if let Some(stripped) = rest.strip_prefix('"') {
+        if let Some(end) = stripped.find('"') {
+            return stripped[..end].to_string();
+        }
+    }
+
+    // Unquoted: drop the trailing file type if present
+    match rest.rsplit_once(char::is_whitespace) {
+        Some((name, _file_type)) => name.trim().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// Remove surrounding quotes from a value.
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Find the `CUESHEET` Vorbis comment of a FLAC stream, skipping over the
+/// other metadata blocks (pictures, seek tables) without reading them.
+fn read_flac_cuesheet(reader: &mut (impl Read + Seek)) -> Result<Option<String>> {
+    let mut marker = [0u8; 4];
+    reader.read_exact(&mut marker)?;
+    // Some taggers put an ID3v2 tag in front of the stream
+    if &marker[..3] == b"ID3" {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header)?;
+        let size = header[2..]
+            .iter()
+            .fold(0i64, |size, &byte| (size << 7) | (byte & 0x7f) as i64);
+        let footer = if header[1] & 0x10 != 0 { 10 } else { 0 };
+        reader.seek(SeekFrom::Current(size + footer))?;
+        reader.read_exact(&mut marker)?;
+    }
+    if &marker != b"fLaC" {
+        bail!("Not a FLAC stream");
+    }
+
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+        // VORBIS_COMMENT
+        if header[0] & 0x7f == 4 {
+            let mut block = vec![0u8; length as usize];
+            reader.read_exact(&mut block)?;
+            return Ok(find_cuesheet_comment(&block));
+        }
+        if is_last {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Current(length as i64))?;
+    }
+}
+
+/// Find the `CUESHEET` entry of a Vorbis comment block.
+fn find_cuesheet_comment(block: &[u8]) -> Option<String> {
+    let read_u32 = |at: usize| -> Option<usize> {
+        let bytes = block.get(at..at + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    };
+    let mut at = 4 + read_u32(0)?;
+    let count = read_u32(at)?;
+    at += 4;
+    for _ in 0..count {
+        let length = read_u32(at)?;
+        let comment = block.get(at + 4..at + 4 + length)?;
+        at += 4 + length;
+        let comment = String::from_utf8_lossy(comment);
+        if let Some((key, value)) = comment.split_once('=') {
+            if key.eq_ignore_ascii_case("CUESHEET") {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CUE: &str = r#"REM GENRE Rock
+PERFORMER "The Band"
+TITLE "The Album"
+FILE "The Album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "First Song"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second Song"
+    PERFORMER "Guest Artist"
+    INDEX 00 03:20:00
+    INDEX 01 03:22:37
+  TRACK 03 AUDIO
+    TITLE "Third Song"
+    INDEX 01 07:45:00
+"#;
+
+    #[test]
+    fn test_parse_cue_time() {
+        assert_eq!(parse_cue_time("00:00:00"), Some(0.0));
+        assert_eq!(parse_cue_time("03:22:00"), Some(202.0));
+        assert_eq!(parse_cue_time("01:00:75"), None);
+        assert_eq!(parse_cue_time("garbage"), None);
+
+        let time = parse_cue_time("00:01:15").unwrap();
+        assert!((time - 1.2).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_parse_sheet() {
+        let sheet = CueSheet::parse(SAMPLE_CUE, Path::new("/music")).unwrap();
+
+        assert_eq!(sheet.title.as_deref(), Some("The Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.tracks.len(), 3);
+
+        let second = sheet.track(2).unwrap();
+        assert_eq!(second.title.as_deref(), Some("Second Song"));
+        assert_eq!(second.performer.as_deref(), Some("Guest Artist"));
+        assert_eq!(second.file, PathBuf::from("/music/The Album.flac"));
+        assert!((second.start_secs - (202.0 + 37.0 / 75.0)).abs() < 0.0001);
+        assert_eq!(second.end_secs, Some(465.0));
+
+        // Last track runs to the end of the file
+        assert_eq!(sheet.track(3).unwrap().end_secs, None);
+    }
+
+    #[test]
+    fn test_parse_sheet_multiple_files() {
+        let content = r#"FILE "a.wav" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+FILE "b.wav" WAVE
+  TRACK 02 AUDIO
+    INDEX 01 00:00:00
+"#;
+        let sheet = CueSheet::parse(content, Path::new("")).unwrap();
+        assert_eq!(sheet.tracks.len(), 2);
+        // Tracks in different files do not bound each other
+        assert_eq!(sheet.tracks[0].end_secs, None);
+        assert_eq!(sheet.tracks[1].file, PathBuf::from("b.wav"));
+    }
+
+    #[test]
+    fn test_parse_sheet_errors() {
+        assert!(CueSheet::parse("", Path::new("")).is_err());
+        assert!(CueSheet::parse("TRACK 01 AUDIO\nINDEX 01 00:00:00", Path::new("")).is_err());
+        assert!(CueSheet::parse("FILE \"a.wav\" WAVE\nTRACK xx AUDIO", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(parse_file_name("\"My Album.wav\" WAVE"), "My Album.wav");
+        assert_eq!(parse_file_name("album.flac WAVE"), "album.flac");
+    }
+
+    #[test]
+    fn test_virtual_track_path_round_trip() {
+        let path = virtual_track_path(Path::new("/music/album.flac"), 3);
+        assert_eq!(path, PathBuf::from("/music/album.flac#track=3"));
+
+        let (file, number) = split_virtual_track(&path);
+        assert_eq!(file, PathBuf::from("/music/album.flac"));
+        assert_eq!(number, Some(3));
+
+        let (file, number) = split_virtual_track(Path::new("/music/song#1.mp3"));
+        assert_eq!(file, PathBuf::from("/music/song#1.mp3"));
+        assert_eq!(number, None);
+    }
+
+    #[test]
+    fn test_resolve_and_expand_from_cue_file() {
+        let dir = std::env::temp_dir().join("oneamp_cue_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let cue_path = dir.join("The Album.cue");
+        std::fs::write(&cue_path, SAMPLE_CUE).unwrap();
+
+        let entries = expand_playlist_entry(&cue_path);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], dir.join("The Album.cue#track=1"));
+        assert_eq!(audio_file(&entries[0]), dir.join("The Album.flac"));
+
+        let resolved = resolve_virtual_track(&virtual_track_path(&cue_path, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.track.title.as_deref(), Some("Second Song"));
+        assert!(resolve_virtual_track(&virtual_track_path(&cue_path, 9)).is_err());
+        assert!(resolve_virtual_track(Path::new("/music/plain.mp3"))
+            .unwrap()
+            .is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_expand_cue_file_named_differently() {
+        let dir = std::env::temp_dir().join("oneamp_cue_test_disc");
+        std::fs::create_dir_all(&dir).unwrap();
+        // Neither album.cue nor album.flac.cue: only the sheet knows its tracks
+        let cue_path = dir.join("disc.cue");
+        std::fs::write(
+            &cue_path,
+            SAMPLE_CUE.replace("The Album.flac", "album.flac"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("album.flac"), b"").unwrap();
+
+        let entries = expand_playlist_entry(&cue_path);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], dir.join("disc.cue#track=2"));
+        for (number, entry) in (1..).zip(&entries) {
+            let resolved = resolve_virtual_track(entry).unwrap().unwrap();
+            assert_eq!(resolved.track.number, number);
+            assert_eq!(resolved.track.file, dir.join("album.flac"));
+        }
+        assert_eq!(audio_file(&entries[2]), dir.join("album.flac"));
+        assert_eq!(
+            audio_file(&dir.join("album.flac#track=2")),
+            dir.join("album.flac")
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_is_cue_file() {
+        assert!(is_cue_file(Path::new("album.CUE")));
+        assert!(!is_cue_file(Path::new("album.flac")));
+    }
+
+    #[test]
+    fn test_expand_flac_with_embedded_sheet() {
+        let dir = std::env::temp_dir().join("oneamp_cue_test_embedded");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut comments = Vec::new();
+        comments.extend_from_slice(&6u32.to_le_bytes());
+        comments.extend_from_slice(b"vendor");
+        comments.extend_from_slice(&2u32.to_le_bytes());
+        for comment in [
+            "TITLE=The Album".to_string(),
+            format!("cuesheet={}", SAMPLE_CUE),
+        ] {
+            comments.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            comments.extend_from_slice(comment.as_bytes());
+        }
+        let mut flac = b"fLaC".to_vec();
+        // STREAMINFO, then a picture to skip, then the comments
+        flac.extend_from_slice(&[0, 0, 0, 34]);
+        flac.extend_from_slice(&[0; 34]);
+        flac.extend_from_slice(&[6, 0, 0, 3, 1, 2, 3]);
+        flac.push(0x84);
+        flac.extend_from_slice(&(comments.len() as u32).to_be_bytes()[1..]);
+        flac.extend_from_slice(&comments);
+        let flac_path = dir.join("rip.flac");
+        std::fs::write(&flac_path, &flac).unwrap();
+
+        let entries = expand_playlist_entry(&flac_path);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2], dir.join("rip.flac#track=3"));
+        let resolved = resolve_virtual_track(&entries[1]).unwrap().unwrap();
+        assert_eq!(resolved.track.file, flac_path);
+        assert_eq!(resolved.track.end_secs, Some(465.0));
+
+        // Only FLAC files are looked into
+        let other = dir.join("rip.ogg");
+        std::fs::write(&other, &flac).unwrap();
+        assert_eq!(expand_playlist_entry(&other), vec![other.clone()]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -273,6 +273,66 @@ impl Default for Equalizer {
     }
 }
 
+/// Built-in presets (gains in dB for the 10 bands), after Winamp's
+pub const PRESETS: [(&str, [f32; 10]); 13] = [
+    ("Flat", [0.0; 10]),
+    (
+        "Classical",
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -7.2, -7.2, -7.2, -9.6],
+    ),
+    ("Club", [0.0, 0.0, 8.0, 5.6, 5.6, 5.6, 3.2, 0.0, 0.0, 0.0]),
+    (
+        "Dance",
+        [9.6, 7.2, 2.4, 0.0, 0.0, -5.6, -7.2, -7.2, 0.0, 0.0],
+    ),
+    (
+        "Full Bass",
+        [9.6, 9.6, 9.6, 5.6, 1.6, -4.0, -8.0, -10.4, -11.2, -11.2],
+    ),
+    (
+        "Large Hall",
+        [10.4, 10.4, 5.6, 5.6, 0.0, -4.8, -4.8, -4.8, 0.0, 0.0],
+    ),
+    ("Live", [-4.8, 0.0, 4.0, 5.6, 5.6, 5.6, 4.0, 2.4, 2.4, 2.4]),
+    ("Party", [7.2, 7.2, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 7.2, 7.2]),
+    (
+        "Pop",
+        [-1.6, 4.8, 7.2, 8.0, 5.6, 0.0, -2.4, -2.4, -1.6, -1.6],
+    ),
+    (
+        "Reggae",
+        [0.0, 0.0, 0.0, -5.6, 0.0, 6.4, 6.4, 0.0, 0.0, 0.0],
+    ),
+    (
+        "Rock",
+        [8.0, 4.8, -5.6, -8.0, -3.2, 4.0, 8.8, 11.2, 11.2, 11.2],
+    ),
+    (
+        "Soft",
+        [4.8, 1.6, 0.0, -2.4, 0.0, 4.0, 8.0, 9.6, 11.2, 12.0],
+    ),
+    (
+        "Techno",
+        [8.0, 5.6, 0.0, -5.6, -4.8, 0.0, 8.0, 9.6, 9.6, 8.8],
+    ),
+];
+
+/// Look up a preset by name, ignoring case, spaces, `-` and `_`
+/// (`full-bass` finds "Full Bass")
+pub fn preset(name: &str) -> Option<[f32; 10]> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let name = normalize(name);
+    PRESETS
+        .iter()
+        .find(|(preset, _)| normalize(preset) == name)
+        .map(|(_, gains)| *gains)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -303,4 +363,15 @@ mod tests {
         eq.set_band_gain(1, -20.0); // Should clamp to -12.0
         assert_eq!(eq.get_band_gain(1), -12.0);
     }
+
+    #[test]
+    fn test_preset_lookup() {
+        assert_eq!(preset("flat"), Some([0.0; 10]));
+        assert_eq!(preset("full-bass"), preset("Full Bass"));
+        assert!(preset("full_bass").is_some());
+        assert!(preset("polka").is_none());
+        for (_, gains) in PRESETS {
+            assert!(gains.iter().all(|g| (-12.0..=12.0).contains(g)));
+        }
+    }
 }
@@ -0,0 +1,64 @@
+// Dithering
+// Converts float samples to integers of the output bit depth with TPDF
+// (triangular) dither, which turns truncation distortion into a constant,
+// signal-independent noise floor.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Float to integer sample conversion at a fixed bit depth
+pub struct Quantizer {
+    /// Largest positive value plus one (2^(bits - 1))
+    scale: f32,
+    rng: StdRng,
+    dither: bool,
+}
+
+impl Quantizer {
+    pub fn new(bits_per_sample: u16, dither: bool) -> Self {
+        Self {
+            scale: (1u32 << (bits_per_sample - 1)) as f32,
+            rng: StdRng::from_entropy(),
+            dither,
+        }
+    }
+
+    /// Quantize one sample in -1..1; out of range values are clipped
+    pub fn quantize(&mut self, sample: f32) -> i32 {
+        let mut value = sample * self.scale;
+        if self.dither {
+            // Difference of two uniform values: triangular over ±1 LSB
+            value += self.rng.gen::<f32>() - self.rng.gen::<f32>();
+        }
+        value.round().clamp(-self.scale, self.scale - 1.0) as i32
+    }
+
+    pub fn quantize_all(&mut self, samples: &[f32]) -> Vec<i32> {
+        samples
+            .iter()
+            .map(|&sample| self.quantize(sample))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize() {
+        let mut quantizer = Quantizer::new(16, false);
+        assert_eq!(quantizer.quantize(0.0), 0);
+        assert_eq!(quantizer.quantize(0.5), 16384);
+        assert_eq!(quantizer.quantize(2.0), 32767);
+        assert_eq!(quantizer.quantize(-2.0), -32768);
+
+        // Dither stays within one step and averages out
+        let mut quantizer = Quantizer::new(16, true);
+        let target = 100.25 / 32768.0;
+        let values: Vec<i32> = (0..10000).map(|_| quantizer.quantize(target)).collect();
+        assert!(values.iter().all(|v| (99..=102).contains(v)));
+        let mean = values.iter().sum::<i32>() as f32 / values.len() as f32;
+        assert!((mean - 100.25).abs() < 0.05, "mean {}", mean);
+    }
+}
@@ -0,0 +1,489 @@
+// FLAC Encoder
+// A compact pure-Rust FLAC writer: fixed-size blocks, fixed polynomial
+// predictors (orders 0-4) with partitioned Rice coding, and stereo
+// decorrelation. Files are somewhat larger than LPC encoders produce, but
+// lossless and readable everywhere. Tags and pictures are written as
+// VORBIS_COMMENT and PICTURE metadata blocks.
+
+use super::tags::Tags;
+use super::{Encoder, StreamFormat};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Frames (inter-channel samples) per FLAC block
+const BLOCK_SIZE: usize = 4096;
+const MAX_FIXED_ORDER: usize = 4;
+const MAX_PARTITION_ORDER: u32 = 8;
+/// Metadata block lengths are 24-bit
+const MAX_BLOCK_LEN: usize = (1 << 24) - 1;
+
+const BLOCK_STREAMINFO: u8 = 0;
+const BLOCK_VORBIS_COMMENT: u8 = 4;
+const BLOCK_PICTURE: u8 = 6;
+
+pub(super) struct FlacWriter {
+    file: BufWriter<File>,
+    format: StreamFormat,
+    /// Interleaved samples not yet encoded
+    pending: Vec<i32>,
+    frame_number: u64,
+    total_frames: u64,
+    min_frame_size: u32,
+    max_frame_size: u32,
+}
+
+impl FlacWriter {
+    pub(super) fn create(path: &Path, format: &StreamFormat, tags: &Tags) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            format: *format,
+            pending: Vec::new(),
+            frame_number: 0,
+            total_frames: 0,
+            min_frame_size: u32::MAX,
+            max_frame_size: 0,
+        };
+
+        let mut blocks = vec![(BLOCK_VORBIS_COMMENT, vorbis_comment(tags))];
+        for picture in &tags.pictures {
+            let mut block = Vec::new();
+            block.extend_from_slice(&picture.picture_type.to_be_bytes());
+            block.extend_from_slice(&(picture.media_type.len() as u32).to_be_bytes());
+            block.extend_from_slice(picture.media_type.as_bytes());
+            block.extend_from_slice(&0u32.to_be_bytes()); // description
+            block.extend_from_slice(&picture.width.to_be_bytes());
+            block.extend_from_slice(&picture.height.to_be_bytes());
+            block.extend_from_slice(&0u32.to_be_bytes()); // color depth unknown
+            block.extend_from_slice(&0u32.to_be_bytes()); // not indexed
+            block.extend_from_slice(&(picture.data.len() as u32).to_be_bytes());
+            block.extend_from_slice(&picture.data);
+            blocks.push((BLOCK_PICTURE, block));
+        }
+        // Oversized pictures can't be stored
+        blocks.retain(|(_, block)| block.len() <= MAX_BLOCK_LEN);
+
+        writer.file.write_all(b"fLaC")?;
+        let streaminfo = writer.streaminfo();
+        writer.write_block(BLOCK_STREAMINFO, &streaminfo, false)?;
+        let count = blocks.len();
+        for (i, (kind, block)) in blocks.iter().enumerate() {
+            writer.write_block(*kind, block, i + 1 == count)?;
+        }
+        Ok(writer)
+    }
+
+    fn write_block(&mut self, kind: u8, data: &[u8], last: bool) -> Result<()> {
+        let len = (data.len() as u32).to_be_bytes();
+        self.file
+            .write_all(&[kind | if last { 0x80 } else { 0 }, len[1], len[2], len[3]])?;
+        self.file.write_all(data)?;
+        Ok(())
+    }
+
+    /// STREAMINFO for what has been written so far (MD5 left unset)
+    fn streaminfo(&self) -> Vec<u8> {
+        let block_size = if self.total_frames > 0 && self.total_frames < BLOCK_SIZE as u64 {
+            self.total_frames
+        } else {
+            BLOCK_SIZE as u64
+        };
+        let mut bits = BitWriter::default();
+        bits.write(block_size, 16);
+        bits.write(block_size, 16);
+        bits.write(
+            if self.max_frame_size > 0 {
+                self.min_frame_size as u64
+            } else {
+                0
+            },
+            24,
+        );
+        bits.write(self.max_frame_size as u64, 24);
+        bits.write(self.format.sample_rate as u64, 20);
+        bits.write(self.format.channels as u64 - 1, 3);
+        bits.write(self.format.bits_per_sample as u64 - 1, 5);
+        bits.write(self.total_frames >> 32, 4);
+        bits.write(self.total_frames & 0xffff_ffff, 32);
+        let mut data = bits.into_bytes();
+        data.extend_from_slice(&[0; 16]);
+        data
+    }
+
+    fn encode_block(&mut self, samples: &[i32]) -> Result<()> {
+        let channels = self.format.channels as usize;
+        let bps = self.format.bits_per_sample as u32;
+        let frames = samples.len() / channels;
+        let channel =
+            |c: usize| -> Vec<i32> { samples.iter().skip(c).step_by(channels).copied().collect() };
+
+        // Channel assignment code and subframes
+        let (assignment, subframes) = if channels == 2 {
+            let left = channel(0);
+            let right = channel(1);
+            let side: Vec<i32> = left.iter().zip(&right).map(|(l, r)| l - r).collect();
+            let mid: Vec<i32> = left
+                .iter()
+                .zip(&right)
+                .map(|(&l, &r)| ((l as i64 + r as i64) >> 1) as i32)
+                .collect();
+            let left = encode_subframe(&left, bps);
+            let right = encode_subframe(&right, bps);
+            let side = encode_subframe(&side, bps + 1);
+            let mid = encode_subframe(&mid, bps);
+            [
+                (1, left.bits() + right.bits(), [&left, &right]),
+                (8, left.bits() + side.bits(), [&left, &side]),
+                (9, side.bits() + right.bits(), [&side, &right]),
+                (10, mid.bits() + side.bits(), [&mid, &side]),
+            ]
+            .into_iter()
+            .min_by_key(|(_, size, _)| *size)
+            .map(|(code, _, pair)| (code, pair.map(BitWriter::clone).to_vec()))
+            .expect("four candidates")
+        } else {
+            let subframes = (0..channels)
+                .map(|c| encode_subframe(&channel(c), bps))
+                .collect();
+            (channels as u64 - 1, subframes)
+        };
+
+        let mut frame = BitWriter::default();
+        frame.write(0b11_1111_1111_1110, 14); // sync code
+        frame.write(0, 1); // reserved
+        frame.write(0, 1); // fixed block size
+        frame.write(7, 4); // block size in a 16-bit field at the end of the header
+        frame.write(0, 4); // sample rate from STREAMINFO
+        frame.write(assignment, 4);
+        frame.write(if bps == 24 { 6 } else { 4 }, 3);
+        frame.write(0, 1); // reserved
+        write_utf8_number(&mut frame, self.frame_number);
+        frame.write(frames as u64 - 1, 16);
+        let crc = crc8(frame.bytes());
+        frame.write(crc as u64, 8);
+
+        for subframe in &subframes {
+            frame.append(subframe);
+        }
+        frame.align();
+        let crc = crc16(frame.bytes());
+        frame.write(crc as u64, 16);
+
+        let bytes = frame.into_bytes();
+        self.min_frame_size = self.min_frame_size.min(bytes.len() as u32);
+        self.max_frame_size = self.max_frame_size.max(bytes.len() as u32);
+        self.file.write_all(&bytes)?;
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        Ok(())
+    }
+}
+
+impl Encoder for FlacWriter {
+    fn write(&mut self, samples: &[i32]) -> Result<()> {
+        self.pending.extend_from_slice(samples);
+        let block_len = BLOCK_SIZE * self.format.channels as usize;
+        let mut start = 0;
+        while self.pending.len() - start >= block_len {
+            let block = self.pending[start..start + block_len].to_vec();
+            self.encode_block(&block)?;
+            start += block_len;
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.encode_block(&block)?;
+        }
+        // Rewrite STREAMINFO now that lengths and frame sizes are known
+        let streaminfo = self.streaminfo();
+        self.file.seek(SeekFrom::Start(8))?;
+        self.file.write_all(&streaminfo)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// VORBIS_COMMENT body: little-endian lengths, `NAME=value` entries
+fn vorbis_comment(tags: &Tags) -> Vec<u8> {
+    let vendor = format!("OneAmp {}", env!("CARGO_PKG_VERSION"));
+    let mut block = Vec::new();
+    block.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    block.extend_from_slice(vendor.as_bytes());
+    block.extend_from_slice(&(tags.fields.len() as u32).to_le_bytes());
+    for (name, value) in &tags.fields {
+        let entry = format!("{}={}", name, value);
+        block.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        block.extend_from_slice(entry.as_bytes());
+    }
+    block
+}
+
+/// Encode one channel with the smallest of the constant, verbatim and fixed
+/// predictor subframe types
+fn encode_subframe(samples: &[i32], bps: u32) -> BitWriter {
+    let mut out = BitWriter::default();
+
+    if samples.iter().all(|&sample| sample == samples[0]) {
+        out.write(0, 8); // constant
+        out.write_signed(samples[0] as i64, bps);
+        return out;
+    }
+
+    let mut best: Option<(usize, Vec<i32>, RiceCoding)> = None;
+    let mut best_bits = samples.len() as u64 * bps as u64; // verbatim
+    for order in 0..=MAX_FIXED_ORDER.min(samples.len() - 1) {
+        let residuals = fixed_residuals(samples, order);
+        let Some(coding) = RiceCoding::best(&residuals, samples.len(), order) else {
+            continue;
+        };
+        let bits = order as u64 * bps as u64 + coding.bits;
+        if bits < best_bits {
+            best_bits = bits;
+            best = Some((order, residuals, coding));
+        }
+    }
+
+    match best {
+        None => {
+            out.write(0b0000_0010, 8); // verbatim
+            for &sample in samples {
+                out.write_signed(sample as i64, bps);
+            }
+        }
+        Some((order, residuals, coding)) => {
+            out.write((0b00_1000 | order as u64) << 1, 8); // fixed, order
+            for &sample in &samples[..order] {
+                out.write_signed(sample as i64, bps);
+            }
+            coding.write(&mut out, &residuals, samples.len(), order);
+        }
+    }
+    out
+}
+
+/// Prediction errors of the fixed polynomial predictor of `order`, for the
+/// samples after the warm-up
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+    let s = |i: usize| samples[i] as i64;
+    (order..samples.len())
+        .map(|i| {
+            (match order {
+                0 => s(i),
+                1 => s(i) - s(i - 1),
+                2 => s(i) - 2 * s(i - 1) + s(i - 2),
+                3 => s(i) - 3 * s(i - 1) + 3 * s(i - 2) - s(i - 3),
+                _ => s(i) - 4 * s(i - 1) + 6 * s(i - 2) - 4 * s(i - 3) + s(i - 4),
+            }) as i32
+        })
+        .collect()
+}
+
+/// Partitioned Rice parameters for a residual
+struct RiceCoding {
+    partition_order: u32,
+    parameters: Vec<u32>,
+    /// Size of the residual section in bits
+    bits: u64,
+}
+
+impl RiceCoding {
+    /// Try every partition order and pick the smallest coding
+    fn best(residuals: &[i32], block_size: usize, order: usize) -> Option<Self> {
+        let folded: Vec<u64> = residuals.iter().map(|&r| zigzag(r)).collect();
+        let mut best: Option<Self> = None;
+
+        for partition_order in 0..=MAX_PARTITION_ORDER {
+            let partitions = 1usize << partition_order;
+            if !block_size.is_multiple_of(partitions) || block_size / partitions <= order {
+                break;
+            }
+            let mut parameters = Vec::with_capacity(partitions);
+            let mut bits = 6u64; // coding method and partition order
+            let mut start = 0;
+            for p in 0..partitions {
+                let len = block_size / partitions - if p == 0 { order } else { 0 };
+                let (parameter, size) = best_parameter(&folded[start..start + len]);
+                parameters.push(parameter);
+                bits += size;
+                start += len;
+            }
+            // Parameters above 14 need the 5-bit parameter coding method
+            let parameter_bits = if parameters.iter().any(|&k| k > 14) {
+                5
+            } else {
+                4
+            };
+            bits += parameter_bits * partitions as u64;
+
+            if best.as_ref().is_none_or(|best| bits < best.bits) {
+                best = Some(Self {
+                    partition_order,
+                    parameters,
+                    bits,
+                });
+            }
+        }
+        best
+    }
+
+    fn write(&self, out: &mut BitWriter, residuals: &[i32], block_size: usize, order: usize) {
+        let wide = self.parameters.iter().any(|&k| k > 14);
+        out.write(wide as u64, 2);
+        out.write(self.partition_order as u64, 4);
+        let partitions = 1usize << self.partition_order;
+        let mut start = 0;
+        for (p, &parameter) in self.parameters.iter().enumerate() {
+            out.write(parameter as u64, if wide { 5 } else { 4 });
+            let len = block_size / partitions - if p == 0 { order } else { 0 };
+            for &residual in &residuals[start..start + len] {
+                let value = zigzag(residual);
+                out.write_unary(value >> parameter);
+                out.write(value & ((1 << parameter) - 1), parameter);
+            }
+            start += len;
+        }
+    }
+}
+
+/// Smallest Rice parameter for a partition and its size in bits
+fn best_parameter(values: &[u64]) -> (u32, u64) {
+    let size = |k: u32| values.iter().map(|v| (v >> k) + 1 + k as u64).sum::<u64>();
+    let mean = values.iter().sum::<u64>() / values.len().max(1) as u64;
+    let estimate = if mean > 0 {
+        63 - mean.leading_zeros()
+    } else {
+        0
+    };
+    (estimate.saturating_sub(1)..=(estimate + 1).min(30))
+        .map(|k| (k, size(k)))
+        .min_by_key(|(_, size)| *size)
+        .expect("non-empty range")
+}
+
+/// Signed to unsigned folding used by Rice codes
+fn zigzag(value: i32) -> u64 {
+    (((value as i64) << 1) ^ ((value as i64) >> 63)) as u64
+}
+
+/// Frame numbers use the UTF-8 byte pattern, extended to 36 bits
+fn write_utf8_number(out: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        out.write(value, 8);
+        return;
+    }
+    let continuation = match value {
+        0x80..=0x7ff => 1,
+        0x800..=0xffff => 2,
+        0x1_0000..=0x1f_ffff => 3,
+        0x20_0000..=0x3ff_ffff => 4,
+        0x400_0000..=0x7fff_ffff => 5,
+        _ => 6,
+    };
+    let prefix = !(0xffu8 >> (continuation + 1)) as u64;
+    out.write(prefix | (value >> (6 * continuation)), 8);
+    for i in (0..continuation).rev() {
+        out.write(0x80 | ((value >> (6 * i)) & 0x3f), 8);
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// MSB-first bit packing
+#[derive(Clone, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits not yet in `bytes`, right-aligned
+    pending: u64,
+    pending_bits: u32,
+}
+
+impl BitWriter {
+    /// Write the low `bits` bits of `value` (at most 32)
+    fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return;
+        }
+        self.pending = (self.pending << bits) | (value & ((1 << bits) - 1));
+        self.pending_bits += bits;
+        while self.pending_bits >= 8 {
+            self.pending_bits -= 8;
+            self.bytes.push((self.pending >> self.pending_bits) as u8);
+        }
+        self.pending &= (1 << self.pending_bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64, bits);
+    }
+
+    /// `value` zeros and a one
+    fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+        self.write(1, value as u32 + 1);
+    }
+
+    fn append(&mut self, other: &BitWriter) {
+        for &byte in &other.bytes {
+            self.write(byte as u64, 8);
+        }
+        self.write(other.pending, other.pending_bits);
+    }
+
+    /// Pad with zeros to a byte boundary
+    fn align(&mut self) {
+        if self.pending_bits > 0 {
+            self.write(0, 8 - self.pending_bits);
+        }
+    }
+
+    fn bits(&self) -> u64 {
+        self.bytes.len() as u64 * 8 + self.pending_bits as u64
+    }
+
+    /// Complete bytes written so far
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
@@ -0,0 +1,416 @@
+// Offline Export
+// Decodes a track with the playback engine's decoder and equalizer, runs it
+// through DSP plugins and the resampler, dithers it to the output bit depth
+// and writes WAV or FLAC with the source's tags and cover art. Exports run
+// as fast as the CPU allows, so several files can be exported in parallel.
+//
+// MP3 and Opus are not offered: there is no pure-Rust encoder for them yet.
+
+pub mod dither;
+mod flac;
+pub mod resample;
+pub mod tags;
+mod wav;
+
+use crate::audio_capture::AudioCaptureBuffer;
+use crate::cue;
+use crate::equalizer::Equalizer;
+use crate::plugins::{AudioBuffer, DSPPlugin};
+use crate::symphonia_player::SymphoniaPlayer;
+use crate::TrackInfo;
+use anyhow::{bail, Context, Result};
+use dither::Quantizer;
+use resample::Resampler;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tags::Tags;
+
+/// Supported output bit depths
+pub const BIT_DEPTHS: [u16; 2] = [16, 24];
+
+/// Output file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    Wav,
+    #[default]
+    Flac,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Wav => "wav",
+            ExportFormat::Flac => "flac",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "wav" | "wave" => Ok(ExportFormat::Wav),
+            "flac" => Ok(ExportFormat::Flac),
+            "mp3" | "opus" => Err(format!(
+                "No {} encoder is available (expected wav or flac)",
+                s.to_uppercase()
+            )),
+            _ => Err(format!(
+                "Invalid export format '{}' (expected wav or flac)",
+                s
+            )),
+        }
+    }
+}
+
+/// What an export does to the audio
+#[derive(Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Output sample rate; `None` keeps the source rate
+    pub sample_rate: Option<u32>,
+    /// One of `BIT_DEPTHS`
+    pub bits_per_sample: u16,
+    /// TPDF dither when reducing to the output bit depth
+    pub dither: bool,
+    /// Equalizer band gains in dB, applied as during playback
+    pub equalizer: Option<[f32; 10]>,
+    /// DSP effects applied in order after the equalizer
+    pub dsp_plugins: Vec<Arc<dyn DSPPlugin>>,
+    /// Copy tags and embedded pictures from the source
+    pub copy_tags: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::default(),
+            sample_rate: None,
+            bits_per_sample: 16,
+            dither: true,
+            equalizer: None,
+            dsp_plugins: Vec::new(),
+            copy_tags: true,
+        }
+    }
+}
+
+/// Stream parameters of an output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+}
+
+/// Result of a finished export
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSummary {
+    pub output: PathBuf,
+    pub format: StreamFormat,
+    /// Frames written (samples per channel)
+    pub frames: u64,
+}
+
+impl ExportSummary {
+    pub fn duration_secs(&self) -> f64 {
+        self.frames as f64 / self.format.sample_rate as f64
+    }
+}
+
+/// Destination of interleaved integer samples
+trait Encoder {
+    fn write(&mut self, samples: &[i32]) -> Result<()>;
+    /// Complete headers and flush the file
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Export `input` (a file or a CUE virtual track) to `output`.
+///
+/// The file is written next to `output` with a `.part` suffix and renamed
+/// when complete, so an interrupted export never leaves a truncated file.
+pub fn export(input: &Path, output: &Path, options: &ExportOptions) -> Result<ExportSummary> {
+    if !BIT_DEPTHS.contains(&options.bits_per_sample) {
+        bail!(
+            "Unsupported bit depth {} (expected 16 or 24)",
+            options.bits_per_sample
+        );
+    }
+
+    let equalizer = Arc::new(Mutex::new(Equalizer::default()));
+    if let Some(gains) = options.equalizer {
+        let mut eq = equalizer.lock().unwrap();
+        eq.set_all_gains(&gains);
+        eq.set_enabled(true);
+    }
+    let capture = Arc::new(Mutex::new(AudioCaptureBuffer::new(0)));
+    let mut player = SymphoniaPlayer::load(input, equalizer, capture)?;
+    let source_rate = player.sample_rate();
+    let channels = player.channels();
+
+    let mut processors = Vec::with_capacity(options.dsp_plugins.len());
+    for plugin in &options.dsp_plugins {
+        let processor = plugin
+            .create_processor()
+            .with_context(|| format!("Failed to start DSP plugin {}", plugin.name()))?;
+        processors.push(processor);
+    }
+
+    let format = StreamFormat {
+        sample_rate: options.sample_rate.unwrap_or(source_rate),
+        channels,
+        bits_per_sample: options.bits_per_sample,
+    };
+    if format.sample_rate == 0 || format.sample_rate > 655_350 {
+        bail!("Unsupported sample rate {}", format.sample_rate);
+    }
+
+    let tags = if options.copy_tags {
+        read_tags(input)?
+    } else {
+        Tags::default()
+    };
+
+    let mut part = output.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let mut encoder: Box<dyn Encoder> = match options.format {
+        ExportFormat::Wav => Box::new(wav::WavWriter::create(&part, &format, &tags)?),
+        ExportFormat::Flac => Box::new(flac::FlacWriter::create(&part, &format, &tags)?),
+    };
+
+    let result = (|| -> Result<u64> {
+        let mut resampler = Resampler::new(source_rate, format.sample_rate, channels);
+        let mut quantizer = Quantizer::new(format.bits_per_sample, options.dither);
+        let mut frames = 0u64;
+        let mut write = |samples: &[f32], encoder: &mut Box<dyn Encoder>| -> Result<()> {
+            frames += (samples.len() / channels as usize) as u64;
+            encoder.write(&quantizer.quantize_all(samples))
+        };
+
+        while let Some(samples) = player.decode_next()? {
+            if samples.is_empty() {
+                continue;
+            }
+            let mut buffer = AudioBuffer {
+                samples,
+                sample_rate: source_rate,
+                channels,
+            };
+            for processor in &mut processors {
+                processor.process(&mut buffer)?;
+            }
+            write(&resampler.process(&buffer.samples), &mut encoder)?;
+        }
+        write(&resampler.flush(), &mut encoder)?;
+        encoder.finish()?;
+        Ok(frames)
+    })();
+
+    let frames = match result {
+        Ok(frames) => frames,
+        Err(err) => {
+            let _ = std::fs::remove_file(&part);
+            return Err(err);
+        }
+    };
+    std::fs::rename(&part, output)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    Ok(ExportSummary {
+        output: output.to_path_buf(),
+        format,
+        frames,
+    })
+}
+
+/// Tags of the source file; virtual tracks take their title, artist, album
+/// and number from the CUE sheet
+fn read_tags(input: &Path) -> Result<Tags> {
+    let Some(resolved) = cue::resolve_virtual_track(input)? else {
+        return Tags::read(input);
+    };
+    let mut tags = Tags::read(&resolved.track.file)?;
+    let info = TrackInfo::from_file(input)?;
+    for (field, value) in [
+        ("TITLE", info.title),
+        ("ARTIST", info.artist),
+        ("ALBUM", info.album),
+        ("TRACKNUMBER", Some(resolved.track.number.to_string())),
+        ("TRACKTOTAL", Some(resolved.sheet.tracks.len().to_string())),
+    ] {
+        if let Some(value) = value {
+            tags.set(field, &value);
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::wav;
+
+    /// Decode a file to interleaved integers of `bits` bits
+    fn decode(path: &Path, bits: u16) -> (Vec<i32>, u32, u16) {
+        let mut player = SymphoniaPlayer::load(
+            path,
+            Arc::new(Mutex::new(Equalizer::default())),
+            Arc::new(Mutex::new(AudioCaptureBuffer::new(0))),
+        )
+        .unwrap();
+        let mut quantizer = Quantizer::new(bits, false);
+        let mut samples = Vec::new();
+        while let Some(chunk) = player.decode_next().unwrap() {
+            samples.extend(quantizer.quantize_all(&chunk));
+        }
+        (samples, player.sample_rate(), player.channels())
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oneamp_export_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_flac_is_lossless() {
+        let dir = test_dir("lossless");
+        // Stereo tone plus noise, longer than one block and not a multiple of it
+        let mut state = 1u32;
+        let samples: Vec<i16> = (0..10_000)
+            .flat_map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = (state >> 16) as i16 as i32 / 64;
+                let tone = ((i as f32 * 0.05).sin() * 12000.0) as i32;
+                [(tone + noise) as i16, (tone / 2 - noise) as i16]
+            })
+            .collect();
+        let input = dir.join("in.wav");
+        std::fs::write(&input, wav(44100, 2, &samples)).unwrap();
+
+        let output = dir.join("out.flac");
+        let options = ExportOptions {
+            dither: false,
+            ..Default::default()
+        };
+        let summary = export(&input, &output, &options).unwrap();
+        assert_eq!(summary.frames, 10_000);
+        assert!(!dir.join("out.flac.part").exists());
+        assert!(std::fs::metadata(&output).unwrap().len() < 40_000);
+
+        let (decoded, rate, channels) = decode(&output, 16);
+        assert_eq!((rate, channels), (44100, 2));
+        let expected: Vec<i32> = samples.iter().map(|&s| s as i32).collect();
+        assert_eq!(decoded, expected);
+
+        // 24-bit mono, including full-scale noise that only fits verbatim
+        let samples: Vec<i32> = (0..5000)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i < 2500 {
+                    (state as i32) >> 8
+                } else {
+                    ((i as f32 * 0.01).sin() * 4_000_000.0) as i32
+                }
+            })
+            .collect();
+        let path = dir.join("24.flac");
+        let format = StreamFormat {
+            sample_rate: 96000,
+            channels: 1,
+            bits_per_sample: 24,
+        };
+        let mut writer: Box<dyn Encoder> =
+            Box::new(flac::FlacWriter::create(&path, &format, &Tags::default()).unwrap());
+        writer.write(&samples).unwrap();
+        writer.finish().unwrap();
+        let (decoded, rate, channels) = decode(&path, 24);
+        assert_eq!((rate, channels), (96000, 1));
+        assert_eq!(decoded, samples);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_with_eq_resampling_and_tags() {
+        let dir = test_dir("tags");
+        let samples: Vec<i32> = (0..44100)
+            .flat_map(|i| {
+                let s = ((i as f32 * 0.1).sin() * 8000.0) as i32;
+                [s, -s]
+            })
+            .collect();
+        let tags = Tags {
+            fields: vec![
+                ("TITLE".into(), "Song".into()),
+                ("ARTIST".into(), "Band".into()),
+                ("TRACKNUMBER".into(), "3".into()),
+            ],
+            pictures: vec![tags::Picture {
+                picture_type: 3,
+                media_type: "image/png".into(),
+                width: 1,
+                height: 1,
+                data: vec![0x89, b'P', b'N', b'G'],
+            }],
+        };
+        let input = dir.join("tagged.flac");
+        let format = StreamFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 16,
+        };
+        let mut writer: Box<dyn Encoder> =
+            Box::new(flac::FlacWriter::create(&input, &format, &tags).unwrap());
+        writer.write(&samples).unwrap();
+        writer.finish().unwrap();
+
+        let read = Tags::read(&input).unwrap();
+        assert_eq!(read.get("TITLE"), Some("Song"));
+        assert_eq!(read.pictures, tags.pictures);
+
+        let options = ExportOptions {
+            format: ExportFormat::Wav,
+            sample_rate: Some(48000),
+            bits_per_sample: 24,
+            equalizer: crate::equalizer::preset("rock"),
+            ..Default::default()
+        };
+        let output = dir.join("out.wav");
+        let summary = export(&input, &output, &options).unwrap();
+        assert_eq!(summary.frames, 48000);
+        assert_eq!(summary.format.bits_per_sample, 24);
+
+        let info = TrackInfo::from_file(&output).unwrap();
+        assert_eq!(info.sample_rate, Some(48000));
+        assert_eq!(info.channels, Some(2));
+        assert_eq!(info.title.as_deref(), Some("Song"));
+        let read = Tags::read(&output).unwrap();
+        assert_eq!(read.get("ARTIST"), Some("Band"));
+        assert_eq!(read.get("TRACKNUMBER"), Some("3"));
+
+        // Without copying, nothing is carried over
+        let options = ExportOptions {
+            copy_tags: false,
+            ..Default::default()
+        };
+        export(&input, &dir.join("bare.flac"), &options).unwrap();
+        assert!(Tags::read(&dir.join("bare.flac"))
+            .unwrap()
+            .fields
+            .is_empty());
+
+        assert!("mp3".parse::<ExportFormat>().is_err());
+        let options = ExportOptions {
+            bits_per_sample: 12,
+            ..Default::default()
+        };
+        assert!(export(&input, &dir.join("x.flac"), &options).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,186 @@
+// Sample Rate Conversion
+// Band-limited (windowed sinc) interpolation of interleaved samples, streamed
+// chunk by chunk. When downsampling the cutoff follows the output Nyquist
+// frequency so nothing aliases.
+
+/// Zero crossings of the sinc on each side of the center
+const ZERO_CROSSINGS: usize = 16;
+/// Kernel table entries per zero crossing
+const TABLE_RESOLUTION: usize = 512;
+
+/// Converts interleaved samples from one rate to another
+pub struct Resampler {
+    channels: usize,
+    /// Input frames per output frame
+    step: f64,
+    /// Cutoff relative to the input Nyquist frequency (1.0 when upsampling)
+    cutoff: f64,
+    /// Kernel half width in input frames
+    half_width: f64,
+    /// Windowed sinc sampled from 0 to `ZERO_CROSSINGS`
+    table: Vec<f32>,
+    /// Input frames still needed, interleaved
+    history: Vec<f32>,
+    /// Position of the next output frame in `history`, in input frames
+    position: f64,
+    input_frames: u64,
+    output_frames: u64,
+    to_rate: u32,
+    from_rate: u32,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        let step = from_rate as f64 / to_rate as f64;
+        let cutoff = (1.0 / step).min(1.0);
+        let half_width = ZERO_CROSSINGS as f64 / cutoff;
+
+        let table = (0..=ZERO_CROSSINGS * TABLE_RESOLUTION)
+            .map(|i| {
+                let x = i as f64 / TABLE_RESOLUTION as f64;
+                let sinc = if i == 0 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+                };
+                // Blackman window over [-ZERO_CROSSINGS, ZERO_CROSSINGS]
+                let phase = std::f64::consts::PI * (x / ZERO_CROSSINGS as f64 + 1.0);
+                let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                (sinc * window) as f32
+            })
+            .collect();
+
+        // Start with silence so the first output frame sees a full kernel
+        let lead_in = half_width.ceil() as usize;
+        Self {
+            channels,
+            step,
+            cutoff,
+            half_width,
+            table,
+            history: vec![0.0; lead_in * channels],
+            position: lead_in as f64,
+            input_frames: 0,
+            output_frames: 0,
+            to_rate,
+            from_rate,
+        }
+    }
+
+    /// Whether the rates differ at all
+    pub fn is_active(&self) -> bool {
+        self.from_rate != self.to_rate
+    }
+
+    /// Resample a chunk; output lags the input by the kernel half width
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        if !self.is_active() {
+            return samples.to_vec();
+        }
+        self.input_frames += (samples.len() / self.channels) as u64;
+        self.history.extend_from_slice(samples);
+        self.drain(false)
+    }
+
+    /// Output the frames still held back at the end of the stream
+    pub fn flush(&mut self) -> Vec<f32> {
+        if !self.is_active() {
+            return Vec::new();
+        }
+        let tail = self.half_width.ceil() as usize + 1;
+        self.history
+            .extend(std::iter::repeat_n(0.0, tail * self.channels));
+        self.drain(true)
+    }
+
+    fn drain(&mut self, flushing: bool) -> Vec<f32> {
+        let available = self.history.len() / self.channels;
+        // Output length the input length calls for
+        let expected = (self.input_frames as f64 / self.step).round() as u64;
+        let mut output = Vec::new();
+
+        while self.position + self.half_width < available as f64 {
+            if flushing && self.output_frames >= expected {
+                break;
+            }
+            let first = (self.position - self.half_width).floor().max(0.0) as usize;
+            let last = ((self.position + self.half_width).ceil() as usize).min(available - 1);
+            for channel in 0..self.channels {
+                let mut sum = 0.0f32;
+                for frame in first..=last {
+                    let weight = self.kernel(self.position - frame as f64);
+                    sum += self.history[frame * self.channels + channel] * weight;
+                }
+                output.push(sum);
+            }
+            self.output_frames += 1;
+            self.position += self.step;
+        }
+
+        // Forget input the kernel won't reach again
+        let consumed = ((self.position - self.half_width).floor().max(0.0) as usize).min(available);
+        self.history.drain(..consumed * self.channels);
+        self.position -= consumed as f64;
+        output
+    }
+
+    /// Kernel weight at a distance of `t` input frames
+    fn kernel(&self, t: f64) -> f32 {
+        let x = (t * self.cutoff).abs() * TABLE_RESOLUTION as f64;
+        let index = x as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let frac = (x - index as f64) as f32;
+        let value = self.table[index] + (self.table[index + 1] - self.table[index]) * frac;
+        value * self.cutoff as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, frequency: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_sine() {
+        let input = sine(44100, 1000.0, 44100);
+        let mut resampler = Resampler::new(44100, 48000, 1);
+        let mut output = Vec::new();
+        for chunk in input.chunks(1000) {
+            output.extend(resampler.process(chunk));
+        }
+        output.extend(resampler.flush());
+        assert_eq!(output.len(), 48000);
+
+        // Away from the edges the output is the same sine at the new rate
+        let expected = sine(48000, 1000.0, 48000);
+        let error = output[1000..47000]
+            .iter()
+            .zip(&expected[1000..47000])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(error < 0.01, "max error {}", error);
+    }
+
+    #[test]
+    fn test_downsample_removes_aliases() {
+        // 30 kHz is above the 24 kHz output Nyquist frequency
+        let input: Vec<f32> = sine(96000, 30000.0, 9600)
+            .into_iter()
+            .flat_map(|s| [s, s])
+            .collect();
+        let mut resampler = Resampler::new(96000, 48000, 2);
+        let mut output = resampler.process(&input);
+        output.extend(resampler.flush());
+        assert_eq!(output.len(), 4800 * 2);
+        let peak = output[400..9200].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(peak < 0.01, "alias peak {}", peak);
+    }
+}
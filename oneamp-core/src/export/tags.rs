@@ -0,0 +1,232 @@
+// Tag Copy-Over
+// Reads the tags and embedded pictures of a source file into Vorbis comment
+// style fields, which the WAV and FLAC writers translate to their formats.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+/// Vorbis comment field names of the standard tags that are copied.
+/// ReplayGain values are left out: they no longer match after the EQ.
+const FIELD_NAMES: [(StandardTagKey, &str); 58] = [
+    (StandardTagKey::AcoustidFingerprint, "ACOUSTID_FINGERPRINT"),
+    (StandardTagKey::AcoustidId, "ACOUSTID_ID"),
+    (StandardTagKey::Album, "ALBUM"),
+    (StandardTagKey::AlbumArtist, "ALBUMARTIST"),
+    (StandardTagKey::Arranger, "ARRANGER"),
+    (StandardTagKey::Artist, "ARTIST"),
+    (StandardTagKey::Bpm, "BPM"),
+    (StandardTagKey::Comment, "COMMENT"),
+    (StandardTagKey::Compilation, "COMPILATION"),
+    (StandardTagKey::Composer, "COMPOSER"),
+    (StandardTagKey::Conductor, "CONDUCTOR"),
+    (StandardTagKey::ContentGroup, "GROUPING"),
+    (StandardTagKey::Copyright, "COPYRIGHT"),
+    (StandardTagKey::Date, "DATE"),
+    (StandardTagKey::Description, "DESCRIPTION"),
+    (StandardTagKey::DiscNumber, "DISCNUMBER"),
+    (StandardTagKey::DiscSubtitle, "DISCSUBTITLE"),
+    (StandardTagKey::DiscTotal, "DISCTOTAL"),
+    (StandardTagKey::EncodedBy, "ENCODEDBY"),
+    (StandardTagKey::Engineer, "ENGINEER"),
+    (StandardTagKey::Genre, "GENRE"),
+    (StandardTagKey::IdentBarcode, "BARCODE"),
+    (StandardTagKey::IdentCatalogNumber, "CATALOGNUMBER"),
+    (StandardTagKey::IdentIsrc, "ISRC"),
+    (StandardTagKey::Label, "LABEL"),
+    (StandardTagKey::Language, "LANGUAGE"),
+    (StandardTagKey::License, "LICENSE"),
+    (StandardTagKey::Lyricist, "LYRICIST"),
+    (StandardTagKey::Lyrics, "LYRICS"),
+    (StandardTagKey::MediaFormat, "MEDIA"),
+    (StandardTagKey::MixDj, "DJMIXER"),
+    (StandardTagKey::MixEngineer, "MIXER"),
+    (StandardTagKey::Mood, "MOOD"),
+    (
+        StandardTagKey::MusicBrainzAlbumArtistId,
+        "MUSICBRAINZ_ALBUMARTISTID",
+    ),
+    (StandardTagKey::MusicBrainzAlbumId, "MUSICBRAINZ_ALBUMID"),
+    (StandardTagKey::MusicBrainzArtistId, "MUSICBRAINZ_ARTISTID"),
+    (
+        StandardTagKey::MusicBrainzRecordingId,
+        "MUSICBRAINZ_TRACKID",
+    ),
+    (
+        StandardTagKey::MusicBrainzReleaseGroupId,
+        "MUSICBRAINZ_RELEASEGROUPID",
+    ),
+    (
+        StandardTagKey::MusicBrainzReleaseTrackId,
+        "MUSICBRAINZ_RELEASETRACKID",
+    ),
+    (StandardTagKey::MusicBrainzWorkId, "MUSICBRAINZ_WORKID"),
+    (StandardTagKey::OriginalAlbum, "ORIGINALALBUM"),
+    (StandardTagKey::OriginalArtist, "ORIGINALARTIST"),
+    (StandardTagKey::OriginalDate, "ORIGINALDATE"),
+    (StandardTagKey::Performer, "PERFORMER"),
+    (StandardTagKey::Producer, "PRODUCER"),
+    (StandardTagKey::Rating, "RATING"),
+    (StandardTagKey::ReleaseCountry, "RELEASECOUNTRY"),
+    (StandardTagKey::Remixer, "REMIXER"),
+    (StandardTagKey::SortAlbum, "ALBUMSORT"),
+    (StandardTagKey::SortAlbumArtist, "ALBUMARTISTSORT"),
+    (StandardTagKey::SortArtist, "ARTISTSORT"),
+    (StandardTagKey::SortComposer, "COMPOSERSORT"),
+    (StandardTagKey::SortTrackTitle, "TITLESORT"),
+    (StandardTagKey::TrackNumber, "TRACKNUMBER"),
+    (StandardTagKey::TrackSubtitle, "SUBTITLE"),
+    (StandardTagKey::TrackTitle, "TITLE"),
+    (StandardTagKey::TrackTotal, "TRACKTOTAL"),
+    (StandardTagKey::Writer, "WRITER"),
+];
+
+/// An embedded picture, with the ID3/FLAC picture type
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Picture {
+    /// 3 is the front cover
+    pub picture_type: u32,
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Tag fields (Vorbis comment names, upper case) and pictures
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+    pub fields: Vec<(String, String)>,
+    pub pictures: Vec<Picture>,
+}
+
+impl Tags {
+    /// Read the tags of a real audio file
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path).context("Failed to open audio file")?;
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+        let mut probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                mss,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .context("Failed to probe audio file")?;
+
+        let mut tags = Self::default();
+        if let Some(metadata) = probed.metadata.get() {
+            if let Some(revision) = metadata.current() {
+                tags.add_revision(revision);
+            }
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            tags.add_revision(revision);
+        }
+        Ok(tags)
+    }
+
+    fn add_revision(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let Some(name) = tag.std_key.and_then(field_name) else {
+                continue;
+            };
+            let value = tag.value.to_string();
+            let value = value.trim_end_matches('\0').trim();
+            if value.is_empty() {
+                continue;
+            }
+            // ID3 stores "3/12" in one frame
+            match (name, value.split_once('/')) {
+                ("TRACKNUMBER", Some((number, total))) => {
+                    self.add("TRACKNUMBER", number);
+                    self.add("TRACKTOTAL", total);
+                }
+                ("DISCNUMBER", Some((number, total))) => {
+                    self.add("DISCNUMBER", number);
+                    self.add("DISCTOTAL", total);
+                }
+                _ => self.add(name, value),
+            }
+        }
+        for visual in revision.visuals() {
+            self.pictures.push(Picture {
+                picture_type: picture_type(visual.usage),
+                media_type: visual.media_type.clone(),
+                width: visual.dimensions.map_or(0, |size| size.width),
+                height: visual.dimensions.map_or(0, |size| size.height),
+                data: visual.data.to_vec(),
+            });
+        }
+    }
+
+    /// Add a field unless the same value is already there
+    fn add(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        if !value.is_empty() && self.get_all(name).all(|existing| existing != value) {
+            self.fields.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    /// First value of a field
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replace every value of a field
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.fields
+            .retain(|(field, _)| !field.eq_ignore_ascii_case(name));
+        self.fields.push((name.to_uppercase(), value.to_string()));
+    }
+}
+
+fn field_name(key: StandardTagKey) -> Option<&'static str> {
+    FIELD_NAMES
+        .iter()
+        .find(|(std_key, _)| *std_key == key)
+        .map(|(_, name)| *name)
+}
+
+/// ID3/FLAC picture type of a symphonia visual
+fn picture_type(usage: Option<StandardVisualKey>) -> u32 {
+    match usage {
+        None => 0,
+        Some(StandardVisualKey::FileIcon) => 1,
+        Some(StandardVisualKey::OtherIcon) => 2,
+        Some(StandardVisualKey::FrontCover) => 3,
+        Some(StandardVisualKey::BackCover) => 4,
+        Some(StandardVisualKey::Leaflet) => 5,
+        Some(StandardVisualKey::Media) => 6,
+        Some(StandardVisualKey::LeadArtistPerformerSoloist) => 7,
+        Some(StandardVisualKey::ArtistPerformer) => 8,
+        Some(StandardVisualKey::Conductor) => 9,
+        Some(StandardVisualKey::BandOrchestra) => 10,
+        Some(StandardVisualKey::Composer) => 11,
+        Some(StandardVisualKey::Lyricist) => 12,
+        Some(StandardVisualKey::RecordingLocation) => 13,
+        Some(StandardVisualKey::RecordingSession) => 14,
+        Some(StandardVisualKey::Performance) => 15,
+        Some(StandardVisualKey::ScreenCapture) => 16,
+        Some(StandardVisualKey::Illustration) => 18,
+        Some(StandardVisualKey::BandArtistLogo) => 19,
+        Some(StandardVisualKey::PublisherStudioLogo) => 20,
+    }
+}
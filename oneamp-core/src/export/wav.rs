@@ -0,0 +1,217 @@
+// WAV Writer
+// Streams integer PCM into a RIFF/WAVE file. Tags go into a LIST/INFO chunk
+// for RIFF readers and an `id3 ` chunk (ID3v2.4 with cover art) for players
+// that read those.
+
+use super::tags::Tags;
+use super::{Encoder, StreamFormat};
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// RIFF INFO ids for the tag fields that have one
+const INFO_IDS: [(&str, &[u8; 4]); 9] = [
+    ("TITLE", b"INAM"),
+    ("ARTIST", b"IART"),
+    ("ALBUM", b"IPRD"),
+    ("TRACKNUMBER", b"IPRT"),
+    ("DATE", b"ICRD"),
+    ("GENRE", b"IGNR"),
+    ("COMMENT", b"ICMT"),
+    ("COMPOSER", b"IMUS"),
+    ("COPYRIGHT", b"ICOP"),
+];
+
+/// ID3v2 text frames for the tag fields that have one; the rest become TXXX
+const ID3_FRAMES: [(&str, &[u8; 4]); 12] = [
+    ("TITLE", b"TIT2"),
+    ("ARTIST", b"TPE1"),
+    ("ALBUM", b"TALB"),
+    ("ALBUMARTIST", b"TPE2"),
+    ("COMPOSER", b"TCOM"),
+    ("GENRE", b"TCON"),
+    ("DATE", b"TDRC"),
+    ("COPYRIGHT", b"TCOP"),
+    ("BPM", b"TBPM"),
+    ("ISRC", b"TSRC"),
+    ("GROUPING", b"TIT1"),
+    ("SUBTITLE", b"TIT3"),
+];
+
+pub(super) struct WavWriter {
+    file: BufWriter<File>,
+    bytes_per_sample: usize,
+    /// Offset of the data chunk's size field
+    data_size_offset: u64,
+    data_len: u64,
+}
+
+impl WavWriter {
+    pub(super) fn create(path: &Path, format: &StreamFormat, tags: &Tags) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+
+        let channels = format.channels as u32;
+        let bytes_per_sample = format.bits_per_sample as u32 / 8;
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?; // patched in finish()
+        file.write_all(b"WAVE")?;
+
+        let mut fmt = Vec::with_capacity(16);
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        fmt.extend_from_slice(&(channels as u16).to_le_bytes());
+        fmt.extend_from_slice(&format.sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(format.sample_rate * channels * bytes_per_sample).to_le_bytes());
+        fmt.extend_from_slice(&((channels * bytes_per_sample) as u16).to_le_bytes());
+        fmt.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+        write_chunk(&mut file, b"fmt ", &fmt)?;
+
+        let info = info_list(tags);
+        if info.len() > 4 {
+            write_chunk(&mut file, b"LIST", &info)?;
+        }
+        if !tags.fields.is_empty() || !tags.pictures.is_empty() {
+            write_chunk(&mut file, b"id3 ", &id3v2(tags))?;
+        }
+
+        file.write_all(b"data")?;
+        let data_size_offset = file.stream_position()?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            bytes_per_sample: bytes_per_sample as usize,
+            data_size_offset,
+            data_len: 0,
+        })
+    }
+}
+
+impl Encoder for WavWriter {
+    fn write(&mut self, samples: &[i32]) -> Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * self.bytes_per_sample);
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes()[..self.bytes_per_sample]);
+        }
+        self.data_len += bytes.len() as u64;
+        if self.data_len > u32::MAX as u64 - 1024 {
+            bail!("WAV files are limited to 4 GiB; export to FLAC instead");
+        }
+        self.file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.data_len % 2 == 1 {
+            self.file.write_all(&[0])?;
+        }
+        let riff_size = self.file.stream_position()? - 8;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(riff_size as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(self.data_size_offset))?;
+        self.file.write_all(&(self.data_len as u32).to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Write a chunk with its padding byte
+fn write_chunk(out: &mut impl Write, id: &[u8; 4], data: &[u8]) -> Result<()> {
+    out.write_all(id)?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)?;
+    if data.len() % 2 == 1 {
+        out.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Body of a LIST chunk of form INFO, with NUL-terminated values
+fn info_list(tags: &Tags) -> Vec<u8> {
+    let mut list = b"INFO".to_vec();
+    for (field, id) in INFO_IDS {
+        if let Some(value) = tags.get(field) {
+            let mut text = value.as_bytes().to_vec();
+            text.push(0);
+            write_chunk(&mut list, id, &text).expect("writing to a Vec");
+        }
+    }
+    list
+}
+
+/// An ID3v2.4 tag with UTF-8 text frames and APIC pictures
+fn id3v2(tags: &Tags) -> Vec<u8> {
+    let mut frames = Vec::new();
+    let mut frame = |id: &[u8; 4], body: &[u8]| {
+        frames.extend_from_slice(id);
+        frames.extend_from_slice(&syncsafe(body.len() as u32));
+        frames.extend_from_slice(&[0, 0]);
+        frames.extend_from_slice(body);
+    };
+    // Encoding byte 3 is UTF-8; multiple values are NUL-separated
+    let text = |values: Vec<&str>| {
+        let mut body = vec![3];
+        body.extend_from_slice(values.join("\0").as_bytes());
+        body
+    };
+
+    let mut done = vec!["TRACKTOTAL", "DISCTOTAL"];
+    for (field, id) in ID3_FRAMES {
+        let values: Vec<&str> = tags.get_all(field).collect();
+        if !values.is_empty() {
+            frame(id, &text(values));
+        }
+        done.push(field);
+    }
+    for (field, total, id) in [
+        ("TRACKNUMBER", "TRACKTOTAL", b"TRCK"),
+        ("DISCNUMBER", "DISCTOTAL", b"TPOS"),
+    ] {
+        if let Some(number) = tags.get(field) {
+            let value = match tags.get(total) {
+                Some(total) => format!("{}/{}", number, total),
+                None => number.to_string(),
+            };
+            frame(id, &text(vec![&value]));
+        }
+        done.push(field);
+    }
+    for comment in tags.get_all("COMMENT") {
+        // Language, empty description, text
+        let mut body = b"\x03eng\0".to_vec();
+        body.extend_from_slice(comment.as_bytes());
+        frame(b"COMM", &body);
+    }
+    done.push("COMMENT");
+    for (field, value) in &tags.fields {
+        if !done.contains(&field.as_str()) {
+            frame(b"TXXX", &text(vec![field, value]));
+        }
+    }
+    for picture in &tags.pictures {
+        let mut body = vec![3];
+        body.extend_from_slice(picture.media_type.as_bytes());
+        body.push(0);
+        body.push(picture.picture_type as u8);
+        body.push(0); // empty description
+        body.extend_from_slice(&picture.data);
+        frame(b"APIC", &body);
+    }
+
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend_from_slice(&syncsafe(frames.len() as u32));
+    tag.extend_from_slice(&frames);
+    tag
+}
+
+/// 28-bit integer in four 7-bit bytes
+fn syncsafe(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8 & 0x7f,
+        (value >> 14) as u8 & 0x7f,
+        (value >> 7) as u8 & 0x7f,
+        value as u8 & 0x7f,
+    ]
+}
@@ -0,0 +1,264 @@
+// File Inspection
+// Detailed technical report for one audio file: the player's track info,
+// codec parameters, tag keys, embedded pictures and an optional full-decode
+// check that counts corrupt packets. Used by `oneamp-cli info`.
+
+use crate::TrackInfo;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+use symphonia::core::codecs::{CodecParameters, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision};
+use symphonia::core::probe::Hint;
+
+/// Decoded frame count below which a file counts as truncated, as a fraction
+/// of the frame count in its header. MP3 headers only carry an estimate.
+const TRUNCATION_TOLERANCE: f64 = 0.99;
+
+/// Everything known about one audio file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileReport {
+    /// What the player shows for the file
+    pub track: TrackInfo,
+    pub codec: CodecDetails,
+    /// Tag keys as stored in the file (e.g. `TIT2`, `TITLE`), without
+    /// duplicates, in file order
+    pub tag_keys: Vec<String>,
+    pub pictures: Vec<PictureInfo>,
+    /// Result of decoding every packet, if requested
+    pub decode: Option<DecodeCheck>,
+}
+
+impl FileReport {
+    /// Whether the file decoded cleanly (always true without a decode check)
+    pub fn is_valid(&self) -> bool {
+        self.decode.as_ref().is_none_or(DecodeCheck::is_valid)
+    }
+}
+
+/// Codec parameters of the default track
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodecDetails {
+    /// Short codec name, e.g. "mp3", "flac", "pcm_s16le"
+    pub name: Option<String>,
+    /// Descriptive codec name
+    pub long_name: Option<String>,
+    pub sample_format: Option<String>,
+    pub bits_per_sample: Option<u32>,
+    pub bits_per_coded_sample: Option<u32>,
+    /// Channel names, e.g. "FRONT_LEFT | FRONT_RIGHT"
+    pub channel_layout: Option<String>,
+    /// Length in frames according to the file headers
+    pub n_frames: Option<u64>,
+    /// Time base as "numer/denom"
+    pub time_base: Option<String>,
+    /// Encoder delay and padding in frames
+    pub delay: Option<u32>,
+    pub padding: Option<u32>,
+    pub max_frames_per_packet: Option<u64>,
+}
+
+/// An embedded picture (cover art)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PictureInfo {
+    /// e.g. "FrontCover"
+    pub usage: Option<String>,
+    pub media_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size_bytes: usize,
+}
+
+/// Outcome of decoding a whole file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DecodeCheck {
+    pub packets: u64,
+    pub frames: u64,
+    /// Packets the decoder rejected as malformed
+    pub corrupt_packets: u64,
+    /// Decoding ended early: fewer frames than the headers announce
+    pub truncated: bool,
+    /// First decoding or demuxing error
+    pub error: Option<String>,
+}
+
+impl DecodeCheck {
+    pub fn is_valid(&self) -> bool {
+        self.corrupt_packets == 0 && !self.truncated && self.error.is_none()
+    }
+}
+
+/// Inspect a real audio file; with `full_decode`, every packet is decoded
+pub fn inspect(path: &Path, full_decode: bool) -> Result<FileReport> {
+    let track = TrackInfo::from_file(path)?;
+
+    let file = File::open(path).context("Failed to open audio file")?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("Failed to probe audio file")?;
+
+    let mut report = FileReport {
+        track,
+        ..Default::default()
+    };
+
+    // Tags read before the container (ID3v2) and by the container itself
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            add_metadata(&mut report, revision);
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        add_metadata(&mut report, revision);
+    }
+
+    let format = &mut probed.format;
+    let params = format
+        .default_track()
+        .context("No audio track found")?
+        .codec_params
+        .clone();
+    report.codec = codec_details(&params);
+
+    if full_decode {
+        report.decode = Some(decode_all(format.as_mut(), &params)?);
+    }
+    Ok(report)
+}
+
+fn add_metadata(report: &mut FileReport, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        if !report.tag_keys.contains(&tag.key) {
+            report.tag_keys.push(tag.key.clone());
+        }
+    }
+    for visual in revision.visuals() {
+        report.pictures.push(PictureInfo {
+            usage: visual.usage.map(|usage| format!("{:?}", usage)),
+            media_type: visual.media_type.clone(),
+            width: visual.dimensions.map(|size| size.width),
+            height: visual.dimensions.map(|size| size.height),
+            size_bytes: visual.data.len(),
+        });
+    }
+}
+
+fn codec_details(params: &CodecParameters) -> CodecDetails {
+    let descriptor = symphonia::default::get_codecs().get_codec(params.codec);
+    CodecDetails {
+        name: descriptor.map(|codec| codec.short_name.to_string()),
+        long_name: descriptor.map(|codec| codec.long_name.to_string()),
+        sample_format: params.sample_format.map(|format| format!("{:?}", format)),
+        bits_per_sample: params.bits_per_sample,
+        bits_per_coded_sample: params.bits_per_coded_sample,
+        channel_layout: params.channels.map(|channels| format!("{:?}", channels)),
+        n_frames: params.n_frames,
+        time_base: params
+            .time_base
+            .map(|base| format!("{}/{}", base.numer, base.denom)),
+        delay: params.delay,
+        padding: params.padding,
+        max_frames_per_packet: params.max_frames_per_packet,
+    }
+}
+
+/// Decode every packet of the default track. Malformed packets are counted
+/// and skipped like the player does; other errors end the check.
+fn decode_all(format: &mut dyn FormatReader, params: &CodecParameters) -> Result<DecodeCheck> {
+    let track_id = format
+        .default_track()
+        .map(|track| track.id)
+        .unwrap_or_default();
+    let mut decoder = symphonia::default::get_codecs()
+        .make(params, &DecoderOptions { verify: true })
+        .context("Unsupported codec")?;
+
+    let mut check = DecodeCheck::default();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => {
+                check.error = Some(err.to_string());
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        check.packets += 1;
+
+        match decoder.decode(&packet) {
+            Ok(buffer) => check.frames += buffer.frames() as u64,
+            Err(SymphoniaError::DecodeError(message)) => {
+                check.corrupt_packets += 1;
+                check.error.get_or_insert_with(|| message.to_string());
+            }
+            Err(err) => {
+                check.error = Some(err.to_string());
+                break;
+            }
+        }
+    }
+
+    if let Some(expected) = params.n_frames {
+        check.truncated = (check.frames as f64) < expected as f64 * TRUNCATION_TOLERANCE;
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::{wav, write_silent};
+
+    #[test]
+    fn test_inspect_wav() {
+        let dir = std::env::temp_dir().join("oneamp_inspect_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let good = dir.join("good.wav");
+        write_silent(&good, 8000);
+        let report = inspect(&good, true).unwrap();
+        assert_eq!(report.track.sample_rate, Some(8000));
+        assert_eq!(report.codec.name.as_deref(), Some("pcm_s16le"));
+        assert_eq!(report.codec.bits_per_sample, Some(16));
+        assert_eq!(report.codec.n_frames, Some(8000));
+        assert!(report.pictures.is_empty());
+        let decode = report.decode.as_ref().unwrap();
+        assert_eq!(decode.frames, 8000);
+        assert!(report.is_valid(), "{:?}", decode);
+
+        // The header announces a second, only half of it is there
+        let cut = dir.join("cut.wav");
+        let mut bytes = wav(8000, 1, &[0; 8000]);
+        bytes.truncate(bytes.len() - 8000);
+        std::fs::write(&cut, bytes).unwrap();
+        let report = inspect(&cut, true).unwrap();
+        assert!(report.decode.as_ref().unwrap().truncated);
+        assert!(!report.is_valid());
+        assert!(inspect(&cut, false).unwrap().is_valid());
+
+        let junk = dir.join("junk.wav");
+        std::fs::write(&junk, b"not audio at all").unwrap();
+        assert!(inspect(&junk, true).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,688 @@
+// Local Control API
+// Newline-delimited JSON over a Unix socket, for scripts and remote control.
+// Clients send `IpcRequest`s and get one `IpcReply` per request; after a
+// `subscribe` request they also receive every playback event as it happens.
+// The player (desktop app or daemon) carries requests out by polling
+// `IpcServer` for `IpcCall`s.
+
+use crate::{AudioEvent, PlayerStatus, RepeatMode, TrackInfo};
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::{select, Receiver, Sender, TrySendError};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Environment variable overriding the socket path
+pub const SOCKET_ENV: &str = "ONEAMP_SOCKET";
+
+/// Events queued per client before further events are dropped for it
+const CLIENT_BUFFER: usize = 256;
+
+/// Private directory counter, so servers binding at once don't share one
+static NEXT_PRIVATE_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Socket used when none is given: `$ONEAMP_SOCKET`, else
+/// `$XDG_RUNTIME_DIR/oneamp.sock`, else a socket in a per-user directory of
+/// the temp directory
+pub fn default_socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(dir).join("oneamp.sock");
+    }
+    user_temp_dir().join("oneamp.sock")
+}
+
+/// `$TMPDIR/oneamp-$UID`, which holds the socket when there is no runtime
+/// directory
+fn user_temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("oneamp-{}", current_uid()))
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail
+    unsafe { libc::getuid() }
+}
+
+/// Make sure a socket in the shared temp directory sits in a directory only
+/// the current user controls, so other users can neither listen in its place
+/// nor block it. The directory is created (0700) if missing.
+fn check_socket_dir(path: &Path) -> Result<()> {
+    let dir = user_temp_dir();
+    if path.parent() != Some(dir.as_path()) {
+        return Ok(());
+    }
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to create {}", dir.display())),
+    }
+    let metadata = std::fs::symlink_metadata(&dir)
+        .with_context(|| format!("Failed to check {}", dir.display()))?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        bail!(
+            "{} is not a private directory owned by the current user",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Commands accepted over the socket, e.g. `{"command":"seek","position_secs":30}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcRequest {
+    /// Resume if paused, otherwise start the current track
+    Play,
+    Pause,
+    TogglePause,
+    Stop,
+    Next,
+    Previous,
+    Seek {
+        position_secs: f32,
+    },
+    /// Output volume, 0.0 - 1.0
+    SetVolume {
+        volume: f32,
+    },
+    SetEqualizerEnabled {
+        enabled: bool,
+    },
+    SetEqualizerBand {
+        band: usize,
+        gain_db: f32,
+    },
+    SetEqualizerBands {
+        gains: Vec<f32>,
+    },
+    ResetEqualizer,
+    /// Add files to the playlist and play the first one
+    Open {
+        paths: Vec<PathBuf>,
+    },
+    /// Add files to the end of the playlist
+    Enqueue {
+        paths: Vec<PathBuf>,
+    },
+    /// Play a playlist entry
+    PlayIndex {
+        index: usize,
+    },
+    /// Remove a playlist entry
+    Remove {
+        index: usize,
+    },
+    Clear,
+    SetRepeat {
+        mode: RepeatMode,
+    },
+    SetShuffle {
+        enabled: bool,
+    },
+    Status,
+    Playlist,
+    /// Stream playback events to this client
+    Subscribe,
+    /// Bring the player window to the front
+    Raise,
+    Quit,
+}
+
+/// Messages sent back to clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IpcReply {
+    Ok,
+    Error {
+        message: String,
+    },
+    Status(PlayerStatus),
+    Playlist {
+        entries: Vec<PathBuf>,
+        current_index: Option<usize>,
+    },
+    /// Playback event, sent to subscribed clients
+    Event(IpcEvent),
+}
+
+impl IpcReply {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error {
+            message: message.into(),
+        }
+    }
+
+    /// Turn an `Error` reply into an `Err`
+    pub fn into_result(self) -> Result<Self> {
+        match self {
+            Self::Error { message } => bail!(message),
+            reply => Ok(reply),
+        }
+    }
+}
+
+/// Playback events as streamed to subscribers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+    TrackLoaded {
+        track: TrackInfo,
+    },
+    /// The playing track's metadata changed (stream titles)
+    TrackUpdated {
+        track: TrackInfo,
+    },
+    /// A stream is filling its buffer
+    Buffering {
+        progress: f32,
+    },
+    Playing,
+    Paused,
+    Stopped,
+    Position {
+        position_secs: f32,
+        duration_secs: f32,
+    },
+    VolumeChanged {
+        volume: f32,
+    },
+    Finished,
+    EqualizerUpdated {
+        enabled: bool,
+        gains: Vec<f32>,
+    },
+    Error {
+        message: String,
+    },
+    SeekFailed {
+        message: String,
+    },
+}
+
+impl IpcEvent {
+    /// Event to forward for an audio engine event. Visualization data and
+    /// internal requests are not forwarded.
+    pub fn from_audio(event: &AudioEvent) -> Option<Self> {
+        Some(match event {
+            AudioEvent::TrackLoaded(track) => Self::TrackLoaded {
+                track: track.clone(),
+            },
+            AudioEvent::TrackUpdated(track) => Self::TrackUpdated {
+                track: track.clone(),
+            },
+            AudioEvent::Buffering(progress) => Self::Buffering {
+                progress: *progress,
+            },
+            AudioEvent::Playing => Self::Playing,
+            AudioEvent::Paused => Self::Paused,
+            AudioEvent::Stopped => Self::Stopped,
+            AudioEvent::Position(position, duration) => Self::Position {
+                position_secs: *position,
+                duration_secs: *duration,
+            },
+            AudioEvent::VolumeChanged(volume) => Self::VolumeChanged { volume: *volume },
+            AudioEvent::Finished => Self::Finished,
+            AudioEvent::EqualizerUpdated(enabled, gains) => Self::EqualizerUpdated {
+                enabled: *enabled,
+                gains: gains.clone(),
+            },
+            AudioEvent::Error(message) => Self::Error {
+                message: message.clone(),
+            },
+            AudioEvent::SeekFailed(message) => Self::SeekFailed {
+                message: message.clone(),
+            },
+            AudioEvent::RequestNext
+            | AudioEvent::RequestPrevious
+            | AudioEvent::VisualizationData(_) => return None,
+        })
+    }
+}
+
+/// A request waiting to be carried out by the player
+#[derive(Debug)]
+pub struct IpcCall {
+    pub request: IpcRequest,
+    reply: Sender<IpcReply>,
+}
+
+impl IpcCall {
+    pub fn reply(self, reply: IpcReply) {
+        let _ = self.reply.send(reply);
+    }
+}
+
+struct Shared {
+    subscribers: Mutex<Vec<Sender<IpcReply>>>,
+    waker: Mutex<Option<Box<dyn Fn() + Send>>>,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Ok(waker) = self.waker.lock() {
+            if let Some(wake) = waker.as_ref() {
+                wake();
+            }
+        }
+    }
+}
+
+/// Listening socket of a running player.
+///
+/// Poll [`IpcServer::try_recv`] (or wait on [`IpcServer::calls`]) and answer
+/// each call; pass audio events to [`IpcServer::broadcast`]. Dropping the
+/// server removes the socket file.
+pub struct IpcServer {
+    path: PathBuf,
+    calls: Receiver<IpcCall>,
+    shared: Arc<Shared>,
+}
+
+impl IpcServer {
+    /// Listen on `path`, replacing a stale socket left by a crashed player.
+    /// Fails if another player is already listening there.
+    pub fn bind(path: &Path) -> Result<Self> {
+        check_socket_dir(path)?;
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                bail!("Another player is already listening on {}", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).ok();
+        }
+
+        let listener = bind_private(path)?;
+
+        let (calls_tx, calls_rx) = crossbeam_channel::unbounded();
+        let shared = Arc::new(Shared {
+            subscribers: Mutex::new(Vec::new()),
+            waker: Mutex::new(None),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let accept_shared = shared.clone();
+        thread::Builder::new()
+            .name("oneamp-ipc".to_string())
+            .spawn(move || accept_loop(listener, calls_tx, accept_shared))
+            .context("Failed to start the IPC thread")?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            calls: calls_rx,
+            shared,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Next pending call, if any (non-blocking)
+    pub fn try_recv(&self) -> Option<IpcCall> {
+        self.calls.try_recv().ok()
+    }
+
+    /// Call channel, for waiting on calls alongside other channels
+    pub fn calls(&self) -> &Receiver<IpcCall> {
+        &self.calls
+    }
+
+    /// Call `wake` whenever a call arrives, e.g. to repaint an idle window
+    pub fn set_waker(&self, wake: impl Fn() + Send + 'static) {
+        if let Ok(mut waker) = self.shared.waker.lock() {
+            *waker = Some(Box::new(wake));
+        }
+    }
+
+    /// Send an audio engine event to subscribed clients. Clients that fall
+    /// too far behind miss events; disconnected ones are dropped.
+    pub fn broadcast(&self, event: &AudioEvent) {
+        let Some(event) = IpcEvent::from_audio(event) else {
+            return;
+        };
+        if let Ok(mut subscribers) = self.shared.subscribers.lock() {
+            subscribers.retain(|subscriber| {
+                !matches!(
+                    subscriber.try_send(IpcReply::Event(event.clone())),
+                    Err(TrySendError::Disconnected(_))
+                )
+            });
+        }
+    }
+}
+
+/// Listen on a socket only the owner may connect to. It is bound in a fresh
+/// 0700 directory next to `path`, restricted to 0600 and then linked into
+/// place, so it is never reachable with looser permissions. Linking fails
+/// if `path` exists, like binding would.
+fn bind_private(path: &Path) -> Result<UnixListener> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let private = dir.join(format!(
+        ".oneamp-{}-{}",
+        std::process::id(),
+        NEXT_PRIVATE_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private)
+        .with_context(|| format!("Failed to create {}", private.display()))?;
+
+    let socket = private.join("sock");
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", path.display()))
+        .and_then(|listener| {
+            std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to restrict {}", path.display()))?;
+            std::fs::hard_link(&socket, path)
+                .with_context(|| format!("Failed to listen on {}", path.display()))?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&socket);
+    let _ = std::fs::remove_dir(&private);
+    listener
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the shutdown flag
+        let _ = UnixStream::connect(&self.path);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn accept_loop(listener: UnixListener, calls: Sender<IpcCall>, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                let calls = calls.clone();
+                let shared = shared.clone();
+                let spawned = thread::Builder::new()
+                    .name("oneamp-ipc-client".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve_client(stream, calls, shared) {
+                            eprintln!("IPC client error: {:#}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    eprintln!("Failed to start IPC client thread: {}", e);
+                }
+            }
+            Err(e) => eprintln!("IPC accept error: {}", e),
+        }
+    }
+}
+
+fn serve_client(stream: UnixStream, calls: Sender<IpcCall>, shared: Arc<Shared>) -> Result<()> {
+    // Replies are never dropped: there is one per request the client sent.
+    // Events are, once a client falls CLIENT_BUFFER behind.
+    let (replies_tx, replies_rx) = crossbeam_channel::unbounded::<IpcReply>();
+    let (events_tx, events_rx) = crossbeam_channel::bounded::<IpcReply>(CLIENT_BUFFER);
+
+    // Replies and events are written by their own thread so a slow client
+    // never blocks the player
+    let mut writer = stream.try_clone()?;
+    thread::Builder::new()
+        .name("oneamp-ipc-writer".to_string())
+        .spawn(move || {
+            while let Some(message) = next_message(&replies_rx, &events_rx) {
+                if write_message(&mut writer, &message).is_err() {
+                    break;
+                }
+            }
+        })?;
+
+    let reader = BufReader::new(stream.try_clone()?);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(IpcRequest::Subscribe) => {
+                if let Ok(mut subscribers) = shared.subscribers.lock() {
+                    subscribers.push(events_tx.clone());
+                }
+                let _ = replies_tx.send(IpcReply::Ok);
+            }
+            Ok(request) => {
+                let call = IpcCall {
+                    request,
+                    reply: replies_tx.clone(),
+                };
+                if calls.send(call).is_err() {
+                    break;
+                }
+                shared.wake();
+            }
+            Err(e) => {
+                let _ = replies_tx.send(IpcReply::error(format!("Invalid request: {}", e)));
+            }
+        }
+    }
+
+    // Stops the writer the next time it has something to send
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    Ok(())
+}
+
+/// Wait for the next message to write, preferring replies. Returns `None`
+/// once the client or the server is gone.
+fn next_message(replies: &Receiver<IpcReply>, events: &Receiver<IpcReply>) -> Option<IpcReply> {
+    if let Ok(reply) = replies.try_recv() {
+        return Some(reply);
+    }
+    select! {
+        recv(replies) -> reply => reply.ok(),
+        recv(events) -> event => event.ok(),
+    }
+}
+
+fn write_message(stream: &mut UnixStream, message: &IpcReply) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Connection to a running player
+pub struct IpcClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl IpcClient {
+    pub fn connect(path: &Path) -> Result<Self> {
+        check_socket_dir(path)?;
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("No player is listening on {}", path.display()))?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    pub fn send(&mut self, request: &IpcRequest) -> Result<()> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Send a request and wait for its reply. Events arriving in between
+    /// (after `subscribe`) are skipped.
+    pub fn request(&mut self, request: &IpcRequest) -> Result<IpcReply> {
+        self.send(request)?;
+        loop {
+            match self.recv()? {
+                IpcReply::Event(_) => continue,
+                reply => return Ok(reply),
+            }
+        }
+    }
+
+    /// Wait for the next message from the player
+    pub fn recv(&mut self) -> Result<IpcReply> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            bail!("The player closed the connection");
+        }
+        serde_json::from_str(&line).context("Invalid message from the player")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("oneamp_ipc_{}_{}.sock", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_json_format() {
+        let request = IpcRequest::Seek {
+            position_secs: 30.0,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"command":"seek","position_secs":30.0}"#
+        );
+        let request: IpcRequest =
+            serde_json::from_str(r#"{"command":"enqueue","paths":["/music/a.mp3"]}"#).unwrap();
+        assert_eq!(
+            request,
+            IpcRequest::Enqueue {
+                paths: vec![PathBuf::from("/music/a.mp3")]
+            }
+        );
+
+        let reply = IpcReply::Event(IpcEvent::Playing);
+        let json = serde_json::to_string(&reply).unwrap();
+        assert_eq!(json, r#"{"type":"event","event":"playing"}"#);
+        assert_eq!(serde_json::from_str::<IpcReply>(&json).unwrap(), reply);
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let path = socket_path("round_trip");
+        let server = IpcServer::bind(&path).unwrap();
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            let mut client = IpcClient::connect(&client_path).unwrap();
+            let status = client.request(&IpcRequest::Status).unwrap();
+            let invalid = client.send(&IpcRequest::Play).and_then(|_| {
+                client.writer.write_all(b"{\"command\":\"dance\"}\n")?;
+                client.recv()
+            });
+            (status, invalid.unwrap())
+        });
+
+        let call = server.calls().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(call.request, IpcRequest::Status);
+        call.reply(IpcReply::Status(PlayerStatus::default()));
+
+        let call = server.calls().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(call.request, IpcRequest::Play);
+
+        let (status, invalid) = client.join().unwrap();
+        assert_eq!(status, IpcReply::Status(PlayerStatus::default()));
+        assert!(invalid.into_result().is_err());
+    }
+
+    #[test]
+    fn test_events_reach_subscribers() {
+        let path = socket_path("events");
+        let server = IpcServer::bind(&path).unwrap();
+        let mut client = IpcClient::connect(&path).unwrap();
+        assert_eq!(
+            client.request(&IpcRequest::Subscribe).unwrap(),
+            IpcReply::Ok
+        );
+
+        server.broadcast(&AudioEvent::VisualizationData(vec![0.0; 16]));
+        server.broadcast(&AudioEvent::Position(1.5, 10.0));
+        assert_eq!(
+            client.recv().unwrap(),
+            IpcReply::Event(IpcEvent::Position {
+                position_secs: 1.5,
+                duration_secs: 10.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_replies_reach_subscribers_that_fall_behind() {
+        let path = socket_path("behind");
+        let server = IpcServer::bind(&path).unwrap();
+        let mut client = IpcClient::connect(&path).unwrap();
+        assert_eq!(
+            client.request(&IpcRequest::Subscribe).unwrap(),
+            IpcReply::Ok
+        );
+
+        // More events than the socket and the client's queue hold
+        for i in 0..20_000 {
+            server.broadcast(&AudioEvent::Position(i as f32, 10.0));
+        }
+        let client = thread::spawn(move || client.request(&IpcRequest::Status).unwrap());
+        let call = server.calls().recv_timeout(Duration::from_secs(5)).unwrap();
+        call.reply(IpcReply::Status(PlayerStatus::default()));
+        assert_eq!(
+            client.join().unwrap(),
+            IpcReply::Status(PlayerStatus::default())
+        );
+    }
+
+    #[test]
+    fn test_single_listener_and_stale_socket() {
+        let path = socket_path("single");
+        let server = IpcServer::bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(IpcServer::bind(&path).is_err());
+        drop(server);
+        assert!(!path.exists());
+
+        // A socket file nobody listens on is replaced
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let server = IpcServer::bind(&path).unwrap();
+        assert!(IpcClient::connect(server.path()).is_ok());
+    }
+
+    #[test]
+    fn test_temp_dir_socket_needs_private_dir() {
+        let dir = user_temp_dir();
+        let path = dir.join(format!("test_{}.sock", std::process::id()));
+        let server = IpcServer::bind(&path).unwrap();
+        let metadata = std::fs::metadata(&dir).unwrap();
+        assert_eq!(metadata.uid(), current_uid());
+        assert_eq!(metadata.mode() & 0o777, 0o700);
+
+        // A directory others can write to is refused
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let connected = IpcClient::connect(&path);
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(connected.is_err());
+        drop(server);
+    }
+}
@@ -2,9 +2,10 @@ use anyhow::{Context, Result};
 use crossbeam_channel::{Receiver, Sender};
 #[cfg(feature = "audio")]
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::Duration;
 use symphonia::core::formats::FormatOptions;
@@ -12,26 +13,64 @@ use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
+pub mod album_art;
 pub mod audio_capture;
 pub mod audio_thread_symphonia;
 #[cfg(feature = "audio")]
 pub mod cpal_output;
+pub mod cue;
 pub mod eq_source;
 pub mod equalizer;
+pub mod export;
+pub mod inspect;
+#[cfg(all(unix, feature = "ipc"))]
+pub mod ipc;
+pub mod library;
+#[cfg(feature = "lyrics")]
+pub mod lyrics;
+pub mod metadata;
+#[cfg(feature = "mpris")]
+pub mod mpris;
+pub mod player;
+pub mod playlist;
+pub mod playlist_file;
 pub mod plugins;
+#[cfg(feature = "podcasts")]
+pub mod podcast;
 #[cfg(feature = "audio")]
 pub mod rodio_output;
+#[cfg(feature = "scrobble")]
+pub mod scrobble;
+#[cfg(feature = "skins")]
+pub mod skin;
+#[cfg(feature = "stats")]
+pub mod smart_playlist;
+pub mod spectrum;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod stream;
 pub mod symphonia_player;
+#[cfg(test)]
+mod test_http;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_wav;
 
 pub use audio_capture::{AudioCaptureBuffer, AudioCaptureSource};
+pub use cue::CueSheet;
 pub use eq_source::EqualizerSource;
 pub use equalizer::Equalizer;
+pub use library::Library;
+pub use metadata::{DurationTotal, MetadataService, TrackMetadata};
+pub use player::{PlaybackState, Player, PlayerStatus};
+pub use playlist::{Playlist, RepeatMode, ShuffleMode};
 
 /// Commands that can be sent to the audio thread
 #[derive(Debug, Clone)]
 pub enum AudioCommand {
-    /// Load and play a file
+    /// Load and play a file or an HTTP(S) stream URL
     Play(PathBuf),
+    /// Load a file paused at a position (in seconds), e.g. to restore a session
+    Load(PathBuf, f32),
     /// Pause playback
     Pause,
     /// Resume playback
@@ -40,6 +79,8 @@ pub enum AudioCommand {
     Stop,
     /// Seek to a position (in seconds)
     Seek(f32),
+    /// Set the output volume (0.0 = silent, 1.0 = full)
+    SetVolume(f32),
     /// Play next track in playlist
     Next,
     /// Play previous track in playlist
@@ -52,6 +93,8 @@ pub enum AudioCommand {
     SetEqualizerBands(Vec<f32>),
     /// Reset equalizer to flat response
     ResetEqualizer,
+    /// Set the prebuffer and reconnection settings for streams played next
+    SetStreamOptions(stream::StreamOptions),
     /// Shutdown the audio thread
     Shutdown,
 }
@@ -61,6 +104,10 @@ pub enum AudioCommand {
 pub enum AudioEvent {
     /// Track loaded successfully with metadata
     TrackLoaded(TrackInfo),
+    /// Metadata of the playing track changed, e.g. a stream's `StreamTitle`
+    TrackUpdated(TrackInfo),
+    /// A stream is filling its buffer (0.0 - 1.0); `Playing` follows once full
+    Buffering(f32),
     /// Playback started
     Playing,
     /// Playback paused
@@ -69,6 +116,8 @@ pub enum AudioEvent {
     Stopped,
     /// Playback position update (current_secs, total_secs)
     Position(f32, f32),
+    /// Output volume changed (0.0 - 1.0)
+    VolumeChanged(f32),
     /// Playback finished (track ended)
     Finished,
     /// Request next track from playlist
@@ -79,17 +128,22 @@ pub enum AudioEvent {
     EqualizerUpdated(bool, Vec<f32>),
     /// Audio samples for visualization
     VisualizationData(Vec<f32>),
-    /// Error occurred
+    /// A track or stream could not be played; playback stopped
     Error(String),
+    /// A seek failed; playback carries on where it was
+    SeekFailed(String),
 }
 
 /// Track metadata information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TrackInfo {
     pub path: PathBuf,
     pub title: Option<String>,
     pub artist: Option<String>,
     pub album: Option<String>,
+    pub genre: Option<String>,
+    /// Star rating (0-5) from a `POPM` frame or an `FMPS_RATING` tag
+    pub rating: Option<u8>,
     pub duration_secs: Option<f32>,
     pub sample_rate: Option<u32>,
     pub channels: Option<u8>,
@@ -97,6 +151,13 @@ pub struct TrackInfo {
     pub codec: Option<String>,
     /// Bitrate in kbps
     pub bitrate: Option<u32>,
+    /// Start offset within the underlying file in seconds (CUE virtual tracks)
+    pub start_offset_secs: Option<f32>,
+    /// End offset within the underlying file in seconds (CUE virtual tracks)
+    pub end_offset_secs: Option<f32>,
+    /// Cover image, set for the playing track: a cached thumbnail of the
+    /// embedded front cover or of a folder image (see `album_art`)
+    pub cover_art: Option<PathBuf>,
 }
 
 impl TrackInfo {
@@ -111,7 +172,7 @@ impl TrackInfo {
             let clean_codec = codec
                 .replace("CODEC(", "")
                 .replace(")", "")
-                .replace("\"" , "")
+                .replace("\"", "")
                 .trim()
                 .to_string();
             parts.push(clean_codec);
@@ -144,7 +205,70 @@ impl TrackInfo {
     }
 
     /// Extract metadata from a file
-    pub fn from_file(path: &PathBuf) -> Result<Self> {
+    ///
+    /// Virtual track paths (`album.flac#track=3`) are resolved through the
+    /// album's CUE sheet.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        // Streams are only known once connected
+        if stream::is_stream_url(path) {
+            return Ok(TrackInfo {
+                path: path.to_path_buf(),
+                ..Default::default()
+            });
+        }
+        match cue::resolve_virtual_track(path)? {
+            Some(resolved) => {
+                let file_info = Self::probe_file(&resolved.track.file)?;
+                Ok(Self::from_cue_track(
+                    &file_info,
+                    &resolved.sheet,
+                    &resolved.track,
+                ))
+            }
+            None => Self::probe_file(path),
+        }
+    }
+
+    /// Build the virtual tracks described by a CUE sheet
+    pub fn from_cue_sheet(sheet: &CueSheet) -> Result<Vec<Self>> {
+        let mut tracks = Vec::with_capacity(sheet.tracks.len());
+        let mut file_info: Option<TrackInfo> = None;
+
+        for track in &sheet.tracks {
+            // Probe each underlying file only once
+            if file_info.as_ref().map(|info| &info.path) != Some(&track.file) {
+                file_info = Some(Self::probe_file(&track.file)?);
+            }
+            if let Some(ref info) = file_info {
+                tracks.push(Self::from_cue_track(info, sheet, track));
+            }
+        }
+
+        Ok(tracks)
+    }
+
+    /// Derive a virtual track's metadata from its file's metadata
+    fn from_cue_track(file_info: &TrackInfo, sheet: &CueSheet, track: &cue::CueTrack) -> Self {
+        let end = track.end_secs.or(file_info.duration_secs);
+
+        TrackInfo {
+            path: track.virtual_path(),
+            title: track.title.clone().or_else(|| file_info.title.clone()),
+            artist: track
+                .performer
+                .clone()
+                .or_else(|| sheet.performer.clone())
+                .or_else(|| file_info.artist.clone()),
+            album: sheet.title.clone().or_else(|| file_info.album.clone()),
+            duration_secs: end.map(|end| (end - track.start_secs).max(0.0)),
+            start_offset_secs: Some(track.start_secs),
+            end_offset_secs: track.end_secs,
+            ..file_info.clone()
+        }
+    }
+
+    /// Probe a real audio file for its metadata
+    fn probe_file(path: &Path) -> Result<Self> {
         let file = File::open(path).context("Failed to open audio file for metadata reading")?;
 
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
@@ -166,19 +290,33 @@ impl TrackInfo {
         let mut title = None;
         let mut artist = None;
         let mut album = None;
+        let mut genre = None;
+        let mut rating = None;
 
         // Get metadata from the format
         if let Some(metadata_rev) = format.metadata().current() {
             for tag in metadata_rev.tags() {
+                // RIFF INFO strings keep their NUL terminator
+                let value = || Some(tag.value.to_string().trim_end_matches('\0').to_string());
                 match tag.std_key {
                     Some(symphonia::core::meta::StandardTagKey::TrackTitle) => {
-                        title = Some(tag.value.to_string());
+                        title = value();
                     }
                     Some(symphonia::core::meta::StandardTagKey::Artist) => {
-                        artist = Some(tag.value.to_string());
+                        artist = value();
                     }
                     Some(symphonia::core::meta::StandardTagKey::Album) => {
-                        album = Some(tag.value.to_string());
+                        album = value();
+                    }
+                    Some(symphonia::core::meta::StandardTagKey::Genre) => {
+                        genre = value();
+                    }
+                    // FMPS ratings are more precise than POPM, prefer them
+                    _ if is_fmps_rating(&tag.key) => {
+                        rating = fmps_stars(&tag.value.to_string()).or(rating);
+                    }
+                    Some(symphonia::core::meta::StandardTagKey::Rating) if rating.is_none() => {
+                        rating = tag_stars(&tag.value);
                     }
                     _ => {}
                 }
@@ -212,19 +350,78 @@ impl TrackInfo {
         }
 
         Ok(TrackInfo {
-            path: path.clone(),
+            path: path.to_path_buf(),
             title,
             artist,
             album,
+            genre,
+            rating,
             duration_secs,
             sample_rate,
             channels,
             codec,
             bitrate,
+            start_offset_secs: None,
+            end_offset_secs: None,
+            cover_art: None,
         })
     }
 }
 
+/// Replace characters that can't appear in file names
+pub fn file_name_safe(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Leading dots would hide the file, trailing ones upset Windows
+    name.trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+/// `FMPS_RATING` Vorbis comments and `TXXX:FMPS_Rating` ID3 frames
+fn is_fmps_rating(key: &str) -> bool {
+    key.rsplit(':')
+        .next()
+        .is_some_and(|name| name.eq_ignore_ascii_case("FMPS_Rating"))
+}
+
+/// Stars from an FMPS rating, a fraction between 0.0 and 1.0
+fn fmps_stars(value: &str) -> Option<u8> {
+    let fraction: f32 = value.trim().parse().ok()?;
+    (0.0..=1.0)
+        .contains(&fraction)
+        .then(|| (fraction * 5.0).round() as u8)
+}
+
+/// Stars from a `POPM` byte (0 unrated, 1-255 as Windows Media Player maps
+/// them) or a `RATING` comment on a 5 or 100 point scale
+fn tag_stars(value: &symphonia::core::meta::Value) -> Option<u8> {
+    use symphonia::core::meta::Value;
+    match value {
+        Value::UnsignedInt(byte) => match byte {
+            0 => None,
+            1..=31 => Some(1),
+            32..=95 => Some(2),
+            96..=159 => Some(3),
+            160..=223 => Some(4),
+            _ => Some(5),
+        },
+        value => {
+            let rating: f32 = value.to_string().trim().parse().ok()?;
+            match rating {
+                r if (0.0..=5.0).contains(&r) => Some(r.round() as u8),
+                r if (0.0..=100.0).contains(&r) => Some((r / 20.0).round() as u8),
+                _ => None,
+            }
+        }
+    }
+}
+
 /// Audio engine that runs in a separate thread
 pub struct AudioEngine {
     command_tx: Sender<AudioCommand>,
@@ -265,6 +462,11 @@ impl AudioEngine {
         self.event_rx.try_recv().ok()
     }
 
+    /// Event channel, for waiting on events alongside other channels
+    pub fn events(&self) -> &Receiver<AudioEvent> {
+        &self.event_rx
+    }
+
     /// Shutdown the audio engine
     pub fn shutdown(mut self) -> Result<()> {
         self.send_command(AudioCommand::Shutdown)?;
@@ -298,6 +500,7 @@ fn audio_thread_main(
     let mut sink: Option<Sink> = None;
     let mut current_track: Option<TrackInfo> = None;
     let mut is_paused = false;
+    let mut volume = 1.0;
 
     // Create equalizer (shared between audio processing and command handling)
     let equalizer = std::sync::Arc::new(std::sync::Mutex::new(Equalizer::new(44100.0)));
@@ -348,6 +551,39 @@ fn audio_thread_main(
                         }
                     }
                 }
+                AudioCommand::Load(path, _position) => {
+                    // rodio can't seek, so the track is loaded paused at the start
+                    sink = None;
+
+                    match TrackInfo::from_file(&path) {
+                        Ok(track_info) => {
+                            current_track = Some(track_info.clone());
+                            let _ = event_tx.send(AudioEvent::TrackLoaded(track_info));
+
+                            match load_and_play(
+                                &path,
+                                &stream_handle,
+                                equalizer.clone(),
+                                capture_buffer.clone(),
+                            ) {
+                                Ok(new_sink) => {
+                                    new_sink.pause();
+                                    sink = Some(new_sink);
+                                    is_paused = true;
+                                    let _ = event_tx.send(AudioEvent::Paused);
+                                }
+                                Err(e) => {
+                                    let _ = event_tx
+                                        .send(AudioEvent::Error(format!("Failed to load: {}", e)));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let _ = event_tx
+                                .send(AudioEvent::Error(format!("Failed to load track: {}", e)));
+                        }
+                    }
+                }
                 AudioCommand::Pause => {
                     if let Some(ref s) = sink {
                         if !is_paused {
@@ -402,7 +638,7 @@ fn audio_thread_main(
                             }
                             Err(e) => {
                                 let _ = event_tx
-                                    .send(AudioEvent::Error(format!("Failed to seek: {}", e)));
+                                    .send(AudioEvent::SeekFailed(format!("Failed to seek: {}", e)));
                             }
                         }
                     }
@@ -452,6 +688,12 @@ fn audio_thread_main(
                         let _ = event_tx.send(AudioEvent::EqualizerUpdated(enabled, gains));
                     }
                 }
+                AudioCommand::SetVolume(new_volume) => {
+                    volume = new_volume.clamp(0.0, 1.0);
+                    let _ = event_tx.send(AudioEvent::VolumeChanged(volume));
+                }
+                // Only the Symphonia audio thread plays streams
+                AudioCommand::SetStreamOptions(_) => {}
                 AudioCommand::Shutdown => {
                     break;
                 }
@@ -460,6 +702,10 @@ fn audio_thread_main(
 
         // Update playback position
         if let Some(ref s) = sink {
+            // New sinks start at full volume
+            if s.volume() != volume {
+                s.set_volume(volume);
+            }
             if s.empty() {
                 // Track finished
                 sink = None;
@@ -520,6 +766,12 @@ fn load_and_play(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_name_safe() {
+        assert_eq!(file_name_safe("AC/DC: Live?"), "AC_DC_ Live_");
+        assert_eq!(file_name_safe("..hidden. "), "hidden");
+    }
+
     #[test]
     fn test_audio_engine_creation() {
         // Test that AudioEngine can be created
@@ -599,6 +851,7 @@ mod tests {
             duration_secs: Some(180.0),
             sample_rate: Some(44100),
             channels: Some(2),
+            ..Default::default()
         };
 
         assert_eq!(track.title, Some("Test Track".to_string()));
@@ -606,6 +859,22 @@ mod tests {
         assert_eq!(track.channels, Some(2));
     }
 
+    #[test]
+    fn test_tag_ratings() {
+        use symphonia::core::meta::Value;
+        assert_eq!(tag_stars(&Value::UnsignedInt(0)), None);
+        assert_eq!(tag_stars(&Value::UnsignedInt(1)), Some(1));
+        assert_eq!(tag_stars(&Value::UnsignedInt(128)), Some(3));
+        assert_eq!(tag_stars(&Value::UnsignedInt(255)), Some(5));
+        assert_eq!(tag_stars(&Value::from("4")), Some(4));
+        assert_eq!(tag_stars(&Value::from("80")), Some(4));
+        assert_eq!(fmps_stars("0.6"), Some(3));
+        assert_eq!(fmps_stars("1.5"), None);
+        assert!(is_fmps_rating("FMPS_RATING"));
+        assert!(is_fmps_rating("TXXX:FMPS_Rating"));
+        assert!(!is_fmps_rating("FMPS_PLAYCOUNT"));
+    }
+
     #[test]
     fn test_audio_capture_buffer() {
         // Test AudioCaptureBuffer
@@ -0,0 +1,144 @@
+// Music Library Index
+// Recursively scans a music directory and keeps the metadata of every track,
+// so front-ends can browse and query by tag without probing files again.
+
+use crate::{cue, TrackInfo};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File extensions treated as audio when scanning
+pub const AUDIO_EXTENSIONS: [&str; 4] = ["mp3", "flac", "ogg", "wav"];
+
+/// Check whether a path has a supported audio extension
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Tracks found under a music directory, sorted by path
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    root: PathBuf,
+    tracks: Vec<TrackInfo>,
+    index: HashMap<PathBuf, usize>,
+}
+
+impl Library {
+    /// An empty library for `root`, e.g. while the first scan is running
+    pub fn empty(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Scan `root` recursively. Albums with a CUE sheet are indexed as one
+    /// entry per virtual track; unreadable files are skipped.
+    pub fn scan(root: &Path) -> Result<Self> {
+        let files = audio_files_in(root)
+            .with_context(|| format!("Failed to read music directory {}", root.display()))?;
+
+        let mut library = Self::empty(root);
+        for file in files {
+            for entry in cue::expand_playlist_entry(&file) {
+                if let Ok(track) = TrackInfo::from_file(&entry) {
+                    library.index.insert(entry, library.tracks.len());
+                    library.tracks.push(track);
+                }
+            }
+        }
+        Ok(library)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn tracks(&self) -> &[TrackInfo] {
+        &self.tracks
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Metadata of an indexed track
+    pub fn get(&self, path: &Path) -> Option<&TrackInfo> {
+        self.index.get(path).map(|&i| &self.tracks[i])
+    }
+
+    /// Path of a track relative to the library root, if it is inside it
+    pub fn relative_path<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.root).ok()
+    }
+
+    /// Tracks in a directory (or the single track at a path), relative paths
+    /// being taken from the library root
+    pub fn tracks_under(&self, path: &Path) -> impl Iterator<Item = &TrackInfo> {
+        let path = self.root.join(path);
+        self.tracks.iter().filter(move |track| {
+            let (file, _) = cue::split_virtual_track(&track.path);
+            track.path == path || file.starts_with(&path)
+        })
+    }
+}
+
+/// Audio files under a directory, recursively, sorted by path
+pub fn audio_files_in(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_audio_files(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_audio_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            // Unreadable subdirectories don't fail the whole scan
+            let _ = collect_audio_files(&path, files);
+        } else if is_audio_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::write_silent;
+
+    #[test]
+    fn test_scan_recurses_and_skips_other_files() {
+        let root = std::env::temp_dir().join("oneamp_library_test_scan");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("artist/album")).unwrap();
+        write_silent(&root.join("single.wav"), 800);
+        write_silent(&root.join("artist/album/01.wav"), 8000);
+        write_silent(&root.join("artist/album/02.WAV"), 8000);
+        std::fs::write(root.join("artist/album/notes.txt"), "liner notes").unwrap();
+        std::fs::write(root.join("artist/album/broken.mp3"), "not audio").unwrap();
+
+        let library = Library::scan(&root).unwrap();
+        assert_eq!(library.len(), 3);
+        assert_eq!(library.tracks()[0].path, root.join("artist/album/01.wav"));
+
+        let album: Vec<&TrackInfo> = library.tracks_under(Path::new("artist")).collect();
+        assert_eq!(album.len(), 2);
+        let track = library.get(&root.join("single.wav")).unwrap();
+        assert!((track.duration_secs.unwrap() - 0.1).abs() < 0.01);
+        assert_eq!(
+            library.relative_path(&track.path),
+            Some(Path::new("single.wav"))
+        );
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,661 @@
+// Lyrics
+// Plain and time-synchronized lyrics from a sidecar `.lrc` file, embedded
+// ID3 `SYLT`/`USLT` frames or `LYRICS` Vorbis comments. LRC files may use
+// the enhanced format with a timestamp per word (`<mm:ss.xx>`), which is
+// kept for karaoke-style highlighting. `LyricsTracker` looks them up in the
+// background, follows playback and reports the line being sung.
+
+use crate::{cue, stream, AudioEvent};
+use crossbeam_channel::Receiver;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+/// Roughly how far the decoder runs ahead of what is heard: the output
+/// keeps about a quarter of a second of samples queued
+pub const DEFAULT_LATENCY_SECS: f32 = 0.25;
+
+/// Positions arrive ten times a second; don't run further ahead than this
+/// when they stop coming, e.g. while a stream buffers
+const MAX_EXTRAPOLATION_SECS: f32 = 1.0;
+
+/// Where lyrics were found
+#[derive(Debug, Clone, PartialEq)]
+pub enum LyricsSource {
+    /// `.lrc` file next to the audio file
+    LrcFile(PathBuf),
+    /// ID3 `SYLT` frame
+    SyncedTag,
+    /// ID3 `USLT` frame or `LYRICS` comment, possibly holding LRC text
+    Tag,
+}
+
+/// A timed word of an enhanced LRC line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricWord {
+    /// Seconds from the start of the track
+    pub time: f32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    /// Seconds from the start of the track, `None` for plain lyrics
+    pub time: Option<f32>,
+    pub text: String,
+    /// Per-word timing, empty unless the lyrics have it
+    pub words: Vec<LyricWord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lyrics {
+    /// Lines in order; either all or none of them are timed
+    pub lines: Vec<LyricLine>,
+    pub source: LyricsSource,
+}
+
+impl Lyrics {
+    /// Find the lyrics of a file: a sidecar `.lrc` first, then synchronized
+    /// and plain lyrics in its tags. Streams and CUE tracks have none.
+    pub fn find(path: &Path) -> Option<Self> {
+        if stream::is_stream_url(path) || cue::split_virtual_track(path).1.is_some() {
+            return None;
+        }
+
+        for extension in ["lrc", "LRC"] {
+            let lrc = path.with_extension(extension);
+            if let Ok(text) = std::fs::read_to_string(&lrc) {
+                let lines = parse_lrc(&text);
+                if !lines.is_empty() {
+                    return Some(Self {
+                        lines,
+                        source: LyricsSource::LrcFile(lrc),
+                    });
+                }
+            }
+        }
+
+        if let Some(lines) = sylt_lines(path) {
+            return Some(Self {
+                lines,
+                source: LyricsSource::SyncedTag,
+            });
+        }
+
+        let lines = parse_lrc(&tag_lyrics(path)?);
+        (!lines.is_empty()).then_some(Self {
+            lines,
+            source: LyricsSource::Tag,
+        })
+    }
+
+    /// Parse LRC text; text without timestamps becomes plain lyrics
+    pub fn parse(text: &str, source: LyricsSource) -> Self {
+        Self {
+            lines: parse_lrc(text),
+            source,
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.first().is_some_and(|line| line.time.is_some())
+    }
+
+    /// Index of the line being sung at `secs`, `None` before the first one
+    /// or for plain lyrics
+    pub fn line_at(&self, secs: f32) -> Option<usize> {
+        let count = self
+            .lines
+            .partition_point(|line| line.time.is_some_and(|time| time <= secs));
+        count.checked_sub(1)
+    }
+
+    /// Index of the word of `line` being sung at `secs`
+    pub fn word_at(&self, line: usize, secs: f32) -> Option<usize> {
+        let words = &self.lines.get(line)?.words;
+        words
+            .partition_point(|word| word.time <= secs)
+            .checked_sub(1)
+    }
+}
+
+/// Parse LRC lines. Lines may carry several timestamps and are sorted by
+/// time; `[offset:ms]` moves every line, positive values earlier. When
+/// nothing is timed the text is returned as plain lines.
+fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut offset = 0.0;
+    let mut timed = Vec::new();
+    let mut plain = Vec::new();
+
+    for raw in text.lines() {
+        let raw = raw.trim();
+        let mut rest = raw;
+        let mut times = Vec::new();
+        while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            } else if let Some((name, value)) = tag.split_once(':') {
+                // ID tags: [ar:Artist], [offset:+250], ...
+                if name.trim().eq_ignore_ascii_case("offset") {
+                    offset = value.trim().parse::<f32>().unwrap_or(0.0) / 1000.0;
+                }
+            } else {
+                break;
+            }
+            rest = after;
+        }
+
+        if times.is_empty() {
+            if rest.len() == raw.len() {
+                plain.push(raw);
+            }
+            continue;
+        }
+        let (text, words) = parse_words(rest);
+        for time in times {
+            timed.push(LyricLine {
+                time: Some(time),
+                text: text.clone(),
+                words: words.clone(),
+            });
+        }
+    }
+
+    if timed.is_empty() {
+        // Trim blank lines around plain lyrics, keep the ones between verses
+        let start = plain.iter().position(|line| !line.is_empty());
+        let end = plain.iter().rposition(|line| !line.is_empty());
+        let (Some(start), Some(end)) = (start, end) else {
+            return Vec::new();
+        };
+        return plain[start..=end]
+            .iter()
+            .map(|line| LyricLine {
+                time: None,
+                text: line.to_string(),
+                words: Vec::new(),
+            })
+            .collect();
+    }
+
+    for line in &mut timed {
+        line.time = line.time.map(|time| (time - offset).max(0.0));
+        for word in &mut line.words {
+            word.time = (word.time - offset).max(0.0);
+        }
+    }
+    timed.sort_by(|a, b| {
+        a.time
+            .partial_cmp(&b.time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    timed
+}
+
+/// Split an enhanced LRC line into its text and timed words
+fn parse_words(line: &str) -> (String, Vec<LyricWord>) {
+    let mut text = String::new();
+    let mut words: Vec<LyricWord> = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let timed = rest
+            .strip_prefix('<')
+            .and_then(|r| r.split_once('>'))
+            .and_then(|(tag, after)| Some((parse_timestamp(tag)?, after)));
+        if let Some((time, after)) = timed {
+            words.push(LyricWord {
+                time,
+                text: String::new(),
+            });
+            rest = after;
+            continue;
+        }
+        let end = rest
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c == '<')
+            .map_or(rest.len(), |(i, _)| i);
+        let chunk = &rest[..end];
+        text.push_str(chunk);
+        if let Some(word) = words.last_mut() {
+            word.text.push_str(chunk);
+        }
+        rest = &rest[end..];
+    }
+    // A closing timestamp only marks where the last word ends
+    words.retain(|word| !word.text.is_empty());
+    (text.trim().to_string(), words)
+}
+
+/// `mm:ss`, `mm:ss.xx`, `mm:ss:xx` or `hh:mm:ss.xx`, in seconds
+fn parse_timestamp(tag: &str) -> Option<f32> {
+    let tag = tag.trim();
+    let parts: Vec<&str> = tag.split(':').collect();
+    let number = |s: &str| -> Option<f32> {
+        (!s.is_empty() && s.chars().all(|c| c.is_ascii_digit() || c == '.'))
+            .then(|| s.parse().ok())
+            .flatten()
+    };
+    match parts[..] {
+        [minutes, seconds] => Some(number(minutes)? * 60.0 + number(seconds)?),
+        [hours, minutes, seconds] if seconds.contains('.') => {
+            Some(number(hours)? * 3600.0 + number(minutes)? * 60.0 + number(seconds)?)
+        }
+        // mm:ss:xx, hundredths after a colon
+        [minutes, seconds, hundredths] => {
+            Some(number(minutes)? * 60.0 + number(seconds)? + number(hundredths)? / 100.0)
+        }
+        _ => None,
+    }
+}
+
+/// Lyrics from an ID3 `SYLT` frame with millisecond timestamps
+fn sylt_lines(path: &Path) -> Option<Vec<LyricLine>> {
+    use id3::frame::{SynchronisedLyricsType, TimestampFormat};
+
+    let tag = id3::Tag::read_from_path(path).ok()?;
+    let frame = tag
+        .synchronised_lyrics()
+        .filter(|sylt| sylt.timestamp_format == TimestampFormat::Ms)
+        .max_by_key(|sylt| sylt.content_type == SynchronisedLyricsType::Lyrics)?;
+
+    // Entries are whole lines, unless some start with a line break: then
+    // they are words and syllables, the breaks starting new lines
+    let per_word = frame
+        .content
+        .iter()
+        .any(|(_, text)| text.starts_with(['\n', '\r']));
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (ms, text) in &frame.content {
+        let time = *ms as f32 / 1000.0;
+        let new_line = !per_word || text.starts_with(['\n', '\r']) || lines.is_empty();
+        let text = text.trim_start_matches(['\n', '\r']);
+        if new_line {
+            lines.push(LyricLine {
+                time: Some(time),
+                text: String::new(),
+                words: Vec::new(),
+            });
+        }
+        let line = lines.last_mut()?;
+        line.text.push_str(text);
+        if per_word {
+            line.words.push(LyricWord {
+                time,
+                text: text.to_string(),
+            });
+        }
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+    }
+    lines.retain(|line| !line.text.is_empty());
+    (!lines.is_empty()).then_some(lines)
+}
+
+/// Text of a `USLT` frame or a `LYRICS`/`UNSYNCEDLYRICS` comment
+fn tag_lyrics(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    let find = |revision: &MetadataRevision| {
+        revision
+            .tags()
+            .iter()
+            .find(|tag| tag.std_key == Some(StandardTagKey::Lyrics))
+            .map(|tag| tag.value.to_string())
+    };
+    // ID3v2 tags in front of MP3 files are read by the probe
+    let from_probe = probed
+        .metadata
+        .get()
+        .and_then(|m| m.current().and_then(find));
+    from_probe.or_else(|| probed.format.metadata().current().and_then(find))
+}
+
+/// What `LyricsTracker` noticed
+#[derive(Debug, Clone, PartialEq)]
+pub enum LyricsEvent {
+    /// Lyrics of the new track, if any
+    Loaded(Option<Lyrics>),
+    /// The line being sung changed
+    Line(Option<usize>),
+}
+
+/// Follows playback to tell which lyrics line is being sung. Positions
+/// come from the decoder, which is ahead of the speakers by the output
+/// latency; between `Position` events the position is extrapolated with
+/// the wall clock.
+pub struct LyricsTracker {
+    lyrics: Option<Lyrics>,
+    /// Lyrics of the loaded track being looked up
+    loading: Option<Receiver<Option<Lyrics>>>,
+    latency_secs: f32,
+    /// Last reported position and when it arrived, while playing
+    position: Option<(f32, Instant)>,
+    paused_at: Option<f32>,
+    line: Option<usize>,
+}
+
+impl Default for LyricsTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_LATENCY_SECS)
+    }
+}
+
+impl LyricsTracker {
+    pub fn new(latency_secs: f32) -> Self {
+        Self {
+            lyrics: None,
+            loading: None,
+            latency_secs,
+            position: None,
+            paused_at: None,
+            line: None,
+        }
+    }
+
+    /// Seconds subtracted from decoder positions; larger values show lines
+    /// later
+    pub fn set_latency(&mut self, latency_secs: f32) {
+        self.latency_secs = latency_secs;
+    }
+
+    pub fn lyrics(&self) -> Option<&Lyrics> {
+        self.lyrics.as_ref()
+    }
+
+    /// Line being sung
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Position heard at `now`, compensated for the output latency
+    pub fn position_at(&self, now: Instant) -> Option<f32> {
+        let heard = match (self.position, self.paused_at) {
+            (Some((position, at)), _) => {
+                let elapsed = now.saturating_duration_since(at).as_secs_f32();
+                position + elapsed.min(MAX_EXTRAPOLATION_SECS)
+            }
+            (None, Some(position)) => position,
+            (None, None) => return None,
+        };
+        Some((heard - self.latency_secs).max(0.0))
+    }
+
+    /// Feed a player event. Loading a track looks up its lyrics on another
+    /// thread; `update` reports them once found.
+    pub fn handle_event(&mut self, event: &AudioEvent) -> Option<LyricsEvent> {
+        match event {
+            AudioEvent::TrackLoaded(track) => {
+                self.set_lyrics(None);
+                self.loading = Some(find_in_background(track.path.clone()));
+                None
+            }
+            AudioEvent::Position(position, _) => {
+                self.position = Some((*position, Instant::now()));
+                self.paused_at = None;
+                self.update(Instant::now())
+            }
+            AudioEvent::Paused => {
+                self.paused_at = self.position.take().map(|(position, at)| {
+                    position + at.elapsed().as_secs_f32().min(MAX_EXTRAPOLATION_SECS)
+                });
+                None
+            }
+            AudioEvent::Stopped | AudioEvent::Finished => {
+                self.position = None;
+                self.paused_at = None;
+                self.update(Instant::now())
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace the lyrics, e.g. with ones loaded elsewhere
+    pub fn set_lyrics(&mut self, lyrics: Option<Lyrics>) {
+        self.lyrics = lyrics;
+        self.loading = None;
+        self.position = None;
+        self.paused_at = None;
+        self.line = None;
+    }
+
+    /// Collect lyrics looked up for the loaded track, else move to the line
+    /// playing at `now`. Call this every frame to follow the lyrics between
+    /// position updates.
+    pub fn update(&mut self, now: Instant) -> Option<LyricsEvent> {
+        if let Some(lyrics) = self.loading.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.loading = None;
+            self.lyrics = lyrics;
+            self.line = None;
+            return Some(LyricsEvent::Loaded(self.lyrics.clone()));
+        }
+
+        let line = self
+            .position_at(now)
+            .and_then(|secs| self.lyrics.as_ref()?.line_at(secs));
+        (line != self.line).then(|| {
+            self.line = line;
+            LyricsEvent::Line(line)
+        })
+    }
+}
+
+/// Look up the lyrics of a track on another thread; reading the sidecar
+/// and probing the tags can take a while on a slow disk
+fn find_in_background(path: PathBuf) -> Receiver<Option<Lyrics>> {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let find = move || {
+        let _ = tx.send(Lyrics::find(&path));
+    };
+    // Without a thread, look them up here rather than not at all
+    let spawned = std::thread::Builder::new()
+        .name("oneamp-lyrics".to_string())
+        .spawn(find.clone());
+    if spawned.is_err() {
+        find();
+    }
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_lrc() {
+        let lyrics = Lyrics::parse(
+            "[ti:Song]\n[ar:Artist]\n[offset:+500]\n\
+             [00:12.00]First line\n\
+             [00:20.50][01:05.25]Chorus\n\
+             [00:15.30]Second line\n\
+             [1:30]Late line\n",
+            LyricsSource::Tag,
+        );
+        assert!(lyrics.is_synced());
+        let timed: Vec<_> = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.time.unwrap(), line.text.as_str()))
+            .collect();
+        assert_eq!(
+            timed,
+            [
+                (11.5, "First line"),
+                (14.8, "Second line"),
+                (20.0, "Chorus"),
+                (64.75, "Chorus"),
+                (89.5, "Late line"),
+            ]
+        );
+
+        assert_eq!(lyrics.line_at(5.0), None);
+        assert_eq!(lyrics.line_at(11.5), Some(0));
+        assert_eq!(lyrics.line_at(30.0), Some(2));
+        assert_eq!(lyrics.line_at(600.0), Some(4));
+    }
+
+    #[test]
+    fn test_enhanced_lrc() {
+        let lyrics = Lyrics::parse(
+            "[00:10.00]<00:10.00>Never <00:10.50>gonna <00:11.00>give <00:11.80>",
+            LyricsSource::Tag,
+        );
+        let line = &lyrics.lines[0];
+        assert_eq!(line.text, "Never gonna give");
+        let words: Vec<_> = line
+            .words
+            .iter()
+            .map(|w| (w.time, w.text.as_str()))
+            .collect();
+        assert_eq!(words, [(10.0, "Never "), (10.5, "gonna "), (11.0, "give ")]);
+        assert_eq!(lyrics.word_at(0, 9.0), None);
+        assert_eq!(lyrics.word_at(0, 10.7), Some(1));
+        assert_eq!(lyrics.word_at(0, 20.0), Some(2));
+    }
+
+    #[test]
+    fn test_plain_lyrics() {
+        let lyrics = Lyrics::parse("\nVerse one\n\nVerse two\n\n", LyricsSource::Tag);
+        assert!(!lyrics.is_synced());
+        let text: Vec<_> = lyrics.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, ["Verse one", "", "Verse two"]);
+        assert_eq!(lyrics.line_at(100.0), None);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(parse_timestamp("01:02.50"), Some(62.5));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62.5));
+        assert_eq!(parse_timestamp("1:01:02.5"), Some(3662.5));
+        assert_eq!(parse_timestamp("ar:Artist"), None);
+        assert_eq!(parse_timestamp("offset:+200"), None);
+    }
+
+    #[test]
+    fn test_sidecar_and_tags() {
+        let dir = std::env::temp_dir().join("oneamp_lyrics_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("song.mp3");
+        std::fs::write(&audio, b"").unwrap();
+        assert_eq!(Lyrics::find(&audio), None);
+
+        // SYLT with a word per entry, line breaks starting new lines
+        let mut tag = id3::Tag::new();
+        id3::TagLike::add_frame(
+            &mut tag,
+            id3::frame::SynchronisedLyrics {
+                lang: "eng".to_string(),
+                timestamp_format: id3::frame::TimestampFormat::Ms,
+                content_type: id3::frame::SynchronisedLyricsType::Lyrics,
+                description: String::new(),
+                content: vec![
+                    (1000, "Hello ".to_string()),
+                    (1500, "world".to_string()),
+                    (3000, "\nBye".to_string()),
+                ],
+            },
+        );
+        tag.write_to_path(&audio, id3::Version::Id3v24).unwrap();
+        let lyrics = Lyrics::find(&audio).unwrap();
+        assert_eq!(lyrics.source, LyricsSource::SyncedTag);
+        let lines: Vec<_> = lyrics
+            .lines
+            .iter()
+            .map(|l| (l.time, l.text.as_str()))
+            .collect();
+        assert_eq!(lines, [(Some(1.0), "Hello world"), (Some(3.0), "Bye")]);
+        assert_eq!(lyrics.lines[0].words.len(), 2);
+
+        // A sidecar file wins
+        let lrc = dir.join("song.lrc");
+        std::fs::write(&lrc, "[00:01.00]From the file").unwrap();
+        let lyrics = Lyrics::find(&audio).unwrap();
+        assert_eq!(lyrics.source, LyricsSource::LrcFile(lrc));
+        assert_eq!(lyrics.lines[0].text, "From the file");
+
+        assert_eq!(Lyrics::find(Path::new("http://radio.example/live")), None);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_tracker_loads_lyrics_in_background() {
+        let dir = std::env::temp_dir().join("oneamp_lyrics_test_tracker");
+        std::fs::create_dir_all(&dir).unwrap();
+        let audio = dir.join("song.mp3");
+        std::fs::write(&audio, b"").unwrap();
+        std::fs::write(dir.join("song.lrc"), "[00:01.00]Found later").unwrap();
+
+        let mut tracker = LyricsTracker::default();
+        let track = crate::TrackInfo {
+            path: audio,
+            ..Default::default()
+        };
+        assert_eq!(tracker.handle_event(&AudioEvent::TrackLoaded(track)), None);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let lyrics = loop {
+            assert!(Instant::now() < deadline, "lyrics lookup timed out");
+            if let Some(LyricsEvent::Loaded(lyrics)) = tracker.update(Instant::now()) {
+                break lyrics;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(lyrics.unwrap().lines[0].text, "Found later");
+        assert!(tracker.lyrics().is_some());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_tracker_compensates_latency() {
+        let mut tracker = LyricsTracker::new(0.5);
+        tracker.set_lyrics(Some(Lyrics::parse(
+            "[00:01.00]One\n[00:02.00]Two\n[00:03.00]Three",
+            LyricsSource::Tag,
+        )));
+
+        assert_eq!(tracker.handle_event(&AudioEvent::Position(1.2, 10.0)), None);
+        assert_eq!(
+            tracker.handle_event(&AudioEvent::Position(1.6, 10.0)),
+            Some(LyricsEvent::Line(Some(0)))
+        );
+
+        // Between updates the clock moves the lyrics on
+        let now = Instant::now();
+        assert_eq!(
+            tracker.update(now + Duration::from_millis(1000)),
+            Some(LyricsEvent::Line(Some(1)))
+        );
+        assert_eq!(tracker.update(now + Duration::from_millis(1100)), None);
+
+        // Seeking back goes back a line, stopping clears it
+        assert_eq!(
+            tracker.handle_event(&AudioEvent::Position(0.5, 10.0)),
+            Some(LyricsEvent::Line(None))
+        );
+        tracker.handle_event(&AudioEvent::Position(2.6, 10.0));
+        assert_eq!(tracker.line(), Some(1));
+        assert_eq!(
+            tracker.handle_event(&AudioEvent::Stopped),
+            Some(LyricsEvent::Line(None))
+        );
+    }
+}
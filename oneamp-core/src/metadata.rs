@@ -0,0 +1,374 @@
+// Background Metadata Scanning
+// Probes tracks on a pool of worker threads so playlists can show titles and
+// durations without blocking the caller. Results are cached by path and
+// revalidated against the file's size and modification time.
+
+use crate::{cue, TrackInfo};
+use crossbeam_channel::{select, Receiver, Sender};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+
+/// Upper bound on worker threads; probing is mostly I/O bound
+const MAX_WORKERS: usize = 4;
+
+/// Size and modification time of a file, used to tell whether a cached
+/// probe is still valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+}
+
+impl FileStamp {
+    /// Stamp of the file behind a playlist entry (the underlying file for
+    /// CUE virtual tracks)
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let (file, _) = cue::split_virtual_track(path);
+        let metadata = std::fs::metadata(file)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Result of probing a track
+#[derive(Debug, Clone)]
+pub enum TrackMetadata {
+    Ready(TrackInfo),
+    /// The file could not be read or decoded; holds the probe error
+    Failed(String),
+}
+
+impl TrackMetadata {
+    fn probe(path: &Path) -> Self {
+        match TrackInfo::from_file(path) {
+            Ok(info) => Self::Ready(info),
+            Err(e) => Self::Failed(format!("{:#}", e)),
+        }
+    }
+
+    pub fn info(&self) -> Option<&TrackInfo> {
+        match self {
+            Self::Ready(info) => Some(info),
+            Self::Failed(_) => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Ready(_) => None,
+            Self::Failed(error) => Some(error),
+        }
+    }
+
+    pub fn duration_secs(&self) -> Option<f32> {
+        self.info().and_then(|info| info.duration_secs)
+    }
+}
+
+/// Sum of the known durations of a set of tracks
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DurationTotal {
+    pub secs: f64,
+    /// Tracks not probed yet, unreadable or without a duration
+    pub unknown: usize,
+}
+
+struct Request {
+    path: PathBuf,
+    /// Stamp of the cached result, if any: the worker skips the probe when
+    /// the file still matches it
+    known: Option<FileStamp>,
+}
+
+enum Response {
+    Probed {
+        path: PathBuf,
+        stamp: Option<FileStamp>,
+        metadata: Box<TrackMetadata>,
+    },
+    Unchanged {
+        path: PathBuf,
+    },
+}
+
+struct CacheEntry {
+    stamp: Option<FileStamp>,
+    metadata: TrackMetadata,
+}
+
+/// Probes track metadata in the background and caches the results.
+///
+/// Call [`MetadataService::poll`] regularly (e.g. once per frame) to collect
+/// finished probes. Dropping the service stops the workers once they finish
+/// the file they are on.
+pub struct MetadataService {
+    /// Tracks someone is looking at right now, probed first
+    urgent: Sender<Request>,
+    background: Sender<Request>,
+    results: Receiver<Response>,
+    cache: HashMap<PathBuf, CacheEntry>,
+    pending: HashSet<PathBuf>,
+    generation: u64,
+}
+
+impl MetadataService {
+    /// Create a service with one worker per CPU (up to 4)
+    pub fn new() -> Self {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+        Self::with_workers(workers)
+    }
+
+    pub fn with_workers(workers: usize) -> Self {
+        let (urgent_tx, urgent_rx) = crossbeam_channel::unbounded::<Request>();
+        let (background_tx, background_rx) = crossbeam_channel::unbounded::<Request>();
+        let (result_tx, result_rx) = crossbeam_channel::unbounded();
+
+        for i in 0..workers.max(1) {
+            let urgent = urgent_rx.clone();
+            let background = background_rx.clone();
+            let results = result_tx.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("oneamp-metadata-{}", i))
+                .spawn(move || worker_main(urgent, background, results));
+            if let Err(e) = spawned {
+                eprintln!("Failed to start metadata worker: {}", e);
+            }
+        }
+
+        Self {
+            urgent: urgent_tx,
+            background: background_tx,
+            results: result_rx,
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            generation: 0,
+        }
+    }
+
+    /// Probe a track ahead of queued background work, unless it is already
+    /// cached or on its way
+    pub fn request(&mut self, path: &Path) {
+        if !self.cache.contains_key(path) && self.pending.insert(path.to_path_buf()) {
+            let _ = self.urgent.send(Request {
+                path: path.to_path_buf(),
+                known: None,
+            });
+        }
+    }
+
+    /// Queue tracks for background probing, skipping cached and pending ones
+    pub fn scan<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        for path in paths {
+            if !self.cache.contains_key(path) && self.pending.insert(path.to_path_buf()) {
+                let _ = self.background.send(Request {
+                    path: path.to_path_buf(),
+                    known: None,
+                });
+            }
+        }
+    }
+
+    /// Check cached tracks against their files and probe again the ones
+    /// that changed
+    pub fn revalidate(&mut self) {
+        for (path, entry) in &self.cache {
+            if self.pending.insert(path.clone()) {
+                let _ = self.background.send(Request {
+                    path: path.clone(),
+                    known: entry.stamp,
+                });
+            }
+        }
+    }
+
+    /// Probe a track on the calling thread if it isn't cached yet
+    pub fn probe_now(&mut self, path: &Path) -> &TrackMetadata {
+        if !self.cache.contains_key(path) {
+            let stamp = FileStamp::of(path).ok();
+            let metadata = TrackMetadata::probe(path);
+            self.insert(path.to_path_buf(), stamp, metadata);
+        }
+        &self.cache[path].metadata
+    }
+
+    /// Collect finished probes. Returns `true` if any cached metadata changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(response) = self.results.try_recv() {
+            match response {
+                Response::Probed {
+                    path,
+                    stamp,
+                    metadata,
+                } => {
+                    self.pending.remove(&path);
+                    self.insert(path, stamp, *metadata);
+                    changed = true;
+                }
+                Response::Unchanged { path } => {
+                    self.pending.remove(&path);
+                }
+            }
+        }
+        changed
+    }
+
+    fn insert(&mut self, path: PathBuf, stamp: Option<FileStamp>, metadata: TrackMetadata) {
+        self.cache.insert(path, CacheEntry { stamp, metadata });
+        self.generation += 1;
+    }
+
+    /// Cached metadata for a track, if it has been probed
+    pub fn get(&self, path: &Path) -> Option<&TrackMetadata> {
+        self.cache.get(path).map(|entry| &entry.metadata)
+    }
+
+    pub fn is_pending(&self, path: &Path) -> bool {
+        self.pending.contains(path)
+    }
+
+    /// Number of tracks waiting to be probed
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Changes whenever cached metadata changes, so callers can tell when
+    /// derived data (filters, totals) needs recomputing
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Add up the durations of the given tracks
+    pub fn total_duration<'a>(&self, paths: impl IntoIterator<Item = &'a Path>) -> DurationTotal {
+        let mut total = DurationTotal::default();
+        for path in paths {
+            match self.get(path).and_then(TrackMetadata::duration_secs) {
+                Some(secs) => total.secs += f64::from(secs),
+                None => total.unknown += 1,
+            }
+        }
+        total
+    }
+}
+
+impl Default for MetadataService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn worker_main(
+    urgent: Receiver<Request>,
+    background: Receiver<Request>,
+    results: Sender<Response>,
+) {
+    while let Some(request) = next_request(&urgent, &background) {
+        let stamp = FileStamp::of(&request.path).ok();
+        let response = if stamp.is_some() && stamp == request.known {
+            Response::Unchanged { path: request.path }
+        } else {
+            let metadata = Box::new(TrackMetadata::probe(&request.path));
+            Response::Probed {
+                path: request.path,
+                stamp,
+                metadata,
+            }
+        };
+
+        if results.send(response).is_err() {
+            break;
+        }
+    }
+}
+
+/// Wait for the next request, preferring urgent ones. Returns `None` once
+/// the service is gone.
+fn next_request(urgent: &Receiver<Request>, background: &Receiver<Request>) -> Option<Request> {
+    if let Ok(request) = urgent.try_recv() {
+        return Some(request);
+    }
+    select! {
+        recv(urgent) -> request => request.ok(),
+        recv(background) -> request => request.ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::write_silent;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(service: &mut MetadataService, path: &Path) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while service.is_pending(path) {
+            assert!(Instant::now() < deadline, "metadata probe timed out");
+            service.poll();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_scan_and_cache() {
+        let dir = std::env::temp_dir().join("oneamp_metadata_test_scan");
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.wav");
+        let corrupt = dir.join("corrupt.mp3");
+        write_silent(&good, 8000);
+        std::fs::write(&corrupt, b"this is not audio").unwrap();
+
+        let mut service = MetadataService::with_workers(2);
+        service.scan([good.as_path(), corrupt.as_path()]);
+        wait_for(&mut service, &good);
+        wait_for(&mut service, &corrupt);
+
+        let duration = service.get(&good).and_then(TrackMetadata::duration_secs);
+        assert!((duration.unwrap() - 1.0).abs() < 0.01);
+        assert!(service.get(&corrupt).unwrap().error().is_some());
+
+        let total = service.total_duration([good.as_path(), corrupt.as_path()]);
+        assert!((total.secs - 1.0).abs() < 0.01);
+        assert_eq!(total.unknown, 1);
+
+        // Cached tracks aren't probed again
+        let generation = service.generation();
+        service.request(&good);
+        assert!(!service.is_pending(&good));
+        assert_eq!(service.generation(), generation);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_revalidate_reprobes_changed_files() {
+        let dir = std::env::temp_dir().join("oneamp_metadata_test_revalidate");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        write_silent(&path, 8000);
+
+        let mut service = MetadataService::with_workers(1);
+        assert!(service.probe_now(&path).duration_secs().is_some());
+
+        // Same file: nothing changes
+        let generation = service.generation();
+        service.revalidate();
+        wait_for(&mut service, &path);
+        assert_eq!(service.generation(), generation);
+
+        // A different size invalidates the cached probe
+        write_silent(&path, 16000);
+        service.revalidate();
+        wait_for(&mut service, &path);
+        let duration = service.get(&path).and_then(TrackMetadata::duration_secs);
+        assert!((duration.unwrap() - 2.0).abs() < 0.01);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
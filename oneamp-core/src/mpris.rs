@@ -0,0 +1,1110 @@
+// MPRIS2 D-Bus Interface
+// Exposes playback to desktop media widgets, media keys and `playerctl`
+// through `org.mpris.MediaPlayer2`, `.Player` and `.TrackList`. Method calls
+// are handed to the application as `MprisRequest`s; the application reports
+// state back through `MprisServer`, which emits the matching change signals.
+
+use crate::{AudioEvent, RepeatMode, TrackInfo};
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::fdo::{self, RequestNameFlags};
+use zbus::interface;
+use zbus::names::BusName;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Str, Value};
+
+/// Well-known bus name; a second instance gets `.instance<pid>` appended
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.oneamp";
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TRACKLIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+const TRACK_ID_PREFIX: &str = "/org/oneamp/track/";
+
+/// Requests from MPRIS clients, to be carried out by the application
+#[derive(Debug, Clone, PartialEq)]
+pub enum MprisRequest {
+    /// Bring the window to the front
+    Raise,
+    Quit,
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Seek relative to the current position, in seconds
+    Seek(f32),
+    /// Jump to a position in the current track, in seconds
+    SetPosition(f32),
+    /// Output volume, 0.0 - 1.0
+    SetVolume(f32),
+    SetRepeat(RepeatMode),
+    SetShuffle(bool),
+    /// Play a file (`OpenUri`)
+    Open(PathBuf),
+    /// Play a playlist entry
+    GoTo(usize),
+    /// Insert a file after a playlist entry (at the start if `None`)
+    AddTrack {
+        path: PathBuf,
+        after: Option<usize>,
+        play: bool,
+    },
+    RemoveTrack(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl PlaybackStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Playing => "Playing",
+            Self::Paused => "Paused",
+            Self::Stopped => "Stopped",
+        }
+    }
+}
+
+/// Everything MPRIS clients can see
+#[derive(Debug, Clone)]
+pub struct PlayerState {
+    pub status: PlaybackStatus,
+    pub track: Option<TrackInfo>,
+    pub position_secs: f32,
+    pub volume: f32,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
+    /// Entries of the playlist being played
+    pub tracks: Vec<PathBuf>,
+    /// Playlist id of each entry in `tracks`, which track ids are built from
+    pub ids: Vec<u32>,
+    pub current_index: Option<usize>,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            status: PlaybackStatus::Stopped,
+            track: None,
+            position_secs: 0.0,
+            volume: 1.0,
+            repeat: RepeatMode::Off,
+            shuffle: false,
+            tracks: Vec::new(),
+            ids: Vec::new(),
+            current_index: None,
+        }
+    }
+}
+
+impl PlayerState {
+    /// Object path identifying a playlist entry by its playlist id, so it
+    /// keeps pointing at the entry when the playlist changes around it
+    fn track_id(id: Option<u32>) -> OwnedObjectPath {
+        let path = match id {
+            Some(id) => format!("{}{}", TRACK_ID_PREFIX, id),
+            None => NO_TRACK.to_string(),
+        };
+        ObjectPath::try_from(path)
+            .map(OwnedObjectPath::from)
+            .unwrap_or_else(|_| {
+                OwnedObjectPath::from(ObjectPath::from_static_str_unchecked(NO_TRACK))
+            })
+    }
+
+    /// Playlist index behind a track id, if it still exists
+    fn track_index(&self, id: &ObjectPath<'_>) -> Option<usize> {
+        let id: u32 = id.as_str().strip_prefix(TRACK_ID_PREFIX)?.parse().ok()?;
+        self.ids.iter().position(|&entry| entry == id)
+    }
+
+    fn track_ids(&self) -> Vec<OwnedObjectPath> {
+        self.ids
+            .iter()
+            .map(|&id| Self::track_id(Some(id)))
+            .collect()
+    }
+
+    /// Track id of the current entry
+    fn current_track_id(&self) -> OwnedObjectPath {
+        Self::track_id(
+            self.current_index
+                .and_then(|index| self.ids.get(index).copied()),
+        )
+    }
+
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        match &self.track {
+            Some(track) => track_metadata(self.current_track_id(), track),
+            None => HashMap::new(),
+        }
+    }
+
+    fn can_go_next(&self) -> bool {
+        match self.current_index {
+            Some(index) => index + 1 < self.tracks.len() || self.repeat != RepeatMode::Off,
+            None => !self.tracks.is_empty(),
+        }
+    }
+
+    fn can_go_previous(&self) -> bool {
+        self.current_index
+            .is_some_and(|index| index > 0 || self.repeat != RepeatMode::Off)
+    }
+
+    fn snapshot(&self) -> PropertySnapshot {
+        PropertySnapshot {
+            status: self.status,
+            repeat: self.repeat,
+            shuffle: self.shuffle,
+            volume: self.volume,
+            current_index: self.current_index,
+            track_path: self.track.as_ref().map(|track| track.path.clone()),
+            can_go_next: self.can_go_next(),
+            can_go_previous: self.can_go_previous(),
+        }
+    }
+
+    /// Player properties that changed since `old`, for PropertiesChanged
+    fn changed_player_properties(
+        &self,
+        old: &PropertySnapshot,
+    ) -> HashMap<&'static str, OwnedValue> {
+        let new = self.snapshot();
+        let mut changed = HashMap::new();
+        if new.status != old.status {
+            changed.insert(
+                "PlaybackStatus",
+                OwnedValue::from(Str::from(new.status.as_str())),
+            );
+        }
+        if new.repeat != old.repeat {
+            changed.insert(
+                "LoopStatus",
+                OwnedValue::from(Str::from(loop_status(new.repeat))),
+            );
+        }
+        if new.shuffle != old.shuffle {
+            changed.insert("Shuffle", OwnedValue::from(new.shuffle));
+        }
+        if new.volume != old.volume {
+            changed.insert("Volume", OwnedValue::from(f64::from(new.volume)));
+        }
+        if new.current_index != old.current_index || new.track_path != old.track_path {
+            if let Ok(metadata) = OwnedValue::try_from(Value::from(self.metadata())) {
+                changed.insert("Metadata", metadata);
+            }
+        }
+        if new.can_go_next != old.can_go_next {
+            changed.insert("CanGoNext", OwnedValue::from(new.can_go_next));
+        }
+        if new.can_go_previous != old.can_go_previous {
+            changed.insert("CanGoPrevious", OwnedValue::from(new.can_go_previous));
+        }
+        let has_track = new.track_path.is_some();
+        if has_track != old.track_path.is_some() {
+            changed.insert("CanPlay", OwnedValue::from(has_track));
+            changed.insert("CanPause", OwnedValue::from(has_track));
+            changed.insert("CanSeek", OwnedValue::from(has_track));
+        }
+        changed
+    }
+}
+
+/// The parts of [`PlayerState`] that Player properties are derived from,
+/// compared to find out which properties changed
+#[derive(Debug, Clone, PartialEq)]
+struct PropertySnapshot {
+    status: PlaybackStatus,
+    repeat: RepeatMode,
+    shuffle: bool,
+    volume: f32,
+    current_index: Option<usize>,
+    track_path: Option<PathBuf>,
+    can_go_next: bool,
+    can_go_previous: bool,
+}
+
+fn loop_status(repeat: RepeatMode) -> &'static str {
+    match repeat {
+        RepeatMode::Off => "None",
+        RepeatMode::One => "Track",
+        RepeatMode::All => "Playlist",
+    }
+}
+
+/// State shared between the D-Bus interfaces and the server handle
+struct Shared {
+    state: Mutex<PlayerState>,
+    requests: Sender<MprisRequest>,
+    /// Called after each request so the application can wake up to handle it
+    waker: Mutex<Option<Box<dyn Fn() + Send>>>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, PlayerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, request: MprisRequest) {
+        let _ = self.requests.send(request);
+        if let Ok(waker) = self.waker.lock() {
+            if let Some(wake) = waker.as_ref() {
+                wake();
+            }
+        }
+    }
+}
+
+/// `org.mpris.MediaPlayer2`
+struct Root {
+    shared: Arc<Shared>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {
+        self.shared.send(MprisRequest::Raise);
+    }
+
+    fn quit(&self) {
+        self.shared.send(MprisRequest::Quit);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "OneAmp".to_string()
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        "oneamp".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["file".to_string(), "http".to_string(), "https".to_string()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        [
+            "audio/mpeg",
+            "audio/aac",
+            "audio/flac",
+            "audio/ogg",
+            "audio/wav",
+            "application/x-cue",
+        ]
+        .iter()
+        .map(|mime| mime.to_string())
+        .collect()
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`
+struct Player {
+    shared: Arc<Shared>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.shared.send(MprisRequest::Next);
+    }
+
+    fn previous(&self) {
+        self.shared.send(MprisRequest::Previous);
+    }
+
+    fn pause(&self) {
+        self.shared.send(MprisRequest::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.shared.send(MprisRequest::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.shared.send(MprisRequest::Stop);
+    }
+
+    fn play(&self) {
+        self.shared.send(MprisRequest::Play);
+    }
+
+    /// Seek by an offset in microseconds
+    fn seek(&self, offset: i64) {
+        self.shared
+            .send(MprisRequest::Seek(offset as f32 / 1_000_000.0));
+    }
+
+    /// Jump to a position in microseconds; ignored if the track changed meanwhile
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let state = self.shared.state();
+        let current = state.current_index.filter(|_| state.track.is_some());
+        if current.is_none() || state.track_index(&track_id) != current || position < 0 {
+            return;
+        }
+        let duration = state
+            .track
+            .as_ref()
+            .and_then(|track| track.duration_secs)
+            .unwrap_or(f32::MAX);
+        let position = position as f32 / 1_000_000.0;
+        drop(state);
+
+        if position <= duration {
+            self.shared.send(MprisRequest::SetPosition(position));
+        }
+    }
+
+    fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        let path = playable_path(&uri)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unsupported URI: {}", uri)))?;
+        self.shared.send(MprisRequest::Open(path));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.shared.state().status.as_str().to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        loop_status(self.shared.state().repeat).to_string()
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&self, value: String) -> zbus::Result<()> {
+        let repeat = match value.as_str() {
+            "None" => RepeatMode::Off,
+            "Track" => RepeatMode::One,
+            "Playlist" => RepeatMode::All,
+            _ => {
+                return Err(
+                    fdo::Error::InvalidArgs(format!("Unknown loop status: {}", value)).into(),
+                )
+            }
+        };
+        self.shared.send(MprisRequest::SetRepeat(repeat));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    /// Only normal speed is supported
+    #[zbus(property)]
+    fn set_rate(&self, _value: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.shared.state().shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&self, value: bool) {
+        self.shared.send(MprisRequest::SetShuffle(value));
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.shared.state().metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        f64::from(self.shared.state().volume)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, value: f64) {
+        self.shared
+            .send(MprisRequest::SetVolume(value.clamp(0.0, 1.0) as f32));
+    }
+
+    /// Position in microseconds. Clients poll it; jumps are announced with `Seeked`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        (f64::from(self.shared.state().position_secs) * 1_000_000.0) as i64
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.shared.state().can_go_next()
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.shared.state().can_go_previous()
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.shared.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.shared.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.shared.state().track.is_some()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// `org.mpris.MediaPlayer2.TrackList`
+struct TrackList {
+    shared: Arc<Shared>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl TrackList {
+    fn get_tracks_metadata(
+        &self,
+        track_ids: Vec<OwnedObjectPath>,
+    ) -> Vec<HashMap<String, OwnedValue>> {
+        let entries: Vec<(OwnedObjectPath, PathBuf)> = {
+            let state = self.shared.state();
+            track_ids
+                .into_iter()
+                .filter_map(|id| {
+                    let index = state.track_index(&id)?;
+                    Some((id, state.tracks[index].clone()))
+                })
+                .collect()
+        };
+
+        // Probe outside the lock; unreadable files still get an id and URL.
+        // Covers come from the cache only, finding new ones is the player's job.
+        entries
+            .into_iter()
+            .map(|(id, path)| {
+                let mut track = TrackInfo::from_file(&path).unwrap_or_else(|_| TrackInfo {
+                    path: path.clone(),
+                    ..TrackInfo::default()
+                });
+                track.cover_art = crate::album_art::cached(&path);
+                track_metadata(id, &track)
+            })
+            .collect()
+    }
+
+    fn add_track(
+        &self,
+        uri: String,
+        after_track: ObjectPath<'_>,
+        set_as_current: bool,
+    ) -> fdo::Result<()> {
+        let path = playable_path(&uri)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unsupported URI: {}", uri)))?;
+        let after = self.shared.state().track_index(&after_track);
+        self.shared.send(MprisRequest::AddTrack {
+            path,
+            after,
+            play: set_as_current,
+        });
+        Ok(())
+    }
+
+    fn remove_track(&self, track_id: ObjectPath<'_>) -> fdo::Result<()> {
+        let index = self
+            .shared
+            .state()
+            .track_index(&track_id)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown track: {}", track_id)))?;
+        self.shared.send(MprisRequest::RemoveTrack(index));
+        Ok(())
+    }
+
+    fn go_to(&self, track_id: ObjectPath<'_>) -> fdo::Result<()> {
+        let index = self
+            .shared
+            .state()
+            .track_index(&track_id)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown track: {}", track_id)))?;
+        self.shared.send(MprisRequest::GoTo(index));
+        Ok(())
+    }
+
+    /// Changes are announced with `TrackListReplaced`
+    #[zbus(property(emits_changed_signal = "invalidates"))]
+    fn tracks(&self) -> Vec<OwnedObjectPath> {
+        self.shared.state().track_ids()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_edit_tracks(&self) -> bool {
+        true
+    }
+}
+
+/// Handle to the exported MPRIS objects.
+///
+/// Feed it the audio engine's events and the playlist state; it answers
+/// property queries and emits change signals. Poll client requests with
+/// [`MprisServer::try_recv_request`].
+pub struct MprisServer {
+    connection: Connection,
+    shared: Arc<Shared>,
+    requests: Receiver<MprisRequest>,
+    bus_name: String,
+}
+
+impl MprisServer {
+    /// Export the player on the session bus
+    pub fn connect_session() -> Result<Self> {
+        Self::start(Builder::session().context("Failed to connect to the session bus")?)
+    }
+
+    /// Export the player on the bus at `address` (e.g. a private test bus)
+    pub fn connect(address: &str) -> Result<Self> {
+        Self::start(Builder::address(address).context("Invalid D-Bus address")?)
+    }
+
+    fn start(builder: Builder<'_>) -> Result<Self> {
+        let (requests_tx, requests_rx) = crossbeam_channel::unbounded();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(PlayerState::default()),
+            requests: requests_tx,
+            waker: Mutex::new(None),
+        });
+
+        let connection = builder
+            .serve_at(
+                OBJECT_PATH,
+                Root {
+                    shared: shared.clone(),
+                },
+            )?
+            .serve_at(
+                OBJECT_PATH,
+                Player {
+                    shared: shared.clone(),
+                },
+            )?
+            .serve_at(
+                OBJECT_PATH,
+                TrackList {
+                    shared: shared.clone(),
+                },
+            )?
+            .build()
+            .context("Failed to connect to D-Bus")?;
+
+        // Another player instance may already own the plain name
+        let mut bus_name = BUS_NAME.to_string();
+        match connection.request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into()) {
+            Ok(_) => {}
+            Err(zbus::Error::NameTaken) => {
+                bus_name = format!("{}.instance{}", BUS_NAME, std::process::id());
+                connection
+                    .request_name_with_flags(bus_name.as_str(), RequestNameFlags::DoNotQueue.into())
+                    .context("Failed to request the MPRIS bus name")?;
+            }
+            Err(e) => return Err(e).context("Failed to request the MPRIS bus name"),
+        }
+
+        Ok(Self {
+            connection,
+            shared,
+            requests: requests_rx,
+            bus_name,
+        })
+    }
+
+    /// Bus name the player was exported under
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    /// Next request from a client, if any (non-blocking)
+    pub fn try_recv_request(&self) -> Option<MprisRequest> {
+        self.requests.try_recv().ok()
+    }
+
+    /// Call `wake` whenever a request arrives, e.g. to repaint an idle window
+    pub fn set_waker(&self, wake: impl Fn() + Send + 'static) {
+        if let Ok(mut waker) = self.shared.waker.lock() {
+            *waker = Some(Box::new(wake));
+        }
+    }
+
+    /// Track playback state from an audio engine event
+    pub fn handle_event(&self, event: &AudioEvent) {
+        self.update(|state| match event {
+            AudioEvent::TrackLoaded(track) => {
+                state.track = Some(track.clone());
+                state.position_secs = 0.0;
+            }
+            AudioEvent::TrackUpdated(track) => state.track = Some(track.clone()),
+            AudioEvent::Playing => state.status = PlaybackStatus::Playing,
+            AudioEvent::Paused => state.status = PlaybackStatus::Paused,
+            AudioEvent::Stopped | AudioEvent::Finished | AudioEvent::Error(_) => {
+                state.status = PlaybackStatus::Stopped;
+                state.position_secs = 0.0;
+            }
+            AudioEvent::Position(position, _) => state.position_secs = *position,
+            AudioEvent::VolumeChanged(volume) => state.volume = *volume,
+            _ => {}
+        });
+    }
+
+    /// Set the entries of the playlist being played and their playlist ids
+    pub fn set_tracks(&self, tracks: Vec<PathBuf>, ids: Vec<u32>, current_index: Option<usize>) {
+        let changed = {
+            let state = self.shared.state();
+            state.tracks != tracks || state.ids != ids || state.current_index != current_index
+        };
+        if !changed {
+            return;
+        }
+
+        self.update(|state| {
+            state.tracks = tracks;
+            state.ids = ids;
+            state.current_index = current_index;
+        });
+
+        let state = self.shared.state().clone();
+        let body = (state.track_ids(), state.current_track_id());
+        self.emit(TRACKLIST_INTERFACE, "TrackListReplaced", &body);
+    }
+
+    pub fn set_current_index(&self, current_index: Option<usize>) {
+        self.update(|state| state.current_index = current_index);
+    }
+
+    pub fn set_repeat(&self, repeat: RepeatMode) {
+        self.update(|state| state.repeat = repeat);
+    }
+
+    pub fn set_shuffle(&self, shuffle: bool) {
+        self.update(|state| state.shuffle = shuffle);
+    }
+
+    /// Announce a jump in the current track (after a seek)
+    pub fn seeked(&self, position_secs: f32) {
+        self.update(|state| state.position_secs = position_secs);
+        let position = (f64::from(position_secs) * 1_000_000.0) as i64;
+        self.emit(PLAYER_INTERFACE, "Seeked", &position);
+    }
+
+    /// Snapshot of what clients currently see
+    pub fn state(&self) -> PlayerState {
+        self.shared.state().clone()
+    }
+
+    /// Apply a change and emit PropertiesChanged for whatever it affected
+    fn update(&self, change: impl FnOnce(&mut PlayerState)) {
+        let changed = {
+            let mut state = self.shared.state();
+            let old = state.snapshot();
+            change(&mut state);
+            state.changed_player_properties(&old)
+        };
+
+        if !changed.is_empty() {
+            let body = (PLAYER_INTERFACE, changed, Vec::<&str>::new());
+            self.emit(
+                "org.freedesktop.DBus.Properties",
+                "PropertiesChanged",
+                &body,
+            );
+        }
+    }
+
+    fn emit<B>(&self, interface: &str, signal: &str, body: &B)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        if let Err(e) =
+            self.connection
+                .emit_signal(None::<BusName<'_>>, OBJECT_PATH, interface, signal, body)
+        {
+            eprintln!("Failed to emit MPRIS signal {}: {}", signal, e);
+        }
+    }
+}
+
+/// MPRIS metadata map for a track
+fn track_metadata(id: OwnedObjectPath, track: &TrackInfo) -> HashMap<String, OwnedValue> {
+    let mut metadata = HashMap::new();
+    metadata.insert(
+        "mpris:trackid".to_string(),
+        OwnedValue::from(id.into_inner()),
+    );
+
+    let mut insert = |key: &str, value: Value<'_>| {
+        if let Ok(value) = value.try_into_owned() {
+            metadata.insert(key.to_string(), value);
+        }
+    };
+
+    insert("xesam:url", Value::from(uri_from_path(&track.path)));
+    if let Some(duration) = track.duration_secs {
+        insert(
+            "mpris:length",
+            Value::from((f64::from(duration) * 1_000_000.0) as i64),
+        );
+    }
+    let title = track.title.clone().unwrap_or_else(|| {
+        track
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    insert("xesam:title", Value::from(title));
+    if let Some(ref artist) = track.artist {
+        insert("xesam:artist", Value::from(vec![artist.clone()]));
+    }
+    if let Some(ref album) = track.album {
+        insert("xesam:album", Value::from(album.clone()));
+    }
+    if let Some(ref cover) = track.cover_art {
+        insert("mpris:artUrl", Value::from(uri_from_path(cover)));
+    }
+    metadata
+}
+
+/// `file://` URI for a path, percent-encoding everything but unreserved characters.
+/// Stream URLs are returned as they are.
+pub fn uri_from_path(path: &Path) -> String {
+    if crate::stream::is_stream_url(path) {
+        return path.to_string_lossy().into_owned();
+    }
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Path of a `file://` URI (or a plain absolute path)
+pub fn path_from_uri(uri: &str) -> Option<PathBuf> {
+    let encoded = match uri.strip_prefix("file://") {
+        // Skip an optional host ("file://localhost/...")
+        Some(rest) => &rest[rest.find('/')?..],
+        None if uri.starts_with('/') => uri,
+        None => return None,
+    };
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut chars = encoded.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Playlist entry for a URI clients ask to open: a file or a stream URL
+fn playable_path(uri: &str) -> Option<PathBuf> {
+    if crate::stream::is_stream_url(Path::new(uri)) {
+        return Some(PathBuf::from(uri));
+    }
+    path_from_uri(uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use zbus::blocking::fdo::PropertiesProxy;
+    use zbus::blocking::Proxy;
+    use zbus::names::InterfaceName;
+
+    /// A throwaway session bus, killed on drop
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+        _config: PathBuf,
+    }
+
+    impl PrivateBus {
+        /// Start `dbus-daemon`, or `None` if it isn't installed
+        fn start(name: &str) -> Option<Self> {
+            let config = std::env::temp_dir().join(format!("oneamp_mpris_{}.conf", name));
+            std::fs::write(
+                &config,
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+            )
+            .ok()?;
+
+            let mut daemon = Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|e| eprintln!("Skipping MPRIS test, dbus-daemon unavailable: {}", e))
+                .ok()?;
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+                _config: config,
+            })
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+            let _ = std::fs::remove_file(&self._config);
+        }
+    }
+
+    fn property(conn: &Connection, interface: &'static str, name: &str) -> OwnedValue {
+        let proxy = PropertiesProxy::builder(conn)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap();
+        proxy
+            .get(InterfaceName::from_static_str(interface).unwrap(), name)
+            .unwrap()
+    }
+
+    fn recv(server: &MprisServer) -> MprisRequest {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(request) = server.try_recv_request() {
+                return request;
+            }
+            assert!(Instant::now() < deadline, "no MPRIS request received");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_uri_round_trip() {
+        let path = Path::new("/music/Café del Mar/01 #1 hit.flac#track=2");
+        let uri = uri_from_path(path);
+        assert!(uri.starts_with("file:///music/Caf%C3%A9%20del%20Mar/"));
+        assert_eq!(path_from_uri(&uri).as_deref(), Some(path));
+        assert_eq!(
+            path_from_uri("file://localhost/tmp/a.mp3").as_deref(),
+            Some(Path::new("/tmp/a.mp3"))
+        );
+        assert_eq!(path_from_uri("http://example.com/a.mp3"), None);
+
+        // Streams are played by URL
+        let url = "http://radio.example/live?id=1";
+        assert_eq!(playable_path(url), Some(PathBuf::from(url)));
+        assert_eq!(uri_from_path(Path::new(url)), url);
+    }
+
+    #[test]
+    fn test_player_over_private_bus() {
+        let Some(bus) = PrivateBus::start("player") else {
+            return;
+        };
+        let server = MprisServer::connect(&bus.address).unwrap();
+        assert_eq!(server.bus_name(), BUS_NAME);
+        let client = Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let player = Proxy::new(&client, BUS_NAME, OBJECT_PATH, PLAYER_INTERFACE).unwrap();
+
+        // Methods become requests
+        player.call_method("PlayPause", &()).unwrap();
+        assert_eq!(recv(&server), MprisRequest::PlayPause);
+        player.call_method("Seek", &(-2_500_000i64)).unwrap();
+        assert_eq!(recv(&server), MprisRequest::Seek(-2.5));
+        player
+            .call_method("OpenUri", &("file:///tmp/some%20song.mp3"))
+            .unwrap();
+        assert_eq!(
+            recv(&server),
+            MprisRequest::Open(PathBuf::from("/tmp/some song.mp3"))
+        );
+        player.set_property("LoopStatus", "Playlist").unwrap();
+        assert_eq!(recv(&server), MprisRequest::SetRepeat(RepeatMode::All));
+
+        // Engine events show up in the properties
+        server.set_tracks(
+            vec![PathBuf::from("/tmp/a.mp3"), PathBuf::from("/tmp/b.mp3")],
+            vec![5, 9],
+            Some(1),
+        );
+        server.handle_event(&AudioEvent::TrackLoaded(TrackInfo {
+            path: PathBuf::from("/tmp/b.mp3"),
+            title: Some("Song B".to_string()),
+            artist: Some("Artist".to_string()),
+            duration_secs: Some(90.0),
+            ..TrackInfo::default()
+        }));
+        server.handle_event(&AudioEvent::Playing);
+        server.handle_event(&AudioEvent::Position(12.0, 90.0));
+        server.handle_event(&AudioEvent::VolumeChanged(0.5));
+
+        let status = property(&client, PLAYER_INTERFACE, "PlaybackStatus");
+        assert_eq!(String::try_from(status).unwrap(), "Playing");
+        let position = property(&client, PLAYER_INTERFACE, "Position");
+        assert_eq!(i64::try_from(position).unwrap(), 12_000_000);
+        let volume = property(&client, PLAYER_INTERFACE, "Volume");
+        assert_eq!(f64::try_from(volume).unwrap(), 0.5);
+
+        let metadata: HashMap<String, OwnedValue> = property(&client, PLAYER_INTERFACE, "Metadata")
+            .try_into()
+            .unwrap();
+        let title = metadata["xesam:title"].try_clone().unwrap();
+        assert_eq!(String::try_from(title).unwrap(), "Song B");
+        let length = metadata["mpris:length"].try_clone().unwrap();
+        assert_eq!(i64::try_from(length).unwrap(), 90_000_000);
+        let track_id = metadata["mpris:trackid"].try_clone().unwrap();
+        let track_id = OwnedObjectPath::try_from(track_id).unwrap();
+        assert_eq!(track_id.as_str(), "/org/oneamp/track/9");
+
+        // SetPosition only applies to the current track
+        player
+            .call_method("SetPosition", &(track_id.clone(), 30_000_000i64))
+            .unwrap();
+        assert_eq!(recv(&server), MprisRequest::SetPosition(30.0));
+
+        // TrackList
+        let tracklist = Proxy::new(&client, BUS_NAME, OBJECT_PATH, TRACKLIST_INTERFACE).unwrap();
+        let tracks: Vec<OwnedObjectPath> = property(&client, TRACKLIST_INTERFACE, "Tracks")
+            .try_into()
+            .unwrap();
+        assert_eq!(tracks.len(), 2);
+        tracklist
+            .call_method("GoTo", &(tracks[0].clone(),))
+            .unwrap();
+        assert_eq!(recv(&server), MprisRequest::GoTo(0));
+
+        // Ids keep pointing at their entry when the playlist is reordered
+        server.set_tracks(
+            vec![PathBuf::from("/tmp/b.mp3"), PathBuf::from("/tmp/a.mp3")],
+            vec![9, 5],
+            Some(0),
+        );
+        tracklist
+            .call_method("GoTo", &(tracks[0].clone(),))
+            .unwrap();
+        assert_eq!(recv(&server), MprisRequest::GoTo(1));
+        assert!(tracklist
+            .call_method("GoTo", &(PlayerState::track_id(Some(7)),))
+            .is_err());
+
+        let root = Proxy::new(&client, BUS_NAME, OBJECT_PATH, "org.mpris.MediaPlayer2").unwrap();
+        let identity: String = root.get_property("Identity").unwrap();
+        assert_eq!(identity, "OneAmp");
+    }
+
+    #[test]
+    fn test_second_instance_gets_unique_name() {
+        let Some(bus) = PrivateBus::start("instances") else {
+            return;
+        };
+        let first = MprisServer::connect(&bus.address).unwrap();
+        let second = MprisServer::connect(&bus.address).unwrap();
+        assert_eq!(first.bus_name(), BUS_NAME);
+        assert!(second
+            .bus_name()
+            .starts_with(&format!("{}.instance", BUS_NAME)));
+    }
+
+    #[test]
+    fn test_waker_runs_on_request() {
+        let Some(bus) = PrivateBus::start("waker") else {
+            return;
+        };
+        let server = MprisServer::connect(&bus.address).unwrap();
+        let (woken_tx, woken_rx) = crossbeam_channel::unbounded();
+        server.set_waker(move || {
+            let _ = woken_tx.send(());
+        });
+
+        let client = Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let player = Proxy::new(&client, BUS_NAME, OBJECT_PATH, PLAYER_INTERFACE).unwrap();
+        player.call_method("Next", &()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(recv(&server), MprisRequest::Next);
+        assert!(woken_rx.recv_deadline(deadline).is_ok());
+    }
+}
@@ -0,0 +1,313 @@
+// Headless Player
+// Ties an `AudioEngine` to a `Playlist` and keeps track of what is playing,
+// for front-ends without their own UI state (daemon, command line).
+
+use crate::{AudioCommand, AudioEngine, AudioEvent, Playlist, RepeatMode, TrackInfo};
+use anyhow::Result;
+use crossbeam_channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// Snapshot of the player, as reported to remote clients
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatus {
+    pub state: PlaybackState,
+    pub track: Option<TrackInfo>,
+    pub position_secs: f32,
+    pub duration_secs: f32,
+    pub volume: f32,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
+    /// Index of the current entry in the playlist
+    pub current_index: Option<usize>,
+    pub playlist_length: usize,
+}
+
+/// Audio engine plus playlist navigation.
+///
+/// Feed every event from [`Player::events`] to [`Player::handle_event`] so
+/// the state stays current and playback moves on when a track finishes.
+pub struct Player {
+    engine: AudioEngine,
+    playlist: Playlist,
+    state: PlaybackState,
+    track: Option<TrackInfo>,
+    position_secs: f32,
+    duration_secs: f32,
+    volume: f32,
+    /// Set when the last track finished and the playlist had nothing after it
+    finished: bool,
+}
+
+impl Player {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            engine: AudioEngine::new()?,
+            playlist: Playlist::new(),
+            state: PlaybackState::Stopped,
+            track: None,
+            position_secs: 0.0,
+            duration_secs: 0.0,
+            volume: 1.0,
+            finished: false,
+        })
+    }
+
+    /// Events from the audio thread
+    pub fn events(&self) -> &Receiver<AudioEvent> {
+        self.engine.events()
+    }
+
+    /// Send a command straight to the audio engine (equalizer, etc.)
+    pub fn send_command(&self, command: AudioCommand) {
+        let _ = self.engine.send_command(command);
+    }
+
+    pub fn playlist(&self) -> &Playlist {
+        &self.playlist
+    }
+
+    pub fn playlist_mut(&mut self) -> &mut Playlist {
+        &mut self.playlist
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        self.state
+    }
+
+    pub fn track(&self) -> Option<&TrackInfo> {
+        self.track.as_ref()
+    }
+
+    pub fn position_secs(&self) -> f32 {
+        self.position_secs
+    }
+
+    /// Whether playback ran off the end of the playlist
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn status(&self) -> PlayerStatus {
+        PlayerStatus {
+            state: self.state,
+            track: self.track.clone(),
+            position_secs: self.position_secs,
+            duration_secs: self.duration_secs,
+            volume: self.volume,
+            repeat: self.playlist.repeat(),
+            shuffle: self.playlist.shuffle(),
+            current_index: self.playlist.current_index(),
+            playlist_length: self.playlist.len(),
+        }
+    }
+
+    /// Resume if paused, otherwise start the current (or first) entry
+    pub fn play(&mut self) {
+        match self.state {
+            PlaybackState::Playing => {}
+            PlaybackState::Paused => self.send_command(AudioCommand::Resume),
+            PlaybackState::Stopped => {
+                let index = self.playlist.current_index().unwrap_or(0);
+                self.play_index(index);
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.state == PlaybackState::Playing {
+            self.send_command(AudioCommand::Pause);
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.state == PlaybackState::Playing {
+            self.pause();
+        } else {
+            self.play();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.send_command(AudioCommand::Stop);
+    }
+
+    /// Play a playlist entry. Returns `false` if there is no such entry.
+    pub fn play_index(&mut self, index: usize) -> bool {
+        match self.playlist.select(index).map(Path::to_path_buf) {
+            Some(path) => {
+                self.send_command(AudioCommand::Play(path));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Play a playlist entry from `position_secs` instead of the start
+    pub fn play_index_from(&mut self, index: usize, position_secs: f32) -> bool {
+        match self.playlist.select(index).map(Path::to_path_buf) {
+            Some(path) => {
+                self.send_command(AudioCommand::Load(path, position_secs.max(0.0)));
+                self.send_command(AudioCommand::Resume);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Skip to the next track. Returns `false` at the end of the playlist.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> bool {
+        match self.playlist.next() {
+            Some(index) => self.play_current(index),
+            None => false,
+        }
+    }
+
+    pub fn previous(&mut self) -> bool {
+        match self.playlist.previous() {
+            Some(index) => self.play_current(index),
+            None => false,
+        }
+    }
+
+    /// Play the entry the playlist just moved to
+    fn play_current(&mut self, index: usize) -> bool {
+        match self.playlist.get(index).map(Path::to_path_buf) {
+            Some(path) => {
+                self.send_command(AudioCommand::Play(path));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn seek(&mut self, position_secs: f32) {
+        self.send_command(AudioCommand::Seek(position_secs.max(0.0)));
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.send_command(AudioCommand::SetVolume(volume));
+    }
+
+    /// Add files to the end of the playlist, expanding CUE sheets.
+    /// Returns how many entries were added.
+    pub fn enqueue(&mut self, paths: &[PathBuf]) -> usize {
+        let before = self.playlist.len();
+        for path in paths {
+            self.playlist.add_expanded(path);
+        }
+        self.playlist.len() - before
+    }
+
+    /// Update the state from an audio engine event
+    pub fn handle_event(&mut self, event: &AudioEvent) {
+        match event {
+            AudioEvent::TrackLoaded(track) => {
+                self.track = Some(track.clone());
+                self.finished = false;
+                self.position_secs = 0.0;
+                self.duration_secs = track.duration_secs.unwrap_or(0.0);
+            }
+            AudioEvent::TrackUpdated(track) => self.track = Some(track.clone()),
+            AudioEvent::Playing => self.state = PlaybackState::Playing,
+            AudioEvent::Paused => self.state = PlaybackState::Paused,
+            AudioEvent::Stopped | AudioEvent::Error(_) => {
+                self.state = PlaybackState::Stopped;
+                self.position_secs = 0.0;
+            }
+            AudioEvent::Position(position, duration) => {
+                self.position_secs = *position;
+                self.duration_secs = *duration;
+            }
+            AudioEvent::VolumeChanged(volume) => self.volume = *volume,
+            AudioEvent::Finished => {
+                self.state = PlaybackState::Stopped;
+                self.position_secs = 0.0;
+                match self.playlist.advance() {
+                    Some(index) => {
+                        self.play_current(index);
+                    }
+                    None => self.finished = true,
+                }
+            }
+            AudioEvent::RequestNext => {
+                self.next();
+            }
+            AudioEvent::RequestPrevious => {
+                self.previous();
+            }
+            AudioEvent::Buffering(_)
+            | AudioEvent::SeekFailed(_)
+            | AudioEvent::EqualizerUpdated(..)
+            | AudioEvent::VisualizationData(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_with_tracks(count: usize) -> Player {
+        let mut player = Player::new().unwrap();
+        let paths: Vec<PathBuf> = (0..count)
+            .map(|i| PathBuf::from(format!("/music/track{}.mp3", i)))
+            .collect();
+        assert_eq!(player.enqueue(&paths), count);
+        player
+    }
+
+    #[test]
+    fn test_events_update_status() {
+        let mut player = player_with_tracks(2);
+        let track = TrackInfo {
+            path: PathBuf::from("/music/track0.mp3"),
+            duration_secs: Some(120.0),
+            ..Default::default()
+        };
+        player.handle_event(&AudioEvent::TrackLoaded(track.clone()));
+        player.handle_event(&AudioEvent::Playing);
+        player.handle_event(&AudioEvent::Position(12.5, 120.0));
+        player.handle_event(&AudioEvent::VolumeChanged(0.5));
+
+        let status = player.status();
+        assert_eq!(status.state, PlaybackState::Playing);
+        assert_eq!(status.track, Some(track));
+        assert_eq!(status.position_secs, 12.5);
+        assert_eq!(status.duration_secs, 120.0);
+        assert_eq!(status.volume, 0.5);
+        assert_eq!(status.playlist_length, 2);
+
+        // A failed seek leaves playback alone; a failed load stops it
+        player.handle_event(&AudioEvent::SeekFailed("Failed to seek".to_string()));
+        assert_eq!(player.status().state, PlaybackState::Playing);
+        assert_eq!(player.status().position_secs, 12.5);
+        player.handle_event(&AudioEvent::Error("Failed to load".to_string()));
+        assert_eq!(player.status().state, PlaybackState::Stopped);
+    }
+
+    #[test]
+    fn test_finished_advances_playlist() {
+        let mut player = player_with_tracks(2);
+        assert!(player.play_index(0));
+        player.handle_event(&AudioEvent::Finished);
+        assert_eq!(player.playlist().current_index(), Some(1));
+        assert!(!player.is_finished());
+
+        // With repeat off, the last track ends playback
+        player.handle_event(&AudioEvent::Finished);
+        assert_eq!(player.playlist().current_index(), Some(1));
+        assert_eq!(player.state(), PlaybackState::Stopped);
+        assert!(player.is_finished());
+    }
+}
@@ -0,0 +1,1167 @@
+// Playlist Model
+// Track ordering shared by the desktop and CLI front-ends: repeat modes,
+// non-repeating shuffle, a "play next" queue and a playback history.
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::cue;
+
+/// Maximum number of entries remembered for "Previous"
+const MAX_HISTORY: usize = 500;
+
+/// What happens when playback reaches the end of a track or of the playlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    /// Stop after the last track
+    #[default]
+    Off,
+    /// Repeat the current track
+    One,
+    /// Start over after the last track
+    All,
+}
+
+impl RepeatMode {
+    /// Get the next mode in the Off -> All -> One cycle (Winamp order)
+    pub fn cycle(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
+impl fmt::Display for RepeatMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepeatMode::Off => write!(f, "off"),
+            RepeatMode::One => write!(f, "one"),
+            RepeatMode::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for RepeatMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(RepeatMode::Off),
+            "one" | "track" => Ok(RepeatMode::One),
+            "all" | "playlist" => Ok(RepeatMode::All),
+            _ => Err(format!(
+                "Invalid repeat mode '{}' (expected off, one or all)",
+                s
+            )),
+        }
+    }
+}
+
+/// How shuffle orders the playlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShuffleMode {
+    /// Shuffle individual tracks
+    #[default]
+    Tracks,
+    /// Shuffle the order of albums, keeping each album's tracks in order
+    Albums,
+}
+
+/// Ordered list of tracks with playback navigation
+#[derive(Debug, Clone, Default)]
+pub struct Playlist {
+    entries: Vec<PathBuf>,
+    /// How many times each path appears in `entries`, so lookups don't scan
+    /// the whole list
+    counts: HashMap<PathBuf, usize>,
+    /// Id of each entry, parallel to `entries`. Ids follow their entry
+    /// through moves and are never reused.
+    ids: Vec<u32>,
+    next_id: u32,
+    current: Option<usize>,
+    repeat: RepeatMode,
+    shuffle: bool,
+    shuffle_mode: ShuffleMode,
+    /// Permutation of entry indices used while shuffle is enabled
+    shuffle_order: Vec<usize>,
+    /// Entries to play next, before continuing with the playlist order
+    queue: VecDeque<usize>,
+    /// Previously played entries, most recent last
+    history: Vec<usize>,
+}
+
+impl Playlist {
+    /// Create an empty playlist
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a playlist from a list of paths
+    pub fn from_paths(paths: Vec<PathBuf>) -> Self {
+        Self {
+            counts: count_paths(&paths),
+            ids: (0..paths.len() as u32).collect(),
+            next_id: paths.len() as u32,
+            entries: paths,
+            ..Self::default()
+        }
+    }
+
+    /// Get all entries in playlist order
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// Get the entry at an index
+    pub fn get(&self, index: usize) -> Option<&Path> {
+        self.entries.get(index).map(PathBuf::as_path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.counts.contains_key(path)
+    }
+
+    /// Ids of all entries in playlist order
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// Id of the entry at an index
+    pub fn id(&self, index: usize) -> Option<u32> {
+        self.ids.get(index).copied()
+    }
+
+    /// Index of the entry with an id
+    pub fn index_of_id(&self, id: u32) -> Option<usize> {
+        self.ids.iter().position(|&i| i == id)
+    }
+
+    /// Append an entry, returning its index
+    pub fn add(&mut self, path: PathBuf) -> usize {
+        *self.counts.entry(path.clone()).or_insert(0) += 1;
+        self.entries.push(path);
+        self.ids.push(self.next_id);
+        self.next_id += 1;
+        let index = self.entries.len() - 1;
+        if self.shuffle {
+            self.insert_into_shuffle_order(index);
+        }
+        index
+    }
+
+    /// Append a file, expanding CUE sheets into virtual tracks.
+    /// Entries already in the playlist are skipped.
+    pub fn add_expanded(&mut self, path: &Path) {
+        for entry in cue::expand_playlist_entry(path) {
+            if !self.contains(&entry) {
+                self.add(entry);
+            }
+        }
+    }
+
+    /// Index of the entry to play for a file added with
+    /// [`Playlist::add_expanded`]: the file itself or, for CUE sheets, the
+    /// first of its tracks
+    pub fn find_expanded(&self, path: &Path) -> Option<usize> {
+        let first = cue::expand_playlist_entry(path).into_iter().next()?;
+        if !self.contains(&first) {
+            return None;
+        }
+        self.entries.iter().position(|entry| *entry == first)
+    }
+
+    /// Remove the entry at an index
+    pub fn remove(&mut self, index: usize) -> Option<PathBuf> {
+        if index >= self.entries.len() {
+            return None;
+        }
+
+        let removed = self.entries.remove(index);
+        self.ids.remove(index);
+        self.uncount(&removed);
+
+        let shift = |i: usize| {
+            if i > index {
+                Some(i - 1)
+            } else if i == index {
+                None
+            } else {
+                Some(i)
+            }
+        };
+
+        self.current = self.current.and_then(shift);
+        self.shuffle_order = self
+            .shuffle_order
+            .iter()
+            .filter_map(|&i| shift(i))
+            .collect();
+        self.queue = self.queue.iter().filter_map(|&i| shift(i)).collect();
+        self.history = self.history.iter().filter_map(|&i| shift(i)).collect();
+
+        Some(removed)
+    }
+
+    /// Rearrange entries. `order[i]` is the old index of the entry that ends up
+    /// at position `i`, so `order` must be a permutation of `0..len()`.
+    ///
+    /// The current track, queue, history and shuffle order follow their entries.
+    pub fn reorder(&mut self, order: &[usize]) {
+        assert_eq!(
+            order.len(),
+            self.entries.len(),
+            "reorder needs a permutation of every entry"
+        );
+
+        let mut new_index = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+
+        let mut old_entries: Vec<Option<PathBuf>> = std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.entries = order
+            .iter()
+            .map(|&old| old_entries[old].take().expect("order is not a permutation"))
+            .collect();
+        self.ids = order.iter().map(|&old| self.ids[old]).collect();
+
+        self.current = self.current.map(|i| new_index[i]);
+        for i in self
+            .shuffle_order
+            .iter_mut()
+            .chain(self.queue.iter_mut())
+            .chain(self.history.iter_mut())
+        {
+            *i = new_index[*i];
+        }
+    }
+
+    /// Remove several entries at once. Out-of-range indices are ignored.
+    pub fn remove_many(&mut self, indices: &[usize]) {
+        let mut keep = vec![true; self.entries.len()];
+        for &i in indices {
+            if let Some(k) = keep.get_mut(i) {
+                *k = false;
+            }
+        }
+        self.retain_by_index(|i| keep[i]);
+    }
+
+    /// Keep only the given entries ("crop")
+    pub fn retain_indices(&mut self, indices: &[usize]) {
+        let mut keep = vec![false; self.entries.len()];
+        for &i in indices {
+            if let Some(k) = keep.get_mut(i) {
+                *k = true;
+            }
+        }
+        self.retain_by_index(|i| keep[i]);
+    }
+
+    /// Move entries so they form a block starting where `to` was.
+    /// `to` is an index into the list before the move (`len()` appends).
+    /// Returns the new index of the first moved entry.
+    pub fn move_entries(&mut self, indices: &[usize], to: usize) -> usize {
+        let mut moving = vec![false; self.entries.len()];
+        for &i in indices {
+            if let Some(m) = moving.get_mut(i) {
+                *m = true;
+            }
+        }
+
+        let (moved, rest): (Vec<usize>, Vec<usize>) =
+            (0..self.entries.len()).partition(|&i| moving[i]);
+        let insert_at = rest.iter().take_while(|&&i| i < to).count();
+
+        let order: Vec<usize> = rest[..insert_at]
+            .iter()
+            .chain(moved.iter())
+            .chain(rest[insert_at..].iter())
+            .copied()
+            .collect();
+        self.reorder(&order);
+        insert_at
+    }
+
+    /// Put the entries in a random order (Winamp's "Randomize list")
+    pub fn randomize(&mut self) {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.shuffle(&mut rand::thread_rng());
+        self.reorder(&order);
+    }
+
+    /// Replace every entry, e.g. to restore an undo snapshot.
+    ///
+    /// The current track, queue and history stay attached to their files when
+    /// those are still in the list.
+    pub fn replace_entries(&mut self, entries: Vec<PathBuf>) {
+        let mut positions: HashMap<&Path, usize> = HashMap::with_capacity(entries.len());
+        for (i, path) in entries.iter().enumerate() {
+            positions.entry(path.as_path()).or_insert(i);
+        }
+        let new_index = |i: usize| {
+            self.entries
+                .get(i)
+                .and_then(|path| positions.get(path.as_path()).copied())
+        };
+
+        let current = self.current.and_then(new_index);
+        let queue: VecDeque<usize> = self.queue.iter().filter_map(|&i| new_index(i)).collect();
+        let history: Vec<usize> = self.history.iter().filter_map(|&i| new_index(i)).collect();
+
+        self.counts = count_paths(&entries);
+        self.ids = (self.next_id..).take(entries.len()).collect();
+        self.next_id += entries.len() as u32;
+        self.entries = entries;
+        self.current = current;
+        self.queue = queue;
+        self.history = history;
+        if self.shuffle {
+            self.reshuffle();
+        }
+    }
+
+    /// Stable sort of the entries by a key derived from each path
+    pub fn sort_by_key<K: Ord>(&mut self, mut key: impl FnMut(&Path) -> K) {
+        let keys: Vec<K> = self.entries.iter().map(|path| key(path)).collect();
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+        self.reorder(&order);
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.counts.clear();
+        self.ids.clear();
+        self.current = None;
+        self.shuffle_order.clear();
+        self.queue.clear();
+        self.history.clear();
+    }
+
+    /// Get the index of the current track
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Get the path of the current track
+    pub fn current(&self) -> Option<&Path> {
+        self.current.and_then(|i| self.get(i))
+    }
+
+    /// Make an entry current (e.g. the user double-clicked it).
+    /// Returns the entry's path, or `None` if the index is out of bounds.
+    pub fn select(&mut self, index: usize) -> Option<&Path> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        self.push_history();
+        self.current = Some(index);
+        self.entries.get(index).map(PathBuf::as_path)
+    }
+
+    /// Move to the next track at the user's request.
+    ///
+    /// Queued entries take priority. Repeat One is ignored so "Next" always
+    /// leaves the current track.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<usize> {
+        let next = self
+            .pop_queue()
+            .or_else(|| self.following_index(self.repeat == RepeatMode::All))?;
+        self.push_history();
+        self.current = Some(next);
+        Some(next)
+    }
+
+    /// Move on after the current track finished playing.
+    ///
+    /// Unlike [`Playlist::next`], this honours Repeat One.
+    pub fn advance(&mut self) -> Option<usize> {
+        if self.repeat == RepeatMode::One {
+            if let Some(current) = self.current {
+                return Some(current);
+            }
+        }
+        self.next()
+    }
+
+    /// Move back to the previously played track.
+    ///
+    /// Uses the playback history first, so "Previous" retraces the actual play
+    /// order in shuffle mode, then falls back to the playlist order.
+    pub fn previous(&mut self) -> Option<usize> {
+        while let Some(index) = self.history.pop() {
+            if index < self.entries.len() && Some(index) != self.current {
+                self.current = Some(index);
+                return Some(index);
+            }
+        }
+
+        let previous = self.preceding_index(self.repeat == RepeatMode::All)?;
+        self.current = Some(previous);
+        Some(previous)
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    /// Switch to the next repeat mode and return it
+    pub fn cycle_repeat(&mut self) -> RepeatMode {
+        self.repeat = self.repeat.cycle();
+        self.repeat
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Enable or disable shuffle. Enabling builds a fresh permutation that
+    /// starts from the current track.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        if shuffle {
+            self.reshuffle();
+        } else {
+            self.shuffle_order.clear();
+        }
+    }
+
+    pub fn shuffle_mode(&self) -> ShuffleMode {
+        self.shuffle_mode
+    }
+
+    pub fn set_shuffle_mode(&mut self, mode: ShuffleMode) {
+        self.shuffle_mode = mode;
+        if self.shuffle {
+            self.reshuffle();
+        }
+    }
+
+    /// Get the current shuffle permutation (empty when shuffle is off)
+    pub fn shuffle_order(&self) -> &[usize] {
+        &self.shuffle_order
+    }
+
+    /// Queue an entry to play next, after any already queued entries
+    pub fn enqueue(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.queue.push_back(index);
+        }
+    }
+
+    /// Add a path to the playlist (if needed) and queue it to play next
+    pub fn enqueue_path(&mut self, path: PathBuf) -> usize {
+        let existing = if self.contains(&path) {
+            self.entries.iter().position(|p| *p == path)
+        } else {
+            None
+        };
+        let index = match existing {
+            Some(index) => index,
+            None => self.add(path),
+        };
+        self.enqueue(index);
+        index
+    }
+
+    /// Remove every occurrence of an entry from the queue
+    pub fn dequeue(&mut self, index: usize) {
+        self.queue.retain(|&i| i != index);
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Get the queued entry indices in play order
+    pub fn queue(&self) -> impl Iterator<Item = usize> + '_ {
+        self.queue.iter().copied()
+    }
+
+    /// Entries still to play after the current one in this pass: the queue
+    /// first, then the rest of the play order without wrapping around
+    pub fn upcoming(&self) -> Vec<usize> {
+        let order: Vec<usize> = if self.shuffle && self.shuffle_order.len() == self.entries.len() {
+            self.shuffle_order.clone()
+        } else {
+            (0..self.entries.len()).collect()
+        };
+        let start = match self.current {
+            Some(current) => order
+                .iter()
+                .position(|&i| i == current)
+                .map_or(0, |p| p + 1),
+            None => 0,
+        };
+
+        let mut upcoming: Vec<usize> = self
+            .queue
+            .iter()
+            .copied()
+            .filter(|&i| i < self.entries.len())
+            .collect();
+        let queued: HashSet<usize> = upcoming.iter().copied().collect();
+        upcoming.extend(order[start..].iter().filter(|i| !queued.contains(i)));
+        upcoming
+    }
+
+    /// Get the 1-based queue position of an entry, if it is queued
+    pub fn queue_position(&self, index: usize) -> Option<usize> {
+        self.queue.iter().position(|&i| i == index).map(|p| p + 1)
+    }
+
+    /// Get the playback history, most recent last
+    pub fn history(&self) -> &[usize] {
+        &self.history
+    }
+
+    fn pop_queue(&mut self) -> Option<usize> {
+        while let Some(index) = self.queue.pop_front() {
+            if index < self.entries.len() {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn push_history(&mut self) {
+        if let Some(current) = self.current {
+            if self.history.last() != Some(&current) {
+                self.history.push(current);
+            }
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+    }
+
+    /// Index that follows the current one in play order
+    fn following_index(&mut self, wrap: bool) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        if !self.shuffle {
+            return match self.current {
+                None => Some(0),
+                Some(current) if current + 1 < self.entries.len() => Some(current + 1),
+                Some(_) if wrap => Some(0),
+                Some(_) => None,
+            };
+        }
+
+        if self.shuffle_order.len() != self.entries.len() {
+            self.reshuffle();
+        }
+
+        let position = self
+            .current
+            .and_then(|current| self.shuffle_order.iter().position(|&i| i == current));
+
+        match position {
+            None => self.shuffle_order.first().copied(),
+            Some(p) if p + 1 < self.shuffle_order.len() => Some(self.shuffle_order[p + 1]),
+            Some(_) if wrap => {
+                // Start a new round with a fresh permutation that doesn't open
+                // with the track (or, in album mode, the album) just played
+                let mut order = self.build_shuffle_order(None);
+                if let Some(last) = self.current {
+                    let last_album = album_key(&self.entries[last]);
+                    let repeated = order
+                        .iter()
+                        .take_while(|&&i| match self.shuffle_mode {
+                            ShuffleMode::Tracks => i == last,
+                            ShuffleMode::Albums => album_key(&self.entries[i]) == last_album,
+                        })
+                        .count();
+                    if repeated < order.len() {
+                        order.rotate_left(repeated);
+                    }
+                }
+                self.shuffle_order = order;
+                self.shuffle_order.first().copied()
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Index that precedes the current one in play order
+    fn preceding_index(&self, wrap: bool) -> Option<usize> {
+        let order: Vec<usize> = if self.shuffle && self.shuffle_order.len() == self.entries.len() {
+            self.shuffle_order.clone()
+        } else {
+            (0..self.entries.len()).collect()
+        };
+
+        let current = self.current?;
+        let position = order.iter().position(|&i| i == current)?;
+
+        if position > 0 {
+            Some(order[position - 1])
+        } else if wrap {
+            order.last().copied()
+        } else {
+            None
+        }
+    }
+
+    /// Drop every entry for which `keep` is false, fixing up all indices
+    fn retain_by_index(&mut self, keep: impl Fn(usize) -> bool) {
+        let mut new_index = vec![None; self.entries.len()];
+        let mut next = 0;
+        for (i, slot) in new_index.iter_mut().enumerate() {
+            if keep(i) {
+                *slot = Some(next);
+                next += 1;
+            }
+        }
+
+        let mut i = 0;
+        self.entries.retain(|_| {
+            i += 1;
+            keep(i - 1)
+        });
+        let mut i = 0;
+        self.ids.retain(|_| {
+            i += 1;
+            keep(i - 1)
+        });
+        self.counts = count_paths(&self.entries);
+
+        let shift = |i: usize| new_index.get(i).copied().flatten();
+        self.current = self.current.and_then(shift);
+        self.shuffle_order = self
+            .shuffle_order
+            .iter()
+            .filter_map(|&i| shift(i))
+            .collect();
+        self.queue = self.queue.iter().filter_map(|&i| shift(i)).collect();
+        self.history = self.history.iter().filter_map(|&i| shift(i)).collect();
+    }
+
+    /// Forget one occurrence of a removed entry
+    fn uncount(&mut self, path: &Path) {
+        if let Some(count) = self.counts.get_mut(path) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(path);
+            }
+        }
+    }
+
+    fn reshuffle(&mut self) {
+        self.shuffle_order = self.build_shuffle_order(self.current);
+    }
+
+    /// Build a permutation of all entries, starting with `first` if given
+    fn build_shuffle_order(&self, first: Option<usize>) -> Vec<usize> {
+        let mut rng = rand::thread_rng();
+
+        let mut order: Vec<usize> = match self.shuffle_mode {
+            ShuffleMode::Tracks => {
+                let mut order: Vec<usize> = (0..self.entries.len()).collect();
+                order.shuffle(&mut rng);
+                order
+            }
+            ShuffleMode::Albums => {
+                let mut albums = self.album_groups();
+                albums.shuffle(&mut rng);
+                albums.into_iter().flatten().collect()
+            }
+        };
+
+        if let Some(first) = first {
+            match self.shuffle_mode {
+                ShuffleMode::Tracks => {
+                    if let Some(pos) = order.iter().position(|&i| i == first) {
+                        order.swap(0, pos);
+                    }
+                }
+                ShuffleMode::Albums => {
+                    // Move the current track's album to the front, starting at the current track
+                    let key = album_key(&self.entries[first]);
+                    let (mut head, tail): (Vec<usize>, Vec<usize>) = order
+                        .into_iter()
+                        .partition(|&i| album_key(&self.entries[i]) == key);
+                    if let Some(pos) = head.iter().position(|&i| i == first) {
+                        head.rotate_left(pos);
+                    }
+                    head.extend(tail);
+                    order = head;
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Group entry indices into albums, preserving playlist order within each album
+    fn album_groups(&self) -> Vec<Vec<usize>> {
+        // Position of each album in `groups`, which keeps first-seen order
+        let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let position = *positions.entry(album_key(entry)).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[position].push(index);
+        }
+        groups
+    }
+
+    /// Insert a newly added entry at a random position among the tracks not yet played
+    fn insert_into_shuffle_order(&mut self, index: usize) {
+        use rand::Rng;
+
+        let start = self
+            .current
+            .and_then(|current| self.shuffle_order.iter().position(|&i| i == current))
+            .map_or(0, |p| p + 1);
+        let position = rand::thread_rng().gen_range(start..=self.shuffle_order.len());
+        self.shuffle_order.insert(position, index);
+    }
+}
+
+/// Number of occurrences of each path
+fn count_paths(paths: &[PathBuf]) -> HashMap<PathBuf, usize> {
+    let mut counts = HashMap::with_capacity(paths.len());
+    for path in paths {
+        *counts.entry(path.clone()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Album grouping key for album shuffle: the directory containing the track
+fn album_key(path: &Path) -> PathBuf {
+    let (file, _) = cue::split_virtual_track(path);
+    file.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(count: usize) -> Playlist {
+        Playlist::from_paths(
+            (0..count)
+                .map(|i| PathBuf::from(format!("/music/track{}.mp3", i)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_repeat_mode_parsing() {
+        assert_eq!("off".parse::<RepeatMode>(), Ok(RepeatMode::Off));
+        assert_eq!("One".parse::<RepeatMode>(), Ok(RepeatMode::One));
+        assert_eq!("all".parse::<RepeatMode>(), Ok(RepeatMode::All));
+        assert!("sometimes".parse::<RepeatMode>().is_err());
+        assert_eq!(RepeatMode::Off.cycle(), RepeatMode::All);
+        assert_eq!(RepeatMode::All.to_string(), "all");
+    }
+
+    #[test]
+    fn test_sequential_next_repeat_off() {
+        let mut list = playlist(3);
+        assert_eq!(list.next(), Some(0));
+        assert_eq!(list.next(), Some(1));
+        assert_eq!(list.next(), Some(2));
+        assert_eq!(list.next(), None);
+        assert_eq!(list.current_index(), Some(2));
+    }
+
+    #[test]
+    fn test_sequential_next_repeat_all() {
+        let mut list = playlist(2);
+        list.set_repeat(RepeatMode::All);
+        list.select(1);
+        assert_eq!(list.next(), Some(0));
+    }
+
+    #[test]
+    fn test_repeat_one() {
+        let mut list = playlist(3);
+        list.set_repeat(RepeatMode::One);
+        list.select(1);
+        assert_eq!(list.advance(), Some(1));
+        // A manual "Next" still moves on
+        assert_eq!(list.next(), Some(2));
+    }
+
+    #[test]
+    fn test_shuffle_is_a_permutation() {
+        let mut list = playlist(50);
+        list.set_shuffle(true);
+
+        let mut played = Vec::new();
+        while let Some(index) = list.next() {
+            played.push(index);
+        }
+
+        played.sort_unstable();
+        assert_eq!(played, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_shuffle_starts_from_current() {
+        let mut list = playlist(20);
+        list.select(7);
+        list.set_shuffle(true);
+        assert_eq!(list.shuffle_order()[0], 7);
+    }
+
+    #[test]
+    fn test_shuffle_repeat_all_reshuffles() {
+        let mut list = playlist(5);
+        list.set_shuffle(true);
+        list.set_repeat(RepeatMode::All);
+
+        let mut played = Vec::new();
+        for _ in 0..10 {
+            played.push(list.next().unwrap());
+        }
+
+        let mut first_round = played[..5].to_vec();
+        first_round.sort_unstable();
+        assert_eq!(first_round, vec![0, 1, 2, 3, 4]);
+        // No track plays twice in a row across rounds
+        assert_ne!(played[4], played[5]);
+    }
+
+    #[test]
+    fn test_album_shuffle_keeps_albums_together() {
+        let mut list = Playlist::from_paths(vec![
+            PathBuf::from("/a/1.mp3"),
+            PathBuf::from("/a/2.mp3"),
+            PathBuf::from("/b/1.mp3"),
+            PathBuf::from("/b/2.mp3"),
+            PathBuf::from("/c/1.mp3"),
+        ]);
+        list.set_shuffle_mode(ShuffleMode::Albums);
+        list.set_shuffle(true);
+
+        let order = list.shuffle_order().to_vec();
+        for pair in [(0, 1), (2, 3)] {
+            let first = order.iter().position(|&i| i == pair.0).unwrap();
+            assert_eq!(order[first + 1], pair.1);
+        }
+    }
+
+    #[test]
+    fn test_album_shuffle_repeat_all_starts_with_another_album() {
+        let mut list = Playlist::from_paths(vec![
+            PathBuf::from("/a/1.mp3"),
+            PathBuf::from("/a/2.mp3"),
+            PathBuf::from("/a/3.mp3"),
+            PathBuf::from("/b/1.mp3"),
+            PathBuf::from("/b/2.mp3"),
+        ]);
+        list.set_shuffle_mode(ShuffleMode::Albums);
+        list.set_shuffle(true);
+        list.set_repeat(RepeatMode::All);
+
+        let mut played = Vec::new();
+        for _ in 0..50 {
+            played.push(list.next().unwrap());
+        }
+        // Albums alternate and always play whole, in order
+        for round in played.chunks(5) {
+            let albums: Vec<_> = round.iter().map(|&i| i < 3).collect();
+            assert!(
+                albums == [true, true, true, false, false]
+                    || albums == [false, false, true, true, true]
+            );
+        }
+        for pair in played.windows(2) {
+            if pair[0] < 3 && pair[1] < 3 || pair[0] >= 3 && pair[1] >= 3 {
+                assert_eq!(pair[1], pair[0] + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_follows_edits() {
+        let mut list = playlist(3);
+        list.add(PathBuf::from("/music/track1.mp3"));
+        list.remove(1);
+        assert!(list.contains(Path::new("/music/track1.mp3")));
+        list.remove_many(&[2]);
+        assert!(!list.contains(Path::new("/music/track1.mp3")));
+
+        list.add_expanded(Path::new("/music/track0.mp3"));
+        assert_eq!(list.len(), 2);
+        list.replace_entries(vec![PathBuf::from("/music/new.mp3")]);
+        assert!(list.contains(Path::new("/music/new.mp3")));
+        assert!(!list.contains(Path::new("/music/track0.mp3")));
+        list.clear();
+        assert!(!list.contains(Path::new("/music/new.mp3")));
+    }
+
+    #[test]
+    fn test_find_expanded_cue_tracks() {
+        let dir = std::env::temp_dir().join("oneamp_playlist_test_find_expanded");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let sheet = dir.join("album.cue");
+        std::fs::write(
+            &sheet,
+            "FILE \"album.flac\" WAVE\n\
+             TRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+             TRACK 02 AUDIO\nINDEX 01 03:00:00\n",
+        )
+        .unwrap();
+
+        let mut list = playlist(2);
+        list.add_expanded(&sheet);
+        assert_eq!(list.len(), 4);
+        // The sheet itself never is an entry, its first track is
+        assert_eq!(list.find_expanded(&sheet), Some(2));
+        assert_eq!(list.find_expanded(Path::new("/music/track1.mp3")), Some(1));
+        assert_eq!(list.find_expanded(Path::new("/music/other.mp3")), None);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_queue_takes_priority() {
+        let mut list = playlist(5);
+        list.select(0);
+        list.enqueue(3);
+        list.enqueue(1);
+        assert_eq!(list.queue_position(1), Some(2));
+
+        assert_eq!(list.next(), Some(3));
+        assert_eq!(list.next(), Some(1));
+        // Back to playlist order after the queue is drained
+        assert_eq!(list.next(), Some(2));
+    }
+
+    #[test]
+    fn test_enqueue_path_adds_missing_entries() {
+        let mut list = playlist(2);
+        let index = list.enqueue_path(PathBuf::from("/music/new.mp3"));
+        assert_eq!(index, 2);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.next(), Some(2));
+    }
+
+    #[test]
+    fn test_previous_uses_history_in_shuffle() {
+        let mut list = playlist(10);
+        list.set_shuffle(true);
+
+        let first = list.next().unwrap();
+        let second = list.next().unwrap();
+        let _third = list.next().unwrap();
+
+        assert_eq!(list.previous(), Some(second));
+        assert_eq!(list.previous(), Some(first));
+    }
+
+    #[test]
+    fn test_previous_without_history() {
+        let mut list = playlist(3);
+        assert_eq!(list.previous(), None);
+
+        list.current = Some(0);
+        assert_eq!(list.previous(), None);
+        list.set_repeat(RepeatMode::All);
+        assert_eq!(list.previous(), Some(2));
+    }
+
+    #[test]
+    fn test_remove_adjusts_indices() {
+        let mut list = playlist(5);
+        list.select(3);
+        list.enqueue(4);
+        list.enqueue(1);
+
+        assert!(list.remove(1).is_some());
+        assert_eq!(list.current_index(), Some(2));
+        assert_eq!(list.queue().collect::<Vec<_>>(), vec![3]);
+
+        list.remove(2);
+        assert_eq!(list.current_index(), None);
+    }
+
+    #[test]
+    fn test_reorder_follows_entries() {
+        let mut list = playlist(4);
+        list.select(1);
+        list.enqueue(3);
+
+        list.reorder(&[3, 2, 1, 0]);
+
+        assert_eq!(list.get(0), Some(Path::new("/music/track3.mp3")));
+        assert_eq!(list.current(), Some(Path::new("/music/track1.mp3")));
+        assert_eq!(list.current_index(), Some(2));
+        assert_eq!(list.queue().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn test_sort_by_key() {
+        let mut list = Playlist::from_paths(vec![
+            PathBuf::from("/music/b.mp3"),
+            PathBuf::from("/music/c.mp3"),
+            PathBuf::from("/music/a.mp3"),
+        ]);
+        list.select(0);
+
+        list.sort_by_key(|path| path.to_path_buf());
+        assert_eq!(list.get(0), Some(Path::new("/music/a.mp3")));
+        assert_eq!(list.current(), Some(Path::new("/music/b.mp3")));
+
+        list.sort_by_key(|path| std::cmp::Reverse(path.to_path_buf()));
+        assert_eq!(list.get(0), Some(Path::new("/music/c.mp3")));
+    }
+
+    #[test]
+    fn test_remove_many_and_retain() {
+        let mut list = playlist(6);
+        list.select(4);
+        list.enqueue(1);
+        list.enqueue(5);
+
+        list.remove_many(&[0, 1, 3]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.current(), Some(Path::new("/music/track4.mp3")));
+        assert_eq!(list.queue().collect::<Vec<_>>(), vec![2]);
+
+        // Crop to the current track
+        list.retain_indices(&[1]);
+        assert_eq!(list.entries(), &[PathBuf::from("/music/track4.mp3")]);
+        assert_eq!(list.current_index(), Some(0));
+        assert_eq!(list.queue().count(), 0);
+    }
+
+    #[test]
+    fn test_move_entries() {
+        let mut list = playlist(5);
+        list.select(0);
+
+        // Move tracks 3 and 4 in front of track 1
+        assert_eq!(list.move_entries(&[3, 4], 1), 1);
+        let names: Vec<_> = list
+            .entries()
+            .iter()
+            .map(|p| p.file_stem().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["track0", "track3", "track4", "track1", "track2"]);
+
+        // Move track0 to the end
+        assert_eq!(list.move_entries(&[0], list.len()), 4);
+        assert_eq!(list.current_index(), Some(4));
+    }
+
+    #[test]
+    fn test_ids_follow_entries() {
+        let mut list = playlist(4);
+        let id = list.id(3).unwrap();
+        list.move_entries(&[3], 0);
+        assert_eq!(list.index_of_id(id), Some(0));
+
+        let removed = list.id(1).unwrap();
+        list.remove_many(&[1, 2]);
+        assert_eq!(list.index_of_id(id), Some(0));
+        assert_eq!(list.index_of_id(removed), None);
+
+        // Ids of removed entries are not handed out again
+        let added = list.add(PathBuf::from("/music/new.mp3"));
+        assert!(list.id(added).unwrap() > id);
+
+        list.clear();
+        assert_eq!(list.index_of_id(id), None);
+    }
+
+    #[test]
+    fn test_randomize_is_a_permutation() {
+        let mut list = playlist(20);
+        list.select(7);
+        list.randomize();
+
+        let mut entries = list.entries().to_vec();
+        entries.sort();
+        let mut expected = playlist(20).entries().to_vec();
+        expected.sort();
+        assert_eq!(entries, expected);
+        assert_eq!(list.current(), Some(Path::new("/music/track7.mp3")));
+    }
+
+    #[test]
+    fn test_replace_entries_keeps_current_by_path() {
+        let mut list = playlist(3);
+        list.select(2);
+        list.enqueue(0);
+
+        list.replace_entries(vec![
+            PathBuf::from("/music/track2.mp3"),
+            PathBuf::from("/music/other.mp3"),
+        ]);
+        assert_eq!(list.current_index(), Some(0));
+        assert_eq!(list.queue().count(), 0);
+    }
+
+    #[test]
+    fn test_add_while_shuffled() {
+        let mut list = playlist(3);
+        list.set_shuffle(true);
+        list.add(PathBuf::from("/music/extra.mp3"));
+
+        let mut order = list.shuffle_order().to_vec();
+        order.sort_unstable();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut list = playlist(3);
+        list.select(1);
+        list.enqueue(2);
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.current_index(), None);
+        assert_eq!(list.queue().count(), 0);
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let mut list = playlist(5);
+        assert_eq!(list.upcoming(), vec![0, 1, 2, 3, 4]);
+
+        list.select(1);
+        list.enqueue(4);
+        assert_eq!(list.upcoming(), vec![4, 2, 3]);
+
+        list.set_shuffle(true);
+        let upcoming = list.upcoming();
+        assert_eq!(upcoming[0], 4);
+        assert_eq!(upcoming.len(), 4);
+        assert!(!upcoming.contains(&1));
+    }
+}
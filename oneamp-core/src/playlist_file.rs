@@ -0,0 +1,151 @@
+// Playlist Files
+// Reads M3U/M3U8 and PLS playlists into lists of file paths.
+// Relative entries are resolved against the playlist's directory; HTTP(S)
+// entries (internet radio) are kept as stream URLs.
+
+use crate::stream;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Playlist file extensions that can be loaded
+pub const PLAYLIST_EXTENSIONS: [&str; 3] = ["m3u", "m3u8", "pls"];
+
+/// Check whether a path is an M3U or PLS playlist
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PLAYLIST_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Read the entries of a playlist file
+pub fn load(path: &Path) -> Result<Vec<PathBuf>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // Plain .m3u files are often Latin-1; keep what decodes
+    let content = String::from_utf8_lossy(&bytes);
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let is_pls = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+    Ok(if is_pls {
+        parse_pls(&content, base_dir)
+    } else {
+        parse_m3u(&content, base_dir)
+    })
+}
+
+/// Parse M3U content: one entry per line, `#` lines are comments or
+/// `#EXTINF` directives
+pub fn parse_m3u(content: &str, base_dir: &Path) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|entry| resolve_entry(entry, base_dir))
+        .collect()
+}
+
+/// Parse PLS content: `FileN=` keys in the `[playlist]` section, in N order
+pub fn parse_pls(content: &str, base_dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<(u32, &str)> = content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let number = key.trim().strip_prefix("File")?.parse().ok()?;
+            Some((number, value.trim()))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    entries
+        .into_iter()
+        .filter_map(|(_, entry)| resolve_entry(entry, base_dir))
+        .collect()
+}
+
+/// Turn an entry into a path. Stream URLs are kept; other URLs are skipped.
+fn resolve_entry(entry: &str, base_dir: &Path) -> Option<PathBuf> {
+    if let Some(path) = entry.strip_prefix("file://") {
+        return Some(PathBuf::from(percent_decode(path)));
+    }
+    if stream::is_stream_url(Path::new(entry)) {
+        return Some(PathBuf::from(entry));
+    }
+    if entry.contains("://") {
+        return None;
+    }
+    // Playlists written on Windows use backslashes
+    let entry = if cfg!(windows) {
+        entry.to_string()
+    } else {
+        entry.replace('\\', "/")
+    };
+    Some(base_dir.join(entry))
+}
+
+/// Decode `%XX` escapes of a `file://` URL
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_m3u() {
+        let content = "#EXTM3U\n#EXTINF:123,Artist - Title\n01.mp3\n\nsub\\02.flac\n/abs/03.ogg\nfile:///abs/My%20Song.wav\nhttp://radio.example/stream\nrtsp://radio.example/live\n";
+        let entries = parse_m3u(content, Path::new("/music"));
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/music/01.mp3"),
+                PathBuf::from("/music/sub/02.flac"),
+                PathBuf::from("/abs/03.ogg"),
+                PathBuf::from("/abs/My Song.wav"),
+                PathBuf::from("http://radio.example/stream"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pls() {
+        let content =
+            "[playlist]\nFile2=b.mp3\nTitle2=B\nFile1=a.mp3\nNumberOfEntries=2\nVersion=2\n";
+        let entries = parse_pls(content, Path::new("/music"));
+        assert_eq!(
+            entries,
+            vec![PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3")]
+        );
+
+        // Radio station playlists list their stream servers
+        let content = "[playlist]\nnumberofentries=2\nFile1=https://ice1.example/radio.mp3\nTitle1=Radio\nLength1=-1\nFile2=http://ice2.example:8000/radio.mp3\n";
+        assert_eq!(
+            parse_pls(content, Path::new("/music")),
+            vec![
+                PathBuf::from("https://ice1.example/radio.mp3"),
+                PathBuf::from("http://ice2.example:8000/radio.mp3"),
+            ]
+        );
+    }
+}
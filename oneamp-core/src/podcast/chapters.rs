@@ -0,0 +1,337 @@
+// Podcast Chapters
+// Chapter marks come from three places: Podlove Simple Chapters inside the
+// feed, Podcasting 2.0 JSON chapter files linked from the feed, and ID3v2
+// `CHAP` frames inside downloaded MP3 files.
+
+use super::Chapter;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+/// Sort chapters by start time and end each one where the next begins,
+/// unless it has its own end
+pub(crate) fn sorted(mut chapters: Vec<Chapter>) -> Vec<Chapter> {
+    chapters.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    for i in 1..chapters.len() {
+        let next_start = chapters[i].start_secs;
+        let previous = &mut chapters[i - 1];
+        if previous
+            .end_secs
+            .is_none_or(|end| end <= previous.start_secs)
+        {
+            previous.end_secs = Some(next_start);
+        }
+    }
+    chapters
+}
+
+/// Index of the chapter playing at `secs`
+pub fn chapter_at(chapters: &[Chapter], secs: f32) -> Option<usize> {
+    chapters
+        .iter()
+        .rposition(|chapter| chapter.start_secs <= secs)
+}
+
+#[derive(Deserialize)]
+struct JsonChapters {
+    chapters: Vec<JsonChapter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonChapter {
+    start_time: f32,
+    end_time: Option<f32>,
+    #[serde(default)]
+    title: String,
+    url: Option<String>,
+    img: Option<String>,
+    /// `false` marks silent chapters that only change artwork or links
+    toc: Option<bool>,
+}
+
+/// Parse a Podcasting 2.0 JSON chapters file (`application/json+chapters`)
+pub fn parse_json_chapters(json: &str) -> Result<Vec<Chapter>> {
+    let file: JsonChapters = serde_json::from_str(json).context("Invalid chapters file")?;
+    Ok(sorted(
+        file.chapters
+            .into_iter()
+            .filter(|chapter| chapter.toc != Some(false) && chapter.start_time >= 0.0)
+            .map(|chapter| Chapter {
+                start_secs: chapter.start_time,
+                end_secs: chapter.end_time,
+                title: chapter.title,
+                url: chapter.url,
+                image: chapter.img,
+            })
+            .collect(),
+    ))
+}
+
+/// Read the ID3v2 `CHAP` frames at the start of an audio file. Files
+/// without an ID3v2 tag have no chapters.
+pub fn read_id3_chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+        return Ok(Vec::new());
+    }
+    let size = syncsafe(&header[6..10]) as usize;
+    let mut tag = header.to_vec();
+    tag.resize(10 + size, 0);
+    file.read_exact(&mut tag[10..])
+        .context("Truncated ID3v2 tag")?;
+    parse_id3_chapters(&tag)
+}
+
+/// Chapters of a complete ID3v2.3 or v2.4 tag, header included
+pub fn parse_id3_chapters(tag: &[u8]) -> Result<Vec<Chapter>> {
+    if tag.len() < 10 || &tag[..3] != b"ID3" {
+        bail!("Not an ID3v2 tag");
+    }
+    let version = tag[3];
+    if !(3..=4).contains(&version) {
+        // ID3v2.2 predates chapters
+        return Ok(Vec::new());
+    }
+    let flags = tag[5];
+    let end = (10 + syncsafe(&tag[6..10]) as usize).min(tag.len());
+    let mut start = 10;
+    if flags & 0x40 != 0 && end >= 14 {
+        // Skip the extended header; v2.3 doesn't count its own size field
+        start += match version {
+            3 => 4 + u32::from_be_bytes(tag[10..14].try_into().unwrap()) as usize,
+            _ => syncsafe(&tag[10..14]) as usize,
+        };
+    }
+
+    let mut chapters = Vec::new();
+    for (id, body) in frames(tag.get(start..end).unwrap_or_default(), version) {
+        if id == b"CHAP" {
+            chapters.extend(chap_frame(body, version));
+        }
+    }
+    Ok(sorted(chapters))
+}
+
+/// Frame IDs and bodies of a run of ID3v2 frames, up to the padding
+fn frames(mut data: &[u8], version: u8) -> Vec<(&[u8], &[u8])> {
+    let mut frames = Vec::new();
+    while data.len() >= 10 && data[0] != 0 {
+        let size = match version {
+            3 => u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            _ => syncsafe(&data[4..8]),
+        } as usize;
+        let Some(body) = data.get(10..10 + size) else {
+            break;
+        };
+        frames.push((&data[..4], body));
+        data = &data[10 + size..];
+    }
+    frames
+}
+
+fn chap_frame(body: &[u8], version: u8) -> Option<Chapter> {
+    let id_end = body.iter().position(|&b| b == 0)?;
+    let times = body.get(id_end + 1..id_end + 17)?;
+    let start_ms = u32::from_be_bytes(times[0..4].try_into().unwrap());
+    let end_ms = u32::from_be_bytes(times[4..8].try_into().unwrap());
+
+    let mut title = String::new();
+    let mut url = None;
+    for (id, subframe) in frames(&body[id_end + 17..], version) {
+        match id {
+            b"TIT2" => title = decode_text(subframe),
+            b"WXXX" => {
+                // Encoding, a description in that encoding, then a Latin-1 URL
+                let (&encoding, rest) = subframe.split_first()?;
+                let terminator: &[u8] = if matches!(encoding, 1 | 2) {
+                    &[0, 0]
+                } else {
+                    &[0]
+                };
+                let start = rest
+                    .windows(terminator.len())
+                    .enumerate()
+                    .step_by(terminator.len())
+                    .find(|(_, window)| *window == terminator)
+                    .map(|(i, _)| i + terminator.len())?;
+                url = Some(latin1(&rest[start..]));
+            }
+            _ => {}
+        }
+    }
+
+    Some(Chapter {
+        start_secs: start_ms as f32 / 1000.0,
+        end_secs: Some(end_ms)
+            .filter(|&end| end > start_ms && end != u32::MAX)
+            .map(|end| end as f32 / 1000.0),
+        title,
+        url: url.filter(|url| !url.is_empty()),
+        image: None,
+    })
+}
+
+/// A text frame: an encoding byte followed by the text
+fn decode_text(data: &[u8]) -> String {
+    let Some((&encoding, text)) = data.split_first() else {
+        return String::new();
+    };
+    let text = match encoding {
+        0 => latin1(text),
+        1 | 2 => {
+            let (big_endian, text) = match text {
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                _ => (encoding == 2, text),
+            };
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|pair| match big_endian {
+                    true => u16::from_be_bytes([pair[0], pair[1]]),
+                    false => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).into_owned(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| char::from(b))
+        .collect()
+}
+
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, &byte| (value << 7) | u32::from(byte & 0x7f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(id: &[u8], body: &[u8], version: u8) -> Vec<u8> {
+        let size = body.len() as u32;
+        let size = match version {
+            3 => size.to_be_bytes(),
+            _ => [
+                (size >> 21) as u8 & 0x7f,
+                (size >> 14) as u8 & 0x7f,
+                (size >> 7) as u8 & 0x7f,
+                size as u8 & 0x7f,
+            ],
+        };
+        let mut frame = id.to_vec();
+        frame.extend_from_slice(&size);
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    fn chap(id: &str, start_ms: u32, end_ms: u32, subframes: &[u8], version: u8) -> Vec<u8> {
+        let mut body = id.as_bytes().to_vec();
+        body.push(0);
+        body.extend_from_slice(&start_ms.to_be_bytes());
+        body.extend_from_slice(&end_ms.to_be_bytes());
+        body.extend_from_slice(&[0xff; 8]);
+        body.extend_from_slice(subframes);
+        frame(b"CHAP", &body, version)
+    }
+
+    fn tag(version: u8, frames: &[u8]) -> Vec<u8> {
+        let size = frames.len() as u32 + 16; // with padding
+        let mut tag = vec![b'I', b'D', b'3', version, 0, 0];
+        tag.extend_from_slice(&[
+            (size >> 21) as u8 & 0x7f,
+            (size >> 14) as u8 & 0x7f,
+            (size >> 7) as u8 & 0x7f,
+            size as u8 & 0x7f,
+        ]);
+        tag.extend_from_slice(frames);
+        tag.resize(tag.len() + 16, 0);
+        tag
+    }
+
+    #[test]
+    fn test_id3_chapters() {
+        for version in [3, 4] {
+            let mut frames = frame(b"TIT2", b"\x03Episode", version);
+            // UTF-16 title with a BOM and a link
+            let title: Vec<u8> = [1, 0xff, 0xfe]
+                .into_iter()
+                .chain("Outro ♪".encode_utf16().flat_map(u16::to_le_bytes))
+                .collect();
+            let mut subframes = frame(b"TIT2", &title, version);
+            subframes.extend(frame(b"WXXX", b"\x00link\x00https://example.com", version));
+            frames.extend(chap("ch2", 90_000, 120_000, &subframes, version));
+            frames.extend(chap(
+                "ch1",
+                0,
+                u32::MAX,
+                &frame(b"TIT2", b"\x00Intro", version),
+                version,
+            ));
+
+            let chapters = parse_id3_chapters(&tag(version, &frames)).unwrap();
+            assert_eq!(chapters.len(), 2);
+            assert_eq!(chapters[0].title, "Intro");
+            assert_eq!(chapters[0].end_secs, Some(90.0));
+            assert_eq!(chapters[1].title, "Outro ♪");
+            assert_eq!(chapters[1].start_secs, 90.0);
+            assert_eq!(chapters[1].end_secs, Some(120.0));
+            assert_eq!(chapters[1].url.as_deref(), Some("https://example.com"));
+        }
+        assert!(parse_id3_chapters(b"not a tag").is_err());
+    }
+
+    #[test]
+    fn test_read_id3_chapters() {
+        let path = std::env::temp_dir().join("oneamp_chapters_test.mp3");
+        let frames = chap("c", 1_500, 3_000, &frame(b"TIT2", b"\x03One", 4), 4);
+        let mut file = tag(4, &frames);
+        file.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        std::fs::write(&path, file).unwrap();
+        let chapters = read_id3_chapters(&path).unwrap();
+        assert_eq!(chapters.len(), 1);
+        assert_eq!(chapters[0].start_secs, 1.5);
+
+        std::fs::write(&path, b"RIFF").unwrap();
+        assert!(read_id3_chapters(&path).unwrap().is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_json_chapters() {
+        let chapters = parse_json_chapters(
+            r#"{"version": "1.2.0", "chapters": [
+                {"startTime": 300, "title": "Two", "img": "https://example.com/2.jpg"},
+                {"startTime": 120, "title": "Art change", "toc": false},
+                {"startTime": 0, "endTime": 100, "title": "One"}
+            ]}"#,
+        )
+        .unwrap();
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["One", "Two"]);
+        assert_eq!(chapters[0].end_secs, Some(100.0));
+        assert_eq!(chapters[1].end_secs, None);
+        assert_eq!(
+            chapters[1].image.as_deref(),
+            Some("https://example.com/2.jpg")
+        );
+        assert!(parse_json_chapters("{}").is_err());
+
+        assert_eq!(chapter_at(&chapters, 50.0), Some(0));
+        assert_eq!(chapter_at(&chapters, 300.0), Some(1));
+        assert_eq!(chapter_at(&chapters[1..], 50.0), None);
+    }
+}
@@ -0,0 +1,256 @@
+// Episode Downloads
+// Episodes are downloaded to a `.part` file next to their destination and
+// renamed once complete. A download that stops early — dropped connection,
+// cancel or quit — resumes from the partial file with a Range request.
+
+use super::Enclosure;
+use anyhow::{bail, Context, Result};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use ureq::http::StatusCode;
+use ureq::Agent;
+
+/// Connections per download before giving up; each resumes where the last
+/// one stopped
+const ATTEMPTS: u32 = 4;
+
+/// Wait before reconnecting, doubled after every failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// File an unfinished download of `destination` is written to
+pub fn partial_path(destination: &Path) -> PathBuf {
+    let mut name = destination.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    destination.with_file_name(name)
+}
+
+/// File extension for an enclosure, from its URL or else its MIME type
+pub fn file_extension(enclosure: &Enclosure) -> &'static str {
+    const KNOWN: [&str; 8] = ["mp3", "m4a", "aac", "ogg", "opus", "flac", "wav", "mp4"];
+    let path = enclosure.url.split(['?', '#']).next().unwrap_or_default();
+    let from_url = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .and_then(|ext| KNOWN.into_iter().find(|known| *known == ext));
+    let from_mime = || match enclosure.mime_type.as_deref()? {
+        "audio/mp4" | "audio/x-m4a" | "audio/m4a" => Some("m4a"),
+        "audio/aac" | "audio/aacp" => Some("aac"),
+        "audio/ogg" | "audio/vorbis" => Some("ogg"),
+        "audio/opus" => Some("opus"),
+        "audio/flac" | "audio/x-flac" => Some("flac"),
+        "audio/wav" | "audio/x-wav" => Some("wav"),
+        "video/mp4" => Some("mp4"),
+        _ => None,
+    };
+    from_url.or_else(from_mime).unwrap_or("mp3")
+}
+
+enum Attempt {
+    Complete,
+    Cancelled,
+}
+
+/// Download `url` to `destination`, resuming an earlier partial download
+/// and reconnecting a few times if the connection drops. `progress` is
+/// called with the bytes received so far and the total size, if known;
+/// returning `false` cancels the download and keeps the partial file.
+pub fn download(
+    agent: &Agent,
+    url: &str,
+    destination: &Path,
+    mut progress: impl FnMut(u64, Option<u64>) -> bool,
+) -> Result<()> {
+    if let Some(dir) = destination.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let partial = partial_path(destination);
+
+    let mut attempt = 0;
+    loop {
+        match download_attempt(agent, url, &partial, &mut progress) {
+            Ok(Attempt::Complete) => break,
+            Ok(Attempt::Cancelled) => bail!("Download cancelled"),
+            Err(e) if attempt + 1 < ATTEMPTS => {
+                eprintln!("Download of {} interrupted: {:#}", url, e);
+                thread::sleep(RETRY_DELAY * 2u32.pow(attempt));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    std::fs::rename(&partial, destination)
+        .with_context(|| format!("Failed to save {}", destination.display()))
+}
+
+fn download_attempt(
+    agent: &Agent,
+    url: &str,
+    partial: &Path,
+    progress: &mut impl FnMut(u64, Option<u64>) -> bool,
+) -> Result<Attempt> {
+    let offset = std::fs::metadata(partial).map_or(0, |m| m.len());
+    let mut request = agent.get(url);
+    if offset > 0 {
+        request = request.header("Range", format!("bytes={}-", offset));
+    }
+    let response = match request.call() {
+        // The partial file already holds the whole episode
+        Err(ureq::Error::StatusCode(416)) if offset > 0 => return Ok(Attempt::Complete),
+        response => response.with_context(|| format!("Failed to download {}", url))?,
+    };
+
+    // Servers that ignore the range send the whole file again
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut received = if resumed { offset } else { 0 };
+    let total = response
+        .body()
+        .content_length()
+        .map(|length| length + received);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+
+    let mut reader = response.into_body().into_reader();
+    let mut chunk = vec![0; CHUNK_BYTES];
+    loop {
+        if !progress(received, total) {
+            return Ok(Attempt::Cancelled);
+        }
+        let read = reader.read(&mut chunk).context("Connection lost")?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&chunk[..read])
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        received += read as u64;
+    }
+    file.flush()?;
+
+    if let Some(total) = total.filter(|&total| received < total) {
+        bail!("Connection closed after {} of {} bytes", received, total);
+    }
+    Ok(Attempt::Complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::http_agent;
+    use crate::test_http::{response, serve};
+
+    const EPISODE: &[u8] = b"0123456789abcdefghij";
+
+    #[test]
+    fn test_resume_partial_download() {
+        let dir = std::env::temp_dir().join("oneamp_download_test_resume");
+        std::fs::remove_dir_all(&dir).ok();
+        let destination = dir.join("episode.mp3");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(partial_path(&destination), &EPISODE[..8]).unwrap();
+
+        let (base, server) = serve(|_| {
+            vec![
+                // The connection drops halfway through the rest...
+                response(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: 12\r\nContent-Range: bytes 8-19/20",
+                    &EPISODE[8..14],
+                ),
+                // ...and the second attempt finishes it
+                response(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nContent-Range: bytes 14-19/20",
+                    &EPISODE[14..],
+                ),
+            ]
+        });
+        let mut totals = Vec::new();
+        download(
+            &http_agent(Duration::from_secs(5)),
+            &format!("{}/ep.mp3", base),
+            &destination,
+            |_, total| {
+                totals.push(total);
+                true
+            },
+        )
+        .unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].to_ascii_lowercase().contains("range: bytes=8-"));
+        assert!(requests[1]
+            .to_ascii_lowercase()
+            .contains("range: bytes=14-"));
+        assert_eq!(std::fs::read(&destination).unwrap(), EPISODE);
+        assert!(!partial_path(&destination).exists());
+        assert!(totals.iter().all(|&total| total == Some(20)));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_download_without_range_support_and_cancel() {
+        let dir = std::env::temp_dir().join("oneamp_download_test_restart");
+        std::fs::remove_dir_all(&dir).ok();
+        let destination = dir.join("episode.mp3");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(partial_path(&destination), b"stale").unwrap();
+
+        let (base, server) = serve(|_| {
+            vec![
+                response("HTTP/1.1 200 OK\r\nContent-Length: 20", EPISODE),
+                response("HTTP/1.1 200 OK\r\nContent-Length: 20", EPISODE),
+            ]
+        });
+        let url = format!("{}/ep.mp3", base);
+        let agent = http_agent(Duration::from_secs(5));
+
+        // Cancelling keeps the partial file
+        let cancelled = download(&agent, &url, &destination, |_, _| false);
+        assert!(cancelled.is_err());
+        assert!(partial_path(&destination).exists());
+        assert!(!destination.exists());
+
+        // The server ignores the range, so the file starts over
+        download(&agent, &url, &destination, |_, _| true).unwrap();
+        server.join().unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), EPISODE);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_file_extension() {
+        let enclosure = |url: &str, mime: Option<&str>| Enclosure {
+            url: url.to_string(),
+            length: None,
+            mime_type: mime.map(String::from),
+        };
+        assert_eq!(
+            file_extension(&enclosure("https://x/a.MP3?id=1", None)),
+            "mp3"
+        );
+        assert_eq!(
+            file_extension(&enclosure("https://x/a.m4a", Some("audio/mpeg"))),
+            "m4a"
+        );
+        assert_eq!(
+            file_extension(&enclosure("https://x/media/42", Some("audio/ogg"))),
+            "ogg"
+        );
+        assert_eq!(
+            file_extension(&enclosure("https://x/media/42", None)),
+            "mp3"
+        );
+    }
+}
@@ -0,0 +1,756 @@
+// Podcast Feeds
+// Parses RSS 2.0 and Atom feeds, with the iTunes, Podcasting 2.0 and
+// Podlove Simple Chapters extensions, into podcasts and episodes.
+// Extension elements are matched by their usual prefixes (`itunes:`,
+// `podcast:`, `psc:`), which is what feeds use in practice.
+
+use super::chapters;
+use super::{Chapter, Enclosure, Episode, Podcast};
+use anyhow::{bail, Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// An XML element with its attributes, text and child elements
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Trimmed text of a child element, if it has any
+    fn text_of(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|child| child.text.trim())
+            .filter(|text| !text.is_empty())
+            .map(String::from)
+    }
+
+    /// An attribute of a child element, e.g. `<itunes:image href="…"/>`
+    fn child_attribute(&self, name: &str, attribute: &str) -> Option<String> {
+        self.child(name)
+            .and_then(|child| child.attribute(attribute))
+            .map(String::from)
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Element> {
+    let mut element = Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        ..Default::default()
+    };
+    for attribute in start.attributes() {
+        let attribute = attribute.context("Invalid XML attribute")?;
+        let value = attribute
+            .unescape_value()
+            .map(|value| value.into_owned())
+            .unwrap_or_else(|_| String::from_utf8_lossy(&attribute.value).into_owned());
+        element.attributes.push((
+            String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+            value,
+        ));
+    }
+    Ok(element)
+}
+
+/// Read a whole document into a tree; the returned element holds the
+/// root element as its only child
+fn parse_xml(xml: &str) -> Result<Element> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack = vec![Element::default()];
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("Invalid XML at byte {}", reader.error_position()))?;
+        match event {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let element = element(&start)?;
+                stack.last_mut().unwrap().children.push(element);
+            }
+            Event::End(_) => {
+                let element = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => bail!("Invalid XML: unexpected closing tag"),
+                }
+            }
+            Event::Text(text) => {
+                // HTML entities such as `&nbsp;` aren't XML; keep them as they are
+                let text = text
+                    .unescape()
+                    .map(|text| text.into_owned())
+                    .unwrap_or_else(|_| String::from_utf8_lossy(&text).into_owned());
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data);
+                stack.last_mut().unwrap().text.push_str(&text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if stack.len() != 1 {
+        bail!("Invalid XML: unexpected end of document");
+    }
+    Ok(stack.pop().unwrap())
+}
+
+/// Parse an RSS or Atom feed downloaded from `feed_url`. Relative links are
+/// resolved against it.
+pub fn parse_feed(feed_url: &str, xml: &str) -> Result<Podcast> {
+    let document = parse_xml(xml)?;
+    let root = document.children.first().context("Empty feed")?;
+    match root.name.as_str() {
+        "rss" => {
+            let channel = root
+                .child("channel")
+                .context("RSS feed without a channel")?;
+            Ok(parse_rss(feed_url, channel, channel.children_named("item")))
+        }
+        // RSS 1.0 keeps the items next to the channel
+        "rdf:RDF" => {
+            let channel = root
+                .child("channel")
+                .context("RSS feed without a channel")?;
+            Ok(parse_rss(feed_url, channel, root.children_named("item")))
+        }
+        "feed" => Ok(parse_atom(feed_url, root)),
+        other => bail!("Not an RSS or Atom feed (found <{}>)", other),
+    }
+}
+
+fn parse_rss<'a>(
+    feed_url: &str,
+    channel: &Element,
+    items: impl Iterator<Item = &'a Element>,
+) -> Podcast {
+    Podcast {
+        feed_url: feed_url.to_string(),
+        title: channel
+            .text_of("title")
+            .unwrap_or_else(|| feed_url.to_string()),
+        author: channel
+            .text_of("itunes:author")
+            .or_else(|| channel.text_of("managingEditor")),
+        description: channel
+            .text_of("description")
+            .or_else(|| channel.text_of("itunes:summary"))
+            .map(|html| plain_text(&html)),
+        link: channel.text_of("link"),
+        image: channel
+            .child_attribute("itunes:image", "href")
+            .or_else(|| {
+                channel
+                    .child("image")
+                    .and_then(|image| image.text_of("url"))
+            })
+            .map(|url| resolve_url(feed_url, &url)),
+        episodes: items
+            .filter_map(|item| rss_episode(feed_url, item))
+            .collect(),
+        updated: None,
+    }
+}
+
+fn rss_episode(feed_url: &str, item: &Element) -> Option<Episode> {
+    let enclosure = item.child("enclosure").and_then(|enclosure| {
+        Some(Enclosure {
+            url: resolve_url(feed_url, enclosure.attribute("url")?),
+            length: enclosure
+                .attribute("length")
+                .and_then(|length| length.parse().ok())
+                .filter(|&length| length > 0),
+            mime_type: enclosure.attribute("type").map(String::from),
+        })
+    });
+    let title = item
+        .text_of("title")
+        .or_else(|| item.text_of("itunes:title"));
+    let guid = item
+        .text_of("guid")
+        .or_else(|| enclosure.as_ref().map(|e| e.url.clone()))
+        .or_else(|| title.clone())?;
+
+    Some(Episode {
+        guid,
+        title: title.unwrap_or_else(|| "Untitled episode".to_string()),
+        description: item
+            .text_of("description")
+            .or_else(|| item.text_of("itunes:summary"))
+            .or_else(|| item.text_of("content:encoded"))
+            .map(|html| plain_text(&html)),
+        published: item.text_of("pubDate").and_then(|date| parse_date(&date)),
+        enclosure,
+        duration_secs: item
+            .text_of("itunes:duration")
+            .and_then(|duration| parse_clock(&duration)),
+        image: item
+            .child_attribute("itunes:image", "href")
+            .map(|url| resolve_url(feed_url, &url)),
+        link: item.text_of("link"),
+        season: item.text_of("itunes:season").and_then(|n| n.parse().ok()),
+        number: item.text_of("itunes:episode").and_then(|n| n.parse().ok()),
+        chapters: psc_chapters(item),
+        chapters_url: item
+            .child_attribute("podcast:chapters", "url")
+            .map(|url| resolve_url(feed_url, &url)),
+        state: Default::default(),
+    })
+}
+
+fn parse_atom(feed_url: &str, feed: &Element) -> Podcast {
+    Podcast {
+        feed_url: feed_url.to_string(),
+        title: feed
+            .text_of("title")
+            .unwrap_or_else(|| feed_url.to_string()),
+        author: feed
+            .child("author")
+            .and_then(|author| author.text_of("name")),
+        description: feed.text_of("subtitle").map(|html| plain_text(&html)),
+        link: atom_link(feed, "alternate").map(|url| resolve_url(feed_url, &url)),
+        image: feed
+            .child_attribute("itunes:image", "href")
+            .or_else(|| feed.text_of("logo"))
+            .or_else(|| feed.text_of("icon"))
+            .map(|url| resolve_url(feed_url, &url)),
+        episodes: feed
+            .children_named("entry")
+            .filter_map(|entry| atom_episode(feed_url, entry))
+            .collect(),
+        updated: None,
+    }
+}
+
+fn atom_episode(feed_url: &str, entry: &Element) -> Option<Episode> {
+    let enclosure = entry
+        .children_named("link")
+        .find(|link| link.attribute("rel") == Some("enclosure"))
+        .and_then(|link| {
+            Some(Enclosure {
+                url: resolve_url(feed_url, link.attribute("href")?),
+                length: link
+                    .attribute("length")
+                    .and_then(|length| length.parse().ok())
+                    .filter(|&length| length > 0),
+                mime_type: link.attribute("type").map(String::from),
+            })
+        });
+    let title = entry.text_of("title");
+    let guid = entry
+        .text_of("id")
+        .or_else(|| enclosure.as_ref().map(|e| e.url.clone()))
+        .or_else(|| title.clone())?;
+
+    Some(Episode {
+        guid,
+        title: title.unwrap_or_else(|| "Untitled episode".to_string()),
+        description: entry
+            .text_of("summary")
+            .or_else(|| entry.text_of("content"))
+            .map(|html| plain_text(&html)),
+        published: entry
+            .text_of("published")
+            .or_else(|| entry.text_of("updated"))
+            .and_then(|date| parse_date(&date)),
+        enclosure,
+        duration_secs: entry
+            .text_of("itunes:duration")
+            .and_then(|duration| parse_clock(&duration)),
+        image: entry
+            .child_attribute("itunes:image", "href")
+            .map(|url| resolve_url(feed_url, &url)),
+        link: atom_link(entry, "alternate").map(|url| resolve_url(feed_url, &url)),
+        season: entry.text_of("itunes:season").and_then(|n| n.parse().ok()),
+        number: entry.text_of("itunes:episode").and_then(|n| n.parse().ok()),
+        chapters: psc_chapters(entry),
+        chapters_url: entry
+            .child_attribute("podcast:chapters", "url")
+            .map(|url| resolve_url(feed_url, &url)),
+        state: Default::default(),
+    })
+}
+
+/// `href` of the Atom link with a given `rel` (links without one are
+/// "alternate")
+fn atom_link(element: &Element, rel: &str) -> Option<String> {
+    element
+        .children_named("link")
+        .find(|link| link.attribute("rel").unwrap_or("alternate") == rel)
+        .and_then(|link| link.attribute("href"))
+        .map(String::from)
+}
+
+/// Podlove Simple Chapters listed inline in an item
+fn psc_chapters(item: &Element) -> Vec<Chapter> {
+    let Some(list) = item.child("psc:chapters") else {
+        return Vec::new();
+    };
+    let chapters = list
+        .children_named("psc:chapter")
+        .filter_map(|chapter| {
+            Some(Chapter {
+                start_secs: parse_clock(chapter.attribute("start")?)?,
+                end_secs: None,
+                title: chapter.attribute("title").unwrap_or_default().to_string(),
+                url: chapter.attribute("href").map(String::from),
+                image: chapter.attribute("image").map(String::from),
+            })
+        })
+        .collect();
+    chapters::sorted(chapters)
+}
+
+/// Resolve a possibly relative URL against the feed's URL
+fn resolve_url(base: &str, url: &str) -> String {
+    let url = url.trim();
+    if url.contains("://") {
+        return url.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return url.to_string();
+    };
+    if let Some(url) = url.strip_prefix("//") {
+        return format!("{}://{}", scheme, url);
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    if url.starts_with('/') {
+        return format!("{}://{}{}", scheme, authority, url);
+    }
+    let path = rest.split(['?', '#']).next().unwrap_or(rest);
+    let directory = match path.rfind('/') {
+        Some(slash) if slash >= authority.len() => &path[..=slash],
+        _ => return format!("{}://{}/{}", scheme, authority, url),
+    };
+    format!("{}://{}{}", scheme, directory, url)
+}
+
+/// Turn an HTML description into plain text: tags are dropped, block
+/// elements become line breaks and entities are decoded
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let tag = rest[start + 1..start + length].trim_start_matches('/');
+        let name: String = tag
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if matches!(
+            name.as_str(),
+            "br" | "p" | "div" | "li" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[start + length + 1..];
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        // Collapse runs of empty lines into one
+        if !line.is_empty() || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(line);
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .and_then(|end| Some((decode_entity(&rest[1..end + 1])?, end + 2)));
+        match entity {
+            Some((c, length)) => {
+                decoded.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    if let Some(decimal) = name.strip_prefix('#') {
+        return decimal.parse().ok().and_then(char::from_u32);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "rsquo" => '’',
+        "lsquo" => '‘',
+        "rdquo" => '”',
+        "ldquo" => '“',
+        _ => return None,
+    })
+}
+
+/// Parse `3600`, `62:03`, `1:02:03` or `00:01:02.500` into seconds
+pub fn parse_clock(text: &str) -> Option<f32> {
+    text.trim().split(':').try_fold(0.0f32, |total, part| {
+        part.trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .map(|value| total * 60.0 + value)
+    })
+}
+
+/// Parse an RFC 2822 (RSS) or RFC 3339 (Atom) date into seconds since the
+/// Unix epoch
+pub fn parse_date(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if text.as_bytes().first().is_some_and(u8::is_ascii_digit) && text.contains('-') {
+        parse_rfc3339(text)
+    } else {
+        parse_rfc2822(text)
+    }
+}
+
+/// `Tue, 02 Jan 2024 10:00:00 GMT`, with any time zone form
+fn parse_rfc2822(text: &str) -> Option<i64> {
+    // The weekday is optional and carries no information
+    let text = text.split_once(',').map_or(text, |(_, rest)| rest);
+    let mut parts = text.split_whitespace();
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = month_number(parts.next()?)?;
+    let mut year: i64 = parts.next()?.parse().ok()?;
+    if year < 100 {
+        year += if year < 50 { 2000 } else { 1900 };
+    }
+    let mut clock = parts.next().unwrap_or("00:00").split(':');
+    let hour = clock.next()?.parse().ok()?;
+    let minute = clock.next()?.parse().ok()?;
+    let second = clock.next().map_or(Some(0), |s| s.parse().ok())?;
+    let offset = parts.next().map_or(0, zone_offset);
+    Some(unix_time(year, month, day, hour, minute, second)? - offset)
+}
+
+/// `2024-01-02T10:00:00Z`, `2024-01-02T11:00:00.5+01:00` or `2024-01-02`
+fn parse_rfc3339(text: &str) -> Option<i64> {
+    let (date, time) = text
+        .split_once(['T', 't', ' '])
+        .unwrap_or((text, "00:00:00Z"));
+    let mut date = date.split('-');
+    let year = date.next()?.parse().ok()?;
+    let month = date.next()?.parse().ok()?;
+    let day = date.next()?.parse().ok()?;
+
+    let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else if let Some(sign) = time.rfind(['+', '-']) {
+        (&time[..sign], zone_offset(&time[sign..]))
+    } else {
+        (time, 0)
+    };
+    let mut clock = clock.split(':');
+    let hour = clock.next()?.parse().ok()?;
+    let minute = clock.next()?.parse().ok()?;
+    let second = clock.next().map_or(Some(0), |s| {
+        s.split('.').next().and_then(|s| s.parse().ok())
+    })?;
+    Some(unix_time(year, month, day, hour, minute, second)? - offset)
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let prefix = name.get(..3)?.to_ascii_lowercase();
+    MONTHS
+        .iter()
+        .position(|month| *month == prefix)
+        .map(|index| index as u32 + 1)
+}
+
+/// Offset of a time zone from UTC in seconds; unknown zones count as UTC
+fn zone_offset(zone: &str) -> i64 {
+    let hours = match zone.to_ascii_uppercase().as_str() {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => {
+            let sign = match zone.chars().next() {
+                Some('-') => -1,
+                Some('+') => 1,
+                _ => return 0,
+            };
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            let (hours, minutes) = match digits.len() {
+                4 => (&digits[..2], &digits[2..]),
+                2 => (&digits[..], "0"),
+                _ => return 0,
+            };
+            let hours: i64 = hours.parse().unwrap_or(0);
+            let minutes: i64 = minutes.parse().unwrap_or(0);
+            return sign * (hours * 3600 + minutes * 60);
+        }
+    };
+    hours * 3600
+}
+
+fn unix_time(year: i64, month: u32, day: u32, hour: i64, minute: i64, second: i64) -> Option<i64> {
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of a number of days since 1970-01-01
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0"
+     xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"
+     xmlns:content="http://purl.org/rss/1.0/modules/content/"
+     xmlns:podcast="https://podcastindex.org/namespace/1.0"
+     xmlns:psc="http://podlove.org/simple-chapters">
+  <channel>
+    <title>Tales &amp; Tunes</title>
+    <link>https://example.com/</link>
+    <description><![CDATA[<p>Music <b>stories</b>.</p><p>Weekly.</p>]]></description>
+    <itunes:author>Jo Doe</itunes:author>
+    <itunes:image href="/cover.jpg"/>
+    <item>
+      <title>Episode 2: Chapters</title>
+      <description>&lt;p&gt;Show notes&amp;nbsp;here&lt;/p&gt;</description>
+      <pubDate>Tue, 02 Jan 2024 11:00:00 +0100</pubDate>
+      <guid isPermaLink="false">tt-2</guid>
+      <enclosure url="media/ep2.mp3" length="1234" type="audio/mpeg"/>
+      <itunes:duration>1:02:03</itunes:duration>
+      <itunes:season>1</itunes:season>
+      <itunes:episode>2</itunes:episode>
+      <podcast:chapters url="https://example.com/ep2.json" type="application/json+chapters"/>
+      <psc:chapters version="1.2">
+        <psc:chapter start="00:10:00.500" title="Second"/>
+        <psc:chapter start="0" title="First" href="https://example.com/a"/>
+      </psc:chapters>
+    </item>
+    <item>
+      <title>No guid</title>
+      <enclosure url="https://cdn.example.com/ep1.mp3" length="0" type="audio/mpeg"/>
+      <itunes:duration>3600</itunes:duration>
+    </item>
+    <item>
+      <description>Nothing to identify this item</description>
+    </item>
+  </channel>
+</rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom Cast</title>
+  <subtitle>Short &lt;i&gt;talks&lt;/i&gt;</subtitle>
+  <link href="https://atom.example.com/"/>
+  <author><name>Sam</name></author>
+  <entry>
+    <id>urn:uuid:1</id>
+    <title>Talk one</title>
+    <published>2024-01-02T10:00:00Z</published>
+    <link rel="alternate" href="https://atom.example.com/1"/>
+    <link rel="enclosure" href="https://atom.example.com/1.ogg" length="99" type="audio/ogg"/>
+    <summary>A talk</summary>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_parse_rss() {
+        let podcast = parse_feed("https://example.com/shows/feed.xml", RSS).unwrap();
+        assert_eq!(podcast.title, "Tales & Tunes");
+        assert_eq!(podcast.author.as_deref(), Some("Jo Doe"));
+        assert_eq!(
+            podcast.description.as_deref(),
+            Some("Music stories.\n\nWeekly.")
+        );
+        assert_eq!(
+            podcast.image.as_deref(),
+            Some("https://example.com/cover.jpg")
+        );
+        assert_eq!(podcast.episodes.len(), 2);
+
+        let episode = &podcast.episodes[0];
+        assert_eq!(episode.guid, "tt-2");
+        assert_eq!(episode.title, "Episode 2: Chapters");
+        assert_eq!(episode.description.as_deref(), Some("Show notes here"));
+        assert_eq!(episode.published, Some(1_704_189_600));
+        let enclosure = episode.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://example.com/shows/media/ep2.mp3");
+        assert_eq!(enclosure.length, Some(1234));
+        assert_eq!(enclosure.mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(episode.duration_secs, Some(3723.0));
+        assert_eq!((episode.season, episode.number), (Some(1), Some(2)));
+        assert_eq!(
+            episode.chapters_url.as_deref(),
+            Some("https://example.com/ep2.json")
+        );
+        let chapters: Vec<(&str, f32, Option<f32>)> = episode
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.start_secs, c.end_secs))
+            .collect();
+        assert_eq!(
+            chapters,
+            [("First", 0.0, Some(600.5)), ("Second", 600.5, None)]
+        );
+        assert_eq!(
+            episode.chapters[0].url.as_deref(),
+            Some("https://example.com/a")
+        );
+
+        // Items without a guid are identified by their enclosure
+        let episode = &podcast.episodes[1];
+        assert_eq!(episode.guid, "https://cdn.example.com/ep1.mp3");
+        assert_eq!(episode.enclosure.as_ref().unwrap().length, None);
+        assert_eq!(episode.duration_secs, Some(3600.0));
+        assert_eq!(episode.published, None);
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let podcast = parse_feed("https://atom.example.com/feed", ATOM).unwrap();
+        assert_eq!(podcast.title, "Atom Cast");
+        assert_eq!(podcast.author.as_deref(), Some("Sam"));
+        assert_eq!(podcast.description.as_deref(), Some("Short talks"));
+        assert_eq!(podcast.link.as_deref(), Some("https://atom.example.com/"));
+
+        let episode = &podcast.episodes[0];
+        assert_eq!(episode.guid, "urn:uuid:1");
+        assert_eq!(episode.published, Some(1_704_189_600));
+        assert_eq!(episode.link.as_deref(), Some("https://atom.example.com/1"));
+        let enclosure = episode.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://atom.example.com/1.ogg");
+        assert_eq!(enclosure.mime_type.as_deref(), Some("audio/ogg"));
+    }
+
+    #[test]
+    fn test_invalid_feeds() {
+        assert!(parse_feed("http://x", "<html><body/></html>").is_err());
+        assert!(parse_feed("http://x", "<rss><channel>").is_err());
+        assert!(parse_feed("http://x", "").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let expected = Some(1_704_189_600);
+        assert_eq!(parse_date("Tue, 02 Jan 2024 10:00:00 GMT"), expected);
+        assert_eq!(parse_date("2 Jan 2024 05:00:00 EST"), expected);
+        assert_eq!(parse_date("Tue, 02 January 24 10:00 +0000"), expected);
+        assert_eq!(parse_date("2024-01-02T10:00:00Z"), expected);
+        assert_eq!(parse_date("2024-01-02T11:00:00.250+01:00"), expected);
+        assert_eq!(parse_date("2024-01-02"), Some(1_704_153_600));
+        assert_eq!(parse_date("yesterday"), None);
+        assert_eq!(parse_date("Tue, 32 Jan 2024 10:00:00 GMT"), None);
+        assert_eq!(civil_from_days(19_724), (2024, 1, 2));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            plain_text(
+                "<p>One&nbsp;&amp; <a href=\"x\">two</a></p>\n\n<p>Three &#8211; &#x41;&bogus;</p>"
+            ),
+            "One & two\n\nThree – A&bogus;"
+        );
+        assert_eq!(plain_text("a < b"), "a < b");
+    }
+
+    #[test]
+    fn test_parse_clock() {
+        assert_eq!(parse_clock("90"), Some(90.0));
+        assert_eq!(parse_clock("1:30"), Some(90.0));
+        assert_eq!(parse_clock("01:00:01.5"), Some(3601.5));
+        assert_eq!(parse_clock("soon"), None);
+    }
+}
@@ -0,0 +1,751 @@
+// Podcasts
+// Subscriptions to RSS/Atom podcast feeds, background feed refreshes and
+// episode downloads, and the playback position and played state of every
+// episode. Episodes play from their download if there is one, otherwise
+// straight from the enclosure URL as an HTTP stream.
+//
+// `PodcastLibrary` is the saved state; `PodcastService` does the network
+// work on background threads and reports back through `poll`.
+
+pub mod chapters;
+pub mod download;
+pub mod feed;
+
+use crate::file_name_safe;
+use crate::stream;
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ureq::Agent;
+
+/// Feeds and chapter files larger than this are refused
+const MAX_FEED_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How long to wait for a server to answer
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Positions closer than this to the start aren't worth resuming from
+const MIN_RESUME_SECS: f32 = 5.0;
+
+/// A subscribed podcast and its episodes, newest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Podcast {
+    pub feed_url: String,
+    pub title: String,
+    pub author: Option<String>,
+    /// Plain text, HTML removed
+    pub description: Option<String>,
+    pub link: Option<String>,
+    pub image: Option<String>,
+    pub episodes: Vec<Episode>,
+    /// When the feed was last fetched, in seconds since the Unix epoch
+    #[serde(default)]
+    pub updated: Option<i64>,
+}
+
+impl Podcast {
+    pub fn episode(&self, guid: &str) -> Option<&Episode> {
+        self.episodes.iter().find(|episode| episode.guid == guid)
+    }
+
+    pub fn unplayed_count(&self) -> usize {
+        self.episodes.iter().filter(|e| !e.state.played).count()
+    }
+}
+
+/// One episode of a podcast
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Episode {
+    /// The feed's `guid`/`id`, or the enclosure URL if it has none
+    pub guid: String,
+    pub title: String,
+    /// Plain text, HTML removed
+    pub description: Option<String>,
+    /// Publication date, in seconds since the Unix epoch
+    pub published: Option<i64>,
+    pub enclosure: Option<Enclosure>,
+    pub duration_secs: Option<f32>,
+    pub image: Option<String>,
+    pub link: Option<String>,
+    pub season: Option<u32>,
+    pub number: Option<u32>,
+    pub chapters: Vec<Chapter>,
+    /// Podcasting 2.0 JSON chapters, fetched on demand
+    pub chapters_url: Option<String>,
+    #[serde(default)]
+    pub state: EpisodeState,
+}
+
+impl Episode {
+    /// What to hand to the player: the downloaded file if it is still
+    /// there, otherwise the enclosure URL
+    pub fn playable_path(&self) -> Option<PathBuf> {
+        match &self.state.download {
+            Some(path) if path.exists() => Some(path.clone()),
+            _ => self.enclosure.as_ref().map(|e| PathBuf::from(&e.url)),
+        }
+    }
+
+    /// Whether `path` is this episode's download or enclosure URL
+    pub fn is_path(&self, path: &Path) -> bool {
+        self.state.download.as_deref() == Some(path)
+            || self
+                .enclosure
+                .as_ref()
+                .is_some_and(|e| Path::new(&e.url) == path)
+    }
+
+    /// Where to continue listening, unless the episode is finished or
+    /// barely started
+    pub fn resume_position(&self) -> Option<f32> {
+        Some(self.state.position_secs).filter(|&secs| !self.state.played && secs >= MIN_RESUME_SECS)
+    }
+
+    pub fn is_downloaded(&self) -> bool {
+        self.state
+            .download
+            .as_ref()
+            .is_some_and(|path| path.exists())
+    }
+}
+
+/// The media file attached to an episode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Enclosure {
+    pub url: String,
+    /// Size in bytes, as announced by the feed
+    pub length: Option<u64>,
+    pub mime_type: Option<String>,
+}
+
+/// A chapter mark inside an episode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_secs: f32,
+    pub end_secs: Option<f32>,
+    pub title: String,
+    pub url: Option<String>,
+    pub image: Option<String>,
+}
+
+/// Listening progress of an episode, kept across feed refreshes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeState {
+    /// Where playback stopped, in seconds
+    pub position_secs: f32,
+    pub played: bool,
+    /// The downloaded file, once complete
+    pub download: Option<PathBuf>,
+}
+
+/// Subscribed podcasts with the state of their episodes, saved as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PodcastLibrary {
+    pub podcasts: Vec<Podcast>,
+}
+
+impl PodcastLibrary {
+    /// Load a saved library; a missing file is an empty library
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid podcast library {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        // Write a temporary file first so a crash can't leave half a library
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path).with_context(|| format!("Failed to save {}", path.display()))
+    }
+
+    pub fn podcast(&self, feed_url: &str) -> Option<&Podcast> {
+        self.podcasts.iter().find(|p| p.feed_url == feed_url)
+    }
+
+    /// Subscribe to a freshly fetched podcast, or update a subscribed one.
+    /// Episodes keep their state and fetched chapters, and downloaded
+    /// episodes the feed no longer lists are kept. Returns the podcast's
+    /// index.
+    pub fn update(&mut self, mut podcast: Podcast) -> usize {
+        let Some(index) = self
+            .podcasts
+            .iter()
+            .position(|p| p.feed_url == podcast.feed_url)
+        else {
+            self.podcasts.push(podcast);
+            return self.podcasts.len() - 1;
+        };
+
+        let mut old: HashMap<String, Episode> = std::mem::take(&mut self.podcasts[index].episodes)
+            .into_iter()
+            .map(|episode| (episode.guid.clone(), episode))
+            .collect();
+        for episode in &mut podcast.episodes {
+            if let Some(previous) = old.remove(&episode.guid) {
+                episode.state = previous.state;
+                if episode.chapters.is_empty() {
+                    episode.chapters = previous.chapters;
+                }
+            }
+        }
+        podcast
+            .episodes
+            .extend(old.into_values().filter(Episode::is_downloaded));
+        sort_episodes(&mut podcast.episodes);
+        self.podcasts[index] = podcast;
+        index
+    }
+
+    pub fn unsubscribe(&mut self, feed_url: &str) -> Option<Podcast> {
+        let index = self.podcasts.iter().position(|p| p.feed_url == feed_url)?;
+        Some(self.podcasts.remove(index))
+    }
+
+    pub fn episode_mut(&mut self, feed_url: &str, guid: &str) -> Option<&mut Episode> {
+        self.podcasts
+            .iter_mut()
+            .find(|p| p.feed_url == feed_url)?
+            .episodes
+            .iter_mut()
+            .find(|e| e.guid == guid)
+    }
+
+    /// The episode being played from `path` (download or enclosure URL)
+    pub fn episode_for_path_mut(&mut self, path: &Path) -> Option<&mut Episode> {
+        self.podcasts
+            .iter_mut()
+            .flat_map(|p| p.episodes.iter_mut())
+            .find(|e| e.is_path(path))
+    }
+
+    /// Store the result of a [`PodcastService`] job. Returns `true` if the
+    /// library changed.
+    pub fn apply(&mut self, event: &PodcastEvent) -> bool {
+        match event {
+            PodcastEvent::Fetched {
+                result: Ok(podcast),
+                ..
+            } => {
+                self.update(podcast.clone());
+                true
+            }
+            PodcastEvent::Chapters {
+                feed_url,
+                guid,
+                result: Ok(chapters),
+            } => match self.episode_mut(feed_url, guid) {
+                Some(episode) => {
+                    episode.chapters = chapters.clone();
+                    true
+                }
+                None => false,
+            },
+            PodcastEvent::Downloaded {
+                feed_url,
+                guid,
+                path,
+                chapters,
+            } => match self.episode_mut(feed_url, guid) {
+                Some(episode) => {
+                    episode.state.download = Some(path.clone());
+                    if episode.chapters.is_empty() {
+                        episode.chapters = chapters.clone();
+                    }
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Newest first; undated episodes keep their feed order at the end
+fn sort_episodes(episodes: &mut [Episode]) {
+    episodes.sort_by_key(|e| std::cmp::Reverse(e.published.unwrap_or(i64::MIN)));
+}
+
+/// Result of a background job of the [`PodcastService`]
+#[derive(Debug, Clone)]
+pub enum PodcastEvent {
+    Fetched {
+        feed_url: String,
+        result: Result<Podcast, String>,
+    },
+    Chapters {
+        feed_url: String,
+        guid: String,
+        result: Result<Vec<Chapter>, String>,
+    },
+    DownloadProgress {
+        feed_url: String,
+        guid: String,
+        received: u64,
+        total: Option<u64>,
+    },
+    /// The episode was downloaded; `chapters` are the ID3 chapters found in
+    /// the file, if any
+    Downloaded {
+        feed_url: String,
+        guid: String,
+        path: PathBuf,
+        chapters: Vec<Chapter>,
+    },
+    /// The download failed; the partial file is kept so the next attempt
+    /// resumes it
+    DownloadFailed {
+        feed_url: String,
+        guid: String,
+        error: String,
+    },
+    /// The download was cancelled; starting it again resumes it
+    DownloadCancelled { feed_url: String, guid: String },
+}
+
+/// Bytes received and expected for a running download
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DownloadProgress {
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+impl DownloadProgress {
+    /// Share downloaded so far, if the size is known
+    pub fn fraction(&self) -> Option<f32> {
+        self.total
+            .filter(|&total| total > 0)
+            .map(|total| (self.received as f64 / total as f64).min(1.0) as f32)
+    }
+}
+
+struct RunningDownload {
+    cancel: Arc<AtomicBool>,
+    progress: DownloadProgress,
+}
+
+/// Fetches feeds and chapters and downloads episodes in the background.
+///
+/// Call [`PodcastService::poll`] regularly (e.g. once per frame) to collect
+/// results and pass them to [`PodcastLibrary::apply`].
+pub struct PodcastService {
+    agent: Agent,
+    download_dir: PathBuf,
+    events: Sender<PodcastEvent>,
+    results: Receiver<PodcastEvent>,
+    downloads: HashMap<(String, String), RunningDownload>,
+}
+
+impl PodcastService {
+    /// Create a service downloading episodes into one folder per podcast
+    /// below `download_dir`
+    pub fn new(download_dir: PathBuf) -> Self {
+        let (events, results) = crossbeam_channel::unbounded();
+        Self {
+            agent: stream::http_agent(TIMEOUT),
+            download_dir,
+            events,
+            results,
+            downloads: HashMap::new(),
+        }
+    }
+
+    /// Fetch and parse a feed, to subscribe to it or refresh it
+    pub fn fetch(&self, feed_url: &str) {
+        let agent = self.agent.clone();
+        let events = self.events.clone();
+        let feed_url = feed_url.trim().to_string();
+        spawn(move || {
+            let result = fetch_feed(&agent, &feed_url).map_err(|e| format!("{:#}", e));
+            let _ = events.send(PodcastEvent::Fetched { feed_url, result });
+        });
+    }
+
+    /// Fetch the Podcasting 2.0 chapters of an episode, if it has any
+    pub fn fetch_chapters(&self, podcast: &Podcast, episode: &Episode) {
+        let Some(url) = episode.chapters_url.clone() else {
+            return;
+        };
+        let agent = self.agent.clone();
+        let events = self.events.clone();
+        let feed_url = podcast.feed_url.clone();
+        let guid = episode.guid.clone();
+        spawn(move || {
+            let result = fetch_text(&agent, &url)
+                .and_then(|json| chapters::parse_json_chapters(&json))
+                .map_err(|e| format!("{:#}", e));
+            let _ = events.send(PodcastEvent::Chapters {
+                feed_url,
+                guid,
+                result,
+            });
+        });
+    }
+
+    /// Where an episode is downloaded to
+    pub fn download_path(&self, podcast: &Podcast, episode: &Episode) -> PathBuf {
+        let mut name = String::new();
+        if let Some(published) = episode.published {
+            name.push_str(&format_date(published));
+            name.push(' ');
+        }
+        name.push_str(&episode.title);
+        let extension = episode
+            .enclosure
+            .as_ref()
+            .map(download::file_extension)
+            .unwrap_or("mp3");
+        self.download_dir
+            .join(file_name_safe(&podcast.title))
+            .join(format!("{}.{}", file_name_safe(&name), extension))
+    }
+
+    /// Start downloading an episode, resuming an earlier partial download.
+    /// Does nothing if it is already downloading or has no enclosure.
+    pub fn download(&mut self, podcast: &Podcast, episode: &Episode) {
+        let key = (podcast.feed_url.clone(), episode.guid.clone());
+        let Some(enclosure) = &episode.enclosure else {
+            return;
+        };
+        if self.downloads.contains_key(&key) {
+            return;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.downloads.insert(
+            key.clone(),
+            RunningDownload {
+                cancel: cancel.clone(),
+                progress: DownloadProgress {
+                    received: 0,
+                    total: enclosure.length,
+                },
+            },
+        );
+
+        let agent = self.agent.clone();
+        let events = self.events.clone();
+        let url = enclosure.url.clone();
+        let path = self.download_path(podcast, episode);
+        let (feed_url, guid) = key;
+        spawn(move || {
+            let progress = |received, total| {
+                let _ = events.send(PodcastEvent::DownloadProgress {
+                    feed_url: feed_url.clone(),
+                    guid: guid.clone(),
+                    received,
+                    total,
+                });
+                !cancel.load(Ordering::Relaxed)
+            };
+            let event = match download::download(&agent, &url, &path, progress) {
+                Ok(()) => PodcastEvent::Downloaded {
+                    chapters: chapters::read_id3_chapters(&path).unwrap_or_default(),
+                    feed_url,
+                    guid,
+                    path,
+                },
+                Err(_) if cancel.load(Ordering::Relaxed) => {
+                    PodcastEvent::DownloadCancelled { feed_url, guid }
+                }
+                Err(e) => PodcastEvent::DownloadFailed {
+                    feed_url,
+                    guid,
+                    error: format!("{:#}", e),
+                },
+            };
+            let _ = events.send(event);
+        });
+    }
+
+    /// Stop a download; starting it again later resumes where it stopped
+    pub fn cancel_download(&mut self, feed_url: &str, guid: &str) {
+        if let Some(download) = self
+            .downloads
+            .get(&(feed_url.to_string(), guid.to_string()))
+        {
+            download.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Progress of a running download
+    pub fn download_progress(&self, feed_url: &str, guid: &str) -> Option<DownloadProgress> {
+        self.downloads
+            .get(&(feed_url.to_string(), guid.to_string()))
+            .map(|download| download.progress)
+    }
+
+    /// Collect finished jobs and download progress
+    pub fn poll(&mut self) -> Vec<PodcastEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.results.try_recv() {
+            match &event {
+                PodcastEvent::DownloadProgress {
+                    feed_url,
+                    guid,
+                    received,
+                    total,
+                } => {
+                    let key = (feed_url.clone(), guid.clone());
+                    if let Some(download) = self.downloads.get_mut(&key) {
+                        download.progress = DownloadProgress {
+                            received: *received,
+                            total: *total,
+                        };
+                    }
+                    // Progress is read through `download_progress`
+                    continue;
+                }
+                PodcastEvent::Downloaded { feed_url, guid, .. }
+                | PodcastEvent::DownloadFailed { feed_url, guid, .. }
+                | PodcastEvent::DownloadCancelled { feed_url, guid } => {
+                    self.downloads.remove(&(feed_url.clone(), guid.clone()));
+                }
+                _ => {}
+            }
+            events.push(event);
+        }
+        events
+    }
+}
+
+impl Drop for PodcastService {
+    fn drop(&mut self) {
+        for download in self.downloads.values() {
+            download.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn spawn(job: impl FnOnce() + Send + 'static) {
+    if let Err(e) = thread::Builder::new()
+        .name("oneamp-podcast".to_string())
+        .spawn(job)
+    {
+        eprintln!("Failed to start podcast job: {}", e);
+    }
+}
+
+fn fetch_text(agent: &Agent, url: &str) -> Result<String> {
+    let bytes = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .into_body()
+        .into_with_config()
+        .limit(MAX_FEED_BYTES)
+        .read_to_vec()
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn fetch_feed(agent: &Agent, feed_url: &str) -> Result<Podcast> {
+    let xml = fetch_text(agent, feed_url)?;
+    let mut podcast = feed::parse_feed(feed_url, &xml)?;
+    podcast.updated = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|now| now.as_secs() as i64);
+    sort_episodes(&mut podcast.episodes);
+    Ok(podcast)
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD` (UTC)
+pub fn format_date(secs: i64) -> String {
+    let (year, month, day) = feed::civil_from_days(secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{response, serve};
+    use std::time::Instant;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"
+     xmlns:podcast="https://podcastindex.org/namespace/1.0">
+  <channel>
+    <title>Local Show</title>
+    <itunes:author>OneAmp</itunes:author>
+    <item>
+      <title>Second</title>
+      <guid>ep-2</guid>
+      <pubDate>Tue, 02 Jan 2024 10:00:00 GMT</pubDate>
+      <enclosure url="{base}/ep2.mp3" length="10" type="audio/mpeg"/>
+      <podcast:chapters url="{base}/ep2.json" type="application/json+chapters"/>
+    </item>
+    <item>
+      <title>First</title>
+      <guid>ep-1</guid>
+      <pubDate>Mon, 01 Jan 2024 10:00:00 GMT</pubDate>
+      <enclosure url="{base}/ep1.mp3" length="6" type="audio/mpeg"/>
+    </item>
+  </channel>
+</rss>"#;
+
+    const CHAPTERS: &str = r#"{"version": "1.2.0", "chapters": [
+        {"startTime": 0, "title": "Intro"},
+        {"startTime": 61.5, "title": "News", "url": "https://example.com"}
+    ]}"#;
+
+    fn wait_for(
+        service: &mut PodcastService,
+        mut done: impl FnMut(&PodcastEvent) -> bool,
+    ) -> PodcastEvent {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            assert!(Instant::now() < deadline, "podcast job timed out");
+            if let Some(event) = service.poll().into_iter().find(|event| done(event)) {
+                return event;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_subscribe_download_and_resume() {
+        let dir = std::env::temp_dir().join("oneamp_podcast_test_subscribe");
+        std::fs::remove_dir_all(&dir).ok();
+        let (base, server) = serve(|base| {
+            let feed = FEED.replace("{base}", base);
+            vec![
+                response(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/rss+xml",
+                    feed.as_bytes(),
+                ),
+                response(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json",
+                    CHAPTERS.as_bytes(),
+                ),
+                response("HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg", b"0123456789"),
+            ]
+        });
+        let feed_url = format!("{}/feed.xml", base);
+        let mut service = PodcastService::new(dir.join("downloads"));
+        let mut library = PodcastLibrary::default();
+
+        service.fetch(&feed_url);
+        let event = wait_for(&mut service, |e| matches!(e, PodcastEvent::Fetched { .. }));
+        assert!(library.apply(&event));
+        let podcast = library.podcast(&feed_url).unwrap().clone();
+        assert_eq!(podcast.title, "Local Show");
+        let titles: Vec<&str> = podcast.episodes.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Second", "First"]);
+
+        let episode = podcast.episode("ep-2").unwrap();
+        service.fetch_chapters(&podcast, episode);
+        let event = wait_for(&mut service, |e| matches!(e, PodcastEvent::Chapters { .. }));
+        assert!(library.apply(&event));
+
+        service.download(&podcast, episode);
+        let event = wait_for(&mut service, |e| {
+            matches!(
+                e,
+                PodcastEvent::Downloaded { .. } | PodcastEvent::DownloadFailed { .. }
+            )
+        });
+        assert!(library.apply(&event), "{:?}", event);
+        assert!(service.download_progress(&feed_url, "ep-2").is_none());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /feed.xml "));
+        assert!(requests[2].starts_with("GET /ep2.mp3 "));
+
+        let episode = library.episode_mut(&feed_url, "ep-2").unwrap();
+        let path = episode.state.download.clone().unwrap();
+        assert_eq!(path, dir.join("downloads/Local Show/2024-01-02 Second.mp3"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+        assert_eq!(episode.playable_path(), Some(path.clone()));
+        assert_eq!(episode.chapters.len(), 2);
+        assert_eq!(episode.chapters[0].end_secs, Some(61.5));
+
+        // Playback position, found by the path the player reports
+        let episode = library.episode_for_path_mut(&path).unwrap();
+        episode.state.position_secs = 42.0;
+        let enclosure = PathBuf::from(format!("{}/ep1.mp3", base));
+        assert_eq!(
+            library.episode_for_path_mut(&enclosure).unwrap().guid,
+            "ep-1"
+        );
+
+        // A refresh keeps the state of known episodes
+        let mut refreshed = feed::parse_feed(&feed_url, &FEED.replace("{base}", &base)).unwrap();
+        refreshed.episodes.retain(|e| e.guid == "ep-2");
+        library.update(refreshed);
+        let episode = library.podcast(&feed_url).unwrap().episode("ep-2").unwrap();
+        assert_eq!(episode.resume_position(), Some(42.0));
+        assert_eq!(episode.chapters.len(), 2);
+
+        // Saved and loaded
+        let saved = dir.join("podcasts.json");
+        library.save(&saved).unwrap();
+        assert_eq!(PodcastLibrary::load(&saved).unwrap(), library);
+        assert!(library.unsubscribe(&feed_url).is_some());
+        assert!(library.podcasts.is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_refresh_keeps_downloaded_episodes() {
+        let dir = std::env::temp_dir().join("oneamp_podcast_test_refresh");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("old.mp3");
+        std::fs::write(&file, b"audio").unwrap();
+
+        let mut library = PodcastLibrary::default();
+        let mut podcast = feed::parse_feed(
+            "http://example.com/feed",
+            &FEED.replace("{base}", "http://example.com"),
+        )
+        .unwrap();
+        library.update(podcast.clone());
+        library
+            .episode_mut(&podcast.feed_url, "ep-1")
+            .unwrap()
+            .state
+            .download = Some(file.clone());
+        library
+            .episode_mut(&podcast.feed_url, "ep-2")
+            .unwrap()
+            .state
+            .played = true;
+
+        // The feed now lists only a new episode
+        podcast.episodes.truncate(1);
+        podcast.episodes[0].guid = "ep-3".to_string();
+        podcast.episodes[0].published = Some(i64::MAX);
+        library.update(podcast);
+
+        let guids: Vec<&str> = library.podcasts[0]
+            .episodes
+            .iter()
+            .map(|e| e.guid.as_str())
+            .collect();
+        assert_eq!(guids, ["ep-3", "ep-1"]);
+        assert_eq!(library.podcasts[0].unplayed_count(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(1_704_189_600), "2024-01-02");
+    }
+}
@@ -124,6 +124,13 @@ impl RodioOutput {
         Ok(())
     }
 
+    /// Set the output volume (1.0 = unchanged)
+    pub fn set_volume(&self, volume: f32) {
+        if let Ok(sink) = self.sink.lock() {
+            sink.set_volume(volume);
+        }
+    }
+
     /// Clear the buffer
     pub fn clear(&self) {
         if let Ok(mut buffer) = self.sample_buffer.lock() {
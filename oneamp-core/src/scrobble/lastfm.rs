@@ -0,0 +1,305 @@
+// Last.fm
+// Scrobbling API 2.0: form-encoded POSTs signed with the MD5 of the sorted
+// parameters and the application secret. `track.scrobble` takes up to 50
+// indexed listens per request.
+
+use super::{http_failure, url_encode, HttpClient, Listen, ScrobbleBackend, SubmitError};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+pub(crate) fn default_api_url() -> String {
+    LastFm::API_URL.to_string()
+}
+
+pub struct LastFm {
+    api_url: String,
+    api_key: String,
+    secret: String,
+    session_key: String,
+}
+
+impl LastFm {
+    pub const API_URL: &'static str = "https://ws.audioscrobbler.com/2.0/";
+
+    pub fn new(api_url: &str, api_key: &str, secret: &str, session_key: &str) -> Self {
+        Self {
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            secret: secret.to_string(),
+            session_key: session_key.to_string(),
+        }
+    }
+
+    /// Trade a username and password for a session key
+    /// (`auth.getMobileSession`)
+    pub fn login(
+        http: &dyn HttpClient,
+        api_url: &str,
+        api_key: &str,
+        secret: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<String> {
+        let params = vec![
+            ("method", "auth.getMobileSession".to_string()),
+            ("username", username.to_string()),
+            ("password", password.to_string()),
+            ("api_key", api_key.to_string()),
+        ];
+        let response = call(http, api_url, secret, params).context("Last.fm login failed")?;
+        let body: Value = serde_json::from_str(&response.body).context("Invalid response")?;
+        if let Some(key) = body["session"]["key"].as_str() {
+            return Ok(key.to_string());
+        }
+        match body["message"].as_str() {
+            Some(message) => bail!("{}", message),
+            None => bail!("Last.fm login failed (HTTP {})", response.status),
+        }
+    }
+
+    fn post(
+        &self,
+        http: &dyn HttpClient,
+        mut params: Vec<(&str, String)>,
+    ) -> Result<Value, SubmitError> {
+        params.push(("api_key", self.api_key.clone()));
+        params.push(("sk", self.session_key.clone()));
+        let response = call(http, &self.api_url, &self.secret, params)
+            .map_err(|e| SubmitError::Retry(format!("{:#}", e)))?;
+        let body: Value = serde_json::from_str(&response.body).unwrap_or(Value::Null);
+        let message = || {
+            body["message"]
+                .as_str()
+                .map_or_else(|| format!("HTTP {}", response.status), String::from)
+        };
+        // Errors come as `{"error": code, "message": ...}`, sometimes with 200
+        match body["error"].as_u64() {
+            // Service offline or busy, operation failed, invalid session
+            // key or rate limited: keep the listens
+            Some(8 | 9 | 11 | 16 | 29) => Err(SubmitError::Retry(message())),
+            Some(_) => Err(SubmitError::Rejected(message())),
+            None if response.status == 200 => Ok(body),
+            None => Err(http_failure(response.status, message())),
+        }
+    }
+}
+
+/// Sign `params` and POST them with the JSON response format
+fn call(
+    http: &dyn HttpClient,
+    api_url: &str,
+    secret: &str,
+    mut params: Vec<(&str, String)>,
+) -> Result<super::HttpResponse> {
+    let signature = api_signature(&params, secret);
+    params.push(("api_sig", signature));
+    params.push(("format", "json".to_string()));
+    let body = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", url_encode(key), url_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    http.post(api_url, "application/x-www-form-urlencoded", &[], &body)
+}
+
+/// MD5 of the parameters sorted by name, each name followed by its value,
+/// then the secret
+fn api_signature(params: &[(&str, String)], secret: &str) -> String {
+    let mut sorted: Vec<_> = params.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    let mut text = String::new();
+    for (key, value) in sorted {
+        text.push_str(key);
+        text.push_str(value);
+    }
+    text.push_str(secret);
+    format!("{:x}", md5::compute(text.as_bytes()))
+}
+
+impl ScrobbleBackend for LastFm {
+    fn name(&self) -> &'static str {
+        "lastfm"
+    }
+
+    fn now_playing(&self, http: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError> {
+        let mut params = vec![
+            ("method", "track.updateNowPlaying".to_string()),
+            ("artist", listen.artist.clone()),
+            ("track", listen.title.clone()),
+        ];
+        if let Some(album) = &listen.album {
+            params.push(("album", album.clone()));
+        }
+        if let Some(duration) = listen.duration_secs {
+            params.push(("duration", duration.to_string()));
+        }
+        self.post(http, params).map(|_| ())
+    }
+
+    fn submit(&self, http: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError> {
+        // Parameter names must outlive the request
+        let names: Vec<[String; 5]> = (0..listens.len())
+            .map(|i| {
+                ["artist", "track", "timestamp", "album", "duration"]
+                    .map(|name| format!("{}[{}]", name, i))
+            })
+            .collect();
+        let mut params = vec![("method", "track.scrobble".to_string())];
+        for (listen, [artist, track, timestamp, album, duration]) in listens.iter().zip(&names) {
+            params.push((artist, listen.artist.clone()));
+            params.push((track, listen.title.clone()));
+            params.push((timestamp, listen.listened_at.to_string()));
+            if let Some(value) = &listen.album {
+                params.push((album, value.clone()));
+            }
+            if let Some(value) = listen.duration_secs {
+                params.push((duration, value.to_string()));
+            }
+        }
+        // Scrobbles Last.fm ignores (too old, filtered) are done with too
+        let body = self.post(http, params)?;
+        let ignored = &body["scrobbles"]["@attr"]["ignored"];
+        if ignored.as_u64().unwrap_or(0) > 0 || ignored.as_str().is_some_and(|n| n != "0") {
+            eprintln!("lastfm: {} scrobbles ignored", ignored);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrobble::UreqClient;
+    use crate::test_http::{response, serve};
+    use std::collections::HashMap;
+
+    fn listen(title: &str) -> Listen {
+        Listen {
+            artist: "Artist & Co".to_string(),
+            title: title.to_string(),
+            album: None,
+            duration_secs: Some(200),
+            listened_at: 1_700_000_000,
+        }
+    }
+
+    fn json(status: &str, body: &str) -> Vec<u8> {
+        response(
+            &format!("HTTP/1.1 {}\r\nContent-Length: {}", status, body.len()),
+            body.as_bytes(),
+        )
+    }
+
+    /// Decoded form fields of a request
+    fn form(request: &str) -> HashMap<String, String> {
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        body.split('&')
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap();
+                (decode(key), decode(value))
+            })
+            .collect()
+    }
+
+    fn decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' {
+                decoded.push(u8::from_str_radix(&value[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(decoded).unwrap()
+    }
+
+    #[test]
+    fn test_api_signature() {
+        let params = [
+            ("method", "auth.getMobileSession".to_string()),
+            ("api_key", "key".to_string()),
+        ];
+        assert_eq!(
+            api_signature(&params, "secret"),
+            format!(
+                "{:x}",
+                md5::compute("api_keykeymethodauth.getMobileSessionsecret")
+            )
+        );
+    }
+
+    #[test]
+    fn test_lastfm_protocol() {
+        let (base, server) = serve(|_| {
+            vec![
+                json("200 OK", r#"{"session": {"name": "user", "key": "sk123"}}"#),
+                json("200 OK", r#"{"nowplaying": {}}"#),
+                json(
+                    "200 OK",
+                    r#"{"scrobbles": {"@attr": {"accepted": 2, "ignored": 0}}}"#,
+                ),
+                json("200 OK", r#"{"error": 11, "message": "Service Offline"}"#),
+                json(
+                    "400 Bad Request",
+                    r#"{"error": 6, "message": "Invalid parameters"}"#,
+                ),
+                json(
+                    "403 Forbidden",
+                    r#"{"error": 4, "message": "Bad password"}"#,
+                ),
+            ]
+        });
+        let url = format!("{}/2.0/", base);
+        let http = UreqClient::default();
+
+        let key = LastFm::login(&http, &url, "key", "secret", "user", "pass").unwrap();
+        assert_eq!(key, "sk123");
+        let service = LastFm::new(&url, "key", "secret", &key);
+        service.now_playing(&http, &listen("One")).unwrap();
+        service
+            .submit(&http, &[listen("One"), listen("Two")])
+            .unwrap();
+        assert_eq!(
+            service.submit(&http, &[listen("One")]),
+            Err(SubmitError::Retry("Service Offline".to_string()))
+        );
+        assert!(matches!(
+            service.submit(&http, &[listen("One")]),
+            Err(SubmitError::Rejected(_))
+        ));
+        let error = LastFm::login(&http, &url, "key", "secret", "user", "wrong").unwrap_err();
+        assert!(format!("{:#}", error).contains("Bad password"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /2.0/ "));
+        for request in &requests {
+            // Every request is signed over all fields but `format`
+            let mut fields = form(request);
+            assert_eq!(fields.remove("format").as_deref(), Some("json"));
+            let signature = fields.remove("api_sig").unwrap();
+            let params: Vec<(&str, String)> = fields
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()))
+                .collect();
+            assert_eq!(signature, api_signature(&params, "secret"));
+        }
+
+        let login = form(&requests[0]);
+        assert_eq!(login["method"], "auth.getMobileSession");
+        assert_eq!(login["username"], "user");
+        let now_playing = form(&requests[1]);
+        assert_eq!(now_playing["method"], "track.updateNowPlaying");
+        assert_eq!(now_playing["artist"], "Artist & Co");
+        assert_eq!(now_playing["sk"], "sk123");
+        let scrobble = form(&requests[2]);
+        assert_eq!(scrobble["method"], "track.scrobble");
+        assert_eq!(scrobble["track[1]"], "Two");
+        assert_eq!(scrobble["timestamp[0]"], "1700000000");
+        assert_eq!(scrobble["duration[1]"], "200");
+        assert!(!scrobble.contains_key("album[0]"));
+    }
+}
@@ -0,0 +1,181 @@
+// ListenBrainz
+// Listens are posted as JSON to `/1/submit-listens` with the user's token:
+// `playing_now` for the current track, `single` or `import` for finished
+// listens.
+
+use super::{http_failure, HttpClient, Listen, ScrobbleBackend, SubmitError};
+use serde_json::{json, Value};
+
+pub(crate) fn default_api_url() -> String {
+    ListenBrainz::API_URL.to_string()
+}
+
+pub struct ListenBrainz {
+    api_url: String,
+    token: String,
+}
+
+impl ListenBrainz {
+    pub const API_URL: &'static str = "https://api.listenbrainz.org";
+
+    pub fn new(api_url: &str, token: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    fn post(&self, http: &dyn HttpClient, body: Value) -> Result<(), SubmitError> {
+        let url = format!("{}/1/submit-listens", self.api_url);
+        let response = http
+            .post(
+                &url,
+                "application/json",
+                &[("Authorization", format!("Token {}", self.token))],
+                &body.to_string(),
+            )
+            .map_err(|e| SubmitError::Retry(format!("{:#}", e)))?;
+        if response.status == 200 {
+            return Ok(());
+        }
+        let message = serde_json::from_str::<Value>(&response.body)
+            .ok()
+            .and_then(|body| body["error"].as_str().map(String::from))
+            .unwrap_or_else(|| format!("HTTP {}", response.status));
+        Err(http_failure(response.status, message))
+    }
+}
+
+fn track_metadata(listen: &Listen) -> Value {
+    let mut additional_info = json!({
+        "media_player": "OneAmp",
+        "submission_client": "OneAmp",
+        "submission_client_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some(duration) = listen.duration_secs {
+        additional_info["duration_ms"] = json!(u64::from(duration) * 1000);
+    }
+    let mut metadata = json!({
+        "artist_name": listen.artist,
+        "track_name": listen.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &listen.album {
+        metadata["release_name"] = json!(album);
+    }
+    metadata
+}
+
+impl ScrobbleBackend for ListenBrainz {
+    fn name(&self) -> &'static str {
+        "listenbrainz"
+    }
+
+    fn now_playing(&self, http: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError> {
+        self.post(
+            http,
+            json!({
+                "listen_type": "playing_now",
+                "payload": [{ "track_metadata": track_metadata(listen) }],
+            }),
+        )
+    }
+
+    fn submit(&self, http: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError> {
+        let payload: Vec<Value> = listens
+            .iter()
+            .map(|listen| {
+                json!({
+                    "listened_at": listen.listened_at,
+                    "track_metadata": track_metadata(listen),
+                })
+            })
+            .collect();
+        let listen_type = if payload.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+        self.post(
+            http,
+            json!({ "listen_type": listen_type, "payload": payload }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrobble::UreqClient;
+    use crate::test_http::{response, serve};
+
+    fn listen(title: &str) -> Listen {
+        Listen {
+            artist: "Artist".to_string(),
+            title: title.to_string(),
+            album: Some("Album".to_string()),
+            duration_secs: Some(200),
+            listened_at: 1_700_000_000,
+        }
+    }
+
+    fn body(request: &str) -> Value {
+        serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap()
+    }
+
+    #[test]
+    fn test_listenbrainz_protocol() {
+        let json = |status: &str, body: &str| {
+            response(
+                &format!("HTTP/1.1 {}\r\nContent-Length: {}", status, body.len()),
+                body.as_bytes(),
+            )
+        };
+        let (base, server) = serve(|_| {
+            vec![
+                json("200 OK", r#"{"status": "ok"}"#),
+                json("200 OK", r#"{"status": "ok"}"#),
+                json(
+                    "401 Unauthorized",
+                    r#"{"code": 401, "error": "Invalid token.."}"#,
+                ),
+                json("400 Bad Request", r#"{"code": 400, "error": "No listens"}"#),
+            ]
+        });
+        let http = UreqClient::default();
+        let service = ListenBrainz::new(&format!("{}/", base), "secret-token");
+
+        service.now_playing(&http, &listen("One")).unwrap();
+        service
+            .submit(&http, &[listen("One"), listen("Two")])
+            .unwrap();
+        // A bad token keeps the listens for later; invalid listens don't
+        assert_eq!(
+            service.submit(&http, &[listen("One")]),
+            Err(SubmitError::Retry("Invalid token..".to_string()))
+        );
+        assert!(matches!(
+            service.submit(&http, &[listen("One")]),
+            Err(SubmitError::Rejected(_))
+        ));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /1/submit-listens "));
+        assert!(requests[0]
+            .to_ascii_lowercase()
+            .contains("authorization: token secret-token"));
+        let now_playing = body(&requests[0]);
+        assert_eq!(now_playing["listen_type"], "playing_now");
+        assert!(now_playing["payload"][0].get("listened_at").is_none());
+        let metadata = &now_playing["payload"][0]["track_metadata"];
+        assert_eq!(metadata["artist_name"], "Artist");
+        assert_eq!(metadata["release_name"], "Album");
+        assert_eq!(metadata["additional_info"]["duration_ms"], 200_000);
+
+        let import = body(&requests[1]);
+        assert_eq!(import["listen_type"], "import");
+        assert_eq!(import["payload"][1]["track_metadata"]["track_name"], "Two");
+        assert_eq!(import["payload"][1]["listened_at"], 1_700_000_000);
+        assert_eq!(body(&requests[2])["listen_type"], "single");
+    }
+}
@@ -0,0 +1,819 @@
+// Scrobbling
+// Listens are reported to Last.fm and ListenBrainz following the usual
+// rules: a track longer than 30 seconds is scrobbled once it has been
+// played for half its duration or four minutes, whichever comes first, and
+// a "now playing" notice is sent when it starts. Submissions run on a
+// background thread; listens that can't be delivered wait in an on-disk
+// queue and are retried in batches with backoff.
+
+mod lastfm;
+mod listenbrainz;
+mod queue;
+
+pub use lastfm::LastFm;
+pub use listenbrainz::ListenBrainz;
+pub use queue::ScrobbleQueue;
+
+use crate::{AudioEvent, TrackInfo};
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Tracks this short or shorter are never scrobbled
+pub const MIN_TRACK_SECS: f32 = 30.0;
+
+/// Playing time after which any track is scrobbled
+pub const MAX_THRESHOLD_SECS: f32 = 240.0;
+
+/// Position jumps larger than this are seeks, not listening time
+const MAX_POSITION_STEP_SECS: f32 = 2.0;
+
+/// First retry delay after a failed submission, doubled on every failure
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A track listened to, as submitted to the scrobbling services
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration_secs: Option<u32>,
+    /// When playback started, in seconds since the Unix epoch
+    pub listened_at: u64,
+}
+
+impl Listen {
+    /// The listen of a track starting now; tracks without an artist and a
+    /// title can't be scrobbled
+    pub fn from_track(track: &TrackInfo) -> Option<Self> {
+        let field = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        Some(Self {
+            artist: field(&track.artist)?,
+            title: field(&track.title)?,
+            album: field(&track.album),
+            duration_secs: track
+                .duration_secs
+                .filter(|&secs| secs > 0.0)
+                .map(|secs| secs.round() as u32),
+            listened_at: unix_time(),
+        })
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Playing time after which a track of `duration_secs` is scrobbled, or
+/// `None` if it's too short. Tracks of unknown length, such as streams,
+/// need the full four minutes.
+pub fn scrobble_threshold(duration_secs: Option<f32>) -> Option<f32> {
+    match duration_secs {
+        Some(duration) if duration <= MIN_TRACK_SECS => None,
+        Some(duration) => Some((duration / 2.0).min(MAX_THRESHOLD_SECS)),
+        None => Some(MAX_THRESHOLD_SECS),
+    }
+}
+
+/// What the scrobbler should report after an event
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrobbleAction {
+    NowPlaying(Listen),
+    Scrobble(Listen),
+}
+
+struct CurrentListen {
+    listen: Listen,
+    /// Seconds actually played, seeks excluded
+    played_secs: f32,
+    last_position: Option<f32>,
+    /// `None` once scrobbled or for tracks too short to scrobble
+    threshold: Option<f32>,
+    announced: bool,
+}
+
+/// Applies the scrobbling rules to the player's events
+#[derive(Default)]
+pub struct ScrobbleTracker {
+    current: Option<CurrentListen>,
+    playing: bool,
+}
+
+impl ScrobbleTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a player event, returning what to report, if anything
+    pub fn handle_event(&mut self, event: &AudioEvent) -> Option<ScrobbleAction> {
+        match event {
+            AudioEvent::TrackLoaded(track) => {
+                // `Load` restores a session paused; `Playing` follows on resume
+                self.playing = false;
+                self.start(track);
+                None
+            }
+            AudioEvent::TrackUpdated(track) => {
+                // A new stream title is a new listen; other updates aren't
+                let changed = match (&self.current, Listen::from_track(track)) {
+                    (Some(current), Some(listen)) => {
+                        current.listen.artist != listen.artist
+                            || current.listen.title != listen.title
+                    }
+                    (None, Some(_)) => true,
+                    (_, None) => false,
+                };
+                if !changed {
+                    return None;
+                }
+                self.start(track);
+                self.announce()
+            }
+            AudioEvent::Playing => {
+                self.playing = true;
+                self.announce()
+            }
+            AudioEvent::Paused | AudioEvent::Stopped | AudioEvent::Error(_) => {
+                self.playing = false;
+                if let Some(current) = &mut self.current {
+                    current.last_position = None;
+                }
+                None
+            }
+            AudioEvent::Position(position, duration) => self.advance(*position, Some(*duration)),
+            AudioEvent::Finished => {
+                let end = self
+                    .current
+                    .as_ref()
+                    .and_then(|current| current.listen.duration_secs)?;
+                let action = self.advance(end as f32, None);
+                self.current = None;
+                action
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, track: &TrackInfo) {
+        self.current = Listen::from_track(track).map(|listen| CurrentListen {
+            threshold: scrobble_threshold(track.duration_secs.filter(|&secs| secs > 0.0)),
+            listen,
+            played_secs: 0.0,
+            last_position: None,
+            announced: false,
+        });
+    }
+
+    fn announce(&mut self) -> Option<ScrobbleAction> {
+        let current = self.current.as_mut().filter(|current| !current.announced)?;
+        if !self.playing {
+            return None;
+        }
+        // A track loaded paused, e.g. a restored session, starts when resumed
+        current.listen.listened_at = unix_time();
+        current.announced = true;
+        Some(ScrobbleAction::NowPlaying(current.listen.clone()))
+    }
+
+    fn advance(&mut self, position: f32, duration: Option<f32>) -> Option<ScrobbleAction> {
+        let current = self.current.as_mut()?;
+        // The length of some files is only known once they play
+        if let Some(duration) = duration.filter(|&secs| secs > 0.0) {
+            if current.listen.duration_secs.is_none() {
+                current.listen.duration_secs = Some(duration.round() as u32);
+                if current.threshold.is_some() {
+                    current.threshold = scrobble_threshold(Some(duration));
+                }
+            }
+        }
+        if self.playing {
+            if let Some(last) = current.last_position {
+                let step = position - last;
+                if step > 0.0 && step <= MAX_POSITION_STEP_SECS {
+                    current.played_secs += step;
+                }
+            }
+        }
+        current.last_position = Some(position);
+
+        let threshold = current.threshold?;
+        if current.played_secs < threshold {
+            return None;
+        }
+        current.threshold = None;
+        Some(ScrobbleAction::Scrobble(current.listen.clone()))
+    }
+}
+
+/// Response of an HTTP request
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// HTTP transport used by the scrobbling services; replaceable to use
+/// another client or to test the protocols
+pub trait HttpClient: Send + Sync {
+    /// POST `body` to `url`. Errors are transport failures; HTTP error
+    /// statuses are returned as responses.
+    fn post(
+        &self,
+        url: &str,
+        content_type: &str,
+        headers: &[(&str, String)],
+        body: &str,
+    ) -> Result<HttpResponse>;
+}
+
+/// `HttpClient` backed by ureq
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+
+impl UreqClient {
+    pub fn new(timeout: Duration) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .http_status_as_error(false)
+            .user_agent(format!("OneAmp/{}", env!("CARGO_PKG_VERSION")))
+            .build()
+            .into();
+        Self { agent }
+    }
+}
+
+impl Default for UreqClient {
+    fn default() -> Self {
+        Self::new(HTTP_TIMEOUT)
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn post(
+        &self,
+        url: &str,
+        content_type: &str,
+        headers: &[(&str, String)],
+        body: &str,
+    ) -> Result<HttpResponse> {
+        let mut request = self.agent.post(url).header("Content-Type", content_type);
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+        let mut response = request
+            .send(body)
+            .with_context(|| format!("Failed to reach {}", url))?;
+        Ok(HttpResponse {
+            status: response.status().as_u16(),
+            body: response.body_mut().read_to_string().unwrap_or_default(),
+        })
+    }
+}
+
+/// Why a submission failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubmitError {
+    /// The service can't be reached, is busy or rejected the credentials;
+    /// the listens are kept and sent again later
+    Retry(String),
+    /// The service refused the listens themselves; they are dropped
+    Rejected(String),
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Retry(message) | SubmitError::Rejected(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Classify a transport error or an HTTP error status with its message
+fn http_failure(status: u16, message: String) -> SubmitError {
+    match status {
+        401 | 403 | 408 | 429 | 500.. => SubmitError::Retry(message),
+        _ => SubmitError::Rejected(message),
+    }
+}
+
+/// A scrobbling service
+pub trait ScrobbleBackend: Send {
+    /// Name used in messages and as the service's offline queue key
+    fn name(&self) -> &'static str;
+
+    /// Most listens accepted in one submission
+    fn max_batch(&self) -> usize {
+        50
+    }
+
+    /// Report the track that just started. Not retried.
+    fn now_playing(&self, http: &dyn HttpClient, listen: &Listen) -> Result<(), SubmitError>;
+
+    /// Submit up to `max_batch` listens
+    fn submit(&self, http: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError>;
+}
+
+/// ListenBrainz account settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListenBrainzConfig {
+    /// User token from the ListenBrainz settings page
+    pub token: String,
+    /// API root, for self-hosted instances
+    #[serde(default = "listenbrainz::default_api_url")]
+    pub api_url: String,
+}
+
+/// Last.fm account settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastFmConfig {
+    pub api_key: String,
+    pub secret: String,
+    /// Session key from `LastFm::login`
+    pub session_key: String,
+    #[serde(default = "lastfm::default_api_url")]
+    pub api_url: String,
+}
+
+/// Scrobbling settings; services without settings are off
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrobbleConfig {
+    pub enabled: bool,
+    pub listenbrainz: Option<ListenBrainzConfig>,
+    pub lastfm: Option<LastFmConfig>,
+}
+
+impl ScrobbleConfig {
+    /// Backends for the configured services
+    pub fn backends(&self) -> Vec<Box<dyn ScrobbleBackend>> {
+        let mut backends: Vec<Box<dyn ScrobbleBackend>> = Vec::new();
+        if !self.enabled {
+            return backends;
+        }
+        if let Some(config) = self.listenbrainz.as_ref().filter(|c| !c.token.is_empty()) {
+            backends.push(Box::new(ListenBrainz::new(&config.api_url, &config.token)));
+        }
+        if let Some(config) = self.lastfm.as_ref().filter(|c| !c.session_key.is_empty()) {
+            backends.push(Box::new(LastFm::new(
+                &config.api_url,
+                &config.api_key,
+                &config.secret,
+                &config.session_key,
+            )));
+        }
+        backends
+    }
+}
+
+/// Progress of the submissions, for display
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrobbleStatus {
+    /// Listens waiting in the offline queue, all services together
+    pub queued: usize,
+    /// Most recent failure, cleared by the next success
+    pub last_error: Option<String>,
+}
+
+enum Job {
+    NowPlaying(Listen),
+    Scrobble(Listen),
+}
+
+/// Scrobbles the player's events in the background
+pub struct ScrobbleService {
+    tracker: ScrobbleTracker,
+    jobs: Sender<Job>,
+    status: Arc<Mutex<ScrobbleStatus>>,
+}
+
+impl ScrobbleService {
+    /// Start submitting to `backends`, with undelivered listens kept in
+    /// the queue file at `queue_path`
+    pub fn start(
+        backends: Vec<Box<dyn ScrobbleBackend>>,
+        http: Arc<dyn HttpClient>,
+        queue_path: Option<PathBuf>,
+    ) -> Self {
+        let queue = match &queue_path {
+            Some(path) => ScrobbleQueue::load(path).unwrap_or_else(|e| {
+                eprintln!("Failed to load the scrobble queue: {:#}", e);
+                ScrobbleQueue::new(Some(path.clone()))
+            }),
+            None => ScrobbleQueue::new(None),
+        };
+        let status = Arc::new(Mutex::new(ScrobbleStatus {
+            queued: queue.len(),
+            last_error: None,
+        }));
+        let (jobs, receiver) = crossbeam_channel::unbounded();
+        let mut worker = Worker {
+            backends: backends
+                .into_iter()
+                .map(|backend| (backend, Backoff::default()))
+                .collect(),
+            http,
+            queue,
+            status: status.clone(),
+        };
+        thread::Builder::new()
+            .name("scrobbler".to_string())
+            .spawn(move || worker.run(receiver))
+            .expect("Failed to spawn the scrobbler thread");
+        Self {
+            tracker: ScrobbleTracker::new(),
+            jobs,
+            status,
+        }
+    }
+
+    /// Start the services in `config`, or `None` if none is set up
+    pub fn from_config(config: &ScrobbleConfig, queue_path: Option<PathBuf>) -> Option<Self> {
+        let backends = config.backends();
+        if backends.is_empty() {
+            return None;
+        }
+        Some(Self::start(
+            backends,
+            Arc::new(UreqClient::default()),
+            queue_path,
+        ))
+    }
+
+    /// Feed a player event
+    pub fn handle_event(&mut self, event: &AudioEvent) {
+        let job = match self.tracker.handle_event(event) {
+            Some(ScrobbleAction::NowPlaying(listen)) => Job::NowPlaying(listen),
+            Some(ScrobbleAction::Scrobble(listen)) => Job::Scrobble(listen),
+            None => return,
+        };
+        let _ = self.jobs.send(job);
+    }
+
+    pub fn status(&self) -> ScrobbleStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+#[derive(Default)]
+struct Backoff {
+    delay: Option<Duration>,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    fn failed(&mut self) {
+        let delay = self
+            .delay
+            .map_or(RETRY_DELAY, |delay| (delay * 2).min(MAX_RETRY_DELAY));
+        self.delay = Some(delay);
+        self.retry_at = Some(Instant::now() + delay);
+    }
+
+    fn ready(&self) -> bool {
+        self.retry_at.is_none_or(|at| Instant::now() >= at)
+    }
+}
+
+struct Worker {
+    backends: Vec<(Box<dyn ScrobbleBackend>, Backoff)>,
+    http: Arc<dyn HttpClient>,
+    queue: ScrobbleQueue,
+    status: Arc<Mutex<ScrobbleStatus>>,
+}
+
+impl Worker {
+    fn run(&mut self, jobs: Receiver<Job>) {
+        // Deliver what an earlier session left behind
+        self.flush();
+        loop {
+            let retry_at = self
+                .backends
+                .iter()
+                .filter(|(backend, _)| !self.queue.pending(backend.name()).is_empty())
+                .filter_map(|(_, backoff)| backoff.retry_at)
+                .min();
+            let job = match retry_at {
+                Some(at) => jobs.recv_timeout(at.saturating_duration_since(Instant::now())),
+                None => jobs.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match job {
+                Ok(Job::NowPlaying(listen)) => {
+                    for (backend, _) in &self.backends {
+                        if let Err(e) = backend.now_playing(self.http.as_ref(), &listen) {
+                            eprintln!("{}: now playing not sent: {}", backend.name(), e);
+                        }
+                    }
+                }
+                Ok(Job::Scrobble(listen)) => {
+                    for (backend, _) in &self.backends {
+                        self.queue.push(backend.name(), listen.clone());
+                    }
+                    self.save_queue();
+                    self.flush();
+                }
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    /// Submit queued listens in batches to every service that isn't
+    /// waiting to retry
+    fn flush(&mut self) {
+        for (backend, backoff) in &mut self.backends {
+            let name = backend.name();
+            if !backoff.ready() {
+                continue;
+            }
+            loop {
+                let pending = self.queue.pending(name);
+                if pending.is_empty() {
+                    break;
+                }
+                let batch = pending.len().min(backend.max_batch().max(1));
+                match backend.submit(self.http.as_ref(), &pending[..batch]) {
+                    Ok(()) => {
+                        *backoff = Backoff::default();
+                        self.status.lock().unwrap().last_error = None;
+                    }
+                    Err(SubmitError::Rejected(message)) => {
+                        eprintln!("{}: {} listens rejected: {}", name, batch, message);
+                    }
+                    Err(SubmitError::Retry(message)) => {
+                        eprintln!("{}: submission failed: {}", name, message);
+                        backoff.failed();
+                        self.status.lock().unwrap().last_error =
+                            Some(format!("{}: {}", name, message));
+                        break;
+                    }
+                }
+                self.queue.remove(name, batch);
+                if let Err(e) = self.queue.save() {
+                    eprintln!("Failed to save the scrobble queue: {:#}", e);
+                }
+            }
+        }
+        self.status.lock().unwrap().queued = self.queue.len();
+    }
+
+    fn save_queue(&mut self) {
+        if let Err(e) = self.queue.save() {
+            eprintln!("Failed to save the scrobble queue: {:#}", e);
+        }
+        self.status.lock().unwrap().queued = self.queue.len();
+    }
+}
+
+/// Percent-encode a value for a URL query or form body
+fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(duration: Option<f32>) -> TrackInfo {
+        TrackInfo {
+            path: PathBuf::from("/music/song.flac"),
+            title: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            duration_secs: duration,
+            ..Default::default()
+        }
+    }
+
+    /// Play `secs` seconds in half-second position updates from `from`
+    fn play(
+        tracker: &mut ScrobbleTracker,
+        from: f32,
+        secs: f32,
+        total: f32,
+    ) -> Vec<ScrobbleAction> {
+        let steps = (secs * 2.0) as usize;
+        (0..=steps)
+            .filter_map(|i| {
+                tracker.handle_event(&AudioEvent::Position(from + i as f32 * 0.5, total))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_threshold() {
+        assert_eq!(scrobble_threshold(Some(20.0)), None);
+        assert_eq!(scrobble_threshold(Some(30.0)), None);
+        assert_eq!(scrobble_threshold(Some(200.0)), Some(100.0));
+        assert_eq!(scrobble_threshold(Some(600.0)), Some(240.0));
+        assert_eq!(scrobble_threshold(None), Some(240.0));
+    }
+
+    #[test]
+    fn test_now_playing_then_scrobble_at_half() {
+        let mut tracker = ScrobbleTracker::new();
+        assert_eq!(
+            tracker.handle_event(&AudioEvent::TrackLoaded(track(Some(100.0)))),
+            None
+        );
+        let Some(ScrobbleAction::NowPlaying(listen)) = tracker.handle_event(&AudioEvent::Playing)
+        else {
+            panic!("expected a now playing notice");
+        };
+        assert_eq!(listen.artist, "Artist");
+        assert_eq!(listen.duration_secs, Some(100));
+
+        assert!(play(&mut tracker, 0.0, 49.0, 100.0).is_empty());
+        let actions = play(&mut tracker, 49.5, 10.0, 100.0);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], ScrobbleAction::Scrobble(_)));
+        // Only once
+        assert!(play(&mut tracker, 60.0, 39.0, 100.0).is_empty());
+        assert_eq!(tracker.handle_event(&AudioEvent::Finished), None);
+    }
+
+    #[test]
+    fn test_listen_starts_at_first_playing() {
+        let mut tracker = ScrobbleTracker::new();
+        tracker.handle_event(&AudioEvent::TrackLoaded(track(Some(100.0))));
+        tracker.current.as_mut().unwrap().listen.listened_at = 0;
+        let Some(ScrobbleAction::NowPlaying(listen)) = tracker.handle_event(&AudioEvent::Playing)
+        else {
+            panic!("expected a now playing notice");
+        };
+        assert!(listen.listened_at > 0);
+
+        // Resuming doesn't start the listen again
+        tracker.current.as_mut().unwrap().listen.listened_at = 1;
+        tracker.handle_event(&AudioEvent::Paused);
+        assert_eq!(tracker.handle_event(&AudioEvent::Playing), None);
+        let actions = play(&mut tracker, 0.0, 60.0, 100.0);
+        assert!(matches!(
+            &actions[..],
+            [ScrobbleAction::Scrobble(listen)] if listen.listened_at == 1
+        ));
+    }
+
+    #[test]
+    fn test_seeks_and_pauses_dont_count() {
+        let mut tracker = ScrobbleTracker::new();
+        tracker.handle_event(&AudioEvent::TrackLoaded(track(Some(100.0))));
+        tracker.handle_event(&AudioEvent::Playing);
+        assert!(play(&mut tracker, 0.0, 10.0, 100.0).is_empty());
+        // Seeking to the end isn't listening
+        assert!(play(&mut tracker, 90.0, 9.5, 100.0).is_empty());
+        // Nor is time spent paused
+        tracker.handle_event(&AudioEvent::Paused);
+        assert!(play(&mut tracker, 20.0, 60.0, 100.0).is_empty());
+        assert_eq!(tracker.handle_event(&AudioEvent::Finished), None);
+    }
+
+    #[test]
+    fn test_short_and_untagged_tracks_are_skipped() {
+        let mut tracker = ScrobbleTracker::new();
+        tracker.handle_event(&AudioEvent::TrackLoaded(track(Some(25.0))));
+        assert!(matches!(
+            tracker.handle_event(&AudioEvent::Playing),
+            Some(ScrobbleAction::NowPlaying(_))
+        ));
+        assert!(play(&mut tracker, 0.0, 25.0, 25.0).is_empty());
+
+        let mut untagged = track(Some(300.0));
+        untagged.artist = None;
+        tracker.handle_event(&AudioEvent::TrackLoaded(untagged));
+        assert_eq!(tracker.handle_event(&AudioEvent::Playing), None);
+        assert!(play(&mut tracker, 0.0, 200.0, 300.0).is_empty());
+    }
+
+    #[test]
+    fn test_long_track_and_stream_titles() {
+        let mut tracker = ScrobbleTracker::new();
+        tracker.handle_event(&AudioEvent::TrackLoaded(track(Some(1200.0))));
+        tracker.handle_event(&AudioEvent::Playing);
+        assert!(play(&mut tracker, 0.0, 239.0, 1200.0).is_empty());
+        assert_eq!(play(&mut tracker, 239.5, 2.0, 1200.0).len(), 1);
+
+        // A stream announces every new title and needs four minutes of it
+        let mut stream = track(None);
+        stream.path = PathBuf::from("http://radio.example/live");
+        tracker.handle_event(&AudioEvent::TrackLoaded(stream.clone()));
+        assert!(tracker.handle_event(&AudioEvent::Playing).is_some());
+        assert_eq!(
+            tracker.handle_event(&AudioEvent::TrackUpdated(stream.clone())),
+            None
+        );
+        stream.title = Some("Next".to_string());
+        let Some(ScrobbleAction::NowPlaying(listen)) =
+            tracker.handle_event(&AudioEvent::TrackUpdated(stream))
+        else {
+            panic!("expected a now playing notice");
+        };
+        assert_eq!(listen.title, "Next");
+        assert!(play(&mut tracker, 0.0, 239.0, 0.0).is_empty());
+        assert_eq!(play(&mut tracker, 239.5, 1.0, 0.0).len(), 1);
+    }
+
+    struct FakeBackend {
+        result: Arc<Mutex<Result<(), SubmitError>>>,
+        batches: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl ScrobbleBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn max_batch(&self) -> usize {
+            2
+        }
+
+        fn now_playing(&self, _: &dyn HttpClient, _: &Listen) -> Result<(), SubmitError> {
+            Ok(())
+        }
+
+        fn submit(&self, _: &dyn HttpClient, listens: &[Listen]) -> Result<(), SubmitError> {
+            self.batches.lock().unwrap().push(listens.len());
+            self.result.lock().unwrap().clone()
+        }
+    }
+
+    struct Offline;
+
+    impl HttpClient for Offline {
+        fn post(&self, _: &str, _: &str, _: &[(&str, String)], _: &str) -> Result<HttpResponse> {
+            anyhow::bail!("offline")
+        }
+    }
+
+    #[test]
+    fn test_worker_batches_and_retries() {
+        let result = Arc::new(Mutex::new(Err(SubmitError::Retry("offline".to_string()))));
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let backend = FakeBackend {
+            result: result.clone(),
+            batches: batches.clone(),
+        };
+        let mut worker = Worker {
+            backends: vec![(Box::new(backend), Backoff::default())],
+            http: Arc::new(Offline),
+            queue: ScrobbleQueue::new(None),
+            status: Arc::default(),
+        };
+        let listen = Listen::from_track(&track(Some(100.0))).unwrap();
+        for _ in 0..5 {
+            worker.queue.push("fake", listen.clone());
+        }
+
+        // Failures keep everything and wait before trying again
+        worker.flush();
+        assert_eq!(*batches.lock().unwrap(), [2]);
+        assert_eq!(worker.queue.len(), 5);
+        assert_eq!(worker.status.lock().unwrap().queued, 5);
+        assert!(worker.status.lock().unwrap().last_error.is_some());
+        worker.flush();
+        assert_eq!(batches.lock().unwrap().len(), 1);
+
+        // Back online: the queue drains in batches
+        *result.lock().unwrap() = Ok(());
+        worker.backends[0].1.retry_at = Some(Instant::now());
+        worker.flush();
+        assert_eq!(*batches.lock().unwrap(), [2, 2, 2, 1]);
+        assert!(worker.queue.is_empty());
+        assert_eq!(*worker.status.lock().unwrap(), ScrobbleStatus::default());
+
+        // Rejected listens are dropped rather than retried
+        *result.lock().unwrap() = Err(SubmitError::Rejected("bad".to_string()));
+        worker.queue.push("fake", listen);
+        worker.flush();
+        assert!(worker.queue.is_empty());
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(url_encode("a b&c=ü~"), "a%20b%26c%3D%C3%BC~");
+    }
+}
@@ -0,0 +1,127 @@
+// Offline Scrobble Queue
+// Listens not yet accepted by a service, kept per service in a JSON file so
+// they survive restarts and offline periods.
+
+use super::Listen;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Oldest listens are dropped beyond this many per service; Last.fm
+/// refuses scrobbles older than two weeks anyway
+const MAX_QUEUED: usize = 10_000;
+
+pub struct ScrobbleQueue {
+    path: Option<PathBuf>,
+    pending: BTreeMap<String, Vec<Listen>>,
+}
+
+impl ScrobbleQueue {
+    /// An empty queue saved to `path`, or kept in memory only
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Load the queue saved at `path`; a missing file is an empty queue
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut queue = Self::new(Some(path.to_path_buf()));
+        if path.exists() {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            queue.pending = serde_json::from_str(&json)
+                .with_context(|| format!("Invalid scrobble queue {}", path.display()))?;
+        }
+        Ok(queue)
+    }
+
+    /// Write the queue to its file, replacing it atomically
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string(&self.pending)?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        std::fs::rename(&temp, path).with_context(|| format!("Failed to save {}", path.display()))
+    }
+
+    /// Queue a listen for `service`
+    pub fn push(&mut self, service: &str, listen: Listen) {
+        let pending = self.pending.entry(service.to_string()).or_default();
+        pending.push(listen);
+        if pending.len() > MAX_QUEUED {
+            pending.drain(..pending.len() - MAX_QUEUED);
+        }
+    }
+
+    /// Listens waiting for `service`, oldest first
+    pub fn pending(&self, service: &str) -> &[Listen] {
+        self.pending.get(service).map_or(&[], Vec::as_slice)
+    }
+
+    /// Remove the `count` oldest listens of `service`
+    pub fn remove(&mut self, service: &str, count: usize) {
+        if let Some(pending) = self.pending.get_mut(service) {
+            pending.drain(..count.min(pending.len()));
+            if pending.is_empty() {
+                self.pending.remove(service);
+            }
+        }
+    }
+
+    /// Listens waiting, all services together
+    pub fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listen(title: &str) -> Listen {
+        Listen {
+            artist: "Artist".to_string(),
+            title: title.to_string(),
+            album: None,
+            duration_secs: Some(200),
+            listened_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_queue_persists_per_service() {
+        let path = std::env::temp_dir().join("oneamp_scrobble_queue_test.json");
+        std::fs::remove_file(&path).ok();
+
+        let mut queue = ScrobbleQueue::load(&path).unwrap();
+        assert!(queue.is_empty());
+        queue.push("lastfm", listen("One"));
+        queue.push("lastfm", listen("Two"));
+        queue.push("listenbrainz", listen("One"));
+        queue.save().unwrap();
+
+        let mut queue = ScrobbleQueue::load(&path).unwrap();
+        assert_eq!(queue.len(), 3);
+        queue.remove("lastfm", 1);
+        assert_eq!(queue.pending("lastfm"), [listen("Two")]);
+        queue.remove("listenbrainz", 5);
+        assert!(queue.pending("listenbrainz").is_empty());
+        assert_eq!(queue.len(), 1);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(ScrobbleQueue::load(&path).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,1437 @@
+// Skins
+// The `skin.toml` format shared by the desktop app and `oneamp-cli skin`:
+// the skin model, loading from a skin folder, a zipped skin or a `.oaskin`
+// package, and validation that reports every problem with its line and
+// column in the file. Old `theme.toml` files are converted into skins by
+// the `theme` module.
+
+mod theme;
+
+pub use theme::{migrate_theme, migrate_theme_file};
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{ImDocument, TableLike};
+
+/// Name of the skin description file in a skin folder or archive
+pub const SKIN_FILE: &str = "skin.toml";
+
+/// Name of the package description file at the root of a `.oaskin` package
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+/// File extension of packaged skins
+pub const PACKAGE_EXTENSION: &str = "oaskin";
+
+/// Represents a complete skin configuration.
+/// A skin defines colors, fonts, metrics, images, and metadata for the OneAmp UI.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Skin {
+    pub metadata: Metadata,
+    pub colors: Colors,
+    pub fonts: Fonts,
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub images: Images,
+
+    /// Folder or archive the skin was loaded from; empty for built-in skins
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Skin {
+    /// Creates a default built-in skin with OneAmp's original colors.
+    pub fn default_builtin() -> Self {
+        Self {
+            metadata: Metadata {
+                name: "OneAmp Dark".to_string(),
+                author: "Manus AI".to_string(),
+                version: "1.0".to_string(),
+                description: "The default OneAmp dark theme.".to_string(),
+            },
+            colors: Colors::default(),
+            fonts: Fonts::default(),
+            metrics: Metrics::default(),
+            layout: Layout::default(),
+            images: Images::default(),
+            path: PathBuf::new(),
+        }
+    }
+
+    /// Font and image files the skin refers to, relative to its directory.
+    pub fn asset_paths(&self) -> Vec<&Path> {
+        self.assets().into_iter().map(|(_, path)| path).collect()
+    }
+
+    /// Font and image files the skin refers to, with the dotted key of each.
+    fn assets(&self) -> Vec<(String, &Path)> {
+        let fonts = &self.fonts;
+        let mut assets = vec![
+            (
+                "fonts.proportional_font".to_string(),
+                fonts.proportional_font.as_ref(),
+            ),
+            (
+                "fonts.monospace_font".to_string(),
+                fonts.monospace_font.as_ref(),
+            ),
+            ("fonts.timer_font".to_string(), fonts.timer_font.as_ref()),
+        ];
+
+        let buttons = &self.images.buttons;
+        let buttons = [
+            ("previous", &buttons.previous),
+            ("play", &buttons.play),
+            ("pause", &buttons.pause),
+            ("stop", &buttons.stop),
+            ("next", &buttons.next),
+        ];
+        for (name, images) in buttons {
+            if let Some(images) = images {
+                let key = format!("images.buttons.{}", name);
+                assets.push((format!("{}.normal", key), Some(&images.normal)));
+                assets.push((format!("{}.hover", key), images.hover.as_ref()));
+                assets.push((format!("{}.pressed", key), images.pressed.as_ref()));
+            }
+        }
+
+        let slices = [
+            ("background", &self.images.background),
+            ("display", &self.images.display),
+        ];
+        for (name, slice) in slices {
+            if let Some(slice) = slice {
+                assets.push((format!("images.{}.image", name), Some(&slice.image)));
+            }
+        }
+
+        assets
+            .into_iter()
+            .filter_map(|(key, path)| Some((key, path?.as_path())))
+            .collect()
+    }
+}
+
+/// Metadata about a skin (name, author, version, etc.)
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Metadata {
+    pub name: String,
+    pub author: String,
+    pub version: String,
+    pub description: String,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            author: "OneAmp".to_string(),
+            version: "1.0".to_string(),
+            description: "Default skin".to_string(),
+        }
+    }
+}
+
+/// Color palette for the entire application.
+/// All colors are specified as hex strings (e.g., "#RRGGBB" or "#RRGGBBAA").
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Colors {
+    pub dark_mode: bool,
+
+    // Main UI colors
+    pub background: String,
+    pub text: String,
+    pub window_fill: String,
+    pub window_stroke: String,
+    pub panel_fill: String,
+
+    // Widget colors
+    pub widget_bg: String,
+    pub widget_stroke: String,
+    pub hovered_widget_bg: String,
+    pub active_widget_bg: String,
+    pub inactive_widget_bg: String,
+
+    // Special colors
+    pub accent: String,
+    pub error: String,
+    pub warning: String,
+
+    // Playlist specific
+    pub playlist_current_track: String,
+    pub playlist_selected_bg: String,
+
+    // LCD display (timer, track info), progress bar, playlist and equalizer
+    // colors. Each is optional and falls back to one of the colors above, see
+    // the methods of the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_accent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress_bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress_fill: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eq_slider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eq_fill: Option<String>,
+
+    /// Colors of the spectrum bars from quiet to loud.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub visualizer_gradient: Vec<String>,
+}
+
+impl Colors {
+    /// Background of the LCD display; falls back to `panel_fill`.
+    pub fn display_bg(&self) -> &str {
+        self.display_bg.as_deref().unwrap_or(&self.panel_fill)
+    }
+
+    /// Text of the LCD display; falls back to `text`.
+    pub fn display_text(&self) -> &str {
+        self.display_text.as_deref().unwrap_or(&self.text)
+    }
+
+    /// Highlights of the LCD display, such as the timer; falls back to `accent`.
+    pub fn display_accent(&self) -> &str {
+        self.display_accent.as_deref().unwrap_or(&self.accent)
+    }
+
+    /// Track of the progress bar; falls back to `widget_bg`.
+    pub fn progress_bg(&self) -> &str {
+        self.progress_bg.as_deref().unwrap_or(&self.widget_bg)
+    }
+
+    /// Played part of the progress bar; falls back to `accent`.
+    pub fn progress_fill(&self) -> &str {
+        self.progress_fill.as_deref().unwrap_or(&self.accent)
+    }
+
+    /// Background of the playlist; falls back to `panel_fill`.
+    pub fn playlist_bg(&self) -> &str {
+        self.playlist_bg.as_deref().unwrap_or(&self.panel_fill)
+    }
+
+    /// Text of the playlist; falls back to `text`.
+    pub fn playlist_text(&self) -> &str {
+        self.playlist_text.as_deref().unwrap_or(&self.text)
+    }
+
+    /// Track of the equalizer sliders; falls back to `widget_bg`.
+    pub fn eq_slider(&self) -> &str {
+        self.eq_slider.as_deref().unwrap_or(&self.widget_bg)
+    }
+
+    /// Level of the equalizer sliders up to 0 dB; falls back to `accent`.
+    pub fn eq_fill(&self) -> &str {
+        self.eq_fill.as_deref().unwrap_or(&self.accent)
+    }
+
+    /// Spectrum bar colors from quiet to loud; falls back to the display
+    /// accent, `warning` and `error`.
+    pub fn visualizer_gradient(&self) -> Vec<&str> {
+        if self.visualizer_gradient.is_empty() {
+            vec![self.display_accent(), &self.warning, &self.error]
+        } else {
+            self.visualizer_gradient
+                .iter()
+                .map(String::as_str)
+                .collect()
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            dark_mode: true,
+            background: "#0a0a0a".to_string(),
+            text: "#ffffff".to_string(),
+            window_fill: "#1a1a1a".to_string(),
+            window_stroke: "#404040".to_string(),
+            panel_fill: "#0f0f0f".to_string(),
+            widget_bg: "#2a2a2a".to_string(),
+            widget_stroke: "#404040".to_string(),
+            hovered_widget_bg: "#3a3a3a".to_string(),
+            active_widget_bg: "#4a4a4a".to_string(),
+            inactive_widget_bg: "#1a1a1a".to_string(),
+            accent: "#00d4ff".to_string(),
+            error: "#ff4444".to_string(),
+            warning: "#ffbb33".to_string(),
+            playlist_current_track: "#00d4ff".to_string(),
+            playlist_selected_bg: "#404040".to_string(),
+            display_bg: None,
+            display_text: None,
+            display_accent: None,
+            progress_bg: None,
+            progress_fill: None,
+            playlist_bg: None,
+            playlist_text: None,
+            eq_slider: None,
+            eq_fill: None,
+            visualizer_gradient: Vec::new(),
+        }
+    }
+}
+
+/// Font configuration for the application.
+/// Fonts can be specified by system name or by a path relative to the skin directory.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Fonts {
+    /// Default proportional font for general UI text.
+    pub proportional: String,
+
+    /// Monospaced font for code-like text (timers, detailed info).
+    pub monospace: String,
+
+    /// Optional path to a custom font file for the timer display.
+    pub timer_font: Option<PathBuf>,
+
+    /// Optional TTF/OTF file used before the default proportional font.
+    #[serde(default)]
+    pub proportional_font: Option<PathBuf>,
+
+    /// Optional TTF/OTF file used before the default monospace font.
+    #[serde(default)]
+    pub monospace_font: Option<PathBuf>,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Self {
+            proportional: "Arial".to_string(),
+            monospace: "Courier New".to_string(),
+            timer_font: None,
+            proportional_font: None,
+            monospace_font: None,
+        }
+    }
+}
+
+/// Image assets drawn instead of the built-in widgets.
+/// Paths are relative to the skin directory; images may be PNG, JPEG or BMP.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Images {
+    /// Transport button images.
+    #[serde(default)]
+    pub buttons: ButtonImages,
+
+    /// Nine-slice background of the main window.
+    pub background: Option<NineSlice>,
+
+    /// Nine-slice frame behind the player display (timer, title, spectrum).
+    pub display: Option<NineSlice>,
+}
+
+/// Images of the transport buttons. Buttons without images keep the
+/// built-in look.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ButtonImages {
+    pub previous: Option<StateImages>,
+    pub play: Option<StateImages>,
+    pub pause: Option<StateImages>,
+    pub stop: Option<StateImages>,
+    pub next: Option<StateImages>,
+}
+
+/// Images of a widget for each state.
+/// `hover` and `pressed` fall back to `normal` when missing.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StateImages {
+    pub normal: PathBuf,
+    pub hover: Option<PathBuf>,
+    pub pressed: Option<PathBuf>,
+}
+
+/// An image stretched over any size with fixed corners: the borders keep
+/// their size, the edges stretch along one axis and the center along both.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NineSlice {
+    pub image: PathBuf,
+
+    /// Border widths in image pixels [left, top, right, bottom].
+    #[serde(default)]
+    pub borders: [u32; 4],
+}
+
+/// Layout and spacing metrics for the UI.
+/// These values control the "density" and feel of the interface.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Metrics {
+    /// Rounding radius for window corners.
+    pub window_rounding: f32,
+
+    /// Rounding radius for widget corners (buttons, sliders, etc.).
+    pub widget_rounding: f32,
+
+    /// Width of scrollbars.
+    pub scrollbar_width: f32,
+
+    /// Padding inside windows.
+    pub window_padding: f32,
+
+    /// Padding inside buttons [x, y].
+    pub button_padding: [f32; 2],
+
+    /// Font size for body text.
+    pub body_text_size: f32,
+
+    /// Font size for headings.
+    pub heading_text_size: f32,
+
+    /// Font size for the timer display.
+    pub timer_text_size: f32,
+
+    /// Font size for the track title and details.
+    #[serde(default = "default_track_info_text_size")]
+    pub track_info_text_size: f32,
+
+    /// Font size for playlist rows and lyrics.
+    #[serde(default = "default_playlist_text_size")]
+    pub playlist_text_size: f32,
+
+    /// Font size for button labels.
+    #[serde(default = "default_button_text_size")]
+    pub button_text_size: f32,
+}
+
+fn default_track_info_text_size() -> f32 {
+    20.0
+}
+
+fn default_playlist_text_size() -> f32 {
+    14.0
+}
+
+fn default_button_text_size() -> f32 {
+    14.0
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            window_rounding: 4.0,
+            widget_rounding: 2.0,
+            scrollbar_width: 8.0,
+            window_padding: 8.0,
+            button_padding: [12.0, 4.0],
+            body_text_size: 14.0,
+            heading_text_size: 18.0,
+            timer_text_size: 48.0,
+            track_info_text_size: default_track_info_text_size(),
+            playlist_text_size: default_playlist_text_size(),
+            button_text_size: default_button_text_size(),
+        }
+    }
+}
+
+/// Sizes of the main window and its sections.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Layout {
+    /// Smallest size of the main window [width, height].
+    pub window_min_size: [f32; 2],
+
+    /// Height of the player section (display and transport buttons).
+    pub player_height: f32,
+
+    /// Height of the equalizer section.
+    pub equalizer_height: f32,
+
+    /// Space between widgets.
+    pub item_spacing: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            window_min_size: [700.0, 600.0],
+            player_height: 200.0,
+            equalizer_height: 220.0,
+            item_spacing: 16.0,
+        }
+    }
+}
+
+/// The `manifest.toml` of a packaged skin, telling installs and updates apart.
+/// The skin's name, author and description come from its `skin.toml`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Identifier the package is installed under: lowercase letters, digits
+    /// and dashes, such as `cyberpunk-neon`
+    pub id: String,
+
+    /// Version of the package, numbers separated by dots; installing a newer
+    /// version of an installed package updates it
+    pub version: String,
+
+    /// Optional preview image shown in the skin gallery
+    #[serde(default)]
+    pub preview: Option<PathBuf>,
+}
+
+/// A loaded `.oaskin` package
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub manifest: Manifest,
+    pub skin: Skin,
+}
+
+/// A problem found in a skin
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Dotted key of the faulty value, such as `colors.accent`
+    pub key: Option<String>,
+    pub message: String,
+    /// Line and column in `skin.toml`, both starting at 1
+    pub location: Option<(usize, usize)>,
+}
+
+impl Problem {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            key: None,
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    fn at_key(key: &str, message: impl Into<String>) -> Self {
+        Self {
+            key: Some(key.to_string()),
+            ..Self::new(message)
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A skin that couldn't be loaded, with everything wrong with it
+#[derive(Debug, Clone)]
+pub struct SkinError {
+    /// The skin's `skin.toml`, or the archive when it couldn't be read
+    pub path: PathBuf,
+    pub problems: Vec<Problem>,
+}
+
+impl SkinError {
+    fn new(path: &Path, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            problems: vec![Problem::new(message)],
+        }
+    }
+
+    /// One line per problem, prefixed with the path like compiler messages:
+    /// `path:line:column: key: message`.
+    pub fn lines(&self) -> Vec<String> {
+        self.problems
+            .iter()
+            .map(|problem| match problem.location {
+                Some(_) => format!("{}:{}", self.path.display(), problem),
+                None => format!("{}: {}", self.path.display(), problem),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for SkinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lines().as_slice() {
+            [line] => write!(f, "{}", line),
+            lines => {
+                write!(f, "{}: {} problems", self.path.display(), lines.len())?;
+                for problem in &self.problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SkinError {}
+
+/// Loads a skin from a skin.toml file.
+///
+/// # Arguments
+/// * `skin_dir` - Path to the directory containing the skin.toml file
+///
+/// # Returns
+/// A `Skin` struct if successful, or every problem found if the file is
+/// missing or invalid.
+pub fn load_skin(skin_dir: &Path) -> Result<Skin, SkinError> {
+    let skin_file = skin_dir.join(SKIN_FILE);
+    let content = fs::read_to_string(&skin_file).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => SkinError::new(&skin_file, "skin.toml not found"),
+        _ => SkinError::new(&skin_file, format!("Failed to read skin.toml: {}", e)),
+    })?;
+
+    let mut skin =
+        parse_skin(&content, |asset| skin_dir.join(asset).is_file()).map_err(|problems| {
+            SkinError {
+                path: skin_file.clone(),
+                problems,
+            }
+        })?;
+
+    // Set the skin's path for relative asset resolution
+    skin.path = skin_dir.to_path_buf();
+    Ok(skin)
+}
+
+/// Loads a zipped skin: a `skin.toml` at the root of the archive or in a
+/// top-level folder, with its assets next to it.
+///
+/// The skin's path is the archive.
+pub fn load_skin_archive(path: &Path) -> Result<Skin, SkinError> {
+    let (mut archive, names) = open_archive(path)?;
+    let root = archive_root(&names)
+        .ok_or_else(|| SkinError::new(path, "skin.toml not found in archive"))?;
+    read_archive_skin(path, &mut archive, &names, &root)
+}
+
+/// Whether a path has the `.oaskin` extension of packaged skins.
+pub fn is_package(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(PACKAGE_EXTENSION))
+}
+
+/// Loads a packaged skin: a `.oaskin` archive with `manifest.toml`,
+/// `skin.toml` and the skin's assets at its root.
+///
+/// The skin's path is the archive.
+pub fn load_package(path: &Path) -> Result<Package, SkinError> {
+    let (mut archive, names) = open_archive(path)?;
+    for file in [MANIFEST_FILE, SKIN_FILE] {
+        if !names.contains(file) {
+            return Err(SkinError::new(
+                path,
+                format!("{} not found at the root of the package", file),
+            ));
+        }
+    }
+
+    let content = read_entry(path, &mut archive, MANIFEST_FILE)?;
+    let manifest = parse_manifest(&content, |asset| names.contains(&archive_name(asset))).map_err(
+        |problems| SkinError {
+            path: path.join(MANIFEST_FILE),
+            problems,
+        },
+    )?;
+    let skin = read_archive_skin(path, &mut archive, &names, "")?;
+    Ok(Package { manifest, skin })
+}
+
+/// Parses and validates the content of a package's `manifest.toml`.
+///
+/// # Arguments
+/// * `content` - The TOML text
+/// * `asset_exists` - Whether the preview image is a file in the package
+///
+/// # Returns
+/// The manifest, or every problem found with its location in `content`.
+pub fn parse_manifest(
+    content: &str,
+    asset_exists: impl Fn(&Path) -> bool,
+) -> Result<Manifest, Vec<Problem>> {
+    let manifest: Manifest =
+        toml::from_str(content).map_err(|e| vec![syntax_problem(content, &e)])?;
+
+    let mut problems = Vec::new();
+    let valid_id = !manifest.id.is_empty()
+        && manifest
+            .id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_id {
+        problems.push(Problem::at_key(
+            "id",
+            format!(
+                "Package id must be lowercase letters, digits and dashes: {:?}",
+                manifest.id
+            ),
+        ));
+    }
+    if version_numbers(&manifest.version).is_none() {
+        problems.push(Problem::at_key(
+            "version",
+            format!(
+                "Version must be numbers separated by dots, such as 1.2.0: {:?}",
+                manifest.version
+            ),
+        ));
+    }
+    if let Some(preview) = &manifest.preview {
+        if let Err(message) = validate_asset(preview, &asset_exists) {
+            problems.push(Problem::at_key("preview", message));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(locate(content, problems))
+    }
+}
+
+/// Compares two package versions number by number, so `1.10` is newer than
+/// `1.9` and `1.0` equals `1.0.0`. Invalid versions are older than any
+/// valid one.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |version: &str| {
+        version_numbers(version).map(|mut numbers| {
+            while numbers.last() == Some(&0) {
+                numbers.pop();
+            }
+            numbers
+        })
+    };
+    numbers(a).cmp(&numbers(b))
+}
+
+/// The numbers of a dotted version, or `None` if it isn't one.
+fn version_numbers(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Packs a skin folder into a `.oaskin` package with its `manifest.toml`,
+/// `skin.toml`, preview and the assets the skin refers to. Other files in
+/// the folder are left out.
+///
+/// # Returns
+/// The package's manifest, once the written package loads.
+pub fn pack_skin(skin_dir: &Path, output: &Path) -> Result<Manifest, SkinError> {
+    let skin = load_skin(skin_dir)?;
+    let manifest_file = skin_dir.join(MANIFEST_FILE);
+    let content = fs::read_to_string(&manifest_file).map_err(|e| {
+        SkinError::new(
+            &manifest_file,
+            format!("Failed to read manifest.toml: {}", e),
+        )
+    })?;
+    let manifest =
+        parse_manifest(&content, |asset| skin_dir.join(asset).is_file()).map_err(|problems| {
+            SkinError {
+                path: manifest_file.clone(),
+                problems,
+            }
+        })?;
+
+    let mut files = vec![PathBuf::from(MANIFEST_FILE), PathBuf::from(SKIN_FILE)];
+    files.extend(manifest.preview.clone());
+    files.extend(skin.asset_paths().into_iter().map(Path::to_path_buf));
+
+    let write = || -> io::Result<()> {
+        let mut zip = zip::ZipWriter::new(File::create(output)?);
+        let mut written = HashSet::new();
+        for file in files {
+            let name = archive_name(&file);
+            if written.insert(name.clone()) {
+                zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+                io::copy(&mut File::open(skin_dir.join(&file))?, &mut zip)?;
+            }
+        }
+        zip.finish()?;
+        Ok(())
+    };
+    write().map_err(|e| SkinError::new(output, format!("Failed to write package: {}", e)))?;
+
+    load_package(output)?;
+    Ok(manifest)
+}
+
+/// Reads a font or image of a skin, from its folder or from its archive.
+pub fn read_asset(skin: &Skin, asset: &Path) -> io::Result<Vec<u8>> {
+    if !skin.path.is_file() {
+        return fs::read(skin.path.join(asset));
+    }
+
+    let mut archive = zip::ZipArchive::new(File::open(&skin.path)?)?;
+    let names: HashSet<String> = archive.file_names().map(str::to_string).collect();
+    let root = archive_root(&names).unwrap_or_default();
+    let mut entry = archive.by_name(&format!("{}{}", root, archive_name(asset)))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Opens a skin archive, with the names of its entries.
+fn open_archive(path: &Path) -> Result<(zip::ZipArchive<File>, HashSet<String>), SkinError> {
+    let file = File::open(path)
+        .map_err(|e| SkinError::new(path, format!("Failed to open archive: {}", e)))?;
+    let archive = zip::ZipArchive::new(file)
+        .map_err(|e| SkinError::new(path, format!("Not a zip archive: {}", e)))?;
+    let names = archive.file_names().map(str::to_string).collect();
+    Ok((archive, names))
+}
+
+/// Folder of the shallowest skin.toml in an archive, at most one folder
+/// deep: empty for the root, or a name ending with a slash.
+fn archive_root(names: &HashSet<String>) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| name.strip_suffix(SKIN_FILE))
+        .filter(|folder| folder.is_empty() || folder.find('/') == Some(folder.len() - 1))
+        .min_by_key(|folder| folder.len())
+        .map(str::to_string)
+}
+
+/// Reads a text file of an archive.
+fn read_entry(
+    path: &Path,
+    archive: &mut zip::ZipArchive<File>,
+    name: &str,
+) -> Result<String, SkinError> {
+    let mut content = String::new();
+    archive
+        .by_name(name)
+        .map_err(io::Error::from)
+        .and_then(|mut entry| entry.read_to_string(&mut content))
+        .map_err(|e| SkinError::new(path, format!("Failed to read {}: {}", name, e)))?;
+    Ok(content)
+}
+
+/// Loads the skin in the `root` folder of an archive.
+fn read_archive_skin(
+    path: &Path,
+    archive: &mut zip::ZipArchive<File>,
+    names: &HashSet<String>,
+    root: &str,
+) -> Result<Skin, SkinError> {
+    let skin_file = format!("{}{}", root, SKIN_FILE);
+    let content = read_entry(path, archive, &skin_file)?;
+    let mut skin = parse_skin(&content, |asset| {
+        names.contains(&format!("{}{}", root, archive_name(asset)))
+    })
+    .map_err(|problems| SkinError {
+        path: path.join(&skin_file),
+        problems,
+    })?;
+    skin.path = path.to_path_buf();
+    Ok(skin)
+}
+
+/// Name of a skin asset inside the skin's folder of an archive.
+fn archive_name(asset: &Path) -> String {
+    let parts: Vec<_> = asset
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    parts.join("/")
+}
+
+/// Parses and validates the content of a `skin.toml`.
+///
+/// # Arguments
+/// * `content` - The TOML text
+/// * `asset_exists` - Whether a font or image path of the skin is a file
+///
+/// # Returns
+/// The skin, or every problem found with its location in `content`.
+pub fn parse_skin(
+    content: &str,
+    asset_exists: impl Fn(&Path) -> bool,
+) -> Result<Skin, Vec<Problem>> {
+    let skin: Skin = toml::from_str(content).map_err(|e| vec![syntax_problem(content, &e)])?;
+
+    let problems = validate_skin(&skin, asset_exists);
+    if problems.is_empty() {
+        Ok(skin)
+    } else {
+        Err(locate(content, problems))
+    }
+}
+
+/// A TOML syntax or type error, at its location in `content`.
+fn syntax_problem(content: &str, error: &toml::de::Error) -> Problem {
+    Problem {
+        key: None,
+        message: error.message().trim().replace('\n', ", "),
+        location: error.span().map(|span| line_column(content, span.start)),
+    }
+}
+
+/// Points problems at the faulty values in `content`.
+fn locate(content: &str, mut problems: Vec<Problem>) -> Vec<Problem> {
+    if let Ok(document) = ImDocument::parse(content) {
+        for problem in &mut problems {
+            problem.location = problem
+                .key
+                .as_deref()
+                .and_then(|key| key_span(document.as_table(), key))
+                .map(|span| line_column(content, span.start));
+        }
+    }
+    problems
+}
+
+/// Validates a skin's configuration.
+/// Checks for required fields, valid color formats, metrics and layout ranges
+/// and that every font and image is a file inside the skin according to
+/// `asset_exists`.
+///
+/// # Returns
+/// Every problem found, without locations; empty if the skin is valid.
+pub fn validate_skin(skin: &Skin, asset_exists: impl Fn(&Path) -> bool) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut check = |key: &str, result: Result<(), String>| {
+        if let Err(message) = result {
+            problems.push(Problem::at_key(key, message));
+        }
+    };
+    let require = |valid: bool, message: String| if valid { Ok(()) } else { Err(message) };
+
+    // Check metadata
+    check(
+        "metadata.name",
+        require(
+            !skin.metadata.name.is_empty(),
+            "Skin name cannot be empty".into(),
+        ),
+    );
+
+    // Check colors are valid hex strings
+    let colors = &skin.colors;
+    let colors = [
+        ("background", &colors.background),
+        ("text", &colors.text),
+        ("window_fill", &colors.window_fill),
+        ("window_stroke", &colors.window_stroke),
+        ("panel_fill", &colors.panel_fill),
+        ("widget_bg", &colors.widget_bg),
+        ("widget_stroke", &colors.widget_stroke),
+        ("hovered_widget_bg", &colors.hovered_widget_bg),
+        ("active_widget_bg", &colors.active_widget_bg),
+        ("inactive_widget_bg", &colors.inactive_widget_bg),
+        ("accent", &colors.accent),
+        ("error", &colors.error),
+        ("warning", &colors.warning),
+        ("playlist_current_track", &colors.playlist_current_track),
+        ("playlist_selected_bg", &colors.playlist_selected_bg),
+    ];
+    for (name, color) in colors {
+        check(&format!("colors.{}", name), validate_hex_color(color));
+    }
+    let colors = &skin.colors;
+    let optional_colors = [
+        ("display_bg", &colors.display_bg),
+        ("display_text", &colors.display_text),
+        ("display_accent", &colors.display_accent),
+        ("progress_bg", &colors.progress_bg),
+        ("progress_fill", &colors.progress_fill),
+        ("playlist_bg", &colors.playlist_bg),
+        ("playlist_text", &colors.playlist_text),
+        ("eq_slider", &colors.eq_slider),
+        ("eq_fill", &colors.eq_fill),
+    ];
+    for (name, color) in optional_colors {
+        if let Some(color) = color {
+            check(&format!("colors.{}", name), validate_hex_color(color));
+        }
+    }
+    for color in &colors.visualizer_gradient {
+        check("colors.visualizer_gradient", validate_hex_color(color));
+    }
+
+    // Check fonts
+    check(
+        "fonts.proportional",
+        require(
+            !skin.fonts.proportional.is_empty(),
+            "Proportional font cannot be empty".into(),
+        ),
+    );
+    check(
+        "fonts.monospace",
+        require(
+            !skin.fonts.monospace.is_empty(),
+            "Monospace font cannot be empty".into(),
+        ),
+    );
+
+    // Check metrics are positive
+    let metrics = &skin.metrics;
+    let non_negative = [
+        ("window_rounding", metrics.window_rounding),
+        ("widget_rounding", metrics.widget_rounding),
+        ("window_padding", metrics.window_padding),
+    ];
+    for (name, value) in non_negative {
+        let message = format!("{} must be non-negative", name);
+        check(&format!("metrics.{}", name), require(value >= 0.0, message));
+    }
+    let positive = [
+        ("scrollbar_width", metrics.scrollbar_width),
+        ("body_text_size", metrics.body_text_size),
+        ("heading_text_size", metrics.heading_text_size),
+        ("timer_text_size", metrics.timer_text_size),
+        ("track_info_text_size", metrics.track_info_text_size),
+        ("playlist_text_size", metrics.playlist_text_size),
+        ("button_text_size", metrics.button_text_size),
+    ];
+    for (name, value) in positive {
+        let message = format!("{} must be positive", name);
+        check(&format!("metrics.{}", name), require(value > 0.0, message));
+    }
+
+    // Check the layout fits on screen
+    let layout = &skin.layout;
+    let [width, height] = layout.window_min_size;
+    check(
+        "layout.window_min_size",
+        require(
+            width > 0.0 && height > 0.0,
+            "window_min_size must be positive".into(),
+        ),
+    );
+    let positive = [
+        ("player_height", layout.player_height),
+        ("equalizer_height", layout.equalizer_height),
+    ];
+    for (name, value) in positive {
+        let message = format!("{} must be positive", name);
+        check(&format!("layout.{}", name), require(value > 0.0, message));
+    }
+    check(
+        "layout.item_spacing",
+        require(
+            layout.item_spacing >= 0.0,
+            "item_spacing must be non-negative".into(),
+        ),
+    );
+
+    // Check fonts and images exist
+    for (key, asset) in skin.assets() {
+        check(&key, validate_asset(asset, &asset_exists));
+    }
+
+    problems
+}
+
+/// Validates that an asset is a file inside the skin directory.
+fn validate_asset(asset: &Path, asset_exists: impl Fn(&Path) -> bool) -> Result<(), String> {
+    let escapes = asset
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return Err(format!(
+            "Asset path must be relative to the skin directory: {:?}",
+            asset
+        ));
+    }
+    if !asset_exists(asset) {
+        return Err(format!("Asset not found: {:?}", asset));
+    }
+    Ok(())
+}
+
+/// Validates that a string is a valid hex color.
+/// Accepts formats: #RGB, #RRGGBB, #RRGGBBAA
+pub fn validate_hex_color(color: &str) -> Result<(), String> {
+    let Some(hex_part) = color.strip_prefix('#') else {
+        return Err(format!("Color must start with '#': {}", color));
+    };
+
+    if hex_part.len() != 3 && hex_part.len() != 6 && hex_part.len() != 8 {
+        return Err(format!(
+            "Color must be #RGB, #RRGGBB, or #RRGGBBAA: {}",
+            color
+        ));
+    }
+
+    if !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Color contains invalid hex digits: {}", color));
+    }
+
+    Ok(())
+}
+
+/// Span of the value at a dotted key, or of the key itself.
+fn key_span(table: &dyn TableLike, key: &str) -> Option<Range<usize>> {
+    let (first, rest) = match key.split_once('.') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (key, None),
+    };
+    let (key, item) = table.get_key_value(first)?;
+    match rest {
+        Some(rest) => key_span(item.as_table_like()?, rest),
+        None => item.span().or_else(|| key.span()),
+    }
+}
+
+/// Line and column of a byte offset, both starting at 1.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oneamp-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_default_skin_creation() {
+        let skin = Skin::default_builtin();
+        assert_eq!(skin.metadata.name, "OneAmp Dark");
+        assert!(skin.colors.dark_mode);
+        assert!(validate_skin(&skin, |_| false).is_empty());
+    }
+
+    #[test]
+    fn test_colors_default() {
+        let colors = Colors::default();
+        assert_eq!(colors.background, "#0a0a0a");
+        assert_eq!(colors.text, "#ffffff");
+    }
+
+    #[test]
+    fn test_fonts_default() {
+        let fonts = Fonts::default();
+        assert_eq!(fonts.proportional, "Arial");
+        assert_eq!(fonts.monospace, "Courier New");
+    }
+
+    #[test]
+    fn test_metrics_default() {
+        let metrics = Metrics::default();
+        assert_eq!(metrics.window_rounding, 4.0);
+        assert_eq!(metrics.timer_text_size, 48.0);
+    }
+
+    #[test]
+    fn test_color_fallbacks() {
+        let mut colors = Colors::default();
+        assert_eq!(colors.display_bg(), colors.panel_fill);
+        assert_eq!(colors.eq_fill(), colors.accent);
+        assert_eq!(
+            colors.visualizer_gradient(),
+            [colors.accent.as_str(), &colors.warning, &colors.error]
+        );
+
+        colors.display_accent = Some("#123456".to_string());
+        colors.visualizer_gradient = vec!["#000000".to_string(), "#ffffff".to_string()];
+        assert_eq!(colors.display_accent(), "#123456");
+        assert_eq!(colors.progress_fill(), colors.accent);
+        assert_eq!(colors.visualizer_gradient(), ["#000000", "#ffffff"]);
+
+        // Skins without the newer colors and layout still load
+        let content = toml::to_string(&Skin::default_builtin())
+            .unwrap()
+            .replace("track_info_text_size = 20.0\n", "")
+            .replace("\n[layout]\n", "\n[unused]\n");
+        let skin = parse_skin(&content, |_| false).unwrap();
+        assert_eq!(skin.metrics.track_info_text_size, 20.0);
+        assert_eq!(skin.layout.window_min_size, [700.0, 600.0]);
+
+        let content = content.replacen(
+            "[colors]\n",
+            "[colors]\neq_slider = \"grey\"\nvisualizer_gradient = [\"#fff\", \"red\"]\n",
+            1,
+        );
+        let problems = parse_skin(&content, |_| false).unwrap_err();
+        let keys: Vec<_> = problems.iter().map(|p| p.key.as_deref().unwrap()).collect();
+        assert_eq!(keys, ["colors.eq_slider", "colors.visualizer_gradient"]);
+    }
+
+    #[test]
+    fn test_images_and_bundled_fonts() {
+        let mut skin = Skin::default_builtin();
+        assert!(skin.asset_paths().is_empty());
+
+        skin.fonts = toml::from_str(
+            r#"
+            proportional = "Arial"
+            monospace = "Courier New"
+            timer_font = "fonts/digital.ttf"
+            "#,
+        )
+        .unwrap();
+        skin.images = toml::from_str(
+            r#"
+            [buttons.play]
+            normal = "play.png"
+            pressed = "play_down.png"
+
+            [background]
+            image = "bg.png"
+            borders = [4, 8, 4, 8]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            skin.images.background.as_ref().unwrap().borders,
+            [4, 8, 4, 8]
+        );
+        assert!(skin.images.display.is_none());
+        assert_eq!(
+            skin.asset_paths(),
+            ["fonts/digital.ttf", "play.png", "play_down.png", "bg.png"].map(Path::new)
+        );
+    }
+
+    #[test]
+    fn test_validate_hex_color_valid() {
+        assert!(validate_hex_color("#fff").is_ok());
+        assert!(validate_hex_color("#ffffff").is_ok());
+        assert!(validate_hex_color("#ffffff80").is_ok());
+    }
+
+    #[test]
+    fn test_validate_hex_color_invalid() {
+        assert!(validate_hex_color("ffffff").is_err()); // Missing #
+        assert!(validate_hex_color("#ff").is_err()); // Too short
+        assert!(validate_hex_color("#gggggg").is_err()); // Invalid hex
+    }
+
+    #[test]
+    fn test_problems_with_locations() {
+        let mut content = toml::to_string(&Skin::default_builtin()).unwrap();
+        content = content
+            .replace("accent = \"#00d4ff\"", "accent = \"blue\"")
+            .replace("scrollbar_width = 8.0", "scrollbar_width = 0.0");
+        content.push_str("\n[images.display]\nimage = \"lcd.png\"\n");
+
+        let problems = parse_skin(&content, |_| false).unwrap_err();
+        let keys: Vec<_> = problems.iter().map(|p| p.key.as_deref().unwrap()).collect();
+        assert_eq!(
+            keys,
+            [
+                "colors.accent",
+                "metrics.scrollbar_width",
+                "images.display.image"
+            ]
+        );
+
+        let line_of = |text: &str| {
+            content
+                .lines()
+                .position(|line| line.contains(text))
+                .unwrap()
+                + 1
+        };
+        assert_eq!(problems[0].location, Some((line_of("accent ="), 10)));
+        assert_eq!(problems[1].location, Some((line_of("scrollbar_width"), 19)));
+        assert_eq!(
+            problems[2].to_string(),
+            format!(
+                "{}:9: images.display.image: Asset not found: \"lcd.png\"",
+                line_of("lcd.png")
+            )
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let problems = parse_skin("[metadata]\nname = \"x\"\nauthor = \n", |_| true).unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].key, None);
+        assert_eq!(problems[0].location.map(|(line, _)| line), Some(3));
+    }
+
+    #[test]
+    fn test_load_skin_assets() {
+        let dir = temp_dir("skin-assets");
+        fs::create_dir_all(dir.join("images")).unwrap();
+        let mut skin = Skin::default_builtin();
+        skin.images.buttons.stop = Some(StateImages {
+            normal: "images/stop.png".into(),
+            hover: Some("images/stop_hover.png".into()),
+            pressed: None,
+        });
+        fs::write(dir.join(SKIN_FILE), toml::to_string(&skin).unwrap()).unwrap();
+        fs::write(dir.join("images").join("stop.png"), b"png").unwrap();
+
+        let error = load_skin(&dir).unwrap_err();
+        assert_eq!(error.path, dir.join(SKIN_FILE));
+        assert_eq!(error.problems.len(), 1);
+        assert!(
+            error.to_string().contains("images/stop_hover.png"),
+            "{}",
+            error
+        );
+        fs::write(dir.join("images").join("stop_hover.png"), b"png").unwrap();
+        let skin = load_skin(&dir).unwrap();
+        assert!(skin.images.buttons.stop.is_some());
+        assert_eq!(skin.path, dir);
+
+        let exists = |asset: &Path| dir.join(asset).is_file();
+        assert!(validate_asset(Path::new("./images/stop.png"), exists).is_ok());
+        assert!(validate_asset(Path::new("../skin.toml"), exists).is_err());
+        assert!(validate_asset(&dir.join(SKIN_FILE), exists).is_err());
+        assert!(validate_asset(Path::new("images"), exists).is_err());
+
+        let missing = load_skin(&dir.join("images")).unwrap_err();
+        assert_eq!(missing.problems, [Problem::new("skin.toml not found")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_skin_archive() {
+        let dir = temp_dir("skin-archive");
+        let mut skin = Skin::default_builtin();
+        skin.metadata.name = "Zipped".to_string();
+        skin.fonts.timer_font = Some("./fonts/lcd.ttf".into());
+        let skin_toml = toml::to_string(&skin).unwrap();
+
+        let write = |name: &str, files: &[(&str, &[u8])]| {
+            let path = dir.join(name);
+            let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+            for (name, data) in files {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                zip.write_all(data).unwrap();
+            }
+            zip.finish().unwrap();
+            path
+        };
+
+        let path = write(
+            "nested.zip",
+            &[
+                ("Zipped/skin.toml", skin_toml.as_bytes()),
+                ("Zipped/fonts/lcd.ttf", b"ttf"),
+            ],
+        );
+        let loaded = load_skin_archive(&path).unwrap();
+        assert_eq!(loaded.metadata.name, "Zipped");
+        assert_eq!(loaded.path, path);
+
+        let path = write("flat.zip", &[("skin.toml", skin_toml.as_bytes())]);
+        let error = load_skin_archive(&path).unwrap_err();
+        assert_eq!(error.path, path.join(SKIN_FILE));
+        assert_eq!(error.problems[0].key.as_deref(), Some("fonts.timer_font"));
+
+        let path = write("deep.zip", &[("a/b/skin.toml", skin_toml.as_bytes())]);
+        assert!(load_skin_archive(&path).is_err());
+        fs::write(dir.join("broken.zip"), b"not a zip").unwrap();
+        assert!(load_skin_archive(&dir.join("broken.zip")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.9.1", "1"), Ordering::Less);
+        assert_eq!(compare_versions("beta", "0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_pack_and_load_package() {
+        let dir = temp_dir("skin-package");
+        let mut skin = Skin::default_builtin();
+        skin.metadata.name = "Packed".to_string();
+        skin.images.display = Some(NineSlice {
+            image: "images/lcd.png".into(),
+            borders: [2; 4],
+        });
+        fs::create_dir_all(dir.join("images")).unwrap();
+        fs::write(dir.join(SKIN_FILE), toml::to_string(&skin).unwrap()).unwrap();
+        fs::write(dir.join("images").join("lcd.png"), b"lcd").unwrap();
+        fs::write(dir.join("preview.png"), b"preview").unwrap();
+        fs::write(dir.join("notes.txt"), b"left out").unwrap();
+
+        let output = dir.join("packed.oaskin");
+        let error = pack_skin(&dir, &output).unwrap_err();
+        assert_eq!(error.path, dir.join(MANIFEST_FILE));
+
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            "id = \"Packed Skin\"\nversion = \"1.x\"\npreview = \"missing.png\"\n",
+        )
+        .unwrap();
+        let error = pack_skin(&dir, &output).unwrap_err();
+        let keys: Vec<_> = error.problems.iter().map(|p| p.key.as_deref()).collect();
+        assert_eq!(keys, [Some("id"), Some("version"), Some("preview")]);
+        assert_eq!(error.problems[1].location, Some((2, 11)));
+
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            "id = \"packed\"\nversion = \"1.2\"\npreview = \"preview.png\"\n",
+        )
+        .unwrap();
+        let manifest = pack_skin(&dir, &output).unwrap();
+        assert_eq!(manifest.id, "packed");
+
+        let package = load_package(&output).unwrap();
+        assert_eq!(package.manifest, manifest);
+        assert_eq!(package.skin.metadata.name, "Packed");
+        assert_eq!(package.skin.path, output);
+        assert_eq!(
+            read_asset(&package.skin, Path::new("./images/lcd.png")).unwrap(),
+            b"lcd"
+        );
+        assert_eq!(
+            read_asset(&package.skin, Path::new("preview.png")).unwrap(),
+            b"preview"
+        );
+        assert!(read_asset(&package.skin, Path::new("notes.txt")).is_err());
+
+        // A zipped skin without a manifest isn't a package
+        let loaded = load_skin(&dir).unwrap();
+        assert_eq!(
+            read_asset(&loaded, Path::new("notes.txt")).unwrap(),
+            b"left out"
+        );
+        fs::remove_file(dir.join(MANIFEST_FILE)).unwrap();
+        let zipped = dir.join("zipped.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zipped).unwrap());
+        zip.start_file(SKIN_FILE, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(
+            toml::to_string(&Skin::default_builtin())
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        assert!(load_skin_archive(&zipped).is_ok());
+        let error = load_package(&zipped).unwrap_err();
+        assert_eq!(
+            error.problems[0].message,
+            "manifest.toml not found at the root of the package"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,369 @@
+// Theme Migration
+// Converts the `theme.toml` files of the old theme system, with RGB arrays
+// for colors and separate font and layout sections, into skins. The skin
+// sets every color the theme had, so it looks the same.
+
+use super::{
+    syntax_problem, validate_skin, Colors, Fonts, Images, Layout, Metadata, Metrics, Problem, Skin,
+    SkinError, SKIN_FILE,
+};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An old `theme.toml`. Missing values take the Winamp Modern theme's.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct Theme {
+    name: String,
+    colors: ThemeColors,
+    fonts: ThemeFonts,
+    layout: ThemeLayout,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "Winamp Modern".to_string(),
+            colors: ThemeColors::default(),
+            fonts: ThemeFonts::default(),
+            layout: ThemeLayout::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ThemeColors {
+    window_bg: [u8; 3],
+    panel_bg: [u8; 3],
+    border: [u8; 3],
+    display_bg: [u8; 3],
+    display_text: [u8; 3],
+    display_accent: [u8; 3],
+    button_normal: [u8; 3],
+    button_hovered: [u8; 3],
+    button_active: [u8; 3],
+    progress_bg: [u8; 3],
+    progress_fill: [u8; 3],
+    playlist_bg: [u8; 3],
+    playlist_text: [u8; 3],
+    playlist_selected: [u8; 3],
+    playlist_playing: [u8; 3],
+    eq_slider: [u8; 3],
+    eq_fill: [u8; 3],
+}
+
+impl Default for ThemeColors {
+    fn default() -> Self {
+        Self {
+            window_bg: [40, 45, 55],
+            panel_bg: [30, 35, 45],
+            border: [60, 65, 75],
+            display_bg: [15, 20, 35],
+            display_text: [100, 180, 255],
+            display_accent: [150, 220, 255],
+            button_normal: [70, 75, 85],
+            button_hovered: [90, 95, 105],
+            button_active: [110, 115, 125],
+            progress_bg: [50, 55, 65],
+            progress_fill: [100, 180, 255],
+            playlist_bg: [25, 30, 40],
+            playlist_text: [200, 200, 200],
+            playlist_selected: [60, 100, 150],
+            playlist_playing: [100, 180, 255],
+            eq_slider: [70, 75, 85],
+            eq_fill: [100, 180, 255],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ThemeFonts {
+    timer_size: f32,
+    track_info_size: f32,
+    playlist_size: f32,
+    button_size: f32,
+}
+
+impl Default for ThemeFonts {
+    fn default() -> Self {
+        Self {
+            timer_size: 48.0,
+            track_info_size: 20.0,
+            playlist_size: 14.0,
+            button_size: 14.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ThemeLayout {
+    window_min_width: f32,
+    window_min_height: f32,
+    player_height: f32,
+    equalizer_height: f32,
+    spacing: f32,
+    padding: f32,
+}
+
+impl Default for ThemeLayout {
+    fn default() -> Self {
+        Self {
+            window_min_width: 700.0,
+            window_min_height: 600.0,
+            player_height: 200.0,
+            equalizer_height: 220.0,
+            spacing: 16.0,
+            padding: 16.0,
+        }
+    }
+}
+
+impl Theme {
+    fn into_skin(self) -> Skin {
+        let colors = &self.colors;
+        let some = |rgb| Some(hex(rgb));
+        let default_colors = Colors::default();
+
+        Skin {
+            metadata: Metadata {
+                name: self.name.clone(),
+                description: "Migrated from theme.toml".to_string(),
+                ..Metadata::default()
+            },
+            colors: Colors {
+                dark_mode: is_dark(colors.panel_bg),
+                background: hex(colors.panel_bg),
+                text: hex(colors.display_text),
+                window_fill: hex(colors.window_bg),
+                window_stroke: hex(colors.border),
+                panel_fill: hex(colors.panel_bg),
+                widget_bg: hex(colors.button_normal),
+                widget_stroke: hex(colors.border),
+                hovered_widget_bg: hex(colors.button_hovered),
+                active_widget_bg: hex(colors.button_active),
+                inactive_widget_bg: hex(colors.button_normal),
+                accent: hex(colors.display_accent),
+                error: default_colors.error,
+                warning: default_colors.warning,
+                playlist_current_track: hex(colors.playlist_playing),
+                playlist_selected_bg: hex(colors.playlist_selected),
+                display_bg: some(colors.display_bg),
+                display_text: some(colors.display_text),
+                display_accent: some(colors.display_accent),
+                progress_bg: some(colors.progress_bg),
+                progress_fill: some(colors.progress_fill),
+                playlist_bg: some(colors.playlist_bg),
+                playlist_text: some(colors.playlist_text),
+                eq_slider: some(colors.eq_slider),
+                eq_fill: some(colors.eq_fill),
+                visualizer_gradient: Vec::new(),
+            },
+            fonts: Fonts::default(),
+            metrics: Metrics {
+                window_padding: self.layout.padding,
+                timer_text_size: self.fonts.timer_size,
+                track_info_text_size: self.fonts.track_info_size,
+                playlist_text_size: self.fonts.playlist_size,
+                button_text_size: self.fonts.button_size,
+                ..Metrics::default()
+            },
+            layout: Layout {
+                window_min_size: [self.layout.window_min_width, self.layout.window_min_height],
+                player_height: self.layout.player_height,
+                equalizer_height: self.layout.equalizer_height,
+                item_spacing: self.layout.spacing,
+            },
+            images: Images::default(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+/// Converts the content of an old `theme.toml` into a skin.
+///
+/// # Returns
+/// The skin, or every problem found: TOML errors with their location in
+/// `content`, or values the skin format doesn't accept, by skin key.
+pub fn migrate_theme(content: &str) -> Result<Skin, Vec<Problem>> {
+    let theme: Theme = toml::from_str(content).map_err(|e| vec![syntax_problem(content, &e)])?;
+    let skin = theme.into_skin();
+    let problems = validate_skin(&skin, |_| false);
+    if problems.is_empty() {
+        Ok(skin)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Converts an old `theme.toml` into a skin folder in `skins_dir`, named
+/// after the theme. An existing skin is never overwritten.
+///
+/// # Returns
+/// The new skin folder.
+pub fn migrate_theme_file(theme_file: &Path, skins_dir: &Path) -> Result<PathBuf, SkinError> {
+    let content = fs::read_to_string(theme_file)
+        .map_err(|e| SkinError::new(theme_file, format!("Failed to read theme: {}", e)))?;
+    let skin = migrate_theme(&content).map_err(|problems| SkinError {
+        path: theme_file.to_path_buf(),
+        problems,
+    })?;
+
+    let skin_dir = skins_dir.join(folder_name(&skin.metadata.name));
+    if skin_dir.join(SKIN_FILE).exists() {
+        return Err(SkinError::new(
+            theme_file,
+            format!("A skin already exists in {}", skin_dir.display()),
+        ));
+    }
+    let content = toml::to_string(&skin)
+        .map_err(|e| SkinError::new(theme_file, format!("Failed to convert theme: {}", e)))?;
+    fs::create_dir_all(&skin_dir)
+        .and_then(|()| fs::write(skin_dir.join(SKIN_FILE), content))
+        .map_err(|e| {
+            SkinError::new(
+                theme_file,
+                format!("Failed to write {}: {}", skin_dir.display(), e),
+            )
+        })?;
+    Ok(skin_dir)
+}
+
+/// A skin folder name: the lowercase letters and digits of a theme's name,
+/// with dashes in between words.
+fn folder_name(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        "migrated-theme".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Whether text should be light on a background, by its perceived brightness.
+fn is_dark([r, g, b]: [u8; 3]) -> bool {
+    u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114 < 128_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{load_skin, parse_skin};
+    use super::*;
+
+    /// The `theme.toml.example` the old theme system shipped
+    const EXAMPLE: &str = r#"
+name = "Winamp Modern"
+
+[colors]
+window_bg = [40, 45, 55]
+panel_bg = [30, 35, 45]
+border = [60, 65, 75]
+display_bg = [15, 20, 35]
+display_text = [100, 180, 255]
+display_accent = [150, 220, 255]
+button_normal = [70, 75, 85]
+button_hovered = [90, 95, 105]
+button_active = [110, 115, 125]
+progress_bg = [50, 55, 65]
+progress_fill = [100, 180, 255]
+playlist_bg = [25, 30, 40]
+playlist_text = [200, 200, 200]
+playlist_selected = [60, 100, 150]
+playlist_playing = [100, 180, 255]
+eq_slider = [70, 75, 85]
+eq_fill = [100, 180, 255]
+
+[fonts]
+timer_size = 32.0
+track_info_size = 14.0
+playlist_size = 13.0
+button_size = 12.0
+
+[layout]
+window_min_width = 600.0
+window_min_height = 500.0
+player_height = 150.0
+equalizer_height = 180.0
+spacing = 8.0
+padding = 10.0
+"#;
+
+    #[test]
+    fn test_migrate_theme() {
+        let skin = migrate_theme(EXAMPLE).unwrap();
+        assert_eq!(skin.metadata.name, "Winamp Modern");
+        assert!(skin.colors.dark_mode);
+        assert_eq!(skin.colors.window_fill, "#282d37");
+        assert_eq!(skin.colors.background, "#1e232d");
+        assert_eq!(skin.colors.display_bg(), "#0f1423");
+        assert_eq!(skin.colors.display_text(), "#64b4ff");
+        assert_eq!(skin.colors.playlist_current_track, "#64b4ff");
+        assert_eq!(skin.colors.playlist_selected_bg, "#3c6496");
+        assert_eq!(skin.colors.eq_slider(), "#464b55");
+        assert_eq!(skin.metrics.timer_text_size, 32.0);
+        assert_eq!(skin.metrics.playlist_text_size, 13.0);
+        assert_eq!(skin.metrics.window_padding, 10.0);
+        assert_eq!(skin.layout.window_min_size, [600.0, 500.0]);
+        assert_eq!(skin.layout.item_spacing, 8.0);
+
+        // The skin survives a round trip through skin.toml
+        let content = toml::to_string(&skin).unwrap();
+        let parsed = parse_skin(&content, |_| false).unwrap();
+        assert_eq!(parsed.colors.eq_fill(), "#64b4ff");
+        assert_eq!(parsed.layout.player_height, 150.0);
+    }
+
+    #[test]
+    fn test_migrate_partial_theme() {
+        // Missing values are the Winamp Modern theme's
+        let skin =
+            migrate_theme("name = \"Mine\"\n[colors]\npanel_bg = [240, 240, 240]\n").unwrap();
+        assert!(!skin.colors.dark_mode);
+        assert_eq!(skin.colors.background, "#f0f0f0");
+        assert_eq!(skin.colors.window_fill, "#282d37");
+        assert_eq!(skin.metrics.timer_text_size, 48.0);
+
+        let problems = migrate_theme("[colors]\nwindow_bg = [300, 0, 0]\n").unwrap_err();
+        assert_eq!(problems[0].location.map(|(line, _)| line), Some(2));
+        let problems = migrate_theme("[fonts]\ntimer_size = 0.0\n").unwrap_err();
+        assert_eq!(problems[0].key.as_deref(), Some("metrics.timer_text_size"));
+    }
+
+    #[test]
+    fn test_migrate_theme_file() {
+        let dir = std::env::temp_dir().join(format!("oneamp-theme-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let theme_file = dir.join("theme.toml");
+        fs::write(&theme_file, EXAMPLE).unwrap();
+
+        let skin_dir = migrate_theme_file(&theme_file, &dir.join("skins")).unwrap();
+        assert_eq!(skin_dir, dir.join("skins").join("winamp-modern"));
+        let skin = load_skin(&skin_dir).unwrap();
+        assert_eq!(skin.metadata.name, "Winamp Modern");
+        assert_eq!(skin.colors.progress_bg(), "#323741");
+
+        // Migrating again keeps the existing skin
+        assert!(migrate_theme_file(&theme_file, &dir.join("skins")).is_err());
+        assert!(migrate_theme_file(&dir.join("missing.toml"), &dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_folder_name() {
+        assert_eq!(folder_name("Winamp Modern"), "winamp-modern");
+        assert_eq!(folder_name("  Dark -- Blue! "), "dark-blue");
+        assert_eq!(folder_name("***"), "migrated-theme");
+    }
+}
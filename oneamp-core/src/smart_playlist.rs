@@ -0,0 +1,786 @@
+// Smart Playlists
+// Playlists defined by a rule over tags and play statistics, such as
+// `rating >= 4 AND NOT played in 30 days` or
+// `genre = Jazz ORDER BY random LIMIT 100`. Rules are kept as text so they
+// can be stored in config files and edited by hand.
+//
+//   rule      := [condition] [ORDER BY key [ASC|DESC]] [LIMIT n]
+//   condition := all (OR all)*
+//   all       := not (AND not)*
+//   not       := NOT not | ( condition ) | PLAYED IN n DAYS | field op value
+//
+// Text fields (title, artist, album, genre, path) compare without regard
+// to case with `=`, `!=`, `contains` and `starts_with`; numeric fields
+// (rating, plays, skips, duration) with `=`, `!=`, `<`, `<=`, `>`, `>=`.
+
+use crate::stats::PlayStats;
+use crate::TrackInfo;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Path,
+    /// Stars, 0 when unrated
+    Rating,
+    Plays,
+    Skips,
+    /// Seconds
+    Duration,
+    /// Only for ordering: most recent play
+    LastPlayed,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "title" => Self::Title,
+            "artist" => Self::Artist,
+            "album" => Self::Album,
+            "genre" => Self::Genre,
+            "path" | "file" => Self::Path,
+            "rating" | "stars" => Self::Rating,
+            "plays" | "playcount" | "play_count" => Self::Plays,
+            "skips" | "skipcount" | "skip_count" => Self::Skips,
+            "duration" | "length" => Self::Duration,
+            "last_played" | "lastplayed" => Self::LastPlayed,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Genre => "genre",
+            Self::Path => "path",
+            Self::Rating => "rating",
+            Self::Plays => "plays",
+            Self::Skips => "skips",
+            Self::Duration => "duration",
+            Self::LastPlayed => "last_played",
+        }
+    }
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            Self::Title | Self::Artist | Self::Album | Self::Genre | Self::Path
+        )
+    }
+
+    fn text<'a>(self, track: &'a TrackInfo) -> Option<std::borrow::Cow<'a, str>> {
+        let text = match self {
+            Self::Title => track.title.as_deref(),
+            Self::Artist => track.artist.as_deref(),
+            Self::Album => track.album.as_deref(),
+            Self::Genre => track.genre.as_deref(),
+            Self::Path => return Some(track.path.to_string_lossy()),
+            _ => None,
+        };
+        text.map(std::borrow::Cow::Borrowed)
+    }
+
+    fn number(self, track: &TrackInfo, stats: &PlayStats) -> Option<f64> {
+        match self {
+            Self::Rating => Some(f64::from(stats.rating(track))),
+            Self::Plays => Some(f64::from(stats.get(&track.path).play_count)),
+            Self::Skips => Some(f64::from(stats.get(&track.path).skip_count)),
+            Self::Duration => track.duration_secs.map(f64::from),
+            Self::LastPlayed => stats.get(&track.path).last_played.map(|at| at as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+    StartsWith,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Equals => "=",
+            Self::NotEquals => "!=",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Contains => "contains",
+            Self::StartsWith => "starts_with",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        field: Field,
+        operator: Operator,
+        value: String,
+    },
+    /// Last played less than this many days ago
+    PlayedWithin(u32),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn matches(&self, track: &TrackInfo, stats: &PlayStats, now: u64) -> bool {
+        match self {
+            Self::And(all) => all.iter().all(|c| c.matches(track, stats, now)),
+            Self::Or(any) => any.iter().any(|c| c.matches(track, stats, now)),
+            Self::Not(condition) => !condition.matches(track, stats, now),
+            Self::PlayedWithin(days) => stats
+                .get(&track.path)
+                .last_played
+                .is_some_and(|at| now.saturating_sub(at) < u64::from(*days) * SECS_PER_DAY),
+            Self::Compare {
+                field,
+                operator,
+                value,
+            } if field.is_text() => {
+                let Some(actual) = field.text(track) else {
+                    // A missing tag only satisfies "not equal"
+                    return *operator == Operator::NotEquals;
+                };
+                let (actual, expected) = (actual.to_lowercase(), value.to_lowercase());
+                match operator {
+                    Operator::Equals => actual == expected,
+                    Operator::NotEquals => actual != expected,
+                    Operator::Contains => actual.contains(&expected),
+                    Operator::StartsWith => actual.starts_with(&expected),
+                    Operator::Less => actual < expected,
+                    Operator::LessOrEqual => actual <= expected,
+                    Operator::Greater => actual > expected,
+                    Operator::GreaterOrEqual => actual >= expected,
+                }
+            }
+            Self::Compare {
+                field,
+                operator,
+                value,
+            } => {
+                let (Some(actual), Ok(expected)) =
+                    (field.number(track, stats), value.parse::<f64>())
+                else {
+                    return *operator == Operator::NotEquals;
+                };
+                match operator {
+                    Operator::Equals => actual == expected,
+                    Operator::NotEquals => actual != expected,
+                    Operator::Less => actual < expected,
+                    Operator::LessOrEqual => actual <= expected,
+                    Operator::Greater => actual > expected,
+                    Operator::GreaterOrEqual => actual >= expected,
+                    Operator::Contains | Operator::StartsWith => false,
+                }
+            }
+        }
+    }
+
+    fn uses_time(&self) -> bool {
+        match self {
+            Self::PlayedWithin(_) => true,
+            Self::And(all) | Self::Or(all) => all.iter().any(Self::uses_time),
+            Self::Not(condition) => condition.uses_time(),
+            Self::Compare { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, all: &[Condition], word: &str| {
+            for (i, condition) in all.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", word)?;
+                }
+                match condition {
+                    Self::And(_) | Self::Or(_) => write!(f, "({})", condition)?,
+                    _ => write!(f, "{}", condition)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Self::And(all) => join(f, all, "AND"),
+            Self::Or(any) => join(f, any, "OR"),
+            Self::Not(condition) => match **condition {
+                Self::And(_) | Self::Or(_) => write!(f, "NOT ({})", condition),
+                _ => write!(f, "NOT {}", condition),
+            },
+            Self::PlayedWithin(days) => write!(f, "played in {} days", days),
+            Self::Compare {
+                field,
+                operator,
+                value,
+            } => {
+                let plain = !value.is_empty()
+                    && value
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'));
+                if plain && Keyword::parse(value).is_none() {
+                    write!(f, "{} {} {}", field.name(), operator.symbol(), value)
+                } else {
+                    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                    write!(f, "{} {} \"{}\"", field.name(), operator.symbol(), escaped)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Shuffled, the same way for the same seed
+    Random,
+    Ascending(Field),
+    Descending(Field),
+}
+
+/// A parsed smart playlist rule
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Rule {
+    /// `None` matches every track
+    pub condition: Option<Condition>,
+    /// `None` keeps the library order
+    pub order: Option<Order>,
+    pub limit: Option<usize>,
+}
+
+impl Rule {
+    /// Paths of the matching tracks, ordered and limited. `now` is the
+    /// Unix time "played in" conditions count from; `seed` picks the
+    /// random order, which stays the same as tracks come and go.
+    pub fn evaluate<'a>(
+        &self,
+        tracks: impl IntoIterator<Item = &'a TrackInfo>,
+        stats: &PlayStats,
+        now: u64,
+        seed: u64,
+    ) -> Vec<PathBuf> {
+        let mut matching: Vec<&TrackInfo> = tracks
+            .into_iter()
+            .filter(|track| {
+                self.condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.matches(track, stats, now))
+            })
+            .collect();
+
+        match self.order {
+            None => {}
+            Some(Order::Random) => {
+                matching.sort_by_cached_key(|track| shuffle_key(seed, &track.path))
+            }
+            Some(Order::Ascending(field) | Order::Descending(field)) => {
+                matching.sort_by(|a, b| {
+                    let ordering = if field.is_text() {
+                        let key =
+                            |track: &TrackInfo| field.text(track).map(|text| text.to_lowercase());
+                        key(a).cmp(&key(b))
+                    } else {
+                        let key = |track: &TrackInfo| field.number(track, stats);
+                        key(a)
+                            .partial_cmp(&key(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    };
+                    match self.order {
+                        Some(Order::Descending(_)) => ordering.reverse(),
+                        _ => ordering,
+                    }
+                });
+            }
+        }
+
+        matching
+            .into_iter()
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|track| track.path.clone())
+            .collect()
+    }
+
+    /// Whether the result changes with time alone ("played in" conditions),
+    /// so it should be refreshed now and then
+    pub fn uses_time(&self) -> bool {
+        self.condition.as_ref().is_some_and(Condition::uses_time)
+    }
+}
+
+fn shuffle_key(seed: u64, path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let mut rule = Rule::default();
+        if !parser.at_end()
+            && !parser.at_keyword(Keyword::Order)
+            && !parser.at_keyword(Keyword::Limit)
+        {
+            rule.condition = Some(parser.condition()?);
+        }
+        if parser.eat_keyword(Keyword::Order) {
+            if !parser.eat_keyword(Keyword::By) {
+                return Err("Expected BY after ORDER".to_string());
+            }
+            let name = parser.word("a field to order by")?;
+            rule.order = Some(if name.eq_ignore_ascii_case("random") {
+                Order::Random
+            } else {
+                let field =
+                    Field::parse(&name).ok_or_else(|| format!("Unknown field '{}'", name))?;
+                if parser.eat_keyword(Keyword::Desc) {
+                    Order::Descending(field)
+                } else {
+                    parser.eat_keyword(Keyword::Asc);
+                    Order::Ascending(field)
+                }
+            });
+        }
+        if parser.eat_keyword(Keyword::Limit) {
+            let count = parser.word("a number after LIMIT")?;
+            rule.limit = Some(
+                count
+                    .parse()
+                    .map_err(|_| format!("Invalid LIMIT '{}'", count))?,
+            );
+        }
+        match parser.peek() {
+            None => Ok(rule),
+            Some(token) => Err(format!("Unexpected '{}'", token)),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(condition) = &self.condition {
+            parts.push(condition.to_string());
+        }
+        match self.order {
+            Some(Order::Random) => parts.push("ORDER BY random".to_string()),
+            Some(Order::Ascending(field)) => parts.push(format!("ORDER BY {}", field.name())),
+            Some(Order::Descending(field)) => parts.push(format!("ORDER BY {} DESC", field.name())),
+            None => {}
+        }
+        if let Some(limit) = self.limit {
+            parts.push(format!("LIMIT {}", limit));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => f.write_str(word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keyword {
+    And,
+    Or,
+    Not,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Limit,
+    Played,
+    In,
+}
+
+impl Keyword {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word.to_ascii_uppercase().as_str() {
+            "AND" => Self::And,
+            "OR" => Self::Or,
+            "NOT" => Self::Not,
+            "ORDER" => Self::Order,
+            "BY" => Self::By,
+            "ASC" => Self::Asc,
+            "DESC" => Self::Desc,
+            "LIMIT" => Self::Limit,
+            "PLAYED" => Self::Played,
+            "IN" => Self::In,
+            _ => return None,
+        })
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(Token::Symbol(if c == '(' { "(" } else { ")" }));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let equals = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Symbol(match (c, equals) {
+                    ('=', _) => "=",
+                    ('!', true) => "!=",
+                    ('<', false) => "<",
+                    ('<', true) => "<=",
+                    ('>', false) => ">",
+                    ('>', true) => ">=",
+                    _ => return Err("Expected '!='".to_string()),
+                }));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some(end) if end == c => break,
+                        Some(other) => value.push(other),
+                        None => return Err("Closing quote expected".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|&c| {
+                    !c.is_whitespace() && !matches!(c, '(' | ')' | '=' | '!' | '<' | '>' | '"')
+                }) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser for rule conditions
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn condition(&mut self) -> Result<Condition, String> {
+        let mut any = vec![self.all()?];
+        while self.eat_keyword(Keyword::Or) {
+            any.push(self.all()?);
+        }
+        Ok(match any.len() {
+            1 => any.pop().unwrap(),
+            _ => Condition::Or(any),
+        })
+    }
+
+    fn all(&mut self) -> Result<Condition, String> {
+        let mut all = vec![self.not()?];
+        while self.eat_keyword(Keyword::And) {
+            all.push(self.not()?);
+        }
+        Ok(match all.len() {
+            1 => all.pop().unwrap(),
+            _ => Condition::And(all),
+        })
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.eat_keyword(Keyword::Not) {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.eat_symbol("(") {
+            let condition = self.condition()?;
+            if !self.eat_symbol(")") {
+                return Err("')' expected".to_string());
+            }
+            return Ok(condition);
+        }
+        if self.eat_keyword(Keyword::Played) {
+            if !self.eat_keyword(Keyword::In) {
+                return Err("Expected IN after PLAYED".to_string());
+            }
+            // "played in 30 days", "played in the last 2 weeks"
+            let mut count = self.word("a number of days")?;
+            for filler in ["the", "last"] {
+                if count.eq_ignore_ascii_case(filler) {
+                    count = self.word("a number of days")?;
+                }
+            }
+            let count: u32 = count
+                .parse()
+                .map_err(|_| format!("Invalid number of days '{}'", count))?;
+            let unit = self.word("days or weeks")?.to_ascii_lowercase();
+            let days = match unit.as_str() {
+                "day" | "days" => count,
+                "week" | "weeks" => count * 7,
+                _ => return Err(format!("Expected days or weeks, found '{}'", unit)),
+            };
+            return Ok(Condition::PlayedWithin(days));
+        }
+
+        let name = self.word("a field name")?;
+        let field = Field::parse(&name).ok_or_else(|| format!("Unknown field '{}'", name))?;
+        if field == Field::LastPlayed {
+            return Err("Use 'played in N days' to filter on last_played".to_string());
+        }
+        let operator = match self.next() {
+            Some(Token::Symbol("=")) => Operator::Equals,
+            Some(Token::Symbol("!=")) => Operator::NotEquals,
+            Some(Token::Symbol("<")) => Operator::Less,
+            Some(Token::Symbol("<=")) => Operator::LessOrEqual,
+            Some(Token::Symbol(">")) => Operator::Greater,
+            Some(Token::Symbol(">=")) => Operator::GreaterOrEqual,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("contains") => Operator::Contains,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("starts_with") => {
+                Operator::StartsWith
+            }
+            other => {
+                return Err(match other {
+                    Some(token) => format!("Unknown operator '{}' after {}", token, name),
+                    None => format!("Expected an operator after {}", name),
+                })
+            }
+        };
+        if !field.is_text() && matches!(operator, Operator::Contains | Operator::StartsWith) {
+            return Err(format!(
+                "{} is a number and can't use {}",
+                name,
+                operator.symbol()
+            ));
+        }
+        let value = match self.next() {
+            Some(Token::Quoted(value)) => value,
+            Some(Token::Word(value)) if Keyword::parse(&value).is_none() => value,
+            _ => {
+                return Err(format!(
+                    "Expected a value after {} {}",
+                    name,
+                    operator.symbol()
+                ))
+            }
+        };
+        if !field.is_text() && value.parse::<f64>().is_err() {
+            return Err(format!("{} needs a number, found '{}'", name, value));
+        }
+        Ok(Condition::Compare {
+            field,
+            operator,
+            value,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn at_keyword(&self, keyword: Keyword) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if Keyword::parse(word) == Some(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// A plain or quoted word
+    fn word(&mut self, expected: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word) | Token::Quoted(word)) => Ok(word),
+            Some(token) => Err(format!("Expected {}, found '{}'", expected, token)),
+            None => Err(format!("Expected {}", expected)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn track(path: &str, artist: &str, genre: Option<&str>, duration: f32) -> TrackInfo {
+        TrackInfo {
+            path: PathBuf::from(path),
+            title: Some(format!("Title {}", path)),
+            artist: Some(artist.to_string()),
+            genre: genre.map(String::from),
+            duration_secs: Some(duration),
+            ..Default::default()
+        }
+    }
+
+    fn library() -> (Vec<TrackInfo>, PlayStats) {
+        let tracks = vec![
+            track("/a.flac", "Miles Davis", Some("Jazz"), 540.0),
+            track("/b.flac", "Coltrane", Some("jazz"), 300.0),
+            track("/c.flac", "Daft Punk", Some("Electronic"), 200.0),
+            track("/d.flac", "Unknown", None, 100.0),
+        ];
+        let mut stats = PlayStats::default();
+        stats.set_rating(Path::new("/a.flac"), 5);
+        stats.set_rating(Path::new("/b.flac"), 4);
+        stats.set_rating(Path::new("/c.flac"), 4);
+        stats.record_play(Path::new("/a.flac"), NOW - 3 * SECS_PER_DAY);
+        stats.record_play(Path::new("/a.flac"), NOW - 2 * SECS_PER_DAY);
+        stats.record_play(Path::new("/c.flac"), NOW - 60 * SECS_PER_DAY);
+        (tracks, stats)
+    }
+
+    fn run(rule: &str) -> Vec<String> {
+        let (tracks, stats) = library();
+        let rule: Rule = rule.parse().unwrap();
+        rule.evaluate(&tracks, &stats, NOW, 7)
+            .into_iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(
+            run("rating >= 4 AND NOT played in 30 days"),
+            ["/b.flac", "/c.flac"]
+        );
+        assert_eq!(run("genre = Jazz"), ["/a.flac", "/b.flac"]);
+        assert_eq!(run("genre != jazz"), ["/c.flac", "/d.flac"]);
+        assert_eq!(
+            run("artist contains 'DAVIS' OR duration < 150"),
+            ["/a.flac", "/d.flac"]
+        );
+        assert_eq!(
+            run("(genre = jazz OR genre = electronic) AND NOT (plays > 1)"),
+            ["/b.flac", "/c.flac"]
+        );
+        assert_eq!(run("played in the last 1 week"), ["/a.flac"]);
+        assert_eq!(run(""), ["/a.flac", "/b.flac", "/c.flac", "/d.flac"]);
+    }
+
+    #[test]
+    fn test_order_and_limit() {
+        assert_eq!(
+            run("ORDER BY duration DESC LIMIT 2"),
+            ["/a.flac", "/b.flac"]
+        );
+        assert_eq!(
+            run("rating > 0 ORDER BY artist"),
+            ["/b.flac", "/c.flac", "/a.flac"]
+        );
+        assert_eq!(run("ORDER BY plays DESC LIMIT 1"), ["/a.flac"]);
+
+        // Random order is stable for a seed, also as the matches change
+        let all = run("ORDER BY random");
+        assert_eq!(all.len(), 4);
+        assert_eq!(all, run("order by RANDOM"));
+        let jazz = run("genre = jazz ORDER BY random LIMIT 100");
+        let expected: Vec<_> = all
+            .iter()
+            .filter(|p| ["/a.flac", "/b.flac"].contains(&p.as_str()))
+            .cloned()
+            .collect();
+        assert_eq!(jazz, expected);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (rule, error) in [
+            ("mood = happy", "Unknown field 'mood'"),
+            ("rating >=", "Expected a value after rating >="),
+            ("rating >= high", "rating needs a number, found 'high'"),
+            (
+                "plays contains 3",
+                "plays is a number and can't use contains",
+            ),
+            ("(genre = jazz", "')' expected"),
+            ("genre = 'jazz", "Closing quote expected"),
+            ("ORDER rating", "Expected BY after ORDER"),
+            ("LIMIT lots", "Invalid LIMIT 'lots'"),
+            ("genre = jazz rating = 5", "Unexpected 'rating'"),
+        ] {
+            assert_eq!(rule.parse::<Rule>().unwrap_err(), error, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for rule in [
+            "rating >= 4 AND NOT played in 30 days",
+            "genre = Jazz ORDER BY random LIMIT 100",
+            "(artist = \"Daft Punk\" OR title contains \"a \\\"b\\\"\") AND NOT (plays > 3 OR skips > 2) ORDER BY last_played DESC",
+            "LIMIT 5",
+        ] {
+            let parsed: Rule = rule.parse().unwrap();
+            assert_eq!(parsed.to_string(), rule);
+            assert_eq!(parsed.to_string().parse::<Rule>().unwrap(), parsed);
+        }
+        assert!("played in 30 days".parse::<Rule>().unwrap().uses_time());
+        assert!(!"rating = 5".parse::<Rule>().unwrap().uses_time());
+    }
+}
@@ -0,0 +1,104 @@
+// Spectrum Analyzer
+// FFT of the visualization samples, grouped into normalized and smoothed
+// bands. Shared by the desktop visualizer and the terminal UI.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Number of samples transformed per update
+pub const FFT_SIZE: usize = 512;
+
+/// Turns `AudioEvent::VisualizationData` samples into spectrum bands in 0..=1
+pub struct SpectrumAnalyzer {
+    bands: Vec<f32>,
+    fft_buffer: Vec<Complex<f32>>,
+    fft_planner: FftPlanner<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// Create an analyzer with `band_count` bands (at most `FFT_SIZE / 2`)
+    pub fn new(band_count: usize) -> Self {
+        Self {
+            bands: vec![0.0; band_count.clamp(1, FFT_SIZE / 2)],
+            fft_buffer: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            fft_planner: FftPlanner::new(),
+        }
+    }
+
+    /// Current band magnitudes, lowest frequency first
+    pub fn bands(&self) -> &[f32] {
+        &self.bands
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft_buffer.len()
+    }
+
+    /// Let the bands fall back to silence (e.g. while paused)
+    pub fn decay(&mut self) {
+        for band in &mut self.bands {
+            *band *= 0.7;
+        }
+    }
+
+    /// Compute the spectrum of new samples
+    pub fn update(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        // Prepare FFT buffer
+        let fft_size = self.fft_buffer.len();
+        for (i, buf) in self.fft_buffer.iter_mut().enumerate() {
+            buf.re = samples.get(i).copied().unwrap_or(0.0);
+            buf.im = 0.0;
+        }
+
+        // Perform FFT
+        let fft = self.fft_planner.plan_fft_forward(fft_size);
+        fft.process(&mut self.fft_buffer);
+
+        // Convert FFT output to spectrum bands
+        let bins_per_band = (fft_size / 2) / self.bands.len();
+
+        for (i, band) in self.bands.iter_mut().enumerate() {
+            let start = i * bins_per_band;
+            let end = ((i + 1) * bins_per_band).min(fft_size / 2);
+
+            // Calculate magnitude for this band
+            let mut magnitude = 0.0;
+            for bin in start..end {
+                let complex = self.fft_buffer[bin];
+                magnitude += (complex.re * complex.re + complex.im * complex.im).sqrt();
+            }
+            magnitude /= (end - start) as f32;
+
+            // Normalize and apply smoothing
+            magnitude = (magnitude / 100.0).min(1.0);
+            *band = *band * 0.7 + magnitude * 0.3;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bands_are_normalized() {
+        let mut analyzer = SpectrumAnalyzer::new(16);
+        let samples: Vec<f32> = (0..FFT_SIZE).map(|i| (i as f32 * 0.3).sin()).collect();
+        for _ in 0..20 {
+            analyzer.update(&samples);
+        }
+        assert_eq!(analyzer.bands().len(), 16);
+        assert!(analyzer.bands().iter().all(|b| (0.0..=1.0).contains(b)));
+        // A 0.3 rad/sample sine lands in the low bands
+        let loudest = (0..16)
+            .max_by(|&a, &b| analyzer.bands()[a].total_cmp(&analyzer.bands()[b]))
+            .unwrap();
+        assert!(loudest < 4, "loudest band {}", loudest);
+
+        analyzer.decay();
+        assert!(analyzer.bands()[loudest] < 1.0);
+    }
+}
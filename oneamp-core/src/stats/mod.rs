@@ -0,0 +1,334 @@
+// Play Statistics
+// Play and skip counts, last-played times and star ratings of every track,
+// kept in a JSON file. `StatsTracker` turns the player's events into plays
+// and skips; `tags` copies ratings and play counts into the files.
+
+pub mod tags;
+
+use crate::{stream, AudioEvent, TrackInfo};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Highest star rating
+pub const MAX_RATING: u8 = 5;
+
+/// Position jumps larger than this are seeks, not listening time
+const MAX_POSITION_STEP_SECS: f32 = 2.0;
+
+/// What is known about a track's listening history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackStats {
+    pub play_count: u32,
+    pub skip_count: u32,
+    /// When the track was last played, in seconds since the Unix epoch
+    pub last_played: Option<u64>,
+    /// 1 to 5 stars, 0 when unrated
+    pub rating: u8,
+}
+
+/// Statistics of every track played or rated, by path
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayStats {
+    tracks: BTreeMap<PathBuf, TrackStats>,
+    /// Bumped on every change, so views can tell when to refresh
+    #[serde(skip)]
+    revision: u64,
+}
+
+impl PlayStats {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid play statistics {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string(self)?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path).with_context(|| format!("Failed to save {}", path.display()))
+    }
+
+    /// Statistics of a track, all zero if it was never played or rated
+    pub fn get(&self, path: &Path) -> TrackStats {
+        self.tracks.get(path).copied().unwrap_or_default()
+    }
+
+    /// Rating of a track, falling back to the one in its tags
+    pub fn rating(&self, track: &TrackInfo) -> u8 {
+        match self.get(&track.path).rating {
+            0 => track.rating.unwrap_or(0),
+            rating => rating,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &TrackStats)> {
+        self.tracks
+            .iter()
+            .map(|(path, stats)| (path.as_path(), stats))
+    }
+
+    /// Changes since the statistics were loaded
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Count a play finished, or listened to long enough, at `at`
+    pub fn record_play(&mut self, path: &Path, at: u64) -> TrackStats {
+        self.update(path, |stats| {
+            stats.play_count += 1;
+            stats.last_played = Some(at);
+        })
+    }
+
+    pub fn record_skip(&mut self, path: &Path) -> TrackStats {
+        self.update(path, |stats| stats.skip_count += 1)
+    }
+
+    /// Rate a track from 1 to 5 stars, or 0 to clear its rating
+    pub fn set_rating(&mut self, path: &Path, rating: u8) -> TrackStats {
+        self.update(path, |stats| stats.rating = rating.min(MAX_RATING))
+    }
+
+    /// Apply a play or skip reported by a `StatsTracker`
+    pub fn apply(&mut self, event: &StatsEvent) -> (PathBuf, TrackStats) {
+        match event {
+            StatsEvent::Played { path, at } => (path.clone(), self.record_play(path, *at)),
+            StatsEvent::Skipped(path) => (path.clone(), self.record_skip(path)),
+        }
+    }
+
+    fn update(&mut self, path: &Path, change: impl FnOnce(&mut TrackStats)) -> TrackStats {
+        let stats = self.tracks.entry(path.to_path_buf()).or_default();
+        change(stats);
+        let stats = *stats;
+        if stats == TrackStats::default() {
+            self.tracks.remove(path);
+        }
+        self.revision += 1;
+        stats
+    }
+}
+
+/// A play or skip seen by a `StatsTracker`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatsEvent {
+    /// Played to the end or past its middle; `at` is when it started
+    Played { path: PathBuf, at: u64 },
+    /// Another track was started before this one counted as played
+    Skipped(PathBuf),
+}
+
+struct CurrentTrack {
+    path: PathBuf,
+    started_at: u64,
+    duration_secs: Option<f32>,
+    played_secs: f32,
+    last_position: Option<f32>,
+    counted: bool,
+}
+
+/// Counts plays and skips from the player's events. A track counts as
+/// played once half of it has been heard or it reaches its end; starting
+/// another track before that, after hearing some of it, is a skip. Streams
+/// aren't counted.
+#[derive(Default)]
+pub struct StatsTracker {
+    current: Option<CurrentTrack>,
+    playing: bool,
+}
+
+impl StatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a player event, returning the play or skip it completed
+    pub fn handle_event(&mut self, event: &AudioEvent) -> Option<StatsEvent> {
+        match event {
+            AudioEvent::TrackLoaded(track) => {
+                let skipped = self
+                    .current
+                    .take()
+                    .filter(|current| !current.counted && current.played_secs > 0.0)
+                    .map(|current| StatsEvent::Skipped(current.path));
+                self.playing = false;
+                self.current = (!stream::is_stream_url(&track.path)).then(|| CurrentTrack {
+                    path: track.path.clone(),
+                    started_at: unix_time(),
+                    duration_secs: track.duration_secs.filter(|&secs| secs > 0.0),
+                    played_secs: 0.0,
+                    last_position: None,
+                    counted: false,
+                });
+                skipped
+            }
+            AudioEvent::Playing => {
+                self.playing = true;
+                None
+            }
+            AudioEvent::Paused | AudioEvent::Stopped | AudioEvent::Error(_) => {
+                self.playing = false;
+                if let Some(current) = &mut self.current {
+                    current.last_position = None;
+                }
+                None
+            }
+            AudioEvent::Position(position, duration) => {
+                let current = self.current.as_mut()?;
+                if current.duration_secs.is_none() && *duration > 0.0 {
+                    current.duration_secs = Some(*duration);
+                }
+                if let (true, Some(last)) = (self.playing, current.last_position) {
+                    let step = position - last;
+                    if step > 0.0 && step <= MAX_POSITION_STEP_SECS {
+                        current.played_secs += step;
+                    }
+                }
+                current.last_position = Some(*position);
+                let half = current.duration_secs? / 2.0;
+                (current.played_secs >= half).then(|| self.count())?
+            }
+            AudioEvent::Finished => {
+                let played = self.current.as_ref()?.played_secs > 0.0;
+                let event = played.then(|| self.count()).flatten();
+                self.current = None;
+                event
+            }
+            _ => None,
+        }
+    }
+
+    /// Count the current track as played, once
+    fn count(&mut self) -> Option<StatsEvent> {
+        let current = self.current.as_mut().filter(|current| !current.counted)?;
+        current.counted = true;
+        Some(StatsEvent::Played {
+            path: current.path.clone(),
+            at: current.started_at,
+        })
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(path: &str, duration: f32) -> AudioEvent {
+        AudioEvent::TrackLoaded(TrackInfo {
+            path: PathBuf::from(path),
+            duration_secs: Some(duration),
+            ..Default::default()
+        })
+    }
+
+    /// Play `secs` seconds in half-second steps from `from`
+    fn play(tracker: &mut StatsTracker, from: f32, secs: f32, total: f32) -> Vec<StatsEvent> {
+        (0..=(secs * 2.0) as usize)
+            .filter_map(|i| {
+                tracker.handle_event(&AudioEvent::Position(from + i as f32 * 0.5, total))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plays_and_skips() {
+        let mut tracker = StatsTracker::new();
+        tracker.handle_event(&loaded("/music/a.flac", 100.0));
+        tracker.handle_event(&AudioEvent::Playing);
+        assert!(play(&mut tracker, 0.0, 49.0, 100.0).is_empty());
+        let events = play(&mut tracker, 49.5, 30.0, 100.0);
+        assert!(
+            matches!(&events[..], [StatsEvent::Played { path, .. }] if path.ends_with("a.flac"))
+        );
+        // Counted once, and moving on after that isn't a skip
+        assert_eq!(tracker.handle_event(&loaded("/music/b.flac", 100.0)), None);
+
+        tracker.handle_event(&AudioEvent::Playing);
+        play(&mut tracker, 0.0, 10.0, 100.0);
+        assert_eq!(
+            tracker.handle_event(&loaded("/music/c.flac", 100.0)),
+            Some(StatsEvent::Skipped(PathBuf::from("/music/b.flac")))
+        );
+
+        // A track loaded but never played isn't skipped
+        assert_eq!(tracker.handle_event(&loaded("/music/d.flac", 100.0)), None);
+
+        // Seeking to the end still counts a play when it finishes
+        tracker.handle_event(&AudioEvent::Playing);
+        play(&mut tracker, 95.0, 5.0, 100.0);
+        assert!(matches!(
+            tracker.handle_event(&AudioEvent::Finished),
+            Some(StatsEvent::Played { .. })
+        ));
+    }
+
+    #[test]
+    fn test_streams_are_not_counted() {
+        let mut tracker = StatsTracker::new();
+        tracker.handle_event(&loaded("http://radio.example/live", 0.0));
+        tracker.handle_event(&AudioEvent::Playing);
+        assert!(play(&mut tracker, 0.0, 600.0, 0.0).is_empty());
+        assert_eq!(tracker.handle_event(&loaded("/music/a.flac", 100.0)), None);
+    }
+
+    #[test]
+    fn test_stats_persist() {
+        let path = std::env::temp_dir().join("oneamp_stats_test.json");
+        std::fs::remove_file(&path).ok();
+        let track = Path::new("/music/a.flac");
+
+        let mut stats = PlayStats::load(&path).unwrap();
+        stats.record_play(track, 1_700_000_000);
+        stats.record_play(track, 1_700_000_500);
+        stats.record_skip(track);
+        assert_eq!(stats.set_rating(track, 9).rating, MAX_RATING);
+        assert_eq!(stats.revision(), 4);
+        stats.save(&path).unwrap();
+
+        let mut loaded = PlayStats::load(&path).unwrap();
+        assert_eq!(
+            loaded.get(track),
+            TrackStats {
+                play_count: 2,
+                skip_count: 1,
+                last_played: Some(1_700_000_500),
+                rating: 5,
+            }
+        );
+        assert_eq!(loaded.get(Path::new("/other.flac")), TrackStats::default());
+
+        // Tag ratings are used until the track is rated here
+        let mut info = TrackInfo {
+            path: PathBuf::from("/music/b.flac"),
+            rating: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(loaded.rating(&info), 3);
+        loaded.set_rating(&info.path, 1);
+        assert_eq!(loaded.rating(&info), 1);
+        loaded.set_rating(&info.path, 0);
+        info.rating = None;
+        assert_eq!(loaded.rating(&info), 0);
+        assert_eq!(loaded.iter().count(), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
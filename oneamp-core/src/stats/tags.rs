@@ -0,0 +1,345 @@
+// Rating and Play Count Tags
+// Copies a track's rating and play count into its file so other players
+// see them: a `POPM` frame and `FMPS_Rating`/`FMPS_Playcount` TXXX frames in
+// the ID3v2 tag of MP3 and WAV files, `FMPS_RATING`/`FMPS_PLAYCOUNT`
+// comments in FLAC files. Other formats are left alone.
+
+use super::{TrackStats, MAX_RATING};
+use anyhow::{bail, Context, Result};
+use id3::frame::{ExtendedText, Popularimeter};
+use id3::{TagLike, Version};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// `POPM` owner our ratings are stored under
+const POPM_USER: &str = "oneamp";
+
+/// Room left after rewritten FLAC metadata, so later updates fit in place
+const FLAC_PADDING: usize = 4096;
+
+const BLOCK_PADDING: u8 = 1;
+const BLOCK_VORBIS_COMMENT: u8 = 4;
+const MAX_BLOCK_LEN: usize = (1 << 24) - 1;
+
+/// Whether `write_stats` supports the file's format
+pub fn can_write(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("mp3" | "wav" | "flac"))
+}
+
+/// Write the rating and play count of `stats` into the file's tags
+pub fn write_stats(path: &Path, stats: &TrackStats) -> Result<()> {
+    match extension(path).as_deref() {
+        Some("mp3" | "wav") => write_id3(path, stats),
+        Some("flac") => write_flac(path, stats),
+        _ => bail!("Can't write ratings to {}", path.display()),
+    }
+    .with_context(|| format!("Failed to tag {}", path.display()))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// `POPM` byte for a star rating, as Windows Media Player writes them
+pub fn popm_rating(stars: u8) -> u8 {
+    [0, 1, 64, 128, 196, 255][stars.min(MAX_RATING) as usize]
+}
+
+/// FMPS rating, a fraction between 0.0 and 1.0
+fn fmps_rating(stars: u8) -> String {
+    format!(
+        "{:.1}",
+        f32::from(stars.min(MAX_RATING)) / f32::from(MAX_RATING)
+    )
+}
+
+/// MP3 and WAV files, the latter with the tag in an `id3 ` chunk
+fn write_id3(path: &Path, stats: &TrackStats) -> Result<()> {
+    let mut tag = id3::no_tag_ok(id3::Tag::read_from_path(path))?.unwrap_or_default();
+    let version = match tag.version() {
+        Version::Id3v22 => Version::Id3v23,
+        version => version,
+    };
+
+    tag.add_frame(Popularimeter {
+        user: POPM_USER.to_string(),
+        rating: popm_rating(stats.rating),
+        counter: u64::from(stats.play_count),
+    });
+    for (name, value) in [
+        (
+            "FMPS_Rating",
+            (stats.rating > 0).then(|| fmps_rating(stats.rating)),
+        ),
+        (
+            "FMPS_Playcount",
+            (stats.play_count > 0).then(|| stats.play_count.to_string()),
+        ),
+    ] {
+        tag.remove_extended_text(Some(name), None);
+        if let Some(value) = value {
+            tag.add_frame(ExtendedText {
+                description: name.to_string(),
+                value,
+            });
+        }
+    }
+
+    // Tag a copy, then swap the files, so the file is never half written
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let written = std::fs::copy(path, &temp)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| {
+            tag.write_to_path(&temp, version)?;
+            File::open(&temp)?.sync_all()?;
+            std::fs::rename(&temp, path)?;
+            Ok(())
+        });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Update the FMPS comments of a FLAC file. The metadata is rewritten in
+/// place when it fits in the existing padding; otherwise the whole file is
+/// copied with new padding.
+fn write_flac(path: &Path, stats: &TrackStats) -> Result<()> {
+    let mut file = File::open(path)?;
+    let mut marker = [0u8; 4];
+    file.read_exact(&mut marker)?;
+    if &marker != b"fLaC" {
+        bail!("Not a FLAC file");
+    }
+
+    // Metadata blocks up to the first audio frame
+    let mut blocks = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header).context("Truncated metadata")?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0; len];
+        file.read_exact(&mut data).context("Truncated metadata")?;
+        blocks.push((header[0] & 0x7f, data));
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    let metadata_end = file.stream_position()? as usize;
+
+    let comments = blocks
+        .iter()
+        .position(|(kind, _)| *kind == BLOCK_VORBIS_COMMENT);
+    let (vendor, mut entries) = match comments {
+        Some(i) => parse_vorbis_comment(&blocks[i].1)?,
+        None => (format!("OneAmp {}", env!("CARGO_PKG_VERSION")), Vec::new()),
+    };
+    entries.retain(|entry| {
+        let name = entry.split('=').next().unwrap_or_default();
+        !name.eq_ignore_ascii_case("FMPS_RATING") && !name.eq_ignore_ascii_case("FMPS_PLAYCOUNT")
+    });
+    if stats.rating > 0 {
+        entries.push(format!("FMPS_RATING={}", fmps_rating(stats.rating)));
+    }
+    if stats.play_count > 0 {
+        entries.push(format!("FMPS_PLAYCOUNT={}", stats.play_count));
+    }
+    let comment_block = vorbis_comment(&vendor, &entries);
+    if comment_block.len() > MAX_BLOCK_LEN {
+        bail!("Vorbis comment too large");
+    }
+    match comments {
+        Some(i) => blocks[i].1 = comment_block,
+        // Right after STREAMINFO, which must come first
+        None => blocks.insert(1.min(blocks.len()), (BLOCK_VORBIS_COMMENT, comment_block)),
+    }
+    blocks.retain(|(kind, _)| *kind != BLOCK_PADDING);
+
+    let available = metadata_end - 4;
+    let needed: usize = blocks.iter().map(|(_, data)| 4 + data.len()).sum();
+    // Fits exactly, or with room for a padding block
+    let in_place = needed == available || needed + 4 <= available;
+    if needed != available {
+        let padding = match in_place {
+            true => available - needed - 4,
+            false => FLAC_PADDING,
+        };
+        blocks.push((BLOCK_PADDING, vec![0; padding]));
+    }
+
+    let mut metadata = b"fLaC".to_vec();
+    let count = blocks.len();
+    for (i, (kind, data)) in blocks.iter().enumerate() {
+        let last = if i + 1 == count { 0x80 } else { 0 };
+        metadata.push(kind | last);
+        metadata.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        metadata.extend_from_slice(data);
+    }
+
+    if in_place {
+        drop(file);
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.write_all(&metadata)?;
+        return Ok(());
+    }
+
+    // Copy the audio after the new metadata, then swap the files
+    let temp = path.with_extension("flac.tmp");
+    let written = File::create(&temp)
+        .and_then(|mut output| {
+            output.write_all(&metadata)?;
+            file.seek(SeekFrom::Start(metadata_end as u64))?;
+            std::io::copy(&mut file, &mut output)?;
+            output.sync_all()?;
+            drop(output);
+            std::fs::rename(&temp, path)
+        })
+        .map_err(anyhow::Error::from);
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Vendor string and `NAME=value` entries of a VORBIS_COMMENT block
+fn parse_vorbis_comment(mut data: &[u8]) -> Result<(String, Vec<String>)> {
+    let vendor = take_string(&mut data)?;
+    let count = take_u32(&mut data)?;
+    let entries = (0..count)
+        .map(|_| take_string(&mut data))
+        .collect::<Result<_>>()?;
+    Ok((vendor, entries))
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32> {
+    let (bytes, rest) = data
+        .split_first_chunk::<4>()
+        .context("Invalid Vorbis comment")?;
+    *data = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+fn take_string(data: &mut &[u8]) -> Result<String> {
+    let len = take_u32(data)? as usize;
+    if data.len() < len {
+        bail!("Invalid Vorbis comment");
+    }
+    let (text, rest) = data.split_at(len);
+    *data = rest;
+    Ok(String::from_utf8_lossy(text).into_owned())
+}
+
+fn vorbis_comment(vendor: &str, entries: &[String]) -> Vec<u8> {
+    let mut block = Vec::new();
+    block.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    block.extend_from_slice(vendor.as_bytes());
+    block.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries {
+        block.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        block.extend_from_slice(entry.as_bytes());
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{export, ExportFormat, ExportOptions};
+    use crate::test_wav::write_silent;
+    use crate::TrackInfo;
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oneamp_stats_tags_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_flac_comments() {
+        let dir = test_dir("flac");
+        let wav = dir.join("source.wav");
+        let flac = dir.join("track.flac");
+        write_silent(&wav, 8000);
+        let options = ExportOptions {
+            format: ExportFormat::Flac,
+            ..Default::default()
+        };
+        export(&wav, &flac, &options).unwrap();
+        let original = std::fs::metadata(&flac).unwrap().len();
+
+        let stats = TrackStats {
+            play_count: 3,
+            rating: 4,
+            ..Default::default()
+        };
+        write_stats(&flac, &stats).unwrap();
+        // No padding to spare: the file was rewritten with some
+        let rewritten = std::fs::metadata(&flac).unwrap().len();
+        assert!(rewritten > original + FLAC_PADDING as u64);
+        let track = TrackInfo::from_file(&flac).unwrap();
+        assert_eq!(track.rating, Some(4));
+        assert!((track.duration_secs.unwrap() - 1.0).abs() < 0.01);
+
+        // The next update fits in the padding
+        let stats = TrackStats { rating: 2, ..stats };
+        write_stats(&flac, &stats).unwrap();
+        assert_eq!(std::fs::metadata(&flac).unwrap().len(), rewritten);
+        let track = TrackInfo::from_file(&flac).unwrap();
+        assert_eq!(track.rating, Some(2));
+        assert!((track.duration_secs.unwrap() - 1.0).abs() < 0.01);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_id3_frames() {
+        let dir = test_dir("wav");
+        let wav = dir.join("track.wav");
+        write_silent(&wav, 8000);
+
+        let mut stats = TrackStats {
+            play_count: 7,
+            rating: 4,
+            ..Default::default()
+        };
+        write_stats(&wav, &stats).unwrap();
+        stats.rating = 0;
+        stats.play_count = 8;
+        write_stats(&wav, &stats).unwrap();
+
+        let tag = id3::Tag::read_from_path(&wav).unwrap();
+        let popm: Vec<_> = tag
+            .frames()
+            .filter_map(|frame| frame.content().popularimeter())
+            .collect();
+        assert_eq!(popm.len(), 1);
+        assert_eq!((popm[0].rating, popm[0].counter), (0, 8));
+        let texts: Vec<_> = tag
+            .extended_texts()
+            .map(|text| (text.description.as_str(), text.value.as_str()))
+            .collect();
+        assert_eq!(texts, [("FMPS_Playcount", "8")]);
+        assert!(TrackInfo::from_file(&wav).is_ok());
+        assert!(!dir.join("track.wav.tmp").exists());
+
+        assert!(!can_write(Path::new("a.ogg")));
+        assert!(write_stats(&dir.join("a.ogg"), &stats).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rating_scales() {
+        assert_eq!(popm_rating(0), 0);
+        assert_eq!(popm_rating(3), 128);
+        assert_eq!(popm_rating(9), 255);
+        assert_eq!(fmps_rating(4), "0.8");
+        assert_eq!(fmps_rating(5), "1.0");
+    }
+}
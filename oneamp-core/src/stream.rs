@@ -0,0 +1,964 @@
+// HTTP Streams
+// Internet radio and other progressive HTTP(S) streams (MP3, AAC, Ogg). A
+// background thread downloads into a bounded buffer, strips Shoutcast/Icecast
+// (ICY) metadata blocks and reconnects with backoff when the connection drops.
+// Stream URLs travel through playlists and `AudioCommand::Play` as paths.
+
+use crate::{playlist_file, TrackInfo};
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use symphonia::core::io::MediaSource;
+use ureq::http::{Response, StatusCode};
+use ureq::{Agent, Body};
+
+/// URL schemes played as streams
+pub const URL_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+/// Most audio held in memory ahead of the decoder
+const MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+/// Playback pauses to rebuffer below this, so the decoder never waits on the network
+const LOW_WATER_BYTES: usize = 8 * 1024;
+/// Largest read handed to the decoder, so the buffer level reflects what is left
+const MAX_READ_BYTES: usize = 4 * 1024;
+/// Bitrate assumed for the prebuffer size when the server doesn't send `icy-br`
+const DEFAULT_BITRATE_KBPS: u32 = 128;
+/// Remote playlists pointing to other playlists followed before giving up
+const MAX_PLAYLIST_DEPTH: usize = 3;
+/// Largest remote playlist read
+const MAX_PLAYLIST_BYTES: u64 = 64 * 1024;
+/// Content types of PLS and M3U playlists
+const PLAYLIST_TYPES: [&str; 5] = [
+    "audio/x-scpls",
+    "application/pls+xml",
+    "audio/x-mpegurl",
+    "audio/mpegurl",
+    "application/x-mpegurl",
+];
+
+/// Check whether a playlist entry is an HTTP(S) stream URL
+pub fn is_stream_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|url| {
+        URL_SCHEMES.iter().any(|scheme| {
+            url.len() > scheme.len()
+                && url.is_char_boundary(scheme.len())
+                && url[..scheme.len()].eq_ignore_ascii_case(scheme)
+        })
+    })
+}
+
+/// Buffering and reconnection settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamOptions {
+    /// Seconds of audio buffered before playback starts and after an underrun
+    pub prebuffer_secs: f32,
+    /// Failed reconnection attempts in a row before playback stops
+    pub reconnect_attempts: u32,
+    /// Delay before the first reconnection attempt; doubles with each attempt
+    pub reconnect_delay: Duration,
+    pub max_reconnect_delay: Duration,
+    /// Reconnect when no data arrived for this long
+    pub stall_timeout: Duration,
+    /// Timeout for connecting and receiving the response headers
+    pub connect_timeout: Duration,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            prebuffer_secs: 2.0,
+            reconnect_attempts: 5,
+            reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
+            stall_timeout: Duration::from_secs(15),
+            connect_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// What the server told about a stream
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamInfo {
+    /// The URL that was opened, before following playlists
+    pub url: String,
+    /// Station name (`icy-name`)
+    pub name: Option<String>,
+    pub genre: Option<String>,
+    pub description: Option<String>,
+    pub content_type: Option<String>,
+    pub bitrate_kbps: Option<u32>,
+    /// Audio bytes between ICY metadata blocks
+    pub metaint: Option<usize>,
+    /// Size of a finite resource; radio streams have none
+    pub content_length: Option<u64>,
+}
+
+impl StreamInfo {
+    fn from_response(url: &str, response: &Response<Body>) -> Self {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            url: url.to_string(),
+            name: header("icy-name"),
+            genre: header("icy-genre"),
+            description: header("icy-description"),
+            content_type: header("content-type"),
+            // Some servers send "128,128"
+            bitrate_kbps: header("icy-br")
+                .and_then(|br| br.split(',').next().and_then(|br| br.trim().parse().ok())),
+            metaint: header("icy-metaint").and_then(|metaint| metaint.parse().ok()),
+            content_length: response.body().content_length(),
+        }
+    }
+
+    /// File extension matching the content type, as a format hint
+    pub fn extension(&self) -> Option<&'static str> {
+        let mime = mime_type(self.content_type.as_deref()?);
+        Some(match mime.as_str() {
+            "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" => "mp3",
+            "audio/aac" | "audio/aacp" | "audio/x-aac" => "aac",
+            "audio/ogg" | "application/ogg" | "audio/vorbis" | "audio/x-ogg" => "ogg",
+            "audio/flac" | "audio/x-flac" => "flac",
+            "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+            _ => return None,
+        })
+    }
+
+    /// Whether this is a radio stream rather than a file served over HTTP
+    fn is_live(&self) -> bool {
+        self.content_length.is_none() && (self.metaint.is_some() || self.name.is_some())
+    }
+
+    /// Track information for the stream, titled after the station until a
+    /// `StreamTitle` arrives
+    pub fn track_info(&self) -> TrackInfo {
+        TrackInfo {
+            path: PathBuf::from(&self.url),
+            title: self.name.clone(),
+            album: self.name.clone(),
+            codec: self.extension().map(str::to_uppercase),
+            bitrate: self.bitrate_kbps.map(|kbps| kbps * 1000),
+            ..Default::default()
+        }
+    }
+}
+
+/// Lower-case MIME type without parameters
+fn mime_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Fields of an ICY metadata block, e.g. `StreamTitle='Artist - Title';`
+pub fn parse_icy_metadata(block: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut rest = block.trim_end_matches('\0');
+    while let Some((key, value)) = rest.split_once("='") {
+        // Values may contain quotes ("Guns N' Roses"); only `';` ends one
+        let (value, next) = match value.find("';") {
+            Some(end) => (&value[..end], &value[end + 2..]),
+            None => (value.trim_end_matches('\''), ""),
+        };
+        fields.push((key.trim().to_string(), value.to_string()));
+        rest = next;
+    }
+    fields
+}
+
+/// Split a `StreamTitle` of the usual "Artist - Title" form
+pub fn split_stream_title(stream_title: &str) -> (Option<String>, Option<String>) {
+    let stream_title = stream_title.trim();
+    match stream_title.split_once(" - ") {
+        Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => (
+            Some(artist.trim().to_string()),
+            Some(title.trim().to_string()),
+        ),
+        _ if stream_title.is_empty() => (None, None),
+        _ => (None, Some(stream_title.to_string())),
+    }
+}
+
+/// Strips the metadata blocks the server interleaves every `metaint` bytes
+struct IcyReader<R> {
+    inner: R,
+    metaint: Option<usize>,
+    /// Audio bytes left before the next metadata block
+    until_metadata: usize,
+    metadata: Option<String>,
+}
+
+impl<R: Read> IcyReader<R> {
+    fn new(inner: R, metaint: Option<usize>) -> Self {
+        Self {
+            inner,
+            metaint: metaint.filter(|&metaint| metaint > 0),
+            until_metadata: metaint.unwrap_or(0),
+            metadata: None,
+        }
+    }
+
+    /// The latest non-empty metadata block
+    fn take_metadata(&mut self) -> Option<String> {
+        self.metadata.take()
+    }
+
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut length = [0u8];
+        if self.inner.read(&mut length)? == 0 {
+            return Ok(false);
+        }
+        let mut block = vec![0; length[0] as usize * 16];
+        self.inner.read_exact(&mut block)?;
+        // Usually UTF-8, but older servers send Latin-1
+        let text = String::from_utf8(block)
+            .unwrap_or_else(|err| err.into_bytes().iter().map(|&b| b as char).collect());
+        let text = text.trim_end_matches('\0');
+        if !text.is_empty() {
+            self.metadata = Some(text.to_string());
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(metaint) = self.metaint else {
+            return self.inner.read(buf);
+        };
+        if self.until_metadata == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.until_metadata = metaint;
+        }
+        let max = buf.len().min(self.until_metadata);
+        let read = self.inner.read(&mut buf[..max])?;
+        self.until_metadata -= read;
+        Ok(read)
+    }
+}
+
+/// Download state shared by the reader, the download threads and the player
+struct Shared {
+    buffer: Mutex<Buffer>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, Buffer>, timeout: Duration) -> MutexGuard<'a, Buffer> {
+        self.changed
+            .wait_timeout(guard, timeout)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    /// Connection allowed to write; stalled connections are abandoned
+    generation: u64,
+    /// Audio bytes received over all connections
+    received: u64,
+    last_data: Option<Instant>,
+    /// The connection waits for the player to make room
+    waiting_for_space: bool,
+    /// Whether the current connection delivered anything
+    delivered: bool,
+    /// Set when the current connection ends, with its error if it failed
+    connection_ended: Option<Option<String>>,
+    /// Latest `StreamTitle`, until the player picks it up
+    title: Option<String>,
+    /// The whole resource was received
+    ended: bool,
+    /// Reconnecting failed for good
+    error: Option<String>,
+    /// Playback waits for the prebuffer to fill
+    buffering: bool,
+    /// The reader was dropped
+    closed: bool,
+}
+
+/// Buffer level as seen by the player
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferStatus {
+    Ready,
+    /// Waiting for the prebuffer, with the fill level in 0..=1
+    Buffering(f32),
+    /// The connection was lost and couldn't be restored
+    Failed(String),
+}
+
+/// A stream being downloaded, read by the decoder. Reads block until data
+/// arrives; check [`StreamHandle::status`] before decoding to avoid that.
+pub struct HttpStream {
+    shared: Arc<Shared>,
+}
+
+/// Buffer status and metadata of an [`HttpStream`], for the player
+#[derive(Clone)]
+pub struct StreamHandle {
+    shared: Arc<Shared>,
+    info: StreamInfo,
+    prebuffer_bytes: usize,
+}
+
+impl HttpStream {
+    /// Connect to a stream and start downloading it. Remote PLS and M3U
+    /// playlists are followed to their first stream.
+    pub fn open(url: &str, options: &StreamOptions) -> Result<(Self, StreamHandle)> {
+        let agent = http_agent(options.connect_timeout);
+
+        let mut stream_url = url.to_string();
+        let mut depth = 0;
+        let response = loop {
+            let response = request(&agent, &stream_url, 0)?;
+            if !is_playlist(&stream_url, &response) {
+                break response;
+            }
+            depth += 1;
+            if depth > MAX_PLAYLIST_DEPTH {
+                bail!("Too many nested playlists behind {}", url);
+            }
+            let content = response
+                .into_body()
+                .into_with_config()
+                .limit(MAX_PLAYLIST_BYTES)
+                .read_to_vec()
+                .with_context(|| format!("Failed to read playlist {}", stream_url))?;
+            stream_url = playlist_stream(&String::from_utf8_lossy(&content))
+                .with_context(|| format!("No stream found in playlist {}", stream_url))?;
+        };
+
+        let info = StreamInfo::from_response(url, &response);
+        let bitrate = info.bitrate_kbps.unwrap_or(DEFAULT_BITRATE_KBPS);
+        let prebuffer_bytes = ((options.prebuffer_secs.max(0.0) * bitrate as f32 * 125.0) as usize)
+            .clamp(LOW_WATER_BYTES * 2, MAX_BUFFERED_BYTES / 2);
+
+        let shared = Arc::new(Shared {
+            buffer: Mutex::new(Buffer {
+                buffering: true,
+                ..Default::default()
+            }),
+            changed: Condvar::new(),
+        });
+        let download = Download {
+            shared: shared.clone(),
+            agent,
+            url: stream_url,
+            info: info.clone(),
+            options: options.clone(),
+        };
+        thread::Builder::new()
+            .name("oneamp-stream".to_string())
+            .spawn(move || download.run(response))
+            .context("Failed to start the download thread")?;
+
+        let handle = StreamHandle {
+            shared: shared.clone(),
+            info,
+            prebuffer_bytes,
+        };
+        Ok((Self { shared }, handle))
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buffer = self.shared.lock();
+        loop {
+            if !buffer.data.is_empty() {
+                let read = buf.len().min(buffer.data.len()).min(MAX_READ_BYTES);
+                for (dst, src) in buf.iter_mut().zip(buffer.data.drain(..read)) {
+                    *dst = src;
+                }
+                self.shared.changed.notify_all();
+                return Ok(read);
+            }
+            if let Some(ref error) = buffer.error {
+                return Err(io::Error::other(error.clone()));
+            }
+            if buffer.ended || buf.is_empty() {
+                return Ok(0);
+            }
+            buffer = self.shared.wait(buffer, Duration::from_millis(100));
+        }
+    }
+}
+
+impl Seek for HttpStream {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "HTTP streams can't seek",
+        ))
+    }
+}
+
+impl MediaSource for HttpStream {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl Drop for HttpStream {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl StreamHandle {
+    pub fn info(&self) -> &StreamInfo {
+        &self.info
+    }
+
+    /// Whether the player can decode without waiting on the network. Once the
+    /// buffer runs low, reports `Buffering` until the prebuffer is full again.
+    pub fn status(&self) -> BufferStatus {
+        let mut buffer = self.shared.lock();
+        let buffered = buffer.data.len();
+        if let Some(ref error) = buffer.error {
+            // Play out what is left first
+            return if buffered == 0 {
+                BufferStatus::Failed(error.clone())
+            } else {
+                BufferStatus::Ready
+            };
+        }
+        if buffer.ended {
+            return BufferStatus::Ready;
+        }
+        if buffer.buffering && buffered >= self.prebuffer_bytes {
+            buffer.buffering = false;
+        } else if !buffer.buffering && buffered < LOW_WATER_BYTES {
+            buffer.buffering = true;
+        }
+        if buffer.buffering {
+            BufferStatus::Buffering((buffered as f32 / self.prebuffer_bytes as f32).min(1.0))
+        } else {
+            BufferStatus::Ready
+        }
+    }
+
+    /// The `StreamTitle` received since the last call, if any
+    pub fn take_title(&self) -> Option<String> {
+        self.shared.lock().title.take()
+    }
+}
+
+/// HTTP client identifying itself as OneAmp that gives up after `timeout`
+pub(crate) fn http_agent(timeout: Duration) -> Agent {
+    Agent::config_builder()
+        .timeout_connect(Some(timeout))
+        .timeout_recv_response(Some(timeout))
+        .user_agent(format!("OneAmp/{}", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into()
+}
+
+/// Send a request asking for ICY metadata, from `offset` on for finite resources
+fn request(agent: &Agent, url: &str, offset: u64) -> Result<Response<Body>> {
+    let mut request = agent.get(url).header("Icy-MetaData", "1");
+    if offset > 0 {
+        request = request.header("Range", format!("bytes={}-", offset));
+    }
+    request
+        .call()
+        .with_context(|| format!("Failed to connect to {}", url))
+}
+
+/// Whether a response is a PLS/M3U playlist rather than audio
+fn is_playlist(url: &str, response: &Response<Body>) -> bool {
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(mime_type);
+    match content_type.as_deref() {
+        Some("application/vnd.apple.mpegurl") => true,
+        Some(mime) if PLAYLIST_TYPES.contains(&mime) => true,
+        // Servers often send playlists as text or generic binary data
+        None | Some("text/plain" | "application/octet-stream") => {
+            let path = url.split(['?', '#']).next().unwrap_or(url);
+            playlist_file::is_playlist_file(Path::new(path))
+        }
+        Some(_) => false,
+    }
+}
+
+/// First stream URL listed in a remote playlist
+fn playlist_stream(content: &str) -> Result<String> {
+    if content.contains("#EXT-X-") {
+        bail!("HLS streams are not supported");
+    }
+    let is_pls = content
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .get(..10)
+        .is_some_and(|start| start.eq_ignore_ascii_case("[playlist]"));
+    let entries = if is_pls {
+        playlist_file::parse_pls(content, Path::new(""))
+    } else {
+        playlist_file::parse_m3u(content, Path::new(""))
+    };
+    entries
+        .iter()
+        .find(|entry| is_stream_url(entry))
+        .and_then(|entry| entry.to_str())
+        .map(str::to_string)
+        .context("The playlist has no stream URLs")
+}
+
+/// How a connection ended
+enum Outcome {
+    /// The reader was dropped
+    Closed,
+    /// The whole resource was received
+    Ended,
+    /// The connection failed or stalled
+    Dropped { delivered: bool, error: String },
+}
+
+/// Keeps a stream flowing into the buffer, reconnecting when it breaks
+struct Download {
+    shared: Arc<Shared>,
+    agent: Agent,
+    /// The stream URL, after following playlists
+    url: String,
+    info: StreamInfo,
+    options: StreamOptions,
+}
+
+impl Download {
+    fn run(self, response: Response<Body>) {
+        let mut response = Some(response);
+        let mut attempt = 0;
+        let mut last_error = String::new();
+        loop {
+            if let Some(response) = response.take() {
+                match self.receive(response) {
+                    Outcome::Closed => return,
+                    Outcome::Ended => {
+                        self.shared.lock().ended = true;
+                        self.shared.changed.notify_all();
+                        return;
+                    }
+                    Outcome::Dropped { delivered, error } => {
+                        if delivered {
+                            attempt = 0;
+                        }
+                        last_error = error;
+                    }
+                }
+            }
+
+            attempt += 1;
+            if attempt > self.options.reconnect_attempts {
+                self.shared.lock().error = Some(format!("Stream lost: {}", last_error));
+                self.shared.changed.notify_all();
+                return;
+            }
+            let delay = self
+                .options
+                .reconnect_delay
+                .saturating_mul(1 << (attempt - 1).min(16))
+                .min(self.options.max_reconnect_delay);
+            eprintln!(
+                "Stream interrupted ({}), reconnecting in {:.1}s (attempt {}/{})",
+                last_error,
+                delay.as_secs_f32(),
+                attempt,
+                self.options.reconnect_attempts
+            );
+            if !self.sleep(delay) {
+                return;
+            }
+
+            // Finite resources continue where they broke off
+            let offset = match self.info.content_length {
+                Some(_) => self.shared.lock().received,
+                None => 0,
+            };
+            match request(&self.agent, &self.url, offset) {
+                Ok(next) => response = Some(next),
+                Err(err) => last_error = format!("{:#}", err),
+            }
+        }
+    }
+
+    /// Pump one connection into the buffer until it ends or stalls
+    fn receive(&self, response: Response<Body>) -> Outcome {
+        let offset = self.shared.lock().received;
+        // A server ignoring the Range header starts over
+        let skip = match response.status() {
+            StatusCode::PARTIAL_CONTENT => 0,
+            _ if self.info.content_length.is_some() => offset,
+            _ => 0,
+        };
+        let metaint = response
+            .headers()
+            .get("icy-metaint")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        let reader = IcyReader::new(response.into_body().into_reader(), metaint);
+
+        let generation = {
+            let mut buffer = self.shared.lock();
+            buffer.generation += 1;
+            buffer.connection_ended = None;
+            buffer.delivered = false;
+            buffer.waiting_for_space = false;
+            buffer.last_data = Some(Instant::now());
+            buffer.generation
+        };
+        let shared = self.shared.clone();
+        thread::spawn(move || pump(shared, generation, reader, skip));
+
+        let mut buffer = self.shared.lock();
+        loop {
+            if buffer.closed {
+                return Outcome::Closed;
+            }
+            if let Some(result) = buffer.connection_ended.take() {
+                let complete = match self.info.content_length {
+                    Some(length) => buffer.received >= length,
+                    None => !self.info.is_live(),
+                };
+                return match result {
+                    None if complete => Outcome::Ended,
+                    result => Outcome::Dropped {
+                        delivered: buffer.delivered,
+                        error: result.unwrap_or_else(|| "connection closed".to_string()),
+                    },
+                };
+            }
+            let stalled = !buffer.waiting_for_space
+                && buffer
+                    .last_data
+                    .is_some_and(|last| last.elapsed() > self.options.stall_timeout);
+            if stalled {
+                // The blocked read can't be interrupted; leave it behind
+                buffer.generation += 1;
+                self.shared.changed.notify_all();
+                return Outcome::Dropped {
+                    delivered: buffer.delivered,
+                    error: "no data received".to_string(),
+                };
+            }
+            buffer = self.shared.wait(buffer, Duration::from_millis(100));
+        }
+    }
+
+    /// Wait before reconnecting. Returns `false` if the reader went away.
+    fn sleep(&self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        let mut buffer = self.shared.lock();
+        loop {
+            if buffer.closed {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            buffer = self.shared.wait(buffer, deadline - now);
+        }
+    }
+}
+
+/// Copy a connection's audio into the buffer while it is the current one
+fn pump(shared: Arc<Shared>, generation: u64, mut reader: IcyReader<impl Read>, mut skip: u64) {
+    let mut chunk = vec![0; 16 * 1024];
+    let result = loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break None,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => break Some(err.to_string()),
+        };
+        let skipped = skip.min(read as u64) as usize;
+        skip -= skipped as u64;
+        let data = &chunk[skipped..read];
+
+        let mut buffer = shared.lock();
+        while buffer.generation == generation
+            && !buffer.closed
+            && buffer.data.len() + data.len() > MAX_BUFFERED_BYTES
+        {
+            buffer.waiting_for_space = true;
+            buffer = shared.wait(buffer, Duration::from_millis(100));
+        }
+        if buffer.generation != generation || buffer.closed {
+            return;
+        }
+        buffer.waiting_for_space = false;
+        buffer.data.extend(data);
+        buffer.received += data.len() as u64;
+        buffer.last_data = Some(Instant::now());
+        buffer.delivered = true;
+        if let Some(metadata) = reader.take_metadata() {
+            if let Some((_, title)) = parse_icy_metadata(&metadata)
+                .into_iter()
+                .find(|(key, _)| key == "StreamTitle")
+            {
+                buffer.title = Some(title);
+            }
+        }
+        shared.changed.notify_all();
+    };
+
+    let mut buffer = shared.lock();
+    if buffer.generation == generation {
+        buffer.connection_ended = Some(result);
+        shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symphonia_player::SymphoniaPlayer;
+    use crate::test_http::{response, serve};
+    use crate::test_wav::wav;
+    use crate::{AudioCaptureBuffer, Equalizer};
+
+    /// Insert an ICY metadata block every `metaint` bytes
+    fn with_metadata(audio: &[u8], metaint: usize, titles: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (i, chunk) in audio.chunks(metaint).enumerate() {
+            body.extend_from_slice(chunk);
+            if chunk.len() < metaint {
+                break;
+            }
+            // Empty titles stand for empty metadata blocks
+            match titles.get(i).filter(|title| !title.is_empty()) {
+                Some(title) => {
+                    let mut block = format!("StreamTitle='{}';", title).into_bytes();
+                    block.resize(block.len().div_ceil(16) * 16, 0);
+                    body.push((block.len() / 16) as u8);
+                    body.extend_from_slice(&block);
+                }
+                None => body.push(0),
+            }
+        }
+        body
+    }
+
+    fn quick_options() -> StreamOptions {
+        StreamOptions {
+            reconnect_attempts: 1,
+            reconnect_delay: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_icy_metadata() {
+        let fields = parse_icy_metadata(
+            "StreamTitle='Guns N' Roses - Don't Cry';StreamUrl='http://x.example/';\0\0",
+        );
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "StreamTitle".to_string(),
+                    "Guns N' Roses - Don't Cry".to_string()
+                ),
+                ("StreamUrl".to_string(), "http://x.example/".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_stream_title("Artist - Title - Live"),
+            (Some("Artist".to_string()), Some("Title - Live".to_string()))
+        );
+        assert_eq!(
+            split_stream_title("Station jingle"),
+            (None, Some("Station jingle".to_string()))
+        );
+        assert_eq!(split_stream_title(" "), (None, None));
+
+        assert!(is_stream_url(Path::new("HTTPS://radio.example/live")));
+        assert!(!is_stream_url(Path::new("/music/http://")));
+        assert!(!is_stream_url(Path::new("http://")));
+    }
+
+    #[test]
+    fn test_icy_reader_strips_metadata() {
+        let audio: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let body = with_metadata(&audio, 100, &["A - One", "", "B - Two"]);
+        let mut reader = IcyReader::new(&body[..], Some(100));
+        let mut out = Vec::new();
+        let mut titles = Vec::new();
+        let mut chunk = [0; 37];
+        loop {
+            let read = reader.read(&mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..read]);
+            if let Some(metadata) = reader.take_metadata() {
+                titles.push(metadata);
+            }
+        }
+        assert_eq!(out, audio);
+        assert_eq!(
+            titles,
+            vec!["StreamTitle='A - One';", "StreamTitle='B - Two';"]
+        );
+    }
+
+    #[test]
+    fn test_play_radio_playlist_with_metadata() {
+        let samples: Vec<i16> = (0..40000)
+            .map(|i| ((i * 37) % 20000 - 10000) as i16)
+            .collect();
+        let body = with_metadata(
+            &wav(8000, 1, &samples),
+            4096,
+            &["Artist - Song", "", "Next - Tune"],
+        );
+        // The station's playlist points to the stream on the same server
+        let (base, server) = serve(|base| {
+            let playlist = format!("[playlist]\nFile1={}/stream\nTitle1=Test FM\n", base);
+            vec![
+                response(
+                    "HTTP/1.1 200 OK\r\nContent-Type: audio/x-scpls",
+                    playlist.as_bytes(),
+                ),
+                response(
+                    "HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\nicy-name: Test FM\r\nicy-br: 128\r\nicy-metaint: 4096",
+                    &body,
+                ),
+            ]
+        });
+
+        let url = format!("{}/radio.pls", base);
+        let (stream, handle) = HttpStream::open(&url, &quick_options()).unwrap();
+        assert_eq!(handle.info().name.as_deref(), Some("Test FM"));
+        assert_eq!(handle.info().bitrate_kbps, Some(128));
+        assert_eq!(handle.info().extension(), Some("wav"));
+        let track = handle.info().track_info();
+        assert_eq!(track.title.as_deref(), Some("Test FM"));
+        assert_eq!(track.path, PathBuf::from(&url));
+
+        let mut player = SymphoniaPlayer::from_source(
+            Box::new(stream),
+            handle.info().extension(),
+            Arc::new(Mutex::new(Equalizer::new(8000.0))),
+            Arc::new(Mutex::new(AudioCaptureBuffer::new(0))),
+        )
+        .unwrap();
+        let mut decoded = Vec::new();
+        let mut titles = Vec::new();
+        while let Ok(Some(chunk)) = player.decode_next() {
+            decoded.extend(chunk);
+            titles.extend(handle.take_title());
+        }
+        let expected: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+        assert_eq!(decoded, expected);
+        assert_eq!(titles.last().map(String::as_str), Some("Next - Tune"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /radio.pls "));
+        assert!(requests[1].starts_with("GET /stream "));
+        assert!(requests[1].to_ascii_lowercase().contains("icy-metadata: 1"));
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let audio: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let (first, rest) = audio.split_at(30_000);
+
+        // A radio stream drops and carries on after reconnecting
+        let live = "HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\nicy-name: Test FM";
+        let (base, server) = serve(|_| vec![response(live, first), response(live, rest)]);
+        let (mut stream, handle) = HttpStream::open(&base, &quick_options()).unwrap();
+        let mut received = Vec::new();
+        let error = stream.read_to_end(&mut received).unwrap_err();
+        assert_eq!(received, audio);
+        // The server is gone after two connections
+        assert!(error.to_string().starts_with("Stream lost"), "{}", error);
+        assert!(matches!(handle.status(), BufferStatus::Failed(_)));
+        let requests = server.join().unwrap();
+        assert!(!requests[1].contains("Range"));
+
+        // A file resumes with a Range request and then ends
+        let length = audio.len();
+        let (base, server) = serve(|_| {
+            let broken = response(
+                &format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\nContent-Length: {}",
+                    length
+                ),
+                first,
+            );
+            vec![
+                broken,
+                response(
+                    &format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Type: audio/mpeg\r\nContent-Length: {}",
+                        rest.len()
+                    ),
+                    rest,
+                ),
+            ]
+        });
+        let (mut stream, handle) = HttpStream::open(&base, &quick_options()).unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert_eq!(received, audio);
+        assert_eq!(handle.status(), BufferStatus::Ready);
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("bytes=30000-"), "{}", requests[1]);
+    }
+
+    #[test]
+    fn test_buffer_status() {
+        let audio = vec![0u8; 64 * 1024];
+        let (base, _server) = serve(|_| {
+            vec![response(
+                "HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\nicy-br: 128",
+                &audio,
+            )]
+        });
+        let options = StreamOptions {
+            prebuffer_secs: 2.0,
+            ..quick_options()
+        };
+        let (mut stream, handle) = HttpStream::open(&base, &options).unwrap();
+        // 2 s at 128 kbps
+        assert_eq!(handle.prebuffer_bytes, 32000);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handle.status() != BufferStatus::Ready {
+            assert!(Instant::now() < deadline, "never finished buffering");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut chunk = vec![0; 64 * 1024];
+        // Reads are capped so the buffer level stays meaningful
+        assert_eq!(stream.read(&mut chunk).unwrap(), MAX_READ_BYTES);
+    }
+}
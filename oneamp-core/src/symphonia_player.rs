@@ -4,13 +4,14 @@ use std::sync::{Arc, Mutex};
 use symphonia::core::audio::{AudioBufferRef, Signal};
 use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::errors::Error as SymphoniaError;
-use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track};
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use symphonia::core::units::{Time, TimeBase};
+use symphonia::core::units::TimeBase;
 
 use crate::audio_capture::AudioCaptureBuffer;
+use crate::cue;
 use crate::equalizer::Equalizer;
 
 /// Symphonia-based audio player with seek support
@@ -18,34 +19,83 @@ pub struct SymphoniaPlayer {
     format_reader: Box<dyn FormatReader>,
     decoder: Box<dyn Decoder>,
     track_id: u32,
-    track: Track,
+    /// Time base of the track's packet timestamps
+    time_base: TimeBase,
     sample_rate: u32,
     channels: u16,
     equalizer: Arc<Mutex<Equalizer>>,
     capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
-    /// Current position in seconds (approximation)
-    current_position: f32,
+    /// Frame of the file the next returned sample belongs to
+    position: u64,
+    /// Frames before this one are dropped, for accurate seeks that landed on
+    /// a packet starting before the requested time
+    skip_until: u64,
+    /// First frame of the track within the file (CUE virtual tracks)
+    start_frame: u64,
+    /// Frame the track ends before (CUE virtual tracks)
+    end_frame: Option<u64>,
 }
 
 impl SymphoniaPlayer {
     /// Load an audio file and prepare for playback
+    ///
+    /// Virtual track paths (`album.flac#track=3`) play only the section of the
+    /// file described by the album's CUE sheet.
     pub fn load(
         path: &Path,
         equalizer: Arc<Mutex<Equalizer>>,
         capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
     ) -> Result<Self> {
+        let (path, start_offset, end_offset) = match cue::resolve_virtual_track(path)? {
+            Some(resolved) => (
+                resolved.track.file,
+                resolved.track.start_secs,
+                resolved.track.end_secs,
+            ),
+            None => (path.to_path_buf(), 0.0, None),
+        };
+        let path = path.as_path();
+
         // Open the file
         let file = std::fs::File::open(path).context("Failed to open audio file")?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+
+        Self::open(
+            Box::new(file),
+            extension,
+            equalizer,
+            capture_buffer,
+            start_offset,
+            end_offset,
+        )
+    }
+
+    /// Prepare playback of a source that isn't a local file, such as an HTTP
+    /// stream; `extension` hints at the format
+    pub fn from_source(
+        source: Box<dyn MediaSource>,
+        extension: Option<&str>,
+        equalizer: Arc<Mutex<Equalizer>>,
+        capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
+    ) -> Result<Self> {
+        Self::open(source, extension, equalizer, capture_buffer, 0.0, None)
+    }
 
+    fn open(
+        source: Box<dyn MediaSource>,
+        extension: Option<&str>,
+        equalizer: Arc<Mutex<Equalizer>>,
+        capture_buffer: Arc<Mutex<AudioCaptureBuffer>>,
+        start_offset: f32,
+        end_offset: Option<f32>,
+    ) -> Result<Self> {
         // Create media source stream
-        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        let mss = MediaSourceStream::new(source, Default::default());
 
         // Create hint based on file extension
         let mut hint = Hint::new();
-        if let Some(ext) = path.extension() {
-            if let Some(ext_str) = ext.to_str() {
-                hint.with_extension(ext_str);
-            }
+        if let Some(ext) = extension {
+            hint.with_extension(ext);
         }
 
         // Probe the media source
@@ -72,6 +122,9 @@ impl SymphoniaPlayer {
         let codec_params = &track.codec_params;
         let sample_rate = codec_params.sample_rate.unwrap_or(44100);
         let channels = codec_params.channels.map(|c| c.count() as u16).unwrap_or(2);
+        let time_base = codec_params
+            .time_base
+            .unwrap_or(TimeBase::new(1, sample_rate));
 
         // Create decoder
         let decoder_opts = DecoderOptions::default();
@@ -84,26 +137,38 @@ impl SymphoniaPlayer {
             eq.set_sample_rate(sample_rate as f32);
         }
 
-        Ok(Self {
+        let mut player = Self {
             format_reader,
             decoder,
             track_id,
-            track,
+            time_base,
             sample_rate,
             channels,
             equalizer,
             capture_buffer,
-            current_position: 0.0,
-        })
+            position: 0,
+            skip_until: 0,
+            start_frame: cue_time_to_frame(start_offset, sample_rate),
+            end_frame: end_offset.map(|end| cue_time_to_frame(end, sample_rate)),
+        };
+
+        if player.start_frame > 0 {
+            player
+                .seek(0.0)
+                .context("Failed to seek to the start of the track")?;
+        }
+
+        Ok(player)
     }
 
-    /// Seek to a specific position in seconds
+    /// Seek to a specific position in seconds (relative to the start of the track)
     pub fn seek(&mut self, seconds: f32) -> Result<()> {
-        let time = Time::from(seconds as f64);
+        let target =
+            self.start_frame + (seconds.max(0.0) as f64 * self.sample_rate as f64).round() as u64;
 
-        let seek_to = SeekTo::Time {
-            time,
-            track_id: Some(self.track_id),
+        let seek_to = SeekTo::TimeStamp {
+            ts: self.frame_to_ts(target),
+            track_id: self.track_id,
         };
 
         // Perform the seek
@@ -112,13 +177,10 @@ impl SymphoniaPlayer {
                 // Reset the decoder after seeking
                 self.decoder.reset();
 
-                // Update current position
-                let time_base = self
-                    .track
-                    .codec_params
-                    .time_base
-                    .unwrap_or(TimeBase::new(1, self.sample_rate));
-                self.current_position = time_base.calc_time(seeked_to.actual_ts).seconds as f32;
+                // The packet we landed on may start before the requested time;
+                // drop those frames so track boundaries are sample-accurate
+                self.position = self.ts_to_frame(seeked_to.required_ts);
+                self.skip_until = self.position;
 
                 Ok(())
             }
@@ -134,6 +196,13 @@ impl SymphoniaPlayer {
     /// Decode the next packet and return audio samples
     /// Returns None if end of stream
     pub fn decode_next(&mut self) -> Result<Option<Vec<f32>>> {
+        // Stop at the end boundary of a virtual track
+        if let Some(end) = self.end_frame {
+            if self.position >= end {
+                return Ok(None);
+            }
+        }
+
         // Get the next packet
         let packet = match self.format_reader.next_packet() {
             Ok(packet) => packet,
@@ -156,7 +225,7 @@ impl SymphoniaPlayer {
         }
 
         // Decode the packet and convert to f32 samples
-        let samples = match self.decoder.decode(&packet) {
+        let mut samples = match self.decoder.decode(&packet) {
             Ok(decoded) => Self::convert_audio_buffer_static(&decoded, self.channels)?,
             Err(SymphoniaError::DecodeError(e)) => {
                 // Skip decode errors and continue
@@ -168,9 +237,24 @@ impl SymphoniaPlayer {
             }
         };
 
-        // Update position estimate
-        let frames = samples.len() / self.channels as usize;
-        self.current_position += frames as f32 / self.sample_rate as f32;
+        let channels = self.channels.max(1) as usize;
+        let packet_start = self.ts_to_frame(packet.ts());
+
+        // Drop frames preceding an accurate seek target
+        let skip =
+            (self.skip_until.saturating_sub(packet_start) as usize).min(samples.len() / channels);
+        samples.drain(..skip * channels);
+        let first = packet_start + skip as u64;
+
+        // Truncate the packet that crosses the end of a virtual track
+        if let Some(end) = self.end_frame {
+            let remaining = end.saturating_sub(first) as usize;
+            if samples.len() / channels > remaining {
+                samples.truncate(remaining * channels);
+            }
+        }
+
+        self.position = first + (samples.len() / channels) as u64;
 
         // Apply equalizer
         let processed_samples = self.apply_equalizer(&samples);
@@ -272,9 +356,22 @@ impl SymphoniaPlayer {
         output
     }
 
-    /// Get current position in seconds
+    /// Get current position in seconds (relative to the start of the track)
     pub fn current_position(&self) -> f32 {
-        self.current_position
+        let frames = self.position.saturating_sub(self.start_frame);
+        (frames as f64 / self.sample_rate as f64) as f32
+    }
+
+    /// Frame of the file a packet timestamp falls on
+    fn ts_to_frame(&self, ts: u64) -> u64 {
+        let TimeBase { numer, denom } = self.time_base;
+        (ts as u128 * numer as u128 * self.sample_rate as u128 / denom as u128) as u64
+    }
+
+    /// Packet timestamp of a frame of the file
+    fn frame_to_ts(&self, frame: u64) -> u64 {
+        let TimeBase { numer, denom } = self.time_base;
+        (frame as u128 * denom as u128 / (numer as u128 * self.sample_rate as u128)) as u64
     }
 
     /// Get sample rate
@@ -287,3 +384,139 @@ impl SymphoniaPlayer {
         self.channels
     }
 }
+
+/// First frame at a CUE time. CUE times are whole 1/75 s frames, so snap to
+/// those before scaling rather than rounding seconds held in an f32.
+fn cue_time_to_frame(secs: f32, sample_rate: u32) -> u64 {
+    let cue_frames = (secs as f64 * 75.0).round() as u64;
+    cue_frames * sample_rate as u64 / 75
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::wav;
+    use std::path::PathBuf;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Write a mono 16-bit WAV whose samples count up from 0, so every
+    /// decoded sample tells its position in the file (modulo 65536)
+    fn write_ramp_wav(path: &Path, sample_rate: u32, frames: u32) {
+        let ramp: Vec<i16> = (0..frames).map(|frame| frame as i16).collect();
+        std::fs::write(path, wav(sample_rate, 1, &ramp)).unwrap();
+    }
+
+    fn load(path: &Path) -> SymphoniaPlayer {
+        SymphoniaPlayer::load(
+            path,
+            Arc::new(Mutex::new(Equalizer::default())),
+            Arc::new(Mutex::new(AudioCaptureBuffer::new(0))),
+        )
+        .unwrap()
+    }
+
+    /// Positions in the file (modulo 65536) of the samples left to play
+    fn decode_all(player: &mut SymphoniaPlayer) -> Vec<u32> {
+        let mut positions = Vec::new();
+        while let Some(samples) = player.decode_next().unwrap() {
+            positions.extend(
+                samples
+                    .iter()
+                    .map(|s| (s * 32768.0).round() as i16 as u16 as u32),
+            );
+        }
+        positions
+    }
+
+    fn album(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oneamp_player_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // 4 s: track 2 runs from 1 s to 2.4 s (frame 30 of 75)
+        write_ramp_wav(&dir.join("album.wav"), SAMPLE_RATE, 4 * SAMPLE_RATE);
+        std::fs::write(
+            dir.join("album.cue"),
+            "FILE \"album.wav\" WAVE\n\
+             TRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+             TRACK 02 AUDIO\nINDEX 01 00:01:00\n\
+             TRACK 03 AUDIO\nINDEX 01 00:02:30\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_virtual_track_boundaries() {
+        let dir = album("boundaries");
+
+        let mut player = load(&dir.join("album.wav#track=2"));
+        assert_eq!(player.current_position(), 0.0);
+        let positions = decode_all(&mut player);
+        assert_eq!(positions.first(), Some(&SAMPLE_RATE));
+        assert_eq!(positions.last(), Some(&(SAMPLE_RATE * 24 / 10 - 1)));
+        assert_eq!(positions.len() as u32, SAMPLE_RATE * 14 / 10);
+        // Finished stays finished
+        assert_eq!(player.decode_next().unwrap(), None);
+        assert!((player.current_position() - 1.4).abs() < 0.001);
+
+        // The last track runs to the end of the file
+        let positions = decode_all(&mut load(&dir.join("album.cue#track=3")));
+        assert_eq!(positions.first(), Some(&(SAMPLE_RATE * 24 / 10)));
+        assert_eq!(positions.last(), Some(&(SAMPLE_RATE * 4 - 1)));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_virtual_track_seek() {
+        let dir = album("seek");
+
+        let mut player = load(&dir.join("album.wav#track=2"));
+        player.seek(0.5).unwrap();
+        assert!((player.current_position() - 0.5).abs() < 0.001);
+        let positions = decode_all(&mut player);
+        assert_eq!(positions.first(), Some(&(SAMPLE_RATE * 3 / 2)));
+        assert_eq!(positions.last(), Some(&(SAMPLE_RATE * 24 / 10 - 1)));
+
+        // Seeking before the track start lands on the start
+        player.seek(-3.0).unwrap();
+        assert_eq!(decode_all(&mut player).first(), Some(&SAMPLE_RATE));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_virtual_track_boundaries_far_into_the_file() {
+        // Over a minute of 1152-frame packets, minutes into a 44.1 kHz file
+        const RATE: u32 = 44100;
+        let dir = std::env::temp_dir().join("oneamp_player_test_far");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_ramp_wav(&dir.join("album.wav"), RATE, 181 * RATE);
+        std::fs::write(
+            dir.join("album.cue"),
+            "FILE \"album.wav\" WAVE\n\
+             TRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+             TRACK 02 AUDIO\nINDEX 01 02:00:37\n\
+             TRACK 03 AUDIO\nINDEX 01 03:00:00\n",
+        )
+        .unwrap();
+        let start = 120 * RATE + 37 * RATE / 75;
+        let end = 180 * RATE;
+
+        let mut player = load(&dir.join("album.wav#track=2"));
+        let positions = decode_all(&mut player);
+        assert_eq!(positions.len() as u32, end - start);
+        assert_eq!(positions.first(), Some(&(start % 65536)));
+        assert_eq!(positions.last(), Some(&((end - 1) % 65536)));
+        assert!(positions
+            .windows(2)
+            .all(|pair| pair[1] == (pair[0] + 1) % 65536));
+        assert_eq!(player.decode_next().unwrap(), None);
+        let length = (end - start) as f32 / RATE as f32;
+        assert!((player.current_position() - length).abs() < 0.001);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
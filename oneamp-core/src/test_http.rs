@@ -0,0 +1,55 @@
+// Test HTTP Server
+// A tiny local server for tests of the stream and podcast clients.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Serve one canned response per connection, then stop listening. The
+/// responses are built from the server's base URL. Returns the base URL
+/// and the requests received, each a head followed by its body.
+pub fn serve(
+    responses: impl FnOnce(&str) -> Vec<Vec<u8>>,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let responses = responses(&base);
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+                request.push_str(&line);
+            }
+            if length > 0 {
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str("\r\n");
+                request.push_str(&String::from_utf8_lossy(&body));
+            }
+            requests.push(request);
+            let _ = socket.write_all(&response);
+        }
+        requests
+    });
+    (base, handle)
+}
+
+pub fn response(headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!("{}\r\nConnection: close\r\n\r\n", headers).into_bytes();
+    response.extend_from_slice(body);
+    response
+}
@@ -0,0 +1,31 @@
+// Test WAV Files
+// Hand-built 16-bit PCM WAV files for tests that need real audio to decode.
+
+use std::path::Path;
+
+/// A 16-bit PCM WAV file of interleaved `samples`
+pub fn wav(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// Write a silent 8 kHz mono WAV file of `frames` frames
+pub fn write_silent(path: &Path, frames: u32) {
+    std::fs::write(path, wav(8000, 1, &vec![0; frames as usize])).unwrap();
+}
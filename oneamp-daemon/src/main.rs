@@ -0,0 +1,298 @@
+// OneAmp Daemon
+// Headless player: plays a playlist without a window and takes its orders
+// from the local control socket (`oneamp-cli ctl`, scripts) and from MPD
+// clients over TCP.
+
+#[cfg(unix)]
+mod mpd;
+
+#[cfg(unix)]
+use anyhow::{Context, Result};
+#[cfg(unix)]
+use clap::Parser;
+#[cfg(unix)]
+use crossbeam_channel::{select, Receiver};
+#[cfg(unix)]
+use mpd::MpdServer;
+#[cfg(unix)]
+use oneamp_core::ipc::{self, IpcReply, IpcRequest, IpcServer};
+#[cfg(unix)]
+use oneamp_core::{AudioCommand, Library, MetadataService, PlaybackState, Player, RepeatMode};
+#[cfg(unix)]
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// How often to collect metadata probes while some are outstanding
+#[cfg(unix)]
+const METADATA_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Headless OneAmp player controlled over a local socket
+#[cfg(unix)]
+#[derive(Parser, Debug)]
+#[command(name = "oneamp-daemon")]
+#[command(about = "OneAmp - headless player controlled with `oneamp-cli ctl`", long_about = None)]
+struct Args {
+    /// Files to add to the playlist and start playing
+    #[arg(value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Control socket path (defaults to $ONEAMP_SOCKET or $XDG_RUNTIME_DIR/oneamp.sock)
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Music directory served to MPD clients (defaults to ~/Music)
+    #[arg(long, value_name = "DIR")]
+    music_dir: Option<PathBuf>,
+
+    /// Address the MPD server listens on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1")]
+    mpd_bind: IpAddr,
+
+    /// MPD server port (0 picks a free port)
+    #[arg(long, value_name = "PORT", default_value_t = 6600)]
+    mpd_port: u16,
+}
+
+#[cfg(unix)]
+struct Daemon {
+    player: Player,
+    server: IpcServer,
+    mpd: MpdServer,
+    library: Library,
+    /// Library scan in progress, if any
+    scan: Receiver<Library>,
+    /// Metadata of playlist entries outside the library
+    metadata: MetadataService,
+    /// Bumped on every playlist change (MPD `playlist` status field)
+    playlist_version: u32,
+    running: bool,
+}
+
+/// What MPD clients are told about when it changes
+#[cfg(unix)]
+#[derive(PartialEq)]
+struct Snapshot {
+    state: PlaybackState,
+    current_index: Option<usize>,
+    playlist_version: u32,
+    volume: f32,
+    repeat: RepeatMode,
+    shuffle: bool,
+}
+
+#[cfg(unix)]
+impl Daemon {
+    fn run(&mut self) -> Result<()> {
+        let events = self.player.events().clone();
+        let calls = self.server.calls().clone();
+        let mpd_calls = self.mpd.calls().clone();
+
+        while self.running {
+            let before = self.snapshot();
+            let metadata_poll = if self.metadata.pending_count() > 0 {
+                crossbeam_channel::after(METADATA_POLL_INTERVAL)
+            } else {
+                crossbeam_channel::never()
+            };
+            select! {
+                recv(events) -> event => {
+                    let event = event.context("The audio engine stopped")?;
+                    self.server.broadcast(&event);
+                    self.player.handle_event(&event);
+                }
+                recv(calls) -> call => {
+                    let Ok(call) = call else { break };
+                    let reply = self.handle_request(call.request.clone());
+                    call.reply(reply);
+                }
+                recv(mpd_calls) -> call => {
+                    let Ok(call) = call else { break };
+                    let response = self.handle_mpd(&call.args);
+                    call.reply(response);
+                }
+                recv(self.scan) -> library => {
+                    self.scan = crossbeam_channel::never();
+                    if let Ok(library) = library {
+                        self.library = library;
+                        self.mpd.notify(&["database", "update"]);
+                    }
+                }
+                recv(metadata_poll) -> _ => {
+                    // New tags for playlist entries: let clients fetch them
+                    if self.metadata.poll() {
+                        self.playlist_changed();
+                    }
+                }
+            }
+            self.notify_changes(&before);
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let status = self.player.status();
+        Snapshot {
+            state: status.state,
+            current_index: status.current_index,
+            playlist_version: self.playlist_version,
+            volume: status.volume,
+            repeat: status.repeat,
+            shuffle: status.shuffle,
+        }
+    }
+
+    /// Wake MPD clients idling on the subsystems that changed
+    fn notify_changes(&self, before: &Snapshot) {
+        let after = self.snapshot();
+        let mut changed = Vec::new();
+        if (before.state, before.current_index) != (after.state, after.current_index) {
+            changed.push("player");
+        }
+        if before.playlist_version != after.playlist_version {
+            changed.push("playlist");
+        }
+        if before.volume != after.volume {
+            changed.push("mixer");
+        }
+        if (before.repeat, before.shuffle) != (after.repeat, after.shuffle) {
+            changed.push("options");
+        }
+        self.mpd.notify(&changed);
+    }
+
+    fn playlist_changed(&mut self) {
+        self.playlist_version = self.playlist_version.wrapping_add(1);
+    }
+
+    /// Index the music directory in the background
+    fn rescan_library(&mut self) {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let root = self.library.root().to_path_buf();
+        std::thread::spawn(move || match Library::scan(&root) {
+            Ok(library) => {
+                let _ = tx.send(library);
+            }
+            Err(e) => eprintln!("{:#}", e),
+        });
+        self.scan = rx;
+    }
+
+    fn handle_request(&mut self, request: IpcRequest) -> IpcReply {
+        let player = &mut self.player;
+        match request {
+            IpcRequest::Play => player.play(),
+            IpcRequest::Pause => player.pause(),
+            IpcRequest::TogglePause => player.toggle_pause(),
+            IpcRequest::Stop => player.stop(),
+            IpcRequest::Next => {
+                if !player.next() {
+                    return IpcReply::error("No next track");
+                }
+            }
+            IpcRequest::Previous => {
+                if !player.previous() {
+                    return IpcReply::error("No previous track");
+                }
+            }
+            IpcRequest::Seek { position_secs } => player.seek(position_secs),
+            IpcRequest::SetVolume { volume } => player.set_volume(volume),
+            IpcRequest::SetEqualizerEnabled { enabled } => {
+                player.send_command(AudioCommand::SetEqualizerEnabled(enabled))
+            }
+            IpcRequest::SetEqualizerBand { band, gain_db } => {
+                player.send_command(AudioCommand::SetEqualizerBand(band, gain_db))
+            }
+            IpcRequest::SetEqualizerBands { gains } => {
+                player.send_command(AudioCommand::SetEqualizerBands(gains))
+            }
+            IpcRequest::ResetEqualizer => player.send_command(AudioCommand::ResetEqualizer),
+            IpcRequest::Open { paths } => {
+                let added = player.enqueue(&paths);
+                // Files already in the playlist play from their entry
+                let playlist = player.playlist();
+                let Some(index) = paths.iter().find_map(|path| playlist.find_expanded(path)) else {
+                    return IpcReply::error("No files added");
+                };
+                player.play_index(index);
+                if added > 0 {
+                    self.playlist_changed();
+                }
+            }
+            IpcRequest::Enqueue { paths } => {
+                player.enqueue(&paths);
+                self.playlist_changed();
+            }
+            IpcRequest::PlayIndex { index } => {
+                if !player.play_index(index) {
+                    return IpcReply::error(format!("No playlist entry {}", index));
+                }
+            }
+            IpcRequest::Remove { index } => {
+                if player.playlist_mut().remove(index).is_none() {
+                    return IpcReply::error(format!("No playlist entry {}", index));
+                }
+                self.playlist_changed();
+            }
+            IpcRequest::Clear => {
+                player.playlist_mut().clear();
+                self.playlist_changed();
+            }
+            IpcRequest::SetRepeat { mode } => player.playlist_mut().set_repeat(mode),
+            IpcRequest::SetShuffle { enabled } => player.playlist_mut().set_shuffle(enabled),
+            IpcRequest::Status => return IpcReply::Status(player.status()),
+            IpcRequest::Playlist => {
+                return IpcReply::Playlist {
+                    entries: player.playlist().entries().to_vec(),
+                    current_index: player.playlist().current_index(),
+                }
+            }
+            // Handled by the server itself; there is no window to raise
+            IpcRequest::Subscribe | IpcRequest::Raise => {}
+            IpcRequest::Quit => self.running = false,
+        }
+        IpcReply::Ok
+    }
+}
+
+#[cfg(unix)]
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let socket = args.socket.unwrap_or_else(ipc::default_socket_path);
+
+    let music_dir = args
+        .music_dir
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Music")))
+        .context("No music directory: pass --music-dir")?;
+
+    let server = IpcServer::bind(&socket)?;
+    println!("Listening on {}", socket.display());
+    let mpd = MpdServer::bind(SocketAddr::new(args.mpd_bind, args.mpd_port))?;
+    println!("MPD server listening on {}", mpd.local_addr());
+
+    let mut player = Player::new().context("Failed to start the audio engine")?;
+    if player.enqueue(&args.files) > 0 {
+        player.play_index(0);
+    }
+
+    let mut daemon = Daemon {
+        player,
+        server,
+        mpd,
+        library: Library::empty(&music_dir),
+        scan: crossbeam_channel::never(),
+        metadata: MetadataService::new(),
+        playlist_version: 1,
+        running: true,
+    };
+    daemon.rescan_library();
+    daemon.run()
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("oneamp-daemon needs Unix domain sockets and is not available on this platform");
+    std::process::exit(1);
+}
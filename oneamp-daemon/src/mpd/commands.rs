@@ -0,0 +1,517 @@
+// MPD Commands
+// Answers MPD commands from the daemon's player, playlist and library.
+// Song ids are the playlist's entry ids, which follow entries as they move.
+
+use super::filter::{Filter, Tag};
+use super::{Ack, AckCode, Response};
+use crate::Daemon;
+use oneamp_core::{cue, PlaybackState, RepeatMode, TrackInfo, TrackMetadata};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Commands answered by `commands`
+const COMMANDS: [&str; 39] = [
+    "add",
+    "addid",
+    "clear",
+    "close",
+    "command_list_begin",
+    "command_list_end",
+    "command_list_ok_begin",
+    "commands",
+    "currentsong",
+    "delete",
+    "find",
+    "idle",
+    "list",
+    "next",
+    "noidle",
+    "outputs",
+    "pause",
+    "ping",
+    "play",
+    "playid",
+    "playlistid",
+    "playlistinfo",
+    "plchanges",
+    "previous",
+    "random",
+    "repeat",
+    "search",
+    "seek",
+    "seekcur",
+    "seekid",
+    "setvol",
+    "single",
+    "stats",
+    "status",
+    "stop",
+    "tagtypes",
+    "update",
+    "urlhandlers",
+    "volume",
+];
+
+impl Daemon {
+    /// Answer one MPD command; `args[0]` is the command name
+    pub(crate) fn handle_mpd(&mut self, args: &[String]) -> Response {
+        let (command, args) = args.split_first().expect("empty MPD command");
+        let mut out = String::new();
+
+        match command.as_str() {
+            "status" => self.mpd_status(&mut out),
+            "currentsong" => {
+                if let Some(index) = self.player.playlist().current_index() {
+                    self.write_song(&mut out, index);
+                }
+            }
+            "stats" => {
+                let tracks = self.library.tracks();
+                let artists: BTreeSet<_> =
+                    tracks.iter().filter_map(|t| t.artist.as_ref()).collect();
+                let albums: BTreeSet<_> = tracks.iter().filter_map(|t| t.album.as_ref()).collect();
+                let db_playtime: f32 = tracks.iter().filter_map(|t| t.duration_secs).sum();
+                let _ = writeln!(out, "artists: {}", artists.len());
+                let _ = writeln!(out, "albums: {}", albums.len());
+                let _ = writeln!(out, "songs: {}", tracks.len());
+                let _ = writeln!(out, "db_playtime: {}", db_playtime as u64);
+            }
+
+            // Playback
+            "play" | "playid" => match args.first() {
+                Some(song) => {
+                    let index = self.song_index(command, song)?;
+                    if !self.player.play_index(index) {
+                        return Err(no_such_song());
+                    }
+                }
+                None => self.player.play(),
+            },
+            "pause" => match args.first().map(|a| parse_bool(a)).transpose()? {
+                Some(true) => self.player.pause(),
+                Some(false) => self.player.play(),
+                None => self.player.toggle_pause(),
+            },
+            "stop" => self.player.stop(),
+            "next" => {
+                self.player.next();
+            }
+            "previous" => {
+                self.player.previous();
+            }
+            "seek" | "seekid" => {
+                let [pos, time] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let index = self.song_index(command, pos)?;
+                let time = parse_time(time)?;
+                if self.player.playlist().current_index() != Some(index)
+                    && !self.player.play_index(index)
+                {
+                    return Err(no_such_song());
+                }
+                self.player.seek(time);
+                self.mpd.notify(&["player"]);
+            }
+            "seekcur" => {
+                let [time] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let offset = parse_time(time.trim_start_matches(['+', '-']))?;
+                let position = match time.chars().next() {
+                    Some('+') => self.player.position_secs() + offset,
+                    Some('-') => self.player.position_secs() - offset,
+                    _ => offset,
+                };
+                self.player.seek(position);
+                self.mpd.notify(&["player"]);
+            }
+
+            // Mixer and options
+            "setvol" => {
+                let [volume] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let volume = parse_int(volume)?.min(100);
+                self.player.set_volume(volume as f32 / 100.0);
+            }
+            "volume" => {
+                let [change] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let change: i32 = change
+                    .parse()
+                    .map_err(|_| Ack::new(AckCode::Arg, format!("Integer expected: {}", change)))?;
+                let volume = (self.volume_percent() as i32 + change).clamp(0, 100);
+                self.player.set_volume(volume as f32 / 100.0);
+            }
+            "repeat" | "single" => {
+                let [state] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let enabled = parse_bool(state)?;
+                let playlist = self.player.playlist_mut();
+                let repeat = match (command.as_str(), enabled, playlist.repeat()) {
+                    ("repeat", false, _) => RepeatMode::Off,
+                    ("repeat", true, RepeatMode::One) => RepeatMode::One,
+                    ("repeat", true, _) => RepeatMode::All,
+                    (_, true, _) => RepeatMode::One,
+                    (_, false, RepeatMode::One) => RepeatMode::All,
+                    (_, false, current) => current,
+                };
+                playlist.set_repeat(repeat);
+            }
+            "random" => {
+                let [state] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                let enabled = parse_bool(state)?;
+                self.player.playlist_mut().set_shuffle(enabled);
+            }
+
+            // Playlist
+            "add" | "addid" => {
+                let Some(uri) = args.first() else {
+                    return Err(wrong_arguments(command));
+                };
+                let paths = self.resolve_uri(uri)?;
+                if command == "addid" && paths.len() != 1 {
+                    return Err(Ack::new(AckCode::Arg, "addid takes a single song"));
+                }
+                let position = args.get(1).map(|pos| parse_int(pos)).transpose()?;
+                // MPD queues a song again when it is already in the playlist
+                let playlist = self.player.playlist_mut();
+                let added: Vec<usize> = paths
+                    .iter()
+                    .flat_map(|path| cue::expand_playlist_entry(path))
+                    .map(|entry| playlist.add(entry))
+                    .collect();
+                let Some(&first) = added.first() else {
+                    return Err(Ack::new(AckCode::NoExist, "No such song"));
+                };
+                let index = match position {
+                    Some(pos) => playlist.move_entries(&added, pos),
+                    None => first,
+                };
+                self.playlist_changed();
+                if command == "addid" {
+                    if let Some(id) = self.player.playlist().id(index) {
+                        let _ = writeln!(out, "Id: {}", id);
+                    }
+                }
+            }
+            "delete" => {
+                let Some(range) = args.first() else {
+                    return Err(wrong_arguments(command));
+                };
+                let (start, end) = self.parse_range(range)?;
+                let indices: Vec<usize> = (start..end).collect();
+                self.player.playlist_mut().remove_many(&indices);
+                self.playlist_changed();
+            }
+            "clear" => {
+                self.player.stop();
+                self.player.playlist_mut().clear();
+                self.playlist_changed();
+            }
+            "playlistinfo" | "playlistid" => {
+                let (start, end) = match args.first() {
+                    Some(id) if command == "playlistid" => {
+                        let index = self.song_index(command, id)?;
+                        (index, index + 1)
+                    }
+                    Some(range) => self.parse_range(range)?,
+                    None => (0, self.player.playlist().len()),
+                };
+                for index in start..end {
+                    self.write_song(&mut out, index);
+                }
+            }
+            "plchanges" => {
+                let [version] = args else {
+                    return Err(wrong_arguments(command));
+                };
+                // Without per-entry versions, any change resends the whole playlist
+                if parse_int(version)? < self.playlist_version as usize {
+                    for index in 0..self.player.playlist().len() {
+                        self.write_song(&mut out, index);
+                    }
+                }
+            }
+
+            // Database
+            "list" => {
+                let Some(tag) = args.first() else {
+                    return Err(wrong_arguments(command));
+                };
+                let tag = Tag::parse(tag)
+                    .filter(|tag| *tag != Tag::Any)
+                    .ok_or_else(|| Ack::new(AckCode::Arg, format!("Unknown tag type: {}", tag)))?;
+                let filter_args = strip_trailing(&args[1..], &["group"]);
+                // `list album ARTIST` is the legacy short form of `list album artist ARTIST`
+                let filter = match filter_args {
+                    [] => None,
+                    [artist] if tag == Tag::Album && !artist.starts_with('(') => Some(
+                        Filter::parse(&["artist".to_string(), artist.clone()], false),
+                    ),
+                    _ => Some(Filter::parse(filter_args, false)),
+                }
+                .transpose()
+                .map_err(|e| Ack::new(AckCode::Arg, e))?;
+
+                let mut values = BTreeSet::new();
+                for track in self.library.tracks() {
+                    let uri = self.uri(&track.path);
+                    if filter
+                        .as_ref()
+                        .is_some_and(|f| !f.matches(track, &uri, false))
+                    {
+                        continue;
+                    }
+                    if let Some(value) = tag.value(track, &uri) {
+                        values.insert(value.to_string());
+                    }
+                }
+                for value in values {
+                    let _ = writeln!(out, "{}: {}", tag.name(), value);
+                }
+            }
+            "find" | "search" => {
+                let fold_case = command == "search";
+                let filter_args = strip_trailing(args, &["sort", "window"]);
+                if filter_args.is_empty() {
+                    return Err(wrong_arguments(command));
+                }
+                let filter =
+                    Filter::parse(filter_args, fold_case).map_err(|e| Ack::new(AckCode::Arg, e))?;
+                for track in self.library.tracks() {
+                    let uri = self.uri(&track.path);
+                    if filter.matches(track, &uri, fold_case) {
+                        write_track(&mut out, &uri, track);
+                    }
+                }
+            }
+            "update" => {
+                self.rescan_library();
+                let _ = writeln!(out, "updating_db: 1");
+            }
+
+            // Introspection
+            "commands" => {
+                for command in COMMANDS {
+                    let _ = writeln!(out, "command: {}", command);
+                }
+            }
+            "tagtypes" => {
+                for tag in [Tag::Artist, Tag::AlbumArtist, Tag::Album, Tag::Title] {
+                    let _ = writeln!(out, "tagtype: {}", tag.name());
+                }
+            }
+            "outputs" => {
+                out.push_str(
+                    "outputid: 0\noutputname: Default output\nplugin: oneamp\noutputenabled: 1\n",
+                );
+            }
+            "urlhandlers" => {}
+
+            _ => {
+                return Err(Ack::new(
+                    AckCode::Unknown,
+                    format!("unknown command \"{}\"", command),
+                ))
+            }
+        }
+        Ok(out)
+    }
+
+    fn mpd_status(&self, out: &mut String) {
+        let status = self.player.status();
+        let state = match status.state {
+            PlaybackState::Playing => "play",
+            PlaybackState::Paused => "pause",
+            PlaybackState::Stopped => "stop",
+        };
+        let _ = writeln!(out, "volume: {}", self.volume_percent());
+        let _ = writeln!(out, "repeat: {}", (status.repeat != RepeatMode::Off) as u8);
+        let _ = writeln!(out, "random: {}", status.shuffle as u8);
+        let _ = writeln!(out, "single: {}", (status.repeat == RepeatMode::One) as u8);
+        let _ = writeln!(out, "consume: 0");
+        let _ = writeln!(out, "playlist: {}", self.playlist_version);
+        let _ = writeln!(out, "playlistlength: {}", status.playlist_length);
+        let _ = writeln!(out, "state: {}", state);
+        if let Some(index) = status.current_index {
+            let _ = writeln!(out, "song: {}", index);
+            if let Some(id) = self.player.playlist().id(index) {
+                let _ = writeln!(out, "songid: {}", id);
+            }
+        }
+        if status.state != PlaybackState::Stopped {
+            let _ = writeln!(
+                out,
+                "time: {}:{}",
+                status.position_secs as u64, status.duration_secs as u64
+            );
+            let _ = writeln!(out, "elapsed: {:.3}", status.position_secs);
+            let _ = writeln!(out, "duration: {:.3}", status.duration_secs);
+        }
+    }
+
+    fn volume_percent(&self) -> u32 {
+        (self.player.status().volume * 100.0).round() as u32
+    }
+
+    /// Write the song block of a playlist entry
+    fn write_song(&mut self, out: &mut String, index: usize) {
+        let Some(path) = self.player.playlist().get(index).map(Path::to_path_buf) else {
+            return;
+        };
+        let track = self.track_info(&path);
+        write_track(out, &self.uri(&path), &track);
+        let _ = writeln!(out, "Pos: {}", index);
+        if let Some(id) = self.player.playlist().id(index) {
+            let _ = writeln!(out, "Id: {}", id);
+        }
+    }
+
+    /// Metadata of a playlist entry, from the library when it is indexed.
+    /// Other files are probed in the background and have only their path
+    /// until the probe finishes.
+    fn track_info(&mut self, path: &Path) -> TrackInfo {
+        if let Some(track) = self.library.get(path) {
+            return track.clone();
+        }
+        if let Some(info) = self.metadata.get(path).and_then(TrackMetadata::info) {
+            return info.clone();
+        }
+        self.metadata.request(path);
+        TrackInfo {
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Playlist index of a song argument: an id for the `*id` commands, a
+    /// position otherwise
+    fn song_index(&self, command: &str, song: &str) -> Result<usize, Ack> {
+        let value = parse_int(song)?;
+        if !command.ends_with("id") {
+            return Ok(value);
+        }
+        u32::try_from(value)
+            .ok()
+            .and_then(|id| self.player.playlist().index_of_id(id))
+            .ok_or_else(|| Ack::new(AckCode::NoExist, "No such song"))
+    }
+
+    /// Song URI: relative to the music directory, absolute outside of it
+    fn uri(&self, path: &Path) -> String {
+        self.library
+            .relative_path(path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Files to add for a URI: a song or directory of the library, or an
+    /// absolute path to a local file
+    fn resolve_uri(&self, uri: &str) -> Result<Vec<PathBuf>, Ack> {
+        let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+        if path.is_absolute() {
+            if !path.is_file() {
+                return Err(Ack::new(AckCode::NoExist, "No such file"));
+            }
+            return Ok(vec![path.to_path_buf()]);
+        }
+
+        let paths: Vec<PathBuf> = self
+            .library
+            .tracks_under(path)
+            .map(|track| track.path.clone())
+            .collect();
+        if paths.is_empty() {
+            return Err(Ack::new(AckCode::NoExist, "No such directory"));
+        }
+        Ok(paths)
+    }
+
+    /// `POS` or `START:END` (END may be omitted) as a half-open range
+    fn parse_range(&self, range: &str) -> Result<(usize, usize), Ack> {
+        let len = self.player.playlist().len();
+        let (start, end) = match range.split_once(':') {
+            Some((start, "")) => (parse_int(start)?, len),
+            Some((start, end)) => (parse_int(start)?, parse_int(end)?),
+            None => {
+                let pos = parse_int(range)?;
+                (pos, pos + 1)
+            }
+        };
+        if start >= len || end > len || start >= end {
+            return Err(Ack::new(AckCode::Arg, "Bad song index"));
+        }
+        Ok((start, end))
+    }
+}
+
+fn write_track(out: &mut String, uri: &str, track: &TrackInfo) {
+    let _ = writeln!(out, "file: {}", uri);
+    if let Some(artist) = &track.artist {
+        let _ = writeln!(out, "Artist: {}", artist);
+    }
+    if let Some(album) = &track.album {
+        let _ = writeln!(out, "Album: {}", album);
+    }
+    if let Some(title) = &track.title {
+        let _ = writeln!(out, "Title: {}", title);
+    }
+    if let Some(duration) = track.duration_secs {
+        let _ = writeln!(out, "Time: {}", duration.round() as u64);
+        let _ = writeln!(out, "duration: {:.3}", duration);
+    }
+}
+
+/// Drop trailing `KEYWORD VALUE` options (`sort`, `window`, `group`) the
+/// daemon does not implement
+fn strip_trailing<'a>(args: &'a [String], keywords: &[&str]) -> &'a [String] {
+    let mut end = args.len();
+    while end >= 2 && keywords.contains(&args[end - 2].to_ascii_lowercase().as_str()) {
+        end -= 2;
+    }
+    &args[..end]
+}
+
+fn parse_int(value: &str) -> Result<usize, Ack> {
+    value
+        .parse()
+        .map_err(|_| Ack::new(AckCode::Arg, format!("Integer expected: {}", value)))
+}
+
+fn parse_time(value: &str) -> Result<f32, Ack> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|time| time.is_finite() && *time >= 0.0)
+        .ok_or_else(|| Ack::new(AckCode::Arg, format!("Number expected: {}", value)))
+}
+
+fn parse_bool(value: &str) -> Result<bool, Ack> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Ack::new(
+            AckCode::Arg,
+            format!("Boolean (0/1) expected: {}", value),
+        )),
+    }
+}
+
+fn no_such_song() -> Ack {
+    Ack::new(AckCode::Arg, "Bad song index")
+}
+
+fn wrong_arguments(command: &str) -> Ack {
+    Ack::new(
+        AckCode::Arg,
+        format!("wrong number of arguments for \"{}\"", command),
+    )
+}
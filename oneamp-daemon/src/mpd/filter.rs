@@ -0,0 +1,348 @@
+// MPD Filters
+// Tag filters for `find`, `search` and `list`: the legacy `TAG VALUE` pairs
+// and the expression syntax of MPD 0.21 (`((Artist == "X") AND (Album != "Y"))`).
+
+use oneamp_core::TrackInfo;
+
+/// Tags the daemon knows about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Artist,
+    /// Answered with the track artist
+    AlbumArtist,
+    Album,
+    Title,
+    File,
+    Any,
+    /// A valid MPD tag the library doesn't index (genre, date, ...); never set
+    Unsupported,
+}
+
+impl Tag {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "artist" => Self::Artist,
+            "albumartist" => Self::AlbumArtist,
+            "album" => Self::Album,
+            "title" => Self::Title,
+            "file" | "base" => Self::File,
+            "any" => Self::Any,
+            "artistsort" | "albumartistsort" | "albumsort" | "titlesort" | "genre" | "date"
+            | "originaldate" | "track" | "disc" | "composer" | "performer" | "comment" | "name"
+            | "label" => Self::Unsupported,
+            _ => return None,
+        })
+    }
+
+    /// Name used in responses (`Artist: ...`)
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Artist => "Artist",
+            Self::AlbumArtist => "AlbumArtist",
+            Self::Album => "Album",
+            Self::Title => "Title",
+            Self::File => "file",
+            Self::Any => "any",
+            Self::Unsupported => "",
+        }
+    }
+
+    /// Value of the tag for a track whose URI is `uri`
+    pub fn value<'a>(self, track: &'a TrackInfo, uri: &'a str) -> Option<&'a str> {
+        match self {
+            Self::Artist | Self::AlbumArtist => track.artist.as_deref(),
+            Self::Album => track.album.as_deref(),
+            Self::Title => track.title.as_deref(),
+            Self::File => Some(uri),
+            Self::Any | Self::Unsupported => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    Contains,
+    StartsWith,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Compare {
+        tag: Tag,
+        operator: Operator,
+        value: String,
+    },
+    And(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Parse the filter arguments of a command. `search` passes
+    /// `fold_case`, which makes pairs match substrings case-insensitively.
+    pub fn parse(args: &[String], fold_case: bool) -> Result<Self, String> {
+        if let [expression] = args {
+            if expression.starts_with('(') {
+                let mut parser = Parser {
+                    chars: expression.chars().collect(),
+                    pos: 0,
+                };
+                let filter = parser.expression()?;
+                parser.skip_spaces();
+                if parser.pos != parser.chars.len() {
+                    return Err("Unparsed garbage after expression".to_string());
+                }
+                return Ok(filter);
+            }
+        }
+
+        if !args.len().is_multiple_of(2) {
+            return Err("Incorrect number of filter arguments".to_string());
+        }
+        let operator = if fold_case {
+            Operator::Contains
+        } else {
+            Operator::Equals
+        };
+        args.chunks(2)
+            .map(|pair| {
+                Ok(Self::Compare {
+                    tag: Tag::parse(&pair[0])
+                        .ok_or_else(|| format!("Unknown filter type: {}", pair[0]))?,
+                    operator,
+                    value: pair[1].clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(Self::And)
+    }
+
+    /// Whether a track matches. With `fold_case`, comparisons ignore case.
+    pub fn matches(&self, track: &TrackInfo, uri: &str, fold_case: bool) -> bool {
+        match self {
+            Self::And(filters) => filters.iter().all(|f| f.matches(track, uri, fold_case)),
+            Self::Not(filter) => !filter.matches(track, uri, fold_case),
+            Self::Compare {
+                tag: Tag::Any,
+                operator,
+                value,
+            } => [Tag::Artist, Tag::Album, Tag::Title, Tag::File]
+                .into_iter()
+                .any(|tag| compare(tag.value(track, uri), *operator, value, fold_case)),
+            Self::Compare {
+                tag,
+                operator,
+                value,
+            } => compare(tag.value(track, uri), *operator, value, fold_case),
+        }
+    }
+}
+
+fn compare(actual: Option<&str>, operator: Operator, expected: &str, fold_case: bool) -> bool {
+    let Some(actual) = actual else {
+        // A missing tag only satisfies "not equal"
+        return operator == Operator::NotEquals;
+    };
+    let (actual, expected) = if fold_case {
+        (actual.to_lowercase(), expected.to_lowercase())
+    } else {
+        (actual.to_string(), expected.to_string())
+    };
+    match operator {
+        Operator::Equals => actual == expected,
+        Operator::NotEquals => actual != expected,
+        Operator::Contains => actual.contains(&expected),
+        Operator::StartsWith => actual.starts_with(&expected),
+    }
+}
+
+/// Recursive descent parser for filter expressions
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn expression(&mut self) -> Result<Filter, String> {
+        self.skip_spaces();
+        if self.eat('!') {
+            return Ok(Filter::Not(Box::new(self.expression()?)));
+        }
+        if !self.eat('(') {
+            return Err("'(' expected".to_string());
+        }
+        self.skip_spaces();
+
+        // Either a group of sub-expressions joined by AND, or a comparison
+        let filter = if self.peek() == Some('(') || self.peek() == Some('!') {
+            let mut filters = vec![self.expression()?];
+            loop {
+                self.skip_spaces();
+                if self.eat_word("AND") {
+                    filters.push(self.expression()?);
+                } else {
+                    break;
+                }
+            }
+            Filter::And(filters)
+        } else {
+            let name = self.word();
+            let tag = Tag::parse(&name).ok_or_else(|| format!("Unknown filter type: {}", name))?;
+            self.skip_spaces();
+            let operator = self.operator()?;
+            self.skip_spaces();
+            let value = self.quoted()?;
+            Filter::Compare {
+                tag,
+                operator,
+                value,
+            }
+        };
+
+        self.skip_spaces();
+        if !self.eat(')') {
+            return Err("')' expected".to_string());
+        }
+        Ok(filter)
+    }
+
+    fn operator(&mut self) -> Result<Operator, String> {
+        for (token, operator) in [
+            ("==", Operator::Equals),
+            ("!=", Operator::NotEquals),
+            ("contains", Operator::Contains),
+            ("starts_with", Operator::StartsWith),
+        ] {
+            if self.eat_word(token) {
+                return Ok(operator);
+            }
+        }
+        Err(format!("Unknown operator near '{}'", self.word()))
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let quote = match self.peek() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Err("Quoted value expected".to_string()),
+        };
+        self.pos += 1;
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(escaped) = self.peek() {
+                        value.push(escaped);
+                        self.pos += 1;
+                    }
+                }
+                c if c == quote => return Ok(value),
+                c => value.push(c),
+            }
+        }
+        Err("Closing quote expected".to_string())
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let end = self.pos + word.chars().count();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn track(artist: &str, album: &str) -> TrackInfo {
+        TrackInfo {
+            path: PathBuf::from("/music/a.mp3"),
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+            title: Some("Song".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pairs() {
+        let track = track("Daft Punk", "Discovery");
+        let find = Filter::parse(&args(&["artist", "Daft Punk"]), false).unwrap();
+        assert!(find.matches(&track, "a.mp3", false));
+        let find = Filter::parse(&args(&["artist", "daft"]), false).unwrap();
+        assert!(!find.matches(&track, "a.mp3", false));
+
+        let search = Filter::parse(&args(&["any", "disco"]), true).unwrap();
+        assert!(search.matches(&track, "a.mp3", true));
+        assert!(Filter::parse(&args(&["artist"]), false).is_err());
+        assert!(Filter::parse(&args(&["mood", "happy"]), false).is_err());
+    }
+
+    #[test]
+    fn test_expressions() {
+        let track = track("Daft Punk", "Discovery");
+        let filter = Filter::parse(
+            &args(&[r#"((Artist == "Daft Punk") AND (Album != 'Homework'))"#]),
+            false,
+        )
+        .unwrap();
+        assert!(filter.matches(&track, "a.mp3", false));
+
+        let filter = Filter::parse(&args(&[r#"(!(file starts_with "b"))"#]), false).unwrap();
+        assert!(filter.matches(&track, "a.mp3", false));
+
+        let filter = Filter::parse(&args(&[r#"(Title == "Say \"Hi\"")"#]), false).unwrap();
+        assert_eq!(
+            filter,
+            Filter::Compare {
+                tag: Tag::Title,
+                operator: Operator::Equals,
+                value: "Say \"Hi\"".to_string(),
+            }
+        );
+
+        assert!(Filter::parse(&args(&["(Artist == \"X\""]), false).is_err());
+        let genre = Filter::parse(&args(&["(Genre == \"Rock\")"]), false).unwrap();
+        assert!(!genre.matches(&track, "a.mp3", false));
+    }
+}
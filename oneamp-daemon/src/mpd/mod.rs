@@ -0,0 +1,419 @@
+// MPD Protocol Server
+// Speaks the core of the Music Player Daemon protocol over TCP so existing
+// MPD clients (mpc, ncmpcpp, phone remotes) can drive the daemon.
+//
+// Each connection gets its own thread; commands are forwarded to the daemon
+// loop as `MpdCall`s, the same way the control socket forwards `IpcCall`s.
+
+mod commands;
+pub mod filter;
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{bounded, select, unbounded, Receiver, Sender};
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+/// Protocol version announced in the greeting
+pub const PROTOCOL_VERSION: &str = "0.21.0";
+
+/// Subsystems reported by `idle`
+pub const SUBSYSTEMS: [&str; 6] = [
+    "database", "playlist", "player", "mixer", "options", "update",
+];
+
+/// Error codes of `ACK` responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckCode {
+    Arg = 2,
+    Unknown = 5,
+    NoExist = 50,
+}
+
+/// A failed command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ack {
+    pub code: AckCode,
+    pub message: String,
+}
+
+impl Ack {
+    pub fn new(code: AckCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// `ACK [code@index] {command} message`
+    fn format(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{}] {{{}}} {}\n",
+            self.code as u8, index, command, self.message
+        )
+    }
+}
+
+/// `key: value` lines of a successful command, without the final `OK`
+pub type Response = Result<String, Ack>;
+
+/// A command received from a client, waiting for the daemon to answer it
+pub struct MpdCall {
+    /// Command name followed by its arguments
+    pub args: Vec<String>,
+    reply: Sender<Response>,
+}
+
+impl MpdCall {
+    pub fn reply(self, response: Response) {
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Subsystem changes a client has not been told about yet
+#[derive(Default)]
+struct ClientEvents {
+    pending: Mutex<BTreeSet<&'static str>>,
+    wake: Mutex<Option<Sender<()>>>,
+}
+
+/// TCP server speaking the MPD protocol
+pub struct MpdServer {
+    local_addr: SocketAddr,
+    calls: Receiver<MpdCall>,
+    clients: Arc<Mutex<Vec<Weak<ClientEvents>>>>,
+}
+
+impl MpdServer {
+    /// Listen on `addr` (port 0 picks a free port)
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+        let (call_tx, calls) = unbounded();
+        let clients: Arc<Mutex<Vec<Weak<ClientEvents>>>> = Arc::default();
+
+        let accept_clients = Arc::clone(&clients);
+        thread::Builder::new()
+            .name("mpd-accept".to_string())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let events = Arc::new(ClientEvents::default());
+                    let mut clients = accept_clients.lock().unwrap();
+                    clients.retain(|client| client.strong_count() > 0);
+                    clients.push(Arc::downgrade(&events));
+                    drop(clients);
+
+                    let calls = call_tx.clone();
+                    let _ =
+                        thread::Builder::new()
+                            .name("mpd-client".to_string())
+                            .spawn(move || {
+                                let _ = serve_client(stream, calls, events);
+                            });
+                }
+            })?;
+
+        Ok(Self {
+            local_addr,
+            calls,
+            clients,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Commands from all clients, for use in `select!`
+    pub fn calls(&self) -> &Receiver<MpdCall> {
+        &self.calls
+    }
+
+    /// Tell idling clients that subsystems changed
+    pub fn notify(&self, subsystems: &[&'static str]) {
+        if subsystems.is_empty() {
+            return;
+        }
+        for client in self.clients.lock().unwrap().iter() {
+            if let Some(client) = client.upgrade() {
+                client.pending.lock().unwrap().extend(subsystems);
+                if let Some(wake) = client.wake.lock().unwrap().as_ref() {
+                    let _ = wake.try_send(());
+                }
+            }
+        }
+    }
+}
+
+fn serve_client(
+    stream: TcpStream,
+    calls: Sender<MpdCall>,
+    events: Arc<ClientEvents>,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    writer.write_all(format!("OK MPD {}\n", PROTOCOL_VERSION).as_bytes())?;
+
+    // Lines are read on their own thread so `idle` can wait for a line
+    // (`noidle`) and for subsystem changes at the same time
+    let (line_tx, lines) = unbounded();
+    thread::Builder::new()
+        .name("mpd-reader".to_string())
+        .spawn(move || {
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+                let trimmed = line.trim_end_matches(['\n', '\r']).to_string();
+                if line_tx.send(trimmed).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        })?;
+
+    let (wake_tx, wake) = bounded(1);
+    *events.wake.lock().unwrap() = Some(wake_tx);
+
+    let client = Client {
+        calls,
+        events,
+        lines,
+        wake,
+    };
+    client.run(&mut writer)
+}
+
+struct Client {
+    calls: Sender<MpdCall>,
+    events: Arc<ClientEvents>,
+    lines: Receiver<String>,
+    wake: Receiver<()>,
+}
+
+impl Client {
+    fn run(&self, out: &mut TcpStream) -> Result<()> {
+        while let Ok(line) = self.lines.recv() {
+            let args = match split_args(&line) {
+                Ok(args) if !args.is_empty() => args,
+                Ok(_) => {
+                    out.write_all(
+                        Ack::new(AckCode::Unknown, "No command given")
+                            .format(0, "")
+                            .as_bytes(),
+                    )?;
+                    continue;
+                }
+                Err(ack) => {
+                    out.write_all(ack.format(0, "").as_bytes())?;
+                    continue;
+                }
+            };
+
+            match args[0].as_str() {
+                "close" => break,
+                "idle" => {
+                    if !self.idle(out, &args[1..])? {
+                        break;
+                    }
+                }
+                // Only meaningful while idling
+                "noidle" => {}
+                "command_list_begin" => self.command_list(out, false)?,
+                "command_list_ok_begin" => self.command_list(out, true)?,
+                _ => match self.execute(&args) {
+                    Some(Ok(response)) => {
+                        out.write_all(response.as_bytes())?;
+                        out.write_all(b"OK\n")?;
+                    }
+                    Some(Err(ack)) => out.write_all(ack.format(0, &args[0]).as_bytes())?,
+                    None => break,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a command. `None` means the daemon is shutting down.
+    fn execute(&self, args: &[String]) -> Option<Response> {
+        match args[0].as_str() {
+            "ping" => Some(Ok(String::new())),
+            "idle" | "noidle" | "close" | "command_list_begin" | "command_list_ok_begin" => {
+                Some(Err(Ack::new(
+                    AckCode::Arg,
+                    format!("{} not allowed here", args[0]),
+                )))
+            }
+            _ => {
+                let (reply, response) = bounded(1);
+                self.calls
+                    .send(MpdCall {
+                        args: args.to_vec(),
+                        reply,
+                    })
+                    .ok()?;
+                response.recv().ok()
+            }
+        }
+    }
+
+    /// Collect commands up to `command_list_end` and run them in order,
+    /// stopping at the first error
+    fn command_list(&self, out: &mut TcpStream, list_ok: bool) -> Result<()> {
+        let mut commands = Vec::new();
+        loop {
+            let Ok(line) = self.lines.recv() else {
+                return Ok(());
+            };
+            if line == "command_list_end" {
+                break;
+            }
+            commands.push(line);
+        }
+
+        let mut response = String::new();
+        for (index, line) in commands.iter().enumerate() {
+            let args = match split_args(line) {
+                Ok(args) if !args.is_empty() => args,
+                Ok(_) => vec![String::new()],
+                Err(ack) => {
+                    response.push_str(&ack.format(index, ""));
+                    out.write_all(response.as_bytes())?;
+                    return Ok(());
+                }
+            };
+            match self.execute(&args) {
+                Some(Ok(lines)) => {
+                    response.push_str(&lines);
+                    if list_ok {
+                        response.push_str("list_OK\n");
+                    }
+                }
+                Some(Err(ack)) => {
+                    response.push_str(&ack.format(index, &args[0]));
+                    out.write_all(response.as_bytes())?;
+                    return Ok(());
+                }
+                None => return Ok(()),
+            }
+        }
+        response.push_str("OK\n");
+        out.write_all(response.as_bytes())?;
+        Ok(())
+    }
+
+    /// Wait until one of `subsystems` (all when empty) changes, or until the
+    /// client sends `noidle`. Returns `false` if the client went away.
+    fn idle(&self, out: &mut TcpStream, subsystems: &[String]) -> Result<bool> {
+        for name in subsystems {
+            if !SUBSYSTEMS.contains(&name.as_str()) {
+                let ack = Ack::new(AckCode::Arg, format!("Unrecognized idle event: {}", name));
+                out.write_all(ack.format(0, "idle").as_bytes())?;
+                return Ok(true);
+            }
+        }
+
+        loop {
+            let changed: Vec<&'static str> = {
+                let mut pending = self.events.pending.lock().unwrap();
+                let changed: Vec<&'static str> = pending
+                    .iter()
+                    .copied()
+                    .filter(|s| subsystems.is_empty() || subsystems.iter().any(|name| name == s))
+                    .collect();
+                for subsystem in &changed {
+                    pending.remove(subsystem);
+                }
+                changed
+            };
+            if !changed.is_empty() {
+                let mut response: String = changed
+                    .iter()
+                    .map(|subsystem| format!("changed: {}\n", subsystem))
+                    .collect();
+                response.push_str("OK\n");
+                out.write_all(response.as_bytes())?;
+                return Ok(true);
+            }
+
+            select! {
+                recv(self.lines) -> line => match line {
+                    Ok(line) if line.trim() == "noidle" => {
+                        out.write_all(b"OK\n")?;
+                        return Ok(true);
+                    }
+                    // Anything else while idling is a protocol error
+                    _ => return Ok(false),
+                },
+                recv(self.wake) -> _ => {}
+            }
+        }
+    }
+}
+
+/// Split a command line into words. Arguments may be double-quoted, with
+/// backslash escapes inside quotes.
+pub fn split_args(line: &str) -> Result<Vec<String>, Ack> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let Some(&first) = chars.peek() else {
+            return Ok(args);
+        };
+
+        let mut arg = String::new();
+        if first == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => arg.push(c),
+                        None => return Err(missing_quote()),
+                    },
+                    Some(c) => arg.push(c),
+                    None => return Err(missing_quote()),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                arg.push(c);
+                chars.next();
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn missing_quote() -> Ack {
+    Ack::new(AckCode::Arg, "Missing closing '\"'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("status").unwrap(), vec!["status"]);
+        assert_eq!(
+            split_args(r#"find  artist "Daft \"Punk\"" album "a\\b""#).unwrap(),
+            vec!["find", "artist", "Daft \"Punk\"", "album", "a\\b"]
+        );
+        assert_eq!(split_args("   ").unwrap(), Vec::<String>::new());
+        assert!(split_args(r#"add "unterminated"#).is_err());
+    }
+
+    #[test]
+    fn test_ack_format() {
+        let ack = Ack::new(AckCode::NoExist, "No such song");
+        assert_eq!(ack.format(2, "play"), "ACK [50@2] {play} No such song\n");
+    }
+}
@@ -0,0 +1,259 @@
+// MPD protocol integration tests: run the daemon on a scratch music
+// directory and talk to it over TCP like an MPD client would.
+
+#![cfg(unix)]
+
+use oneamp_core::test_wav::write_silent;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A running daemon, killed when dropped
+struct Daemon {
+    child: Child,
+    port: u16,
+    dir: PathBuf,
+}
+
+impl Daemon {
+    fn start(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("oneamp_daemon_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        let music = dir.join("music");
+        std::fs::create_dir_all(music.join("Artist/Album")).unwrap();
+        write_silent(&music.join("Artist/Album/01 Intro.wav"), 8000);
+        write_silent(&music.join("Artist/Album/02 Outro.wav"), 8000);
+        write_silent(&music.join("single.wav"), 4000);
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_oneamp-daemon"))
+            .arg("--music-dir")
+            .arg(&music)
+            .arg("--socket")
+            .arg(dir.join("oneamp.sock"))
+            .args(["--mpd-port", "0"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        let port = loop {
+            line.clear();
+            assert!(stdout.read_line(&mut line).unwrap() > 0, "daemon exited");
+            if let Some(addr) = line.trim().strip_prefix("MPD server listening on ") {
+                break addr.rsplit(':').next().unwrap().parse().unwrap();
+            }
+        };
+
+        Self { child, port, dir }
+    }
+
+    fn connect(&self) -> Client {
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            stream,
+        };
+        assert!(client.read_line().starts_with("OK MPD "));
+
+        // Wait for the library scan
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while client.command("stats").unwrap().get("songs") != Some(&"3".to_string()) {
+            assert!(Instant::now() < deadline, "library scan timed out");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        client
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Client {
+    fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    fn send(&mut self, line: &str) {
+        writeln!(self.stream, "{}", line).unwrap();
+    }
+
+    /// Response lines up to `OK`, or the `ACK` line as an error
+    fn lines(&mut self, command: &str) -> Result<Vec<(String, String)>, String> {
+        self.send(command);
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line();
+            if line == "OK" {
+                return Ok(lines);
+            }
+            if line.starts_with("ACK ") {
+                return Err(line);
+            }
+            let (key, value) = line.split_once(": ").expect("malformed response line");
+            lines.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    fn command(
+        &mut self,
+        command: &str,
+    ) -> Result<std::collections::HashMap<String, String>, String> {
+        self.lines(command).map(|lines| lines.into_iter().collect())
+    }
+
+    fn values(&mut self, command: &str, key: &str) -> Vec<String> {
+        self.lines(command)
+            .unwrap()
+            .into_iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .collect()
+    }
+}
+
+#[test]
+fn test_status_and_playlist() {
+    let daemon = Daemon::start("playlist");
+    let mut client = daemon.connect();
+
+    let status = client.command("status").unwrap();
+    assert_eq!(status["state"], "stop");
+    assert_eq!(status["playlistlength"], "0");
+    let version: u32 = status["playlist"].parse().unwrap();
+
+    client.command("add Artist").unwrap();
+    client.command("add \"single.wav\"").unwrap();
+    assert!(client
+        .command("add missing.wav")
+        .unwrap_err()
+        .starts_with("ACK [50@0] {add}"));
+
+    let status = client.command("status").unwrap();
+    assert_eq!(status["playlistlength"], "3");
+    assert!(status["playlist"].parse::<u32>().unwrap() > version);
+    assert_eq!(
+        client.values("playlistinfo", "file"),
+        [
+            "Artist/Album/01 Intro.wav",
+            "Artist/Album/02 Outro.wav",
+            "single.wav"
+        ]
+    );
+    assert_eq!(client.values("playlistinfo 1:", "Pos"), ["1", "2"]);
+    assert_eq!(client.values("playlistinfo 2", "Time"), ["1"]);
+
+    // No audio device in CI, so only check that the entry became current
+    client.command("play 1").unwrap();
+    let song = client.command("currentsong").unwrap();
+    assert_eq!(song["file"], "Artist/Album/02 Outro.wav");
+    assert_eq!(song["Pos"], "1");
+    assert!(client.command("play 7").is_err());
+
+    // Ids stay with their entries when positions shift
+    let ids = client.values("playlistinfo", "Id");
+    client.command("delete 0").unwrap();
+    assert_eq!(client.values("playlistinfo", "Pos"), ["0", "1"]);
+    assert_eq!(client.values("playlistinfo", "Id"), ids[1..]);
+    client.command(&format!("playid {}", ids[2])).unwrap();
+    assert_eq!(client.command("status").unwrap()["songid"], ids[2]);
+    assert_eq!(
+        client.values(&format!("playlistid {}", ids[2]), "file"),
+        ["single.wav"]
+    );
+    assert!(client.command(&format!("playid {}", ids[0])).is_err());
+    client.command("clear").unwrap();
+    assert_eq!(client.command("status").unwrap()["playlistlength"], "0");
+
+    // A song already in the playlist is queued again with a new id
+    let first = client.values("addid \"single.wav\"", "Id");
+    let second = client.values("addid \"single.wav\" 0", "Id");
+    assert_eq!(first.len(), 1);
+    assert_eq!(second.len(), 1);
+    assert_ne!(first, second);
+    assert_eq!(
+        client.values("playlistinfo", "Id"),
+        [second[0].as_str(), &first[0]]
+    );
+}
+
+#[test]
+fn test_database_queries() {
+    let daemon = Daemon::start("database");
+    let mut client = daemon.connect();
+
+    assert_eq!(
+        client.values("find file \"single.wav\"", "file"),
+        ["single.wav"]
+    );
+    assert_eq!(client.values("search file INTRO", "file").len(), 1);
+    assert_eq!(
+        client.values("search \"(file starts_with 'Artist/')\"", "file"),
+        ["Artist/Album/01 Intro.wav", "Artist/Album/02 Outro.wav"]
+    );
+    assert_eq!(client.values("list file", "file").len(), 3);
+    assert!(client.values("find artist Nobody", "file").is_empty());
+    assert!(client.command("find mood happy").is_err());
+    assert!(client
+        .command("frobnicate")
+        .unwrap_err()
+        .contains("{frobnicate}"));
+}
+
+#[test]
+fn test_command_lists_and_idle() {
+    let daemon = Daemon::start("idle");
+    let mut client = daemon.connect();
+
+    // command_list_ok_begin separates the responses with list_OK
+    client.send("command_list_ok_begin");
+    client.send("ping");
+    client.send("add single.wav");
+    client.send("command_list_end");
+    assert_eq!(client.read_line(), "list_OK");
+    assert_eq!(client.read_line(), "list_OK");
+    assert_eq!(client.read_line(), "OK");
+
+    // Errors report the failing command's position in the list
+    client.send("command_list_begin");
+    client.send("ping");
+    client.send("play 9");
+    client.send("command_list_end");
+    assert!(client.read_line().starts_with("ACK [2@1] {play}"));
+
+    // Changes made by another client wake an idling one
+    let mut other = daemon.connect();
+    assert_eq!(
+        client.values("idle playlist", "changed"),
+        ["playlist"],
+        "pending changes are reported right away"
+    );
+    client.send("idle playlist options");
+    std::thread::sleep(Duration::from_millis(100));
+    other.command("random 1").unwrap();
+    assert_eq!(client.read_line(), "changed: options");
+    assert_eq!(client.read_line(), "OK");
+
+    client.send("idle mixer");
+    client.send("noidle");
+    assert_eq!(client.read_line(), "OK");
+    assert_eq!(client.command("status").unwrap()["random"], "1");
+}
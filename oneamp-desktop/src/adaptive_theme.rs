@@ -0,0 +1,433 @@
+// Adaptive Theme
+// Optional tinting of the theme with the colors of the playing track's
+// cover: a median cut over the decoded album art gives a dominant and an
+// accent color, and the theme's accent, visualizer and selection colors
+// fade toward them. Colors are adjusted until they meet the WCAG contrast
+// ratio for text against the theme's backgrounds.
+
+use crate::animations::AnimatedColor;
+use crate::theme::{ColorScheme, Theme};
+use egui::ColorImage;
+
+/// WCAG AA contrast ratio for normal text
+pub const MIN_CONTRAST: f32 = 4.5;
+
+/// Pixels sampled from a cover, evenly spread
+const MAX_SAMPLES: usize = 4096;
+/// Color boxes the median cut splits the samples into
+const PALETTE_SIZE: usize = 8;
+/// Share of the samples a box needs to be picked as the accent
+const MIN_ACCENT_SHARE: f32 = 0.02;
+/// Fraction of the way to the target covered per frame at 60 FPS
+const FADE_SPEED: f32 = 0.06;
+/// How far visualizer gradient stops move toward the accent, keeping some
+/// of the skin's quiet-to-loud progression
+const GRADIENT_TINT: f32 = 0.6;
+
+/// Colors picked from a cover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Most common color
+    pub dominant: [u8; 3],
+    /// Most colorful color that isn't a speck
+    pub accent: [u8; 3],
+}
+
+impl Palette {
+    /// Median cut over the opaque pixels of an image; `None` if it has none
+    pub fn from_image(image: &ColorImage) -> Option<Self> {
+        let step = (image.pixels.len() / MAX_SAMPLES).max(1);
+        let samples: Vec<[u8; 3]> = image
+            .pixels
+            .iter()
+            .step_by(step)
+            .filter(|pixel| pixel.a() >= 128)
+            .map(|pixel| [pixel.r(), pixel.g(), pixel.b()])
+            .collect();
+        if samples.is_empty() {
+            return None;
+        }
+        let total = samples.len();
+
+        // Split the box with the widest channel range at its median
+        let mut boxes = vec![samples];
+        while boxes.len() < PALETTE_SIZE {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .map(|(index, pixels)| {
+                    let (channel, range) = widest_channel(pixels);
+                    (range, index, channel)
+                })
+                .filter(|&(range, _, _)| range > 0)
+                .max();
+            let Some((_, index, channel)) = widest else {
+                break;
+            };
+            let mut pixels = boxes.swap_remove(index);
+            pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+            let upper = pixels.split_off(pixels.len() / 2);
+            boxes.push(pixels);
+            boxes.push(upper);
+        }
+
+        let swatches: Vec<([u8; 3], f32)> = boxes
+            .iter()
+            .map(|pixels| (average(pixels), pixels.len() as f32 / total as f32))
+            .collect();
+        let dominant = swatches
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|&(color, _)| color)?;
+        let score = |&(color, share): &([u8; 3], f32)| chroma(color) * share.sqrt();
+        let accent = swatches
+            .iter()
+            .filter(|&&(_, share)| share >= MIN_ACCENT_SHARE)
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .map_or(dominant, |&(color, _)| color);
+        Some(Self { dominant, accent })
+    }
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+            (channel, max.saturating_sub(min))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for pixel in pixels {
+        for (total, &value) in sum.iter_mut().zip(pixel) {
+            *total += u64::from(value);
+        }
+    }
+    let count = pixels.len().max(1) as u64;
+    sum.map(|total| (total / count) as u8)
+}
+
+/// Difference between the strongest and weakest channel, 0.0 - 1.0
+fn chroma(color: [u8; 3]) -> f32 {
+    let max = color.iter().max().copied().unwrap_or(0);
+    let min = color.iter().min().copied().unwrap_or(0);
+    f32::from(max - min) / 255.0
+}
+
+/// WCAG relative luminance of an sRGB color
+pub fn relative_luminance(color: [u8; 3]) -> f32 {
+    let linear = |value: u8| {
+        let c = f32::from(value) / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2])
+}
+
+/// WCAG contrast ratio, from 1.0 (same luminance) to 21.0 (black on white)
+pub fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Lighten or darken a color, whichever takes less, until it has at least
+/// `min_ratio` contrast against every background
+pub fn ensure_contrast(color: [u8; 3], backgrounds: &[[u8; 3]], min_ratio: f32) -> [u8; 3] {
+    let worst = |color: [u8; 3]| {
+        backgrounds
+            .iter()
+            .map(|&background| contrast_ratio(color, background))
+            .fold(f32::INFINITY, f32::min)
+    };
+    if worst(color) >= min_ratio {
+        return color;
+    }
+
+    const STEPS: u8 = 50;
+    let toward = |target: u8| {
+        (1..=STEPS)
+            .map(|step| mix(color, [target; 3], f32::from(step) / f32::from(STEPS)))
+            .position(|mixed| worst(mixed) >= min_ratio)
+    };
+    match (toward(u8::MAX), toward(0)) {
+        (Some(light), Some(dark)) if dark < light => mix_step(color, 0, dark, STEPS),
+        (Some(light), _) => mix_step(color, u8::MAX, light, STEPS),
+        (None, Some(dark)) => mix_step(color, 0, dark, STEPS),
+        // Neither gets there: take the more readable extreme
+        (None, None) if worst([u8::MAX; 3]) >= worst([0; 3]) => [u8::MAX; 3],
+        (None, None) => [0; 3],
+    }
+}
+
+fn mix_step(color: [u8; 3], target: u8, index: usize, steps: u8) -> [u8; 3] {
+    mix(color, [target; 3], (index + 1) as f32 / f32::from(steps))
+}
+
+fn mix(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    [0, 1, 2].map(|i| (f32::from(a[i]) + (f32::from(b[i]) - f32::from(a[i])) * t).round() as u8)
+}
+
+/// Theme colors fading toward the palette of the current cover
+pub struct AdaptiveTheme {
+    enabled: bool,
+    /// Colors of the theme without tinting
+    base: ColorScheme,
+    palette: Option<Palette>,
+    accent: AnimatedColor,
+    selection: AnimatedColor,
+    /// One per visualizer gradient stop
+    gradient: Vec<AnimatedColor>,
+}
+
+impl AdaptiveTheme {
+    pub fn new(theme: &Theme, enabled: bool) -> Self {
+        Self {
+            enabled,
+            base: theme.colors.clone(),
+            palette: None,
+            accent: AnimatedColor::new(theme.colors.display_accent, FADE_SPEED),
+            selection: AnimatedColor::new(theme.colors.playlist_selected, FADE_SPEED),
+            gradient: animated(&theme.colors.visualizer_gradient),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn tinting on or off; the theme fades back when turned off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Use the colors of a new untinted theme, e.g. after a skin change; the
+    /// tint fades from where it is toward the new targets
+    pub fn set_base(&mut self, theme: &Theme) {
+        self.base = theme.colors.clone();
+    }
+
+    /// Pick the palette of a new cover, or fade back without one
+    pub fn set_cover(&mut self, image: Option<&ColorImage>) {
+        self.palette = image.and_then(Palette::from_image);
+    }
+
+    /// Colors to fade toward: readable palette colors, or the base theme's
+    fn targets(&self) -> ([u8; 3], [u8; 3]) {
+        let base = &self.base;
+        match self.palette.filter(|_| self.enabled) {
+            Some(palette) => (
+                ensure_contrast(
+                    palette.accent,
+                    &[base.display_bg, base.playlist_bg, base.panel_bg],
+                    MIN_CONTRAST,
+                ),
+                // Selected rows keep their text color
+                ensure_contrast(palette.dominant, &[base.playlist_text], MIN_CONTRAST),
+            ),
+            None => (base.display_accent, base.playlist_selected),
+        }
+    }
+
+    /// Visualizer gradient to fade toward: the base stops tinted with the
+    /// palette's accent and kept readable on the display, or the base stops
+    fn gradient_targets(&self) -> Vec<[u8; 3]> {
+        let base = &self.base;
+        match self.palette.filter(|_| self.enabled) {
+            Some(palette) => base
+                .visualizer_gradient
+                .iter()
+                .map(|&stop| {
+                    ensure_contrast(
+                        mix(stop, palette.accent, GRADIENT_TINT),
+                        &[base.display_bg],
+                        MIN_CONTRAST,
+                    )
+                })
+                .collect(),
+            None => base.visualizer_gradient.clone(),
+        }
+    }
+
+    /// Advance the fade by `dt` seconds and write the colors into the theme.
+    /// Returns true while colors are still changing.
+    pub fn update(&mut self, theme: &mut Theme, dt: f32) -> bool {
+        let (accent, selection) = self.targets();
+        self.accent.set_target(accent);
+        self.selection.set_target(selection);
+        // A long frame shouldn't overshoot the target
+        let dt = dt.min(0.1);
+        self.accent.update(dt);
+        self.selection.update(dt);
+
+        // A skin with another number of stops starts from its own gradient
+        let gradient = self.gradient_targets();
+        if self.gradient.len() != gradient.len() {
+            self.gradient = animated(&self.base.visualizer_gradient);
+        }
+        for (stop, &target) in self.gradient.iter_mut().zip(&gradient) {
+            stop.set_target(target);
+            stop.update(dt);
+        }
+
+        let animating = self.accent.is_animating()
+            || self.selection.is_animating()
+            || self.gradient.iter().any(AnimatedColor::is_animating);
+        // Settle exactly on the target so untinted colors match the theme
+        let accent = if self.accent.is_animating() {
+            self.accent.get_u8()
+        } else {
+            accent
+        };
+        let selection = if self.selection.is_animating() {
+            self.selection.get_u8()
+        } else {
+            selection
+        };
+        let colors = &mut theme.colors;
+        colors.display_accent = accent;
+        colors.progress_fill = accent;
+        colors.eq_fill = accent;
+        colors.playlist_playing = accent;
+        colors.playlist_selected = selection;
+        colors.visualizer_gradient = self
+            .gradient
+            .iter()
+            .zip(gradient)
+            .map(|(stop, target)| {
+                if stop.is_animating() {
+                    stop.get_u8()
+                } else {
+                    target
+                }
+            })
+            .collect();
+        animating
+    }
+}
+
+fn animated(gradient: &[[u8; 3]]) -> Vec<AnimatedColor> {
+    gradient
+        .iter()
+        .map(|&stop| AnimatedColor::new(stop, FADE_SPEED))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+
+    /// 100 pixels of `main`, `spots` pixels of `spot`
+    fn image(main: [u8; 3], spot: [u8; 3], spots: usize) -> ColorImage {
+        let pixel = |[r, g, b]: [u8; 3]| Color32::from_rgb(r, g, b);
+        let mut pixels = vec![pixel(main); 100 - spots];
+        pixels.extend(std::iter::repeat_n(pixel(spot), spots));
+        ColorImage {
+            size: [10, 10],
+            pixels,
+        }
+    }
+
+    #[test]
+    fn test_palette_from_image() {
+        let palette = Palette::from_image(&image([20, 20, 30], [230, 40, 40], 20)).unwrap();
+        assert_eq!(palette.dominant, [20, 20, 30]);
+        assert_eq!(palette.accent, [230, 40, 40]);
+
+        // A speck of color isn't an accent
+        let palette = Palette::from_image(&image([90, 90, 90], [0, 255, 0], 1)).unwrap();
+        assert_eq!(palette.accent, [90, 90, 90]);
+
+        let transparent = ColorImage {
+            size: [2, 2],
+            pixels: vec![Color32::TRANSPARENT; 4],
+        };
+        assert_eq!(Palette::from_image(&transparent), None);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        assert!((contrast_ratio([0; 3], [255; 3]) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio([255; 3], [0; 3]) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio([120, 40, 200], [120, 40, 200]) - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_ensure_contrast() {
+        // Readable colors are kept
+        assert_eq!(
+            ensure_contrast([255, 200, 0], &[[20, 20, 25]], MIN_CONTRAST),
+            [255, 200, 0]
+        );
+
+        // A dark red on a dark background is lightened, a pale yellow on a
+        // light one darkened
+        let dark_bg = [20, 20, 25];
+        let lightened = ensure_contrast([90, 10, 10], &[dark_bg], MIN_CONTRAST);
+        assert!(contrast_ratio(lightened, dark_bg) >= MIN_CONTRAST);
+        assert!(relative_luminance(lightened) > relative_luminance([90, 10, 10]));
+
+        let light_bg = [245, 245, 240];
+        let darkened = ensure_contrast([250, 240, 150], &[light_bg], MIN_CONTRAST);
+        assert!(contrast_ratio(darkened, light_bg) >= MIN_CONTRAST);
+        assert!(relative_luminance(darkened) < relative_luminance([250, 240, 150]));
+
+        // Mid grays are readable on both black and white
+        let both = ensure_contrast([128, 128, 128], &[[0; 3], [255; 3]], MIN_CONTRAST);
+        assert!(contrast_ratio(both, [0; 3]) >= MIN_CONTRAST);
+        assert!(contrast_ratio(both, [255; 3]) >= MIN_CONTRAST);
+
+        // Nothing reaches 7:1 on a mid gray: the more readable extreme is used
+        assert_eq!(ensure_contrast([128; 3], &[[118; 3]], 7.0), [0; 3]);
+    }
+
+    #[test]
+    fn test_adaptive_theme_fades_and_restores() {
+        let mut theme = Theme::default();
+        let base = theme.colors.clone();
+        let mut adaptive = AdaptiveTheme::new(&theme, true);
+        let cover = image([10, 10, 10], [40, 60, 230], 30);
+
+        // Nothing changes without a cover
+        assert!(!adaptive.update(&mut theme, 1.0 / 60.0));
+        assert_eq!(theme.colors.display_accent, base.display_accent);
+
+        adaptive.set_cover(Some(&cover));
+        assert!(adaptive.update(&mut theme, 1.0 / 60.0));
+        for _ in 0..600 {
+            adaptive.update(&mut theme, 1.0 / 60.0);
+        }
+        assert!(!adaptive.update(&mut theme, 1.0 / 60.0));
+        let accent = theme.colors.display_accent;
+        assert_ne!(accent, base.display_accent);
+        assert_eq!(theme.colors.progress_fill, accent);
+        assert_eq!(theme.colors.playlist_playing, accent);
+        for background in [base.display_bg, base.playlist_bg, base.panel_bg] {
+            assert!(contrast_ratio(accent, background) >= MIN_CONTRAST);
+        }
+        assert!(contrast_ratio(theme.colors.playlist_selected, base.playlist_text) >= MIN_CONTRAST);
+        let gradient = &theme.colors.visualizer_gradient;
+        assert_eq!(gradient.len(), base.visualizer_gradient.len());
+        assert_ne!(gradient, &base.visualizer_gradient);
+        for &stop in gradient {
+            assert!(contrast_ratio(stop, base.display_bg) >= MIN_CONTRAST);
+        }
+
+        // Turning it off fades back to the theme's own colors
+        adaptive.set_enabled(false);
+        for _ in 0..600 {
+            adaptive.update(&mut theme, 1.0 / 60.0);
+        }
+        assert_eq!(theme.colors.display_accent, base.display_accent);
+        assert_eq!(theme.colors.playlist_selected, base.playlist_selected);
+        assert_eq!(theme.colors.visualizer_gradient, base.visualizer_gradient);
+    }
+}
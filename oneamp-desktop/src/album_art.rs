@@ -2,14 +2,16 @@ use crate::theme::Theme;
 use crate::visual_effects::VisualEffects;
 use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions, Ui};
 use eframe::egui::{Color32, Painter, Pos2, Rect, Vec2};
-use std::path::Path;
+use oneamp_core::TrackInfo;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// Album art display with reflection effect
 pub struct AlbumArtDisplay {
     texture: Option<TextureHandle>,
     image_data: Option<Arc<ColorImage>>,
-    last_track_path: Option<String>,
+    /// Track and cover the image was loaded for
+    loaded: Option<(PathBuf, Option<PathBuf>)>,
 }
 
 impl AlbumArtDisplay {
@@ -17,23 +19,26 @@ impl AlbumArtDisplay {
         Self {
             texture: None,
             image_data: None,
-            last_track_path: None,
+            loaded: None,
         }
     }
 
-    /// Load album art from a track file
-    pub fn load_from_track(&mut self, track_path: &Path, ctx: &egui::Context) {
-        let path_str = track_path.to_string_lossy().to_string();
-
-        // Skip if already loaded for this track
-        if self.last_track_path.as_ref() == Some(&path_str) {
-            return;
+    /// Load the cover image the core found for a track. Returns true when
+    /// the track or its cover changed, and the image with them. The core
+    /// finds covers after loading a track, so one may show up later.
+    pub fn load_from_track(&mut self, track: &TrackInfo, ctx: &egui::Context) -> bool {
+        // Skip if already loaded for this track and cover
+        if self
+            .loaded
+            .as_ref()
+            .is_some_and(|(path, cover_art)| *path == track.path && *cover_art == track.cover_art)
+        {
+            return false;
         }
 
-        self.last_track_path = Some(path_str);
+        self.loaded = Some((track.path.clone(), track.cover_art.clone()));
 
-        // Try to extract album art using lofty
-        match extract_album_art(track_path) {
+        match track.cover_art.as_deref().and_then(load_image) {
             Some(image_data) => {
                 self.image_data = Some(Arc::new(image_data.clone()));
                 self.texture =
@@ -45,6 +50,12 @@ impl AlbumArtDisplay {
                 self.image_data = None;
             }
         }
+        true
+    }
+
+    /// Decoded cover of the current track
+    pub fn image(&self) -> Option<&ColorImage> {
+        self.image_data.as_deref()
     }
 
     /// Render the album art with reflection effect
@@ -114,31 +125,9 @@ impl Default for AlbumArtDisplay {
     }
 }
 
-/// Extract album art from audio file using lofty
-fn extract_album_art(path: &Path) -> Option<ColorImage> {
-    use lofty::file::TaggedFileExt;
-    use lofty::picture::Picture;
-    use lofty::probe::Probe;
-
-    // Read the audio file
-    let tagged_file = Probe::open(path).ok()?.read().ok()?;
-
-    // Try to get the first picture
-    let picture: &Picture = tagged_file
-        .primary_tag()
-        .and_then(|tag| tag.pictures().first())
-        .or_else(|| {
-            tagged_file
-                .tags()
-                .iter()
-                .find_map(|tag| tag.pictures().first())
-        })?;
-
-    // Decode image data
-    let image_data = picture.data();
-
-    // Try to decode with image crate
-    let img = image::load_from_memory(image_data).ok()?;
+/// Decode a cover image file
+fn load_image(path: &Path) -> Option<ColorImage> {
+    let img = image::open(path).ok()?;
     let rgba = img.to_rgba8();
 
     let size = [rgba.width() as usize, rgba.height() as usize];
@@ -242,7 +231,7 @@ mod tests {
     fn test_album_art_display_creation() {
         let display = AlbumArtDisplay::new();
         assert!(!display.has_art());
-        assert!(display.last_track_path.is_none());
+        assert!(display.loaded.is_none());
     }
 
     #[test]
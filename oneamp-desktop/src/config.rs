@@ -1,7 +1,18 @@
 use anyhow::{Context, Result};
+use oneamp_core::scrobble::ScrobbleConfig;
+use oneamp_core::{RepeatMode, ShuffleMode};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::playlist_tabs::{PlaylistSort, SmartRule};
+
+/// Current config schema version.
+/// Bump this and add a step to `migrate` when the layout changes.
+pub const CONFIG_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EqualizerConfig {
@@ -18,13 +29,85 @@ impl Default for EqualizerConfig {
     }
 }
 
+/// Window position and size, in logical points
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// Outer position (not available on Wayland)
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+    pub maximized: bool,
+}
+
+/// A playlist tab and its view state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistTabConfig {
+    pub name: String,
+    pub entries: Vec<PathBuf>,
+    pub current_index: Option<usize>,
+    pub selected_index: Option<usize>,
+    pub scroll_offset: f32,
+    pub sort: Option<PlaylistSort>,
+    pub shuffle: bool,
+    pub shuffle_mode: ShuffleMode,
+    pub repeat: RepeatMode,
+    /// Rule the entries come from, for smart playlists
+    pub smart: Option<SmartRule>,
+}
+
+/// Music folder and play statistics settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryConfig {
+    /// Folder smart playlists pick tracks from, the system music folder if unset
+    pub music_dir: Option<PathBuf>,
+    /// Also write ratings and play counts into the files' tags
+    pub sync_tags: bool,
+}
+
+/// Playback state restored on the next launch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub tabs: Vec<PlaylistTabConfig>,
+    /// Tab shown in the playlist section
+    pub active_tab: usize,
+    /// Tab the current track belongs to
+    pub playing_tab: usize,
+    pub position_secs: f32,
+    pub window: Option<WindowGeometry>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// Schema version, 0 for configs written before versioning
+    #[serde(default)]
+    pub version: u32,
     pub equalizer: EqualizerConfig,
     #[serde(default = "default_first_run")]
     pub first_run: bool,
     #[serde(default = "default_active_skin")]
     pub active_skin: String,
+    /// Start playing the restored track instead of waiting paused
+    #[serde(default)]
+    pub auto_resume: bool,
+    #[serde(default)]
+    pub session: SessionConfig,
+    /// Last.fm and ListenBrainz accounts
+    #[serde(default)]
+    pub scrobbling: ScrobbleConfig,
+    /// Music folder and tag syncing of play statistics
+    #[serde(default)]
+    pub library: LibraryConfig,
+    /// Delay applied to synchronized lyrics, for the audio output latency
+    #[serde(default = "default_lyrics_latency_ms")]
+    pub lyrics_latency_ms: i32,
+    /// Tint the theme with the colors of the playing track's cover
+    #[serde(default)]
+    pub adaptive_theme: bool,
+    /// Pixel scale of classic `.wsz` skins, 1 or 2
+    #[serde(default = "default_classic_skin_scale")]
+    pub classic_skin_scale: u8,
 }
 
 fn default_active_skin() -> String {
@@ -35,16 +118,90 @@ fn default_first_run() -> bool {
     true
 }
 
+fn default_classic_skin_scale() -> u8 {
+    1
+}
+
+fn default_lyrics_latency_ms() -> i32 {
+    (oneamp_core::lyrics::DEFAULT_LATENCY_SECS * 1000.0) as i32
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             equalizer: EqualizerConfig::default(),
             first_run: true,
             active_skin: default_active_skin(),
+            auto_resume: false,
+            session: SessionConfig::default(),
+            scrobbling: ScrobbleConfig::default(),
+            library: LibraryConfig::default(),
+            lyrics_latency_ms: default_lyrics_latency_ms(),
+            adaptive_theme: false,
+            classic_skin_scale: default_classic_skin_scale(),
         }
     }
 }
 
+/// Upgrade a raw config document to the current schema version
+fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    let mut version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+
+    if version > CONFIG_VERSION {
+        eprintln!(
+            "Config was written by a newer OneAmp (schema {}), unknown fields will be dropped",
+            version
+        );
+        return value;
+    }
+
+    while version < CONFIG_VERSION {
+        match version {
+            // 0 -> 1: schema versioning and session restore.
+            // New fields all have serde defaults, nothing to convert.
+            0 => {}
+            // 1 -> 2: the single session playlist becomes the first tab
+            1 => migrate_single_playlist_to_tabs(&mut value),
+            _ => unreachable!("missing config migration from version {}", version),
+        }
+        version += 1;
+    }
+
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), CONFIG_VERSION.into());
+    }
+    value
+}
+
+/// Move the v1 `session.playlist` and its playback settings into `session.tabs`
+fn migrate_single_playlist_to_tabs(value: &mut serde_json::Value) {
+    let Some(session) = value.get_mut("session").and_then(|s| s.as_object_mut()) else {
+        return;
+    };
+
+    let mut tab = serde_json::Map::new();
+    tab.insert("name".to_string(), "Default".into());
+    if let Some(entries) = session.remove("playlist") {
+        tab.insert("entries".to_string(), entries);
+    }
+    for key in ["current_index", "shuffle", "shuffle_mode", "repeat"] {
+        if let Some(field) = session.remove(key) {
+            tab.insert(key.to_string(), field);
+        }
+    }
+    if let Some(current) = tab.get("current_index").cloned() {
+        tab.insert("selected_index".to_string(), current);
+    }
+
+    session.insert(
+        "tabs".to_string(),
+        vec![serde_json::Value::Object(tab)].into(),
+    );
+    session.insert("active_tab".to_string(), 0.into());
+    session.insert("playing_tab".to_string(), 0.into());
+}
+
 impl AppConfig {
     /// Get the config file path
     fn config_path() -> Result<PathBuf> {
@@ -66,7 +223,7 @@ impl AppConfig {
             Ok(path) => {
                 if path.exists() {
                     match fs::read_to_string(&path) {
-                        Ok(content) => match serde_json::from_str::<AppConfig>(&content) {
+                        Ok(content) => match Self::parse(&content) {
                             Ok(mut config) => {
                                 let is_first = config.first_run;
                                 config.first_run = false;
@@ -85,15 +242,126 @@ impl AppConfig {
         (Self::default(), true)
     }
 
+    /// Parse a config document, migrating it from older schema versions
+    fn parse(content: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        serde_json::from_value(migrate(value))
+    }
+
     /// Save configuration to file
     pub fn save(&self) -> Result<()> {
+        Self::write(&self.to_json()?)
+    }
+
+    fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize config")
+    }
+
+    /// Replace the config file with serialized config
+    fn write(content: &str) -> Result<()> {
         let path = Self::config_path()?;
-        let content = serde_json::to_string_pretty(self).context("Failed to serialize config")?;
-        fs::write(&path, content).context("Failed to write config file")?;
+
+        // Write to a temporary file first so a crash mid-write can't corrupt the config
+        let tmp_path = path.with_extension("json.tmp");
+        write_private(&tmp_path, content).context("Failed to write config file")?;
+        fs::rename(&tmp_path, &path).context("Failed to replace config file")?;
         Ok(())
     }
 }
 
+/// Saves the config on a background thread so large playlists don't hold up
+/// frames. Saves queued while one is written collapse into the newest, and a
+/// config identical to the last one written is skipped.
+pub struct ConfigWriter {
+    sender: Option<mpsc::Sender<AppConfig>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ConfigWriter {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel::<AppConfig>();
+        let spawned = thread::Builder::new()
+            .name("oneamp-config".to_string())
+            .spawn(move || {
+                let mut written: Option<String> = None;
+                while let Ok(mut config) = receiver.recv() {
+                    while let Ok(newer) = receiver.try_recv() {
+                        config = newer;
+                    }
+                    let result = config.to_json().and_then(|content| {
+                        if written.as_ref() != Some(&content) {
+                            AppConfig::write(&content)?;
+                            written = Some(content);
+                        }
+                        Ok(())
+                    });
+                    if let Err(e) = result {
+                        eprintln!("Failed to save configuration: {:#}", e);
+                    }
+                }
+            });
+        match spawned {
+            Ok(thread) => Self {
+                sender: Some(sender),
+                thread: Some(thread),
+            },
+            Err(e) => {
+                eprintln!("Failed to start the config writer: {}", e);
+                Self {
+                    sender: None,
+                    thread: None,
+                }
+            }
+        }
+    }
+
+    /// Queue a config to be written. Without a writer thread it is written
+    /// right away.
+    pub fn save(&self, config: AppConfig) {
+        let unsent = match &self.sender {
+            Some(sender) => sender.send(config).err().map(|e| e.0),
+            None => Some(config),
+        };
+        if let Some(config) = unsent {
+            if let Err(e) = config.save() {
+                eprintln!("Failed to save configuration: {:#}", e);
+            }
+        }
+    }
+
+    /// Write the queued config and stop the writer, e.g. before exiting
+    pub fn finish(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Default for ConfigWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ConfigWriter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Write a file only its owner can read: the config holds scrobbling
+/// credentials. A leftover file is replaced so it can't keep looser
+/// permissions.
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content.as_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,6 +377,25 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!(
+            "oneamp_config_private_{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, "stale").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "{}").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_app_config_default() {
         let config = AppConfig::default();
@@ -143,6 +430,7 @@ mod tests {
             },
             first_run: false,
             active_skin: "Winamp5 Classified".to_string(),
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&config).expect("Should serialize");
@@ -153,6 +441,115 @@ mod tests {
         assert_eq!(config.active_skin, deserialized.active_skin);
     }
 
+    #[test]
+    fn test_session_persistence() {
+        let config = AppConfig {
+            auto_resume: true,
+            session: SessionConfig {
+                tabs: vec![
+                    PlaylistTabConfig {
+                        name: "Default".to_string(),
+                        entries: vec![
+                            PathBuf::from("/music/a.flac"),
+                            PathBuf::from("/music/b.flac"),
+                        ],
+                        current_index: Some(1),
+                        shuffle: true,
+                        shuffle_mode: ShuffleMode::Albums,
+                        repeat: RepeatMode::All,
+                        ..Default::default()
+                    },
+                    PlaylistTabConfig {
+                        name: "Party".to_string(),
+                        smart: Some(SmartRule {
+                            rule: "rating >= 4 ORDER BY random LIMIT 50".to_string(),
+                            seed: 7,
+                        }),
+                        ..Default::default()
+                    },
+                ],
+                active_tab: 1,
+                playing_tab: 0,
+                position_secs: 42.5,
+                window: Some(WindowGeometry {
+                    position: Some([100.0, 50.0]),
+                    size: [800.0, 600.0],
+                    maximized: false,
+                }),
+            },
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&config).expect("Should serialize");
+        assert!(json.contains("\"repeat\":\"all\""));
+        let deserialized = AppConfig::parse(&json).expect("Should deserialize");
+
+        assert!(deserialized.auto_resume);
+        let session = &deserialized.session;
+        assert_eq!(session.tabs.len(), 2);
+        assert_eq!(session.tabs[0].entries, config.session.tabs[0].entries);
+        assert_eq!(session.tabs[0].current_index, Some(1));
+        assert!(session.tabs[0].shuffle);
+        assert_eq!(session.tabs[0].shuffle_mode, ShuffleMode::Albums);
+        assert_eq!(session.tabs[0].repeat, RepeatMode::All);
+        assert_eq!(session.tabs[1].name, "Party");
+        assert_eq!(session.tabs[1].smart, config.session.tabs[1].smart);
+        assert_eq!(session.tabs[0].smart, None);
+        assert_eq!(session.active_tab, 1);
+        assert_eq!(session.position_secs, 42.5);
+        assert_eq!(session.window, config.session.window);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_config() {
+        // Config as written before the schema version was added
+        let json = r#"{
+            "equalizer": { "enabled": true, "gains": [0, 0, 0, 0, 0, 0, 0, 0, 0, 3] },
+            "first_run": false,
+            "active_skin": "Winamp5 Classified"
+        }"#;
+
+        let config = AppConfig::parse(json).expect("Should migrate");
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.equalizer.enabled);
+        assert_eq!(config.active_skin, "Winamp5 Classified");
+        assert!(!config.auto_resume);
+        assert!(config.session.tabs.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_single_playlist_to_tabs() {
+        // Version 1 stored a single playlist in the session
+        let json = r#"{
+            "version": 1,
+            "equalizer": { "enabled": false, "gains": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },
+            "session": {
+                "playlist": ["/music/a.flac", "/music/b.flac"],
+                "current_index": 1,
+                "position_secs": 12.0,
+                "shuffle": true,
+                "shuffle_mode": "albums",
+                "repeat": "one"
+            }
+        }"#;
+
+        let config = AppConfig::parse(json).expect("Should migrate");
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        let session = &config.session;
+        assert_eq!(session.tabs.len(), 1);
+        assert_eq!(session.tabs[0].name, "Default");
+        assert_eq!(session.tabs[0].entries.len(), 2);
+        assert_eq!(session.tabs[0].current_index, Some(1));
+        assert_eq!(session.tabs[0].selected_index, Some(1));
+        assert!(session.tabs[0].shuffle);
+        assert_eq!(session.tabs[0].shuffle_mode, ShuffleMode::Albums);
+        assert_eq!(session.tabs[0].repeat, RepeatMode::One);
+        assert_eq!(session.playing_tab, 0);
+        assert_eq!(session.position_secs, 12.0);
+    }
+
     #[test]
     fn test_active_skin_default() {
         let config = AppConfig::default();
@@ -1,3 +1,4 @@
+use crate::skins::assets::{ButtonTextures, SkinAssets};
 use crate::theme::Theme;
 use eframe::egui::{self, Color32, Painter, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2};
 
@@ -110,16 +111,33 @@ impl ButtonIcon {
     }
 }
 
-/// Render a circular control button with 3D effect
+/// Render a circular control button with 3D effect, or the skin's images
+/// for the button when it has any
 pub fn control_button(
     ui: &mut Ui,
     theme: &Theme,
     icon: ButtonIcon,
     active: bool,
     size: f32,
+    image: Option<&ButtonTextures>,
 ) -> Response {
     let (rect, response) = ui.allocate_exact_size(Vec2::splat(size), Sense::click());
 
+    if let Some(image) = image {
+        if ui.is_rect_visible(rect) {
+            image.paint(
+                ui.painter(),
+                rect,
+                response.hovered(),
+                response.is_pointer_button_down_on(),
+            );
+        }
+        response.widget_info(|| {
+            egui::WidgetInfo::labeled(egui::WidgetType::Button, true, format!("{:?}", icon))
+        });
+        return response;
+    }
+
     if ui.is_rect_visible(rect) {
         let painter = ui.painter();
         let center = rect.center();
@@ -272,6 +290,7 @@ pub fn control_button_row(
     theme: &Theme,
     is_playing: bool,
     is_paused: bool,
+    assets: &SkinAssets,
 ) -> ControlAction {
     let mut action = ControlAction::None;
 
@@ -279,7 +298,8 @@ pub fn control_button_row(
         ui.add_space(12.0);
 
         // Previous
-        if control_button(ui, theme, ButtonIcon::Previous, false, 60.0).clicked() {
+        let image = assets.button(ButtonIcon::Previous);
+        if control_button(ui, theme, ButtonIcon::Previous, false, 60.0, image).clicked() {
             action = ControlAction::Previous;
         }
 
@@ -292,7 +312,8 @@ pub fn control_button_row(
             ButtonIcon::Play
         };
 
-        if control_button(ui, theme, play_pause_icon, is_playing, 70.0).clicked() {
+        let image = assets.button(play_pause_icon);
+        if control_button(ui, theme, play_pause_icon, is_playing, 70.0, image).clicked() {
             action = if is_playing && !is_paused {
                 ControlAction::Pause
             } else {
@@ -303,14 +324,16 @@ pub fn control_button_row(
         ui.add_space(8.0);
 
         // Stop
-        if control_button(ui, theme, ButtonIcon::Stop, false, 60.0).clicked() {
+        let image = assets.button(ButtonIcon::Stop);
+        if control_button(ui, theme, ButtonIcon::Stop, false, 60.0, image).clicked() {
             action = ControlAction::Stop;
         }
 
         ui.add_space(8.0);
 
         // Next
-        if control_button(ui, theme, ButtonIcon::Next, false, 60.0).clicked() {
+        let image = assets.button(ButtonIcon::Next);
+        if control_button(ui, theme, ButtonIcon::Next, false, 60.0, image).clicked() {
             action = ControlAction::Next;
         }
 
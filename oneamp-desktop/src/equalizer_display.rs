@@ -203,16 +203,8 @@ fn render_eq_slider_3d(ui: &mut Ui, theme: &Theme, value: &mut f32, peak_value:
                 Vec2::new(track_width, fill_height),
             );
 
-            // Gradient color based on value
-            let color = if *value > 6.0 {
-                Color32::from_rgb(255, 50, 50) // Red
-            } else if *value > 0.0 {
-                Color32::from_rgb(255, 200, 50) // Yellow
-            } else if *value > -6.0 {
-                Color32::from_rgb(50, 255, 100) // Green
-            } else {
-                Color32::from_rgb(50, 150, 255) // Blue
-            };
+            // Skin color based on value
+            let color = theme.eq_level_color(*value);
 
             VisualEffects::gradient_rect_vertical(
                 painter,
@@ -228,11 +220,7 @@ fn render_eq_slider_3d(ui: &mut Ui, theme: &Theme, value: &mut f32, peak_value:
             let normalized_peak = (peak_value + 12.0) / 24.0;
             let peak_y = track_rect.bottom() - slider_height * normalized_peak.clamp(0.0, 1.0);
 
-            let peak_color = if peak_value > 6.0 {
-                Color32::from_rgb(255, 100, 100)
-            } else {
-                Color32::from_rgb(100, 255, 150)
-            };
+            let peak_color = theme.eq_level_color(peak_value).linear_multiply(1.2);
 
             // Glow for peak
             for i in 0..3 {
@@ -0,0 +1,352 @@
+// Music Library and Play Statistics
+// The music folder, scanned in the background for smart playlists to pick
+// from, and the play counts and ratings of every track, saved to
+// `stats.json` next to the config file and optionally written to tags.
+
+use egui::{Color32, RichText};
+use oneamp_core::smart_playlist::Rule;
+use oneamp_core::stats::{self, tags, PlayStats, StatsTracker, TrackStats, MAX_RATING};
+use oneamp_core::{AudioEvent, Library};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::config::LibraryConfig;
+use crate::playlist_tabs::PlaylistTabs;
+
+pub struct LibraryPanel {
+    config: LibraryConfig,
+    library: Library,
+    /// Bumped when a scan finishes, 0 until the first one does
+    generation: u64,
+    scan: Option<Receiver<Result<Library, String>>>,
+    stats: PlayStats,
+    stats_path: Option<PathBuf>,
+    saved_revision: u64,
+    tracker: StatsTracker,
+    /// Tag writes done on a background thread, and their errors
+    tag_writer: Sender<(PathBuf, TrackStats)>,
+    tag_errors: Receiver<String>,
+    /// Track being played, and tag writes held back until it stops, so its
+    /// file isn't rewritten under the decoder
+    playing: Option<PathBuf>,
+    deferred_tags: HashMap<PathBuf, TrackStats>,
+    error: Option<String>,
+}
+
+impl LibraryPanel {
+    pub fn new(config: LibraryConfig) -> Self {
+        let stats_path = dirs::config_dir().map(|dir| dir.join("oneamp").join("stats.json"));
+        let stats = match stats_path.as_deref().map(PlayStats::load) {
+            Some(Ok(stats)) => stats,
+            Some(Err(e)) => {
+                eprintln!("Failed to load play statistics: {:#}", e);
+                PlayStats::default()
+            }
+            None => PlayStats::default(),
+        };
+
+        let (tag_writer, writes) = mpsc::channel::<(PathBuf, TrackStats)>();
+        let (errors, tag_errors) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (path, stats) in writes {
+                if let Err(e) = tags::write_stats(&path, &stats) {
+                    let _ = errors.send(format!("{:#}", e));
+                }
+            }
+        });
+
+        let mut panel = Self {
+            library: Library::default(),
+            generation: 0,
+            scan: None,
+            saved_revision: stats.revision(),
+            stats,
+            stats_path,
+            tracker: StatsTracker::new(),
+            tag_writer,
+            tag_errors,
+            playing: None,
+            deferred_tags: HashMap::new(),
+            error: None,
+            config,
+        };
+        panel.rescan();
+        panel
+    }
+
+    /// Settings to save in the config file
+    pub fn config(&self) -> &LibraryConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &PlayStats {
+        &self.stats
+    }
+
+    /// Folder being scanned, the system music folder unless one was picked
+    fn music_dir(&self) -> Option<PathBuf> {
+        self.config.music_dir.clone().or_else(dirs::audio_dir)
+    }
+
+    /// Scan the music folder again in the background
+    pub fn rescan(&mut self) {
+        let Some(dir) = self.music_dir() else {
+            self.error = Some("No music folder, pick one below".to_string());
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(Library::scan(&dir).map_err(|e| format!("{:#}", e)));
+        });
+        self.scan = Some(rx);
+    }
+
+    /// Count plays and skips from a player event
+    pub fn handle_event(&mut self, event: &AudioEvent) {
+        if let Some(event) = self.tracker.handle_event(event) {
+            let (path, stats) = self.stats.apply(&event);
+            self.sync_tags(path, stats);
+        }
+
+        match event {
+            AudioEvent::TrackLoaded(track) => self.playing = Some(track.path.clone()),
+            AudioEvent::Stopped | AudioEvent::Finished => self.playing = None,
+            _ => return,
+        }
+        let playing = self.playing.as_ref();
+        let tag_writer = &self.tag_writer;
+        self.deferred_tags.retain(|path, stats| {
+            if Some(path) == playing {
+                return true;
+            }
+            let _ = tag_writer.send((path.clone(), *stats));
+            false
+        });
+    }
+
+    /// Rate tracks from 1 to 5 stars, or 0 to clear their rating
+    pub fn set_rating(&mut self, paths: &[PathBuf], rating: u8) {
+        for path in paths {
+            let stats = self.stats.set_rating(path, rating);
+            self.sync_tags(path.clone(), stats);
+        }
+    }
+
+    fn sync_tags(&mut self, path: PathBuf, stats: TrackStats) {
+        if !self.config.sync_tags || !tags::can_write(&path) {
+            return;
+        }
+        if self.playing.as_ref() == Some(&path) {
+            self.deferred_tags.insert(path, stats);
+        } else {
+            let _ = self.tag_writer.send((path, stats));
+        }
+    }
+
+    /// Collect a finished scan and tag errors
+    pub fn update(&mut self) {
+        if let Some(result) = self.scan.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.scan = None;
+            match result {
+                Ok(library) => {
+                    self.library = library;
+                    self.generation += 1;
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+        if let Some(e) = self.tag_errors.try_iter().last() {
+            self.error = Some(e);
+        }
+    }
+
+    /// Refill the smart playlists whose rule, the library or the statistics
+    /// changed. Returns true if any playlist changed.
+    pub fn refresh_smart_playlists(&self, playlists: &mut PlaylistTabs) -> bool {
+        // Keep the saved entries until the library has been read
+        if self.generation == 0 {
+            return false;
+        }
+        let now = stats::unix_time();
+        let mut changed = false;
+        for tab in playlists.tabs_mut() {
+            changed |= tab.refresh_smart(self.library.tracks(), self.generation, &self.stats, now);
+        }
+        changed
+    }
+
+    /// Save the statistics if they changed since the last save
+    pub fn save(&mut self) {
+        let Some(path) = &self.stats_path else {
+            return;
+        };
+        if self.stats.revision() == self.saved_revision {
+            return;
+        }
+        match self.stats.save(path) {
+            Ok(()) => self.saved_revision = self.stats.revision(),
+            Err(e) => eprintln!("Failed to save play statistics: {:#}", e),
+        }
+    }
+
+    /// Draw the settings. Returns true when they changed and should be saved.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        if self.scan.is_some() {
+            ui.ctx().request_repaint();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Music folder:");
+            let dir = self.music_dir();
+            ui.label(RichText::new(dir.as_deref().map_or_else(
+                || "none".to_string(),
+                |dir| dir.display().to_string(),
+            )));
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Choose…").clicked() {
+                if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                    self.config.music_dir = Some(dir);
+                    self.rescan();
+                    changed = true;
+                }
+            }
+            if ui
+                .add_enabled(self.scan.is_none(), egui::Button::new("Rescan"))
+                .clicked()
+            {
+                self.rescan();
+            }
+            if self.scan.is_some() {
+                ui.spinner();
+                ui.label("Scanning…");
+            } else if self.generation > 0 {
+                ui.label(format!("{} tracks", self.library.len()));
+            }
+        });
+        ui.add_space(8.0);
+
+        let rated = self.stats.iter().filter(|(_, s)| s.rating > 0).count();
+        let played = self.stats.iter().filter(|(_, s)| s.play_count > 0).count();
+        ui.label(format!("{} tracks played, {} rated", played, rated));
+        if ui
+            .checkbox(
+                &mut self.config.sync_tags,
+                "Write ratings and play counts to tags",
+            )
+            .on_hover_text("POPM and FMPS frames in MP3 and WAV files, comments in FLAC files")
+            .changed()
+        {
+            changed = true;
+        }
+
+        if let Some(error) = &self.error {
+            ui.add_space(8.0);
+            ui.colored_label(Color32::from_rgb(220, 80, 80), error);
+        }
+        changed
+    }
+}
+
+/// A seed for a new `ORDER BY random` order
+pub fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+/// "★★★☆☆" for a rating
+pub fn stars(rating: u8) -> String {
+    (1..=MAX_RATING)
+        .map(|star| if star <= rating { '★' } else { '☆' })
+        .collect()
+}
+
+/// What the smart playlist editor asks for
+pub enum SmartEditorAction {
+    Save { name: String, rule: String },
+    Cancel,
+}
+
+/// Dialog creating a smart playlist or changing its name and rule
+pub struct SmartPlaylistEditor {
+    /// Tab being edited, `None` for a new playlist
+    pub tab: Option<usize>,
+    name: String,
+    rule: String,
+    /// Rule text checked last, and how many tracks it matches or its error
+    checked: Option<(String, Result<usize, String>)>,
+}
+
+impl SmartPlaylistEditor {
+    pub fn new(tab: Option<usize>, name: &str, rule: &str) -> Self {
+        Self {
+            tab,
+            name: name.to_string(),
+            rule: rule.to_string(),
+            checked: None,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, panel: &LibraryPanel) -> Option<SmartEditorAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.name);
+        });
+        ui.label("Rule:");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.rule)
+                .hint_text("rating >= 4 AND NOT played in 30 days")
+                .desired_rows(3)
+                .desired_width(400.0)
+                .font(egui::TextStyle::Monospace),
+        );
+        ui.label(
+            RichText::new(
+                "Fields: title, artist, album, genre, path, rating, plays, skips, duration\n\
+                 e.g. genre = Jazz ORDER BY random LIMIT 100",
+            )
+            .small()
+            .weak(),
+        );
+
+        if self.checked.as_ref().map(|(rule, _)| rule) != Some(&self.rule) {
+            let result = self.rule.parse::<Rule>().map(|rule| {
+                rule.evaluate(panel.library.tracks(), &panel.stats, stats::unix_time(), 0)
+                    .len()
+            });
+            self.checked = Some((self.rule.clone(), result));
+        }
+        let valid = match self.checked.as_ref().map(|(_, result)| result) {
+            Some(Ok(count)) => {
+                ui.label(format!("{} tracks in the library match", count));
+                true
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::from_rgb(220, 80, 80), e);
+                false
+            }
+            None => false,
+        };
+
+        ui.horizontal(|ui| {
+            let ready = valid && !self.name.trim().is_empty();
+            if ui.add_enabled(ready, egui::Button::new("Save")).clicked() {
+                action = Some(SmartEditorAction::Save {
+                    name: self.name.trim().to_string(),
+                    rule: self.rule.trim().to_string(),
+                });
+            }
+            if ui.button("Cancel").clicked() {
+                action = Some(SmartEditorAction::Cancel);
+            }
+        });
+        action
+    }
+}
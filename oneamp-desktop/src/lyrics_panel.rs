@@ -0,0 +1,171 @@
+// Lyrics Panel
+// Lyrics of the playing track, following the music: the line being sung
+// is highlighted and kept in view, word by word for enhanced LRC files.
+// Clicking a timed line seeks to it.
+
+use egui::text::LayoutJob;
+use egui::{FontId, RichText, TextFormat};
+use oneamp_core::lyrics::{LyricsEvent, LyricsSource, LyricsTracker};
+use oneamp_core::AudioEvent;
+use std::time::Instant;
+
+use crate::theme::Theme;
+
+/// What the panel asks the player to do
+pub enum LyricsAction {
+    /// Seek to a line
+    Seek(f32),
+    /// The delay was adjusted and should be saved
+    LatencyChanged,
+}
+
+pub struct LyricsPanel {
+    tracker: LyricsTracker,
+    latency_ms: i32,
+    /// The current line changed and should be scrolled into view
+    scroll_to_line: bool,
+}
+
+impl LyricsPanel {
+    pub fn new(latency_ms: i32) -> Self {
+        Self {
+            tracker: LyricsTracker::new(latency_ms as f32 / 1000.0),
+            latency_ms,
+            scroll_to_line: false,
+        }
+    }
+
+    /// Delay applied to positions, in milliseconds
+    pub fn latency_ms(&self) -> i32 {
+        self.latency_ms
+    }
+
+    /// Feed a player event; loading a track looks up its lyrics
+    pub fn handle_event(&mut self, event: &AudioEvent) {
+        if let Some(LyricsEvent::Line(_)) = self.tracker.handle_event(event) {
+            self.scroll_to_line = true;
+        }
+    }
+
+    /// Follow the music between position updates
+    pub fn update(&mut self) {
+        if self.tracker.update(Instant::now()).is_some() {
+            self.scroll_to_line = true;
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, theme: &Theme) -> Option<LyricsAction> {
+        let mut action = None;
+        let mut latency_changed = false;
+        let Some(lyrics) = self.tracker.lyrics() else {
+            ui.label("No lyrics for this track");
+            ui.label(
+                RichText::new("Put a .lrc file with the same name next to it")
+                    .small()
+                    .weak(),
+            );
+            return None;
+        };
+
+        ui.horizontal(|ui| {
+            let source = match &lyrics.source {
+                LyricsSource::LrcFile(path) => path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                LyricsSource::SyncedTag => "Synchronized lyrics tag".to_string(),
+                LyricsSource::Tag => "Lyrics tag".to_string(),
+            };
+            ui.label(RichText::new(source).small().weak());
+            if lyrics.is_synced() {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let slider = egui::Slider::new(&mut self.latency_ms, -1000..=2000)
+                        .suffix(" ms")
+                        .text("Delay");
+                    let response = ui
+                        .add(slider)
+                        .on_hover_text("Show lines later if they run ahead of the music");
+                    latency_changed = response.changed();
+                    // Saved once the slider is let go, not at every step
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        action = Some(LyricsAction::LatencyChanged);
+                    }
+                });
+            }
+        });
+        ui.separator();
+
+        let current = self.tracker.line();
+        let position = self.tracker.position_at(Instant::now());
+        let text_color = Theme::color32(&theme.colors.playlist_text);
+        let sung_color = Theme::color32(&theme.colors.playlist_playing);
+        let font = FontId::proportional(theme.fonts.playlist_size + 2.0);
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    for (index, line) in lyrics.lines.iter().enumerate() {
+                        let is_current = current == Some(index);
+                        let color = match (is_current, line.time) {
+                            (true, _) => sung_color,
+                            (false, Some(_)) => text_color.linear_multiply(0.6),
+                            (false, None) => text_color,
+                        };
+
+                        // Karaoke: sung words of the current line are lit
+                        let mut job = LayoutJob::default();
+                        let sung_word = position
+                            .filter(|_| is_current)
+                            .and_then(|secs| lyrics.word_at(index, secs));
+                        if let (Some(sung_word), false) = (sung_word, line.words.is_empty()) {
+                            for (i, word) in line.words.iter().enumerate() {
+                                let color = if i <= sung_word {
+                                    sung_color
+                                } else {
+                                    text_color
+                                };
+                                job.append(
+                                    &word.text,
+                                    0.0,
+                                    TextFormat::simple(font.clone(), color),
+                                );
+                            }
+                        } else {
+                            let text = if line.text.is_empty() {
+                                " "
+                            } else {
+                                &line.text
+                            };
+                            job.append(text, 0.0, TextFormat::simple(font.clone(), color));
+                        }
+
+                        let response = match line.time {
+                            Some(time) => {
+                                let response = ui
+                                    .add(egui::Label::new(job).sense(egui::Sense::click()))
+                                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                if response.clicked() {
+                                    action = Some(LyricsAction::Seek(time));
+                                }
+                                response
+                            }
+                            None => ui.label(job),
+                        };
+                        if is_current && self.scroll_to_line {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                            self.scroll_to_line = false;
+                        }
+                    }
+                });
+            });
+
+        if latency_changed {
+            self.tracker.set_latency(self.latency_ms as f32 / 1000.0);
+        }
+        if current.is_some() {
+            ui.ctx().request_repaint();
+        }
+        action
+    }
+}
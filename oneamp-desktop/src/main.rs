@@ -1,9 +1,21 @@
 use eframe::egui;
-use oneamp_core::{AudioCommand, AudioEngine, AudioEvent, TrackInfo};
-use std::path::PathBuf;
+#[cfg(unix)]
+use oneamp_core::ipc::{self, IpcCall, IpcClient, IpcReply, IpcRequest, IpcServer};
+#[cfg(target_os = "linux")]
+use oneamp_core::mpris::{MprisRequest, MprisServer};
+use oneamp_core::{
+    stream, AudioCommand, AudioEngine, AudioEvent, MetadataService, PlaybackState, RepeatMode,
+    ShuffleMode, TrackInfo,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the session is saved while running, for crash safety
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 mod config;
-use config::AppConfig;
+use config::{AppConfig, ConfigWriter, SessionConfig, WindowGeometry, CONFIG_VERSION};
 
 mod visualizer;
 use visualizer::Visualizer;
@@ -12,6 +24,7 @@ mod theme;
 use theme::Theme;
 
 mod track_display;
+use track_display::TrackDisplay;
 
 mod ui_components;
 
@@ -22,6 +35,9 @@ mod custom_widgets;
 mod animations;
 use animations::AnimationTimer;
 
+mod adaptive_theme;
+use adaptive_theme::AdaptiveTheme;
+
 mod equalizer_display;
 use equalizer_display::EqualizerDisplay;
 
@@ -40,10 +56,74 @@ use onedrop_visualizer::OneDropVisualizer;
 mod platform_detection;
 use platform_detection::PlatformInfo;
 
+mod playlist_tabs;
+use playlist_tabs::{PlaylistTabs, SmartRule, SortKey};
+
+mod library;
+use library::{LibraryPanel, SmartEditorAction, SmartPlaylistEditor};
+
+mod lyrics_panel;
+use lyrics_panel::{LyricsAction, LyricsPanel};
+
+mod podcasts;
+use podcasts::{PodcastAction, PodcastPanel};
+mod scrobbling;
+use scrobbling::ScrobblePanel;
+
 mod skins;
+use skins::classic::ClassicSkin;
+use skins::classic_view::{ClassicAction, ClassicState, ClassicView};
+use skins::watcher::SkinWatcher;
 use skins::SkinManager;
 
 fn main() -> eframe::Result {
+    // Files given on the command line are opened, by the running instance if any
+    let open_paths: Vec<PathBuf> = std::env::args_os()
+        .skip(1)
+        .map(PathBuf::from)
+        .filter_map(|path| {
+            // Stream URLs are passed on as they are
+            if stream::is_stream_url(&path) {
+                return Some(path);
+            }
+            match path.canonicalize() {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    eprintln!("Cannot open {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    #[cfg(unix)]
+    let ipc = {
+        let socket = ipc::default_socket_path();
+        if let Ok(mut client) = IpcClient::connect(&socket) {
+            let request = if open_paths.is_empty() {
+                IpcRequest::Raise
+            } else {
+                IpcRequest::Open {
+                    paths: open_paths.clone(),
+                }
+            };
+            match client.request(&request) {
+                Ok(_) => {
+                    println!("OneAmp is already running, forwarded to it");
+                    return Ok(());
+                }
+                Err(e) => eprintln!("Failed to reach the running instance: {:#}", e),
+            }
+        }
+        match IpcServer::bind(&socket) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("Control socket unavailable: {:#}", e);
+                None
+            }
+        }
+    };
+
     let theme = Theme::default();
 
     // Smart platform detection for window chrome
@@ -61,12 +141,23 @@ fn main() -> eframe::Result {
         }
     );
 
+    // Restore the window geometry from the previous session
+    let (config, is_first_run) = AppConfig::load();
+    let window = config.session.window;
+    let mut viewport =
+        egui::ViewportBuilder::default().with_inner_size(window.map(|w| w.size).unwrap_or([
+            theme.layout.window_min_width,
+            theme.layout.window_min_height,
+        ]));
+    if let Some(window) = window {
+        if let Some(position) = window.position {
+            viewport = viewport.with_position(position);
+        }
+        viewport = viewport.with_maximized(window.maximized);
+    }
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([
-                theme.layout.window_min_width,
-                theme.layout.window_min_height,
-            ])
+        viewport: viewport
             .with_min_inner_size([
                 theme.layout.window_min_width,
                 theme.layout.window_min_height,
@@ -82,7 +173,17 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "OneAmp",
         options,
-        Box::new(move |cc| Ok(Box::new(OneAmpApp::new(cc, use_custom_chrome)))),
+        Box::new(move |cc| {
+            Ok(Box::new(OneAmpApp::new(
+                cc,
+                use_custom_chrome,
+                config,
+                is_first_run,
+                open_paths,
+                #[cfg(unix)]
+                ipc,
+            )))
+        }),
     )
 }
 
@@ -92,12 +193,49 @@ struct OneAmpApp {
     playback_state: PlaybackState,
     current_position: f32,
     total_duration: f32,
+    volume: f32,
     error_message: Option<String>,
-
-    // Playlist
-    playlist: Vec<PathBuf>,
-    current_track_index: Option<usize>,
-    selected_track_index: Option<usize>,
+    /// Fill level of a stream's buffer while it is buffering
+    buffering: Option<f32>,
+    /// URL being typed in the "Add URL" dialog
+    add_url: Option<String>,
+
+    // Playlists, one per tab
+    playlists: PlaylistTabs,
+    renaming_tab: Option<(usize, String)>,
+    focus_filter: bool,
+
+    // Titles and durations, probed in the background
+    metadata: MetadataService,
+
+    // Podcast subscriptions, downloads and listening progress
+    podcasts: PodcastPanel,
+    show_podcasts: bool,
+
+    // Music folder, play statistics and smart playlists
+    library: LibraryPanel,
+    show_library: bool,
+    smart_editor: Option<SmartPlaylistEditor>,
+
+    // Synchronized lyrics of the playing track
+    lyrics: LyricsPanel,
+    show_lyrics: bool,
+
+    // Last.fm and ListenBrainz
+    scrobbler: ScrobblePanel,
+    show_scrobbling: bool,
+    window_focused: bool,
+
+    // Media keys and desktop widgets (MPRIS)
+    #[cfg(target_os = "linux")]
+    mpris: Option<MprisServer>,
+    /// Playing tab and revision last sent to MPRIS clients
+    #[cfg(target_os = "linux")]
+    mpris_playlist: Option<(usize, u64)>,
+
+    // Control socket for scripts and `oneamp-cli ctl`
+    #[cfg(unix)]
+    ipc: Option<IpcServer>,
 
     // Equalizer
     eq_enabled: bool,
@@ -110,10 +248,20 @@ struct OneAmpApp {
 
     // Theme
     theme: Theme,
+    /// Tints `theme` with the cover's colors when enabled
+    adaptive_theme: AdaptiveTheme,
+    /// Minimum window size last sent to the viewport, from the skin's layout
+    window_min_size: [f32; 2],
 
     // Skin system
     skin_manager: SkinManager,
     show_skin_selector: bool,
+    /// Windows of the active skin when it's a classic `.wsz` skin
+    classic_view: ClassicView,
+    /// Reloads the active skin when its files change
+    skin_watcher: SkinWatcher,
+    /// Skin path and errors of the last failed reload, shown until it loads
+    skin_error: Option<(PathBuf, String)>,
 
     // UI state
     scroll_offset: usize,
@@ -139,20 +287,23 @@ struct OneAmpApp {
     // Performance monitoring
     frame_times: std::collections::VecDeque<f32>,
     show_fps: bool,
-}
 
-#[derive(Debug, Clone, PartialEq)]
-enum PlaybackState {
-    Stopped,
-    Playing,
-    Paused,
+    // Session restore
+    auto_resume: bool,
+    window_geometry: Option<WindowGeometry>,
+    last_session_save: std::time::Instant,
+    config_writer: ConfigWriter,
 }
 
 impl OneAmpApp {
-    fn new(cc: &eframe::CreationContext<'_>, use_custom_chrome: bool) -> Self {
-        let theme = Theme::default();
-        theme.apply_to_egui(&cc.egui_ctx);
-
+    fn new(
+        cc: &eframe::CreationContext<'_>,
+        use_custom_chrome: bool,
+        config: AppConfig,
+        is_first_run: bool,
+        open_paths: Vec<PathBuf>,
+        #[cfg(unix)] ipc: Option<IpcServer>,
+    ) -> Self {
         // Suppress ALSA warnings by redirecting stderr during audio engine initialization
         #[cfg(feature = "audio")]
         {
@@ -170,15 +321,20 @@ impl OneAmpApp {
             }
         };
 
-        let (config, is_first_run) = AppConfig::load();
-
         // Initialize skin manager
         let skins_dir = dirs::config_dir()
             .map(|d| d.join("oneamp").join("skins"))
             .unwrap_or_else(|| PathBuf::from("./skins"));
 
+        migrate_legacy_theme(&skins_dir);
         let mut skin_manager = SkinManager::discover_and_load(&skins_dir);
 
+        // Installed .oaskin packages live with the user's data
+        let packages_dir = dirs::data_dir()
+            .map(|d| d.join("oneamp").join("skins"))
+            .unwrap_or_else(|| PathBuf::from("./skins"));
+        skin_manager.discover_packages(&packages_dir);
+
         // Load the active skin from config
         if let Some(index) = skin_manager.find_skin_by_name(&config.active_skin) {
             skin_manager.set_active_skin(index);
@@ -186,6 +342,8 @@ impl OneAmpApp {
 
         // Apply the active skin
         skin_manager.apply_skin(&cc.egui_ctx);
+        let theme = Theme::from_skin(skin_manager.get_active_skin());
+        theme.apply_to_egui(&cc.egui_ctx);
 
         let mut app = Self {
             audio_engine,
@@ -193,10 +351,30 @@ impl OneAmpApp {
             playback_state: PlaybackState::Stopped,
             current_position: 0.0,
             total_duration: 0.0,
+            volume: 1.0,
             error_message: None,
-            playlist: Vec::new(),
-            current_track_index: None,
-            selected_track_index: None,
+            buffering: None,
+            add_url: None,
+            playlists: PlaylistTabs::new(),
+            renaming_tab: None,
+            focus_filter: false,
+            metadata: MetadataService::new(),
+            podcasts: PodcastPanel::new(),
+            show_podcasts: false,
+            library: LibraryPanel::new(config.library.clone()),
+            show_library: false,
+            smart_editor: None,
+            lyrics: LyricsPanel::new(config.lyrics_latency_ms),
+            show_lyrics: false,
+            scrobbler: ScrobblePanel::new(config.scrobbling.clone()),
+            show_scrobbling: false,
+            window_focused: true,
+            #[cfg(target_os = "linux")]
+            mpris: start_mpris(&cc.egui_ctx),
+            #[cfg(target_os = "linux")]
+            mpris_playlist: None,
+            #[cfg(unix)]
+            ipc,
             eq_enabled: config.equalizer.enabled,
             eq_gains: config.equalizer.gains.clone(),
             eq_frequencies: vec![
@@ -204,9 +382,14 @@ impl OneAmpApp {
             ],
             show_equalizer: false,
             visualizer: Visualizer::new(),
+            adaptive_theme: AdaptiveTheme::new(&theme, config.adaptive_theme),
+            window_min_size: [theme.layout.window_min_width, theme.layout.window_min_height],
             theme,
             skin_manager,
             show_skin_selector: false,
+            classic_view: ClassicView::new(config.classic_skin_scale),
+            skin_watcher: SkinWatcher::default(),
+            skin_error: None,
             scroll_offset: 0,
             last_scroll_update: std::time::Instant::now(),
             animation_timer: AnimationTimer::new(),
@@ -220,6 +403,10 @@ impl OneAmpApp {
             visualizer_fullscreen: false,
             frame_times: std::collections::VecDeque::with_capacity(60),
             show_fps: false,
+            auto_resume: config.auto_resume,
+            window_geometry: config.session.window,
+            last_session_save: std::time::Instant::now(),
+            config_writer: ConfigWriter::new(),
         };
 
         // Initialize OneDrop visualizer asynchronously
@@ -246,13 +433,112 @@ impl OneAmpApp {
             let _ = engine.send_command(AudioCommand::SetEqualizerBands(config.equalizer.gains));
         }
 
-        if is_first_run {
+        #[cfg(unix)]
+        if let Some(ref server) = app.ipc {
+            let ctx = cc.egui_ctx.clone();
+            server.set_waker(move || ctx.request_repaint());
+        }
+
+        if is_first_run && open_paths.is_empty() {
             app.play_jingle();
+        } else {
+            app.restore_session(config.session);
+        }
+        if !open_paths.is_empty() {
+            app.open_paths(&open_paths);
         }
 
         app
     }
 
+    /// Reload the playlists and current track saved by the previous session.
+    /// The track is loaded paused at the saved position unless auto-resume is on.
+    fn restore_session(&mut self, session: SessionConfig) {
+        self.playlists =
+            PlaylistTabs::from_config(session.tabs, session.active_tab, session.playing_tab);
+        let current = self
+            .playlists
+            .playing()
+            .playlist
+            .current()
+            .map(Path::to_path_buf);
+
+        if let (Some(path), Some(ref engine)) = (current, &self.audio_engine) {
+            self.current_position = session.position_secs;
+            let _ = engine.send_command(AudioCommand::Load(path, session.position_secs));
+            if self.auto_resume {
+                let _ = engine.send_command(AudioCommand::Resume);
+            }
+        }
+    }
+
+    /// Snapshot the state to restore on the next launch
+    fn session_config(&self) -> SessionConfig {
+        let playing = self.playback_state != PlaybackState::Stopped;
+        SessionConfig {
+            tabs: self.playlists.to_config(),
+            active_tab: self.playlists.active_index(),
+            playing_tab: self.playlists.playing_index(),
+            position_secs: if playing { self.current_position } else { 0.0 },
+            window: self.window_geometry,
+        }
+    }
+
+    /// Save settings and the current session to the config file
+    fn save_config(&mut self) {
+        let config = AppConfig {
+            version: CONFIG_VERSION,
+            equalizer: config::EqualizerConfig {
+                enabled: self.eq_enabled,
+                gains: self.eq_gains.clone(),
+            },
+            first_run: false,
+            active_skin: self.skin_manager.get_active_skin().metadata.name.clone(),
+            auto_resume: self.auto_resume,
+            session: self.session_config(),
+            scrobbling: self.scrobbler.config().clone(),
+            library: self.library.config().clone(),
+            lyrics_latency_ms: self.lyrics.latency_ms(),
+            adaptive_theme: self.adaptive_theme.is_enabled(),
+            classic_skin_scale: self.classic_view.scale(),
+        };
+        self.config_writer.save(config);
+        self.podcasts.save();
+        self.library.save();
+        self.last_session_save = std::time::Instant::now();
+    }
+
+    /// Remember the window geometry so it can be restored on the next launch
+    fn track_window_geometry(&mut self, ctx: &egui::Context) {
+        let (inner, outer, maximized, minimized) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.inner_rect,
+                viewport.outer_rect,
+                viewport.maximized.unwrap_or(false),
+                viewport.minimized.unwrap_or(false),
+            )
+        });
+
+        if minimized {
+            return;
+        }
+
+        match (&mut self.window_geometry, maximized) {
+            // Keep the unmaximized size so un-maximizing after a restore works
+            (Some(geometry), true) => geometry.maximized = true,
+            _ => {
+                if let Some(inner) = inner {
+                    self.window_geometry = Some(WindowGeometry {
+                        position: outer.map(|rect| [rect.min.x, rect.min.y]),
+                        size: [inner.width(), inner.height()],
+                        maximized,
+                    });
+                }
+            }
+        }
+    }
+
     fn play_jingle(&mut self) {
         const JINGLE_DATA: &[u8] = include_bytes!("../../packaging/jingle.wav");
 
@@ -278,13 +564,33 @@ impl OneAmpApp {
 
         // Process events
         for event in events {
+            #[cfg(target_os = "linux")]
+            if let Some(ref mpris) = self.mpris {
+                mpris.handle_event(&event);
+            }
+            #[cfg(unix)]
+            if let Some(ref server) = self.ipc {
+                server.broadcast(&event);
+            }
+            self.scrobbler.handle_event(&event);
+            self.library.handle_event(&event);
+            self.lyrics.handle_event(&event);
+
             match event {
                 AudioEvent::TrackLoaded(track_info) => {
                     self.current_track = Some(track_info);
                     self.error_message = None;
+                    self.buffering = None;
+                }
+                AudioEvent::TrackUpdated(track_info) => {
+                    self.current_track = Some(track_info);
+                }
+                AudioEvent::Buffering(progress) => {
+                    self.buffering = Some(progress);
                 }
                 AudioEvent::Playing => {
                     self.playback_state = PlaybackState::Playing;
+                    self.buffering = None;
                 }
                 AudioEvent::Paused => {
                     self.playback_state = PlaybackState::Paused;
@@ -292,16 +598,23 @@ impl OneAmpApp {
                 AudioEvent::Stopped => {
                     self.playback_state = PlaybackState::Stopped;
                     self.current_position = 0.0;
+                    self.buffering = None;
                 }
                 AudioEvent::Position(current, total) => {
                     self.current_position = current;
                     self.total_duration = total;
+                    if let Some(track) = &self.current_track {
+                        self.podcasts.track_position(&track.path, current);
+                    }
                 }
                 AudioEvent::Finished => {
                     self.playback_state = PlaybackState::Stopped;
                     self.current_position = 0.0;
-                    if !self.playlist.is_empty() {
-                        self.play_next();
+                    if let Some(track) = &self.current_track {
+                        self.podcasts.track_finished(&track.path);
+                    }
+                    if let Some(index) = self.playlists.playing_mut().playlist.advance() {
+                        self.play_current_entry(index);
                     }
                 }
                 AudioEvent::RequestNext => {
@@ -317,9 +630,16 @@ impl OneAmpApp {
                 AudioEvent::VisualizationData(samples) => {
                     self.visualizer.update(&samples);
                 }
+                AudioEvent::VolumeChanged(volume) => {
+                    self.volume = volume;
+                }
                 AudioEvent::Error(msg) => {
                     self.error_message = Some(msg);
                     self.playback_state = PlaybackState::Stopped;
+                    self.buffering = None;
+                }
+                AudioEvent::SeekFailed(msg) => {
+                    self.error_message = Some(msg);
                 }
             }
         }
@@ -331,32 +651,55 @@ impl OneAmpApp {
         }
     }
 
+    /// Play an entry of the tab being shown, which becomes the playing tab
     fn play_track_at_index(&mut self, index: usize) {
-        if index < self.playlist.len() {
-            self.current_track_index = Some(index);
-            self.play_file(self.playlist[index].clone());
+        self.playlists.set_playing(self.playlists.active_index());
+        let playlist = &mut self.playlists.playing_mut().playlist;
+        if let Some(path) = playlist.select(index).map(|p| p.to_path_buf()) {
+            self.play_file(path);
+        }
+    }
+
+    /// Add a podcast episode to the tab being shown and play it, from
+    /// `start` seconds if given
+    fn play_episode(&mut self, path: PathBuf, start: Option<f32>) {
+        self.add_paths_to_playlist(std::slice::from_ref(&path));
+        self.playlists.set_playing(self.playlists.active_index());
+        let playlist = &mut self.playlists.playing_mut().playlist;
+        let Some(index) = playlist.entries().iter().position(|entry| *entry == path) else {
+            return;
+        };
+        playlist.select(index);
+        if let Some(ref engine) = self.audio_engine {
+            match start {
+                Some(secs) => {
+                    let _ = engine.send_command(AudioCommand::Load(path, secs));
+                    let _ = engine.send_command(AudioCommand::Resume);
+                }
+                None => {
+                    let _ = engine.send_command(AudioCommand::Play(path));
+                }
+            }
+        }
+    }
+
+    /// Play the entry the playing tab just moved to
+    fn play_current_entry(&mut self, index: usize) {
+        let playlist = &self.playlists.playing().playlist;
+        if let Some(path) = playlist.get(index).map(|p| p.to_path_buf()) {
+            self.play_file(path);
         }
     }
 
     fn play_next(&mut self) {
-        if let Some(current_idx) = self.current_track_index {
-            let next_idx = (current_idx + 1) % self.playlist.len();
-            self.play_track_at_index(next_idx);
-        } else if !self.playlist.is_empty() {
-            self.play_track_at_index(0);
+        if let Some(index) = self.playlists.playing_mut().playlist.next() {
+            self.play_current_entry(index);
         }
     }
 
     fn play_previous(&mut self) {
-        if let Some(current_idx) = self.current_track_index {
-            let prev_idx = if current_idx == 0 {
-                self.playlist.len() - 1
-            } else {
-                current_idx - 1
-            };
-            self.play_track_at_index(prev_idx);
-        } else if !self.playlist.is_empty() {
-            self.play_track_at_index(self.playlist.len() - 1);
+        if let Some(index) = self.playlists.playing_mut().playlist.previous() {
+            self.play_current_entry(index);
         }
     }
 
@@ -370,8 +713,10 @@ impl OneAmpApp {
                     let _ = engine.send_command(AudioCommand::Resume);
                 }
                 PlaybackState::Stopped => {
-                    if !self.playlist.is_empty() {
-                        self.play_track_at_index(self.current_track_index.unwrap_or(0));
+                    let playlist = &mut self.playlists.playing_mut().playlist;
+                    let index = playlist.current_index().unwrap_or(0);
+                    if let Some(path) = playlist.select(index).map(|p| p.to_path_buf()) {
+                        self.play_file(path);
                     }
                 }
             }
@@ -384,69 +729,389 @@ impl OneAmpApp {
         }
     }
 
+    fn seek(&mut self, position: f32) {
+        if let Some(ref engine) = self.audio_engine {
+            let _ = engine.send_command(AudioCommand::Seek(position));
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(ref mpris) = self.mpris {
+            mpris.seeked(position);
+        }
+    }
+
+    /// Player, equalizer and playlist windows of a classic skin
+    fn show_classic_skin(&mut self, ui: &mut egui::Ui, skin: &Arc<ClassicSkin>) {
+        let mut scale = self.classic_view.scale();
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut scale, 1, "1x");
+            ui.selectable_value(&mut scale, 2, "2x");
+            if ui.button("🎨 Skins").clicked() {
+                self.show_skin_selector = !self.show_skin_selector;
+            }
+        });
+        if scale != self.classic_view.scale() {
+            self.classic_view.set_scale(scale);
+            self.save_config();
+        }
+
+        let tab = self.playlists.active();
+        let showing_playing = self.playlists.active_index() == self.playlists.playing_index();
+        let state = ClassicState {
+            track: self.current_track.as_ref(),
+            playback: self.playback_state,
+            position: self.current_position,
+            duration: self.total_duration,
+            spectrum: self.visualizer.get_spectrum(),
+            eq_enabled: self.eq_enabled,
+            eq_gains: &self.eq_gains,
+            playlist: tab,
+            current: tab.playlist.current_index().filter(|_| showing_playing),
+        };
+        let Some(action) = self.classic_view.show(ui, skin, &state, &mut self.metadata) else {
+            return;
+        };
+
+        match action {
+            ClassicAction::Previous => self.play_previous(),
+            // Play restarts the playing track, as in Winamp
+            ClassicAction::Play if self.playback_state == PlaybackState::Playing => {
+                if let Some(index) = self.playlists.playing().playlist.current_index() {
+                    self.play_current_entry(index);
+                }
+            }
+            ClassicAction::Play => self.toggle_play_pause(),
+            ClassicAction::Pause if self.playback_state != PlaybackState::Stopped => {
+                self.toggle_play_pause()
+            }
+            ClassicAction::Pause => {}
+            ClassicAction::Stop => self.stop(),
+            ClassicAction::Next => self.play_next(),
+            ClassicAction::Eject => self.add_files_to_playlist(),
+            ClassicAction::Seek(secs) => self.seek(secs),
+            ClassicAction::SetEqEnabled(enabled) => {
+                self.eq_enabled = enabled;
+                if let Some(ref engine) = self.audio_engine {
+                    let _ = engine.send_command(AudioCommand::SetEqualizerEnabled(enabled));
+                }
+            }
+            ClassicAction::SetEqBand(band, gain) => {
+                if let Some(slot) = self.eq_gains.get_mut(band) {
+                    *slot = gain;
+                }
+                if let Some(ref engine) = self.audio_engine {
+                    let _ = engine.send_command(AudioCommand::SetEqualizerBand(band, gain));
+                }
+            }
+            ClassicAction::Select(index) => self.playlists.active_mut().select_only(index),
+            ClassicAction::PlayEntry(index) => self.play_track_at_index(index),
+        }
+    }
+
     fn add_files_to_playlist(&mut self) {
         if let Some(paths) = rfd::FileDialog::new()
-            .add_filter("Audio Files", &["mp3", "flac", "ogg", "wav"])
+            .add_filter("Audio Files", &["mp3", "flac", "ogg", "wav", "cue"])
             .pick_files()
         {
-            for path in paths {
-                if !self.playlist.contains(&path) {
-                    self.playlist.push(path);
-                }
-            }
+            self.add_paths_to_playlist(&paths);
+        }
+    }
+
+    /// Add files to the active playlist as one undo step, expanding CUE
+    /// sheets into their virtual tracks
+    fn add_paths_to_playlist(&mut self, paths: &[PathBuf]) {
+        self.playlists.active_mut().add_paths(paths);
+    }
+
+    /// Add files to the active playlist and play the first of them. CUE
+    /// sheets play from their first track, files already listed from their
+    /// existing entry.
+    fn open_paths(&mut self, paths: &[PathBuf]) {
+        self.add_paths_to_playlist(paths);
+        let playlist = &self.playlists.active().playlist;
+        if let Some(index) = paths.iter().find_map(|path| playlist.find_expanded(path)) {
+            self.play_track_at_index(index);
         }
     }
 
     fn add_folder_to_playlist(&mut self) {
         if let Some(folder) = rfd::FileDialog::new().pick_folder() {
             if let Ok(entries) = std::fs::read_dir(folder) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(ext) = path.extension() {
-                            if ["mp3", "flac", "ogg", "wav"].contains(&ext.to_str().unwrap_or(""))
-                                && !self.playlist.contains(&path)
-                            {
-                                self.playlist.push(path);
-                            }
-                        }
-                    }
-                }
+                let mut paths: Vec<PathBuf> = entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file() && is_playlist_file(path))
+                    .collect();
+                paths.sort();
+                self.add_paths_to_playlist(&paths);
             }
         }
     }
 
-    fn remove_selected_track(&mut self) {
-        if let Some(index) = self.selected_track_index {
-            if index < self.playlist.len() {
-                self.playlist.remove(index);
-                if let Some(current_idx) = self.current_track_index {
-                    if current_idx == index {
-                        self.current_track_index = None;
-                    } else if current_idx > index {
-                        self.current_track_index = Some(current_idx - 1);
-                    }
-                }
-                if index >= self.playlist.len() && !self.playlist.is_empty() {
-                    self.selected_track_index = Some(self.playlist.len() - 1);
-                } else if self.playlist.is_empty() {
-                    self.selected_track_index = None;
-                }
+    fn remove_selected_tracks(&mut self) {
+        self.playlists.active_mut().remove_selected();
+    }
+
+    fn clear_playlist(&mut self) {
+        self.playlists.active_mut().clear();
+    }
+
+    fn remove_dead_files(&mut self) {
+        let removed = self.playlists.active_mut().remove_dead_files();
+        if removed == 0 {
+            self.error_message = Some("No missing files in this playlist".to_string());
+        }
+    }
+
+    /// Feed new playlist entries to the metadata scanner and collect results
+    fn update_metadata(&mut self, ctx: &egui::Context) {
+        for tab in self.playlists.tabs_mut() {
+            if tab.take_entries_changed() {
+                self.metadata
+                    .scan(tab.playlist.entries().iter().map(PathBuf::as_path));
+            }
+        }
+
+        // Files may have been retagged while we were in the background
+        let focused = ctx.input(|i| i.focused);
+        if focused && !self.window_focused {
+            self.metadata.revalidate();
+        }
+        self.window_focused = focused;
+
+        if self.metadata.poll() {
+            for tab in self.playlists.tabs_mut() {
+                tab.refresh_sort(&self.metadata);
             }
+            ctx.request_repaint();
         }
     }
 
-    fn clear_playlist(&mut self) {
-        self.playlist.clear();
-        self.current_track_index = None;
-        self.selected_track_index = None;
+    /// "12 tracks • 45:07 total • 10:02 left"; durations still being scanned
+    /// are marked with "+"
+    fn playlist_summary(&mut self) -> String {
+        let is_playing_tab = self.playlists.active_index() == self.playlists.playing_index();
+        let tab = self.playlists.active_mut();
+        let durations = tab.durations(&self.metadata);
+        let format = |secs: f64, unknown: usize| {
+            let plus = if unknown > 0 { "+" } else { "" };
+            format!(
+                "{}{}",
+                TrackDisplay::format_duration_digital(secs as f32),
+                plus
+            )
+        };
+
+        let mut summary = format!(
+            "{} tracks • {} total",
+            tab.playlist.len(),
+            format(durations.total.secs, durations.total.unknown)
+        );
+        if is_playing_tab && tab.playlist.current_index().is_some() {
+            let current_left = (self.total_duration - self.current_position).max(0.0);
+            summary.push_str(&format!(
+                " • {} left",
+                format(
+                    durations.upcoming.secs + f64::from(current_left),
+                    durations.upcoming.unknown
+                )
+            ));
+        }
+        summary
+    }
+
+    fn handle_playlist_actions(&mut self, actions: ui_components::PlaylistActions) {
+        let rows = self.playlists.active_mut().visible_rows(&self.metadata);
+        let tab = self.playlists.active_mut();
+        tab.scroll_offset = actions.scroll_offset;
+        if let Some(idx) = actions.enqueue_track {
+            tab.playlist.enqueue(idx);
+        }
+        if let Some(idx) = actions.dequeue_track {
+            tab.playlist.dequeue(idx);
+        }
+        if let Some((idx, modifiers)) = actions.click_track {
+            tab.click(idx, modifiers.command, modifiers.shift, &rows);
+        }
+        if actions.remove_selected {
+            tab.remove_selected();
+        }
+        if actions.crop_selected {
+            tab.crop_to_selection();
+        }
+        if let Some(stars) = actions.rate_selected {
+            let paths = tab.selected_paths();
+            self.library.set_rating(&paths, stars);
+        }
+        if let Some((indices, to)) = actions.move_tracks {
+            tab.move_entries(&indices, to);
+        }
+        if let Some(payload) = actions.copy_here {
+            let target = self.playlists.active_index();
+            self.playlists
+                .copy_entries(payload.tab, &payload.indices, target);
+        }
+        if let Some(idx) = actions.play_track {
+            self.play_track_at_index(idx);
+        }
+    }
+
+    fn new_playlist_tab(&mut self) {
+        self.playlists.add_tab();
+        self.save_config();
+    }
+
+    /// Open the smart playlist editor for a new playlist, or for tab `index`
+    fn edit_smart_playlist(&mut self, index: Option<usize>) {
+        let editor = match index.and_then(|i| self.playlists.tabs().get(i)) {
+            Some(tab) => SmartPlaylistEditor::new(
+                index,
+                &tab.name,
+                tab.smart().map_or("", |smart| smart.rule.as_str()),
+            ),
+            None => SmartPlaylistEditor::new(None, "Smart playlist", ""),
+        };
+        self.smart_editor = Some(editor);
+    }
+
+    fn save_smart_playlist(&mut self, index: Option<usize>, name: &str, rule: String) {
+        let result = match index {
+            Some(index) => {
+                // The tab may have been closed while the editor was open
+                let Some(tab) = self.playlists.tabs_mut().get_mut(index) else {
+                    return;
+                };
+                let seed = tab.smart().map_or_else(library::random_seed, |smart| smart.seed);
+                let result = tab.set_smart(Some(SmartRule { rule, seed }));
+                self.playlists.rename(index, name);
+                result
+            }
+            None => self
+                .playlists
+                .add_smart_tab(
+                    name,
+                    SmartRule {
+                        rule,
+                        seed: library::random_seed(),
+                    },
+                )
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => {
+                self.library.refresh_smart_playlists(&mut self.playlists);
+                self.save_config();
+            }
+            Err(e) => self.error_message = Some(format!("Invalid rule: {}", e)),
+        }
+    }
+
+    fn close_playlist_tab(&mut self, index: usize) {
+        self.renaming_tab = None;
+        self.playlists.close_tab(index);
+        self.save_config();
+    }
+
+    fn undo_close_playlist_tab(&mut self) {
+        if self.playlists.undo_close().is_some() {
+            self.save_config();
+        }
+    }
+
+    fn handle_playlist_tab_actions(&mut self, actions: ui_components::PlaylistTabActions) {
+        if let Some(idx) = actions.select_tab {
+            self.playlists.set_active(idx);
+        }
+        if let Some(idx) = actions.start_rename {
+            let name = self.playlists.tabs()[idx].name.clone();
+            self.renaming_tab = Some((idx, name));
+        }
+        if let Some((idx, name)) = actions.finish_rename {
+            self.playlists.rename(idx, &name);
+            self.renaming_tab = None;
+            self.save_config();
+        }
+        if actions.cancel_rename {
+            self.renaming_tab = None;
+        }
+        if let Some((target, payload)) = actions.copy_to_tab {
+            if self
+                .playlists
+                .copy_entries(payload.tab, &payload.indices, target)
+                > 0
+            {
+                self.save_config();
+            }
+        }
+        if actions.new_tab {
+            self.new_playlist_tab();
+        }
+        if actions.new_smart_tab {
+            self.edit_smart_playlist(None);
+        }
+        if let Some(idx) = actions.edit_smart {
+            self.edit_smart_playlist(Some(idx));
+        }
+        if let Some(idx) = actions.reshuffle {
+            let tab = &mut self.playlists.tabs_mut()[idx];
+            if let Some(smart) = tab.smart().cloned() {
+                let seed = library::random_seed();
+                let _ = tab.set_smart(Some(SmartRule { seed, ..smart }));
+                self.library.refresh_smart_playlists(&mut self.playlists);
+                self.save_config();
+            }
+        }
+        if let Some(idx) = actions.close_tab {
+            self.close_playlist_tab(idx);
+        }
+        if actions.undo_close {
+            self.undo_close_playlist_tab();
+        }
     }
 
     fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
+        // Don't steal keys from text fields (e.g. renaming a tab)
+        let typing = ctx.wants_keyboard_input();
         ctx.input(|i| {
-            if i.key_pressed(egui::Key::Space) {
+            if !typing && i.key_pressed(egui::Key::Space) {
                 self.toggle_play_pause();
             }
+            // Winamp's "jump to file"
+            if !typing && i.key_pressed(egui::Key::J) {
+                self.focus_filter = true;
+            }
+            if !typing && i.key_pressed(egui::Key::Enter) {
+                if let Some(idx) = self.playlists.active().focused() {
+                    self.play_track_at_index(idx);
+                }
+            }
+            if !typing && i.key_pressed(egui::Key::Delete) {
+                self.remove_selected_tracks();
+            }
+            if !typing && i.modifiers.command && i.key_pressed(egui::Key::A) {
+                let tab = self.playlists.active_mut();
+                let rows = tab.visible_rows(&self.metadata);
+                tab.select_all(&rows);
+            }
+            if !typing && i.modifiers.command && i.key_pressed(egui::Key::Z) {
+                if i.modifiers.shift {
+                    self.playlists.active_mut().redo();
+                } else {
+                    self.playlists.active_mut().undo();
+                }
+            }
+            if !typing && i.modifiers.command && i.key_pressed(egui::Key::Y) {
+                self.playlists.active_mut().redo();
+            }
+            if i.modifiers.ctrl && i.key_pressed(egui::Key::T) {
+                if i.modifiers.shift {
+                    self.undo_close_playlist_tab();
+                } else {
+                    self.new_playlist_tab();
+                }
+            }
+            if i.modifiers.ctrl && i.key_pressed(egui::Key::W) {
+                self.close_playlist_tab(self.playlists.active_index());
+            }
             if i.modifiers.ctrl && i.key_pressed(egui::Key::O) {
                 if let Some(path) = rfd::FileDialog::new()
                     .add_filter("Audio Files", &["mp3", "flac", "ogg", "wav"])
@@ -459,39 +1124,373 @@ impl OneAmpApp {
     }
 
     fn handle_dropped_files(&mut self, ctx: &egui::Context) {
-        ctx.input(|i| {
-            if !i.raw.dropped_files.is_empty() {
-                for file in &i.raw.dropped_files {
-                    if let Some(path) = &file.path {
-                        if path.is_file() {
-                            if let Some(ext) = path.extension() {
-                                if ["mp3", "flac", "ogg", "wav"]
-                                    .contains(&ext.to_str().unwrap_or(""))
-                                {
-                                    if !self.playlist.contains(path) {
-                                        self.playlist.push(path.clone());
-                                    }
-                                }
-                            }
-                        }
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+
+        // Skins dropped on the window are installed and applied
+        let (skins, dropped): (Vec<PathBuf>, Vec<PathBuf>) = dropped
+            .into_iter()
+            .filter(|path| path.is_file())
+            .partition(|path| is_skin_file(path));
+        for file in skins {
+            self.install_skin(&file);
+        }
+
+        let paths: Vec<PathBuf> = dropped
+            .into_iter()
+            .filter(|path| is_playlist_file(path))
+            .collect();
+        if !paths.is_empty() {
+            self.add_paths_to_playlist(&paths);
+        }
+    }
+
+    /// Installs a skin package or classic skin and makes it the active skin.
+    fn install_skin(&mut self, file: &Path) {
+        match self.skin_manager.install(file) {
+            Ok(index) => {
+                self.skin_manager.set_active_skin(index);
+                self.save_config();
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Failed to install skin: {:#}", e));
+            }
+        }
+    }
+
+    /// Carry out MPRIS requests and report the playing playlist back
+    #[cfg(target_os = "linux")]
+    fn update_mpris(&mut self, ctx: &egui::Context) {
+        while let Some(request) = self.mpris.as_ref().and_then(MprisServer::try_recv_request) {
+            self.handle_mpris_request(ctx, request);
+        }
+
+        let Some(ref mpris) = self.mpris else {
+            return;
+        };
+        let tab = self.playlists.playing();
+        let playlist = &tab.playlist;
+        let key = (self.playlists.playing_index(), tab.revision());
+        if self.mpris_playlist != Some(key) {
+            mpris.set_tracks(
+                playlist.entries().to_vec(),
+                playlist.ids().to_vec(),
+                playlist.current_index(),
+            );
+            self.mpris_playlist = Some(key);
+        } else {
+            mpris.set_current_index(playlist.current_index());
+        }
+        mpris.set_repeat(playlist.repeat());
+        mpris.set_shuffle(playlist.shuffle());
+    }
+
+    #[cfg(target_os = "linux")]
+    fn handle_mpris_request(&mut self, ctx: &egui::Context, request: MprisRequest) {
+        match request {
+            MprisRequest::Raise => raise_window(ctx),
+            MprisRequest::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            MprisRequest::Play => {
+                if self.playback_state != PlaybackState::Playing {
+                    self.toggle_play_pause();
+                }
+            }
+            MprisRequest::Pause => {
+                if self.playback_state == PlaybackState::Playing {
+                    self.toggle_play_pause();
+                }
+            }
+            MprisRequest::PlayPause => self.toggle_play_pause(),
+            MprisRequest::Stop => self.stop(),
+            MprisRequest::Next => self.play_next(),
+            MprisRequest::Previous => self.play_previous(),
+            MprisRequest::Seek(offset) => {
+                let position = (self.current_position + offset).max(0.0);
+                // Seeking past the end moves on to the next track
+                if self.total_duration > 0.0 && position > self.total_duration {
+                    self.play_next();
+                } else {
+                    self.seek(position);
+                }
+            }
+            MprisRequest::SetPosition(position) => {
+                if position >= 0.0 && position <= self.total_duration {
+                    self.seek(position);
+                }
+            }
+            MprisRequest::SetVolume(volume) => {
+                if let Some(ref engine) = self.audio_engine {
+                    let _ = engine.send_command(AudioCommand::SetVolume(volume));
+                }
+            }
+            MprisRequest::SetRepeat(repeat) => {
+                self.playlists.playing_mut().playlist.set_repeat(repeat);
+            }
+            MprisRequest::SetShuffle(shuffle) => {
+                self.playlists.playing_mut().playlist.set_shuffle(shuffle);
+            }
+            MprisRequest::Open(path) => self.open_paths(&[path]),
+            MprisRequest::GoTo(index) => {
+                let playlist = &mut self.playlists.playing_mut().playlist;
+                if playlist.select(index).is_some() {
+                    self.play_current_entry(index);
+                }
+            }
+            MprisRequest::AddTrack { path, after, play } => {
+                let tab = self.playlists.playing_mut();
+                let at = after.map_or(0, |index| index + 1);
+                let index = tab
+                    .insert_entries(vec![path.clone()], at)
+                    .or_else(|| tab.playlist.entries().iter().position(|p| *p == path));
+                if let (true, Some(index)) = (play, index) {
+                    if tab.playlist.select(index).is_some() {
+                        self.play_current_entry(index);
                     }
                 }
             }
-        });
+            MprisRequest::RemoveTrack(index) => {
+                self.playlists.playing_mut().remove_entries(&[index]);
+            }
+        }
+    }
+
+    /// Answer requests from the control socket
+    #[cfg(unix)]
+    fn update_ipc(&mut self, ctx: &egui::Context) {
+        while let Some(call) = self.ipc.as_ref().and_then(IpcServer::try_recv) {
+            self.handle_ipc_call(ctx, call);
+        }
+    }
+
+    #[cfg(unix)]
+    fn handle_ipc_call(&mut self, ctx: &egui::Context, call: IpcCall) {
+        let reply = match call.request.clone() {
+            IpcRequest::Play => {
+                if self.playback_state != PlaybackState::Playing {
+                    self.toggle_play_pause();
+                }
+                IpcReply::Ok
+            }
+            IpcRequest::Pause => {
+                if self.playback_state == PlaybackState::Playing {
+                    self.toggle_play_pause();
+                }
+                IpcReply::Ok
+            }
+            IpcRequest::TogglePause => {
+                self.toggle_play_pause();
+                IpcReply::Ok
+            }
+            IpcRequest::Stop => {
+                self.stop();
+                IpcReply::Ok
+            }
+            IpcRequest::Next => {
+                self.play_next();
+                IpcReply::Ok
+            }
+            IpcRequest::Previous => {
+                self.play_previous();
+                IpcReply::Ok
+            }
+            IpcRequest::Seek { position_secs } => {
+                self.seek(position_secs.max(0.0));
+                IpcReply::Ok
+            }
+            IpcRequest::SetVolume { volume } => {
+                self.send_ipc_command(AudioCommand::SetVolume(volume))
+            }
+            IpcRequest::SetEqualizerEnabled { enabled } => {
+                self.send_ipc_command(AudioCommand::SetEqualizerEnabled(enabled))
+            }
+            IpcRequest::SetEqualizerBand { band, gain_db } => {
+                self.send_ipc_command(AudioCommand::SetEqualizerBand(band, gain_db))
+            }
+            IpcRequest::SetEqualizerBands { gains } => {
+                self.send_ipc_command(AudioCommand::SetEqualizerBands(gains))
+            }
+            IpcRequest::ResetEqualizer => self.send_ipc_command(AudioCommand::ResetEqualizer),
+            IpcRequest::Open { paths } => {
+                self.open_paths(&paths);
+                IpcReply::Ok
+            }
+            IpcRequest::Enqueue { paths } => {
+                self.playlists.playing_mut().add_paths(&paths);
+                IpcReply::Ok
+            }
+            IpcRequest::PlayIndex { index } => {
+                let playlist = &mut self.playlists.playing_mut().playlist;
+                if playlist.select(index).is_some() {
+                    self.play_current_entry(index);
+                    IpcReply::Ok
+                } else {
+                    IpcReply::error(format!("No playlist entry {}", index))
+                }
+            }
+            IpcRequest::Remove { index } => {
+                let tab = self.playlists.playing_mut();
+                if index < tab.playlist.len() {
+                    tab.remove_entries(&[index]);
+                    IpcReply::Ok
+                } else {
+                    IpcReply::error(format!("No playlist entry {}", index))
+                }
+            }
+            IpcRequest::Clear => {
+                self.playlists.playing_mut().clear();
+                IpcReply::Ok
+            }
+            IpcRequest::SetRepeat { mode } => {
+                self.playlists.playing_mut().playlist.set_repeat(mode);
+                IpcReply::Ok
+            }
+            IpcRequest::SetShuffle { enabled } => {
+                self.playlists.playing_mut().playlist.set_shuffle(enabled);
+                IpcReply::Ok
+            }
+            IpcRequest::Status => IpcReply::Status(self.player_status()),
+            IpcRequest::Playlist => {
+                let playlist = &self.playlists.playing().playlist;
+                IpcReply::Playlist {
+                    entries: playlist.entries().to_vec(),
+                    current_index: playlist.current_index(),
+                }
+            }
+            IpcRequest::Raise => {
+                raise_window(ctx);
+                IpcReply::Ok
+            }
+            IpcRequest::Quit => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                IpcReply::Ok
+            }
+            // Handled by the server itself
+            IpcRequest::Subscribe => IpcReply::Ok,
+        };
+        call.reply(reply);
+    }
+
+    #[cfg(unix)]
+    fn send_ipc_command(&self, command: AudioCommand) -> IpcReply {
+        match self.audio_engine {
+            Some(ref engine) => match engine.send_command(command) {
+                Ok(()) => IpcReply::Ok,
+                Err(e) => IpcReply::error(format!("{:#}", e)),
+            },
+            None => IpcReply::error("No audio engine"),
+        }
+    }
+
+    /// What the player is doing, for control socket clients
+    #[cfg(unix)]
+    fn player_status(&self) -> oneamp_core::PlayerStatus {
+        let playlist = &self.playlists.playing().playlist;
+        oneamp_core::PlayerStatus {
+            state: self.playback_state,
+            track: self.current_track.clone(),
+            position_secs: self.current_position,
+            duration_secs: self.total_duration,
+            volume: self.volume,
+            repeat: playlist.repeat(),
+            shuffle: playlist.shuffle(),
+            current_index: playlist.current_index(),
+            playlist_length: playlist.len(),
+        }
+    }
+}
+
+/// Un-minimize the window and bring it to the front
+#[cfg(unix)]
+fn raise_window(ctx: &egui::Context) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+}
+
+/// Export the player over MPRIS, repainting when a client sends a request
+#[cfg(target_os = "linux")]
+fn start_mpris(ctx: &egui::Context) -> Option<MprisServer> {
+    match MprisServer::connect_session() {
+        Ok(server) => {
+            let ctx = ctx.clone();
+            server.set_waker(move || ctx.request_repaint());
+            Some(server)
+        }
+        Err(e) => {
+            eprintln!("MPRIS unavailable: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Whether a file can be added to a playlist, judging by its extension
+fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["mp3", "flac", "ogg", "wav", "cue"].contains(&ext))
+}
+
+/// Whether a file is a skin package or classic skin, judging by its extension
+fn is_skin_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["oaskin", "wsz"].contains(&ext.to_lowercase().as_str()))
+}
+
+/// Convert the `theme.toml` of the old theme system into a skin once, then
+/// rename it so it isn't converted again
+fn migrate_legacy_theme(skins_dir: &Path) {
+    let Some(theme_file) = dirs::config_dir().map(|d| d.join("oneamp").join("theme.toml")) else {
+        return;
+    };
+    if !theme_file.is_file() {
+        return;
+    }
+    match oneamp_core::skin::migrate_theme_file(&theme_file, skins_dir) {
+        Ok(skin_dir) => {
+            println!("Migrated {} to {}", theme_file.display(), skin_dir.display());
+            let migrated = theme_file.with_extension("toml.migrated");
+            if let Err(e) = std::fs::rename(&theme_file, &migrated) {
+                eprintln!("Failed to rename {}: {}", theme_file.display(), e);
+            }
+        }
+        Err(e) => eprintln!("Failed to migrate theme: {}", e),
     }
 }
 
 impl eframe::App for OneAmpApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Reload the active skin when it's edited, keeping the last working
+        // version if the edit broke it
+        let skin_path = self.skin_manager.get_active_skin().path.clone();
+        if self.skin_watcher.poll(&skin_path) {
+            self.skin_error = match self.skin_manager.reload_active_skin() {
+                Ok(()) => None,
+                Err(e) => Some((skin_path, format!("{:#}", e))),
+            };
+        }
+
         // Apply the active skin at the beginning of each frame
         self.skin_manager.apply_skin(ctx);
 
-        // Create theme from the active skin and apply it
-        let active_skin = self.skin_manager.get_active_skin();
-        let skin_theme = Theme::from_skin(active_skin);
-        skin_theme.apply_to_egui(ctx);
-
-        // Also apply the default theme for any missing properties
+        // Resolve the theme from the active skin, fade it toward the cover's
+        // colors and apply what the skin's style doesn't cover
+        let theme = Theme::from_skin(self.skin_manager.get_active_skin());
+        let min_size = [theme.layout.window_min_width, theme.layout.window_min_height];
+        if min_size != self.window_min_size {
+            ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(min_size.into()));
+            self.window_min_size = min_size;
+        }
+        self.adaptive_theme.set_base(&theme);
+        self.theme = theme;
+        let dt = ctx.input(|i| i.stable_dt);
+        if self.adaptive_theme.update(&mut self.theme, dt) {
+            ctx.request_repaint();
+        }
         self.theme.apply_to_egui(ctx);
 
         // Custom window chrome (platform-specific)
@@ -518,6 +1517,15 @@ impl eframe::App for OneAmpApp {
         self.handle_keyboard_shortcuts(ctx);
         self.handle_dropped_files(ctx);
         self.process_audio_events();
+        self.library.update();
+        self.library.refresh_smart_playlists(&mut self.playlists);
+        self.lyrics.update();
+        self.update_metadata(ctx);
+        self.podcasts.update();
+        #[cfg(target_os = "linux")]
+        self.update_mpris(ctx);
+        #[cfg(unix)]
+        self.update_ipc(ctx);
 
         // Update FPS counter
         let delta_time = ctx.input(|i| i.unstable_dt);
@@ -548,26 +1556,159 @@ impl eframe::App for OneAmpApp {
 
         // Show skin selector dialog
         if self.show_skin_selector {
-            let mut config = AppConfig::load().0;
             let skin_changed = egui::Window::new("Skin Selector")
                 .open(&mut self.show_skin_selector)
                 .show(ctx, |ui| {
-                    skins::ui::skin_selector_dialog(ui, &mut self.skin_manager)
+                    let mut changed =
+                        match skins::ui::skin_selector_dialog(ui, &mut self.skin_manager) {
+                            Ok(changed) => changed,
+                            Err(e) => {
+                                self.error_message =
+                                    Some(format!("Failed to uninstall skin: {:#}", e));
+                                false
+                            }
+                        };
+                    if ui.button("📥 Install skin (.oaskin, .wsz)…").clicked() {
+                        if let Some(file) = rfd::FileDialog::new()
+                            .add_filter("Skins", &["oaskin", "wsz"])
+                            .pick_file()
+                        {
+                            match self.skin_manager.install(&file) {
+                                Ok(index) => {
+                                    changed = self.skin_manager.set_active_skin(index);
+                                }
+                                Err(e) => {
+                                    self.error_message =
+                                        Some(format!("Failed to install skin: {:#}", e));
+                                }
+                            }
+                        }
+                    }
+                    ui.separator();
+                    let mut adaptive = self.adaptive_theme.is_enabled();
+                    if ui
+                        .checkbox(&mut adaptive, "Tint with album art colors")
+                        .on_hover_text("Accent colors follow the playing track's cover")
+                        .changed()
+                    {
+                        self.adaptive_theme.set_enabled(adaptive);
+                        changed = true;
+                    }
+                    changed
                 })
                 .and_then(|r| r.inner)
                 .unwrap_or(false);
 
             if skin_changed {
                 // Save the new skin selection
-                config.active_skin = self.skin_manager.get_active_skin().metadata.name.clone();
-                let _ = config.save();
+                self.save_config();
+            }
+        }
+
+        // Show podcasts panel
+        if self.show_podcasts {
+            let playing = self
+                .current_track
+                .as_ref()
+                .map(|track| (track.path.as_path(), self.current_position));
+            let action = egui::Window::new("Podcasts")
+                .open(&mut self.show_podcasts)
+                .default_size([640.0, 480.0])
+                .show(ctx, |ui| self.podcasts.show(ui, playing))
+                .and_then(|r| r.inner)
+                .flatten();
+            match action {
+                Some(PodcastAction::Play { path, start }) => self.play_episode(path, start),
+                Some(PodcastAction::Seek(secs)) => self.seek(secs),
+                None => {}
+            }
+        }
+
+        // Show scrobbling settings
+        if self.show_scrobbling {
+            let changed = egui::Window::new("Scrobbling")
+                .open(&mut self.show_scrobbling)
+                .resizable(false)
+                .show(ctx, |ui| self.scrobbler.show(ui))
+                .and_then(|r| r.inner)
+                .unwrap_or(false);
+            if changed {
+                self.save_config();
+            }
+        }
+
+        // Show lyrics
+        if self.show_lyrics {
+            let action = egui::Window::new("Lyrics")
+                .open(&mut self.show_lyrics)
+                .default_size([360.0, 420.0])
+                .show(ctx, |ui| self.lyrics.show(ui, &self.theme))
+                .and_then(|r| r.inner)
+                .flatten();
+            match action {
+                Some(LyricsAction::Seek(secs)) => self.seek(secs),
+                Some(LyricsAction::LatencyChanged) => self.save_config(),
+                None => {}
+            }
+        }
+
+        // Show library settings
+        if self.show_library {
+            let changed = egui::Window::new("Library")
+                .open(&mut self.show_library)
+                .resizable(false)
+                .show(ctx, |ui| self.library.show(ui))
+                .and_then(|r| r.inner)
+                .unwrap_or(false);
+            if changed {
+                self.save_config();
+            }
+        }
+
+        // Smart playlist editor
+        if let Some(mut editor) = self.smart_editor.take() {
+            let mut open = true;
+            let title = match editor.tab {
+                Some(_) => "Edit Smart Playlist",
+                None => "New Smart Playlist",
+            };
+            let action = egui::Window::new(title)
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .show(ctx, |ui| editor.show(ui, &self.library))
+                .and_then(|r| r.inner)
+                .flatten();
+            match action {
+                Some(SmartEditorAction::Save { name, rule }) => {
+                    self.save_smart_playlist(editor.tab, &name, rule)
+                }
+                Some(SmartEditorAction::Cancel) => {}
+                None if open => self.smart_editor = Some(editor),
+                None => {}
             }
         }
 
+        // Periodically save the session in case of a crash
+        self.track_window_geometry(ctx);
+        if self.last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+            self.save_config();
+        }
+
         ctx.request_repaint();
 
         // Main vertical layout: Player -> Equalizer -> Playlist
         egui::CentralPanel::default().show(ctx, |ui| {
+            // Classic skins draw their own windows instead
+            if let Some(classic) = self.skin_manager.active_classic_skin().cloned() {
+                self.show_classic_skin(ui, &classic);
+                return;
+            }
+
+            if let Some(background) = self.skin_manager.assets().background() {
+                background.paint(ui.painter(), ui.max_rect());
+            }
+
             ui.vertical(|ui| {
                 // PLAYER SECTION
                 ui_components::render_player_section(
@@ -578,6 +1719,7 @@ impl eframe::App for OneAmpApp {
                     self.total_duration,
                     self.visualizer.get_spectrum(),
                     &mut self.scroll_offset,
+                    self.skin_manager.assets(),
                 );
 
                 ui.add_space(16.0);
@@ -589,9 +1731,10 @@ impl eframe::App for OneAmpApp {
                     self.current_position,
                     self.total_duration,
                 ) {
-                    if let Some(ref engine) = self.audio_engine {
-                        let _ = engine.send_command(AudioCommand::Seek(seek_pos));
-                    }
+                    self.seek(seek_pos);
+                }
+                if let Some(progress) = self.buffering {
+                    ui.label(format!("Buffering… {:.0}%", progress * 100.0));
                 }
 
                 ui.add_space(16.0);
@@ -602,7 +1745,9 @@ impl eframe::App for OneAmpApp {
 
                     // Album art on the left
                     if let Some(ref track) = self.current_track {
-                        self.album_art.load_from_track(&track.path, ctx);
+                        if self.album_art.load_from_track(track, ctx) {
+                            self.adaptive_theme.set_cover(self.album_art.image());
+                        }
                     }
 
                     if self.album_art.has_art() {
@@ -619,6 +1764,7 @@ impl eframe::App for OneAmpApp {
                             &self.theme,
                             self.playback_state == PlaybackState::Playing,
                             self.playback_state == PlaybackState::Paused,
+                            self.skin_manager.assets(),
                         );
 
                         match action {
@@ -635,6 +1781,8 @@ impl eframe::App for OneAmpApp {
                 ui.add_space(8.0);
                 ui.separator();
 
+                let button_size = self.theme.fonts.button_size;
+
                 // VISUALIZER TOGGLE
                 ui.horizontal(|ui| {
                     ui.label(egui::RichText::new("📊 Visualizer:").size(14.0));
@@ -648,9 +1796,23 @@ impl eframe::App for OneAmpApp {
 
                     // Skin selector button
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button(egui::RichText::new("🎨 Skins").size(14.0)).clicked() {
+                        if ui.button(egui::RichText::new("🎨 Skins").size(button_size)).clicked() {
                             self.show_skin_selector = !self.show_skin_selector;
                         }
+                        if ui.button(egui::RichText::new("🎙 Podcasts").size(button_size)).clicked() {
+                            self.show_podcasts = !self.show_podcasts;
+                        }
+                        if ui.button(egui::RichText::new("📡 Scrobbling").size(button_size)).clicked() {
+                            self.show_scrobbling = !self.show_scrobbling;
+                        }
+                        if ui.button(egui::RichText::new("📚 Library").size(button_size)).clicked() {
+                            self.show_library = !self.show_library;
+                        }
+                        if ui.button(egui::RichText::new("🎤 Lyrics").size(button_size)).clicked() {
+                            self.show_lyrics = !self.show_lyrics;
+                        }
+                        ui.checkbox(&mut self.auto_resume, "Resume on startup")
+                            .on_hover_text("Start playing the last track when OneAmp opens");
                     });
 
                     let has_presets = self.onedrop.as_ref().map_or(false, |od| od.has_presets());
@@ -709,7 +1871,7 @@ impl eframe::App for OneAmpApp {
                             ui.separator();
 
                             // Fullscreen toggle
-                            if ui.button(egui::RichText::new("⛶ Fullscreen").size(14.0)).clicked() {
+                            if ui.button(egui::RichText::new("⛶ Fullscreen").size(button_size)).clicked() {
                                 self.visualizer_fullscreen = !self.visualizer_fullscreen;
                             }
 
@@ -785,7 +1947,7 @@ impl eframe::App for OneAmpApp {
                 ui.horizontal(|ui| {
                     ui.heading(egui::RichText::new("🎺 Equalizer").size(16.0));
                     if ui
-                        .button(egui::RichText::new(if self.show_equalizer { "▼" } else { "▶" }).size(14.0))
+                        .button(egui::RichText::new(if self.show_equalizer { "▼" } else { "▶" }).size(button_size))
                         .clicked()
                     {
                         self.show_equalizer = !self.show_equalizer;
@@ -794,13 +1956,19 @@ impl eframe::App for OneAmpApp {
 
                 if self.show_equalizer {
                     ui.add_space(8.0);
-                    if self.equalizer_display.render(
-                        ui,
-                        &self.theme,
-                        &mut self.eq_enabled,
-                        &mut self.eq_gains,
-                        &self.eq_frequencies,
-                    ) {
+                    let changed = ui
+                        .scope(|ui| {
+                            ui.set_min_height(self.theme.layout.equalizer_height);
+                            self.equalizer_display.render(
+                                ui,
+                                &self.theme,
+                                &mut self.eq_enabled,
+                                &mut self.eq_gains,
+                                &self.eq_frequencies,
+                            )
+                        })
+                        .inner;
+                    if changed {
                         if let Some(ref engine) = self.audio_engine {
                             let _ = engine
                                 .send_command(AudioCommand::SetEqualizerEnabled(self.eq_enabled));
@@ -824,37 +1992,209 @@ impl eframe::App for OneAmpApp {
                         if ui.button(egui::RichText::new("📁 Add Folder").size(13.0)).clicked() {
                             self.add_folder_to_playlist();
                         }
+                        if ui
+                            .button(egui::RichText::new("🌐 Add URL").size(13.0))
+                            .on_hover_text("Add an internet radio stream or playlist URL")
+                            .clicked()
+                        {
+                            self.add_url = Some(String::new());
+                        }
                         if ui
                             .add_enabled(
-                                self.selected_track_index.is_some(),
+                                self.playlists.active().has_selection(),
                                 egui::Button::new(egui::RichText::new("➖ Remove").size(13.0)),
                             )
+                            .on_hover_text("Remove selected tracks (Del)")
                             .clicked()
                         {
-                            self.remove_selected_track();
+                            self.remove_selected_tracks();
                         }
                         if ui.button(egui::RichText::new("🗑 Clear").size(13.0)).clicked() {
                             self.clear_playlist();
                         }
+
+                        ui.separator();
+
+                        let mut remove_dead = false;
+                        let tab = self.playlists.active_mut();
+
+                        // Sorting the same column again reverses the order
+                        ui.menu_button(egui::RichText::new("⇅ Sort").size(13.0), |ui| {
+                            for key in SortKey::ALL {
+                                let arrow = match tab.sort {
+                                    Some(sort) if sort.key == key && sort.descending => " ▼",
+                                    Some(sort) if sort.key == key => " ▲",
+                                    _ => "",
+                                };
+                                if ui.button(format!("{}{}", key.label(), arrow)).clicked() {
+                                    tab.sort_by(key, &self.metadata);
+                                    ui.close_menu();
+                                }
+                            }
+                            ui.separator();
+                            if ui.button("Randomize").clicked() {
+                                tab.randomize();
+                                ui.close_menu();
+                            }
+                        });
+
+                        ui.menu_button(egui::RichText::new("✏ Edit").size(13.0), |ui| {
+                            if ui
+                                .add_enabled(tab.can_undo(), egui::Button::new("Undo (Ctrl+Z)"))
+                                .clicked()
+                            {
+                                tab.undo();
+                                ui.close_menu();
+                            }
+                            if ui
+                                .add_enabled(tab.can_redo(), egui::Button::new("Redo (Ctrl+Y)"))
+                                .clicked()
+                            {
+                                tab.redo();
+                                ui.close_menu();
+                            }
+                            ui.separator();
+                            if ui.button("Select all (Ctrl+A)").clicked() {
+                                let rows = tab.visible_rows(&self.metadata);
+                                tab.select_all(&rows);
+                                ui.close_menu();
+                            }
+                            if ui
+                                .add_enabled(
+                                    tab.has_selection(),
+                                    egui::Button::new("Crop to selection"),
+                                )
+                                .clicked()
+                            {
+                                tab.crop_to_selection();
+                                ui.close_menu();
+                            }
+                            if ui.button("Remove missing files").clicked() {
+                                remove_dead = true;
+                                ui.close_menu();
+                            }
+                        });
+
+                        let playlist = &mut tab.playlist;
+
+                        // Repeat: off -> all -> one
+                        let repeat_label = match playlist.repeat() {
+                            RepeatMode::Off => "🔁 Repeat: Off",
+                            RepeatMode::All => "🔁 Repeat: All",
+                            RepeatMode::One => "🔂 Repeat: One",
+                        };
+                        if ui
+                            .selectable_label(
+                                playlist.repeat() != RepeatMode::Off,
+                                egui::RichText::new(repeat_label).size(13.0),
+                            )
+                            .clicked()
+                        {
+                            playlist.cycle_repeat();
+                        }
+
+                        let album_shuffle = playlist.shuffle_mode() == ShuffleMode::Albums;
+                        if ui
+                            .selectable_label(
+                                playlist.shuffle() && album_shuffle,
+                                egui::RichText::new("💿 Albums").size(13.0),
+                            )
+                            .on_hover_text("Shuffle whole albums")
+                            .clicked()
+                        {
+                            if playlist.shuffle() && album_shuffle {
+                                playlist.set_shuffle(false);
+                            } else {
+                                playlist.set_shuffle_mode(ShuffleMode::Albums);
+                                playlist.set_shuffle(true);
+                            }
+                        }
+                        if ui
+                            .selectable_label(
+                                playlist.shuffle() && !album_shuffle,
+                                egui::RichText::new("🔀 Shuffle").size(13.0),
+                            )
+                            .clicked()
+                        {
+                            if playlist.shuffle() && !album_shuffle {
+                                playlist.set_shuffle(false);
+                            } else {
+                                playlist.set_shuffle_mode(ShuffleMode::Tracks);
+                                playlist.set_shuffle(true);
+                            }
+                        }
+
+                        if remove_dead {
+                            self.remove_dead_files();
+                        }
                     });
                 });
 
-                ui.add_space(4.0);
-
-                let actions = ui_components::render_playlist(
+                // PLAYLIST TABS
+                let tab_actions = ui_components::render_playlist_tabs(
                     ui,
                     &self.theme,
-                    &self.playlist,
-                    self.current_track_index,
-                    self.selected_track_index,
+                    &self.playlists,
+                    &mut self.renaming_tab,
                 );
+                self.handle_playlist_tab_actions(tab_actions);
+
+                ui.add_space(4.0);
+
+                // FILTER ("jump to file")
+                let rows = self.playlists.active_mut().visible_rows(&self.metadata);
+                let mut play_first = None;
+                ui.horizontal(|ui| {
+                    let tab = self.playlists.active_mut();
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut tab.filter)
+                            .id(egui::Id::new("playlist_filter"))
+                            .hint_text("🔍 Jump to file (J)")
+                            .desired_width(220.0),
+                    );
+                    if self.focus_filter {
+                        response.request_focus();
+                        self.focus_filter = false;
+                    }
+                    if response.lost_focus() {
+                        if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            if let Some(&first) = rows.first() {
+                                tab.select_only(first);
+                                play_first = Some(first);
+                            }
+                        } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                            tab.filter.clear();
+                        }
+                    }
+                    let tab = self.playlists.active();
+                    if !tab.filter.is_empty() {
+                        ui.label(format!("{} of {}", rows.len(), tab.playlist.len()));
+                    }
 
-                if let Some(idx) = actions.play_track {
+                    let summary = self.playlist_summary();
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(egui::RichText::new(summary).size(12.0));
+                        let pending = self.metadata.pending_count();
+                        if pending > 0 {
+                            ui.spinner()
+                                .on_hover_text(format!("Reading tags of {} files", pending));
+                        }
+                    });
+                });
+                if let Some(idx) = play_first {
                     self.play_track_at_index(idx);
                 }
-                if let Some(idx) = actions.select_track {
-                    self.selected_track_index = Some(idx);
-                }
+                let rows = self.playlists.active_mut().visible_rows(&self.metadata);
+
+                let actions = ui_components::render_playlist(
+                    ui,
+                    &self.theme,
+                    &self.playlists,
+                    &rows,
+                    &mut self.metadata,
+                    self.library.stats(),
+                );
+                self.handle_playlist_actions(actions);
             });
         });
 
@@ -876,6 +2216,63 @@ impl eframe::App for OneAmpApp {
             self.error_message = None;
         }
 
+        // Errors of the active skin's last reload
+        let active_skin = self.skin_manager.get_active_skin();
+        if let Some((_, error)) = self
+            .skin_error
+            .as_ref()
+            .filter(|(path, _)| *path == active_skin.path)
+        {
+            let color = skins::parser::hex_to_color32(&active_skin.colors.error)
+                .unwrap_or(egui::Color32::RED);
+            egui::Area::new(egui::Id::new("skin_error"))
+                .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -16.0])
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.colored_label(
+                            color,
+                            format!(
+                                "⚠ Skin \"{}\" has errors; showing the last working version",
+                                active_skin.metadata.name
+                            ),
+                        );
+                        ui.label(egui::RichText::new(error).monospace());
+                    });
+                });
+        }
+
+        // "Add URL" dialog
+        if let Some(mut url) = self.add_url.take() {
+            let mut open = true;
+            let mut add = false;
+            egui::Window::new("Add URL")
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut url)
+                            .hint_text("https://example.com/radio.pls")
+                            .desired_width(320.0),
+                    );
+                    response.request_focus();
+                    let entered =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Add").clicked() || entered {
+                        add = true;
+                    }
+                });
+            let path = PathBuf::from(url.trim());
+            if add && stream::is_stream_url(&path) {
+                self.add_paths_to_playlist(&[path]);
+            } else if add {
+                self.error_message = Some(format!("Not an HTTP(S) URL: {}", url.trim()));
+            } else if open {
+                self.add_url = Some(url);
+            }
+        }
+
         // Fullscreen visualizer mode
         if self.visualizer_fullscreen && self.use_onedrop {
             egui::CentralPanel::default().show(ctx, |ui| {
@@ -922,4 +2319,9 @@ impl eframe::App for OneAmpApp {
             });
         }
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_config();
+        self.config_writer.finish();
+    }
 }
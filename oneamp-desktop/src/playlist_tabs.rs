@@ -0,0 +1,1287 @@
+use oneamp_core::smart_playlist::Rule;
+use oneamp_core::stats::PlayStats;
+use oneamp_core::{cue, stream, DurationTotal, MetadataService, Playlist, TrackInfo, TrackMetadata};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::PlaylistTabConfig;
+use crate::track_display::TrackDisplay;
+
+/// Maximum number of closed tabs that can be reopened with undo
+const MAX_CLOSED_TABS: usize = 10;
+
+/// Column a playlist can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Path,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 5] = [
+        SortKey::Title,
+        SortKey::Artist,
+        SortKey::Album,
+        SortKey::Duration,
+        SortKey::Path,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Title => "Title",
+            SortKey::Artist => "Artist",
+            SortKey::Album => "Album",
+            SortKey::Duration => "Duration",
+            SortKey::Path => "File path",
+        }
+    }
+}
+
+/// Sort order last applied to a playlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// Rule filling a smart playlist, see [`oneamp_core::smart_playlist`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartRule {
+    pub rule: String,
+    /// Picks the `ORDER BY random` order; a new seed reshuffles
+    pub seed: u64,
+}
+
+/// What the entries of a smart playlist were last computed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SmartKey {
+    library_generation: u64,
+    stats_revision: u64,
+    /// Minutes since the epoch, for rules depending on the time
+    minute: u64,
+}
+
+/// Maximum number of undo steps kept per playlist
+const MAX_UNDO_STEPS: usize = 50;
+
+/// Maximum number of entries kept across all undo snapshots of a playlist,
+/// so huge playlists don't keep dozens of full copies alive
+const MAX_UNDO_ENTRIES: usize = 1_000_000;
+
+/// Rows matching the filter, remembered until the filter or the entries change
+#[derive(Debug, Clone)]
+struct FilteredRows {
+    filter: String,
+    revision: u64,
+    metadata_generation: u64,
+    rows: Arc<[usize]>,
+}
+
+/// Total and remaining play time of a playlist
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlaylistDurations {
+    pub total: DurationTotal,
+    /// Tracks after the current one, see [`Playlist::upcoming`]
+    pub upcoming: DurationTotal,
+}
+
+/// What [`PlaylistDurations`] were computed from
+#[derive(Debug, Clone, PartialEq)]
+struct DurationsKey {
+    revision: u64,
+    metadata_generation: u64,
+    current: Option<usize>,
+    shuffle: bool,
+    queue: Vec<usize>,
+}
+
+/// A named playlist with its own view state
+#[derive(Debug, Clone)]
+pub struct PlaylistTab {
+    pub name: String,
+    pub playlist: Playlist,
+    pub scroll_offset: f32,
+    pub sort: Option<PlaylistSort>,
+    /// Incremental search typed in the filter box
+    pub filter: String,
+    selection: BTreeSet<usize>,
+    /// Row the last plain or ctrl click landed on, where shift-click ranges start
+    anchor: Option<usize>,
+    undo: Vec<Vec<PathBuf>>,
+    redo: Vec<Vec<PathBuf>>,
+    /// Bumped on every edit of the entries
+    revision: u64,
+    /// Revision whose entries were last handed to the metadata scanner
+    scanned_revision: Option<u64>,
+    /// Revision left by a sort that ran before all entries were scanned
+    resort_revision: Option<u64>,
+    filtered: Option<FilteredRows>,
+    durations: Option<(DurationsKey, PlaylistDurations)>,
+    smart: Option<SmartRule>,
+    /// Parsed smart rule, `None` while it's invalid
+    smart_rule: Option<Rule>,
+    smart_key: Option<SmartKey>,
+}
+
+impl PlaylistTab {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            playlist: Playlist::new(),
+            scroll_offset: 0.0,
+            sort: None,
+            filter: String::new(),
+            selection: BTreeSet::new(),
+            anchor: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            revision: 0,
+            scanned_revision: None,
+            resort_revision: None,
+            filtered: None,
+            durations: None,
+            smart: None,
+            smart_rule: None,
+            smart_key: None,
+        }
+    }
+
+    /// Rebuild a tab saved by a previous session
+    pub fn from_config(config: PlaylistTabConfig) -> Self {
+        let mut playlist = Playlist::from_paths(config.entries);
+        playlist.set_repeat(config.repeat);
+        playlist.set_shuffle_mode(config.shuffle_mode);
+        if let Some(index) = config.current_index {
+            playlist.select(index);
+        }
+        // Enable shuffle after selecting so the shuffle order starts at the current track
+        playlist.set_shuffle(config.shuffle);
+
+        let mut tab = Self::new(config.name);
+        tab.playlist = playlist;
+        tab.scroll_offset = config.scroll_offset;
+        tab.sort = config.sort;
+        if let Some(index) = config.selected_index.filter(|&i| i < tab.playlist.len()) {
+            tab.select_only(index);
+        }
+        if let Err(e) = tab.set_smart(config.smart) {
+            eprintln!("Invalid rule for smart playlist \"{}\": {}", tab.name, e);
+        }
+        tab
+    }
+
+    pub fn to_config(&self) -> PlaylistTabConfig {
+        PlaylistTabConfig {
+            name: self.name.clone(),
+            entries: self.playlist.entries().to_vec(),
+            current_index: self.playlist.current_index(),
+            selected_index: self.anchor,
+            scroll_offset: self.scroll_offset,
+            sort: self.sort,
+            shuffle: self.playlist.shuffle(),
+            shuffle_mode: self.playlist.shuffle_mode(),
+            repeat: self.playlist.repeat(),
+            smart: self.smart.clone(),
+        }
+    }
+
+    // --- Smart playlists ---
+
+    pub fn smart(&self) -> Option<&SmartRule> {
+        self.smart.as_ref()
+    }
+
+    /// Make this a smart playlist, or a plain one with `None`. An invalid
+    /// rule is kept so it can be fixed, but fills nothing until then.
+    pub fn set_smart(&mut self, smart: Option<SmartRule>) -> Result<(), String> {
+        self.smart_key = None;
+        self.smart_rule = None;
+        self.smart = smart;
+        if let Some(smart) = &self.smart {
+            self.smart_rule = Some(smart.rule.parse()?);
+        }
+        Ok(())
+    }
+
+    /// Refill a smart playlist from the library tracks, unless nothing it
+    /// depends on changed since the last time. The track playing stays in
+    /// the list even if it no longer matches. Returns true if the entries
+    /// changed.
+    pub fn refresh_smart(
+        &mut self,
+        tracks: &[TrackInfo],
+        library_generation: u64,
+        stats: &PlayStats,
+        now: u64,
+    ) -> bool {
+        let (Some(smart), Some(rule)) = (&self.smart, &self.smart_rule) else {
+            return false;
+        };
+        let key = SmartKey {
+            library_generation,
+            stats_revision: stats.revision(),
+            minute: if rule.uses_time() { now / 60 } else { 0 },
+        };
+        if self.smart_key == Some(key) {
+            return false;
+        }
+        self.smart_key = Some(key);
+
+        let mut entries = rule.evaluate(tracks, stats, now, smart.seed);
+        if let (Some(index), Some(current)) =
+            (self.playlist.current_index(), self.playlist.current())
+        {
+            if !entries.iter().any(|path| path == current) {
+                entries.insert(index.min(entries.len()), current.to_path_buf());
+            }
+        }
+        if entries == self.playlist.entries() {
+            return false;
+        }
+
+        let selected = self.selected_paths();
+        self.playlist.replace_entries(entries);
+        self.reselect_paths(selected);
+        self.sort = None;
+        self.edited();
+        true
+    }
+
+    // --- Selection ---
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selection.contains(&index)
+    }
+
+    pub fn has_selection(&self) -> bool {
+        !self.selection.is_empty()
+    }
+
+    /// Selected entry indices in playlist order
+    pub fn selected_indices(&self) -> Vec<usize> {
+        self.selection.iter().copied().collect()
+    }
+
+    /// The row keyboard and shift-click actions start from
+    pub fn focused(&self) -> Option<usize> {
+        self.anchor
+    }
+
+    pub fn select_only(&mut self, index: usize) {
+        self.selection.clear();
+        self.selection.insert(index);
+        self.anchor = Some(index);
+    }
+
+    /// Apply a click on a row, Explorer style: ctrl toggles, shift extends
+    /// from the anchor along the rows currently shown.
+    pub fn click(&mut self, index: usize, ctrl: bool, shift: bool, rows: &[usize]) {
+        if shift {
+            let anchor = self.anchor.unwrap_or(index);
+            let from = rows.iter().position(|&i| i == anchor);
+            let to = rows.iter().position(|&i| i == index);
+            if let (Some(from), Some(to)) = (from, to) {
+                if !ctrl {
+                    self.selection.clear();
+                }
+                let (start, end) = if from <= to { (from, to) } else { (to, from) };
+                self.selection.extend(rows[start..=end].iter().copied());
+                return;
+            }
+        }
+
+        if ctrl {
+            if !self.selection.remove(&index) {
+                self.selection.insert(index);
+            }
+            self.anchor = Some(index);
+        } else {
+            self.select_only(index);
+        }
+    }
+
+    pub fn select_all(&mut self, rows: &[usize]) {
+        self.selection = rows.iter().copied().collect();
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+        self.anchor = None;
+    }
+
+    // --- Filter ---
+
+    /// Entry indices to show: all of them, or those matching the filter by
+    /// title or path (case-insensitive)
+    pub fn visible_rows(&mut self, metadata: &MetadataService) -> Arc<[usize]> {
+        let needle = self.filter.trim().to_lowercase();
+        // Titles only matter while filtering
+        let metadata_generation = if needle.is_empty() {
+            0
+        } else {
+            metadata.generation()
+        };
+
+        if let Some(cached) = &self.filtered {
+            if cached.filter == self.filter
+                && cached.revision == self.revision
+                && cached.metadata_generation == metadata_generation
+            {
+                return cached.rows.clone();
+            }
+        }
+
+        let rows: Arc<[usize]> = if needle.is_empty() {
+            (0..self.playlist.len()).collect()
+        } else {
+            self.playlist
+                .entries()
+                .iter()
+                .enumerate()
+                .filter(|(_, path)| {
+                    metadata
+                        .get(path)
+                        .and_then(TrackMetadata::info)
+                        .is_some_and(|track| {
+                            TrackDisplay::get_title(track)
+                                .to_lowercase()
+                                .contains(&needle)
+                        })
+                        || path.to_string_lossy().to_lowercase().contains(&needle)
+                })
+                .map(|(i, _)| i)
+                .collect()
+        };
+
+        self.filtered = Some(FilteredRows {
+            filter: self.filter.clone(),
+            revision: self.revision,
+            metadata_generation,
+            rows: rows.clone(),
+        });
+        rows
+    }
+
+    /// Whether the entries changed since the last call, i.e. there may be
+    /// new files for the metadata scanner
+    pub fn take_entries_changed(&mut self) -> bool {
+        let changed = self.scanned_revision != Some(self.revision);
+        self.scanned_revision = Some(self.revision);
+        changed
+    }
+
+    /// Changes whenever the entries change
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Total play time of the playlist and of the tracks left to play
+    pub fn durations(&mut self, metadata: &MetadataService) -> PlaylistDurations {
+        let key = DurationsKey {
+            revision: self.revision,
+            metadata_generation: metadata.generation(),
+            current: self.playlist.current_index(),
+            shuffle: self.playlist.shuffle(),
+            queue: self.playlist.queue().collect(),
+        };
+        if let Some((cached_key, durations)) = &self.durations {
+            if *cached_key == key {
+                return *durations;
+            }
+        }
+
+        let entries = self.playlist.entries();
+        let durations = PlaylistDurations {
+            total: metadata.total_duration(entries.iter().map(PathBuf::as_path)),
+            upcoming: metadata.total_duration(
+                self.playlist
+                    .upcoming()
+                    .into_iter()
+                    .map(|i| entries[i].as_path()),
+            ),
+        };
+        self.durations = Some((key, durations));
+        durations
+    }
+
+    // --- Edits (undoable) ---
+
+    /// Remember the entries before an edit
+    fn checkpoint(&mut self) {
+        self.undo.push(self.playlist.entries().to_vec());
+        self.redo.clear();
+
+        let mut total: usize = self.undo.iter().map(Vec::len).sum();
+        while self.undo.len() > MAX_UNDO_STEPS || (total > MAX_UNDO_ENTRIES && self.undo.len() > 1)
+        {
+            total -= self.undo.remove(0).len();
+        }
+    }
+
+    /// Record that the entries changed
+    fn edited(&mut self) {
+        self.revision += 1;
+        self.filtered = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(entries) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(self.playlist.entries().to_vec());
+        self.playlist.replace_entries(entries);
+        self.clear_selection();
+        self.edited();
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(entries) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(self.playlist.entries().to_vec());
+        self.playlist.replace_entries(entries);
+        self.clear_selection();
+        self.edited();
+        true
+    }
+
+    /// Add files, expanding CUE sheets and skipping entries already present.
+    /// Returns how many entries were added.
+    pub fn add_paths(&mut self, paths: &[PathBuf]) -> usize {
+        let before = self.playlist.len();
+        self.checkpoint();
+        for path in paths {
+            self.playlist.add_expanded(path);
+        }
+        self.finish_add(before)
+    }
+
+    /// Append entries as they are (no CUE expansion), skipping duplicates.
+    /// Returns how many entries were added.
+    pub fn append_entries(&mut self, paths: Vec<PathBuf>) -> usize {
+        let before = self.playlist.len();
+        self.checkpoint();
+        for path in paths {
+            if !self.playlist.contains(&path) {
+                self.playlist.add(path);
+            }
+        }
+        self.finish_add(before)
+    }
+
+    /// Insert entries before `at`, skipping duplicates. Returns the index of
+    /// the first inserted entry, or `None` if nothing was added.
+    pub fn insert_entries(&mut self, paths: Vec<PathBuf>, at: usize) -> Option<usize> {
+        let before = self.playlist.len();
+        if self.append_entries(paths) == 0 {
+            return None;
+        }
+        let added: Vec<usize> = (before..self.playlist.len()).collect();
+        let start = self.playlist.move_entries(&added, at.min(before));
+        self.clear_selection();
+        self.edited();
+        Some(start)
+    }
+
+    fn finish_add(&mut self, before: usize) -> usize {
+        let added = self.playlist.len() - before;
+        if added == 0 {
+            // Nothing changed, forget the checkpoint
+            self.undo.pop();
+        } else {
+            self.edited();
+        }
+        added
+    }
+
+    pub fn remove_selected(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        let first = self.selection.iter().next().copied();
+        self.playlist.remove_many(&self.selected_indices());
+        self.clear_selection();
+        self.edited();
+
+        // Keep a selection near the removed rows for repeated deletes
+        if let Some(first) = first {
+            if !self.playlist.is_empty() {
+                self.select_only(first.min(self.playlist.len() - 1));
+            }
+        }
+    }
+
+    /// Remove entries by index
+    pub fn remove_entries(&mut self, indices: &[usize]) {
+        if indices.iter().all(|&i| i >= self.playlist.len()) {
+            return;
+        }
+        self.checkpoint();
+        self.playlist.remove_many(indices);
+        self.clear_selection();
+        self.edited();
+    }
+
+    /// Keep only the selected entries
+    pub fn crop_to_selection(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        self.playlist.retain_indices(&self.selected_indices());
+        self.clear_selection();
+        self.edited();
+    }
+
+    /// Remove entries whose file no longer exists. Returns how many were removed.
+    pub fn remove_dead_files(&mut self) -> usize {
+        let dead: Vec<usize> = self
+            .playlist
+            .entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| {
+                let (file, _) = cue::split_virtual_track(path);
+                !stream::is_stream_url(path) && !file.exists()
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if !dead.is_empty() {
+            self.checkpoint();
+            self.playlist.remove_many(&dead);
+            self.clear_selection();
+            self.edited();
+        }
+        dead.len()
+    }
+
+    pub fn clear(&mut self) {
+        if self.playlist.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        self.playlist.clear();
+        self.clear_selection();
+        self.edited();
+    }
+
+    /// Move entries to just before `to` (an index into the list before the
+    /// move), keeping them selected
+    pub fn move_entries(&mut self, indices: &[usize], to: usize) {
+        if indices.is_empty() {
+            return;
+        }
+        self.checkpoint();
+        let start = self.playlist.move_entries(indices, to);
+        self.selection = (start..start + indices.len()).collect();
+        self.anchor = Some(start);
+        self.edited();
+    }
+
+    /// Sort by a column. Sorting again by the same column reverses the order.
+    /// Tracks the scanner hasn't reached yet sort by their file name until
+    /// [`Self::refresh_sort`] sorts again with their metadata.
+    pub fn sort_by(&mut self, key: SortKey, metadata: &MetadataService) {
+        let descending = matches!(self.sort, Some(sort) if sort.key == key && !sort.descending);
+        self.checkpoint();
+        self.apply_sort(PlaylistSort { key, descending }, metadata);
+    }
+
+    /// Sort again once every entry of a playlist sorted before its metadata
+    /// was known has been scanned, unless it was edited since. Returns true
+    /// if the entries were sorted.
+    pub fn refresh_sort(&mut self, metadata: &MetadataService) -> bool {
+        let Some(revision) = self.resort_revision else {
+            return false;
+        };
+        let Some(sort) = self.sort.filter(|_| revision == self.revision) else {
+            // Keep the order the user made since
+            self.resort_revision = None;
+            return false;
+        };
+        if !self.is_scanned(metadata) {
+            return false;
+        }
+        self.apply_sort(sort, metadata);
+        true
+    }
+
+    fn apply_sort(&mut self, sort: PlaylistSort, metadata: &MetadataService) {
+        let PlaylistSort { key, descending } = sort;
+        let selected = self.selected_paths();
+
+        match key {
+            SortKey::Path => sort_playlist(&mut self.playlist, descending, |path| {
+                path.to_string_lossy().to_lowercase()
+            }),
+            SortKey::Duration => sort_playlist(&mut self.playlist, descending, |path| {
+                metadata
+                    .get(path)
+                    .and_then(TrackMetadata::duration_secs)
+                    .map(|secs| (secs * 1000.0) as u64)
+            }),
+            SortKey::Title | SortKey::Artist | SortKey::Album => {
+                sort_playlist(&mut self.playlist, descending, |path| {
+                    match metadata.get(path) {
+                        Some(track) => text_sort_key(path, track.info(), key),
+                        None => (false, file_name_key(path)),
+                    }
+                })
+            }
+        }
+
+        self.reselect_paths(selected);
+        self.sort = Some(sort);
+        self.edited();
+        let complete = key == SortKey::Path || self.is_scanned(metadata);
+        self.resort_revision = (!complete).then_some(self.revision);
+    }
+
+    /// Whether every entry has metadata, probed or failed
+    fn is_scanned(&self, metadata: &MetadataService) -> bool {
+        self.playlist
+            .entries()
+            .iter()
+            .all(|path| metadata.get(path).is_some())
+    }
+
+    /// Put the entries in a random order
+    pub fn randomize(&mut self) {
+        self.checkpoint();
+        let selected = self.selected_paths();
+        self.playlist.randomize();
+        self.reselect_paths(selected);
+        self.sort = None;
+        self.edited();
+    }
+
+    /// Files of the selected entries, in playlist order
+    pub fn selected_paths(&self) -> Vec<PathBuf> {
+        self.selection
+            .iter()
+            .filter_map(|&i| self.playlist.get(i))
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Select the same files again after the entries were reordered
+    fn reselect_paths(&mut self, paths: Vec<PathBuf>) {
+        let paths: HashSet<PathBuf> = paths.into_iter().collect();
+        self.selection = self
+            .playlist
+            .entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| paths.contains(*path))
+            .map(|(i, _)| i)
+            .collect();
+        self.anchor = self.selection.iter().next().copied();
+    }
+}
+
+fn sort_playlist<K: Ord>(playlist: &mut Playlist, descending: bool, key: impl FnMut(&Path) -> K) {
+    if descending {
+        let mut key = key;
+        playlist.sort_by_key(|path| Reverse(key(path)));
+    } else {
+        playlist.sort_by_key(key);
+    }
+}
+
+/// Case-insensitive tag value, falling back to the file name for titles.
+/// Tracks without the tag sort last.
+fn text_sort_key(path: &Path, track: Option<&TrackInfo>, key: SortKey) -> (bool, String) {
+    let value = track.and_then(|track| match key {
+        SortKey::Title => track.title.as_ref(),
+        SortKey::Artist => track.artist.as_ref(),
+        SortKey::Album => track.album.as_ref(),
+        _ => None,
+    });
+
+    match value {
+        Some(value) => (false, value.to_lowercase()),
+        None if key == SortKey::Title => (false, file_name_key(path)),
+        None => (true, String::new()),
+    }
+}
+
+fn file_name_key(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// A tab that was closed, remembered so it can be reopened
+#[derive(Debug, Clone)]
+struct ClosedTab {
+    index: usize,
+    tab: PlaylistTab,
+}
+
+/// All open playlists, the one shown and the one playing
+#[derive(Debug, Clone)]
+pub struct PlaylistTabs {
+    tabs: Vec<PlaylistTab>,
+    active: usize,
+    playing: usize,
+    closed: Vec<ClosedTab>,
+}
+
+impl Default for PlaylistTabs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaylistTabs {
+    /// Create a single empty "Default" playlist
+    pub fn new() -> Self {
+        Self {
+            tabs: vec![PlaylistTab::new("Default")],
+            active: 0,
+            playing: 0,
+            closed: Vec::new(),
+        }
+    }
+
+    /// Rebuild the tabs saved by a previous session
+    pub fn from_config(tabs: Vec<PlaylistTabConfig>, active: usize, playing: usize) -> Self {
+        if tabs.is_empty() {
+            return Self::new();
+        }
+
+        let tabs: Vec<PlaylistTab> = tabs.into_iter().map(PlaylistTab::from_config).collect();
+        let last = tabs.len() - 1;
+        Self {
+            tabs,
+            active: active.min(last),
+            playing: playing.min(last),
+            closed: Vec::new(),
+        }
+    }
+
+    pub fn to_config(&self) -> Vec<PlaylistTabConfig> {
+        self.tabs.iter().map(PlaylistTab::to_config).collect()
+    }
+
+    pub fn tabs(&self) -> &[PlaylistTab] {
+        &self.tabs
+    }
+
+    pub fn tabs_mut(&mut self) -> &mut [PlaylistTab] {
+        &mut self.tabs
+    }
+
+    /// Index of the tab being shown
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Index of the tab that Next/Previous and end-of-track navigate
+    pub fn playing_index(&self) -> usize {
+        self.playing
+    }
+
+    pub fn active(&self) -> &PlaylistTab {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut PlaylistTab {
+        &mut self.tabs[self.active]
+    }
+
+    pub fn playing(&self) -> &PlaylistTab {
+        &self.tabs[self.playing]
+    }
+
+    pub fn playing_mut(&mut self) -> &mut PlaylistTab {
+        &mut self.tabs[self.playing]
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.active = index;
+        }
+    }
+
+    pub fn set_playing(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.playing = index;
+        }
+    }
+
+    /// Open a new empty tab and show it. Returns its index.
+    pub fn add_tab(&mut self) -> usize {
+        let name = (1..)
+            .map(|n| format!("Playlist {}", n))
+            .find(|name| !self.tabs.iter().any(|tab| tab.name == *name))
+            .unwrap_or_default();
+        self.tabs.push(PlaylistTab::new(name));
+        self.active = self.tabs.len() - 1;
+        self.active
+    }
+
+    /// Open a smart playlist and show it. Returns its index.
+    pub fn add_smart_tab(&mut self, name: &str, smart: SmartRule) -> Result<usize, String> {
+        let mut tab = PlaylistTab::new(name.trim());
+        tab.set_smart(Some(smart))?;
+        self.tabs.push(tab);
+        self.active = self.tabs.len() - 1;
+        Ok(self.active)
+    }
+
+    pub fn rename(&mut self, index: usize, name: &str) {
+        let name = name.trim();
+        if let Some(tab) = self.tabs.get_mut(index) {
+            if !name.is_empty() {
+                tab.name = name.to_string();
+            }
+        }
+    }
+
+    /// Close a tab, keeping it around for [`PlaylistTabs::undo_close`].
+    /// Closing the last tab leaves an empty "Default" playlist.
+    pub fn close_tab(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            return;
+        }
+
+        let tab = self.tabs.remove(index);
+        self.closed.push(ClosedTab { index, tab });
+        if self.closed.len() > MAX_CLOSED_TABS {
+            self.closed.remove(0);
+        }
+
+        if self.tabs.is_empty() {
+            self.tabs.push(PlaylistTab::new("Default"));
+        }
+
+        let last = self.tabs.len() - 1;
+        let shift = |i: usize| if i > index { i - 1 } else { i.min(last) };
+        self.active = shift(self.active);
+        // Playback moves on within the tab now shown
+        self.playing = if self.playing == index {
+            self.active
+        } else {
+            shift(self.playing)
+        };
+    }
+
+    /// Name of the tab [`PlaylistTabs::undo_close`] would reopen
+    pub fn last_closed_name(&self) -> Option<&str> {
+        self.closed.last().map(|closed| closed.tab.name.as_str())
+    }
+
+    /// Reopen the most recently closed tab at its old position and show it
+    pub fn undo_close(&mut self) -> Option<usize> {
+        let ClosedTab { index, tab } = self.closed.pop()?;
+        let index = index.min(self.tabs.len());
+        self.tabs.insert(index, tab);
+
+        if self.playing >= index {
+            self.playing += 1;
+        }
+        self.active = index;
+        Some(index)
+    }
+
+    /// Append copies of entries from one tab to another, skipping files the
+    /// target already contains. Returns how many entries were added.
+    pub fn copy_entries(&mut self, from: usize, indices: &[usize], to: usize) -> usize {
+        if from == to || from >= self.tabs.len() || to >= self.tabs.len() {
+            return 0;
+        }
+
+        let paths: Vec<_> = indices
+            .iter()
+            .filter_map(|&i| self.tabs[from].playlist.get(i))
+            .map(Path::to_path_buf)
+            .collect();
+
+        self.tabs[to].append_entries(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oneamp_core::test_wav::write_silent;
+
+    fn tabs_with_entries() -> PlaylistTabs {
+        let mut tabs = PlaylistTabs::new();
+        tabs.active_mut().append_entries(
+            (0..3)
+                .map(|i| PathBuf::from(format!("/music/track{}.mp3", i)))
+                .collect(),
+        );
+        tabs
+    }
+
+    fn tab_with_entries(count: usize) -> PlaylistTab {
+        let mut tab = PlaylistTab::new("Test");
+        tab.append_entries(
+            (0..count)
+                .map(|i| PathBuf::from(format!("/music/track{}.mp3", i)))
+                .collect(),
+        );
+        tab
+    }
+
+    #[test]
+    fn test_add_tab_unique_names() {
+        let mut tabs = PlaylistTabs::new();
+        assert_eq!(tabs.add_tab(), 1);
+        assert_eq!(tabs.add_tab(), 2);
+        assert_eq!(tabs.tabs()[1].name, "Playlist 1");
+        assert_eq!(tabs.tabs()[2].name, "Playlist 2");
+        assert_eq!(tabs.active_index(), 2);
+    }
+
+    #[test]
+    fn test_rename_ignores_blank_names() {
+        let mut tabs = PlaylistTabs::new();
+        tabs.rename(0, "  Party  ");
+        assert_eq!(tabs.active().name, "Party");
+        tabs.rename(0, "   ");
+        assert_eq!(tabs.active().name, "Party");
+    }
+
+    #[test]
+    fn test_close_and_undo() {
+        let mut tabs = tabs_with_entries();
+        tabs.add_tab();
+        tabs.rename(1, "Party");
+        tabs.set_playing(1);
+
+        tabs.close_tab(0);
+        assert_eq!(tabs.tabs().len(), 1);
+        assert_eq!(tabs.active().name, "Party");
+        assert_eq!(tabs.playing_index(), 0);
+        assert_eq!(tabs.last_closed_name(), Some("Default"));
+
+        assert_eq!(tabs.undo_close(), Some(0));
+        assert_eq!(tabs.tabs().len(), 2);
+        assert_eq!(tabs.active().name, "Default");
+        assert_eq!(tabs.active().playlist.len(), 3);
+        assert_eq!(tabs.playing().name, "Party");
+        assert_eq!(tabs.undo_close(), None);
+    }
+
+    #[test]
+    fn test_closing_last_tab_leaves_empty_default() {
+        let mut tabs = tabs_with_entries();
+        tabs.close_tab(0);
+        assert_eq!(tabs.tabs().len(), 1);
+        assert!(tabs.active().playlist.is_empty());
+    }
+
+    #[test]
+    fn test_copy_entries_skips_duplicates() {
+        let mut tabs = tabs_with_entries();
+        let party = tabs.add_tab();
+
+        assert_eq!(tabs.copy_entries(0, &[0, 2], party), 2);
+        assert_eq!(tabs.copy_entries(0, &[0, 1], party), 1);
+        assert_eq!(tabs.tabs()[party].playlist.len(), 3);
+        assert_eq!(tabs.tabs()[0].playlist.len(), 3);
+        assert_eq!(tabs.copy_entries(party, &[0], party), 0);
+    }
+
+    #[test]
+    fn test_sort_by_path_toggles_direction() {
+        let metadata = MetadataService::with_workers(1);
+        let mut tabs = tabs_with_entries();
+        let tab = tabs.active_mut();
+        tab.select_only(0);
+
+        tab.sort_by(SortKey::Path, &metadata);
+        assert_eq!(
+            tab.sort,
+            Some(PlaylistSort {
+                key: SortKey::Path,
+                descending: false
+            })
+        );
+
+        tab.sort_by(SortKey::Path, &metadata);
+        assert!(tab.sort.unwrap().descending);
+        assert_eq!(tab.playlist.get(0), Some(Path::new("/music/track2.mp3")));
+        // Selection follows the file
+        assert_eq!(tab.selected_indices(), vec![2]);
+    }
+
+    #[test]
+    fn test_sort_waits_for_metadata() {
+        let dir = std::env::temp_dir().join("oneamp_playlist_tabs_sort");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = [("a.wav", 3), ("b.wav", 1), ("c.wav", 2)]
+            .iter()
+            .map(|&(name, secs)| {
+                let path = dir.join(name);
+                write_silent(&path, secs * 8000);
+                path
+            })
+            .collect();
+
+        let mut metadata = MetadataService::with_workers(1);
+        let mut tab = PlaylistTab::new("Default");
+        tab.append_entries(paths.clone());
+
+        // Nothing is scanned yet: the order stays until the durations are known
+        tab.sort_by(SortKey::Duration, &metadata);
+        assert_eq!(tab.playlist.entries(), &paths[..]);
+        assert!(!tab.refresh_sort(&metadata));
+
+        for path in &paths {
+            metadata.probe_now(path);
+        }
+        assert!(tab.refresh_sort(&metadata));
+        let sorted = [paths[1].clone(), paths[2].clone(), paths[0].clone()];
+        assert_eq!(tab.playlist.entries(), &sorted[..]);
+        assert!(!tab.refresh_sort(&metadata));
+        // Undo goes back to before the sort, not to the file name order
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.entries(), &paths[..]);
+
+        // An edit after sorting keeps the user's order
+        let mut tab = PlaylistTab::new("Edited");
+        tab.append_entries(paths.clone());
+        tab.append_entries(vec![dir.join("d.wav")]);
+        tab.sort_by(SortKey::Duration, &metadata);
+        tab.move_entries(&[0], 3);
+        let moved = tab.playlist.entries().to_vec();
+        metadata.probe_now(&dir.join("d.wav"));
+        assert!(!tab.refresh_sort(&metadata));
+        assert_eq!(tab.playlist.entries(), &moved[..]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut tabs = tabs_with_entries();
+        tabs.active_mut().playlist.select(1);
+        tabs.active_mut().select_only(2);
+        tabs.active_mut().scroll_offset = 120.0;
+        tabs.add_tab();
+
+        let restored = PlaylistTabs::from_config(tabs.to_config(), 1, 0);
+        assert_eq!(restored.tabs().len(), 2);
+        assert_eq!(restored.active_index(), 1);
+        let first = &restored.tabs()[0];
+        assert_eq!(first.playlist.len(), 3);
+        assert_eq!(first.playlist.current_index(), Some(1));
+        assert_eq!(first.focused(), Some(2));
+        assert_eq!(first.scroll_offset, 120.0);
+    }
+
+    #[test]
+    fn test_click_selection() {
+        let mut tab = tab_with_entries(6);
+        let rows: Vec<usize> = (0..6).collect();
+
+        tab.click(1, false, false, &rows);
+        tab.click(4, false, true, &rows);
+        assert_eq!(tab.selected_indices(), vec![1, 2, 3, 4]);
+
+        tab.click(2, true, false, &rows);
+        assert_eq!(tab.selected_indices(), vec![1, 3, 4]);
+
+        // Shift-click ranges follow the filtered rows
+        let filtered = [0, 2, 5];
+        tab.click(0, false, false, &filtered);
+        tab.click(5, false, true, &filtered);
+        assert_eq!(tab.selected_indices(), vec![0, 2, 5]);
+    }
+
+    #[test]
+    fn test_filter_rows() {
+        let mut tab = tab_with_entries(12);
+        let metadata = MetadataService::with_workers(1);
+
+        assert_eq!(tab.visible_rows(&metadata).len(), 12);
+
+        tab.filter = "TRACK1".to_string();
+        assert_eq!(&*tab.visible_rows(&metadata), &[1, 10, 11]);
+
+        tab.filter.clear();
+        assert_eq!(tab.visible_rows(&metadata).len(), 12);
+    }
+
+    #[test]
+    fn test_remove_crop_undo_redo() {
+        let mut tab = tab_with_entries(5);
+        let rows: Vec<usize> = (0..5).collect();
+
+        tab.click(1, false, false, &rows);
+        tab.click(2, false, true, &rows);
+        tab.remove_selected();
+        assert_eq!(tab.playlist.len(), 3);
+
+        tab.click(0, false, false, &rows);
+        tab.crop_to_selection();
+        assert_eq!(
+            tab.playlist.entries(),
+            &[PathBuf::from("/music/track0.mp3")]
+        );
+
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.len(), 3);
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.len(), 5);
+        assert!(tab.redo());
+        assert_eq!(tab.playlist.len(), 3);
+
+        // A new edit drops the redo history
+        tab.clear();
+        assert!(!tab.can_redo());
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.len(), 3);
+    }
+
+    #[test]
+    fn test_move_entries_keeps_selection() {
+        let mut tab = tab_with_entries(4);
+        tab.move_entries(&[0, 1], 4);
+        assert_eq!(tab.playlist.get(0), Some(Path::new("/music/track2.mp3")));
+        assert_eq!(tab.selected_indices(), vec![2, 3]);
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.get(0), Some(Path::new("/music/track0.mp3")));
+    }
+
+    #[test]
+    fn test_insert_and_remove_entries() {
+        let mut tab = tab_with_entries(3);
+        let revision = tab.revision();
+        let new = PathBuf::from("/music/new.mp3");
+        assert_eq!(tab.insert_entries(vec![new.clone()], 1), Some(1));
+        assert_eq!(tab.playlist.get(1), Some(new.as_path()));
+        assert_ne!(tab.revision(), revision);
+        assert_eq!(tab.insert_entries(vec![new], 0), None);
+
+        tab.remove_entries(&[1]);
+        assert_eq!(tab.playlist.len(), 3);
+        assert!(tab.undo());
+        assert_eq!(tab.playlist.len(), 4);
+    }
+
+    #[test]
+    fn test_remove_dead_files() {
+        let mut tab = PlaylistTab::new("Test");
+        tab.append_entries(vec![
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("/nonexistent/oneamp/missing.mp3"),
+        ]);
+        assert_eq!(tab.remove_dead_files(), 1);
+        assert_eq!(tab.playlist.entries(), &[PathBuf::from("Cargo.toml")]);
+    }
+
+    #[test]
+    fn test_adding_nothing_leaves_no_undo_step() {
+        let mut tab = tab_with_entries(2);
+        assert!(tab.can_undo());
+        tab.undo();
+        assert!(!tab.can_undo());
+        tab.redo();
+        assert_eq!(
+            tab.append_entries(vec![PathBuf::from("/music/track0.mp3")]),
+            0
+        );
+        assert_eq!(tab.undo.len(), 1);
+    }
+
+    #[test]
+    fn test_entries_changed_once_per_edit() {
+        let mut tab = tab_with_entries(2);
+        assert!(tab.take_entries_changed());
+        assert!(!tab.take_entries_changed());
+        tab.remove_dead_files();
+        assert!(tab.take_entries_changed());
+    }
+
+    #[test]
+    fn test_durations_of_unscanned_tracks_are_unknown() {
+        let mut tab = tab_with_entries(3);
+        tab.playlist.select(0);
+        let metadata = MetadataService::with_workers(1);
+        let durations = tab.durations(&metadata);
+        assert_eq!(durations.total.unknown, 3);
+        assert_eq!(durations.upcoming.unknown, 2);
+    }
+
+    #[test]
+    fn test_smart_playlist_refresh() {
+        let tracks: Vec<TrackInfo> = (0..4)
+            .map(|i| TrackInfo {
+                path: PathBuf::from(format!("/music/track{}.mp3", i)),
+                ..Default::default()
+            })
+            .collect();
+        let mut stats = PlayStats::default();
+        stats.set_rating(&tracks[1].path, 5);
+        stats.set_rating(&tracks[3].path, 4);
+
+        let mut tabs = PlaylistTabs::new();
+        let smart = SmartRule {
+            rule: "rating >= 4".to_string(),
+            seed: 1,
+        };
+        let index = tabs.add_smart_tab("Favourites", smart).unwrap();
+        let tab = &mut tabs.tabs_mut()[index];
+        assert!(tab.refresh_smart(&tracks, 1, &stats, 0));
+        assert_eq!(
+            tab.playlist.entries(),
+            [tracks[1].path.clone(), tracks[3].path.clone()]
+        );
+        // Nothing to do until the library or the statistics change
+        assert!(!tab.refresh_smart(&tracks, 1, &stats, 0));
+
+        // The playing track stays while it plays
+        tab.playlist.select(0);
+        stats.set_rating(&tracks[1].path, 2);
+        stats.set_rating(&tracks[2].path, 4);
+        assert!(tab.refresh_smart(&tracks, 1, &stats, 0));
+        assert_eq!(
+            tab.playlist.entries(),
+            [
+                tracks[1].path.clone(),
+                tracks[2].path.clone(),
+                tracks[3].path.clone()
+            ]
+        );
+        assert_eq!(tab.playlist.current_index(), Some(0));
+
+        let restored = PlaylistTabs::from_config(tabs.to_config(), 1, 0);
+        assert_eq!(restored.tabs()[1].smart(), tabs.tabs()[1].smart());
+        assert!(restored.tabs()[0].smart().is_none());
+
+        let invalid = SmartRule {
+            rule: "rating >=".to_string(),
+            seed: 1,
+        };
+        assert!(tabs.add_smart_tab("Broken", invalid.clone()).is_err());
+        let mut tab = PlaylistTab::new("Broken");
+        assert!(tab.set_smart(Some(invalid)).is_err());
+        assert!(tab.smart().is_some());
+        assert!(!tab.refresh_smart(&tracks, 1, &stats, 0));
+    }
+}
@@ -0,0 +1,466 @@
+// Podcasts Panel
+// Subscribe to feeds, browse episodes, download them and pick up where
+// you left off. Positions and played state are saved to `podcasts.json`
+// next to the config file; episodes are downloaded to ~/Music/Podcasts.
+
+use egui::{Color32, RichText};
+use oneamp_core::podcast::{
+    chapters, format_date, Episode, Podcast, PodcastEvent, PodcastLibrary, PodcastService,
+};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::track_display::TrackDisplay;
+
+/// What the panel asks the player to do
+pub enum PodcastAction {
+    /// Add the episode to the playlist and play it, optionally from a position
+    Play { path: PathBuf, start: Option<f32> },
+    /// Seek in the episode that is playing
+    Seek(f32),
+}
+
+/// Changes to an episode picked in the panel, applied once drawing is done
+enum EpisodeAction {
+    Play { start: Option<f32> },
+    Download,
+    CancelDownload,
+    DeleteDownload,
+    SetPlayed(bool),
+}
+
+pub struct PodcastPanel {
+    library: PodcastLibrary,
+    service: PodcastService,
+    library_path: Option<PathBuf>,
+    /// Library changed since it was last saved
+    dirty: bool,
+    /// Feed URL being typed
+    new_feed: String,
+    /// Feeds being fetched
+    fetching: HashSet<String>,
+    /// Feed shown on the right
+    selected: Option<String>,
+    error: Option<String>,
+}
+
+impl PodcastPanel {
+    pub fn new() -> Self {
+        let library_path = dirs::config_dir().map(|dir| dir.join("oneamp").join("podcasts.json"));
+        let library = match library_path.as_deref().map(PodcastLibrary::load) {
+            Some(Ok(library)) => library,
+            Some(Err(e)) => {
+                eprintln!("Failed to load podcasts: {:#}", e);
+                PodcastLibrary::default()
+            }
+            None => PodcastLibrary::default(),
+        };
+        let download_dir = dirs::audio_dir()
+            .or_else(|| dirs::home_dir().map(|home| home.join("Music")))
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("Podcasts");
+
+        Self {
+            selected: library.podcasts.first().map(|p| p.feed_url.clone()),
+            library,
+            service: PodcastService::new(download_dir),
+            library_path,
+            dirty: false,
+            new_feed: String::new(),
+            fetching: HashSet::new(),
+            error: None,
+        }
+    }
+
+    /// Collect finished feed fetches and downloads
+    pub fn update(&mut self) {
+        for event in self.service.poll() {
+            self.dirty |= self.library.apply(&event);
+            match event {
+                PodcastEvent::Fetched { feed_url, result } => {
+                    self.fetching.remove(&feed_url);
+                    match result {
+                        Ok(_) => {
+                            if self.selected.is_none() {
+                                self.selected = Some(feed_url);
+                            }
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                PodcastEvent::DownloadFailed { error, .. } => {
+                    self.error = Some(error);
+                }
+                PodcastEvent::Chapters { result: Err(e), .. } => {
+                    eprintln!("Failed to load chapters: {}", e);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Remember how far into an episode playback is
+    pub fn track_position(&mut self, path: &Path, position_secs: f32) {
+        if let Some(episode) = self.library.episode_for_path_mut(path) {
+            if (episode.state.position_secs - position_secs).abs() >= 1.0 {
+                episode.state.position_secs = position_secs;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// An episode played to the end
+    pub fn track_finished(&mut self, path: &Path) {
+        if let Some(episode) = self.library.episode_for_path_mut(path) {
+            episode.state.played = true;
+            episode.state.position_secs = 0.0;
+            self.dirty = true;
+        }
+    }
+
+    /// Save positions and subscriptions if anything changed
+    pub fn save(&mut self) {
+        let Some(path) = &self.library_path else {
+            return;
+        };
+        if self.dirty {
+            match self.library.save(path) {
+                Ok(()) => self.dirty = false,
+                Err(e) => eprintln!("Failed to save podcasts: {:#}", e),
+            }
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        playing: Option<(&Path, f32)>,
+    ) -> Option<PodcastAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.new_feed)
+                    .hint_text("Feed URL")
+                    .desired_width(280.0),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let url = self.new_feed.trim().to_string();
+            if (ui
+                .add_enabled(!url.is_empty(), egui::Button::new("➕ Subscribe"))
+                .clicked()
+                || entered)
+                && !url.is_empty()
+            {
+                self.fetch(&url);
+                self.selected = Some(url);
+                self.new_feed.clear();
+            }
+            if ui.button("⟳ Refresh all").clicked() {
+                let feeds: Vec<String> = self
+                    .library
+                    .podcasts
+                    .iter()
+                    .map(|p| p.feed_url.clone())
+                    .collect();
+                for feed in feeds {
+                    self.fetch(&feed);
+                }
+            }
+            if !self.fetching.is_empty() {
+                ui.spinner();
+            }
+        });
+
+        if let Some(error) = self.error.clone() {
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::from_rgb(230, 90, 90), format!("⚠ {}", error));
+                if ui.small_button("✖").clicked() {
+                    self.error = None;
+                }
+            });
+        }
+        ui.separator();
+
+        if self.library.podcasts.is_empty() {
+            ui.label("No subscriptions yet. Paste a podcast's RSS or Atom feed URL above.");
+            return None;
+        }
+
+        ui.horizontal_top(|ui| {
+            // Subscriptions
+            ui.vertical(|ui| {
+                ui.set_width(180.0);
+                egui::ScrollArea::vertical()
+                    .id_salt("podcast_list")
+                    .show(ui, |ui| {
+                        for podcast in &self.library.podcasts {
+                            let unplayed = podcast.unplayed_count();
+                            let label = if unplayed > 0 {
+                                format!("{} ({})", podcast.title, unplayed)
+                            } else {
+                                podcast.title.clone()
+                            };
+                            let selected =
+                                self.selected.as_deref() == Some(podcast.feed_url.as_str());
+                            if ui.selectable_label(selected, label).clicked() {
+                                self.selected = Some(podcast.feed_url.clone());
+                            }
+                        }
+                    });
+            });
+            ui.separator();
+
+            // Episodes of the selected podcast
+            ui.vertical(|ui| {
+                let Some(feed_url) = self.selected.clone() else {
+                    return;
+                };
+                let Some(podcast) = self.library.podcast(&feed_url) else {
+                    ui.label("Fetching feed…");
+                    return;
+                };
+
+                let (mut refresh, mut unsubscribe) = (false, false);
+                ui.horizontal(|ui| {
+                    ui.heading(&podcast.title);
+                    if ui.small_button("⟳").on_hover_text("Refresh").clicked() {
+                        refresh = true;
+                    }
+                    if ui.small_button("Unsubscribe").clicked() {
+                        unsubscribe = true;
+                    }
+                });
+                if let Some(author) = &podcast.author {
+                    ui.label(RichText::new(author).italics());
+                }
+
+                let mut changes = Vec::new();
+                egui::ScrollArea::vertical()
+                    .id_salt("podcast_episodes")
+                    .show(ui, |ui| {
+                        for episode in &podcast.episodes {
+                            let playing_position = playing
+                                .filter(|(path, _)| episode.is_path(path))
+                                .map(|(_, secs)| secs);
+                            if let Some(change) = self.episode_row(
+                                ui,
+                                podcast,
+                                episode,
+                                playing_position,
+                                &mut action,
+                            ) {
+                                changes.push((episode.guid.clone(), change));
+                            }
+                            ui.separator();
+                        }
+                    });
+
+                if refresh {
+                    self.fetch(&feed_url);
+                }
+                if unsubscribe {
+                    self.library.unsubscribe(&feed_url);
+                    self.selected = self.library.podcasts.first().map(|p| p.feed_url.clone());
+                    self.dirty = true;
+                }
+                for (guid, change) in changes {
+                    if let Some(play) = self.apply(&feed_url, &guid, change) {
+                        action = Some(play);
+                    }
+                }
+            });
+        });
+
+        action
+    }
+
+    fn fetch(&mut self, feed_url: &str) {
+        if self.fetching.insert(feed_url.to_string()) {
+            self.service.fetch(feed_url);
+        }
+    }
+
+    fn episode_row(
+        &self,
+        ui: &mut egui::Ui,
+        podcast: &Podcast,
+        episode: &Episode,
+        playing_position: Option<f32>,
+        action: &mut Option<PodcastAction>,
+    ) -> Option<EpisodeAction> {
+        let mut change = None;
+
+        let title = RichText::new(&episode.title).strong();
+        let title = if episode.state.played {
+            title.weak()
+        } else {
+            title
+        };
+        ui.label(title);
+
+        let mut details = Vec::new();
+        if let Some(published) = episode.published {
+            details.push(format_date(published));
+        }
+        if let Some(duration) = episode.duration_secs {
+            details.push(TrackDisplay::format_duration_digital(duration));
+        }
+        if let Some(position) = episode.resume_position() {
+            details.push(format!(
+                "stopped at {}",
+                TrackDisplay::format_duration_digital(position)
+            ));
+        }
+        if episode.state.played {
+            details.push("played".to_string());
+        }
+        if !details.is_empty() {
+            ui.label(RichText::new(details.join(" · ")).small());
+        }
+
+        ui.horizontal(|ui| {
+            let play_label = if episode.resume_position().is_some() {
+                "▶ Resume"
+            } else {
+                "▶ Play"
+            };
+            if ui
+                .add_enabled(episode.enclosure.is_some(), egui::Button::new(play_label))
+                .clicked()
+            {
+                change = Some(EpisodeAction::Play {
+                    start: episode.resume_position(),
+                });
+            }
+
+            match self
+                .service
+                .download_progress(&podcast.feed_url, &episode.guid)
+            {
+                Some(progress) => {
+                    let bar = match progress.fraction() {
+                        Some(fraction) => egui::ProgressBar::new(fraction).show_percentage(),
+                        None => egui::ProgressBar::new(0.0)
+                            .text(format!("{:.1} MB", progress.received as f64 / 1e6)),
+                    };
+                    ui.add(bar.desired_width(120.0));
+                    if ui
+                        .small_button("✖")
+                        .on_hover_text("Cancel download")
+                        .clicked()
+                    {
+                        change = Some(EpisodeAction::CancelDownload);
+                    }
+                }
+                None if episode.is_downloaded() => {
+                    let path = episode.state.download.as_deref().unwrap_or(Path::new(""));
+                    ui.label("💾").on_hover_text(path.display().to_string());
+                    if ui
+                        .small_button("🗑")
+                        .on_hover_text("Delete download")
+                        .clicked()
+                    {
+                        change = Some(EpisodeAction::DeleteDownload);
+                    }
+                }
+                None => {
+                    if ui
+                        .add_enabled(episode.enclosure.is_some(), egui::Button::new("⬇ Download"))
+                        .clicked()
+                    {
+                        change = Some(EpisodeAction::Download);
+                    }
+                }
+            }
+
+            let mut played = episode.state.played;
+            if ui.checkbox(&mut played, "Played").changed() {
+                change = Some(EpisodeAction::SetPlayed(played));
+            }
+        });
+
+        let has_notes = episode.description.is_some() || !episode.chapters.is_empty();
+        if has_notes {
+            egui::CollapsingHeader::new("Show notes")
+                .id_salt(("episode_notes", &podcast.feed_url, &episode.guid))
+                .default_open(playing_position.is_some() && !episode.chapters.is_empty())
+                .show(ui, |ui| {
+                    if let Some(description) = &episode.description {
+                        ui.label(description);
+                    }
+                    if !episode.chapters.is_empty() {
+                        ui.add_space(4.0);
+                        ui.label(RichText::new("Chapters").strong());
+                        let current = playing_position
+                            .and_then(|secs| chapters::chapter_at(&episode.chapters, secs));
+                        for (i, chapter) in episode.chapters.iter().enumerate() {
+                            let label = format!(
+                                "{}  {}",
+                                TrackDisplay::format_duration_digital(chapter.start_secs),
+                                chapter.title
+                            );
+                            if ui.selectable_label(current == Some(i), label).clicked() {
+                                // Seek within the playing episode, otherwise start it there
+                                match playing_position {
+                                    Some(_) => {
+                                        *action = Some(PodcastAction::Seek(chapter.start_secs))
+                                    }
+                                    None => {
+                                        change = Some(EpisodeAction::Play {
+                                            start: Some(chapter.start_secs),
+                                        })
+                                    }
+                                }
+                            }
+                        }
+                    }
+                });
+        }
+
+        change
+    }
+
+    /// Apply a change picked in the panel; returns what to play, if anything
+    fn apply(
+        &mut self,
+        feed_url: &str,
+        guid: &str,
+        change: EpisodeAction,
+    ) -> Option<PodcastAction> {
+        let podcast = self.library.podcast(feed_url)?;
+        let episode = podcast.episode(guid)?;
+        match change {
+            EpisodeAction::Play { start } => {
+                if episode.chapters.is_empty() {
+                    self.service.fetch_chapters(podcast, episode);
+                }
+                let path = episode.playable_path()?;
+                // Streamed episodes can't seek, so they start over
+                let start = start.filter(|_| episode.is_downloaded());
+                return Some(PodcastAction::Play { path, start });
+            }
+            EpisodeAction::Download => {
+                let (podcast, episode) = (podcast.clone(), episode.clone());
+                self.service.download(&podcast, &episode);
+            }
+            EpisodeAction::CancelDownload => self.service.cancel_download(feed_url, guid),
+            EpisodeAction::DeleteDownload => {
+                let episode = self.library.episode_mut(feed_url, guid)?;
+                if let Some(path) = episode.state.download.take() {
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        self.error = Some(format!("Failed to delete {}: {}", path.display(), e));
+                    }
+                }
+                self.dirty = true;
+            }
+            EpisodeAction::SetPlayed(played) => {
+                let episode = self.library.episode_mut(feed_url, guid)?;
+                episode.state.played = played;
+                episode.state.position_secs = 0.0;
+                self.dirty = true;
+            }
+        }
+        None
+    }
+}
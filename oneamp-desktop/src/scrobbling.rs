@@ -0,0 +1,258 @@
+// Scrobbling Settings
+// Last.fm and ListenBrainz accounts, and the scrobbler fed with the
+// player's events. Listens that can't be sent wait in `scrobbles.json`
+// next to the config file.
+
+use egui::{Color32, RichText};
+use oneamp_core::scrobble::{
+    LastFm, LastFmConfig, ListenBrainz, ListenBrainzConfig, ScrobbleConfig, ScrobbleService,
+    UreqClient,
+};
+use oneamp_core::AudioEvent;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+pub struct ScrobblePanel {
+    config: ScrobbleConfig,
+    service: Option<ScrobbleService>,
+    queue_path: Option<PathBuf>,
+    /// Fields being edited, applied with "Save"
+    listenbrainz_token: String,
+    lastfm_api_key: String,
+    lastfm_secret: String,
+    lastfm_username: String,
+    lastfm_password: String,
+    /// Last.fm login running in the background
+    login: Option<Receiver<Result<String, String>>>,
+    error: Option<String>,
+}
+
+impl ScrobblePanel {
+    pub fn new(config: ScrobbleConfig) -> Self {
+        let queue_path = dirs::config_dir().map(|dir| dir.join("oneamp").join("scrobbles.json"));
+        let mut panel = Self {
+            listenbrainz_token: config
+                .listenbrainz
+                .as_ref()
+                .map(|lb| lb.token.clone())
+                .unwrap_or_default(),
+            lastfm_api_key: config
+                .lastfm
+                .as_ref()
+                .map(|fm| fm.api_key.clone())
+                .unwrap_or_default(),
+            lastfm_secret: config
+                .lastfm
+                .as_ref()
+                .map(|fm| fm.secret.clone())
+                .unwrap_or_default(),
+            lastfm_username: String::new(),
+            lastfm_password: String::new(),
+            config,
+            service: None,
+            queue_path,
+            login: None,
+            error: None,
+        };
+        panel.restart();
+        panel
+    }
+
+    /// Settings to save in the config file
+    pub fn config(&self) -> &ScrobbleConfig {
+        &self.config
+    }
+
+    /// Feed a player event to the scrobbler
+    pub fn handle_event(&mut self, event: &AudioEvent) {
+        if let Some(service) = &mut self.service {
+            service.handle_event(event);
+        }
+    }
+
+    fn restart(&mut self) {
+        // Drop the old service first so its queue is saved before reloading
+        self.service = None;
+        self.service = ScrobbleService::from_config(&self.config, self.queue_path.clone());
+    }
+
+    /// Collect a finished Last.fm login
+    fn poll_login(&mut self) -> bool {
+        let Some(result) = self.login.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+            return false;
+        };
+        self.login = None;
+        match result {
+            Ok(session_key) => {
+                self.lastfm_password.clear();
+                self.config.lastfm = Some(LastFmConfig {
+                    api_key: self.lastfm_api_key.trim().to_string(),
+                    secret: self.lastfm_secret.trim().to_string(),
+                    session_key,
+                    api_url: self
+                        .config
+                        .lastfm
+                        .take()
+                        .map(|fm| fm.api_url)
+                        .unwrap_or_else(|| LastFm::API_URL.to_string()),
+                });
+                self.error = None;
+                self.restart();
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    fn start_login(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let api_url = self
+            .config
+            .lastfm
+            .as_ref()
+            .map(|fm| fm.api_url.clone())
+            .unwrap_or_else(|| LastFm::API_URL.to_string());
+        let api_key = self.lastfm_api_key.trim().to_string();
+        let secret = self.lastfm_secret.trim().to_string();
+        let username = self.lastfm_username.trim().to_string();
+        let password = self.lastfm_password.clone();
+        std::thread::spawn(move || {
+            let result = LastFm::login(
+                &UreqClient::default(),
+                &api_url,
+                &api_key,
+                &secret,
+                &username,
+                &password,
+            )
+            .map_err(|e| format!("{:#}", e));
+            let _ = tx.send(result);
+        });
+        self.login = Some(rx);
+        self.error = None;
+    }
+
+    /// Draw the settings. Returns true when they changed and should be saved.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = self.poll_login();
+        if self.login.is_some() {
+            ui.ctx().request_repaint();
+        }
+
+        if ui
+            .checkbox(&mut self.config.enabled, "Scrobble played tracks")
+            .on_hover_text("Tracks count once played for half their length or four minutes")
+            .changed()
+        {
+            self.restart();
+            changed = true;
+        }
+        ui.add_space(8.0);
+
+        ui.label(RichText::new("ListenBrainz").strong());
+        ui.horizontal(|ui| {
+            ui.label("User token:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.listenbrainz_token)
+                    .password(true)
+                    .desired_width(260.0),
+            );
+            let token = self.listenbrainz_token.trim().to_string();
+            let saved = self
+                .config
+                .listenbrainz
+                .as_ref()
+                .map(|lb| lb.token.as_str());
+            if ui
+                .add_enabled(
+                    saved.unwrap_or_default() != token,
+                    egui::Button::new("Save"),
+                )
+                .clicked()
+            {
+                self.config.listenbrainz = (!token.is_empty()).then(|| ListenBrainzConfig {
+                    token,
+                    api_url: self
+                        .config
+                        .listenbrainz
+                        .take()
+                        .map(|lb| lb.api_url)
+                        .unwrap_or_else(|| ListenBrainz::API_URL.to_string()),
+                });
+                self.restart();
+                changed = true;
+            }
+        });
+        ui.add_space(8.0);
+
+        ui.label(RichText::new("Last.fm").strong());
+        let connected = self
+            .config
+            .lastfm
+            .as_ref()
+            .is_some_and(|fm| !fm.session_key.is_empty());
+        if connected {
+            ui.horizontal(|ui| {
+                ui.label("Connected");
+                if ui.button("Disconnect").clicked() {
+                    self.config.lastfm = None;
+                    self.restart();
+                    changed = true;
+                }
+            });
+        } else {
+            egui::Grid::new("lastfm_login")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("API key:");
+                    ui.text_edit_singleline(&mut self.lastfm_api_key);
+                    ui.end_row();
+                    ui.label("Shared secret:");
+                    ui.add(egui::TextEdit::singleline(&mut self.lastfm_secret).password(true));
+                    ui.end_row();
+                    ui.label("Username:");
+                    ui.text_edit_singleline(&mut self.lastfm_username);
+                    ui.end_row();
+                    ui.label("Password:");
+                    ui.add(egui::TextEdit::singleline(&mut self.lastfm_password).password(true));
+                    ui.end_row();
+                });
+            let ready = [
+                &self.lastfm_api_key,
+                &self.lastfm_secret,
+                &self.lastfm_username,
+                &self.lastfm_password,
+            ]
+            .iter()
+            .all(|field| !field.trim().is_empty());
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(ready && self.login.is_none(), egui::Button::new("Connect"))
+                    .clicked()
+                {
+                    self.start_login();
+                }
+                if self.login.is_some() {
+                    ui.spinner();
+                }
+            });
+        }
+        ui.add_space(8.0);
+
+        if let Some(status) = self.service.as_ref().map(ScrobbleService::status) {
+            if status.queued > 0 {
+                ui.label(format!("{} listens waiting to be sent", status.queued));
+            }
+            if let Some(error) = status.last_error {
+                ui.colored_label(Color32::from_rgb(220, 120, 80), error);
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::from_rgb(220, 80, 80), error);
+        }
+        changed
+    }
+}
@@ -0,0 +1,347 @@
+// Skin Assets
+// Textures and fonts of the active skin, loaded into egui when the skin
+// changes, from the skin's folder or straight from its archive. Assets that
+// fail to load are skipped and keep the built-in look.
+
+use super::{NineSlice, Skin, StateImages};
+use crate::control_buttons::ButtonIcon;
+use anyhow::{anyhow, Result};
+use eframe::egui::{
+    self, Color32, ColorImage, FontData, FontDefinitions, FontFamily, Mesh, Painter, Pos2, Rect,
+    Shape, TextureHandle, TextureOptions,
+};
+use oneamp_core::skin::read_asset;
+use std::path::{Path, PathBuf};
+
+/// Font family of a skin's timer font.
+const TIMER_FAMILY: &str = "skin_timer";
+
+/// Textures of a widget for each state.
+pub struct ButtonTextures {
+    normal: TextureHandle,
+    hover: Option<TextureHandle>,
+    pressed: Option<TextureHandle>,
+}
+
+impl ButtonTextures {
+    /// Texture for the given state; hover and pressed fall back to normal.
+    pub fn get(&self, hovered: bool, pressed: bool) -> &TextureHandle {
+        let state = if pressed {
+            self.pressed.as_ref()
+        } else if hovered {
+            self.hover.as_ref()
+        } else {
+            None
+        };
+        state.unwrap_or(&self.normal)
+    }
+
+    /// Paints the texture for the given state centered in `rect`, keeping
+    /// its aspect ratio.
+    pub fn paint(&self, painter: &Painter, rect: Rect, hovered: bool, pressed: bool) {
+        let texture = self.get(hovered, pressed);
+        let size = texture.size_vec2();
+        let scale = (rect.width() / size.x).min(rect.height() / size.y);
+        let target = Rect::from_center_size(rect.center(), size * scale);
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        painter.image(texture.id(), target, uv, Color32::WHITE);
+    }
+}
+
+/// A nine-slice image ready to be painted.
+pub struct NineSliceTexture {
+    texture: TextureHandle,
+    borders: [u32; 4],
+}
+
+impl NineSliceTexture {
+    /// Paints the image stretched over `rect`.
+    pub fn paint(&self, painter: &Painter, rect: Rect) {
+        let pixels_per_point = painter.ctx().pixels_per_point();
+        let mut mesh = Mesh::with_texture(self.texture.id());
+        for (cell, uv) in nine_slice(rect, self.texture.size(), self.borders, pixels_per_point) {
+            mesh.add_rect_with_uv(cell, uv, Color32::WHITE);
+        }
+        painter.add(Shape::mesh(mesh));
+    }
+}
+
+/// Cells of a nine-slice image drawn over `rect`, as (screen, uv) rect pairs.
+///
+/// Borders are drawn at one image pixel per physical pixel and shrink evenly
+/// when `rect` is too small to hold them. Empty cells are left out.
+pub fn nine_slice(
+    rect: Rect,
+    image_size: [usize; 2],
+    borders: [u32; 4],
+    pixels_per_point: f32,
+) -> Vec<(Rect, Rect)> {
+    let [width, height] = image_size.map(|size| size as f32);
+    let [left, top, right, bottom] = borders.map(|border| border as f32);
+
+    // Opposite borders can't overlap in the image either
+    let (left, right) = fit(left, right, width);
+    let (top, bottom) = fit(top, bottom, height);
+
+    let points = |border: f32| border / pixels_per_point;
+    let xs = edges(rect.min.x, rect.max.x, points(left), points(right));
+    let ys = edges(rect.min.y, rect.max.y, points(top), points(bottom));
+    let us = [0.0, left / width, 1.0 - right / width, 1.0];
+    let vs = [0.0, top / height, 1.0 - bottom / height, 1.0];
+
+    let mut cells = Vec::with_capacity(9);
+    for row in 0..3 {
+        for col in 0..3 {
+            let cell = Rect::from_min_max(
+                Pos2::new(xs[col], ys[row]),
+                Pos2::new(xs[col + 1], ys[row + 1]),
+            );
+            if cell.width() > 0.0 && cell.height() > 0.0 {
+                let uv = Rect::from_min_max(
+                    Pos2::new(us[col], vs[row]),
+                    Pos2::new(us[col + 1], vs[row + 1]),
+                );
+                cells.push((cell, uv));
+            }
+        }
+    }
+    cells
+}
+
+/// Two opposite borders, scaled down together to fit in `length`.
+fn fit(start: f32, end: f32, length: f32) -> (f32, f32) {
+    let total = start + end;
+    if total > length && total > 0.0 {
+        let scale = length / total;
+        (start * scale, end * scale)
+    } else {
+        (start, end)
+    }
+}
+
+/// Cell edges along one axis.
+fn edges(min: f32, max: f32, start: f32, end: f32) -> [f32; 4] {
+    let (start, end) = fit(start, end, (max - min).max(0.0));
+    [min, min + start, max - end, max]
+}
+
+/// Textures and fonts of the active skin.
+#[derive(Default)]
+pub struct SkinAssets {
+    /// Directory and name of the skin the assets were loaded from.
+    loaded: Option<(PathBuf, String)>,
+
+    buttons: Vec<(ButtonIcon, ButtonTextures)>,
+    background: Option<NineSliceTexture>,
+    display: Option<NineSliceTexture>,
+
+    /// Whether the skin's timer font is installed.
+    timer_font: bool,
+}
+
+impl SkinAssets {
+    /// Loads the skin's textures and fonts into the context, unless they
+    /// are already loaded.
+    pub fn sync(&mut self, ctx: &egui::Context, skin: &Skin) {
+        let key = (skin.path.clone(), skin.metadata.name.clone());
+        if self.loaded.as_ref() == Some(&key) {
+            return;
+        }
+        *self = Self::load(ctx, skin);
+        self.loaded = Some(key);
+    }
+
+    fn load(ctx: &egui::Context, skin: &Skin) -> Self {
+        let texture = |path: &Path| match load_texture(ctx, skin, path) {
+            Ok(texture) => Some(texture),
+            Err(e) => {
+                eprintln!("Skin {:?}: {}", skin.metadata.name, e);
+                None
+            }
+        };
+        let button = |icon: ButtonIcon, images: &Option<StateImages>| {
+            let images = images.as_ref()?;
+            Some((
+                icon,
+                ButtonTextures {
+                    normal: texture(&images.normal)?,
+                    hover: images.hover.as_deref().and_then(texture),
+                    pressed: images.pressed.as_deref().and_then(texture),
+                },
+            ))
+        };
+        let nine_slice = |slice: &Option<NineSlice>| {
+            let slice = slice.as_ref()?;
+            Some(NineSliceTexture {
+                texture: texture(&slice.image)?,
+                borders: slice.borders,
+            })
+        };
+
+        let images = &skin.images;
+        let buttons = [
+            button(ButtonIcon::Previous, &images.buttons.previous),
+            button(ButtonIcon::Play, &images.buttons.play),
+            button(ButtonIcon::Pause, &images.buttons.pause),
+            button(ButtonIcon::Stop, &images.buttons.stop),
+            button(ButtonIcon::Next, &images.buttons.next),
+        ];
+
+        Self {
+            loaded: None,
+            buttons: buttons.into_iter().flatten().collect(),
+            background: nine_slice(&images.background),
+            display: nine_slice(&images.display),
+            timer_font: load_fonts(ctx, skin),
+        }
+    }
+
+    /// Textures of a transport button, if the skin has any.
+    pub fn button(&self, icon: ButtonIcon) -> Option<&ButtonTextures> {
+        self.buttons
+            .iter()
+            .find(|(button, _)| *button == icon)
+            .map(|(_, textures)| textures)
+    }
+
+    /// Nine-slice background of the main window.
+    pub fn background(&self) -> Option<&NineSliceTexture> {
+        self.background.as_ref()
+    }
+
+    /// Nine-slice frame behind the player display.
+    pub fn display(&self) -> Option<&NineSliceTexture> {
+        self.display.as_ref()
+    }
+
+    /// Font family of the timer: the skin's timer font, or monospace.
+    pub fn timer_family(&self) -> FontFamily {
+        if self.timer_font {
+            FontFamily::Name(TIMER_FAMILY.into())
+        } else {
+            FontFamily::Monospace
+        }
+    }
+}
+
+/// Decodes an image of the skin into a texture.
+pub fn load_texture(ctx: &egui::Context, skin: &Skin, path: &Path) -> Result<TextureHandle> {
+    let image = read_asset(skin, path)
+        .map_err(anyhow::Error::from)
+        .and_then(|data| Ok(image::load_from_memory(&data)?))
+        .map_err(|e| anyhow!("Failed to load image {:?}: {}", path, e))?
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let image = ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    Ok(ctx.load_texture(
+        format!("skin:{}:{}", skin.path.display(), path.display()),
+        image,
+        TextureOptions::LINEAR,
+    ))
+}
+
+/// Reads a font file of the skin, checking egui will be able to parse it.
+fn read_font(skin: &Skin, path: &Path) -> Result<Vec<u8>> {
+    let data =
+        read_asset(skin, path).map_err(|e| anyhow!("Failed to read font {:?}: {}", path, e))?;
+    ab_glyph::FontRef::try_from_slice(&data)
+        .map_err(|e| anyhow!("Invalid font {:?}: {}", path, e))?;
+    Ok(data)
+}
+
+/// Installs the skin's bundled fonts on top of egui's defaults.
+///
+/// # Returns
+/// `true` if the skin's timer font was installed.
+fn load_fonts(ctx: &egui::Context, skin: &Skin) -> bool {
+    let read = |path: &Option<PathBuf>| {
+        let path = path.as_ref()?;
+        read_font(skin, path)
+            .map_err(|e| eprintln!("Skin {:?}: {}", skin.metadata.name, e))
+            .ok()
+    };
+    let mut fonts = FontDefinitions::default();
+
+    let families = [
+        (
+            "skin_proportional",
+            FontFamily::Proportional,
+            &skin.fonts.proportional_font,
+        ),
+        (
+            "skin_monospace",
+            FontFamily::Monospace,
+            &skin.fonts.monospace_font,
+        ),
+    ];
+    for (name, family, path) in families {
+        if let Some(data) = read(path) {
+            fonts
+                .font_data
+                .insert(name.to_string(), FontData::from_owned(data).into());
+            fonts
+                .families
+                .entry(family)
+                .or_default()
+                .insert(0, name.to_string());
+        }
+    }
+
+    // The timer falls back to the monospace fonts for missing glyphs
+    let timer_font = read(&skin.fonts.timer_font).is_some_and(|data| {
+        fonts
+            .font_data
+            .insert(TIMER_FAMILY.to_string(), FontData::from_owned(data).into());
+        let mut family = vec![TIMER_FAMILY.to_string()];
+        family.extend(fonts.families[&FontFamily::Monospace].iter().cloned());
+        fonts
+            .families
+            .insert(FontFamily::Name(TIMER_FAMILY.into()), family);
+        true
+    });
+
+    ctx.set_fonts(fonts);
+    timer_font
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(min: [f32; 2], max: [f32; 2]) -> Rect {
+        Rect::from_min_max(min.into(), max.into())
+    }
+
+    #[test]
+    fn test_nine_slice() {
+        let cells = nine_slice(rect([0.0, 0.0], [100.0, 50.0]), [32, 16], [4, 2, 8, 2], 2.0);
+        assert_eq!(cells.len(), 9);
+
+        // Corners keep their size in physical pixels
+        let (top_left, uv) = cells[0];
+        assert_eq!(top_left, rect([0.0, 0.0], [2.0, 1.0]));
+        assert_eq!(uv, rect([0.0, 0.0], [4.0 / 32.0, 2.0 / 16.0]));
+        let (bottom_right, uv) = cells[8];
+        assert_eq!(bottom_right, rect([96.0, 49.0], [100.0, 50.0]));
+        assert_eq!(uv, rect([24.0 / 32.0, 14.0 / 16.0], [1.0, 1.0]));
+
+        // The center stretches over the rest
+        assert_eq!(cells[4].0, rect([2.0, 1.0], [96.0, 49.0]));
+    }
+
+    #[test]
+    fn test_nine_slice_small_rect() {
+        // Borders shrink evenly to fit
+        let cells = nine_slice(rect([0.0, 0.0], [6.0, 10.0]), [16, 16], [6, 0, 6, 0], 1.0);
+        let xs: Vec<_> = cells
+            .iter()
+            .map(|(cell, _)| (cell.min.x, cell.max.x))
+            .collect();
+        assert_eq!(xs, [(0.0, 3.0), (3.0, 6.0)]);
+
+        // Borders wider than the image are clamped to it
+        let cells = nine_slice(rect([0.0, 0.0], [100.0, 10.0]), [8, 8], [8, 0, 8, 0], 1.0);
+        assert_eq!(cells[0].1, rect([0.0, 0.0], [0.5, 1.0]));
+        assert_eq!(cells[0].0, rect([0.0, 0.0], [4.0, 10.0]));
+    }
+}
@@ -0,0 +1,395 @@
+// Classic Winamp 2.x Skins
+// Bitmap skins packaged as `.wsz` (zip) archives: sprite sheets for the main
+// and equalizer windows, `pledit.txt` for the playlist colors and
+// `viscolor.txt` for the visualizer colors. Sprite coordinates follow the
+// fixed layout Winamp 2.x expects of every skin.
+
+use egui::{Color32, ColorImage};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Size of the main and equalizer windows, in skin pixels
+pub const WINDOW_WIDTH: f32 = 275.0;
+pub const WINDOW_HEIGHT: f32 = 116.0;
+
+/// Width of a `text.bmp` glyph
+pub const GLYPH_WIDTH: f32 = 5.0;
+
+/// Frames of the equalizer slider background, from -12 dB to +12 dB
+pub const EQ_SLIDER_FRAMES: u16 = 28;
+
+/// Bitmaps of a classic skin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sheet {
+    Main,
+    CButtons,
+    TitleBar,
+    Text,
+    Numbers,
+    PosBar,
+    EqMain,
+}
+
+impl Sheet {
+    pub const ALL: [Sheet; 7] = [
+        Sheet::Main,
+        Sheet::CButtons,
+        Sheet::TitleBar,
+        Sheet::Text,
+        Sheet::Numbers,
+        Sheet::PosBar,
+        Sheet::EqMain,
+    ];
+
+    /// File name of the bitmap in the archive, lowercase
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Sheet::Main => "main.bmp",
+            Sheet::CButtons => "cbuttons.bmp",
+            Sheet::TitleBar => "titlebar.bmp",
+            Sheet::Text => "text.bmp",
+            Sheet::Numbers => "numbers.bmp",
+            Sheet::PosBar => "posbar.bmp",
+            Sheet::EqMain => "eqmain.bmp",
+        }
+    }
+}
+
+/// Rectangle of a sprite sheet, in skin pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub sheet: Sheet,
+    pub x: u16,
+    pub y: u16,
+    pub w: u16,
+    pub h: u16,
+}
+
+impl Sprite {
+    pub const fn new(sheet: Sheet, x: u16, y: u16, w: u16, h: u16) -> Self {
+        Self { sheet, x, y, w, h }
+    }
+}
+
+/// Sprites of the main and equalizer windows
+pub mod sprites {
+    use super::{Sheet, Sprite};
+
+    pub const MAIN_BACKGROUND: Sprite = Sprite::new(Sheet::Main, 0, 0, 275, 116);
+    pub const MAIN_TITLE_BAR: Sprite = Sprite::new(Sheet::TitleBar, 27, 0, 275, 14);
+
+    pub const PREVIOUS: Sprite = Sprite::new(Sheet::CButtons, 0, 0, 23, 18);
+    pub const PREVIOUS_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 0, 18, 23, 18);
+    pub const PLAY: Sprite = Sprite::new(Sheet::CButtons, 23, 0, 23, 18);
+    pub const PLAY_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 23, 18, 23, 18);
+    pub const PAUSE: Sprite = Sprite::new(Sheet::CButtons, 46, 0, 23, 18);
+    pub const PAUSE_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 46, 18, 23, 18);
+    pub const STOP: Sprite = Sprite::new(Sheet::CButtons, 69, 0, 23, 18);
+    pub const STOP_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 69, 18, 23, 18);
+    pub const NEXT: Sprite = Sprite::new(Sheet::CButtons, 92, 0, 22, 18);
+    pub const NEXT_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 92, 18, 22, 18);
+    pub const EJECT: Sprite = Sprite::new(Sheet::CButtons, 114, 0, 22, 16);
+    pub const EJECT_PRESSED: Sprite = Sprite::new(Sheet::CButtons, 114, 16, 22, 16);
+
+    pub const POSBAR_BACKGROUND: Sprite = Sprite::new(Sheet::PosBar, 0, 0, 248, 10);
+    pub const POSBAR_THUMB: Sprite = Sprite::new(Sheet::PosBar, 248, 0, 29, 10);
+    pub const POSBAR_THUMB_PRESSED: Sprite = Sprite::new(Sheet::PosBar, 278, 0, 29, 10);
+
+    pub const EQ_BACKGROUND: Sprite = Sprite::new(Sheet::EqMain, 0, 0, 275, 116);
+    pub const EQ_TITLE_BAR: Sprite = Sprite::new(Sheet::EqMain, 0, 134, 275, 14);
+    pub const EQ_ON: Sprite = Sprite::new(Sheet::EqMain, 10, 119, 26, 12);
+    pub const EQ_ON_PRESSED: Sprite = Sprite::new(Sheet::EqMain, 128, 119, 26, 12);
+    pub const EQ_ON_ENABLED: Sprite = Sprite::new(Sheet::EqMain, 69, 119, 26, 12);
+    pub const EQ_ON_ENABLED_PRESSED: Sprite = Sprite::new(Sheet::EqMain, 187, 119, 26, 12);
+    pub const EQ_SLIDER_THUMB: Sprite = Sprite::new(Sheet::EqMain, 0, 164, 11, 11);
+    pub const EQ_SLIDER_THUMB_PRESSED: Sprite = Sprite::new(Sheet::EqMain, 0, 176, 11, 11);
+    pub const EQ_GRAPH_BACKGROUND: Sprite = Sprite::new(Sheet::EqMain, 0, 294, 113, 19);
+    /// Color of the response curve at each row of the graph
+    pub const EQ_GRAPH_LINE_COLORS: Sprite = Sprite::new(Sheet::EqMain, 115, 294, 1, 19);
+    pub const EQ_PREAMP_LINE: Sprite = Sprite::new(Sheet::EqMain, 0, 314, 113, 1);
+}
+
+/// Layout of the main and equalizer windows: where each sprite goes, in skin
+/// pixels from the window's top left corner
+pub mod layout {
+    pub const PREVIOUS: (f32, f32) = (16.0, 88.0);
+    pub const PLAY: (f32, f32) = (39.0, 88.0);
+    pub const PAUSE: (f32, f32) = (62.0, 88.0);
+    pub const STOP: (f32, f32) = (85.0, 88.0);
+    pub const NEXT: (f32, f32) = (108.0, 88.0);
+    pub const EJECT: (f32, f32) = (136.0, 89.0);
+    pub const POSBAR: (f32, f32) = (16.0, 72.0);
+    /// Minutes and seconds digits
+    pub const TIME_DIGITS: [f32; 4] = [48.0, 60.0, 78.0, 90.0];
+    pub const TIME_TOP: f32 = 26.0;
+    pub const MARQUEE: (f32, f32) = (111.0, 27.0);
+    pub const MARQUEE_WIDTH: f32 = 154.0;
+    pub const KBPS: (f32, f32) = (111.0, 43.0);
+    pub const KHZ: (f32, f32) = (156.0, 43.0);
+    pub const VISUALIZER: (f32, f32) = (24.0, 43.0);
+    pub const VISUALIZER_SIZE: (f32, f32) = (76.0, 16.0);
+
+    pub const EQ_ON: (f32, f32) = (14.0, 18.0);
+    pub const EQ_GRAPH: (f32, f32) = (86.0, 17.0);
+    pub const EQ_PREAMP: (f32, f32) = (21.0, 38.0);
+    pub const EQ_FIRST_BAND: (f32, f32) = (78.0, 38.0);
+    pub const EQ_BAND_STEP: f32 = 18.0;
+    pub const EQ_SLIDER_SIZE: (f32, f32) = (14.0, 63.0);
+}
+
+/// Background of an equalizer slider at a gain, in dB: one of 28 frames laid
+/// out 14 per row
+pub fn eq_slider_background(gain_db: f32) -> Sprite {
+    let last = f32::from(EQ_SLIDER_FRAMES - 1);
+    let frame = ((gain_db.clamp(-12.0, 12.0) + 12.0) / 24.0 * last).round() as u16;
+    Sprite::new(
+        Sheet::EqMain,
+        13 + (frame % 14) * 15,
+        164 + (frame / 14) * 65,
+        14,
+        63,
+    )
+}
+
+/// `numbers.bmp` digit
+pub fn digit(value: u8) -> Sprite {
+    Sprite::new(Sheet::Numbers, u16::from(value.min(9)) * 9, 0, 9, 13)
+}
+
+/// `text.bmp` glyph of a character; unknown characters are blank
+pub fn glyph(c: char) -> Sprite {
+    let (row, col) = glyph_cell(c.to_ascii_lowercase()).unwrap_or((0, 30));
+    Sprite::new(Sheet::Text, col * 5, row * 6, 5, 6)
+}
+
+fn glyph_cell(c: char) -> Option<(u16, u16)> {
+    const ROW_1: &str = "0123456789….:()-'!_+\\/[]^&%,=$#";
+    const ROW_2: &str = "ÅÖÄ?*";
+    let cell = match c {
+        'a'..='z' => (0, c as u16 - 'a' as u16),
+        '"' => (0, 26),
+        '@' => (0, 27),
+        '<' | '{' => (1, 22),
+        '>' | '}' => (1, 23),
+        'å' => (2, 0),
+        'ö' => (2, 1),
+        'ä' => (2, 2),
+        _ => {
+            if let Some(col) = ROW_1.chars().position(|other| other == c) {
+                (1, col as u16)
+            } else {
+                (2, ROW_2.chars().position(|other| other == c)? as u16)
+            }
+        }
+    };
+    Some(cell)
+}
+
+/// Playlist colors from `pledit.txt`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistColors {
+    pub normal: Color32,
+    pub current: Color32,
+    pub normal_bg: Color32,
+    pub selected_bg: Color32,
+    /// Font name; the playlist is drawn with the default font
+    pub font: String,
+}
+
+impl Default for PlaylistColors {
+    fn default() -> Self {
+        Self {
+            normal: Color32::from_rgb(0x00, 0xff, 0x00),
+            current: Color32::from_rgb(0xff, 0xff, 0xff),
+            normal_bg: Color32::from_rgb(0x00, 0x00, 0x00),
+            selected_bg: Color32::from_rgb(0x00, 0x00, 0xff),
+            font: "Arial".to_string(),
+        }
+    }
+}
+
+/// Parses `pledit.txt`, an INI file with a `[Text]` section. Keys are
+/// case-insensitive and the `#` of colors is optional; missing or invalid
+/// entries keep their default.
+pub fn parse_pledit(text: &str) -> PlaylistColors {
+    let mut colors = PlaylistColors::default();
+    let mut in_text = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_text = line.eq_ignore_ascii_case("[text]");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !in_text {
+            continue;
+        }
+        let value = value.trim();
+        let key = key.trim().to_ascii_lowercase();
+        if key == "font" {
+            colors.font = value.to_string();
+            continue;
+        }
+        let Some(color) = parse_ini_color(value) else {
+            continue;
+        };
+        match key.as_str() {
+            "normal" => colors.normal = color,
+            "current" => colors.current = color,
+            "normalbg" => colors.normal_bg = color,
+            "selectedbg" => colors.selected_bg = color,
+            _ => {}
+        }
+    }
+    colors
+}
+
+fn parse_ini_color(value: &str) -> Option<Color32> {
+    let hex = value.trim_start_matches('#').get(..6)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Visualizer colors: 0 is the background, 1 the dots, 2-17 the spectrum
+/// from top to bottom, 18-22 the oscilloscope and 23 the peaks
+pub const DEFAULT_VIS_COLORS: [[u8; 3]; 24] = [
+    [0, 0, 0],
+    [24, 33, 41],
+    [239, 49, 16],
+    [206, 41, 16],
+    [214, 90, 0],
+    [214, 102, 0],
+    [214, 115, 0],
+    [198, 123, 8],
+    [222, 165, 24],
+    [214, 181, 33],
+    [189, 222, 41],
+    [148, 222, 33],
+    [41, 206, 16],
+    [50, 190, 16],
+    [57, 181, 16],
+    [49, 156, 8],
+    [41, 148, 0],
+    [24, 132, 8],
+    [255, 255, 255],
+    [214, 214, 222],
+    [181, 189, 189],
+    [160, 170, 175],
+    [148, 156, 165],
+    [150, 150, 150],
+];
+
+/// Parses `viscolor.txt`: one `r,g,b` per line, usually followed by a
+/// comment. Lines past the 24th are ignored; missing ones keep the default.
+pub fn parse_viscolor(text: &str) -> [Color32; 24] {
+    let mut colors = DEFAULT_VIS_COLORS.map(|[r, g, b]| Color32::from_rgb(r, g, b));
+    let parsed = text.lines().filter_map(|line| {
+        let mut channels = line
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse::<u16>().ok().map(|v| v.min(255) as u8));
+        let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+        Some(Color32::from_rgb(r, g, b))
+    });
+    for (color, value) in colors.iter_mut().zip(parsed) {
+        *color = value;
+    }
+    colors
+}
+
+/// A classic skin: its decoded bitmaps and text files. Bitmaps missing from
+/// the archive aren't drawn.
+pub struct ClassicSkin {
+    sheets: HashMap<Sheet, ColorImage>,
+    pub playlist: PlaylistColors,
+    pub vis_colors: [Color32; 24],
+}
+
+impl ClassicSkin {
+    pub fn new(playlist: PlaylistColors, vis_colors: [Color32; 24]) -> Self {
+        Self {
+            sheets: HashMap::new(),
+            playlist,
+            vis_colors,
+        }
+    }
+
+    pub fn set_sheet(&mut self, sheet: Sheet, image: ColorImage) {
+        self.sheets.insert(sheet, image);
+    }
+
+    pub fn sheet(&self, sheet: Sheet) -> Option<&ColorImage> {
+        self.sheets.get(&sheet)
+    }
+
+    /// Color of a bitmap pixel, for colors skins keep in their sprite sheets
+    pub fn pixel(&self, sheet: Sheet, x: usize, y: usize) -> Option<Color32> {
+        let image = self.sheet(sheet)?;
+        let [width, height] = image.size;
+        (x < width && y < height).then(|| image.pixels[y * width + x])
+    }
+}
+
+impl fmt::Debug for ClassicSkin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sheets: Vec<_> = self.sheets.keys().collect();
+        sheets.sort_by_key(|sheet| sheet.file_name());
+        f.debug_struct("ClassicSkin")
+            .field("sheets", &sheets)
+            .field("playlist", &self.playlist)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pledit() {
+        let text = "[Text]\r\nNormal=#00FF00\r\ncurrent=FFFFFF\r\nNormalBG=#0a0b0c\r\n\
+                    SelectedBG=#zzzzzz\r\nFont=Tahoma\r\n[Other]\r\nNormal=#123456\r\n";
+        let colors = parse_pledit(text);
+        assert_eq!(colors.normal, Color32::from_rgb(0, 255, 0));
+        assert_eq!(colors.current, Color32::WHITE);
+        assert_eq!(colors.normal_bg, Color32::from_rgb(10, 11, 12));
+        // Invalid colors keep the default
+        assert_eq!(colors.selected_bg, PlaylistColors::default().selected_bg);
+        assert_eq!(colors.font, "Tahoma");
+
+        assert_eq!(parse_pledit(""), PlaylistColors::default());
+    }
+
+    #[test]
+    fn test_parse_viscolor() {
+        let text = "0,0,0, // color 0 = black\n10, 20, 300 // clamped\nnonsense\n";
+        let colors = parse_viscolor(text);
+        assert_eq!(colors[0], Color32::BLACK);
+        assert_eq!(colors[1], Color32::from_rgb(10, 20, 255));
+        let [r, g, b] = DEFAULT_VIS_COLORS[2];
+        assert_eq!(colors[2], Color32::from_rgb(r, g, b));
+    }
+
+    #[test]
+    fn test_glyphs() {
+        assert_eq!(glyph('a'), Sprite::new(Sheet::Text, 0, 0, 5, 6));
+        assert_eq!(glyph('Z'), Sprite::new(Sheet::Text, 125, 0, 5, 6));
+        assert_eq!(glyph('7'), Sprite::new(Sheet::Text, 35, 6, 5, 6));
+        assert_eq!(glyph(':'), Sprite::new(Sheet::Text, 60, 6, 5, 6));
+        assert_eq!(glyph('?'), Sprite::new(Sheet::Text, 15, 12, 5, 6));
+        assert_eq!(glyph(' '), glyph('~'));
+        assert_eq!(glyph(' ').x, 150);
+        assert_eq!(digit(3), Sprite::new(Sheet::Numbers, 27, 0, 9, 13));
+    }
+
+    #[test]
+    fn test_eq_slider_background() {
+        let bottom = eq_slider_background(-12.0);
+        assert_eq!((bottom.x, bottom.y), (13, 164));
+        let top = eq_slider_background(20.0);
+        assert_eq!((top.x, top.y), (13 + 13 * 15, 164 + 65));
+        let flat = eq_slider_background(0.0);
+        assert_eq!((flat.x, flat.y), (13, 164 + 65));
+    }
+}
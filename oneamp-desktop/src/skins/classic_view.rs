@@ -0,0 +1,608 @@
+// Classic Skin View
+// Draws the main, equalizer and playlist windows of a classic Winamp skin,
+// docked one under the other. One skin pixel covers exactly `scale` physical
+// pixels and textures are sampled without filtering, so the bitmaps come out
+// pixel-exact at 1x and 2x.
+
+use super::classic::{
+    self, layout, sprites, ClassicSkin, Sheet, Sprite, GLYPH_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use crate::playlist_tabs::PlaylistTab;
+use crate::track_display::TrackDisplay;
+use eframe::egui;
+use egui::{
+    Align2, Color32, FontId, Painter, Pos2, Rect, Response, Sense, TextureHandle, TextureOptions,
+    Ui, Vec2,
+};
+use oneamp_core::{MetadataService, PlaybackState, TrackInfo, TrackMetadata};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// Playlist row height and font size, in skin pixels
+const ROW_HEIGHT: f32 = 13.0;
+const ROW_FONT_SIZE: f32 = 9.0;
+/// Room kept for the durations at the right of the playlist, in skin pixels
+const DURATION_WIDTH: f32 = 32.0;
+/// Smallest playlist window height, in skin pixels
+const MIN_PLAYLIST_HEIGHT: f32 = 116.0;
+/// Marquee scrolling speed, in characters per second
+const MARQUEE_SPEED: f64 = 5.0;
+const MARQUEE_SEPARATOR: &str = "  ***  ";
+
+/// Something the user did in the classic windows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClassicAction {
+    Previous,
+    Play,
+    Pause,
+    Stop,
+    Next,
+    /// Open files
+    Eject,
+    Seek(f32),
+    SetEqEnabled(bool),
+    SetEqBand(usize, f32),
+    /// Select a playlist entry
+    Select(usize),
+    PlayEntry(usize),
+}
+
+/// What the classic windows show
+pub struct ClassicState<'a> {
+    pub track: Option<&'a TrackInfo>,
+    pub playback: PlaybackState,
+    pub position: f32,
+    pub duration: f32,
+    pub spectrum: &'a [f32],
+    pub eq_enabled: bool,
+    pub eq_gains: &'a [f32],
+    /// Tab shown in the playlist window
+    pub playlist: &'a PlaylistTab,
+    /// Entry of `playlist` that is playing
+    pub current: Option<usize>,
+}
+
+/// Classic skin windows, with their textures and the state of their controls
+pub struct ClassicView {
+    scale: u8,
+    textures: HashMap<Sheet, TextureHandle>,
+    /// Skin the textures were made from
+    loaded: Option<Arc<ClassicSkin>>,
+    /// Position bar value while its thumb is held, 0..=1
+    seeking: Option<f32>,
+    /// Playlist scroll position, in skin pixels
+    playlist_scroll: f32,
+}
+
+impl ClassicView {
+    /// A view drawn at 1x or 2x
+    pub fn new(scale: u8) -> Self {
+        Self {
+            scale: scale.clamp(1, 2),
+            textures: HashMap::new(),
+            loaded: None,
+            seeking: None,
+            playlist_scroll: 0.0,
+        }
+    }
+
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: u8) {
+        self.scale = scale.clamp(1, 2);
+    }
+
+    /// Draws the windows at the top left of the available space, the
+    /// playlist taking the remaining height
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        skin: &Arc<ClassicSkin>,
+        state: &ClassicState,
+        metadata: &mut MetadataService,
+    ) -> Option<ClassicAction> {
+        self.load_textures(ui.ctx(), skin);
+
+        // Start on a physical pixel so sprite edges fall between pixels
+        let ppp = ui.ctx().pixels_per_point();
+        let unit = f32::from(self.scale) / ppp;
+        let available = ui.available_rect_before_wrap();
+        let origin = Pos2::new(
+            (available.min.x * ppp).ceil() / ppp,
+            (available.min.y * ppp).ceil() / ppp,
+        );
+        let playlist_height = ((available.max.y - origin.y) / unit - 2.0 * WINDOW_HEIGHT)
+            .floor()
+            .max(MIN_PLAYLIST_HEIGHT);
+        let size = Vec2::new(WINDOW_WIDTH, 2.0 * WINDOW_HEIGHT + playlist_height) * unit;
+        let rect = Rect::from_min_size(origin, size);
+        ui.allocate_rect(rect, Sense::hover());
+
+        let canvas = Canvas {
+            ui,
+            painter: ui.painter_at(rect),
+            origin,
+            unit,
+            skin,
+            textures: &self.textures,
+        };
+        let time = ui.input(|i| i.time);
+        let main = main_window(&canvas, state, &mut self.seeking, time);
+        let eq = eq_window(&canvas.window(WINDOW_HEIGHT), state);
+        let playlist = playlist_window(
+            &canvas.window(2.0 * WINDOW_HEIGHT),
+            state,
+            metadata,
+            &mut self.playlist_scroll,
+            playlist_height,
+        );
+        main.or(eq).or(playlist)
+    }
+
+    fn load_textures(&mut self, ctx: &egui::Context, skin: &Arc<ClassicSkin>) {
+        if self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| Arc::ptr_eq(loaded, skin))
+        {
+            return;
+        }
+        self.textures = Sheet::ALL
+            .into_iter()
+            .filter_map(|sheet| {
+                let image = skin.sheet(sheet)?.clone();
+                let name = format!("classic_skin_{}", sheet.file_name());
+                Some((
+                    sheet,
+                    ctx.load_texture(name, image, TextureOptions::NEAREST),
+                ))
+            })
+            .collect();
+        self.loaded = Some(skin.clone());
+    }
+}
+
+/// Draws and hit-tests in skin pixels from the top left corner of a window
+struct Canvas<'a> {
+    ui: &'a Ui,
+    painter: Painter,
+    origin: Pos2,
+    unit: f32,
+    skin: &'a ClassicSkin,
+    textures: &'a HashMap<Sheet, TextureHandle>,
+}
+
+impl Canvas<'_> {
+    /// The window `top` skin pixels down
+    fn window(&self, top: f32) -> Canvas<'_> {
+        Canvas {
+            ui: self.ui,
+            painter: self.painter.clone(),
+            origin: self.origin + Vec2::new(0.0, top * self.unit),
+            unit: self.unit,
+            skin: self.skin,
+            textures: self.textures,
+        }
+    }
+
+    fn rect(&self, x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect::from_min_size(
+            self.origin + Vec2::new(x, y) * self.unit,
+            Vec2::new(w, h) * self.unit,
+        )
+    }
+
+    /// Draws a sprite; sprites past the edge of their sheet aren't drawn
+    fn sprite(&self, sprite: Sprite, x: f32, y: f32) {
+        let Some(texture) = self.textures.get(&sprite.sheet) else {
+            return;
+        };
+        let [width, height] = texture.size();
+        let (right, bottom) = (sprite.x + sprite.w, sprite.y + sprite.h);
+        if usize::from(right) > width || usize::from(bottom) > height {
+            return;
+        }
+        let (width, height) = (width as f32, height as f32);
+        let uv = Rect::from_min_max(
+            Pos2::new(f32::from(sprite.x) / width, f32::from(sprite.y) / height),
+            Pos2::new(f32::from(right) / width, f32::from(bottom) / height),
+        );
+        let rect = self.rect(x, y, f32::from(sprite.w), f32::from(sprite.h));
+        self.painter.image(texture.id(), rect, uv, Color32::WHITE);
+    }
+
+    fn fill(&self, x: f32, y: f32, w: f32, h: f32, color: Color32) {
+        self.painter.rect_filled(self.rect(x, y, w, h), 0.0, color);
+    }
+
+    /// Draws `text.bmp` characters, as many as fit in `max_width`
+    fn text(&self, text: impl Iterator<Item = char>, x: f32, y: f32, max_width: f32) {
+        let fits = (max_width / GLYPH_WIDTH) as usize;
+        for (i, c) in text.take(fits).enumerate() {
+            self.sprite(classic::glyph(c), x + i as f32 * GLYPH_WIDTH, y);
+        }
+    }
+
+    fn interact(&self, id: impl Hash, x: f32, y: f32, w: f32, h: f32, sense: Sense) -> Response {
+        let id = self.ui.id().with(("classic_skin", id));
+        self.ui.interact(self.rect(x, y, w, h), id, sense)
+    }
+
+    /// A button showing its pressed sprite while held
+    fn button(&self, id: &str, sprite: Sprite, pressed: Sprite, (x, y): (f32, f32)) -> bool {
+        let (w, h) = (f32::from(sprite.w), f32::from(sprite.h));
+        let response = self.interact(id, x, y, w, h, Sense::click());
+        let shown = if response.is_pointer_button_down_on() {
+            pressed
+        } else {
+            sprite
+        };
+        self.sprite(shown, x, y);
+        response.clicked()
+    }
+}
+
+/// Time as Winamp shows it in the playlist, `m:ss`
+fn format_time(secs: f32) -> String {
+    let secs = secs.max(0.0) as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn main_window(
+    c: &Canvas,
+    state: &ClassicState,
+    seeking: &mut Option<f32>,
+    time: f64,
+) -> Option<ClassicAction> {
+    c.sprite(sprites::MAIN_BACKGROUND, 0.0, 0.0);
+    c.sprite(sprites::MAIN_TITLE_BAR, 0.0, 0.0);
+    let active = state.playback != PlaybackState::Stopped;
+
+    // Elapsed time, blinking while paused
+    let blink_off = state.playback == PlaybackState::Paused && time % 1.0 >= 0.5;
+    if active && !blink_off {
+        let secs = state.position.max(0.0) as u32;
+        let minutes = (secs / 60).min(99);
+        let digits = [minutes / 10, minutes % 10, secs % 60 / 10, secs % 10];
+        for (x, digit) in layout::TIME_DIGITS.into_iter().zip(digits) {
+            c.sprite(classic::digit(digit as u8), x, layout::TIME_TOP);
+        }
+    }
+
+    // Song title, scrolling when it doesn't fit, and stream info
+    if let Some(track) = state.track {
+        let mut title = TrackDisplay::get_title(track);
+        if let Some(index) = state.current {
+            title = format!("{}. {}", index + 1, title);
+        }
+        if state.duration > 0.0 {
+            title = format!("{} ({})", title, format_time(state.duration));
+        }
+        let (x, y) = layout::MARQUEE;
+        let fits = (layout::MARQUEE_WIDTH / GLYPH_WIDTH) as usize;
+        let chars: Vec<char> = title.chars().collect();
+        if chars.len() <= fits {
+            c.text(chars.into_iter(), x, y, layout::MARQUEE_WIDTH);
+        } else {
+            let looped: Vec<char> = chars.into_iter().chain(MARQUEE_SEPARATOR.chars()).collect();
+            let offset = (time * MARQUEE_SPEED) as usize % looped.len();
+            let text = looped.iter().cycle().skip(offset).copied();
+            c.text(text, x, y, layout::MARQUEE_WIDTH);
+        }
+
+        if let Some(kbps) = track.bitrate {
+            let (x, y) = layout::KBPS;
+            c.text(
+                format!("{:>3}", kbps.min(999)).chars(),
+                x,
+                y,
+                3.0 * GLYPH_WIDTH,
+            );
+        }
+        if let Some(rate) = track.sample_rate {
+            let (x, y) = layout::KHZ;
+            let khz = (rate / 1000).min(99);
+            c.text(format!("{:>2}", khz).chars(), x, y, 2.0 * GLYPH_WIDTH);
+        }
+    }
+
+    if active {
+        visualizer(c, state.spectrum);
+    }
+
+    let mut action = None;
+
+    // Position bar, seeking when the thumb is released
+    if state.duration > 0.0 {
+        let (x, y) = layout::POSBAR;
+        let bar = sprites::POSBAR_BACKGROUND;
+        let thumb = sprites::POSBAR_THUMB;
+        let travel = f32::from(bar.w - thumb.w);
+        c.sprite(bar, x, y);
+
+        let (w, h) = (f32::from(bar.w), f32::from(bar.h));
+        let response = c.interact("posbar", x, y, w, h, Sense::click_and_drag());
+        if response.is_pointer_button_down_on() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let offset = (pointer.x - response.rect.min.x) / c.unit - f32::from(thumb.w) / 2.0;
+                *seeking = Some((offset / travel).clamp(0.0, 1.0));
+            }
+        }
+        let ratio = seeking.unwrap_or(state.position / state.duration);
+        let shown = if seeking.is_some() {
+            sprites::POSBAR_THUMB_PRESSED
+        } else {
+            thumb
+        };
+        c.sprite(shown, x + (ratio.clamp(0.0, 1.0) * travel).round(), y);
+
+        if response.drag_stopped() || response.clicked() {
+            if let Some(ratio) = seeking.take() {
+                action = Some(ClassicAction::Seek(ratio * state.duration));
+            }
+        }
+        if !response.is_pointer_button_down_on() {
+            *seeking = None;
+        }
+    } else {
+        *seeking = None;
+    }
+
+    let buttons = [
+        (
+            "previous",
+            sprites::PREVIOUS,
+            sprites::PREVIOUS_PRESSED,
+            layout::PREVIOUS,
+            ClassicAction::Previous,
+        ),
+        (
+            "play",
+            sprites::PLAY,
+            sprites::PLAY_PRESSED,
+            layout::PLAY,
+            ClassicAction::Play,
+        ),
+        (
+            "pause",
+            sprites::PAUSE,
+            sprites::PAUSE_PRESSED,
+            layout::PAUSE,
+            ClassicAction::Pause,
+        ),
+        (
+            "stop",
+            sprites::STOP,
+            sprites::STOP_PRESSED,
+            layout::STOP,
+            ClassicAction::Stop,
+        ),
+        (
+            "next",
+            sprites::NEXT,
+            sprites::NEXT_PRESSED,
+            layout::NEXT,
+            ClassicAction::Next,
+        ),
+        (
+            "eject",
+            sprites::EJECT,
+            sprites::EJECT_PRESSED,
+            layout::EJECT,
+            ClassicAction::Eject,
+        ),
+    ];
+    for (id, sprite, pressed, position, button_action) in buttons {
+        if c.button(id, sprite, pressed, position) {
+            action = Some(button_action);
+        }
+    }
+    action
+}
+
+/// Spectrum analyzer in `viscolor.txt` colors: bars 3 pixels wide, each row
+/// in its own color
+fn visualizer(c: &Canvas, spectrum: &[f32]) {
+    let colors = &c.skin.vis_colors;
+    let (x, y) = layout::VISUALIZER;
+    let (width, height) = layout::VISUALIZER_SIZE;
+    c.fill(x, y, width, height, colors[0]);
+    if spectrum.is_empty() {
+        return;
+    }
+
+    let bars = (width / 4.0) as usize;
+    for bar in 0..bars {
+        let start = bar * spectrum.len() / bars;
+        let end = ((bar + 1) * spectrum.len() / bars).max(start + 1);
+        let level = spectrum[start..end].iter().sum::<f32>() / (end - start) as f32;
+        let bar_height = (level.clamp(0.0, 1.0) * height).round();
+        for row in (height - bar_height) as usize..height as usize {
+            let left = x + bar as f32 * 4.0;
+            c.fill(left, y + row as f32, 3.0, 1.0, colors[2 + row]);
+        }
+    }
+}
+
+fn eq_window(c: &Canvas, state: &ClassicState) -> Option<ClassicAction> {
+    c.sprite(sprites::EQ_BACKGROUND, 0.0, 0.0);
+    c.sprite(sprites::EQ_TITLE_BAR, 0.0, 0.0);
+    let mut action = None;
+
+    let (on, on_pressed) = if state.eq_enabled {
+        (sprites::EQ_ON_ENABLED, sprites::EQ_ON_ENABLED_PRESSED)
+    } else {
+        (sprites::EQ_ON, sprites::EQ_ON_PRESSED)
+    };
+    if c.button("eq_on", on, on_pressed, layout::EQ_ON) {
+        action = Some(ClassicAction::SetEqEnabled(!state.eq_enabled));
+    }
+
+    eq_graph(c, state.eq_gains);
+
+    // The engine has no preamp, so its slider stays at 0 dB
+    let (x, y) = layout::EQ_PREAMP;
+    eq_slider(c, "preamp", x, y, 0.0, false);
+
+    let (x, y) = layout::EQ_FIRST_BAND;
+    for (band, &gain) in state.eq_gains.iter().enumerate().take(10) {
+        let left = x + band as f32 * layout::EQ_BAND_STEP;
+        if let Some(gain) = eq_slider(c, ("band", band), left, y, gain, true) {
+            action = Some(ClassicAction::SetEqBand(band, gain));
+        }
+    }
+    action
+}
+
+/// Equalizer slider from -12 dB at the bottom to +12 dB at the top. Returns
+/// the gain it was dragged to.
+fn eq_slider(c: &Canvas, id: impl Hash, x: f32, y: f32, gain: f32, enabled: bool) -> Option<f32> {
+    let (w, h) = layout::EQ_SLIDER_SIZE;
+    c.sprite(classic::eq_slider_background(gain), x, y);
+    let thumb = sprites::EQ_SLIDER_THUMB;
+    let travel = h - f32::from(thumb.h);
+
+    let sense = if enabled {
+        Sense::click_and_drag()
+    } else {
+        Sense::hover()
+    };
+    let response = c.interact(id, x, y, w, h, sense);
+    let pressed = enabled && response.is_pointer_button_down_on();
+    let mut new_gain = None;
+    if let Some(pointer) = response.interact_pointer_pos().filter(|_| pressed) {
+        let offset = (pointer.y - response.rect.min.y) / c.unit - f32::from(thumb.h) / 2.0;
+        let value = (12.0 - offset / travel * 24.0).clamp(-12.0, 12.0);
+        let value = (value * 10.0).round() / 10.0;
+        if (value - gain).abs() > f32::EPSILON {
+            new_gain = Some(value);
+        }
+    }
+
+    let shown = new_gain.unwrap_or(gain).clamp(-12.0, 12.0);
+    let top = ((12.0 - shown) / 24.0 * travel).round();
+    let sprite = if pressed {
+        sprites::EQ_SLIDER_THUMB_PRESSED
+    } else {
+        thumb
+    };
+    c.sprite(sprite, x + 1.0, y + top);
+    new_gain
+}
+
+/// Response curve through the band gains, each row in the color the skin
+/// gives it
+fn eq_graph(c: &Canvas, gains: &[f32]) {
+    let (x, y) = layout::EQ_GRAPH;
+    let background = sprites::EQ_GRAPH_BACKGROUND;
+    c.sprite(background, x, y);
+    let rows = f32::from(background.h) - 1.0;
+    c.sprite(sprites::EQ_PREAMP_LINE, x, y + (rows / 2.0).round());
+    if gains.len() < 2 {
+        return;
+    }
+
+    let line_colors = sprites::EQ_GRAPH_LINE_COLORS;
+    let last_column = f32::from(background.w) - 1.0;
+    for column in 0..background.w {
+        let position = f32::from(column) / last_column * (gains.len() - 1) as f32;
+        let band = (position as usize).min(gains.len() - 2);
+        let t = position - band as f32;
+        let gain = gains[band] * (1.0 - t) + gains[band + 1] * t;
+        let row = ((12.0 - gain.clamp(-12.0, 12.0)) / 24.0 * rows).round();
+        let color = c
+            .skin
+            .pixel(
+                line_colors.sheet,
+                usize::from(line_colors.x),
+                usize::from(line_colors.y) + row as usize,
+            )
+            .unwrap_or(c.skin.playlist.normal);
+        c.fill(x + f32::from(column), y + row, 1.0, 1.0, color);
+    }
+}
+
+/// Playlist in `pledit.txt` colors: a click selects an entry, a double click
+/// plays it
+fn playlist_window(
+    c: &Canvas,
+    state: &ClassicState,
+    metadata: &mut MetadataService,
+    scroll: &mut f32,
+    height: f32,
+) -> Option<ClassicAction> {
+    let colors = &c.skin.playlist;
+    c.fill(0.0, 0.0, WINDOW_WIDTH, height, colors.normal_bg);
+
+    let playlist = &state.playlist.playlist;
+    let visible_rows = (height / ROW_HEIGHT) as usize;
+    let response = c.interact("playlist", 0.0, 0.0, WINDOW_WIDTH, height, Sense::click());
+    if response.hovered() {
+        *scroll -= c.ui.input(|i| i.raw_scroll_delta.y) / c.unit;
+    }
+    let max_scroll = playlist.len().saturating_sub(visible_rows) as f32 * ROW_HEIGHT;
+    *scroll = scroll.clamp(0.0, max_scroll);
+    let first = (*scroll / ROW_HEIGHT) as usize;
+
+    let font = FontId::proportional(ROW_FONT_SIZE * c.unit);
+    for (row, index) in (first..playlist.len()).take(visible_rows).enumerate() {
+        let Some(path) = playlist.get(index) else {
+            break;
+        };
+        let top = row as f32 * ROW_HEIGHT;
+        if state.playlist.is_selected(index) {
+            c.fill(0.0, top, WINDOW_WIDTH, ROW_HEIGHT, colors.selected_bg);
+        }
+
+        // Bare file names until the background scan reaches the track
+        metadata.request(path);
+        let track = metadata.get(path);
+        let title = match track.and_then(TrackMetadata::info) {
+            Some(info) => TrackDisplay::get_title(info),
+            None => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+        };
+        let color = if state.current == Some(index) {
+            colors.current
+        } else {
+            colors.normal
+        };
+
+        let row_rect = c.rect(2.0, top, WINDOW_WIDTH - 4.0, ROW_HEIGHT);
+        let title_rect = row_rect.with_max_x(row_rect.max.x - DURATION_WIDTH * c.unit);
+        c.painter.with_clip_rect(title_rect).text(
+            title_rect.left_center(),
+            Align2::LEFT_CENTER,
+            format!("{}. {}", index + 1, title),
+            font.clone(),
+            color,
+        );
+        if let Some(duration) = track.and_then(TrackMetadata::duration_secs) {
+            c.painter.text(
+                row_rect.right_center(),
+                Align2::RIGHT_CENTER,
+                format_time(duration),
+                font.clone(),
+                color,
+            );
+        }
+    }
+
+    let pointer = response.interact_pointer_pos()?;
+    let row = ((pointer.y - response.rect.min.y) / c.unit / ROW_HEIGHT) as usize;
+    let index = first + row;
+    if index >= playlist.len() {
+        None
+    } else if response.double_clicked() {
+        Some(ClassicAction::PlayEntry(index))
+    } else if response.clicked() {
+        Some(ClassicAction::Select(index))
+    } else {
+        None
+    }
+}
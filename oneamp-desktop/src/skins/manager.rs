@@ -1,10 +1,18 @@
 // Skin Manager
-// Responsible for discovering, loading, and applying skins.
+// Responsible for discovering, loading, and applying skins, and for installing
+// and uninstalling `.oaskin` packages.
 
+use super::assets::{self, SkinAssets};
+use super::classic::ClassicSkin;
 use super::{parser, Skin};
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use egui::TextureHandle;
+use oneamp_core::skin::{self, Manifest};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Manages the discovery, loading, and application of skins.
 pub struct SkinManager {
@@ -13,6 +21,24 @@ pub struct SkinManager {
 
     /// Index of the currently active skin in the `available_skins` list.
     pub active_skin_index: usize,
+
+    /// Directory skins are discovered in and imported to.
+    skins_dir: PathBuf,
+
+    /// Directory `.oaskin` packages are installed to.
+    packages_dir: PathBuf,
+
+    /// Sprite sheets of the classic `.wsz` skins, by skin path.
+    classic_skins: HashMap<PathBuf, Arc<ClassicSkin>>,
+
+    /// Manifests of the installed packages, by skin path.
+    packages: HashMap<PathBuf, Manifest>,
+
+    /// Preview textures of the packages shown so far, by skin path.
+    previews: HashMap<PathBuf, Option<TextureHandle>>,
+
+    /// Textures and fonts of the active skin.
+    assets: SkinAssets,
 }
 
 impl SkinManager {
@@ -20,30 +46,38 @@ impl SkinManager {
     ///
     /// # Arguments
     /// * `skins_dir` - Path to the directory containing skin subdirectories
+    ///   and classic `.wsz` skins
     ///
     /// # Returns
     /// A new `SkinManager` with discovered skins. If no skins are found or the
     /// directory doesn't exist, the manager will contain only the default built-in skin.
     pub fn discover_and_load(skins_dir: &Path) -> Self {
-        let mut available_skins = vec![Skin::default_builtin()];
+        let mut manager = Self {
+            available_skins: vec![Skin::default_builtin()],
+            active_skin_index: 0,
+            skins_dir: skins_dir.to_path_buf(),
+            packages_dir: skins_dir.to_path_buf(),
+            classic_skins: HashMap::new(),
+            packages: HashMap::new(),
+            previews: HashMap::new(),
+            assets: SkinAssets::default(),
+        };
 
         if !skins_dir.exists() {
             eprintln!("Skins directory not found: {:?}", skins_dir);
-            return Self {
-                available_skins,
-                active_skin_index: 0,
-            };
+            return manager;
         }
 
-        // Scan the skins directory for subdirectories
+        // Scan the skins directory for subdirectories and .wsz files
         match fs::read_dir(skins_dir) {
             Ok(entries) => {
                 for entry in entries.flatten() {
                     let path = entry.path();
-                    if path.is_dir() {
-                        match parser::load_skin(&path) {
-                            Ok(skin) => {
-                                available_skins.push(skin);
+                    if path.is_dir() || parser::is_wsz(&path) {
+                        match parser::load_any(&path) {
+                            Ok((skin, classic)) => {
+                                manager.set_classic(&skin, classic);
+                                manager.available_skins.push(skin);
                             }
                             Err(e) => {
                                 eprintln!("Failed to load skin from {:?}: {}", path, e);
@@ -57,9 +91,27 @@ impl SkinManager {
             }
         }
 
-        Self {
-            available_skins,
-            active_skin_index: 0,
+        manager
+    }
+
+    /// Adds the `.oaskin` packages of a directory, where packages will also
+    /// be installed.
+    pub fn discover_packages(&mut self, packages_dir: &Path) {
+        self.packages_dir = packages_dir.to_path_buf();
+        let Ok(entries) = fs::read_dir(packages_dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if skin::is_package(&path) {
+                match skin::load_package(&path) {
+                    Ok(package) => {
+                        self.packages.insert(path, package.manifest);
+                        self.available_skins.push(package.skin);
+                    }
+                    Err(e) => eprintln!("Failed to load skin package {:?}: {}", path, e),
+                }
+            }
         }
     }
 
@@ -76,6 +128,18 @@ impl SkinManager {
         &mut self.available_skins[index]
     }
 
+    /// Gets the sprite sheets of the active skin if it's a classic skin.
+    pub fn active_classic_skin(&self) -> Option<&Arc<ClassicSkin>> {
+        self.classic_skins.get(&self.get_active_skin().path)
+    }
+
+    fn set_classic(&mut self, skin: &Skin, classic: Option<Arc<ClassicSkin>>) {
+        match classic {
+            Some(classic) => self.classic_skins.insert(skin.path.clone(), classic),
+            None => self.classic_skins.remove(&skin.path),
+        };
+    }
+
     /// Sets the active skin by index.
     ///
     /// # Arguments
@@ -108,9 +172,14 @@ impl SkinManager {
     /// Applies the active skin to the egui context.
     ///
     /// This method constructs an `egui::Visuals` and `egui::Style` from the active skin
-    /// and applies them to the provided context.
-    pub fn apply_skin(&self, ctx: &egui::Context) {
-        let skin = self.get_active_skin();
+    /// and applies them to the provided context, and loads the skin's images and
+    /// fonts when the active skin changed.
+    pub fn apply_skin(&mut self, ctx: &egui::Context) {
+        let skin = self
+            .available_skins
+            .get(self.active_skin_index)
+            .unwrap_or(&self.available_skins[0]);
+        self.assets.sync(ctx, skin);
 
         // Create visuals from the skin's colors
         let mut visuals = if skin.colors.dark_mode {
@@ -158,15 +227,179 @@ impl SkinManager {
         ctx.set_style(style);
     }
 
+    /// Gets the loaded images and fonts of the active skin.
+    pub fn assets(&self) -> &SkinAssets {
+        &self.assets
+    }
+
     /// Reloads the active skin from disk.
     ///
     /// This is useful for development when skin files are being edited.
     pub fn reload_active_skin(&mut self) -> Result<()> {
         let skin_path = self.get_active_skin().path.clone();
-        let reloaded_skin = parser::load_skin(&skin_path)?;
+        let (reloaded_skin, classic) = parser::load_any(&skin_path)?;
+        self.set_classic(&reloaded_skin, classic);
         self.available_skins[self.active_skin_index] = reloaded_skin;
+        // Load the images and fonts again on the next apply
+        self.assets = SkinAssets::default();
+        self.previews.remove(&skin_path);
         Ok(())
     }
+
+    /// Installs a `.oaskin` package or imports a classic `.wsz` skin.
+    ///
+    /// # Returns
+    /// The index of the installed skin.
+    pub fn install(&mut self, file: &Path) -> Result<usize> {
+        if skin::is_package(file) {
+            self.install_package(file)
+        } else if parser::is_wsz(file) {
+            self.import_wsz(file)
+        } else {
+            bail!("Not a skin package: {:?}", file)
+        }
+    }
+
+    /// Installs a `.oaskin` package into the packages directory, as
+    /// `<id>.oaskin`. Installing a newer version of an installed package
+    /// updates it in place.
+    ///
+    /// # Returns
+    /// The index of the installed skin, or an error if the package is invalid
+    /// or the same or a newer version is already installed.
+    pub fn install_package(&mut self, file: &Path) -> Result<usize> {
+        // Check the package before copying it
+        let package = skin::load_package(file)?;
+        let manifest = package.manifest;
+
+        let installed = self
+            .packages
+            .iter()
+            .find(|(_, installed)| installed.id == manifest.id)
+            .map(|(path, installed)| (path.clone(), installed.version.clone()));
+        if let Some((_, version)) = &installed {
+            if skin::compare_versions(&manifest.version, version) != Ordering::Greater {
+                bail!(
+                    "{} {} is already installed",
+                    package.skin.metadata.name,
+                    version
+                );
+            }
+        }
+
+        let target = self
+            .packages_dir
+            .join(format!("{}.{}", manifest.id, skin::PACKAGE_EXTENSION));
+        if target != file {
+            fs::create_dir_all(&self.packages_dir)
+                .map_err(|e| anyhow!("Failed to create {:?}: {}", self.packages_dir, e))?;
+            fs::copy(file, &target).map_err(|e| anyhow!("Failed to copy {:?}: {}", file, e))?;
+        }
+        let skin = skin::load_package(&target)?.skin;
+
+        // An update replaces the installed skin, even under another file name
+        let index = match installed {
+            Some((path, _)) => {
+                if path != target {
+                    let _ = fs::remove_file(&path);
+                }
+                self.packages.remove(&path);
+                self.previews.remove(&path);
+                self.available_skins
+                    .iter()
+                    .position(|skin| skin.path == path)
+            }
+            None => None,
+        };
+        self.packages.insert(target.clone(), manifest);
+        self.previews.remove(&target);
+
+        match index {
+            Some(index) => {
+                self.available_skins[index] = skin;
+                if index == self.active_skin_index {
+                    self.assets = SkinAssets::default();
+                }
+                Ok(index)
+            }
+            None => {
+                self.available_skins.push(skin);
+                Ok(self.available_skins.len() - 1)
+            }
+        }
+    }
+
+    /// Uninstalls the package of a skin, deleting its file. Uninstalling the
+    /// active skin switches back to the built-in skin.
+    pub fn uninstall_package(&mut self, index: usize) -> Result<()> {
+        let path = match self.available_skins.get(index) {
+            Some(skin) if self.packages.contains_key(&skin.path) => skin.path.clone(),
+            _ => bail!("Not an installed skin package"),
+        };
+        fs::remove_file(&path).map_err(|e| anyhow!("Failed to delete {:?}: {}", path, e))?;
+
+        self.packages.remove(&path);
+        self.previews.remove(&path);
+        self.available_skins.remove(index);
+        match self.active_skin_index.cmp(&index) {
+            Ordering::Equal => self.active_skin_index = 0,
+            Ordering::Greater => self.active_skin_index -= 1,
+            Ordering::Less => {}
+        }
+        Ok(())
+    }
+
+    /// Gets the manifest of a skin if it's an installed package.
+    pub fn manifest(&self, index: usize) -> Option<&Manifest> {
+        let skin = self.available_skins.get(index)?;
+        self.packages.get(&skin.path)
+    }
+
+    /// Gets the preview image of a package, loading it on first use.
+    pub fn preview(&mut self, ctx: &egui::Context, index: usize) -> Option<TextureHandle> {
+        let skin = self.available_skins.get(index)?;
+        let preview = self.packages.get(&skin.path)?.preview.as_deref()?;
+        self.previews
+            .entry(skin.path.clone())
+            .or_insert_with(|| {
+                assets::load_texture(ctx, skin, preview)
+                    .map_err(|e| eprintln!("Skin {:?}: {}", skin.metadata.name, e))
+                    .ok()
+            })
+            .clone()
+    }
+
+    /// Imports a classic `.wsz` skin: copies it into the skins directory and
+    /// adds it to the list, replacing a skin of the same name.
+    ///
+    /// # Returns
+    /// The index of the imported skin.
+    pub fn import_wsz(&mut self, file: &Path) -> Result<usize> {
+        // Check the archive before copying it
+        parser::load_wsz(file)?;
+        let file_name = file
+            .file_name()
+            .ok_or_else(|| anyhow!("Not a skin file: {:?}", file))?;
+        let target = self.skins_dir.join(file_name);
+        if target != file {
+            fs::create_dir_all(&self.skins_dir)
+                .map_err(|e| anyhow!("Failed to create {:?}: {}", self.skins_dir, e))?;
+            fs::copy(file, &target).map_err(|e| anyhow!("Failed to copy {:?}: {}", file, e))?;
+        }
+        let (skin, classic) = parser::load_wsz(&target)?;
+        self.set_classic(&skin, Some(classic));
+
+        match self.find_skin_by_name(&skin.metadata.name) {
+            Some(index) if index > 0 => {
+                self.available_skins[index] = skin;
+                Ok(index)
+            }
+            _ => {
+                self.available_skins.push(skin);
+                Ok(self.available_skins.len() - 1)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -202,4 +435,102 @@ mod tests {
         assert_eq!(index, Some(0));
         assert_eq!(manager.find_skin_by_name("Nonexistent"), None);
     }
+
+    #[test]
+    fn test_wsz_discovery_and_import() {
+        let dir = std::env::temp_dir().join(format!("oneamp-skins-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("skins")).unwrap();
+        let data = parser::tests::wsz(&[("main.bmp", &parser::tests::bmp(275, 116, [0; 3]))]);
+        fs::write(dir.join("skins").join("Base.wsz"), &data).unwrap();
+        fs::write(dir.join("skins").join("Broken.wsz"), b"not a zip").unwrap();
+        fs::write(dir.join("Classic.WSZ"), &data).unwrap();
+
+        let mut manager = SkinManager::discover_and_load(&dir.join("skins"));
+        let base = manager.find_skin_by_name("Base").unwrap();
+        manager.set_active_skin(base);
+        assert!(manager.active_classic_skin().is_some());
+        manager.set_active_skin(0);
+        assert!(manager.active_classic_skin().is_none());
+        assert_eq!(manager.find_skin_by_name("Broken"), None);
+
+        let index = manager.import_wsz(&dir.join("Classic.WSZ")).unwrap();
+        assert_eq!(manager.available_skins[index].metadata.name, "Classic");
+        assert!(dir.join("skins").join("Classic.WSZ").is_file());
+        // Importing again replaces the skin
+        assert_eq!(manager.import_wsz(&dir.join("Classic.WSZ")).unwrap(), index);
+        assert!(manager
+            .import_wsz(&dir.join("skins").join("Broken.wsz"))
+            .is_err());
+
+        manager.set_active_skin(index);
+        assert!(manager.reload_active_skin().is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Packs a skin named `name` into `dir/file`.
+    fn package(dir: &Path, file: &str, id: &str, version: &str, name: &str) -> PathBuf {
+        let skin_dir = dir.join(format!("{}-src", file));
+        fs::create_dir_all(&skin_dir).unwrap();
+        let mut skin = Skin::default_builtin();
+        skin.metadata.name = name.to_string();
+        fs::write(skin_dir.join("skin.toml"), toml::to_string(&skin).unwrap()).unwrap();
+        fs::write(
+            skin_dir.join("manifest.toml"),
+            format!("id = \"{}\"\nversion = \"{}\"\n", id, version),
+        )
+        .unwrap();
+        let path = dir.join(file);
+        skin::pack_skin(&skin_dir, &path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_install_update_and_uninstall_packages() {
+        let dir = std::env::temp_dir().join(format!("oneamp-packages-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let installed = dir.join("installed");
+        fs::create_dir_all(&installed).unwrap();
+        package(&installed, "neon.oaskin", "neon", "1.0", "Neon");
+        fs::write(installed.join("broken.oaskin"), b"not a zip").unwrap();
+
+        let mut manager = SkinManager::discover_and_load(&dir.join("skins"));
+        manager.discover_packages(&installed);
+        let neon = manager.find_skin_by_name("Neon").unwrap();
+        assert_eq!(manager.manifest(neon).unwrap().version, "1.0");
+        assert!(manager.manifest(0).is_none());
+
+        let mono = package(&dir, "mono-1.0.oaskin", "mono", "1.0", "Mono");
+        let index = manager.install(&mono).unwrap();
+        assert!(installed.join("mono.oaskin").is_file());
+        assert_eq!(
+            manager.available_skins[index].path,
+            installed.join("mono.oaskin")
+        );
+        let ids: Vec<_> = (0..manager.available_skins.len())
+            .filter_map(|index| Some(manager.manifest(index)?.id.as_str()))
+            .collect();
+        assert_eq!(ids, ["neon", "mono"]);
+
+        // Only newer versions update
+        let error = manager.install(&mono).unwrap_err();
+        assert_eq!(error.to_string(), "Mono 1.0 is already installed");
+        let mono = package(&dir, "mono-1.1.oaskin", "mono", "1.1", "Mono Two");
+        assert_eq!(manager.install(&mono).unwrap(), index);
+        assert_eq!(manager.available_skins[index].metadata.name, "Mono Two");
+        assert_eq!(manager.manifest(index).unwrap().version, "1.1");
+
+        // Uninstalling the active skin goes back to the built-in one
+        manager.set_active_skin(index);
+        assert!(manager.uninstall_package(0).is_err());
+        manager.uninstall_package(index).unwrap();
+        assert!(!installed.join("mono.oaskin").exists());
+        assert_eq!(manager.active_skin_index, 0);
+        assert_eq!(manager.find_skin_by_name("Mono Two"), None);
+
+        manager.set_active_skin(neon);
+        assert!(manager.reload_active_skin().is_ok());
+        assert!(manager.install(&dir.join("skins")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,120 +1,16 @@
-// Skin TOML Parser
-// Responsible for loading and validating skin.toml files.
+// Skin Parser
+// Loads skin folders and `.oaskin` packages through `oneamp_core::skin`,
+// converts skin colors for egui, and imports classic Winamp 2.x `.wsz` skins.
 
-use super::Skin;
+use super::classic::{self, ClassicSkin, Sheet};
+use super::{Colors, Fonts, Metadata, Metrics, Skin};
 use anyhow::{anyhow, Result};
+use oneamp_core::skin::{is_package, load_package, load_skin};
+use std::collections::HashMap;
 use std::fs;
+use std::io::{Read, Seek};
 use std::path::Path;
-
-/// Loads a skin from a skin.toml file.
-///
-/// # Arguments
-/// * `skin_dir` - Path to the directory containing the skin.toml file
-///
-/// # Returns
-/// A `Skin` struct if successful, or an error if the file is missing or invalid.
-pub fn load_skin(skin_dir: &Path) -> Result<Skin> {
-    let skin_file = skin_dir.join("skin.toml");
-
-    if !skin_file.exists() {
-        return Err(anyhow!("skin.toml not found in {:?}", skin_dir));
-    }
-
-    let content =
-        fs::read_to_string(&skin_file).map_err(|e| anyhow!("Failed to read skin.toml: {}", e))?;
-
-    let mut skin: Skin =
-        toml::from_str(&content).map_err(|e| anyhow!("Failed to parse skin.toml: {}", e))?;
-
-    // Set the skin's path for relative asset resolution
-    skin.path = skin_dir.to_path_buf();
-
-    // Validate the skin
-    validate_skin(&skin)?;
-
-    Ok(skin)
-}
-
-/// Validates a skin's configuration.
-/// Checks for required fields and valid color formats.
-fn validate_skin(skin: &Skin) -> Result<()> {
-    // Check metadata
-    if skin.metadata.name.is_empty() {
-        return Err(anyhow!("Skin name cannot be empty"));
-    }
-
-    // Check colors are valid hex strings
-    validate_hex_color(&skin.colors.background)?;
-    validate_hex_color(&skin.colors.text)?;
-    validate_hex_color(&skin.colors.window_fill)?;
-    validate_hex_color(&skin.colors.window_stroke)?;
-    validate_hex_color(&skin.colors.panel_fill)?;
-    validate_hex_color(&skin.colors.widget_bg)?;
-    validate_hex_color(&skin.colors.widget_stroke)?;
-    validate_hex_color(&skin.colors.hovered_widget_bg)?;
-    validate_hex_color(&skin.colors.active_widget_bg)?;
-    validate_hex_color(&skin.colors.inactive_widget_bg)?;
-    validate_hex_color(&skin.colors.accent)?;
-    validate_hex_color(&skin.colors.error)?;
-    validate_hex_color(&skin.colors.warning)?;
-    validate_hex_color(&skin.colors.playlist_current_track)?;
-    validate_hex_color(&skin.colors.playlist_selected_bg)?;
-
-    // Check fonts
-    if skin.fonts.proportional.is_empty() {
-        return Err(anyhow!("Proportional font cannot be empty"));
-    }
-    if skin.fonts.monospace.is_empty() {
-        return Err(anyhow!("Monospace font cannot be empty"));
-    }
-
-    // Check metrics are positive
-    if skin.metrics.window_rounding < 0.0 {
-        return Err(anyhow!("window_rounding must be non-negative"));
-    }
-    if skin.metrics.widget_rounding < 0.0 {
-        return Err(anyhow!("widget_rounding must be non-negative"));
-    }
-    if skin.metrics.scrollbar_width <= 0.0 {
-        return Err(anyhow!("scrollbar_width must be positive"));
-    }
-    if skin.metrics.window_padding < 0.0 {
-        return Err(anyhow!("window_padding must be non-negative"));
-    }
-    if skin.metrics.body_text_size <= 0.0 {
-        return Err(anyhow!("body_text_size must be positive"));
-    }
-    if skin.metrics.heading_text_size <= 0.0 {
-        return Err(anyhow!("heading_text_size must be positive"));
-    }
-    if skin.metrics.timer_text_size <= 0.0 {
-        return Err(anyhow!("timer_text_size must be positive"));
-    }
-
-    Ok(())
-}
-
-/// Validates that a string is a valid hex color.
-/// Accepts formats: #RGB, #RRGGBB, #RRGGBBAA
-fn validate_hex_color(color: &str) -> Result<()> {
-    if !color.starts_with('#') {
-        return Err(anyhow!("Color must start with '#': {}", color));
-    }
-
-    let hex_part = &color[1..];
-    if hex_part.len() != 3 && hex_part.len() != 6 && hex_part.len() != 8 {
-        return Err(anyhow!(
-            "Color must be #RGB, #RRGGBB, or #RRGGBBAA: {}",
-            color
-        ));
-    }
-
-    if !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err(anyhow!("Color contains invalid hex digits: {}", color));
-    }
-
-    Ok(())
-}
+use std::sync::Arc;
 
 /// Converts a hex color string to an egui::Color32.
 /// Accepts formats: #RGB, #RRGGBB, #RRGGBBAA
@@ -154,23 +50,142 @@ pub fn hex_to_color32(hex: &str) -> Result<egui::Color32> {
     Ok(egui::Color32::from_rgba_unmultiplied(r, g, b, a))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Whether a path is a classic Winamp skin archive
+pub fn is_wsz(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("wsz"))
+}
 
-    #[test]
-    fn test_validate_hex_color_valid() {
-        assert!(validate_hex_color("#fff").is_ok());
-        assert!(validate_hex_color("#ffffff").is_ok());
-        assert!(validate_hex_color("#ffffff80").is_ok());
+/// Loads a skin directory, a `.oaskin` package or a classic `.wsz` skin,
+/// with the sprite sheets of classic skins.
+pub fn load_any(path: &Path) -> Result<(Skin, Option<Arc<ClassicSkin>>)> {
+    if is_wsz(path) {
+        let (skin, classic) = load_wsz(path)?;
+        Ok((skin, Some(classic)))
+    } else if is_package(path) {
+        Ok((load_package(path)?.skin, None))
+    } else {
+        Ok((load_skin(path)?, None))
     }
+}
 
-    #[test]
-    fn test_validate_hex_color_invalid() {
-        assert!(validate_hex_color("ffffff").is_err()); // Missing #
-        assert!(validate_hex_color("#ff").is_err()); // Too short
-        assert!(validate_hex_color("#gggggg").is_err()); // Invalid hex
+/// Imports a classic Winamp 2.x skin from a `.wsz` file.
+///
+/// The skin is named after the file. See `read_wsz` for the archive contents.
+pub fn load_wsz(path: &Path) -> Result<(Skin, Arc<ClassicSkin>)> {
+    let file = fs::File::open(path).map_err(|e| anyhow!("Failed to open {:?}: {}", path, e))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (mut skin, classic) = read_wsz(file, &name).map_err(|e| anyhow!("{:?}: {}", path, e))?;
+    skin.path = path.to_path_buf();
+    Ok((skin, Arc::new(classic)))
+}
+
+/// Reads a classic skin from a zip archive.
+///
+/// Files are found by name, ignoring case and any folder inside the archive.
+/// `main.bmp` is required; other missing bitmaps aren't drawn and missing
+/// `pledit.txt` or `viscolor.txt` entries keep Winamp's default colors. The
+/// skin's color palette, used for the rest of the UI, comes from the playlist
+/// colors.
+pub fn read_wsz<R: Read + Seek>(reader: R, name: &str) -> Result<(Skin, ClassicSkin)> {
+    let mut archive =
+        zip::ZipArchive::new(reader).map_err(|e| anyhow!("Not a skin archive: {}", e))?;
+
+    // Lowercase file names to entry indices; the first of duplicates wins
+    let mut entries = HashMap::new();
+    for index in 0..archive.len() {
+        let Ok(entry) = archive.by_index(index) else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let file_name = entry.name().rsplit(['/', '\\']).next().unwrap_or_default();
+        entries.entry(file_name.to_lowercase()).or_insert(index);
     }
+    let mut read = |file_name: &str| -> Result<Option<Vec<u8>>> {
+        let Some(&index) = entries.get(file_name) else {
+            return Ok(None);
+        };
+        let mut data = Vec::new();
+        archive
+            .by_index(index)?
+            .read_to_end(&mut data)
+            .map_err(|e| anyhow!("Failed to read {}: {}", file_name, e))?;
+        Ok(Some(data))
+    };
+
+    let text =
+        |data: Option<Vec<u8>>| String::from_utf8_lossy(&data.unwrap_or_default()).into_owned();
+    let playlist = classic::parse_pledit(&text(read("pledit.txt")?));
+    let vis_colors = classic::parse_viscolor(&text(read("viscolor.txt")?));
+    let mut classic = ClassicSkin::new(playlist, vis_colors);
+    for sheet in Sheet::ALL {
+        let Some(data) = read(sheet.file_name())? else {
+            continue;
+        };
+        let image = image::load_from_memory_with_format(&data, image::ImageFormat::Bmp)
+            .map_err(|e| anyhow!("Failed to decode {}: {}", sheet.file_name(), e))?
+            .to_rgba8();
+        let size = [image.width() as usize, image.height() as usize];
+        classic.set_sheet(
+            sheet,
+            egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+        );
+    }
+    if classic.sheet(Sheet::Main).is_none() {
+        return Err(anyhow!("main.bmp not found"));
+    }
+
+    let pl = &classic.playlist;
+    let hex =
+        |color: egui::Color32| format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b());
+    let [r, g, b, _] = pl.normal_bg.to_array();
+    let defaults = Colors::default();
+    let colors = Colors {
+        dark_mode: u32::from(r) + u32::from(g) + u32::from(b) < 3 * 128,
+        background: hex(pl.normal_bg),
+        text: hex(pl.normal),
+        window_fill: hex(pl.normal_bg),
+        window_stroke: hex(pl.normal),
+        panel_fill: hex(pl.normal_bg),
+        widget_bg: hex(pl.selected_bg),
+        widget_stroke: hex(pl.normal),
+        hovered_widget_bg: hex(pl.selected_bg),
+        active_widget_bg: hex(pl.selected_bg),
+        inactive_widget_bg: hex(pl.normal_bg),
+        accent: hex(pl.current),
+        playlist_current_track: hex(pl.current),
+        playlist_selected_bg: hex(pl.selected_bg),
+        ..defaults
+    };
+
+    let skin = Skin {
+        metadata: Metadata {
+            name: name.to_string(),
+            author: "Unknown".to_string(),
+            version: "2.x".to_string(),
+            description: "Classic Winamp skin".to_string(),
+        },
+        colors,
+        fonts: Fonts::default(),
+        metrics: Metrics::default(),
+        layout: Default::default(),
+        images: Default::default(),
+        path: Default::default(),
+    };
+    Ok((skin, classic))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
 
     #[test]
     fn test_hex_to_color32_rrggbb() {
@@ -194,4 +209,58 @@ mod tests {
         assert_eq!(color.g(), 255);
         assert_eq!(color.b(), 255);
     }
+
+    /// A `.wsz` archive of solid-color bitmaps and text files
+    pub(crate) fn wsz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut zip, data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    pub(crate) fn bmp(width: u32, height: u32, rgb: [u8; 3]) -> Vec<u8> {
+        let image = image::RgbImage::from_pixel(width, height, image::Rgb(rgb));
+        let mut data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut data, image::ImageFormat::Bmp).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_read_wsz() {
+        let main = bmp(275, 116, [10, 20, 30]);
+        let numbers = bmp(99, 13, [1, 2, 3]);
+        let pledit = b"[Text]\nNormal=#00FF00\nNormalBG=#000000\nCurrent=#FFFFFF\n";
+        let data = wsz(&[
+            ("Base/", b""),
+            ("Base/MAIN.BMP", &main),
+            ("Base/Numbers.bmp", &numbers),
+            ("Base/PlEdit.txt", pledit),
+        ]);
+        let (skin, classic) = read_wsz(std::io::Cursor::new(data), "Base").unwrap();
+        assert_eq!(skin.metadata.name, "Base");
+        assert!(oneamp_core::skin::validate_skin(&skin, |_| false).is_empty());
+        assert_eq!(skin.colors.text, "#00ff00");
+        assert!(skin.colors.dark_mode);
+
+        assert_eq!(classic.sheet(Sheet::Main).unwrap().size, [275, 116]);
+        assert_eq!(
+            classic.pixel(Sheet::Numbers, 98, 12),
+            Some(egui::Color32::from_rgb(1, 2, 3))
+        );
+        assert_eq!(classic.pixel(Sheet::Numbers, 99, 0), None);
+        assert!(classic.sheet(Sheet::CButtons).is_none());
+        assert_eq!(classic.playlist.current, egui::Color32::WHITE);
+    }
+
+    #[test]
+    fn test_read_wsz_errors() {
+        let no_main = wsz(&[("pledit.txt", b"[Text]")]);
+        assert!(read_wsz(std::io::Cursor::new(no_main), "x").is_err());
+        let broken = wsz(&[("main.bmp", b"not a bitmap")]);
+        assert!(read_wsz(std::io::Cursor::new(broken), "x").is_err());
+        assert!(read_wsz(std::io::Cursor::new(b"not a zip".to_vec()), "x").is_err());
+    }
 }
@@ -1,8 +1,12 @@
 // Skin Selection and Management UI
-// Provides UI components for selecting and managing skins.
+// Provides UI components for selecting and managing skins, including the
+// preview gallery of the skin selector.
 
-use super::SkinManager;
-use egui::{RichText, Ui};
+use super::{Skin, SkinManager};
+use anyhow::Result;
+use egui::{
+    Align2, FontId, Pos2, Rect, Response, RichText, Sense, Stroke, TextureHandle, Ui, Vec2,
+};
 
 /// Renders a skin selector menu in the UI.
 ///
@@ -111,77 +115,141 @@ pub fn skin_info_panel(ui: &mut Ui, skin_manager: &SkinManager) {
     });
 }
 
-/// Renders a skin selector dialog.
+/// Width of a skin card in the selector gallery.
+const CARD_WIDTH: f32 = 160.0;
+
+/// Size of the preview at the top of a skin card.
+const PREVIEW_SIZE: Vec2 = Vec2::new(CARD_WIDTH, 100.0);
+
+/// Renders a skin selector dialog: a gallery of skin cards with their
+/// preview, and an uninstall button for installed packages.
 ///
 /// # Arguments
 /// * `ui` - The egui UI context
 /// * `skin_manager` - The skin manager instance
 ///
 /// # Returns
-/// `true` if a skin was selected, `false` otherwise
-pub fn skin_selector_dialog(ui: &mut Ui, skin_manager: &mut SkinManager) -> bool {
-    let mut skin_changed = false;
-
-    // Collect skin data before rendering to avoid borrow checker issues
-    let skins_data: Vec<_> = skin_manager
-        .available_skins
-        .iter()
-        .enumerate()
-        .map(|(index, skin)| {
-            (
-                index,
-                skin.metadata.name.clone(),
-                skin.metadata.description.clone(),
-                skin.metadata.author.clone(),
-                index == skin_manager.active_skin_index,
-            )
-        })
-        .collect();
+/// `true` if the active skin changed, `false` otherwise, or an error if a
+/// package couldn't be uninstalled
+pub fn skin_selector_dialog(ui: &mut Ui, skin_manager: &mut SkinManager) -> Result<bool> {
+    let mut selected = None;
+    let mut uninstall = None;
 
-    ui.label("Available Skins:");
+    ui.label(
+        RichText::new("Drop .oaskin or .wsz files on the window to install them")
+            .small()
+            .color(egui::Color32::GRAY),
+    );
     ui.separator();
 
-    for (index, name, description, author, is_active) in skins_data {
-        ui.group(|ui| {
-            ui.vertical(|ui| {
-                let label = if is_active {
-                    RichText::new(&name)
-                        .strong()
-                        .color(egui::Color32::from_rgb(0, 212, 255))
-                } else {
-                    RichText::new(&name).strong()
-                };
-
-                ui.label(label);
-                ui.label(
-                    RichText::new(&description)
-                        .small()
-                        .color(egui::Color32::GRAY),
-                );
-                ui.label(
-                    RichText::new(format!("by {}", author))
-                        .small()
-                        .color(egui::Color32::DARK_GRAY),
-                );
-
-                ui.horizontal(|ui| {
-                    if !is_active && ui.button("Select").clicked() {
-                        if skin_manager.set_active_skin(index) {
-                            skin_changed = true;
-                        }
-                    }
-
-                    if is_active {
-                        ui.label(
-                            RichText::new("✓ Active").color(egui::Color32::from_rgb(0, 212, 255)),
-                        );
-                    }
-                });
+    egui::ScrollArea::vertical()
+        .max_height(420.0)
+        .show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for index in 0..skin_manager.available_skins.len() {
+                    let preview = skin_manager.preview(ui.ctx(), index);
+                    let skin = &skin_manager.available_skins[index];
+                    let manifest = skin_manager.manifest(index);
+                    let is_active = index == skin_manager.active_skin_index;
+
+                    ui.group(|ui| {
+                        ui.set_width(CARD_WIDTH);
+                        ui.vertical(|ui| {
+                            let response = skin_preview(ui, skin, preview.as_ref())
+                                .on_hover_text(&skin.metadata.description);
+                            if response.clicked() && !is_active {
+                                selected = Some(index);
+                            }
+
+                            let label = RichText::new(&skin.metadata.name).strong();
+                            ui.label(if is_active {
+                                label.color(egui::Color32::from_rgb(0, 212, 255))
+                            } else {
+                                label
+                            });
+                            let version = manifest.map_or(&skin.metadata.version, |m| &m.version);
+                            ui.label(
+                                RichText::new(format!("{} · by {}", version, skin.metadata.author))
+                                    .small()
+                                    .color(egui::Color32::DARK_GRAY),
+                            );
+
+                            ui.horizontal(|ui| {
+                                if is_active {
+                                    ui.label(
+                                        RichText::new("✓ Active")
+                                            .color(egui::Color32::from_rgb(0, 212, 255)),
+                                    );
+                                } else if ui.button("Select").clicked() {
+                                    selected = Some(index);
+                                }
+                                if manifest.is_some()
+                                    && ui
+                                        .small_button("🗑")
+                                        .on_hover_text("Uninstall this skin package")
+                                        .clicked()
+                                {
+                                    uninstall = Some(index);
+                                }
+                            });
+                        });
+                    });
+                }
             });
         });
+
+    if let Some(index) = uninstall {
+        let was_active = index == skin_manager.active_skin_index;
+        skin_manager.uninstall_package(index)?;
+        return Ok(was_active);
     }
+    Ok(selected.is_some_and(|index| skin_manager.set_active_skin(index)))
+}
 
-    skin_changed
+/// Paints a skin's preview image, or a sketch of its colors when it has none.
+fn skin_preview(ui: &mut Ui, skin: &Skin, preview: Option<&TextureHandle>) -> Response {
+    let (rect, response) = ui.allocate_exact_size(PREVIEW_SIZE, Sense::click());
+    let painter = ui.painter_at(rect);
+    let colors = &skin.colors;
+    let color = |hex: &str, fallback| parse_hex_color(hex).unwrap_or(fallback);
+    let background = color(&colors.background, egui::Color32::BLACK);
+    painter.rect_filled(rect, 2.0, background);
+
+    if let Some(texture) = preview {
+        let size = texture.size_vec2();
+        let scale = (rect.width() / size.x).min(rect.height() / size.y);
+        let image = Rect::from_center_size(rect.center(), size * scale);
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        painter.image(texture.id(), image, uv, egui::Color32::WHITE);
+    } else {
+        // A window with a title, an accent bar and a selected playlist row
+        let window = rect.shrink(10.0);
+        painter.rect_filled(window, 2.0, color(&colors.window_fill, background));
+        let text = color(&colors.text, egui::Color32::WHITE);
+        painter.text(
+            window.left_top() + Vec2::new(6.0, 6.0),
+            Align2::LEFT_TOP,
+            "0:42",
+            FontId::monospace(18.0),
+            text,
+        );
+        let accent = color(&colors.accent, text);
+        let bar = Rect::from_min_size(
+            window.left_top() + Vec2::new(6.0, 34.0),
+            Vec2::new(window.width() * 0.6, 4.0),
+        );
+        painter.rect_filled(bar, 1.0, accent);
+        let row = Rect::from_min_size(
+            window.left_top() + Vec2::new(0.0, 48.0),
+            Vec2::new(window.width(), 14.0),
+        );
+        painter.rect_filled(row, 0.0, color(&colors.playlist_selected_bg, accent));
+    }
+
+    if response.hovered() {
+        painter.rect_stroke(rect, 2.0, Stroke::new(1.0, egui::Color32::GRAY));
+    }
+    response
 }
 
 /// Helper function to parse hex color strings.
@@ -0,0 +1,114 @@
+// Skin Watcher
+// Notices when the files of the active skin change on disk, so skin authors
+// see their edits while the app runs. Polls modification times, which works
+// the same for skin folders and `.wsz` files on every platform.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the skin files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Size and modification time of every file of a skin, sorted by path
+type Snapshot = Vec<(PathBuf, u64, Option<SystemTime>)>;
+
+/// Watches the files of one skin at a time.
+pub struct SkinWatcher {
+    path: PathBuf,
+    snapshot: Snapshot,
+    last_poll: Instant,
+}
+
+impl Default for SkinWatcher {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            snapshot: Snapshot::new(),
+            last_poll: Instant::now(),
+        }
+    }
+}
+
+impl SkinWatcher {
+    /// Whether the files of the skin at `path` changed since the last check,
+    /// checking at most every `POLL_INTERVAL`.
+    pub fn poll(&mut self, path: &Path) -> bool {
+        if path == self.path && self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        self.changed(path)
+    }
+
+    /// Whether the files of the skin at `path` changed since the last check.
+    /// Switching to another skin isn't a change. Built-in skins, with an
+    /// empty path, never change.
+    pub fn changed(&mut self, path: &Path) -> bool {
+        let snapshot = snapshot(path);
+        if path != self.path {
+            self.path = path.to_path_buf();
+            self.snapshot = snapshot;
+            return false;
+        }
+        if snapshot == self.snapshot {
+            return false;
+        }
+        self.snapshot = snapshot;
+        true
+    }
+}
+
+/// Files under `path`, or `path` itself if it's a file.
+fn snapshot(path: &Path) -> Snapshot {
+    let mut files = Snapshot::new();
+    if !path.as_os_str().is_empty() {
+        add_files(path, &mut files);
+    }
+    files.sort();
+    files
+}
+
+fn add_files(path: &Path, files: &mut Snapshot) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            add_files(&entry.path(), files);
+        }
+    } else {
+        files.push((path.to_path_buf(), metadata.len(), metadata.modified().ok()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skin_watcher() {
+        let dir = std::env::temp_dir().join(format!("oneamp-skin-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("images")).unwrap();
+        fs::write(dir.join("skin.toml"), "a").unwrap();
+
+        let mut watcher = SkinWatcher::default();
+        assert!(!watcher.changed(Path::new("")));
+        assert!(!watcher.changed(&dir));
+        assert!(!watcher.changed(&dir));
+
+        fs::write(dir.join("skin.toml"), "ab").unwrap();
+        assert!(watcher.changed(&dir));
+        assert!(!watcher.changed(&dir));
+
+        fs::write(dir.join("images").join("play.png"), "png").unwrap();
+        assert!(watcher.changed(&dir));
+        fs::remove_file(dir.join("images").join("play.png")).unwrap();
+        assert!(watcher.changed(&dir));
+
+        // Back to the built-in skin
+        assert!(!watcher.changed(Path::new("")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
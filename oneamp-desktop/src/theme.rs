@@ -1,17 +1,22 @@
+// Theme
+// The active skin's colors, text sizes and layout resolved for drawing:
+// hex colors parsed and optional skin colors replaced by their fallbacks.
+// Widgets read the theme and the adaptive theme tints its accent colors,
+// but the skin is the only source of its values; old `theme.toml` files are
+// converted into skins by `oneamp_core::skin::migrate_theme`.
+
+use crate::skins::{parser, Skin};
 use eframe::egui;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 
-/// Theme configuration for OneAmp
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Colors, text sizes and layout of the active skin
+#[derive(Debug, Clone)]
 pub struct Theme {
-    pub name: String,
     pub colors: ColorScheme,
     pub fonts: FontConfig,
     pub layout: LayoutConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct ColorScheme {
     // Main window colors
     pub window_bg: [u8; 3],
@@ -41,9 +46,16 @@ pub struct ColorScheme {
     // Equalizer
     pub eq_slider: [u8; 3],
     pub eq_fill: [u8; 3],
+
+    // Spectrum bars from quiet to loud
+    pub visualizer_gradient: Vec<[u8; 3]>,
+
+    // Levels above normal
+    pub warning: [u8; 3],
+    pub error: [u8; 3],
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct FontConfig {
     pub timer_size: f32,
     pub track_info_size: f32,
@@ -51,7 +63,7 @@ pub struct FontConfig {
     pub button_size: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct LayoutConfig {
     pub window_min_width: f32,
     pub window_min_height: f32,
@@ -62,129 +74,89 @@ pub struct LayoutConfig {
 }
 
 impl Default for Theme {
+    /// The theme of the built-in skin
     fn default() -> Self {
-        Self::winamp_modern()
+        Self::from_skin(&Skin::default_builtin())
     }
 }
 
 impl Theme {
-    /// Winamp Modern inspired theme (blue/metallic)
-    pub fn winamp_modern() -> Self {
-        Theme {
-            name: "Winamp Modern".to_string(),
-            colors: ColorScheme {
-                window_bg: [40, 45, 55],
-                panel_bg: [30, 35, 45],
-                border: [60, 65, 75],
-
-                display_bg: [15, 20, 35],
-                display_text: [100, 180, 255],
-                display_accent: [150, 220, 255],
-
-                button_normal: [70, 75, 85],
-                button_hovered: [90, 95, 105],
-                button_active: [110, 115, 125],
-
-                progress_bg: [50, 55, 65],
-                progress_fill: [100, 180, 255],
-
-                playlist_bg: [25, 30, 40],
-                playlist_text: [200, 200, 200],
-                playlist_selected: [60, 100, 150],
-                playlist_playing: [100, 180, 255],
-
-                eq_slider: [70, 75, 85],
-                eq_fill: [100, 180, 255],
-            },
-            fonts: FontConfig {
-                timer_size: 48.0,
-                track_info_size: 20.0,
-                playlist_size: 14.0,
-                button_size: 14.0,
-            },
-            layout: LayoutConfig {
-                window_min_width: 700.0,
-                window_min_height: 600.0,
-                player_height: 200.0,
-                equalizer_height: 220.0,
-                spacing: 16.0,
-                padding: 16.0,
-            },
-        }
-    }
+    /// Resolve the colors, text sizes and layout of a skin
+    pub fn from_skin(skin: &Skin) -> Self {
+        // Skins are validated when loaded, gray only shows up for built-in
+        // skins with a typo
+        let parse_color = |hex: &str| -> [u8; 3] {
+            parser::hex_to_color32(hex)
+                .map(|c| [c.r(), c.g(), c.b()])
+                .unwrap_or([128, 128, 128])
+        };
+        let colors = &skin.colors;
+        let metrics = &skin.metrics;
+        let layout = &skin.layout;
 
-    /// Dark theme (original OneAmp style)
-    pub fn dark() -> Self {
         Theme {
-            name: "Dark".to_string(),
             colors: ColorScheme {
-                window_bg: [30, 30, 35],
-                panel_bg: [25, 25, 30],
-                border: [50, 50, 60],
+                // Main window colors
+                window_bg: parse_color(&colors.window_fill),
+                panel_bg: parse_color(&colors.background),
+                border: parse_color(&colors.window_stroke),
 
-                display_bg: [20, 20, 25],
-                display_text: [220, 220, 220],
-                display_accent: [0, 150, 200],
+                // Display colors
+                display_bg: parse_color(colors.display_bg()),
+                display_text: parse_color(colors.display_text()),
+                display_accent: parse_color(colors.display_accent()),
 
-                button_normal: [50, 50, 60],
-                button_hovered: [70, 70, 80],
-                button_active: [90, 90, 100],
+                // Button colors
+                button_normal: parse_color(&colors.widget_bg),
+                button_hovered: parse_color(&colors.hovered_widget_bg),
+                button_active: parse_color(&colors.active_widget_bg),
 
-                progress_bg: [40, 40, 50],
-                progress_fill: [0, 150, 200],
+                // Progress bar
+                progress_bg: parse_color(colors.progress_bg()),
+                progress_fill: parse_color(colors.progress_fill()),
 
-                playlist_bg: [20, 20, 25],
-                playlist_text: [200, 200, 200],
-                playlist_selected: [50, 50, 70],
-                playlist_playing: [0, 150, 200],
+                // Playlist
+                playlist_bg: parse_color(colors.playlist_bg()),
+                playlist_text: parse_color(colors.playlist_text()),
+                playlist_selected: parse_color(&colors.playlist_selected_bg),
+                playlist_playing: parse_color(&colors.playlist_current_track),
 
-                eq_slider: [50, 50, 60],
-                eq_fill: [0, 150, 200],
+                // Equalizer
+                eq_slider: parse_color(colors.eq_slider()),
+                eq_fill: parse_color(colors.eq_fill()),
+
+                visualizer_gradient: colors
+                    .visualizer_gradient()
+                    .into_iter()
+                    .map(parse_color)
+                    .collect(),
+                warning: parse_color(&colors.warning),
+                error: parse_color(&colors.error),
             },
             fonts: FontConfig {
-                timer_size: 44.0,
-                track_info_size: 20.0,
-                playlist_size: 14.0,
-                button_size: 14.0,
+                timer_size: metrics.timer_text_size,
+                track_info_size: metrics.track_info_text_size,
+                playlist_size: metrics.playlist_text_size,
+                button_size: metrics.button_text_size,
             },
             layout: LayoutConfig {
-                window_min_width: 700.0,
-                window_min_height: 600.0,
-                player_height: 200.0,
-                equalizer_height: 220.0,
-                spacing: 16.0,
-                padding: 16.0,
+                window_min_width: layout.window_min_size[0],
+                window_min_height: layout.window_min_size[1],
+                player_height: layout.player_height,
+                equalizer_height: layout.equalizer_height,
+                spacing: layout.item_spacing,
+                padding: metrics.window_padding,
             },
         }
     }
 
-    /// Load theme from file
-    pub fn load(path: &PathBuf) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let theme: Theme = toml::from_str(&content)?;
-        Ok(theme)
-    }
-
-    /// Save theme to file
-    pub fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
-        let content = toml::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
-        Ok(())
-    }
-
-    /// Apply theme to egui context
+    /// Apply what the skin's egui style doesn't cover: button fills, the
+    /// (possibly tinted) selection color and widget spacing
     pub fn apply_to_egui(&self, ctx: &egui::Context) {
         let mut style = (*ctx.style()).clone();
 
-        // Dark mode
-        style.visuals.dark_mode = true;
-
         // Window colors
         style.visuals.window_fill = Self::color32(&self.colors.window_bg);
-        style.visuals.panel_fill = Self::color32(&self.colors.panel_bg);
-
-        // Text color
-        style.visuals.override_text_color = Some(Self::color32(&self.colors.display_text));
 
         // Button colors
         style.visuals.widgets.inactive.weak_bg_fill = Self::color32(&self.colors.button_normal);
@@ -206,65 +178,36 @@ impl Theme {
         egui::Color32::from_rgb(rgb[0], rgb[1], rgb[2])
     }
 
-    /// Create a theme from a skin configuration
-    /// This integrates skin colors into the theme structure
-    pub fn from_skin(skin: &crate::skins::Skin) -> Self {
-        use crate::skins::parser;
-
-        // Parse hex colors from skin
-        let parse_color = |hex: &str| -> [u8; 3] {
-            parser::hex_to_color32(hex)
-                .map(|c| [c.r(), c.g(), c.b()])
-                .unwrap_or([128, 128, 128]) // Fallback to gray if parsing fails
+    /// Color of a spectrum bar at `level` (0.0 - 1.0), blended between the
+    /// evenly spread stops of the visualizer gradient
+    pub fn visualizer_color(&self, level: f32) -> egui::Color32 {
+        let stops = &self.colors.visualizer_gradient;
+        let Some(last) = stops.len().checked_sub(1) else {
+            return Self::color32(&self.colors.display_accent);
         };
+        let position = level.clamp(0.0, 1.0) * last as f32;
+        let index = (position.floor() as usize).min(last.saturating_sub(1));
+        let (from, to) = (stops[index], stops[(index + 1).min(last)]);
+        let t = position - index as f32;
+        let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+        egui::Color32::from_rgb(
+            mix(from[0], to[0]),
+            mix(from[1], to[1]),
+            mix(from[2], to[2]),
+        )
+    }
 
-        Theme {
-            name: skin.metadata.name.clone(),
-            colors: ColorScheme {
-                // Main window colors
-                window_bg: parse_color(&skin.colors.background),
-                panel_bg: parse_color(&skin.colors.background),
-                border: parse_color(&skin.colors.window_stroke),
-
-                // Display colors
-                display_bg: parse_color(&skin.colors.background),
-                display_text: parse_color(&skin.colors.text),
-                display_accent: parse_color(&skin.colors.accent),
-
-                // Button colors
-                button_normal: parse_color(&skin.colors.widget_bg),
-                button_hovered: parse_color(&skin.colors.hovered_widget_bg),
-                button_active: parse_color(&skin.colors.active_widget_bg),
-
-                // Progress bar
-                progress_bg: parse_color(&skin.colors.background),
-                progress_fill: parse_color(&skin.colors.accent),
-
-                // Playlist
-                playlist_bg: parse_color(&skin.colors.background),
-                playlist_text: parse_color(&skin.colors.text),
-                playlist_selected: parse_color(&skin.colors.accent),
-                playlist_playing: parse_color(&skin.colors.accent),
-
-                // Equalizer
-                eq_slider: parse_color(&skin.colors.widget_bg),
-                eq_fill: parse_color(&skin.colors.accent),
-            },
-            fonts: FontConfig {
-                timer_size: (skin.metrics.timer_text_size * 1.5).max(40.0),
-                track_info_size: (skin.metrics.body_text_size * 1.4).max(18.0),
-                playlist_size: (skin.metrics.body_text_size * 1.1).max(13.0),
-                button_size: (skin.metrics.body_text_size * 1.2).max(13.0),
-            },
-            layout: LayoutConfig {
-                window_min_width: 700.0,
-                window_min_height: 600.0,
-                player_height: 200.0,
-                equalizer_height: 220.0,
-                spacing: 16.0,
-                padding: (skin.metrics.window_padding * 1.5).max(14.0),
-            },
-        }
+    /// Fill color of an equalizer band at `gain` dB: the skin's EQ fill up
+    /// to 0 dB, then its warning and error colors for strong boosts
+    pub fn eq_level_color(&self, gain: f32) -> egui::Color32 {
+        let rgb = if gain > 6.0 {
+            &self.colors.error
+        } else if gain > 0.0 {
+            &self.colors.warning
+        } else {
+            &self.colors.eq_fill
+        };
+        Self::color32(rgb)
     }
 }
 
@@ -275,33 +218,29 @@ mod tests {
     #[test]
     fn test_default_theme() {
         let theme = Theme::default();
-        assert_eq!(theme.name, "Winamp Modern");
+        assert_eq!(theme.colors.panel_bg, [10, 10, 10]);
     }
 
     #[test]
-    fn test_winamp_modern_theme() {
-        let theme = Theme::winamp_modern();
-        assert_eq!(theme.name, "Winamp Modern");
-        assert_eq!(theme.colors.window_bg, [40, 45, 55]);
-        assert_eq!(theme.fonts.timer_size, 32.0);
-        assert_eq!(theme.layout.window_min_width, 600.0);
-    }
-
-    #[test]
-    fn test_dark_theme() {
-        let theme = Theme::dark();
-        assert_eq!(theme.name, "Dark");
-        assert_eq!(theme.colors.window_bg, [30, 30, 35]);
-    }
-
-    #[test]
-    fn test_theme_serialization() {
-        let theme = Theme::winamp_modern();
-        let toml = toml::to_string(&theme).unwrap();
-        let deserialized: Theme = toml::from_str(&toml).unwrap();
-        assert_eq!(theme.name, deserialized.name);
-        assert_eq!(theme.colors.window_bg, deserialized.colors.window_bg);
-        assert_eq!(theme.fonts.timer_size, deserialized.fonts.timer_size);
+    fn test_theme_from_skin() {
+        let mut skin = Skin::default_builtin();
+        skin.colors.playlist_current_track = "#112233".to_string();
+        skin.colors.playlist_selected_bg = "#445566".to_string();
+        skin.colors.eq_slider = Some("#010203".to_string());
+        skin.metrics.playlist_text_size = 15.0;
+        skin.layout.window_min_size = [640.0, 480.0];
+
+        let theme = Theme::from_skin(&skin);
+        assert_eq!(theme.colors.playlist_playing, [0x11, 0x22, 0x33]);
+        assert_eq!(theme.colors.playlist_selected, [0x44, 0x55, 0x66]);
+        assert_eq!(theme.colors.eq_slider, [1, 2, 3]);
+        assert_eq!(theme.fonts.playlist_size, 15.0);
+        assert_eq!(theme.layout.window_min_width, 640.0);
+
+        // Unset skin colors fall back to the main ones
+        assert_eq!(theme.colors.display_accent, [0x00, 0xd4, 0xff]);
+        assert_eq!(theme.colors.eq_fill, theme.colors.display_accent);
+        assert_eq!(theme.colors.display_bg, [0x0f, 0x0f, 0x0f]);
     }
 
     #[test]
@@ -314,35 +253,53 @@ mod tests {
     }
 
     #[test]
-    fn test_theme_save_load() {
-        use std::fs;
-        use std::path::PathBuf;
-
-        let theme = Theme::winamp_modern();
-        let temp_path = PathBuf::from("/tmp/test_theme.toml");
-
-        // Save
-        theme.save(&temp_path).unwrap();
-
-        // Load
-        let loaded = Theme::load(&temp_path).unwrap();
-        assert_eq!(theme.name, loaded.name);
-
-        // Cleanup
-        let _ = fs::remove_file(temp_path);
+    fn test_visualizer_color() {
+        let mut theme = Theme::default();
+        theme.colors.visualizer_gradient = vec![[0, 0, 0], [100, 200, 0], [200, 0, 0]];
+        assert_eq!(
+            theme.visualizer_color(0.0),
+            egui::Color32::from_rgb(0, 0, 0)
+        );
+        assert_eq!(
+            theme.visualizer_color(0.25),
+            egui::Color32::from_rgb(50, 100, 0)
+        );
+        assert_eq!(
+            theme.visualizer_color(0.5),
+            egui::Color32::from_rgb(100, 200, 0)
+        );
+        assert_eq!(
+            theme.visualizer_color(2.0),
+            egui::Color32::from_rgb(200, 0, 0)
+        );
+
+        theme.colors.visualizer_gradient = vec![[9, 9, 9]];
+        assert_eq!(
+            theme.visualizer_color(0.7),
+            egui::Color32::from_rgb(9, 9, 9)
+        );
+        theme.colors.visualizer_gradient.clear();
+        assert_eq!(
+            theme.visualizer_color(0.7),
+            Theme::color32(&theme.colors.display_accent)
+        );
     }
 
     #[test]
-    fn test_all_themes_have_valid_colors() {
-        for theme in [Theme::winamp_modern(), Theme::dark()] {
-            // All RGB values should be 0-255
-            for &val in &theme.colors.window_bg {
-                assert!(val <= 255);
-            }
-            for &val in &theme.colors.display_text {
-                assert!(val <= 255);
-            }
-        }
+    fn test_eq_level_color() {
+        let theme = Theme::default();
+        assert_eq!(
+            theme.eq_level_color(-3.0),
+            Theme::color32(&theme.colors.eq_fill)
+        );
+        assert_eq!(
+            theme.eq_level_color(3.0),
+            Theme::color32(&theme.colors.warning)
+        );
+        assert_eq!(
+            theme.eq_level_color(9.0),
+            Theme::color32(&theme.colors.error)
+        );
     }
 
     #[test]
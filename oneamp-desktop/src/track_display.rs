@@ -154,6 +154,7 @@ mod tests {
             channels: Some(2),
             codec: Some("MP3".to_string()),
             bitrate: Some(320000),
+            ..Default::default()
         };
 
         assert_eq!(TrackDisplay::get_title(&track), "Test Artist - Test Song");
@@ -171,6 +172,7 @@ mod tests {
             channels: Some(2),
             codec: Some("MP3".to_string()),
             bitrate: Some(320000),
+            ..Default::default()
         };
 
         let info = TrackDisplay::get_technical_info(&track);
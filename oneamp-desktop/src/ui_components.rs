@@ -1,9 +1,14 @@
+use crate::library;
+use crate::playlist_tabs::PlaylistTabs;
+use crate::skins::assets::SkinAssets;
 use crate::theme::Theme;
 use crate::track_display::TrackDisplay;
 use eframe::egui;
-use oneamp_core::TrackInfo;
+use oneamp_core::stats::{PlayStats, MAX_RATING};
+use oneamp_core::{MetadataService, TrackInfo, TrackMetadata};
 
 /// Render the player section (timer, track info, visualizer)
+#[allow(clippy::too_many_arguments)]
 pub fn render_player_section(
     ui: &mut egui::Ui,
     theme: &Theme,
@@ -12,9 +17,19 @@ pub fn render_player_section(
     _total_duration: f32,
     visualizer_data: &[f32],
     scroll_offset: &mut usize,
+    assets: &SkinAssets,
 ) {
     let player_height = theme.layout.player_height;
 
+    // Skin frame behind the display
+    if let Some(display) = assets.display() {
+        let rect = egui::Rect::from_min_size(
+            ui.cursor().min,
+            egui::vec2(ui.available_width(), player_height),
+        );
+        display.paint(ui.painter(), rect);
+    }
+
     ui.allocate_ui_with_layout(
         egui::vec2(ui.available_width(), player_height),
         egui::Layout::top_down(egui::Align::Center),
@@ -27,7 +42,7 @@ pub fn render_player_section(
                 egui::RichText::new(timer_text)
                     .size(theme.fonts.timer_size)
                     .color(Theme::color32(&theme.colors.display_text))
-                    .monospace(),
+                    .family(assets.timer_family()),
             );
 
             ui.add_space(12.0);
@@ -107,13 +122,7 @@ fn render_visualizer(ui: &mut egui::Ui, theme: &Theme, data: &[f32]) {
         );
 
         // Gradient color based on height
-        let color = if value > 0.8 {
-            egui::Color32::from_rgb(255, 50, 50) // Red
-        } else if value > 0.5 {
-            egui::Color32::from_rgb(255, 200, 50) // Yellow
-        } else {
-            Theme::color32(&theme.colors.display_accent) // Blue/Green
-        };
+        let color = theme.visualizer_color(value);
 
         // Glow for high bars
         if value > 0.6 {
@@ -346,84 +355,388 @@ pub fn render_equalizer(
     changed
 }
 
+/// Playlist rows being dragged, dropped on a tab to copy them there
+#[derive(Debug, Clone)]
+pub struct PlaylistDragPayload {
+    pub tab: usize,
+    pub indices: Vec<usize>,
+}
+
+/// Actions from the playlist tab bar
+#[derive(Default)]
+pub struct PlaylistTabActions {
+    pub select_tab: Option<usize>,
+    pub new_tab: bool,
+    pub new_smart_tab: bool,
+    pub edit_smart: Option<usize>,
+    /// New random order for a smart playlist
+    pub reshuffle: Option<usize>,
+    pub close_tab: Option<usize>,
+    pub undo_close: bool,
+    pub start_rename: Option<usize>,
+    pub finish_rename: Option<(usize, String)>,
+    pub cancel_rename: bool,
+    /// Rows dropped onto a tab (target tab, payload)
+    pub copy_to_tab: Option<(usize, PlaylistDragPayload)>,
+}
+
+/// Render the playlist tabs. `renaming` holds the tab being renamed and its edited name.
+pub fn render_playlist_tabs(
+    ui: &mut egui::Ui,
+    theme: &Theme,
+    playlists: &PlaylistTabs,
+    renaming: &mut Option<(usize, String)>,
+) -> PlaylistTabActions {
+    let mut actions = PlaylistTabActions::default();
+
+    ui.horizontal_wrapped(|ui| {
+        for (idx, tab) in playlists.tabs().iter().enumerate() {
+            if let Some((rename_idx, name)) = renaming.as_mut() {
+                if *rename_idx == idx {
+                    let response = ui.add(egui::TextEdit::singleline(name).desired_width(120.0));
+                    if !response.has_focus() && !response.lost_focus() {
+                        response.request_focus();
+                    }
+                    if response.lost_focus() {
+                        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                            actions.cancel_rename = true;
+                        } else {
+                            actions.finish_rename = Some((idx, name.clone()));
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            let is_active = idx == playlists.active_index();
+            let icon = if tab.smart().is_some() { "✨ " } else { "" };
+            let mut label =
+                egui::RichText::new(format!("{}{} ({})", icon, tab.name, tab.playlist.len()))
+                    .size(13.0);
+            if idx == playlists.playing_index() {
+                label = label.color(Theme::color32(&theme.colors.playlist_playing));
+            }
+
+            let response = ui.selectable_label(is_active, label);
+
+            // Highlight the tab under a dragged selection
+            if response
+                .dnd_hover_payload::<PlaylistDragPayload>()
+                .is_some_and(|payload| payload.tab != idx)
+            {
+                ui.painter().rect_stroke(
+                    response.rect,
+                    2.0,
+                    egui::Stroke::new(2.0, Theme::color32(&theme.colors.playlist_selected)),
+                );
+            }
+            if let Some(payload) = response.dnd_release_payload::<PlaylistDragPayload>() {
+                actions.copy_to_tab = Some((idx, (*payload).clone()));
+            }
+
+            if response.clicked() {
+                actions.select_tab = Some(idx);
+            }
+            if response.double_clicked() {
+                actions.start_rename = Some(idx);
+            }
+            if response.middle_clicked() {
+                actions.close_tab = Some(idx);
+            }
+            response.context_menu(|ui| {
+                if ui.button("Rename").clicked() {
+                    actions.start_rename = Some(idx);
+                    ui.close_menu();
+                }
+                if tab.smart().is_some() {
+                    if ui.button("Edit rule…").clicked() {
+                        actions.edit_smart = Some(idx);
+                        ui.close_menu();
+                    }
+                    if ui.button("Reshuffle").clicked() {
+                        actions.reshuffle = Some(idx);
+                        ui.close_menu();
+                    }
+                }
+                if ui.button("Close").clicked() {
+                    actions.close_tab = Some(idx);
+                    ui.close_menu();
+                }
+            });
+        }
+
+        if ui
+            .small_button("➕")
+            .on_hover_text("New playlist (Ctrl+T)")
+            .clicked()
+        {
+            actions.new_tab = true;
+        }
+        if ui
+            .small_button("✨")
+            .on_hover_text("New smart playlist")
+            .clicked()
+        {
+            actions.new_smart_tab = true;
+        }
+
+        if let Some(name) = playlists.last_closed_name() {
+            if ui
+                .small_button(format!("↶ Reopen \"{}\"", name))
+                .on_hover_text("Undo close (Ctrl+Shift+T)")
+                .clicked()
+            {
+                actions.undo_close = true;
+            }
+        }
+    });
+
+    actions
+}
+
 /// Render playlist section with drag-drop support
 pub struct PlaylistActions {
     pub play_track: Option<usize>,
-    pub select_track: Option<usize>,
+    /// Row clicked, with the modifiers held (ctrl/shift extend the selection)
+    pub click_track: Option<(usize, egui::Modifiers)>,
+    pub enqueue_track: Option<usize>,
+    pub dequeue_track: Option<usize>,
+    pub remove_selected: bool,
+    pub crop_selected: bool,
+    /// Stars given to the selected rows, 0 to clear
+    pub rate_selected: Option<u8>,
+    /// Entries dragged within the tab, and the index to insert them before
+    pub move_tracks: Option<(Vec<usize>, usize)>,
+    /// Entries dragged in from another tab
+    pub copy_here: Option<PlaylistDragPayload>,
+    pub scroll_offset: f32,
 }
 
+/// Render the rows of the tab being shown. Only the rows scrolled into view
+/// are laid out, so very long playlists stay responsive.
 pub fn render_playlist(
     ui: &mut egui::Ui,
     theme: &Theme,
-    playlist: &[std::path::PathBuf],
-    current_track_index: Option<usize>,
-    selected_track_index: Option<usize>,
+    playlists: &PlaylistTabs,
+    rows: &[usize],
+    metadata: &mut MetadataService,
+    stats: &PlayStats,
 ) -> PlaylistActions {
+    let tab = playlists.active();
+    let tab_index = playlists.active_index();
+    let is_playing_tab = tab_index == playlists.playing_index();
     let mut actions = PlaylistActions {
         play_track: None,
-        select_track: None,
+        click_track: None,
+        enqueue_track: None,
+        dequeue_track: None,
+        remove_selected: false,
+        crop_selected: false,
+        rate_selected: None,
+        move_tracks: None,
+        copy_here: None,
+        scroll_offset: tab.scroll_offset,
     };
+    let playlist = &tab.playlist;
+    // Only the playing tab shows a "now playing" marker
+    let current_track_index = playlist.current_index().filter(|_| is_playing_tab);
+    let faded_text = Theme::color32(&theme.colors.playlist_text).linear_multiply(0.5);
+
+    if playlist.is_empty() || rows.is_empty() {
+        let hint = if playlist.is_empty() {
+            "Drag and drop audio files here"
+        } else {
+            "No matching tracks"
+        };
+        ui.add_space(20.0);
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new(hint).size(14.0).color(faded_text));
+        });
+        return actions;
+    }
 
-    egui::ScrollArea::vertical()
+    let row_height = theme.fonts.playlist_size + 6.0;
+
+    let output = egui::ScrollArea::vertical()
+        .id_salt(("playlist", tab_index))
+        .vertical_scroll_offset(tab.scroll_offset)
         .auto_shrink([false; 2])
-        .show(ui, |ui| {
-            if playlist.is_empty() {
-                ui.add_space(20.0);
-                ui.vertical_centered(|ui| {
-                    ui.label(
-                        egui::RichText::new("Drag and drop audio files here")
-                            .size(14.0)
-                            .color(
-                                Theme::color32(&theme.colors.playlist_text).linear_multiply(0.5),
-                            ),
-                    );
-                });
-            } else {
-                for (idx, path) in playlist.iter().enumerate() {
-                    // Try to get track info for display
-                    let display_text =
-                        if let Ok(track_info) = oneamp_core::TrackInfo::from_file(path) {
-                            TrackDisplay::get_title(&track_info)
-                        } else {
-                            path.file_name()
-                                .and_then(|n| n.to_str())
-                                .unwrap_or("Unknown")
-                                .to_string()
-                        };
+        .show_rows(ui, row_height, rows.len(), |ui, visible| {
+            for &idx in &rows[visible] {
+                let Some(path) = playlist.get(idx) else {
+                    continue;
+                };
+                // Bare file names until the background scan reaches the track
+                metadata.request(path);
+                let track = metadata.get(path);
+                let probe_error = track.and_then(TrackMetadata::error);
+                let display_text = match track.and_then(TrackMetadata::info) {
+                    Some(info) => TrackDisplay::get_title(info),
+                    None => path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+                };
+                let duration = track
+                    .and_then(TrackMetadata::duration_secs)
+                    .map(TrackDisplay::format_duration);
+                let rating = match track.and_then(TrackMetadata::info) {
+                    Some(info) => stats.rating(info),
+                    None => stats.get(path).rating,
+                };
 
-                    let is_current = current_track_index == Some(idx);
-                    let is_selected = selected_track_index == Some(idx);
+                let is_current = current_track_index == Some(idx);
+                let is_selected = tab.is_selected(idx);
+
+                // Add visual indicators
+                let prefix = if is_current {
+                    "▶ "
+                } else if is_selected {
+                    "★ "
+                } else if probe_error.is_some() {
+                    "⚠ "
+                } else {
+                    "  "
+                };
+
+                // Queue position, Winamp style: "[2] Artist - Title"
+                let queue_position = playlist.queue_position(idx);
+                let queue_marker = queue_position
+                    .map(|pos| format!("[{}] ", pos))
+                    .unwrap_or_default();
+
+                let mut text =
+                    egui::RichText::new(format!("{}{}{}", prefix, queue_marker, display_text))
+                        .size(theme.fonts.playlist_size);
+
+                if is_current {
+                    text = text.color(Theme::color32(&theme.colors.playlist_playing));
+                } else if is_selected {
+                    text = text.color(Theme::color32(&theme.colors.playlist_selected));
+                } else if probe_error.is_some() {
+                    text = text.color(ui.visuals().error_fg_color);
+                }
 
-                    // Add visual indicators
-                    let prefix = if is_current {
-                        "▶ "
-                    } else if is_selected {
-                        "★ "
+                let row = ui.allocate_ui_with_layout(
+                    egui::vec2(ui.available_width(), row_height),
+                    egui::Layout::left_to_right(egui::Align::Center),
+                    |ui| {
+                        ui.set_min_size(egui::vec2(ui.available_width(), row_height));
+                        ui.selectable_label(is_selected, text)
+                            .interact(egui::Sense::drag())
+                    },
+                );
+                let row_rect = row.response.rect;
+                let mut response = row.inner;
+
+                if let Some(duration) = duration {
+                    ui.painter().text(
+                        row_rect.right_center() - egui::vec2(6.0, 0.0),
+                        egui::Align2::RIGHT_CENTER,
+                        duration,
+                        egui::FontId::proportional(theme.fonts.playlist_size),
+                        faded_text,
+                    );
+                }
+                if let Some(error) = probe_error {
+                    response = response.on_hover_text(format!("Unreadable file: {}", error));
+                }
+
+                if response.drag_started() {
+                    // Dragging a selected row drags the whole selection
+                    let indices = if is_selected {
+                        tab.selected_indices()
                     } else {
-                        "  "
+                        vec![idx]
                     };
+                    response.dnd_set_drag_payload(PlaylistDragPayload {
+                        tab: tab_index,
+                        indices,
+                    });
+                }
 
-                    let mut text =
-                        egui::RichText::new(format!("{}{}", prefix, display_text)).size(theme.fonts.playlist_size);
+                // Show where dropped rows will land: above or below this one
+                if let Some(payload) = response.dnd_hover_payload::<PlaylistDragPayload>() {
+                    let below = ui
+                        .ctx()
+                        .pointer_interact_pos()
+                        .is_some_and(|pos| pos.y > response.rect.center().y);
+                    if payload.tab == tab_index {
+                        let y = if below {
+                            row_rect.bottom()
+                        } else {
+                            row_rect.top()
+                        };
+                        ui.painter().hline(
+                            row_rect.x_range(),
+                            y,
+                            egui::Stroke::new(2.0, Theme::color32(&theme.colors.playlist_playing)),
+                        );
+                    } else {
+                        ui.painter().rect_stroke(
+                            response.rect,
+                            2.0,
+                            egui::Stroke::new(1.0, Theme::color32(&theme.colors.playlist_playing)),
+                        );
+                    }
 
-                    if is_current {
-                        text = text.color(Theme::color32(&theme.colors.playlist_playing));
-                    } else if is_selected {
-                        text = text.color(Theme::color32(&theme.colors.playlist_selected));
+                    if let Some(payload) = response.dnd_release_payload::<PlaylistDragPayload>() {
+                        if payload.tab == tab_index {
+                            let to = if below { idx + 1 } else { idx };
+                            actions.move_tracks = Some((payload.indices.clone(), to));
+                        } else {
+                            actions.copy_here = Some((*payload).clone());
+                        }
                     }
+                }
 
-                    let response = ui.selectable_label(is_selected, text);
+                if response.clicked() {
+                    actions.click_track = Some((idx, ui.input(|i| i.modifiers)));
+                }
 
-                    if response.clicked() {
-                        actions.select_track = Some(idx);
-                    }
+                // Right-clicking outside the selection selects the row first
+                if response.secondary_clicked() && !is_selected {
+                    actions.click_track = Some((idx, egui::Modifiers::NONE));
+                }
 
-                    if response.double_clicked() {
-                        actions.play_track = Some(idx);
-                    }
+                if response.double_clicked() {
+                    actions.play_track = Some(idx);
                 }
+
+                response.context_menu(|ui| {
+                    if ui.button("Play next").clicked() {
+                        actions.enqueue_track = Some(idx);
+                        ui.close_menu();
+                    }
+                    if queue_position.is_some() && ui.button("Remove from queue").clicked() {
+                        actions.dequeue_track = Some(idx);
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Rating", |ui| {
+                        for stars in (0..=MAX_RATING).rev() {
+                            let label = match stars {
+                                0 => "No rating".to_string(),
+                                _ => library::stars(stars),
+                            };
+                            if ui.radio(rating == stars, label).clicked() {
+                                actions.rate_selected = Some(stars);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.separator();
+                    if ui.button("Remove (Del)").clicked() {
+                        actions.remove_selected = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Crop to selection").clicked() {
+                        actions.crop_selected = true;
+                        ui.close_menu();
+                    }
+                });
             }
         });
 
+    actions.scroll_offset = output.state.offset.y;
     actions
 }
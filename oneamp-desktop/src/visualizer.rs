@@ -1,5 +1,5 @@
 use egui::{Color32, Painter, Pos2, Rect, Stroke, Vec2};
-use rustfft::{num_complex::Complex, FftPlanner};
+use oneamp_core::spectrum::SpectrumAnalyzer;
 
 /// Type of visualization
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -28,9 +28,7 @@ impl VisualizationType {
 pub struct Visualizer {
     viz_type: VisualizationType,
     samples: Vec<f32>,
-    spectrum: Vec<f32>,
-    fft_buffer: Vec<Complex<f32>>,
-    fft_planner: FftPlanner<f32>,
+    analyzer: SpectrumAnalyzer,
 }
 
 impl Visualizer {
@@ -38,9 +36,7 @@ impl Visualizer {
         Self {
             viz_type: VisualizationType::Oscilloscope,
             samples: vec![0.0; 256],
-            spectrum: vec![0.0; 64],
-            fft_buffer: vec![Complex::new(0.0, 0.0); 512],
-            fft_planner: FftPlanner::new(),
+            analyzer: SpectrumAnalyzer::new(64),
         }
     }
 
@@ -56,7 +52,7 @@ impl Visualizer {
 
     /// Get spectrum data for external rendering
     pub fn get_spectrum(&self) -> &[f32] {
-        &self.spectrum
+        self.analyzer.bands()
     }
 
     /// Update with new audio samples
@@ -74,51 +70,7 @@ impl Visualizer {
             }
         }
 
-        // Compute spectrum using simple FFT approximation
-        self.compute_spectrum(audio_samples);
-    }
-
-    /// Compute spectrum from audio samples using FFT
-    fn compute_spectrum(&mut self, samples: &[f32]) {
-        if samples.is_empty() {
-            return;
-        }
-
-        // Prepare FFT buffer
-        let fft_size = self.fft_buffer.len();
-        for (i, buf) in self.fft_buffer.iter_mut().enumerate() {
-            if i < samples.len() {
-                buf.re = samples[i];
-                buf.im = 0.0;
-            } else {
-                buf.re = 0.0;
-                buf.im = 0.0;
-            }
-        }
-
-        // Perform FFT
-        let fft = self.fft_planner.plan_fft_forward(fft_size);
-        fft.process(&mut self.fft_buffer);
-
-        // Convert FFT output to spectrum bands
-        let bins_per_band = (fft_size / 2) / self.spectrum.len();
-
-        for (i, band) in self.spectrum.iter_mut().enumerate() {
-            let start = i * bins_per_band;
-            let end = ((i + 1) * bins_per_band).min(fft_size / 2);
-
-            // Calculate magnitude for this band
-            let mut magnitude = 0.0;
-            for bin in start..end {
-                let complex = self.fft_buffer[bin];
-                magnitude += (complex.re * complex.re + complex.im * complex.im).sqrt();
-            }
-            magnitude /= (end - start) as f32;
-
-            // Normalize and apply smoothing
-            magnitude = (magnitude / 100.0).min(1.0); // Normalize
-            *band = *band * 0.7 + magnitude * 0.3; // Smooth
-        }
+        self.analyzer.update(audio_samples);
     }
 
     /// Draw the visualizer
@@ -169,10 +121,10 @@ impl Visualizer {
     fn draw_spectrum(&self, painter: &Painter, rect: Rect) {
         let width = rect.width();
         let height = rect.height();
-        let bar_width = width / self.spectrum.len() as f32;
+        let bar_width = width / self.get_spectrum().len() as f32;
         let bar_spacing = 2.0;
 
-        for (i, &energy) in self.spectrum.iter().enumerate() {
+        for (i, &energy) in self.get_spectrum().iter().enumerate() {
             let x = rect.left() + i as f32 * bar_width;
             let bar_height = energy * height * 2.0; // Scale up for visibility
             let bar_height = bar_height.min(height); // Clamp to max height
@@ -271,15 +223,15 @@ impl Visualizer {
         use crate::visual_effects::VisualEffects;
         use eframe::egui::{Color32, Pos2, Rect as EguiRect, Vec2};
 
-        if self.spectrum.is_empty() {
+        if self.get_spectrum().is_empty() {
             return;
         }
 
-        let bar_count = self.spectrum.len();
+        let bar_count = self.get_spectrum().len();
         let bar_width = (rect.width() / bar_count as f32) * 0.8;
         let spacing = (rect.width() / bar_count as f32) * 0.2;
 
-        for (i, &magnitude) in self.spectrum.iter().enumerate() {
+        for (i, &magnitude) in self.get_spectrum().iter().enumerate() {
             let x = rect.left() + i as f32 * (bar_width + spacing);
             let bar_height = magnitude * rect.height();
 
@@ -358,7 +310,7 @@ mod tests {
         let visualizer = Visualizer::new();
         assert_eq!(visualizer.viz_type(), VisualizationType::Oscilloscope);
         assert_eq!(visualizer.samples.len(), 256);
-        assert_eq!(visualizer.spectrum.len(), 64);
+        assert_eq!(visualizer.get_spectrum().len(), 64);
     }
 
     #[test]
@@ -388,7 +340,7 @@ mod tests {
 
         // Samples should still be initialized
         assert_eq!(visualizer.samples.len(), 256);
-        assert_eq!(visualizer.spectrum.len(), 64);
+        assert_eq!(visualizer.get_spectrum().len(), 64);
     }
 
     #[test]
@@ -418,10 +370,14 @@ mod tests {
         visualizer.update(&samples);
 
         // Spectrum should be updated
-        assert_eq!(visualizer.spectrum.len(), 64);
+        assert_eq!(visualizer.get_spectrum().len(), 64);
 
         // At least some spectrum bands should have energy
-        let non_zero_bands = visualizer.spectrum.iter().filter(|&&s| s > 0.0).count();
+        let non_zero_bands = visualizer
+            .get_spectrum()
+            .iter()
+            .filter(|&&s| s > 0.0)
+            .count();
         assert!(
             non_zero_bands > 0,
             "Should have some non-zero spectrum bands"
@@ -438,7 +394,7 @@ mod tests {
         visualizer.update(&samples);
 
         // All spectrum values should be normalized (between 0 and 1)
-        for &magnitude in &visualizer.spectrum {
+        for &magnitude in visualizer.get_spectrum() {
             assert!(
                 magnitude >= 0.0 && magnitude <= 1.0,
                 "Spectrum magnitude should be normalized: {}",
@@ -451,7 +407,7 @@ mod tests {
     fn test_fft_buffer_size() {
         let visualizer = Visualizer::new();
         assert_eq!(
-            visualizer.fft_buffer.len(),
+            visualizer.analyzer.fft_size(),
             512,
             "FFT buffer should be 512 samples"
         );
@@ -469,7 +425,7 @@ mod tests {
 
         // Should still be valid
         assert_eq!(visualizer.samples.len(), 256);
-        assert_eq!(visualizer.spectrum.len(), 64);
+        assert_eq!(visualizer.get_spectrum().len(), 64);
     }
 
     #[test]